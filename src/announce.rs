@@ -0,0 +1,134 @@
+//! Screen-reader mode: after each key, one plain-text line saying what changed
+//! (a value pushed, a result, an error, a mode), for the log panel and, with
+//! `screen-reader = "print"`, stdout on exit. Symbols a speech synthesizer
+//! stumbles over are spelled out.
+
+use tuic_core::Calculator;
+
+/// Lines kept in the log; the oldest go first.
+const MAX_LINES: usize = 1000;
+
+/// The parts of the calculator a description is made from, taken before a key.
+pub struct Snapshot {
+    stack_len: usize,
+    top: Option<String>,
+    history_len: usize,
+    error: Option<String>,
+    notice: Option<String>,
+    modes: String,
+}
+
+impl Snapshot {
+    pub fn of(calculator: &Calculator) -> Self {
+        Self {
+            stack_len: calculator.stack().len(),
+            top: top_value(calculator),
+            history_len: calculator.history.len(),
+            error: error_text(calculator),
+            notice: calculator.notice.clone(),
+            modes: calculator.get_mode_string(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Announcer {
+    pub lines: Vec<String>,
+}
+
+impl Announcer {
+    /// Logs what changed since `before`; keys that only edit the input say nothing.
+    pub fn announce(&mut self, before: &Snapshot, calculator: &Calculator) {
+        if let Some(line) = describe(before, calculator) {
+            if self.lines.len() == MAX_LINES {
+                self.lines.remove(0);
+            }
+            self.lines.push(line);
+        }
+    }
+}
+
+pub fn describe(before: &Snapshot, calculator: &Calculator) -> Option<String> {
+    let mut parts = Vec::new();
+    let error = error_text(calculator);
+    if error.is_some() && error != before.error {
+        parts.extend(error);
+    }
+
+    let stack_len = calculator.stack().len();
+    let top = top_value(calculator);
+    // A history line with a result is a calculation; one without is a number entered
+    let new_result = calculator.history.len() != before.history_len
+        && calculator.history.last().is_some_and(|entry| entry.result.is_some());
+    if new_result && let (Some(entry), Some(value)) = (calculator.history.last(), &top) {
+        parts.push(format!("Result {}, from {}", value, entry.expression.trim()));
+    } else if stack_len > before.stack_len {
+        let pushed = stack_len - before.stack_len;
+        parts.push(match (pushed, &top) {
+            (1, Some(value)) => format!("Pushed {}", value),
+            (count, _) => format!("Pushed {} values, top {}", count, top.as_deref().unwrap_or("")),
+        });
+    } else if stack_len < before.stack_len {
+        parts.push(match &top {
+            Some(value) => format!("Removed {}, top now {}", before.stack_len - stack_len, value),
+            None => "Stack empty".to_string(),
+        });
+    } else if top != before.top
+        && let Some(value) = &top
+    {
+        parts.push(format!("Top now {}", value));
+    }
+
+    let modes = calculator.get_mode_string();
+    if modes != before.modes {
+        parts.push(modes.replace(" | ", ", "));
+    }
+    if calculator.notice.is_some() && calculator.notice != before.notice {
+        parts.extend(calculator.notice.clone());
+    }
+    (!parts.is_empty()).then(|| plain(&parts.join(". ")))
+}
+
+fn top_value(calculator: &Calculator) -> Option<String> {
+    calculator.peek().map(|value| calculator.format_stack_value(value))
+}
+
+fn error_text(calculator: &Calculator) -> Option<String> {
+    calculator.error.as_ref().map(|error| format!("Error {}: {}", error.code(), error))
+}
+
+/// Symbols read out as words, so `√2 × π` is "sqrt2 times pi" rather than silence.
+pub fn plain(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            'π' => out.push_str("pi"),
+            '√' => out.push_str("sqrt"),
+            '×' | '·' => out.push_str(" times "),
+            '÷' => out.push_str(" divided by "),
+            '±' => out.push_str(" plus or minus "),
+            '°' => out.push_str(" degrees"),
+            '∠' => out.push_str(" angle "),
+            '≈' => out.push_str(" approximately "),
+            '≤' => out.push_str(" at most "),
+            '≥' => out.push_str(" at least "),
+            '≠' => out.push_str(" not equal to "),
+            '→' => out.push_str(" to "),
+            'Σ' => out.push_str("sum "),
+            '←' | '★' | '⚠' => {}
+            ch => out.push(ch),
+        }
+    }
+    out.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spells_out_symbols() {
+        assert_eq!(plain("5 ∠ 53.13°"), "5 angle 53.13 degrees");
+        assert_eq!(plain("3.1 ± 0.2  ★"), "3.1 plus or minus 0.2");
+    }
+}
@@ -0,0 +1,790 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind};
+use ratatui::layout::{Position, Rect};
+use ratatui::widgets::ListState;
+use tuic_core::config::{Autosave, Config, Keymap, Layout, ScreenReader};
+use tuic_core::find::Found;
+use tuic_core::limits::{self, Limit};
+use tuic_core::locale::DecimalSeparator;
+use tuic_core::settings::{self, Setting, SettingsDialog};
+use tuic_core::journal::{self, Journal};
+use tuic_core::versioned;
+use tuic_core::{backup, config_dir, Calculator, CalculatorError, CalculatorMode, EntryOrigin, StackValue, Warning};
+
+use crate::gnuplot;
+use crate::keymap::Focus;
+use crate::rates;
+use crate::update;
+use crate::row_cache::RowCache;
+#[cfg(unix)]
+use crate::server::Server;
+use crate::stopwatch::{Stopwatch, TimerMode};
+use crate::announce::Announcer;
+use crate::debug::DebugInfo;
+use crate::error_log::ErrorLog;
+use crate::theme::{self, Theme};
+use crate::theme_watch::ThemeWatcher;
+use crate::vi::ViMode;
+
+/// Key repeat comes every 30-50 ms, and a person pressing again takes longer.
+const REPEAT_WINDOW: Duration = Duration::from_millis(100);
+
+/// Main panel that Up/Down act on; a mouse click picks it.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Panel {
+    #[default]
+    Stack,
+    History,
+    Input,
+}
+
+/// Terminal front end around the engine: theme, dialogs and list scrolling.
+pub struct App {
+    pub calculator: Calculator,
+    pub stack_list_state: ListState, // Stack scrolling
+    pub history_list_state: ListState, // History scrolling
+    pub current_theme: Theme,
+    pub available_themes: Vec<String>,
+    pub show_theme_selector: bool,
+    pub theme_list_state: ListState,
+    pub theme_before_selector: Option<Theme>, // Restored when the selector is cancelled
+    pub show_theme_colors: bool, // i in the selector: every color of the theme being previewed
+    pub theme_colors_scroll: u16,
+    pub show_help: bool,
+    pub show_quick_ref: bool, // The '?' card for the current mode
+    pub help_focus: Focus, // What the keyboard was on when help opened
+    pub show_float_inspector: bool,
+    pub show_inspect: bool, // Selected entry in every base and notation
+    pub show_iteration_plot: bool,
+    pub show_stack_chart: bool,
+    pub stack_chart_sparkline: bool, // Sparkline instead of bars
+    pub show_stopwatch: bool,
+    pub stopwatch: Stopwatch, // Keeps running while its popup is closed
+    pub show_error_log: bool,
+    pub error_log: ErrorLog, // The session's recent errors, for the Ctrl+O popup
+    pub layout: Layout,
+    pub layout_before_presentation: Layout, // Restored when presentation mode is left
+    pub show_display: bool, // Seven-segment display of the current value across the top
+    pub announcer: Option<Announcer>, // Screen-reader mode's log of changes; None when it's off
+    pub show_debug: bool, // The F12 overlay
+    pub debug: DebugInfo, // What the overlay shows
+    pub print_announcements: bool, // Log printed to stdout on exit
+    pub vi_mode: Option<ViMode>, // Set when vi bindings are on
+    pub vi_pending_delete: bool, // First 'd' of 'dd' pressed
+    pub ticks: u64, // Timer ticks since start, for animations
+    pub needs_redraw: bool, // Something on screen changed since the last frame
+    save_status_shown: Option<String>, // As of the last tick, so "saved 2m ago" ticking over redraws
+    pub last_key_tick: u64,
+    pub repeat_window: Duration, // The same navigation key again within this counts as held down
+    last_navigation: Option<(KeyCode, Instant)>,
+    navigation_repeats: usize, // Up/Down presses in a row while held, for acceleration
+    pub error_flash: u8, // Ticks left of the status flash after an error
+    pub rates_fetch: Option<Receiver<Result<String, String>>>, // Exchange rate request in flight
+    pub update_check: Option<Receiver<Result<String, String>>>, // Latest release request in flight
+    pub theme_watcher: Option<ThemeWatcher>, // Reloads the theme when its file is edited
+    pub panel_focus: Panel,
+    pub panel_areas: Vec<(Panel, Rect)>, // Where each panel was last drawn, for mouse hit-testing
+    pub row_cache: RowCache, // Formatted stack and history rows
+    pub journal: Option<journal::Slot>, // Where the session is journaled for crash recovery; None keeps no journal
+    pub config_path: Option<PathBuf>, // config.toml, where the settings popup saves to; None saves nowhere
+    pub log_path: Option<PathBuf>, // tui-calculator.log, when log-level is on
+    journal_written: String, // Contents last journaled, so an unchanged session isn't rewritten
+    journal_seen: String, // Contents at the last update, for counting changes between saves
+    pub journal_saved_at: Option<Instant>,
+    pub unsaved_changes: u32, // Changes since the journal was last written
+    pub autosave: Autosave,
+    pub gnuplot_command: String, // Template run by Ctrl+G
+    pub recovery: Option<Journal>, // Session left behind by a crash, offered for restoring at startup
+    #[cfg(unix)]
+    pub server: Option<Server>, // `serve` socket for editors and scripts
+}
+
+/// Ticks the status panel flashes for when a key causes an error.
+const ERROR_FLASH_TICKS: u8 = 2;
+
+impl App {
+    /// Theme problems don't stop the calculator: it falls back to the built-in
+    /// default and warns about it in the status bar.
+    pub fn new(mut calculator: Calculator) -> Result<Self> {
+        let current_theme_name = config_dir()
+            .ok()
+            .and_then(|dir| fs::read_to_string(dir.join("theme.txt")).ok())
+            .map_or_else(|| "default".to_string(), |name| name.trim().to_string());
+
+        let initial_theme = match load_theme(&current_theme_name) {
+            Ok(theme) => theme,
+            Err(e) => {
+                calculator.warning = Some(Warning::ThemeFallback { name: current_theme_name, reason: e.to_string() });
+                load_theme("default").unwrap_or_else(|_| Theme::builtin())
+            }
+        };
+        Self::with_theme(calculator, initial_theme)
+    }
+
+    /// An app drawn in `initial_theme` rather than the one saved in the config
+    /// directory, so tests and snapshots don't depend on the user's choice.
+    pub fn with_theme(calculator: Calculator, initial_theme: Theme) -> Result<Self> {
+        let mut available_themes = Vec::new();
+        let themes_dir = "themes";
+        if Path::new(themes_dir).is_dir() {
+            for entry in fs::read_dir(themes_dir)? {
+                let entry = entry?;
+                let path = entry.path();
+                if path.is_file()
+                    && let Some(name_str) = path.file_stem().and_then(|stem| stem.to_str())
+                {
+                    available_themes.push(name_str.to_string());
+                }
+            }
+        }
+        if !available_themes.iter().any(|name| name == "default") {
+            available_themes.insert(0, "default".to_string()); // The built-in one
+        }
+        for (name, _) in &theme::BUILTIN[1..] {
+            if !available_themes.iter().any(|available| available == name) {
+                available_themes.push(name.to_string());
+            }
+        }
+
+        let mut app = Self {
+            calculator,
+            stack_list_state: ListState::default(),
+            history_list_state: ListState::default(),
+            current_theme: initial_theme,
+            available_themes,
+            show_theme_selector: false,
+            theme_list_state: ListState::default(),
+            theme_before_selector: None,
+            show_theme_colors: false,
+            theme_colors_scroll: 0,
+            show_help: false,
+            show_quick_ref: false,
+            help_focus: Focus::Input,
+            show_float_inspector: false,
+            show_inspect: false,
+            show_iteration_plot: false,
+            show_stack_chart: false,
+            stack_chart_sparkline: false,
+            show_stopwatch: false,
+            stopwatch: Stopwatch::default(),
+            show_error_log: false,
+            error_log: ErrorLog::default(),
+            layout: Layout::Full,
+            layout_before_presentation: Layout::Full,
+            show_display: false,
+            announcer: None,
+            show_debug: false,
+            debug: DebugInfo::default(),
+            print_announcements: false,
+            vi_mode: None,
+            vi_pending_delete: false,
+            ticks: 0,
+            needs_redraw: true,
+            save_status_shown: None,
+            last_key_tick: 0,
+            repeat_window: REPEAT_WINDOW,
+            last_navigation: None,
+            navigation_repeats: 0,
+            error_flash: 0,
+            rates_fetch: None,
+            update_check: None,
+            theme_watcher: None,
+            panel_focus: Panel::Stack,
+            panel_areas: Vec::new(),
+            row_cache: RowCache::default(),
+            journal: None,
+            config_path: None,
+            log_path: None,
+            journal_written: String::new(),
+            journal_seen: String::new(),
+            journal_saved_at: None,
+            unsaved_changes: 0,
+            autosave: Autosave::default(),
+            gnuplot_command: gnuplot::DEFAULT_COMMAND.to_string(),
+            recovery: None,
+            #[cfg(unix)]
+            server: None,
+        };
+        if let Some(warning) = &app.calculator.warning {
+            app.error_log.record_warning(warning, "");
+        }
+        Ok(app)
+    }
+
+    /// Front-end settings from the config: layout, key bindings, and a theme that wins over the last one picked.
+    pub fn apply_config(&mut self, config: &Config) {
+        if let Some(layout) = config.layout {
+            self.layout = layout;
+        }
+        if let Some(screen_reader) = config.screen_reader {
+            self.announcer = (screen_reader != ScreenReader::Off).then(Announcer::default);
+            self.print_announcements = screen_reader == ScreenReader::Print;
+        }
+        if let Some(autosave) = config.autosave {
+            self.autosave = autosave;
+        }
+        if let Some(command) = &config.gnuplot_command {
+            self.gnuplot_command.clone_from(command);
+        }
+        if let Some(keys) = config.keys {
+            self.vi_mode = (keys == Keymap::Vi).then_some(ViMode::Normal);
+        }
+        if let Some(name) = &config.theme {
+            match load_theme(name) {
+                Ok(theme) => self.current_theme = theme,
+                Err(e) => self.calculator.error = Some(CalculatorError::Config(format!("theme '{}': {}", name, e))),
+            }
+        }
+    }
+
+    /// `--quick`: an infix line on its own, which the first result ends (see handle_key).
+    pub fn start_quick(&mut self) {
+        self.layout = Layout::Quick;
+        self.calculator.mode = CalculatorMode::Infix;
+    }
+
+    /// A setting in the settings popup as it is now, written as in config.toml.
+    pub fn setting_value(&self, setting: Setting) -> String {
+        let calculator = &self.calculator;
+        match setting {
+            Setting::Precision => calculator.precision.map_or_else(|| "auto".to_string(), |digits| digits.to_string()),
+            Setting::StackSize => limits::label(calculator.stack_limit),
+            Setting::HistorySize => limits::label(calculator.history_limit),
+            Setting::Mode => if calculator.mode == CalculatorMode::RPN { "rpn" } else { "infix" }.to_string(),
+            Setting::Autosave => settings::autosave_value(self.autosave),
+            Setting::Decimal => if calculator.decimal_separator == DecimalSeparator::Comma { "comma" } else { "point" }.to_string(),
+            Setting::Keys => if self.vi_mode.is_some() { "vi" } else { "default" }.to_string(),
+        }
+    }
+
+    /// Left/Right in the settings popup: the selected setting's next value,
+    /// applied now and saved to config.toml for the next start.
+    pub fn step_selected_setting(&mut self, up: bool) {
+        let Some(setting) = self.calculator.settings_dialog.as_ref().map(SettingsDialog::selected_setting) else {
+            return;
+        };
+        let value = setting.step(&self.setting_value(setting), up);
+        if value == self.setting_value(setting) {
+            return;
+        }
+        let mut config = Config::default();
+        if let Err(e) = config.set(setting.key(), value) {
+            self.calculator.error = Some(CalculatorError::Config(e.to_string()));
+            return;
+        }
+        // Only this setting: the calculator's apply_config also resets what a config leaves out
+        let calculator = &mut self.calculator;
+        match setting {
+            Setting::Precision => calculator.precision = config.precision.unwrap_or_default(),
+            Setting::StackSize => calculator.set_limit(Limit::Stack, config.stack_size.unwrap_or(calculator.stack_limit)),
+            Setting::HistorySize => calculator.set_limit(Limit::History, config.history_size.unwrap_or(calculator.history_limit)),
+            Setting::Mode => calculator.toggle_mode(),
+            Setting::Decimal => calculator.decimal_separator = config.decimal.unwrap_or_default(),
+            Setting::Autosave | Setting::Keys => self.apply_config(&config),
+        }
+        self.calculator.notice = Some(match &self.config_path {
+            Some(path) => match settings::save(path, setting.key(), value) {
+                Ok(()) => format!("{} = {}, saved to {}", setting.key(), value, path.display()),
+                Err(e) => format!("{} = {} for this session; not saved: {:#}", setting.key(), value, e),
+            },
+            None => format!("{} = {} for this session", setting.key(), value),
+        });
+    }
+
+    /// Advances the animations: the caret blink and the error flash. Also where a countdown runs out.
+    /// Only what moves on its own asks for a frame, so an idle calculator isn't redrawn every tick.
+    pub fn on_tick(&mut self) {
+        let caret_visible = self.caret_visible();
+        let flashing = self.error_flash > 0;
+        self.ticks += 1;
+        self.error_flash = self.error_flash.saturating_sub(1);
+        if self.stopwatch.check_finished(Instant::now()) {
+            self.calculator.notice = Some("Countdown finished".to_string());
+            self.needs_redraw = true;
+        }
+        let save_status = self.save_status();
+        if caret_visible != self.caret_visible() || flashing || (self.show_stopwatch && self.stopwatch.is_running()) || save_status != self.save_status_shown || self.show_debug {
+            self.needs_redraw = true;
+        }
+        self.save_status_shown = save_status;
+    }
+
+    /// Restarts the caret blink after a key, and flashes the status if the key brought a new error.
+    pub fn on_key(&mut self, had_error: bool) {
+        self.last_key_tick = self.ticks;
+        if !had_error && self.calculator.error.is_some() {
+            self.error_flash = ERROR_FLASH_TICKS;
+        }
+    }
+
+    /// The caret stays solid while typing and blinks once idle, two ticks on and two off.
+    pub fn caret_visible(&self) -> bool {
+        ((self.ticks - self.last_key_tick) / 2).is_multiple_of(2)
+    }
+
+    /// Journals the session to `slot` from now on. A journal already there was left
+    /// by a session that didn't exit cleanly, and is offered for restoring.
+    pub fn start_journal(&mut self, slot: journal::Slot) {
+        match journal::load(&slot.path) {
+            Ok(found) => self.recovery = found,
+            Err(e) => {
+                self.calculator.error = Some(CalculatorError::Journal(e.to_string()));
+                if e.is::<versioned::NewerVersion>() {
+                    return; // A newer release's journal is left for it rather than overwritten
+                }
+            }
+        }
+        self.journal = Some(slot);
+        self.journal_seen = self.journal_contents().unwrap_or_default(); // The starting state isn't a change
+    }
+
+    fn journal_contents(&self) -> Option<String> {
+        versioned::JOURNAL.to_string(&Journal::capture(&self.calculator)).ok()
+    }
+
+    /// Rewrites the journal when the session changed and the autosave setting
+    /// says it's due. Waits while the restore prompt is up, so the interrupted
+    /// session isn't overwritten before the choice.
+    pub fn update_journal(&mut self) {
+        if self.journal.is_none() || self.recovery.is_some() {
+            return;
+        }
+        let Some(contents) = self.journal_contents() else {
+            return;
+        };
+        if contents == self.journal_written {
+            self.unsaved_changes = 0; // Changed back to what was saved
+            return;
+        }
+        if contents != self.journal_seen {
+            self.unsaved_changes += 1;
+            self.journal_seen.clone_from(&contents);
+        }
+        let due = match self.autosave {
+            Autosave::Change => true,
+            Autosave::Seconds(seconds) => self.journal_saved_at.is_none_or(|at| at.elapsed() >= Duration::from_secs(seconds)),
+            Autosave::Operations(operations) => self.unsaved_changes >= operations,
+            Autosave::Off => false,
+        };
+        if due {
+            self.save_journal(contents);
+        }
+    }
+
+    fn save_journal(&mut self, contents: String) {
+        let Some(slot) = &self.journal else {
+            return;
+        };
+        if let Err(e) = journal::write(&slot.path, &contents) {
+            // Reported once; the next change tries again
+            self.calculator.error = Some(CalculatorError::Journal(e.to_string()));
+        }
+        self.journal_written = contents;
+        self.journal_saved_at = Some(Instant::now());
+        self.unsaved_changes = 0;
+        self.needs_redraw = true;
+    }
+
+    /// The status bar's note on the journal, e.g. "● 3 unsaved · saved 2m ago".
+    /// None without a journal, or before anything needed saving.
+    pub fn save_status(&self) -> Option<String> {
+        self.journal.as_ref()?;
+        let unsaved = match self.unsaved_changes {
+            0 => None,
+            1 => Some("● 1 unsaved change".to_string()),
+            count => Some(format!("● {} unsaved changes", count)),
+        };
+        let saved = self.journal_saved_at.map(|at| {
+            let seconds = at.elapsed().as_secs();
+            match seconds {
+                0..60 => format!("saved {}s ago", seconds),
+                60..3600 => format!("saved {}m ago", seconds / 60),
+                _ => format!("saved {}h ago", seconds / 3600),
+            }
+        });
+        match (unsaved, saved) {
+            (Some(unsaved), Some(saved)) => Some(format!("{} · {}", unsaved, saved)),
+            (unsaved, saved) => unsaved.or(saved),
+        }
+    }
+
+    /// A clean exit: nothing to recover next time, unless the restore prompt was never answered.
+    pub fn finish_journal(&mut self) -> Result<()> {
+        match &self.journal {
+            Some(slot) if self.recovery.is_none() => journal::remove(&slot.path),
+            _ => Ok(()),
+        }
+    }
+
+    /// Answers the startup prompt: restores the interrupted session or starts fresh.
+    pub fn resolve_recovery(&mut self, restore: bool) {
+        if let Some(journal) = self.recovery.take()
+            && restore
+        {
+            journal.restore(&mut self.calculator);
+            self.calculator.notice = Some("Restored the interrupted session".to_string());
+        }
+        // Either way the session now replaces the interrupted one on disk
+        if let Some(contents) = self.journal_contents() {
+            self.journal_seen.clone_from(&contents);
+            self.save_journal(contents);
+        }
+    }
+
+    /// Answers the requests `serve` clients sent since the last frame.
+    #[cfg(unix)]
+    pub fn poll_server(&mut self) {
+        let Some(server) = &self.server else {
+            return;
+        };
+        let requests: Vec<_> = server.requests.try_iter().collect();
+        self.needs_redraw |= !requests.is_empty();
+        for request in requests {
+            let answer = self.answer(&request.line);
+            let _ = request.reply.send(answer); // The client may have hung up
+        }
+    }
+
+    /// The reply to one `serve` request line.
+    pub fn answer(&mut self, line: &str) -> String {
+        let line = line.trim();
+        let calculator = &mut self.calculator;
+        // Scripts read and write numbers with a point, whatever the user has set
+        let separator = std::mem::take(&mut calculator.decimal_separator);
+        let outcome = match line.split_once(' ').unwrap_or((line, "")) {
+            ("push", expression) => calculator.push_expression(expression, EntryOrigin::Ipc),
+            ("top", "") => calculator.peek().cloned().ok_or(CalculatorError::StackUnderflow { operation: "top".to_string(), needed: 1, available: 0 }),
+            ("eval", expression) => calculator.evaluate(expression).map(StackValue::Real),
+            _ => calculator.evaluate(line).map(StackValue::Real),
+        };
+        let answer = match outcome {
+            Ok(value) => format!("ok {}", calculator.format_stack_value(&value)),
+            Err(e) => format!("error {} {}", e.code(), e),
+        };
+        calculator.decimal_separator = separator;
+        answer
+    }
+
+    /// Starts a requested rates fetch, and hands its result to the calculator once it arrives.
+    pub fn poll_rates_fetch(&mut self) {
+        if std::mem::take(&mut self.calculator.rates_fetch_requested) && self.rates_fetch.is_none() {
+            self.rates_fetch = Some(rates::spawn_fetch());
+        }
+        let Some(receiver) = &self.rates_fetch else {
+            return;
+        };
+        let response = match receiver.try_recv() {
+            Ok(response) => response,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => Err("the request stopped unexpectedly".to_string()),
+        };
+        self.rates_fetch = None;
+        self.calculator.apply_fetched_rates(response);
+        self.needs_redraw = true;
+    }
+
+    /// What the about popup lists: the config, the themes and the files the calculator writes.
+    pub fn about_files(&self) -> Vec<(&'static str, Option<PathBuf>)> {
+        let data = |name: &str| config_dir().ok().map(|dir| dir.join(name));
+        vec![
+            ("Config", self.config_path.clone()),
+            ("Themes", std::env::current_dir().ok().map(|dir| dir.join("themes"))),
+            ("History", data("history.json")),
+            ("Session journal", self.journal.as_ref().map(|slot| slot.path.clone()).or_else(|| data("session.journal"))),
+            ("Exchange rates", data("rates.json")),
+            ("Theme choice", data("theme.txt")),
+            ("Log", self.log_path.clone()),
+        ]
+    }
+
+    /// Asks for the latest release, unless a check is already under way.
+    pub fn start_update_check(&mut self) {
+        if self.update_check.is_none() {
+            self.update_check = Some(update::spawn_check());
+            self.calculator.notice = Some("Checking for a newer release...".to_string());
+        }
+    }
+
+    /// Shows the result of the update check in the status bar once it arrives.
+    pub fn poll_update_check(&mut self) {
+        let Some(receiver) = &self.update_check else {
+            return;
+        };
+        let outcome = match receiver.try_recv() {
+            Ok(outcome) => outcome,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => Err("the request stopped unexpectedly".to_string()),
+        };
+        self.update_check = None;
+        self.calculator.notice = Some(update::summary(outcome));
+        self.needs_redraw = true;
+    }
+
+    /// Watches `themes/` for edits to the theme in use. Without the directory
+    /// only built-in themes are in use, and there is nothing to watch.
+    pub fn watch_themes(&mut self) {
+        if !Path::new("themes").is_dir() {
+            return;
+        }
+        match ThemeWatcher::start(Path::new("themes")) {
+            Ok(watcher) => self.theme_watcher = Some(watcher),
+            Err(e) => self.calculator.error = Some(CalculatorError::Theme(format!("can't watch themes/: {}", e))),
+        }
+    }
+
+    /// Re-applies the theme in use when its file changed. A file caught half-written
+    /// or with a mistake leaves the theme as it was, with the error in the status bar.
+    pub fn poll_theme_changes(&mut self) {
+        let Some(watcher) = &self.theme_watcher else {
+            return;
+        };
+        let name = self.current_theme.name.clone();
+        if !watcher.changed().contains(&name) {
+            return;
+        }
+        self.needs_redraw = true;
+        match load_theme(&name) {
+            Ok(theme) => {
+                self.current_theme = theme;
+                if matches!(self.calculator.error, Some(CalculatorError::Theme(_))) {
+                    self.calculator.error = None;
+                }
+                tracing::info!(theme = %name, "theme reloaded");
+                self.calculator.notice = Some(format!("Theme '{}' reloaded", name));
+            }
+            Err(e) => {
+                self.debug.record_error(e.as_ref());
+                let error = CalculatorError::Theme(format!("theme '{}': {}", name, e));
+                self.error_log.record(&error, "");
+                self.calculator.error = Some(error);
+            }
+        }
+    }
+
+    /// Bracketed paste goes to whatever takes text. Popups without a text field
+    /// ignore it rather than typing into the input hidden under them.
+    pub fn paste(&mut self, text: &str) {
+        let calculator = &self.calculator;
+        let popup = self.recovery.is_some() || self.show_help || self.show_quick_ref || self.show_float_inspector || self.show_iteration_plot || calculator.table_import.is_some() || calculator.usage_stats.is_some() || calculator.result_stats.is_some() || calculator.backup_picker.is_some();
+        if popup || !matches!(self.focus(), Focus::Input | Focus::Stack | Focus::Command | Focus::ViNormal) {
+            return;
+        }
+        // Like typing a digit in vi normal mode, a paste starts insert mode
+        if self.vi_mode == Some(ViMode::Normal) {
+            self.vi_mode = Some(ViMode::Insert);
+        }
+        self.calculator.handle_paste(text);
+    }
+
+    pub fn toggle_help(&mut self) {
+        if !self.show_help {
+            self.help_focus = self.focus();
+        }
+        self.show_help = !self.show_help;
+    }
+
+    pub fn toggle_float_inspector(&mut self) {
+        self.show_float_inspector = !self.show_float_inspector;
+    }
+
+    pub fn toggle_inspect(&mut self) {
+        self.show_inspect = !self.show_inspect && self.calculator.selected_entry().is_some();
+    }
+
+    pub fn toggle_iteration_plot(&mut self) {
+        self.show_iteration_plot = !self.show_iteration_plot && self.calculator.last_iteration.is_some();
+    }
+
+    /// Ctrl+G: the real values on the stack, or the sequence while the
+    /// iteration plot is open, plotted by gnuplot in a window of its own.
+    pub fn send_to_gnuplot(&mut self) {
+        let (values, name, title) = match &self.calculator.last_iteration {
+            Some(sequence) if self.show_iteration_plot => (sequence.clone(), "sequence", "iterate(...) sequence"),
+            _ => (self.calculator.stack.iter().filter_map(|entry| entry.result.as_real()).filter(|value| value.is_finite()).collect(), "stack", "Stack"),
+        };
+        if values.is_empty() {
+            self.calculator.notice = Some("No real values on the stack to plot".to_string());
+            return;
+        }
+        match gnuplot::send(&values, name, title, &self.gnuplot_command) {
+            Ok(data) => self.calculator.notice = Some(format!("Sent {} values to gnuplot ({})", values.len(), data.display())),
+            Err(e) => self.calculator.error = Some(CalculatorError::Export(e.to_string())),
+        }
+    }
+
+    pub fn toggle_stack_chart(&mut self) {
+        self.show_stack_chart = !self.show_stack_chart;
+    }
+
+    pub fn toggle_stopwatch(&mut self) {
+        self.show_stopwatch = !self.show_stopwatch;
+    }
+
+    /// Opens on the newest error.
+    pub fn toggle_error_log(&mut self) {
+        self.show_error_log = !self.show_error_log;
+        self.error_log.selected = 0;
+    }
+
+    /// Closes the error log with the selected error's input back in the input line.
+    pub fn reuse_logged_input(&mut self) {
+        if let Some(error) = self.error_log.selected() {
+            let input = error.input.clone();
+            self.calculator.restore_input(&input);
+        }
+        self.show_error_log = false;
+    }
+
+    /// Pushes the seconds run so far, as an entry that can be undone.
+    pub fn push_stopwatch(&mut self, now: Instant) {
+        let seconds = self.stopwatch.elapsed(now).as_secs_f64();
+        // Milliseconds are as precise as a key press can time anything
+        let text = format!("{}", (seconds * 1000.0).round() / 1000.0);
+        if self.calculator.push_expression(&text, EntryOrigin::Typed).is_ok() {
+            let kind = if self.stopwatch.mode == TimerMode::Stopwatch { "Stopwatch" } else { "Countdown" };
+            self.calculator.notice = Some(format!("{} time pushed: {} s", kind, text));
+        }
+    }
+
+    /// Switches to the presentation layout and back to the one before it.
+    pub fn toggle_presentation(&mut self) {
+        if self.layout == Layout::Present {
+            self.layout = self.layout_before_presentation;
+        } else {
+            self.layout_before_presentation = self.layout;
+            self.layout = Layout::Present;
+        }
+    }
+
+    pub fn toggle_display(&mut self) {
+        self.show_display = !self.show_display;
+    }
+
+    pub fn toggle_theme_selector(&mut self) {
+        self.show_theme_selector = !self.show_theme_selector;
+        self.theme_before_selector = self.show_theme_selector.then(|| self.current_theme.clone());
+        if self.show_theme_selector {
+            // When opening the selector, pre-select the current theme
+            if let Some(index) = self.available_themes.iter().position(|name| name == &self.current_theme.name) {
+                self.theme_list_state.select(Some(index));
+            } else {
+                // If current theme not found in available themes (e.g., deleted), select first
+                self.theme_list_state.select(Some(0));
+            }
+        }
+    }
+
+    /// Moves the selector up or down (wrapping around) and previews the theme there.
+    pub fn move_theme_selection(&mut self, down: bool) {
+        let count = self.available_themes.len();
+        if count == 0 {
+            return;
+        }
+        let selected = self.theme_list_state.selected().unwrap_or(0);
+        let next = if down { (selected + 1) % count } else { (selected + count - 1) % count };
+        self.theme_list_state.select(Some(next));
+        let theme_name = self.available_themes[next].clone();
+        self.preview_theme(&theme_name);
+    }
+
+    /// Closes the selector and goes back to the theme it was opened with.
+    pub fn cancel_theme_selector(&mut self) {
+        if let Some(theme) = self.theme_before_selector.take() {
+            self.current_theme = theme;
+        }
+        self.show_theme_selector = false;
+    }
+
+    pub fn set_theme(&mut self, theme_name: &str) -> Result<()> {
+        self.current_theme = load_theme(theme_name)?;
+
+        // Save selected theme to config file
+        backup::write_with_backup(&config_dir()?.join("theme.txt"), theme_name)
+    }
+
+    pub fn preview_theme(&mut self, theme_name: &str) {
+        if let Ok(theme) = load_theme(theme_name) {
+            self.current_theme = theme;
+        }
+    }
+
+    /// Focuses the panel under a left click, if any.
+    pub fn click(&mut self, column: u16, row: u16) {
+        if let Some((panel, _)) = self.panel_areas.iter().find(|(_, area)| area.contains(Position::new(column, row))) {
+            self.panel_focus = *panel;
+        }
+    }
+
+    /// Moves the focused panel's selection `rows` down (negative: up), stopping at
+    /// either end; in the input, goes to its start or end.
+    pub fn focused_move(&mut self, rows: isize) {
+        match self.panel_focus {
+            Panel::Stack => self.browse_stack_by(rows),
+            Panel::History => self.browse_history_by(rows),
+            Panel::Input if rows < 0 => self.calculator.move_cursor_home(),
+            Panel::Input => self.calculator.move_cursor_end(),
+        }
+    }
+
+    /// Ctrl+Up/Down: a screenful of the focused panel, as many rows as it showed last frame.
+    pub fn focused_page(&mut self, down: bool) {
+        let rows = self.panel_areas.iter().find(|(panel, _)| *panel == self.panel_focus).map_or(10, |(_, area)| area.height.saturating_sub(2).max(1)) as isize;
+        self.focused_move(if down { rows } else { -rows });
+    }
+
+    /// Rows an Up or Down moves: one, then more the longer it's held. A key held down
+    /// arrives as repeats, or where the terminal doesn't mark them, as presses in quick succession.
+    pub fn navigation_step(&mut self, key: KeyEvent) -> usize {
+        let now = Instant::now();
+        let held = key.kind == KeyEventKind::Repeat || self.last_navigation.is_some_and(|(code, at)| code == key.code && now.duration_since(at) < self.repeat_window);
+        self.navigation_repeats = if held { self.navigation_repeats + 1 } else { 0 };
+        self.last_navigation = Some((key.code, now));
+        match self.navigation_repeats {
+            0..10 => 1,
+            10..30 => 5,
+            _ => 20,
+        }
+    }
+
+    pub fn browse_stack_by(&mut self, rows: isize) {
+        self.calculator.browse_stack_to(self.calculator.stack_position.saturating_add_signed(rows));
+        self.stack_list_state.select(Some(self.calculator.stack_position));
+    }
+
+    /// After `:find`, focuses the panel the match is in and scrolls it into view.
+    pub fn follow_found(&mut self) {
+        match self.calculator.found.take() {
+            Some(Found::Stack(level)) => {
+                self.panel_focus = Panel::Stack;
+                self.stack_list_state.select(Some(level));
+            }
+            Some(Found::History(index)) => {
+                self.panel_focus = Panel::History;
+                self.history_list_state.select(Some(index));
+            }
+            None => {}
+        }
+    }
+
+    pub fn browse_history_by(&mut self, rows: isize) {
+        if self.calculator.history.is_empty() {
+            return;
+        }
+        self.calculator.browse_history_to(self.calculator.history_position.saturating_add_signed(rows));
+        self.history_list_state.select(Some(self.calculator.history_position));
+    }
+}
+
+/// Reads `themes/<name>.json`; "default" falls back to the built-in theme when the file is missing.
+fn load_theme(name: &str) -> Result<Theme> {
+    match fs::read_to_string(format!("themes/{}.json", name)) {
+        Ok(content) => Ok(serde_json::from_str(&content)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && let Some(theme) = Theme::builtin_named(name) => Ok(theme),
+        Err(e) => Err(e.into()),
+    }
+}
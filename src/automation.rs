@@ -0,0 +1,82 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::time::Duration;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{backend::TestBackend, Terminal};
+use tuic_core::EntryOrigin;
+
+use crate::app::App;
+use crate::keyspec::parse_key;
+use crate::{handle_key, ui};
+
+const DEFAULT_SIZE: (u16, u16) = (100, 40);
+
+/// Drives the UI headlessly from a script read line by line from `path`
+/// (typically a FIFO), printing rendered frames to stdout on request.
+///
+/// Script commands:
+///   key <spec>...     press keys, e.g. `key 2 Enter`, `key Ctrl+c`, `key F2 Alt+m`
+///   type <text>       press each character of the text
+///   paste <text>      bracketed paste; `\n` and `\t` are unescaped
+///   resize <w> <h>    change the virtual terminal size
+///   dump              print the current frame
+///   quit              stop (so does end of input or a quitting key)
+pub fn run(path: &str, app: &mut App) -> io::Result<()> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut terminal = Terminal::new(TestBackend::new(DEFAULT_SIZE.0, DEFAULT_SIZE.1))?;
+    let mut stdout = io::stdout().lock();
+    app.calculator.input_origin = EntryOrigin::Script;
+    app.repeat_window = Duration::ZERO; // Scripted keys come all at once but aren't held down
+
+    for line in reader.lines() {
+        let line = line?;
+        let (command, argument) = line.trim_start().split_once(' ').unwrap_or((line.trim(), ""));
+        match command {
+            "" => {}
+            "key" => {
+                for spec in argument.split_whitespace() {
+                    let key = parse_key(spec).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("unknown key '{}'", spec)))?;
+                    if handle_key(app, key) {
+                        return Ok(());
+                    }
+                }
+            }
+            "type" => {
+                for ch in argument.chars() {
+                    if handle_key(app, KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE)) {
+                        return Ok(());
+                    }
+                }
+            }
+            "paste" => app.paste(&argument.replace("\\n", "\n").replace("\\t", "\t")),
+            "resize" => {
+                let size: Vec<u16> = argument.split_whitespace().filter_map(|n| n.parse().ok()).collect();
+                if let [width, height] = size[..] {
+                    terminal.backend_mut().resize(width, height);
+                }
+            }
+            "dump" => {
+                terminal.draw(|f| ui::draw(f, app))?;
+                writeln!(stdout, "{}", frame_text(terminal.backend()))?;
+                stdout.flush()?;
+            }
+            "quit" => return Ok(()),
+            other => writeln!(io::stderr(), "automation: unknown command '{}'", other)?,
+        }
+    }
+    Ok(())
+}
+
+/// Frame rows with trailing blanks trimmed, followed by a separator line.
+fn frame_text(backend: &TestBackend) -> String {
+    let buffer = backend.buffer();
+    let mut text = String::new();
+    for y in 0..buffer.area.height {
+        let row: String = (0..buffer.area.width).map(|x| buffer[(x, y)].symbol()).collect();
+        text.push_str(row.trim_end());
+        text.push('\n');
+    }
+    text.push_str("---");
+    text
+}
@@ -0,0 +1,179 @@
+//! `batch` and `eval`: evaluates one infix expression per stdin line and prints the
+//! results, for pipelines. Blank lines and `#` comments are skipped, and `:`
+//! lines run as commands (`:set precision 2`) that print nothing unless they fail.
+//! The stack printed on quitting (`print-on-exit`) uses the same formats.
+
+use std::io::{self, BufRead, Write};
+
+use serde_json::{json, Map, Value};
+use tuic_core::config::{PrintFormat, PrintOnExit};
+use tuic_core::{Calculator, CalculatorError, EntryOrigin, StackValue};
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Format {
+    Plain, // The result, or `error <code> <message>`, one line each
+    Json,  // One JSON object per line
+    Csv,   // line,expression,result,error with a header row
+}
+
+/// Evaluates every line of `input`; returns whether all of them succeeded.
+pub fn run(calculator: &mut Calculator, input: impl BufRead, output: &mut impl Write, format: Format) -> io::Result<bool> {
+    let mut all_ok = true;
+    if format == Format::Csv {
+        writeln!(output, "line,expression,result,error")?;
+    }
+    for (index, line) in input.lines().enumerate() {
+        let line = line?;
+        let expression = line.trim();
+        if expression.is_empty() || expression.starts_with('#') {
+            continue;
+        }
+        let outcome = calculator.push_expression(expression, EntryOrigin::Script);
+        // A command that worked prints nothing, even with no top of the stack to return
+        if expression.starts_with(':') && matches!(outcome, Ok(_) | Err(CalculatorError::InvalidExpression)) {
+            continue;
+        }
+        all_ok &= outcome.is_ok();
+        write_row(output, format, index + 1, expression, outcome.map(|value| (calculator.format_stack_value(&value), value)))?;
+    }
+    Ok(all_ok)
+}
+
+fn write_row(output: &mut impl Write, format: Format, line: usize, expression: &str, outcome: Result<(String, StackValue), CalculatorError>) -> io::Result<()> {
+    match format {
+        Format::Plain => match outcome {
+            Ok((text, _)) => writeln!(output, "{}", text),
+            Err(e) => writeln!(output, "error {} {}", e.code(), e),
+        },
+        Format::Json => {
+            let mut row = Map::new();
+            row.insert("line".to_string(), json!(line));
+            row.insert("expression".to_string(), json!(expression));
+            match outcome {
+                Ok((text, value)) => {
+                    // A number where JSON has one; complex and uncertain values only as text
+                    if let StackValue::Real(number) = value {
+                        row.insert("value".to_string(), json!(number));
+                    }
+                    row.insert("result".to_string(), json!(text));
+                }
+                Err(e) => {
+                    row.insert("error".to_string(), json!({ "code": e.code(), "message": e.to_string() }));
+                }
+            }
+            writeln!(output, "{}", Value::Object(row))
+        }
+        Format::Csv => {
+            let (result, error) = match outcome {
+                Ok((text, _)) => (text, String::new()),
+                Err(e) => (String::new(), format!("{} {}", e.code(), e)),
+            };
+            writeln!(output, "{},{},{},{}", line, csv_field(expression), csv_field(&result), csv_field(&error))
+        }
+    }
+}
+
+/// The entries `which` selects, oldest first so the top is the last line;
+/// levels count from 1 at the top, as in `:drop`.
+pub fn write_stack(calculator: &Calculator, which: PrintOnExit, format: PrintFormat, output: &mut impl Write) -> io::Result<()> {
+    let count = match which {
+        PrintOnExit::Off => return Ok(()),
+        PrintOnExit::Top(count) => count.min(calculator.stack.len()),
+        PrintOnExit::Stack => calculator.stack.len(),
+    };
+    if format == PrintFormat::Csv {
+        writeln!(output, "level,expression,result")?;
+    }
+    for level in (1..=count).rev() {
+        let Some(entry) = calculator.stack.from_top(level - 1) else {
+            continue;
+        };
+        let text = calculator.format_stack_value(&entry.result);
+        match format {
+            PrintFormat::Plain => writeln!(output, "{}", text)?,
+            PrintFormat::Json => {
+                let mut row = Map::new();
+                row.insert("level".to_string(), json!(level));
+                row.insert("expression".to_string(), json!(&*entry.expression));
+                if let StackValue::Real(number) = entry.result {
+                    row.insert("value".to_string(), json!(number));
+                }
+                row.insert("result".to_string(), json!(text));
+                writeln!(output, "{}", Value::Object(row))?;
+            }
+            PrintFormat::Csv => writeln!(output, "{},{},{}", level, csv_field(&entry.expression), csv_field(&text))?,
+        }
+    }
+    Ok(())
+}
+
+/// Quotes a field holding a comma, quote or line break, doubling its quotes.
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n']) { format!("\"{}\"", text.replace('"', "\"\"")) } else { text.to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn batch(input: &str, format: Format) -> (String, bool) {
+        let mut output = Vec::new();
+        let ok = run(&mut Calculator::new(), input.as_bytes(), &mut output, format).unwrap();
+        (String::from_utf8(output).unwrap(), ok)
+    }
+
+    #[test]
+    fn formats() {
+        let input = "2 + 3\n\n# a comment\n:set precision 2\n1 / 3\n";
+        assert_eq!(batch(input, Format::Plain), ("5\n0.33\n".to_string(), true));
+        assert_eq!(
+            batch(input, Format::Json).0,
+            "{\"expression\":\"2 + 3\",\"line\":1,\"result\":\"5\",\"value\":5.0}\n{\"expression\":\"1 / 3\",\"line\":5,\"result\":\"0.33\",\"value\":0.3333333333333333}\n"
+        );
+        assert_eq!(batch("gcd(12, 18)\n", Format::Csv).0, "line,expression,result,error\n1,\"gcd(12, 18)\",6,\n");
+        assert_eq!(batch(":set precision 2\n1 / 3\n", Format::Plain), ("0.33\n".to_string(), true));
+    }
+
+    #[test]
+    fn failures_are_reported_per_line() {
+        let (output, ok) = batch("1 / 0\n4\n", Format::Json);
+        assert!(!ok);
+        assert_eq!(
+            output.lines().next(),
+            Some("{\"error\":{\"code\":\"E201\",\"message\":\"Division by zero (1 / 0)\"},\"expression\":\"1 / 0\",\"line\":1}")
+        );
+        assert_eq!(batch(":nonsense\n", Format::Plain).0, "error E401 Unknown command: nonsense\n");
+    }
+
+    #[test]
+    fn stack_printed_on_exit() {
+        let mut calculator = Calculator::new();
+        for expression in ["1 + 1", "10 / 4", "7"] {
+            calculator.push_expression(expression, EntryOrigin::Script).unwrap();
+        }
+        let print = |which, format| {
+            let mut output = Vec::new();
+            write_stack(&calculator, which, format, &mut output).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+        assert_eq!(print(PrintOnExit::Top(1), PrintFormat::Plain), "7\n");
+        assert_eq!(print(PrintOnExit::Stack, PrintFormat::Plain), "2\n2.5\n7\n");
+        assert_eq!(print(PrintOnExit::Top(2), PrintFormat::Csv), "level,expression,result\n2,10 / 4,2.5\n1,7,7\n");
+        assert_eq!(print(PrintOnExit::Top(9), PrintFormat::Json).lines().next(), Some("{\"expression\":\"1 + 1\",\"level\":3,\"result\":\"2\",\"value\":2.0}"));
+        assert_eq!(print(PrintOnExit::Off, PrintFormat::Plain), "");
+    }
+
+    #[test]
+    fn defined_variables() {
+        let mut config = tuic_core::config::Config::default();
+        config.set("define", "rate=0.21").unwrap();
+        let mut calculator = Calculator::new();
+        calculator.apply_config(&config);
+        let mut output = Vec::new();
+        assert!(!run(&mut calculator, "100 * rate
+[1, 2] * rate
+rates
+".as_bytes(), &mut output, Format::Plain).unwrap());
+        assert_eq!(String::from_utf8(output).unwrap(), "21\n[0.21, 0.42]\nerror E204 Unknown variable 'rates'\n");
+    }
+}
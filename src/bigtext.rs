@@ -0,0 +1,139 @@
+//! Large digits for reading at a distance: block letters five rows tall for
+//! the presentation layout, and three-row seven-segment digits for the
+//! desk-calculator display.
+
+/// Rows in every glyph.
+pub const HEIGHT: usize = 5;
+
+/// The glyph for a character, one string per row; None for a character there is no glyph for.
+fn glyph(ch: char) -> Option<[&'static str; HEIGHT]> {
+    Some(match ch {
+        '0' => ["███", "█ █", "█ █", "█ █", "███"],
+        '1' => [" █ ", "██ ", " █ ", " █ ", "███"],
+        '2' => ["███", "  █", "███", "█  ", "███"],
+        '3' => ["███", "  █", " ██", "  █", "███"],
+        '4' => ["█ █", "█ █", "███", "  █", "  █"],
+        '5' => ["███", "█  ", "███", "  █", "███"],
+        '6' => ["███", "█  ", "███", "█ █", "███"],
+        '7' => ["███", "  █", "  █", "  █", "  █"],
+        '8' => ["███", "█ █", "███", "█ █", "███"],
+        '9' => ["███", "█ █", "███", "  █", "███"],
+        // Hex digits, and the letters of 0x, 0b, 1e-9 and 3+4i
+        'A' | 'a' => ["███", "█ █", "███", "█ █", "█ █"],
+        'B' => ["██ ", "█ █", "██ ", "█ █", "██ "],
+        'C' | 'c' => ["███", "█  ", "█  ", "█  ", "███"],
+        'D' | 'd' => ["██ ", "█ █", "█ █", "█ █", "██ "],
+        'E' | 'e' => ["███", "█  ", "██ ", "█  ", "███"],
+        'F' | 'f' => ["███", "█  ", "██ ", "█  ", "█  "],
+        'b' => ["█  ", "█  ", "███", "█ █", "███"],
+        'x' => ["   ", "   ", "█ █", " █ ", "█ █"],
+        'i' => ["█", " ", "█", "█", "█"],
+        '.' => [" ", " ", " ", " ", "█"],
+        ',' => [" ", " ", " ", "█", "▀"],
+        '-' => ["   ", "   ", "███", "   ", "   "],
+        '+' => ["   ", " █ ", "███", " █ ", "   "],
+        ' ' => ["  ", "  ", "  ", "  ", "  "],
+        _ => return None,
+    })
+}
+
+/// `text` in block letters with a column between them, or None when it has a
+/// character without a glyph or would be wider than `width`.
+pub fn render(text: &str, width: usize) -> Option<Vec<String>> {
+    let glyphs = text.chars().map(glyph).collect::<Option<Vec<_>>>()?;
+    let lines: Vec<String> = (0..HEIGHT).map(|row| glyphs.iter().map(|glyph| glyph[row]).collect::<Vec<_>>().join(" ")).collect();
+    (lines[0].chars().count() <= width).then_some(lines)
+}
+
+/// Rows in a seven-segment digit.
+pub const SEGMENT_HEIGHT: usize = 3;
+
+/// Segments lit for a character, as bits `gfedcba`: a is the top, then clockwise, g the middle.
+fn segments(ch: char) -> Option<u8> {
+    Some(match ch {
+        '0' => 0b0111111,
+        '1' => 0b0000110,
+        '2' => 0b1011011,
+        '3' => 0b1001111,
+        '4' => 0b1100110,
+        '5' => 0b1101101,
+        '6' => 0b1111101,
+        '7' => 0b0000111,
+        '8' => 0b1111111,
+        '9' => 0b1101111,
+        'A' | 'a' => 0b1110111,
+        'B' | 'b' => 0b1111100,
+        'C' | 'c' => 0b0111001,
+        'D' | 'd' => 0b1011110,
+        'E' | 'e' => 0b1111001,
+        'F' | 'f' => 0b1110001,
+        '-' => 0b1000000,
+        ' ' => 0,
+        _ => return None,
+    })
+}
+
+/// A digit on a grid of 3×5 cells, drawn two cells to a character with half blocks.
+fn segment_glyph(bits: u8) -> [String; SEGMENT_HEIGHT] {
+    let lit = |segment: u8| bits & (1 << segment) != 0;
+    let cell = |row: usize, column: usize| match (row, column) {
+        (0, _) => lit(0) || (column == 0 && lit(5)) || (column == 2 && lit(1)),
+        (1, 0) => lit(5),
+        (1, 2) => lit(1),
+        (2, _) => lit(6) || (column == 0 && (lit(5) || lit(4))) || (column == 2 && (lit(1) || lit(2))),
+        (3, 0) => lit(4),
+        (3, 2) => lit(2),
+        (4, _) => lit(3) || (column == 0 && lit(4)) || (column == 2 && lit(2)),
+        _ => false,
+    };
+    std::array::from_fn(|row| {
+        (0..3)
+            .map(|column| match (cell(row * 2, column), cell(row * 2 + 1, column)) {
+                (true, true) => '█',
+                (true, false) => '▀',
+                (false, true) => '▄',
+                (false, false) => ' ',
+            })
+            .collect()
+    })
+}
+
+/// `text` in seven-segment digits, a decimal point sitting low between two of
+/// them; None when it has a character a segment display can't show or is wider than `width`.
+pub fn seven_segment(text: &str, width: usize) -> Option<Vec<String>> {
+    let mut lines = vec![String::new(); SEGMENT_HEIGHT];
+    for ch in text.chars() {
+        if ch == '.' || ch == ',' {
+            lines[SEGMENT_HEIGHT - 1].push('▀');
+            lines[..SEGMENT_HEIGHT - 1].iter_mut().for_each(|line| line.push(' '));
+            continue;
+        }
+        for (line, row) in lines.iter_mut().zip(segment_glyph(segments(ch)?)) {
+            line.push(' ');
+            line.push_str(&row);
+        }
+    }
+    (lines[0].chars().count() <= width).then_some(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_numbers_in_block_letters() {
+        let lines = render("-1.5", 80).unwrap();
+        assert_eq!(lines, ["     █    ███", "    ██    █  ", "███  █    ███", "     █      █", "    ███ █ ███"].map(str::to_string));
+        assert_eq!(render("12", 6).map(|lines| lines.len()), None); // Seven columns wide
+        assert_eq!(render("12", 7).map(|lines| lines.len()), Some(HEIGHT));
+        assert_eq!(render("1∠30°", 80), None);
+    }
+
+    #[test]
+    fn renders_seven_segment_digits() {
+        let lines = seven_segment("-8.1", 80).unwrap();
+        assert_eq!(lines, ["     █▀█    █", " ▀▀▀ █▀█    █", "     ▀▀▀▀   ▀"].map(str::to_string));
+        assert_eq!(seven_segment("0x1F", 80), None);
+        assert_eq!(seven_segment("1E-3", 15).map(|lines| lines.len()), None); // Sixteen columns wide
+    }
+}
@@ -0,0 +1,329 @@
+//! The command line: settings that override config.toml, and subcommands that
+//! run without the terminal UI. Completions and the man page are generated
+//! from the same definition, so they list exactly what is accepted.
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use tuic_core::config::Config;
+
+use crate::batch::Format;
+use crate::theme;
+
+#[derive(Parser)]
+#[command(name = "tui-calculator", version, about = "RPN and infix calculator for the terminal")]
+pub struct Cli {
+    #[command(flatten)]
+    pub settings: Settings,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    // Spellings from before the subcommands, kept so existing scripts work
+    #[arg(long, value_name = "SOCKET", hide = true)]
+    serve: Option<PathBuf>,
+    #[arg(long, hide = true)]
+    batch: bool,
+    #[arg(long, value_enum, hide = true, requires = "batch")]
+    format: Option<Format>,
+    /// Popup calculator for a hotkey: only the input and status lines; Enter copies the result and exits
+    #[arg(long)]
+    pub quick: bool,
+}
+
+#[derive(Clone, Subcommand)]
+pub enum Command {
+    /// Evaluate infix expressions and print the results, one per line
+    Eval {
+        #[arg(required = true, value_name = "EXPRESSION")]
+        expressions: Vec<String>,
+        #[arg(long, value_enum, default_value_t = Format::Plain)]
+        format: Format,
+    },
+    /// Evaluate one infix expression per line of standard input
+    Batch {
+        #[arg(long, value_enum, default_value_t = Format::Plain)]
+        format: Format,
+    },
+    /// Run the calculator and answer requests on a Unix socket
+    Serve { socket: PathBuf },
+    /// Print a completion script for a shell
+    Completions { shell: Shell },
+    /// Print the man page in roff
+    Man,
+    /// Work with theme files
+    Theme {
+        #[command(subcommand)]
+        command: ThemeCommand,
+    },
+}
+
+#[derive(Clone, Subcommand)]
+pub enum ThemeCommand {
+    /// Report missing fields and invalid colors in theme files, with line and column
+    Check {
+        #[arg(required = true, value_name = "FILE")]
+        files: Vec<PathBuf>,
+    },
+}
+
+/// Every config.toml key as a flag; a flag wins over the file.
+#[derive(clap::Args)]
+pub struct Settings {
+    /// Entry mode at startup
+    #[arg(long, global = true, value_parser = ["rpn", "infix"])]
+    mode: Option<String>,
+    /// Angle unit for trigonometry
+    #[arg(long, global = true, value_parser = ["rad", "deg"])]
+    angle: Option<String>,
+    /// Number base the stack is shown in
+    #[arg(long, global = true, value_parser = ["dec", "hex", "bin"])]
+    base: Option<String>,
+    /// Digits after the point, 0-20, or auto
+    #[arg(long, global = true, value_name = "N|auto")]
+    precision: Option<String>,
+    /// A built-in theme or one in themes/
+    #[arg(long, global = true, value_name = "NAME")]
+    theme: Option<String>,
+    /// How much of the screen the panels take
+    #[arg(long, global = true, value_parser = ["full", "compact", "present"])]
+    layout: Option<String>,
+    /// History entries kept
+    #[arg(long, global = true, value_name = "N|unlimited")]
+    history_size: Option<String>,
+    /// Stack entries kept
+    #[arg(long, global = true, value_name = "N|unlimited")]
+    stack_size: Option<String>,
+    /// Longest infix input accepted, in characters
+    #[arg(long, global = true, value_name = "N")]
+    max_input_length: Option<String>,
+    /// Deepest nesting of parentheses accepted
+    #[arg(long, global = true, value_name = "N")]
+    max_nesting: Option<String>,
+    /// Most numbers, names and operators in one expression
+    #[arg(long, global = true, value_name = "N")]
+    max_tokens: Option<String>,
+    /// Key bindings
+    #[arg(long, global = true, value_parser = ["default", "vi"])]
+    keys: Option<String>,
+    /// Share the history with other running instances
+    #[arg(long, global = true, value_parser = ["on", "off"])]
+    share_history: Option<String>,
+    /// Ask GitHub for a newer release at start
+    #[arg(long, global = true, value_parser = ["on", "off"])]
+    update_check: Option<String>,
+    /// How a half is rounded at the shown precision
+    #[arg(long, global = true, value_parser = ["half-away", "half-even"])]
+    rounding: Option<String>,
+    /// Decimal separator, instead of the one from the locale
+    #[arg(long, global = true, value_parser = ["point", "comma"])]
+    decimal: Option<String>,
+    /// Decimal separator of pasted amounts like $1,234.56; auto guesses it
+    #[arg(long, global = true, value_parser = ["auto", "point", "comma"])]
+    paste_decimal: Option<String>,
+    /// Read 4.7k, 100n or 2.2M as numbers with SI prefixes
+    #[arg(long, global = true, value_parser = ["on", "off"])]
+    si_prefixes: Option<String>,
+    /// Read 2**10, 7 // 2 and 1_000_000 in infix as Python does
+    #[arg(long, global = true, value_parser = ["on", "off"])]
+    python_syntax: Option<String>,
+    /// Log each change in plain text; print also writes it out on exit
+    #[arg(long, global = true, value_parser = ["off", "on", "print"])]
+    screen_reader: Option<String>,
+    /// Log to tui-calculator.log in the config directory at this level
+    #[arg(long, global = true, value_parser = ["off", "error", "warn", "info", "debug", "trace"])]
+    log_level: Option<String>,
+    /// When the session journal is saved: change, off, <N>s or <K>ops
+    #[arg(long, global = true, value_name = "WHEN")]
+    autosave: Option<String>,
+    /// Sales tax percent for TAX+ and TAX-
+    #[arg(long, global = true, value_name = "PERCENT")]
+    tax_rate: Option<String>,
+    /// Tip percent for TIP
+    #[arg(long, global = true, value_name = "PERCENT")]
+    tip_rate: Option<String>,
+    /// Command Ctrl+G runs on the plot script; {script} and {data} are its files
+    #[arg(long, global = true, value_name = "TEMPLATE")]
+    gnuplot_command: Option<String>,
+    /// Print stack entries to stdout on quitting: off, top, stack or a number of entries
+    #[arg(long, global = true, value_name = "WHICH")]
+    print_on_exit: Option<String>,
+    /// Format of the entries printed on quitting
+    #[arg(long, global = true, value_parser = ["plain", "json", "csv"])]
+    print_format: Option<String>,
+    /// Bind a variable for expressions, e.g. rate=0.21; may be repeated
+    #[arg(long, global = true, value_name = "NAME=NUMBER")]
+    define: Vec<String>,
+    /// Format of history lines, e.g. "{expr} = {result}"
+    #[arg(long, global = true, value_name = "TEMPLATE")]
+    history_template: Option<String>,
+    /// Format of copied entries
+    #[arg(long, global = true, value_name = "TEMPLATE")]
+    copy_template: Option<String>,
+    /// Format of exported entries
+    #[arg(long, global = true, value_name = "TEMPLATE")]
+    export_template: Option<String>,
+}
+
+impl Settings {
+    /// Sets the flags that were given over `config`, checking them as the file's values are.
+    pub fn apply(&self, config: &mut Config) -> anyhow::Result<()> {
+        let flags = [
+            ("mode", &self.mode),
+            ("angle", &self.angle),
+            ("base", &self.base),
+            ("precision", &self.precision),
+            ("theme", &self.theme),
+            ("layout", &self.layout),
+            ("history-size", &self.history_size),
+            ("stack-size", &self.stack_size),
+            ("max-input-length", &self.max_input_length),
+            ("max-nesting", &self.max_nesting),
+            ("max-tokens", &self.max_tokens),
+            ("keys", &self.keys),
+            ("share-history", &self.share_history),
+            ("rounding", &self.rounding),
+            ("decimal", &self.decimal),
+            ("paste-decimal", &self.paste_decimal),
+            ("si-prefixes", &self.si_prefixes),
+            ("python-syntax", &self.python_syntax),
+            ("screen-reader", &self.screen_reader),
+            ("log-level", &self.log_level),
+            ("update-check", &self.update_check),
+            ("autosave", &self.autosave),
+            ("tax-rate", &self.tax_rate),
+            ("tip-rate", &self.tip_rate),
+            ("gnuplot-command", &self.gnuplot_command),
+            ("print-on-exit", &self.print_on_exit),
+            ("print-format", &self.print_format),
+            ("history-template", &self.history_template),
+            ("copy-template", &self.copy_template),
+            ("export-template", &self.export_template),
+        ];
+        for (key, value) in flags {
+            if let Some(value) = value {
+                config.set(key, value)?;
+            }
+        }
+        for define in &self.define {
+            config.set("define", define)?;
+        }
+        Ok(())
+    }
+}
+
+impl Cli {
+    /// The subcommand, with the old `--serve` and `--batch` flags read as theirs.
+    pub fn subcommand(&self) -> Option<Command> {
+        match (&self.command, &self.serve) {
+            (Some(command), _) => Some(command.clone()),
+            (None, Some(socket)) => Some(Command::Serve { socket: socket.clone() }),
+            (None, None) if self.batch => Some(Command::Batch { format: self.format.unwrap_or(Format::Plain) }),
+            (None, None) => None,
+        }
+    }
+
+    /// Reports a mistake the way clap reports its own, and exits with status 2.
+    pub fn fail(message: impl std::fmt::Display) -> ! {
+        Cli::command().error(clap::error::ErrorKind::InvalidValue, message).exit()
+    }
+}
+
+/// Writes the completion script for `shell`.
+pub fn completions(shell: Shell, out: &mut impl Write) {
+    clap_complete::generate(shell, &mut Cli::command(), "tui-calculator", out);
+}
+
+/// Writes the man page.
+pub fn man_page(out: &mut impl Write) -> io::Result<()> {
+    clap_mangen::Man::new(Cli::command()).render(out)
+}
+
+/// Writes `file:line:column: error: message` for each problem in the theme
+/// files, or `file: ok`; false when any has an error rather than only warnings.
+pub fn check_themes(files: &[PathBuf], out: &mut impl Write) -> io::Result<bool> {
+    let mut all_ok = true;
+    for file in files {
+        let problems = match std::fs::read_to_string(file) {
+            Ok(text) => theme::check(&text),
+            Err(e) => {
+                writeln!(out, "{}: error: {}", file.display(), e)?;
+                all_ok = false;
+                continue;
+            }
+        };
+        for problem in &problems {
+            writeln!(out, "{}:{}:{}: {}: {}", file.display(), problem.line, problem.column, if problem.warning { "warning" } else { "error" }, problem.message)?;
+        }
+        if problems.iter().all(|problem| problem.warning) {
+            writeln!(out, "{}: ok", file.display())?;
+        } else {
+            all_ok = false;
+        }
+    }
+    Ok(all_ok)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tuic_core::config::KEYS;
+
+    #[test]
+    fn every_setting_is_a_flag() {
+        let command = Cli::command();
+        for key in KEYS {
+            assert!(command.get_arguments().any(|arg| arg.get_long() == Some(key)), "no --{}", key);
+        }
+        command.debug_assert();
+    }
+
+    #[test]
+    fn subcommands_and_old_flags() {
+        let cli = Cli::try_parse_from(["tui-calculator", "--mode", "infix", "eval", "2 + 3", "--precision", "2"]).unwrap();
+        assert!(matches!(cli.subcommand(), Some(Command::Eval { expressions, format: Format::Plain }) if expressions == ["2 + 3"]));
+        let mut config = Config::default();
+        cli.settings.apply(&mut config).unwrap();
+        assert_eq!(config.precision, Some(Some(2)));
+
+        let cli = Cli::try_parse_from(["tui-calculator", "--define", "rate=0.21", "--define", "n=4", "eval", "100 * rate"]).unwrap();
+        cli.settings.apply(&mut config).unwrap();
+        assert_eq!(config.defines, [("rate".to_string(), 0.21), ("n".to_string(), 4.0)]);
+
+        let cli = Cli::try_parse_from(["tui-calculator", "--batch", "--format", "csv"]).unwrap();
+        assert!(matches!(cli.subcommand(), Some(Command::Batch { format: Format::Csv })));
+        let cli = Cli::try_parse_from(["tui-calculator", "--serve", "/tmp/tuic.sock"]).unwrap();
+        assert!(matches!(cli.subcommand(), Some(Command::Serve { .. })));
+        let cli = Cli::try_parse_from(["tui-calculator", "theme", "check", "themes/dracula.json"]).unwrap();
+        assert!(matches!(cli.subcommand(), Some(Command::Theme { command: ThemeCommand::Check { files } }) if files == [PathBuf::from("themes/dracula.json")]));
+        assert!(Cli::try_parse_from(["tui-calculator", "--mode", "postfix"]).is_err());
+        assert!(Cli::try_parse_from(["tui-calculator", "--format", "csv"]).is_err());
+
+        let cli = Cli::try_parse_from(["tui-calculator", "--precision", "99"]).unwrap();
+        assert!(cli.settings.apply(&mut Config::default()).is_err());
+    }
+
+    #[test]
+    fn checks_theme_files() {
+        let broken = std::env::temp_dir().join(format!("tuic-theme-check-{}.json", std::process::id()));
+        std::fs::write(&broken, "{\n  \"name\": \"broken\"\n}\n").unwrap();
+        let mut out = Vec::new();
+        assert!(!check_themes(&[PathBuf::from("themes/dracula.json"), broken.clone()], &mut out).unwrap());
+        std::fs::remove_file(&broken).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("themes/dracula.json: ok\n"), "{}", out);
+        assert!(out.contains(&format!("{}:3:1: error: missing fields `background`, `foreground`, ", broken.display())));
+        assert!(!out.contains(&format!("{}: ok", broken.display())));
+    }
+
+    #[test]
+    fn generates_completions_and_a_man_page() {
+        let mut script = Vec::new();
+        completions(Shell::Bash, &mut script);
+        let script = String::from_utf8(script).unwrap();
+        assert!(script.contains("--angle") && script.contains("completions"));
+        let mut page = Vec::new();
+        man_page(&mut page).unwrap();
+        assert!(String::from_utf8(page).unwrap().contains(".TH tui-calculator"));
+    }
+}
@@ -0,0 +1,103 @@
+//! System clipboard. A local clipboard utility (wl-copy, xclip, xsel, pbcopy)
+//! is used where there is one; otherwise, and always over SSH, OSC 52 asks the
+//! terminal emulator to set the clipboard, which needs no platform code and
+//! reaches the clipboard of the machine the terminal runs on. Inside tmux or
+//! screen the sequence is wrapped so it passes through to the outer terminal.
+
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+pub fn copy(text: &str) -> io::Result<()> {
+    let env = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+    for (program, args) in local_commands(env).iter().filter_map(|command| command.split_first()) {
+        if copy_with(program, args, text).is_ok() {
+            return Ok(());
+        }
+    }
+    let mut output = crate::terminal_output(); // The terminal, even when stdout is captured
+    write!(output, "{}", osc52(text, env))?;
+    output.flush()
+}
+
+/// The clipboard utilities to try, in order, when this is a local session with a display.
+fn local_commands(env: impl Fn(&str) -> Option<String>) -> &'static [&'static [&'static str]] {
+    if env("SSH_CONNECTION").is_some() || env("SSH_TTY").is_some() {
+        &[] // Its clipboard is on the remote machine, not the user's
+    } else if cfg!(target_os = "macos") {
+        &[&["pbcopy"]]
+    } else if env("WAYLAND_DISPLAY").is_some() {
+        &[&["wl-copy"]]
+    } else if env("DISPLAY").is_some() {
+        &[&["xclip", "-selection", "clipboard"], &["xsel", "--clipboard", "--input"]]
+    } else {
+        &[]
+    }
+}
+
+/// Fails when the utility isn't installed or exits unsuccessfully, so OSC 52 takes over.
+fn copy_with(program: &str, args: &[&str], text: &str) -> io::Result<()> {
+    let mut child = Command::new(program).args(args).stdin(Stdio::piped()).stdout(Stdio::null()).stderr(Stdio::null()).spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+    match child.wait()? {
+        status if status.success() => Ok(()),
+        status => Err(io::Error::other(format!("{} exited with {}", program, status))),
+    }
+}
+
+/// The OSC 52 sequence, wrapped for a terminal multiplexer between us and the terminal.
+fn osc52(text: &str, env: impl Fn(&str) -> Option<String>) -> String {
+    let sequence = format!("\x1b]52;c;{}\x07", base64(text.as_bytes()));
+    if env("TMUX").is_some() {
+        // tmux passes a DCS on with its escapes doubled
+        format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"))
+    } else if env("STY").is_some() {
+        format!("\x1bP{}\x1b\\", sequence)
+    } else {
+        sequence
+    }
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, byte)| group | u32::from(*byte) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(group >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64("2·3".as_bytes()), "MsK3Mw==");
+    }
+
+    #[test]
+    fn picks_a_route_to_the_clipboard() {
+        let env = |vars: &'static [(&'static str, &'static str)]| move |name: &str| vars.iter().find(|(var, _)| *var == name).map(|(_, value)| value.to_string());
+        assert_eq!(osc52("2", env(&[])), "\x1b]52;c;Mg==\x07");
+        assert_eq!(osc52("2", env(&[("TMUX", "/tmp/tmux-0/default,1,0")])), "\x1bPtmux;\x1b\x1b]52;c;Mg==\x07\x1b\\");
+        assert_eq!(osc52("2", env(&[("STY", "1.pts-0")])), "\x1bP\x1b]52;c;Mg==\x07\x1b\\");
+        if !cfg!(target_os = "macos") {
+            assert_eq!(local_commands(env(&[("WAYLAND_DISPLAY", "wayland-0")])), [["wl-copy"]]);
+            assert!(local_commands(env(&[])).is_empty());
+        }
+        assert!(local_commands(env(&[("DISPLAY", ":0"), ("SSH_CONNECTION", "10.0.0.2 5000 10.0.0.1 22")])).is_empty());
+    }
+}
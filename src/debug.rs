@@ -0,0 +1,38 @@
+//! The F12 overlay, for making bug reports actionable: how long the last frame
+//! took to draw, the last key, the state the key handler branches on, and the
+//! last error with everything that caused it. It stays up while keys go to the
+//! screen underneath, so a key's effect can be watched.
+
+use std::error::Error;
+use std::time::Duration;
+
+#[derive(Default)]
+pub struct DebugInfo {
+    pub frame_time: Duration,
+    pub last_key: Option<String>, // In the automation syntax, e.g. "Alt+m"
+    pub last_error: Vec<String>, // The error first, then its causes; kept after the status clears
+}
+
+impl DebugInfo {
+    pub fn record_error(&mut self, error: &(dyn Error + 'static)) {
+        self.last_error = chain(error);
+        tracing::warn!(error = %self.last_error.join(": "), "error");
+    }
+}
+
+/// An error's message and those of its sources, outermost first.
+pub fn chain(error: &(dyn Error + 'static)) -> Vec<String> {
+    std::iter::successors(Some(error), |&error| error.source()).map(|error| error.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follows_the_source_chain() {
+        let error = anyhow::anyhow!("missing brace").context("themes/mine.json").context("reloading the theme");
+        let error: &(dyn Error + 'static) = error.as_ref();
+        assert_eq!(chain(error), ["reloading the theme", "themes/mine.json", "missing brace"]);
+    }
+}
@@ -0,0 +1,101 @@
+//! Ctrl+O: the errors and warnings of the session, which otherwise go away
+//! with the next key. Each one is kept with when it happened and the input
+//! that caused it, in a ring of the most recent ones.
+
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tuic_core::{CalculatorError, Warning};
+
+const MAX_ERRORS: usize = 50;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoggedError {
+    pub timestamp_ms: u64,
+    pub code: &'static str,
+    pub message: String,
+    pub input: String, // The line being typed or submitted; empty for a key on the stack
+}
+
+impl LoggedError {
+    pub fn is_warning(&self) -> bool {
+        self.code.starts_with('W')
+    }
+
+    /// `HH:MM:SS` in UTC, the date being the session's.
+    pub fn time_label(&self) -> String {
+        tuic_core::backup::utc_label(self.timestamp_ms)[11..].to_string()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ErrorLog {
+    errors: VecDeque<LoggedError>, // Oldest first
+    pub selected: usize,           // Counted from the newest, as the popup lists them
+}
+
+impl ErrorLog {
+    pub fn record(&mut self, error: &CalculatorError, input: &str) {
+        self.push(error.code(), error.to_string(), input);
+    }
+
+    pub fn record_warning(&mut self, warning: &Warning, input: &str) {
+        self.push(warning.code(), warning.to_string(), input);
+    }
+
+    fn push(&mut self, code: &'static str, message: String, input: &str) {
+        let timestamp_ms = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_millis() as u64);
+        if self.errors.len() >= MAX_ERRORS {
+            self.errors.pop_front();
+        }
+        self.errors.push_back(LoggedError { timestamp_ms, code, message, input: input.to_string() });
+    }
+
+    /// Newest first.
+    pub fn iter(&self) -> impl Iterator<Item = &LoggedError> {
+        self.errors.iter().rev()
+    }
+
+    pub fn len(&self) -> usize {
+        self.errors.len()
+    }
+
+    pub fn warnings(&self) -> usize {
+        self.errors.iter().filter(|error| error.is_warning()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn move_selection(&mut self, older: bool) {
+        self.selected = if older { (self.selected + 1).min(self.errors.len().saturating_sub(1)) } else { self.selected.saturating_sub(1) };
+    }
+
+    pub fn selected(&self) -> Option<&LoggedError> {
+        self.iter().nth(self.selected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_newest_errors() {
+        let mut log = ErrorLog::default();
+        for dividend in 0..MAX_ERRORS + 2 {
+            log.record(&CalculatorError::DivisionByZero { dividend: dividend as f64 }, &format!("{} / 0", dividend));
+        }
+        assert_eq!(log.len(), MAX_ERRORS);
+        let newest = log.selected().unwrap();
+        assert_eq!((newest.code, newest.input.as_str()), ("E201", "51 / 0"));
+        assert_eq!(newest.time_label().len(), "12:34:56".len());
+        log.move_selection(true);
+        assert_eq!(log.selected().unwrap().input, "50 / 0");
+        for _ in 0..MAX_ERRORS {
+            log.move_selection(true);
+        }
+        assert_eq!(log.selected().unwrap().input, "2 / 0");
+    }
+}
@@ -0,0 +1,73 @@
+//! Real plots beyond the F10 chart: the stack, or the last iterate(...)
+//! sequence, is written to a CSV in the temp directory next to a short gnuplot
+//! script, and the `gnuplot-command` template is run on them. The command runs
+//! in the background with its output discarded, so the UI keeps the terminal.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// `{script}` and `{data}` are replaced with the paths of the files written.
+pub const DEFAULT_COMMAND: &str = "gnuplot -persist {script}";
+
+/// `n,value` rows, numbered from 1 in the order given.
+pub fn csv(values: &[f64]) -> String {
+    let mut out = String::from("n,value\n");
+    for (i, value) in values.iter().enumerate() {
+        out.push_str(&format!("{},{}\n", i + 1, value));
+    }
+    out
+}
+
+/// Plots `data` as points joined by lines, under `title`.
+pub fn script(data: &Path, title: &str) -> String {
+    format!(
+        "set datafile separator \",\"\nset title \"{}\"\nset xlabel \"n\"\nset key off\nset grid\nplot '{}' using 1:2 skip 1 with linespoints pointtype 7\n",
+        title.replace('"', "'"),
+        data.display()
+    )
+}
+
+/// The template split into a program and its arguments, with the paths filled in.
+pub fn command_line(template: &str, script: &Path, data: &Path) -> Vec<String> {
+    template
+        .split_whitespace()
+        .map(|word| word.replace("{script}", &script.display().to_string()).replace("{data}", &data.display().to_string()))
+        .collect()
+}
+
+/// Writes `name.csv` and `name.gp` to the temp directory and starts the
+/// command; returns the CSV's path.
+pub fn send(values: &[f64], name: &str, title: &str, template: &str) -> io::Result<PathBuf> {
+    let dir = std::env::temp_dir().join(format!("tuic-gnuplot-{}", std::process::id()));
+    fs::create_dir_all(&dir)?;
+    let data = dir.join(format!("{}.csv", name));
+    let script_path = dir.join(format!("{}.gp", name));
+    fs::write(&data, csv(values))?;
+    fs::write(&script_path, script(&data, title))?;
+    let words = command_line(template, &script_path, &data);
+    let Some((program, args)) = words.split_first() else {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "gnuplot-command is empty"));
+    };
+    let mut child = Command::new(program).args(args).stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).spawn().map_err(|e| io::Error::new(e.kind(), format!("{}: {}", program, e)))?;
+    // Reaped in the background, so a plot window left open doesn't hold anything up
+    thread::spawn(move || child.wait());
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_the_data_and_fills_in_the_command() {
+        assert_eq!(csv(&[2.5, -1.0, 1e-3]), "n,value\n1,2.5\n2,-1\n3,0.001\n");
+        let (script_path, data) = (Path::new("/tmp/t/stack.gp"), Path::new("/tmp/t/stack.csv"));
+        let script = script(data, "Stack \"now\"");
+        assert!(script.contains("set title \"Stack 'now'\"") && script.contains("plot '/tmp/t/stack.csv' using 1:2 skip 1"));
+        assert_eq!(command_line(DEFAULT_COMMAND, script_path, data), ["gnuplot", "-persist", "/tmp/t/stack.gp"]);
+        assert_eq!(command_line("sh -c  plot.sh {data}", script_path, data), ["sh", "-c", "plot.sh", "/tmp/t/stack.csv"]);
+    }
+}
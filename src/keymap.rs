@@ -0,0 +1,280 @@
+//! The keybinding table behind the help overlay. Keys are written in the
+//! automation syntax (`Ctrl+e`, `PageUp`), so the tests can press every one.
+
+use tuic_core::CalculatorMode;
+
+use crate::app::App;
+use crate::vi::ViMode;
+
+/// What has the keyboard when help is opened.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Focus {
+    Input,   // Typing a number or expression
+    Stack,   // Browsing the stack with Up/Down
+    Command, // A ':' command line
+    ViNormal,
+    ThemeSelector,
+    ThemeColors,
+    FunctionPlot,
+    StackChart,
+    Rates,
+    Subnet,
+    Inspect,
+    Stopwatch,
+    ErrorLog,
+    Compare,
+    Angles,
+    Limits,
+    Settings,
+    About,
+    QuickRef,
+    Confirm, // A destructive action waiting for y or n
+}
+
+impl Focus {
+    pub fn title(self) -> &'static str {
+        match self {
+            Focus::Input => "Input",
+            Focus::Stack => "Stack",
+            Focus::Command => "Command line",
+            Focus::ViNormal => "Vi normal mode",
+            Focus::ThemeSelector => "Theme selector",
+            Focus::ThemeColors => "Theme colors",
+            Focus::FunctionPlot => "Function plot",
+            Focus::StackChart => "Stack chart",
+            Focus::Rates => "Exchange rates",
+            Focus::Subnet => "Subnet",
+            Focus::Inspect => "Inspect",
+            Focus::Stopwatch => "Stopwatch",
+            Focus::ErrorLog => "Error log",
+            Focus::Compare => "Compare",
+            Focus::Angles => "Angle units",
+            Focus::Limits => "Limits",
+            Focus::Settings => "Settings",
+            Focus::About => "About",
+            Focus::QuickRef => "Quick reference",
+            Focus::Confirm => "Confirmation",
+        }
+    }
+
+    /// Whether the main screen's shortcuts (modes, F-keys) apply too.
+    pub fn is_main_screen(self) -> bool {
+        matches!(self, Focus::Input | Focus::Stack | Focus::ViNormal)
+    }
+}
+
+pub struct Binding {
+    pub keys: &'static [&'static str], // Alternatives; a space separates a key sequence ("d d")
+    pub action: &'static str,
+    pub focus: &'static [Focus],
+}
+
+use Focus::*;
+
+const MAIN: &[Focus] = &[Input, Stack, ViNormal];
+const EDITING: &[Focus] = &[Input, Command];
+
+pub const BINDINGS: &[Binding] = &[
+    // Typing
+    Binding { keys: &["Enter"], action: "RPN: push the number, or duplicate the top. Infix: evaluate", focus: &[Input, Stack] },
+    Binding { keys: &["+", "-", "*", "/", "^"], action: "Push what's typed, then apply the operator to the top two", focus: &[Input, Stack] },
+    Binding { keys: &["Backspace"], action: "Delete the character before the cursor", focus: EDITING },
+    Binding { keys: &["Left", "Right"], action: "Move the cursor", focus: EDITING },
+    Binding { keys: &["Ctrl+Left", "Ctrl+Right"], action: "Move the cursor by word", focus: EDITING },
+    Binding { keys: &["Home", "End"], action: "Move the cursor to the start or end", focus: EDITING },
+    Binding { keys: &["Delete"], action: "Delete under the cursor, or drop the top of the stack when the input is empty", focus: &[Input, Stack] },
+    Binding { keys: &["Tab"], action: "Infix: complete a function or constant name", focus: &[Input] },
+    Binding { keys: &["Ctrl+f"], action: "Apply the suggested fix shown under an error", focus: &[Input] },
+    Binding { keys: &["Ctrl+e"], action: "Open the multi-line expression editor", focus: MAIN },
+    Binding { keys: &["Enter"], action: "Run the command", focus: &[Command] },
+    Binding { keys: &["Esc"], action: "Cancel the command", focus: &[Command] },
+    // Vi normal mode
+    Binding { keys: &["j", "k"], action: "Browse the stack down or up", focus: &[ViNormal] },
+    Binding { keys: &["d d"], action: "Drop the top of the stack", focus: &[ViNormal] },
+    Binding { keys: &["p"], action: "Duplicate the top of the stack", focus: &[ViNormal] },
+    Binding { keys: &["i", "a"], action: "Insert mode (a digit, '(' or ':' also starts it); Esc comes back", focus: &[ViNormal] },
+    // Stack and history
+    Binding { keys: &["Up", "Down"], action: "Browse the stack, or the history or input panel after clicking it", focus: &[Input, Stack] },
+    Binding { keys: &["Home", "End"], action: "With nothing typed: jump to the top or bottom of the stack (or the clicked history)", focus: &[Input, Stack] },
+    Binding { keys: &["Ctrl+Up", "Ctrl+Down"], action: "Move a screenful through the stack or the clicked history", focus: &[Input, Stack] },
+    Binding { keys: &["PageUp", "PageDown"], action: "Browse the history", focus: MAIN },
+    Binding { keys: &["Alt+Up", "Alt+Down"], action: "Bring back earlier input lines, failed ones too", focus: MAIN },
+    Binding { keys: &["Insert"], action: "Swap the top two stack items", focus: MAIN },
+    Binding { keys: &["u"], action: "Undo the last stack change", focus: MAIN },
+    Binding { keys: &["n"], action: "Flip the sign (+/-) of the number being typed, or negate the top of the stack", focus: MAIN },
+    Binding { keys: &["p", "Alt+p"], action: "Push π (Alt+p inserts pi in infix)", focus: MAIN },
+    Binding { keys: &["e", "Alt+e"], action: "Push e (Alt+e in HEX mode; inserts e in infix)", focus: MAIN },
+    Binding { keys: &["x", "Alt+x"], action: "TAX+: add the tax-rate percent to the top of the stack (Alt+x in infix)", focus: MAIN },
+    Binding { keys: &["X", "Alt+X"], action: "TAX-: take the tax back out of a price that includes it", focus: MAIN },
+    Binding { keys: &["g", "Alt+g"], action: "TIP: add the tip-rate percent to the top of the stack (Alt+g in infix)", focus: MAIN },
+    Binding { keys: &["G", "Alt+G"], action: "GT: push the grand total of every result so far (':gt reset' zeroes it)", focus: MAIN },
+    Binding { keys: &["Ctrl+g"], action: "Plot the real values on the stack in gnuplot (gnuplot-command in config.toml)", focus: MAIN },
+    Binding { keys: &["Ctrl+p"], action: "Pin or unpin the browsed history entry", focus: MAIN },
+    Binding { keys: &["Ctrl+r"], action: "Re-run the browsed history entry under the current modes", focus: MAIN },
+    Binding { keys: &["y", "Alt+y"], action: "Insert the selected entry's value at the cursor (Alt+y in infix)", focus: MAIN },
+    Binding { keys: &["Y", "Alt+Y"], action: "Insert the selected entry's expression at the cursor", focus: MAIN },
+    Binding { keys: &["Ctrl+y"], action: "Copy the selected entry's value to the clipboard", focus: MAIN },
+    Binding { keys: &["c", "Alt+c"], action: "Clear the input (Alt+c in HEX mode, where a-f are digits)", focus: MAIN },
+    Binding { keys: &["Ctrl+c"], action: "Clear input, stack and unpinned history, after asking", focus: MAIN },
+    Binding { keys: &["Ctrl+k"], action: "Clear the stack only (u undoes it)", focus: MAIN },
+    Binding { keys: &["Ctrl+w"], action: "Clear the unpinned history only", focus: MAIN },
+    Binding { keys: &["Ctrl+l"], action: "Dismiss the error", focus: MAIN },
+    // Modes and views
+    Binding { keys: &["m"], action: "Toggle RPN/Infix mode", focus: MAIN },
+    Binding { keys: &["F1"], action: "Toggle RAD/DEG angles", focus: MAIN },
+    Binding { keys: &["F2"], action: "Cycle DEC/HEX/BIN", focus: MAIN },
+    Binding { keys: &["F3"], action: "Toggle rectangular/polar complex display", focus: MAIN },
+    Binding { keys: &["F4"], action: "Cycle the HEX/BIN word size", focus: MAIN },
+    Binding { keys: &["Space"], action: "Toggle scientific notation", focus: MAIN },
+    Binding { keys: &["r"], action: "Show raw (fully parenthesized) stack expressions", focus: MAIN },
+    Binding { keys: &["i", "Alt+i"], action: "Inspect the selected entry in every base and notation (Alt+i in vi normal and infix)", focus: MAIN },
+    Binding { keys: &["b", "Alt+b"], action: "Show the selected entry in the other bases, without switching (Alt+b in infix and HEX)", focus: MAIN },
+    Binding { keys: &["v", "Alt+v"], action: "Compare the selected entry (or s2) with the top: difference, ratio, % change (Alt+v in infix)", focus: MAIN },
+    Binding { keys: &["a", "Alt+a"], action: "The selected entry as an angle in radians, degrees, gradians and turns (Alt+a in infix and HEX)", focus: MAIN },
+    Binding { keys: &["z", "Alt+z"], action: "Presentation layout: the top of the stack in block digits, no mode or help panels (Alt+z in infix)", focus: MAIN },
+    Binding { keys: &["l", "Alt+l"], action: "Seven-segment display of the current value across the top (Alt+l in infix)", focus: MAIN },
+    Binding { keys: &["F5"], action: "IEEE-754 inspector for the top of the stack", focus: MAIN },
+    Binding { keys: &["F6"], action: "Plot the last iterate(...) sequence", focus: MAIN },
+    Binding { keys: &["F7"], action: "Side calculator; Enter inserts its result at the cursor", focus: MAIN },
+    Binding { keys: &["F8", "F9"], action: "Convert x, y to r, θ and back", focus: MAIN },
+    Binding { keys: &["F10"], action: "Chart the stack values", focus: MAIN },
+    Binding { keys: &["F11"], action: "Stopwatch and countdown timer", focus: MAIN },
+    Binding { keys: &["Ctrl+o"], action: "Error log: the session's recent errors with their time and input", focus: MAIN },
+    Binding { keys: &["t"], action: "Open the theme selector", focus: MAIN },
+    Binding { keys: &["h"], action: "This help", focus: MAIN },
+    Binding { keys: &["?"], action: "Quick reference card for the current mode", focus: MAIN },
+    Binding { keys: &["q", "Esc"], action: "Quit", focus: &[Input, Stack] },
+    Binding { keys: &["q"], action: "Quit", focus: &[ViNormal, FunctionPlot, StackChart, Rates, Subnet, Inspect, Stopwatch, ErrorLog, Compare, Angles, Limits, Settings, About] },
+    // Popups
+    Binding { keys: &["Up", "Down"], action: "Preview the previous or next theme", focus: &[ThemeSelector] },
+    Binding { keys: &["Enter"], action: "Keep the selected theme", focus: &[ThemeSelector] },
+    Binding { keys: &["i"], action: "List the previewed theme's colors with swatches and hex values", focus: &[ThemeSelector] },
+    Binding { keys: &["Esc", "t"], action: "Close and go back to the previous theme", focus: &[ThemeSelector] },
+    Binding { keys: &["Up", "Down"], action: "Scroll", focus: &[ThemeColors] },
+    Binding { keys: &["Esc", "Enter", "i"], action: "Back to the theme selector", focus: &[ThemeColors] },
+    Binding { keys: &["Left", "Right"], action: "Pan", focus: &[FunctionPlot] },
+    Binding { keys: &["Up", "+"], action: "Zoom in", focus: &[FunctionPlot] },
+    Binding { keys: &["Down", "-"], action: "Zoom out", focus: &[FunctionPlot] },
+    Binding { keys: &["Esc", "Enter"], action: "Close the plot", focus: &[FunctionPlot] },
+    Binding { keys: &["Tab"], action: "Switch between bars and a sparkline", focus: &[StackChart] },
+    Binding { keys: &["g"], action: "Plot the values in gnuplot", focus: &[StackChart] },
+    Binding { keys: &["Esc", "F10"], action: "Close the chart", focus: &[StackChart] },
+    Binding { keys: &["f"], action: "Fetch current rates", focus: &[Rates] },
+    Binding { keys: &["Esc", "Enter"], action: "Close the rates", focus: &[Rates] },
+    Binding { keys: &["u"], action: "Check GitHub for a newer release", focus: &[About] },
+    Binding { keys: &["Esc", "Enter"], action: "Close", focus: &[About] },
+    Binding { keys: &["Esc", "Enter"], action: "Close the subnet details", focus: &[Subnet] },
+    Binding { keys: &["Esc", "Enter", "i"], action: "Close the inspector", focus: &[Inspect] },
+    Binding { keys: &["Space"], action: "Start or pause", focus: &[Stopwatch] },
+    Binding { keys: &["r"], action: "Reset", focus: &[Stopwatch] },
+    Binding { keys: &["Tab"], action: "Switch between stopwatch and countdown", focus: &[Stopwatch] },
+    Binding { keys: &["Up", "Down"], action: "Lengthen or shorten the countdown by 10 s (PageUp/PageDown by a minute)", focus: &[Stopwatch] },
+    Binding { keys: &["Enter"], action: "Push the elapsed seconds onto the stack", focus: &[Stopwatch] },
+    Binding { keys: &["Esc", "F11"], action: "Close; the clock keeps running", focus: &[Stopwatch] },
+    Binding { keys: &["Up", "Down"], action: "Select a newer or older error", focus: &[ErrorLog] },
+    Binding { keys: &["Enter"], action: "Put the selected error's input back in the input line to fix it", focus: &[ErrorLog] },
+    Binding { keys: &["Esc", "Ctrl+o"], action: "Close the error log", focus: &[ErrorLog] },
+    Binding { keys: &["Up", "Down"], action: "Select the difference, ratio or percent change", focus: &[Compare] },
+    Binding { keys: &["Enter"], action: "Push the selected one and close", focus: &[Compare] },
+    Binding { keys: &["Esc", "v"], action: "Close without pushing", focus: &[Compare] },
+    Binding { keys: &["Up", "Down"], action: "Select a unit", focus: &[Angles] },
+    Binding { keys: &["Enter"], action: "Replace the entry with its value in that unit and close", focus: &[Angles] },
+    Binding { keys: &["Esc", "a"], action: "Close without changing the entry", focus: &[Angles] },
+    Binding { keys: &["Up", "Down"], action: "Select the stack or history limit", focus: &[Limits] },
+    Binding { keys: &["Left", "Right"], action: "Lower or raise it, up to unlimited; a lower one drops the oldest entries", focus: &[Limits] },
+    Binding { keys: &["Esc", "Enter"], action: "Close", focus: &[Limits] },
+    Binding { keys: &["Up", "Down"], action: "Select a setting", focus: &[Settings] },
+    Binding { keys: &["Left", "Right"], action: "Step it through its values, applied now and saved to config.toml", focus: &[Settings] },
+    Binding { keys: &["Esc", "Enter"], action: "Close", focus: &[Settings] },
+    Binding { keys: &["Esc", "?"], action: "Close the quick reference", focus: &[QuickRef] },
+    Binding { keys: &["h"], action: "The full help", focus: &[QuickRef] },
+    Binding { keys: &["y"], action: "Yes, go ahead", focus: &[Confirm] },
+    Binding { keys: &["n", "Esc"], action: "No, leave everything as it was", focus: &[Confirm] },
+    Binding { keys: &["?"], action: "This help", focus: &[ThemeSelector, ThemeColors, FunctionPlot, StackChart, Rates, Subnet, Inspect, Stopwatch, ErrorLog, Compare, Angles, Limits, Settings, About] },
+    Binding { keys: &["F12"], action: "Debug overlay: frame time, last key, state and the last error", focus: &[Input, Stack, Command, ViNormal, ThemeSelector, ThemeColors, FunctionPlot, StackChart, Rates, Subnet, Inspect, Stopwatch, ErrorLog, Compare, Angles, Limits, Settings, About, QuickRef, Confirm] },
+];
+
+/// Bindings that apply in `focus`, in table order.
+pub fn bindings_for(focus: Focus) -> impl Iterator<Item = &'static Binding> {
+    BINDINGS.iter().filter(move |binding| binding.focus.contains(&focus))
+}
+
+/// Shown under the bindings when letters don't do what the table says.
+pub fn note(focus: Focus, app: &App) -> Option<&'static str> {
+    match focus {
+        Input if app.calculator.mode == CalculatorMode::Infix => Some("Infix mode types letters into the expression; hold Alt for the letter shortcuts (Alt+m, Alt+h, ...)"),
+        Command => Some("A command line takes every key as text; see the README for the commands"),
+        _ => None,
+    }
+}
+
+impl App {
+    /// Where the keyboard is going, for the help overlay.
+    pub fn focus(&self) -> Focus {
+        if self.calculator.confirmation.is_some() {
+            Confirm
+        } else if self.show_quick_ref {
+            QuickRef
+        } else if self.show_theme_colors {
+            ThemeColors
+        } else if self.show_theme_selector {
+            ThemeSelector
+        } else if self.calculator.function_plot.is_some() {
+            FunctionPlot
+        } else if self.show_stack_chart {
+            StackChart
+        } else if self.show_stopwatch {
+            Stopwatch
+        } else if self.show_error_log {
+            ErrorLog
+        } else if self.calculator.comparison.is_some() {
+            Compare
+        } else if self.calculator.angle_conversion.is_some() {
+            Angles
+        } else if self.calculator.settings_dialog.is_some() {
+            Settings
+        } else if self.calculator.limits_dialog.is_some() {
+            Limits
+        } else if self.calculator.subnet.is_some() {
+            Subnet
+        } else if self.show_inspect {
+            Inspect
+        } else if self.calculator.show_about {
+            About
+        } else if self.calculator.show_rates {
+            Rates
+        } else if self.calculator.is_command_input() {
+            Command
+        } else if self.vi_mode == Some(ViMode::Normal) {
+            ViNormal
+        } else if self.stack_list_state.selected().is_some() && self.calculator.input.is_empty() {
+            Stack
+        } else {
+            Input
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keyspec::parse_key;
+
+    #[test]
+    fn every_key_parses() {
+        for binding in BINDINGS {
+            for key in binding.keys.iter().flat_map(|keys| keys.split(' ')) {
+                assert!(parse_key(key).is_some(), "'{}' for '{}'", key, binding.action);
+            }
+            assert!(!binding.focus.is_empty(), "'{}' applies nowhere", binding.action);
+        }
+    }
+
+    #[test]
+    fn focus_picks_the_bindings() {
+        let keys = |focus| bindings_for(focus).flat_map(|binding| binding.keys.iter().copied()).collect::<Vec<_>>();
+        assert!(keys(ThemeSelector).contains(&"Enter") && !keys(ThemeSelector).contains(&"F1"));
+        assert!(keys(ViNormal).contains(&"d d") && !keys(Input).contains(&"d d"));
+        assert!(keys(Command).contains(&"Backspace") && !keys(Command).contains(&"m"));
+    }
+}
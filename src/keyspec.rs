@@ -0,0 +1,62 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// Parses `Enter`, `F5`, `Ctrl+c`, `Alt+m`, `a`, `Space`, ...
+#[cfg(any(test, feature = "automation"))]
+pub fn parse_key(spec: &str) -> Option<KeyEvent> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut name = spec;
+    while let Some((prefix, rest)) = name.split_once('+').filter(|(_, rest)| !rest.is_empty()) {
+        modifiers |= match prefix.to_ascii_lowercase().as_str() {
+            "ctrl" => KeyModifiers::CONTROL,
+            "alt" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            _ => return None,
+        };
+        name = rest;
+    }
+
+    let code = match name.to_ascii_lowercase().as_str() {
+        "enter" => KeyCode::Enter,
+        "esc" => KeyCode::Esc,
+        "tab" => KeyCode::Tab,
+        "backtab" => KeyCode::BackTab,
+        "backspace" => KeyCode::Backspace,
+        "delete" => KeyCode::Delete,
+        "insert" => KeyCode::Insert,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "space" => KeyCode::Char(' '),
+        f if f.len() > 1 && f.starts_with('f') => KeyCode::F(f[1..].parse().ok()?),
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(ch), None) => KeyCode::Char(ch),
+                _ => return None,
+            }
+        }
+    };
+    Some(KeyEvent::new(code, modifiers))
+}
+
+/// A key in the syntax `parse_key` reads, for logs and the debug overlay.
+pub fn format_key(key: &KeyEvent) -> String {
+    let mut spec = String::new();
+    for (modifier, name) in [(KeyModifiers::CONTROL, "Ctrl+"), (KeyModifiers::ALT, "Alt+"), (KeyModifiers::SHIFT, "Shift+")] {
+        if key.modifiers.contains(modifier) {
+            spec.push_str(name);
+        }
+    }
+    match key.code {
+        KeyCode::Char(' ') => spec.push_str("Space"),
+        KeyCode::Char(ch) => spec.push(ch),
+        KeyCode::F(n) => spec.push_str(&format!("F{}", n)),
+        code => spec.push_str(&format!("{:?}", code)),
+    }
+    spec
+}
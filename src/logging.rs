@@ -0,0 +1,36 @@
+//! Diagnostics for bug reports: with `log-level` set, tracing events go to
+//! `tui-calculator.log` in the config directory, appended to across sessions.
+//! The default is off, and then nothing is written.
+
+use std::fs::{self, OpenOptions};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use anyhow::{Result, anyhow};
+use tracing_subscriber::filter::LevelFilter;
+use tuic_core::config::LogLevel;
+use tuic_core::config_dir;
+
+/// Starts logging at `level`; returns the log file's path, or None when logging is off.
+pub fn init(level: LogLevel) -> Result<Option<PathBuf>> {
+    let filter = match level {
+        LogLevel::Off => return Ok(None),
+        LogLevel::Error => LevelFilter::ERROR,
+        LogLevel::Warn => LevelFilter::WARN,
+        LogLevel::Info => LevelFilter::INFO,
+        LogLevel::Debug => LevelFilter::DEBUG,
+        LogLevel::Trace => LevelFilter::TRACE,
+    };
+    let dir = config_dir()?;
+    fs::create_dir_all(&dir)?;
+    let path = dir.join("tui-calculator.log");
+    let file = OpenOptions::new().create(true).append(true).open(&path)?;
+    tracing_subscriber::fmt()
+        .with_writer(Mutex::new(file))
+        .with_ansi(false)
+        .with_max_level(filter)
+        .try_init()
+        .map_err(|e| anyhow!(e))?;
+    tracing::info!(version = env!("CARGO_PKG_VERSION"), "started");
+    Ok(Some(path))
+}
@@ -1,10 +1,46 @@
-mod calculator;
+mod announce;
+mod app;
+mod batch;
+mod bigtext;
+mod cli;
+mod clipboard;
+mod debug;
+mod error_log;
+mod gnuplot;
+mod keymap;
+#[cfg(feature = "automation")]
+mod automation;
+mod keyspec;
+mod logging;
+mod quickref;
+#[cfg(test)]
+mod snapshots;
+#[cfg(test)]
+mod testing;
+mod rates;
+mod row_cache;
+#[cfg(unix)]
+mod server;
+mod stopwatch;
 mod ui;
+mod text;
 mod theme;
+mod theme_watch;
+mod update;
+mod vi;
 
-use calculator::Calculator;
+use app::{App, Panel};
+use clap::Parser;
+use cli::{Cli, Command};
+use keymap::Focus;
+use vi::ViMode;
+use tuic_core::config::{self, Config};
+use tuic_core::journal;
+use tuic_core::locale::DecimalSeparator;
+use tuic_core::calculator::RateAdjustment;
+use tuic_core::{config_dir, BaseMode, Calculator, CalculatorError, CalculatorMode};
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers},
+    event::{self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
@@ -12,180 +48,988 @@ use ratatui::{
     backend::{Backend, CrosstermBackend},
     Terminal,
 };
-use std::{error::Error, io, io::Write}; // Added io::Write
+use std::{error::Error, io, io::IsTerminal, io::Write}; // Added io::Write
+use std::time::{Duration, Instant};
+
+/// Interval of the animations (caret blink, error flash, clocks) while no key arrives.
+const TICK_RATE: Duration = Duration::from_millis(250);
+
+/// Shortest time between frames. Keys that arrive faster, from a held key or a
+/// slow link catching up, are all applied and then drawn once.
+const FRAME_INTERVAL: Duration = Duration::from_millis(30);
 
 struct TuiGuard;
 
 impl Drop for TuiGuard {
     fn drop(&mut self) {
-        disable_raw_mode().expect("Failed to disable raw mode");
-        execute!(
-            io::stdout(),
-            LeaveAlternateScreen,
-            DisableMouseCapture
-        ).expect("Failed to restore terminal");
-        io::stdout().flush().expect("Failed to flush stdout");
+        restore_terminal();
     }
 }
 
+/// Where the UI is drawn: stdout, or stderr when stdout is captured, as in
+/// `result=$(tui-calculator --print-on-exit top)`.
+pub(crate) fn terminal_output() -> Box<dyn Write> {
+    if io::stdout().is_terminal() { Box::new(io::stdout()) } else { Box::new(io::stderr()) }
+}
+
+/// Leaves raw mode and the alternate screen. Errors are ignored: this also runs
+/// while panicking, where a second panic would abort without any message.
+fn restore_terminal() {
+    let _ = disable_raw_mode();
+    let mut output = terminal_output();
+    let _ = execute!(
+        output,
+        LeaveAlternateScreen,
+        DisableMouseCapture,
+        DisableBracketedPaste,
+        crossterm::cursor::Show
+    );
+    let _ = output.flush();
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
+    // Scripted, headless session: no terminal setup and no history written back
+    #[cfg(feature = "automation")]
+    {
+        let args: Vec<String> = std::env::args().collect();
+        if let Some(index) = args.iter().position(|arg| arg == "--automation") {
+            let path = args.get(index + 1).ok_or("--automation needs a script or FIFO path")?;
+            let mut app = App::new(Calculator::with_saved_history())?;
+            automation::run(path, &mut app)?;
+            return Ok(());
+        }
+    }
+
+    // config.toml, then command-line overrides; bad flags stop here, before the terminal is taken over
+    let (mut config, config_error) = match Config::load() {
+        Ok(config) => (config, None),
+        Err(e) => (Config::default(), Some(e.to_string())),
+    };
+    let cli = Cli::parse();
+    if let Err(e) = config.apply_env(std::env::vars()).and_then(|()| cli.settings.apply(&mut config)) {
+        Cli::fail(e);
+    }
+    let command = cli.subcommand();
+    match command {
+        Some(Command::Completions { shell }) => {
+            cli::completions(shell, &mut io::stdout());
+            return Ok(());
+        }
+        Some(Command::Man) => {
+            cli::man_page(&mut io::stdout())?;
+            return Ok(());
+        }
+        Some(Command::Theme { command: cli::ThemeCommand::Check { files } }) => {
+            let all_ok = cli::check_themes(&files, &mut io::stdout())?;
+            std::process::exit(if all_ok { 0 } else { 1 });
+        }
+        _ => {}
+    }
+    // A log that can't be opened is reported and the calculator runs without one
+    let (log_path, log_error) = match logging::init(config.log_level.unwrap_or(config::LogLevel::Off)) {
+        Ok(path) => (path, None),
+        Err(e) => (None, Some(e)),
+    };
+
+    // Expressions from the arguments or stdin, results to stdout, and no terminal at all
+    if let Some(Command::Eval { format, .. } | Command::Batch { format }) = command {
+        if let Some(error) = config_error {
+            eprintln!("tui-calculator: {}", error);
+        }
+        if let Some(error) = log_error {
+            eprintln!("tui-calculator: no log file: {:#}", error);
+        }
+        config.share_history = None; // A batch run leaves the history alone
+        config.decimal = None; // and reads numbers with a point, like serve
+        let mut calculator = Calculator::new();
+        calculator.apply_config(&config);
+        let all_ok = match &command {
+            Some(Command::Eval { expressions, .. }) => batch::run(&mut calculator, io::Cursor::new(expressions.join("\n")), &mut io::stdout().lock(), format)?,
+            _ => batch::run(&mut calculator, io::stdin().lock(), &mut io::stdout().lock(), format)?,
+        };
+        std::process::exit(if all_ok { 0 } else { 1 });
+    }
+    #[cfg(unix)]
+    let server = match &command {
+        Some(Command::Serve { socket }) => match server::Server::start(socket) {
+            Ok(server) => Some(server),
+            Err(e) => {
+                eprintln!("tui-calculator: serve {}: {}", socket.display(), e);
+                std::process::exit(1);
+            }
+        },
+        _ => None,
+    };
+    #[cfg(not(unix))]
+    if let Some(Command::Serve { .. }) = command {
+        eprintln!("tui-calculator: serve needs Unix domain sockets, which this platform doesn't have");
+        std::process::exit(2);
+    }
+
+    // Restore the terminal before the panic message is printed, or it lands on the
+    // alternate screen and vanishes with it
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        restore_terminal();
+        default_hook(info);
+    }));
+
     // Setup terminal
     let _guard = TuiGuard; // This ensures drop is called on exit
     enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
+    let mut output = terminal_output();
+    execute!(output, EnterAlternateScreen, EnableMouseCapture, EnableBracketedPaste)?;
+    let backend = CrosstermBackend::new(output);
     let mut terminal = Terminal::new(backend)?;
 
     // Create calculator
-    let mut calculator = Calculator::new()?;
+    let mut calculator = Calculator::with_saved_history();
+    calculator.decimal_separator = DecimalSeparator::from_environment(); // Unless the config says otherwise
+    calculator.apply_config(&config);
+    let mut app = match App::new(calculator) {
+        Ok(app) => app,
+        Err(e) => {
+            // Most likely the themes directory is missing; say so instead of vanishing
+            let message = format!("{:#}", e);
+            show_startup_error(&mut terminal, &message)?;
+            drop(_guard);
+            eprintln!("tui-calculator: {}", message);
+            std::process::exit(1);
+        }
+    };
+    app.apply_config(&config);
+    app.config_path = Config::path().ok();
+    app.log_path = log_path;
+    if cli.quick {
+        app.start_quick();
+    } else if config.update_check == Some(true) {
+        app.start_update_check();
+    }
+    if let Some(error) = config_error {
+        tracing::warn!(%error, "config");
+        app.calculator.error = Some(CalculatorError::Config(error));
+    }
+    if let Some(error) = log_error {
+        app.calculator.notice = Some(format!("No log file: {:#}", error));
+    }
+    // A quick calculation has nothing worth recovering, and the recovery prompt would be in its way
+    if !cli.quick
+        && let Ok(slot) = config_dir().and_then(|dir| journal::claim(&dir))
+    {
+        app.start_journal(slot);
+    }
+    app.watch_themes();
+    #[cfg(unix)]
+    {
+        app.server = server;
+    }
 
     // Run the app
-    let res = run_app(&mut terminal, &mut calculator);
+    let res = run_app(&mut terminal, &mut app);
 
     terminal.show_cursor()?; // Keep show_cursor here, as it's specific to the terminal instance
+    drop(_guard); // Back on the normal screen, so the messages below stay visible
 
     if let Err(err) = res {
-        println!("{err:?}");
+        eprintln!("{err}");
+    } else {
+        if let Err(err) = app.finish_journal() {
+            eprintln!("Failed to remove the session journal: {err:?}");
+        }
+        let which = config.print_on_exit.unwrap_or_default();
+        batch::write_stack(&app.calculator, which, config.print_format.unwrap_or_default(), &mut io::stdout().lock())?;
+    }
+
+    if app.print_announcements
+        && let Some(announcer) = &app.announcer
+    {
+        for line in &announcer.lines {
+            println!("{}", line);
+        }
+    }
+
+    app.calculator.sync_shared_history();
+    if let Err(err) = app.calculator.save_history() {
+        eprintln!("Failed to save history: {err:?}");
     }
 
     Ok(())
 }
 
+/// Shows why the calculator couldn't start until a key is pressed.
+fn show_startup_error<B: Backend>(terminal: &mut Terminal<B>, message: &str) -> io::Result<()> {
+    terminal.draw(|f| ui::draw_startup_error(f, message))?;
+    loop {
+        if let Event::Key(_) = event::read()? {
+            return Ok(());
+        }
+    }
+}
+
 fn run_app<B: Backend>(
     terminal: &mut Terminal<B>,
-    calculator: &mut Calculator,
+    app: &mut App,
 ) -> io::Result<()> {
+    let mut last_tick = Instant::now();
+    let mut last_frame: Option<Instant> = None;
     loop {
-        terminal.draw(|f| ui::draw(f, calculator))?;
-
-        if let Event::Key(key) = event::read()? {
-            if calculator.show_help {
-                // Only allow help toggle and exit when help is shown
-                match key.code {
-                    KeyCode::Char('h') | KeyCode::Char('H') | KeyCode::Esc => {
-                        calculator.toggle_help();
-                    }
-                    KeyCode::Char('q') => {
-                        return Ok(());
+        if let Some(text) = app.calculator.clipboard.take() {
+            clipboard::copy(&text)?;
+        }
+        app.poll_rates_fetch();
+        app.poll_update_check();
+        app.poll_theme_changes();
+        app.update_journal();
+        app.needs_redraw |= app.calculator.sync_shared_history();
+        #[cfg(unix)]
+        app.poll_server();
+        // Only when something changed, and no more often than FRAME_INTERVAL
+        if app.needs_redraw && last_frame.is_none_or(|at| at.elapsed() >= FRAME_INTERVAL) {
+            let frame_start = Instant::now();
+            terminal.draw(|f| ui::draw(f, app))?;
+            app.debug.frame_time = frame_start.elapsed();
+            app.needs_redraw = false;
+            last_frame = Some(frame_start);
+        }
+
+        // Wait for input only until the next tick, so animations keep running, or
+        // until a frame held back by FRAME_INTERVAL is due
+        let mut timeout = TICK_RATE.saturating_sub(last_tick.elapsed());
+        if app.needs_redraw
+            && let Some(at) = last_frame
+        {
+            timeout = timeout.min(FRAME_INTERVAL.saturating_sub(at.elapsed()));
+        }
+        if event::poll(timeout)? {
+            let event = event::read()?;
+            if let Event::Resize(..) = event {
+                // Start from a blank screen so nothing drawn at the old size is left behind
+                terminal.autoresize()?;
+                terminal.clear()?;
+                app.needs_redraw = true;
+                continue;
+            }
+            if let Event::Paste(text) = &event {
+                app.paste(text);
+                app.needs_redraw = true;
+                continue;
+            }
+
+            // Other mouse events, movement included, change nothing on screen
+            if let Event::Mouse(MouseEvent { kind: MouseEventKind::Down(MouseButton::Left), column, row, .. }) = event {
+                app.click(column, row);
+                app.needs_redraw = true;
+                continue;
+            }
+
+            if let Event::Key(key) = event {
+                let had_error = app.calculator.error.is_some();
+                if handle_key(app, key) {
+                    // A copy the last key asked for, --quick's result
+                    if let Some(text) = app.calculator.clipboard.take() {
+                        clipboard::copy(&text)?;
                     }
-                    _ => {}
+                    return Ok(());
                 }
-            } else if calculator.show_theme_selector {
-                match key.code {
-                    KeyCode::Up => {
-                        // Navigate up in theme list
-                        let selected = calculator.theme_list_state.selected().unwrap_or(0);
-                        if selected > 0 {
-                            calculator.theme_list_state.select(Some(selected - 1));
-                        } else {
-                            calculator.theme_list_state.select(Some(calculator.available_themes.len() - 1));
-                        }
-                        // Preview theme
-                        if let Some(selected_index) = calculator.theme_list_state.selected() {
-                            let theme_name = calculator.available_themes[selected_index].clone();
-                            calculator.preview_theme(&theme_name);
-                        }
-                    }
-                    KeyCode::Down => {
-                        // Navigate down in theme list
-                        let selected = calculator.theme_list_state.selected().unwrap_or(0);
-                        if selected < calculator.available_themes.len() - 1 {
-                            calculator.theme_list_state.select(Some(selected + 1));
-                        } else {
-                            calculator.theme_list_state.select(Some(0));
-                        }
-                        // Preview theme
-                        if let Some(selected_index) = calculator.theme_list_state.selected() {
-                            let theme_name = calculator.available_themes[selected_index].clone();
-                            calculator.preview_theme(&theme_name);
-                        }
-                    }
-                    KeyCode::Enter => {
-                        // Select theme
-                        if let Some(selected_index) = calculator.theme_list_state.selected() {
-                            let theme_name = calculator.available_themes[selected_index].clone();
-                            if let Err(e) = calculator.set_theme(&theme_name) {
-                                calculator.error = Some(format!("Failed to set theme: {}", e));
-                            }
-                        }
-                        calculator.toggle_theme_selector(); // Close selector after selection
-                    }
-                    KeyCode::Esc | KeyCode::Char('t') | KeyCode::Char('T') => {
-                        calculator.toggle_theme_selector(); // Close selector
-                    }
-                    _ => {}
+                app.on_key(had_error);
+                app.needs_redraw = true;
+            }
+        }
+        if last_tick.elapsed() >= TICK_RATE {
+            app.on_tick();
+            last_tick = Instant::now();
+        }
+    }
+}
+
+/// Applies one key press to the calculator; returns true when the app should quit.
+/// In screen-reader mode, what the key changed is logged.
+/// Every key and new error is logged, and F12 toggles the debug overlay over any screen.
+fn handle_key(app: &mut App, key: KeyEvent) -> bool {
+    let spec = keyspec::format_key(&key);
+    tracing::debug!(key = %spec, "key");
+    app.debug.last_key = Some(spec);
+    if key.code == KeyCode::F(12) {
+        app.show_debug = !app.show_debug;
+        return false;
+    }
+    let before = app.announcer.is_some().then(|| announce::Snapshot::of(&app.calculator));
+    let error_before = app.calculator.error.clone();
+    let input_before = app.calculator.input.clone();
+    let mut quit = apply_key(app, key);
+    app.follow_found();
+    // --quick: the first expression that evaluates is copied, and that's the session
+    if app.layout == config::Layout::Quick
+        && key.code == KeyCode::Enter
+        && !input_before.is_empty()
+        && !input_before.starts_with(':')
+        && app.calculator.error.is_none()
+        && app.calculator.input.is_empty()
+    {
+        app.calculator.copy_selected();
+        quit = true;
+    }
+    // The line Enter submitted, or what's left of it after the key
+    let input = if input_before.is_empty() { &app.calculator.input } else { &input_before };
+    if let Some(error) = &app.calculator.error
+        && app.calculator.error != error_before
+    {
+        app.debug.record_error(error);
+        app.error_log.record(error, input);
+    }
+    if let Some(warning) = &app.calculator.warning {
+        app.error_log.record_warning(warning, input);
+    }
+    if let (Some(before), Some(announcer)) = (before, &mut app.announcer) {
+        announcer.announce(&before, &app.calculator);
+    }
+    quit
+}
+
+fn apply_key(app: &mut App, key: KeyEvent) -> bool {
+    app.calculator.notice = None;
+    app.calculator.warning = None;
+    if app.recovery.is_some() {
+        // Nothing else until the interrupted session is restored or set aside
+        match key.code {
+            KeyCode::Char('y') | KeyCode::Char('Y') | KeyCode::Enter => app.resolve_recovery(true),
+            KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => app.resolve_recovery(false),
+            KeyCode::Char('q') | KeyCode::Char('Q') => return true,
+            _ => {}
+        }
+    } else if app.focus() == Focus::Confirm {
+        // Nothing else until it's answered, so a stray key can't slip past it
+        match key.code {
+            KeyCode::Char('y') | KeyCode::Char('Y') => app.calculator.answer_confirmation(true),
+            KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => app.calculator.answer_confirmation(false),
+            _ => {}
+        }
+    } else if app.show_help {
+        // Only allow help toggle and exit when help is shown
+        match key.code {
+            KeyCode::Char('h') | KeyCode::Char('H') | KeyCode::Esc => {
+                app.toggle_help();
+            }
+            KeyCode::Char('q') => {
+                return true;
+            }
+            _ => {}
+        }
+    } else if app.show_quick_ref {
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('?') => app.show_quick_ref = false,
+            KeyCode::Char('h') => {
+                app.show_quick_ref = false;
+                app.toggle_help();
+            }
+            KeyCode::Char('q') => return true,
+            _ => {}
+        }
+    } else if app.show_float_inspector {
+        match key.code {
+            KeyCode::F(5) | KeyCode::Esc => {
+                app.toggle_float_inspector();
+            }
+            KeyCode::Char('q') => {
+                return true;
+            }
+            _ => {}
+        }
+    } else if app.show_inspect {
+        match key.code {
+            KeyCode::Char('i') | KeyCode::Char('I') | KeyCode::Esc | KeyCode::Enter => {
+                app.toggle_inspect();
+            }
+            KeyCode::Char('?') => {
+                app.toggle_help();
+            }
+            KeyCode::Char('q') => {
+                return true;
+            }
+            _ => {}
+        }
+    } else if app.calculator.table_import.is_some() {
+        match key.code {
+            KeyCode::Left | KeyCode::Up => {
+                if let Some(import) = &mut app.calculator.table_import {
+                    import.move_cursor(false);
                 }
-            } else {
-                match key.code {
-                    KeyCode::Char('q') | KeyCode::Esc => {
-                        return Ok(());
-                    }
-                    KeyCode::Char('h') | KeyCode::Char('H') => {
-                        calculator.toggle_help();
-                    }
-                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        calculator.clear_all();
-                    }
-                    KeyCode::Char('c') | KeyCode::Char('C') => {
-                        calculator.clear_input();
-                    }
-                    KeyCode::Enter => {
-                        calculator.enter();
-                    }
-                    KeyCode::Backspace => {
-                        calculator.backspace();
-                    }
-                    // Stack operations
-                    KeyCode::Delete => {
-                        calculator.drop();
-                    }
-                    KeyCode::Insert => {
-                        calculator.swap();
-                    }
-                    KeyCode::Char('n') | KeyCode::Char('N') => {
-                        calculator.negate();
-                    }
-                    // Mode switching (using F-function keys)
-                    KeyCode::F(1) => {
-                        calculator.toggle_angle_mode();
-                    }
-                    KeyCode::F(2) => {
-                        calculator.cycle_base_mode();
-                    }
-                    KeyCode::F(3) => {
-                        calculator.toggle_complex_mode();
-                    }
-                    KeyCode::Char('m') | KeyCode::Char('M') => { // Toggle RPN/Infix mode
-                        calculator.toggle_mode();
-                    }
-                    KeyCode::Char('t') | KeyCode::Char('T') => { // Toggle theme selector
-                        calculator.toggle_theme_selector();
-                    }
-                    KeyCode::Char(' ') => {
-                        calculator.toggle_abbreviation();
-                    }
-                    // Stack browsing
-                    KeyCode::Up => {
-                        calculator.browse_stack_up();
-                    }
-                    KeyCode::Down => {
-                        calculator.browse_stack_down();
-                    }
-                    // History browsing
-                    KeyCode::PageUp => {
-                        calculator.browse_history_up();
-                    }
-                    KeyCode::PageDown => {
-                        calculator.browse_history_down();
-                    }
-                    // All character input (numbers and operators)
-                    KeyCode::Char(ch) => {
-                        calculator.handle_char_input(ch);
+            }
+            KeyCode::Right | KeyCode::Down => {
+                if let Some(import) = &mut app.calculator.table_import {
+                    import.move_cursor(true);
+                }
+            }
+            KeyCode::Char(' ') => {
+                if let Some(import) = &mut app.calculator.table_import {
+                    import.toggle_column();
+                }
+            }
+            KeyCode::Char('s') | KeyCode::Char('S') => {
+                if let Some(import) = &mut app.calculator.table_import {
+                    import.toggle_summary();
+                }
+            }
+            KeyCode::Enter => {
+                app.calculator.import_table();
+            }
+            KeyCode::Esc => {
+                app.calculator.cancel_table_import();
+            }
+            _ => {}
+        }
+    } else if app.show_iteration_plot {
+        match key.code {
+            KeyCode::F(6) | KeyCode::Esc => {
+                app.toggle_iteration_plot();
+            }
+            KeyCode::Char('g') => {
+                app.send_to_gnuplot();
+            }
+            KeyCode::Char('q') => {
+                return true;
+            }
+            _ => {}
+        }
+    } else if app.show_stack_chart {
+        match key.code {
+            KeyCode::Tab => {
+                app.stack_chart_sparkline = !app.stack_chart_sparkline;
+            }
+            KeyCode::F(10) | KeyCode::Esc => {
+                app.toggle_stack_chart();
+            }
+            KeyCode::Char('g') => {
+                app.send_to_gnuplot();
+            }
+            KeyCode::Char('?') => {
+                app.toggle_help();
+            }
+            KeyCode::Char('q') => {
+                return true;
+            }
+            _ => {}
+        }
+    } else if app.show_stopwatch {
+        let now = Instant::now();
+        match key.code {
+            KeyCode::Char(' ') => app.stopwatch.toggle(now),
+            KeyCode::Char('r') | KeyCode::Char('R') => app.stopwatch.reset(),
+            KeyCode::Tab => app.stopwatch.switch_mode(),
+            KeyCode::Up => app.stopwatch.adjust_countdown(true, stopwatch::COUNTDOWN_STEP),
+            KeyCode::Down => app.stopwatch.adjust_countdown(false, stopwatch::COUNTDOWN_STEP),
+            KeyCode::PageUp => app.stopwatch.adjust_countdown(true, Duration::from_secs(60)),
+            KeyCode::PageDown => app.stopwatch.adjust_countdown(false, Duration::from_secs(60)),
+            KeyCode::Enter => app.push_stopwatch(now),
+            KeyCode::F(11) | KeyCode::Esc => app.toggle_stopwatch(),
+            KeyCode::Char('?') => app.toggle_help(),
+            KeyCode::Char('q') => return true,
+            _ => {}
+        }
+    } else if app.show_error_log {
+        match key.code {
+            KeyCode::Up | KeyCode::Down => app.error_log.move_selection(key.code == KeyCode::Down),
+            KeyCode::Enter => app.reuse_logged_input(),
+            KeyCode::Char('o') if key.modifiers.contains(KeyModifiers::CONTROL) => app.toggle_error_log(),
+            KeyCode::Esc => app.toggle_error_log(),
+            KeyCode::Char('?') => app.toggle_help(),
+            KeyCode::Char('q') => return true,
+            _ => {}
+        }
+    } else if let Some(comparison) = &mut app.calculator.comparison {
+        match key.code {
+            KeyCode::Up | KeyCode::Down => comparison.move_selection(key.code == KeyCode::Down),
+            KeyCode::Enter => app.calculator.push_comparison(),
+            KeyCode::Esc | KeyCode::Char('v') => app.calculator.close_comparison(),
+            KeyCode::Char('?') => app.toggle_help(),
+            KeyCode::Char('q') => return true,
+            _ => {}
+        }
+    } else if let Some(conversion) = &mut app.calculator.angle_conversion {
+        match key.code {
+            KeyCode::Up | KeyCode::Down => conversion.move_selection(key.code == KeyCode::Down),
+            KeyCode::Enter => app.calculator.replace_with_angle_conversion(),
+            KeyCode::Esc | KeyCode::Char('a') => app.calculator.close_angle_conversion(),
+            KeyCode::Char('?') => app.toggle_help(),
+            KeyCode::Char('q') => return true,
+            _ => {}
+        }
+    } else if let Some(dialog) = &mut app.calculator.settings_dialog {
+        match key.code {
+            KeyCode::Up | KeyCode::Down => dialog.move_selection(key.code == KeyCode::Down),
+            KeyCode::Left | KeyCode::Right => app.step_selected_setting(key.code == KeyCode::Right),
+            KeyCode::Esc | KeyCode::Enter => app.calculator.settings_dialog = None,
+            KeyCode::Char('?') => app.toggle_help(),
+            KeyCode::Char('q') => return true,
+            _ => {}
+        }
+    } else if let Some(dialog) = &mut app.calculator.limits_dialog {
+        match key.code {
+            KeyCode::Up | KeyCode::Down => dialog.move_selection(key.code == KeyCode::Down),
+            KeyCode::Left | KeyCode::Right => app.calculator.step_selected_limit(key.code == KeyCode::Right),
+            KeyCode::Esc | KeyCode::Enter => app.calculator.close_limits(),
+            KeyCode::Char('?') => app.toggle_help(),
+            KeyCode::Char('q') => return true,
+            _ => {}
+        }
+    } else if app.calculator.subnet.is_some() {
+        match key.code {
+            KeyCode::Esc | KeyCode::Enter => {
+                app.calculator.close_subnet();
+            }
+            KeyCode::Char('?') => {
+                app.toggle_help();
+            }
+            KeyCode::Char('q') => {
+                return true;
+            }
+            _ => {}
+        }
+    } else if app.calculator.show_about {
+        match key.code {
+            KeyCode::Char('u') => {
+                app.start_update_check();
+            }
+            KeyCode::Esc | KeyCode::Enter => {
+                app.calculator.show_about = false;
+            }
+            KeyCode::Char('?') => {
+                app.toggle_help();
+            }
+            KeyCode::Char('q') => {
+                return true;
+            }
+            _ => {}
+        }
+    } else if app.calculator.show_rates {
+        match key.code {
+            KeyCode::Char('f') => {
+                app.calculator.request_rates_fetch();
+            }
+            KeyCode::Esc | KeyCode::Enter => {
+                app.calculator.show_rates = false;
+            }
+            KeyCode::Char('?') => {
+                app.toggle_help();
+            }
+            KeyCode::Char('q') => {
+                return true;
+            }
+            _ => {}
+        }
+    } else if app.calculator.usage_stats.is_some() {
+        match key.code {
+            KeyCode::Esc | KeyCode::Enter => {
+                app.calculator.close_usage_stats();
+            }
+            KeyCode::Char('q') => {
+                return true;
+            }
+            _ => {}
+        }
+    } else if app.calculator.result_stats.is_some() {
+        match key.code {
+            KeyCode::Esc | KeyCode::Enter => {
+                app.calculator.close_result_stats();
+            }
+            KeyCode::Char('q') => {
+                return true;
+            }
+            _ => {}
+        }
+    } else if app.calculator.backup_picker.is_some() {
+        match key.code {
+            KeyCode::Up | KeyCode::Down => {
+                if let Some(picker) = &mut app.calculator.backup_picker {
+                    picker.move_selection(key.code == KeyCode::Down);
+                }
+            }
+            KeyCode::Enter => {
+                app.calculator.restore_selected_backup();
+            }
+            KeyCode::Esc => {
+                app.calculator.close_backup_picker();
+            }
+            _ => {}
+        }
+    } else if let Some(plot) = &mut app.calculator.function_plot {
+        match key.code {
+            KeyCode::Left => plot.pan(-0.1),
+            KeyCode::Right => plot.pan(0.1),
+            KeyCode::Up | KeyCode::Char('+') => plot.zoom(0.8),
+            KeyCode::Down | KeyCode::Char('-') => plot.zoom(1.25),
+            KeyCode::Esc | KeyCode::Enter => {
+                app.calculator.close_plot();
+            }
+            KeyCode::Char('?') => {
+                app.toggle_help();
+            }
+            KeyCode::Char('q') => {
+                return true;
+            }
+            _ => {}
+        }
+    } else if app.calculator.editor.is_some() {
+        // Ctrl+Enter only reaches us on terminals that report it, so Ctrl+S works too
+        if key.modifiers.contains(KeyModifiers::CONTROL) && matches!(key.code, KeyCode::Enter | KeyCode::Char('s')) {
+            app.calculator.submit_editor();
+            return false;
+        }
+        // Editing replaces the last attempt's error with the live result
+        app.calculator.error = None;
+        let Some(editor) = &mut app.calculator.editor else {
+            return false;
+        };
+        match key.code {
+            KeyCode::Esc => {
+                app.calculator.close_editor();
+            }
+            KeyCode::Enter => editor.insert_newline(),
+            KeyCode::Backspace => editor.backspace(),
+            KeyCode::Left => editor.move_left(),
+            KeyCode::Right => editor.move_right(),
+            KeyCode::Up => editor.move_up(),
+            KeyCode::Down => editor.move_down(),
+            KeyCode::Home => editor.move_home(),
+            KeyCode::End => editor.move_end(),
+            KeyCode::Char(ch) if !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+                editor.insert_char(ch);
+            }
+            _ => {}
+        }
+    } else if let Some(mini_input) = &mut app.calculator.mini_input {
+        match key.code {
+            KeyCode::Enter => {
+                app.calculator.insert_mini_result();
+            }
+            KeyCode::Esc | KeyCode::F(7) => {
+                app.calculator.close_mini_calculator();
+            }
+            KeyCode::Backspace => {
+                mini_input.pop();
+            }
+            KeyCode::Char(ch) if !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+                mini_input.push(ch);
+            }
+            _ => {}
+        }
+    } else if app.calculator.completion.is_some() {
+        match key.code {
+            KeyCode::Down | KeyCode::Tab => {
+                if let Some(completion) = &mut app.calculator.completion {
+                    completion.move_selection(true);
+                }
+            }
+            KeyCode::Up | KeyCode::BackTab => {
+                if let Some(completion) = &mut app.calculator.completion {
+                    completion.move_selection(false);
+                }
+            }
+            KeyCode::Enter => {
+                app.calculator.accept_completion();
+            }
+            KeyCode::Esc => {
+                app.calculator.cancel_completion();
+            }
+            // Typing keeps narrowing the list
+            KeyCode::Char(ch) if !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+                app.calculator.handle_char_input(ch);
+                app.calculator.update_completion();
+            }
+            KeyCode::Backspace => {
+                app.calculator.backspace();
+                app.calculator.update_completion();
+            }
+            _ => {}
+        }
+    } else if app.show_theme_colors {
+        match key.code {
+            KeyCode::Up => {
+                app.theme_colors_scroll = app.theme_colors_scroll.saturating_sub(1);
+            }
+            KeyCode::Down => {
+                app.theme_colors_scroll = (app.theme_colors_scroll + 1).min(app.current_theme.color_fields().len() as u16);
+            }
+            KeyCode::Esc | KeyCode::Enter | KeyCode::Char('i') => {
+                app.show_theme_colors = false; // Back to the selector
+            }
+            KeyCode::Char('?') => {
+                app.toggle_help();
+            }
+            _ => {}
+        }
+    } else if app.show_theme_selector {
+        match key.code {
+            KeyCode::Up | KeyCode::Down => {
+                app.move_theme_selection(key.code == KeyCode::Down);
+            }
+            KeyCode::Char('i') => {
+                app.show_theme_colors = true;
+                app.theme_colors_scroll = 0;
+            }
+            KeyCode::Enter => {
+                // Select theme
+                if let Some(selected_index) = app.theme_list_state.selected() {
+                    let theme_name = app.available_themes[selected_index].clone();
+                    if let Err(e) = app.set_theme(&theme_name) {
+                        app.calculator.error = Some(CalculatorError::Theme(e.to_string()));
                     }
-                    _ => {}
                 }
+                app.toggle_theme_selector(); // Close selector after selection
+            }
+            KeyCode::Esc | KeyCode::Char('t') | KeyCode::Char('T') => {
+                app.cancel_theme_selector(); // Close and undo the preview
+            }
+            KeyCode::Char('?') => {
+                app.toggle_help();
+            }
+            _ => {}
+        }
+    } else {
+        let vi_normal = app.vi_mode == Some(ViMode::Normal);
+        if vi_normal && let Some(quit) = vi::handle_normal_key(app, key) {
+            return quit;
+        }
+        // Command lines, infix expressions and RPN words take letters as text; Alt+<key> still reaches the shortcut
+        let rpn_words = app.calculator.rpn_words && app.calculator.rapid_start.is_none();
+        let text_entry = !vi_normal && (app.calculator.is_command_input() || app.calculator.mode == CalculatorMode::Infix || rpn_words);
+        // In HEX mode a-f are digits too, so clearing the input takes Alt+c there; so are
+        // they after a typed 0x in any mode, and x, b or o after a leading 0
+        let hex_entry = app.calculator.base_mode == BaseMode::Hexadecimal;
+        match key.code {
+            // '?' is in no expression or number, so only a command line takes it as text
+            KeyCode::Char('?') if !app.calculator.is_command_input() && !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+                app.show_quick_ref = true;
+            }
+            KeyCode::Char(ch) if (text_entry || (hex_entry && ch.is_ascii_hexdigit()) || (!vi_normal && app.calculator.continues_number(ch))) && !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+                app.calculator.handle_char_input(ch);
+            }
+            KeyCode::Esc if app.vi_mode == Some(ViMode::Insert) => {
+                vi::leave_insert(app);
+            }
+            KeyCode::Esc if app.calculator.is_command_input() => {
+                app.calculator.clear_input();
+            }
+            KeyCode::Char('q') | KeyCode::Esc => {
+                return true;
+            }
+            KeyCode::Char('h') | KeyCode::Char('H') => {
+                app.toggle_help();
+            }
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                app.calculator.request_clear_all();
+            }
+            KeyCode::Char('k') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                app.calculator.clear_stack();
+            }
+            KeyCode::Char('g') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                app.send_to_gnuplot();
+            }
+            KeyCode::Char('o') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                app.toggle_error_log();
+            }
+            KeyCode::Char('w') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                app.calculator.clear_history();
+            }
+            KeyCode::Char('l') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                app.calculator.clear_error();
+            }
+            KeyCode::Char('c') | KeyCode::Char('C') => {
+                app.calculator.clear_input();
+            }
+            KeyCode::Char('f') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                app.calculator.apply_suggestion();
+            }
+            KeyCode::Char('e') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                app.calculator.open_editor();
+            }
+            KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                app.calculator.toggle_pin();
+            }
+            KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                app.calculator.rerun_history();
+            }
+            KeyCode::Enter => {
+                app.calculator.enter();
+            }
+            KeyCode::Tab if app.calculator.mode == CalculatorMode::Infix => {
+                app.calculator.complete();
+            }
+            KeyCode::Backspace => {
+                app.calculator.backspace();
+            }
+            // Cursor movement within the input line
+            KeyCode::Left if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                app.calculator.move_cursor_word_left();
+            }
+            KeyCode::Right if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                app.calculator.move_cursor_word_right();
+            }
+            KeyCode::Left => {
+                app.calculator.move_cursor_left();
+            }
+            KeyCode::Right => {
+                app.calculator.move_cursor_right();
+            }
+            // With nothing typed, or in the clicked history panel, they jump to the first or last entry
+            KeyCode::Home | KeyCode::End if app.calculator.input.is_empty() || app.panel_focus == Panel::History => {
+                app.focused_move(if key.code == KeyCode::End { isize::MAX } else { isize::MIN });
+            }
+            KeyCode::Home => {
+                app.calculator.move_cursor_home();
+            }
+            KeyCode::End => {
+                app.calculator.move_cursor_end();
+            }
+            // Delete edits the input while there is one, otherwise drops from the stack
+            KeyCode::Delete if !app.calculator.input.is_empty() => {
+                app.calculator.delete_forward();
+            }
+            // Stack operations
+            KeyCode::Delete => {
+                app.calculator.drop();
+            }
+            KeyCode::Insert => {
+                app.calculator.swap();
+            }
+            KeyCode::Char('u') | KeyCode::Char('U') => {
+                app.calculator.undo();
+            }
+            KeyCode::Char('n') | KeyCode::Char('N') => {
+                app.calculator.negate();
+            }
+            // Mode switching (using F-function keys)
+            KeyCode::F(1) => {
+                app.calculator.toggle_angle_mode();
+            }
+            KeyCode::F(2) => {
+                app.calculator.cycle_base_mode();
+            }
+            KeyCode::F(3) => {
+                app.calculator.toggle_complex_mode();
+            }
+            KeyCode::F(4) => {
+                app.calculator.cycle_word_size();
+            }
+            KeyCode::F(5) => {
+                app.toggle_float_inspector();
+            }
+            KeyCode::F(6) => {
+                app.toggle_iteration_plot();
+            }
+            KeyCode::F(7) => {
+                app.calculator.open_mini_calculator();
+            }
+            KeyCode::F(8) => {
+                app.calculator.convert_to_polar();
+            }
+            KeyCode::F(9) => {
+                app.calculator.convert_to_rectangular();
+            }
+            KeyCode::F(10) => {
+                app.toggle_stack_chart();
+            }
+            KeyCode::F(11) => {
+                app.toggle_stopwatch();
+            }
+            KeyCode::Char('m') | KeyCode::Char('M') => { // Toggle RPN/Infix mode
+                app.calculator.toggle_mode();
+            }
+            KeyCode::Char('t') | KeyCode::Char('T') => { // Toggle theme selector
+                app.toggle_theme_selector();
+            }
+            KeyCode::Char(' ') if app.calculator.rapid_start.is_some() && app.calculator.mode == CalculatorMode::RPN => {
+                app.calculator.enter();
+            }
+            KeyCode::Char(' ') => {
+                app.calculator.toggle_abbreviation();
+            }
+            KeyCode::Char('r') | KeyCode::Char('R') => {
+                app.calculator.toggle_raw_expressions();
+            }
+            KeyCode::Char('i') | KeyCode::Char('I') => {
+                app.toggle_inspect();
+            }
+            // A look at the selected entry in the other bases, without switching (Alt+b in infix and HEX)
+            KeyCode::Char('b') => {
+                app.calculator.peek_bases();
+            }
+            // Before and after: the selected entry against the top (Alt+v in infix)
+            KeyCode::Char('v') => {
+                app.calculator.open_comparison();
+            }
+            // The selected entry in every angle unit (Alt+a in infix and HEX)
+            KeyCode::Char('a') => {
+                app.calculator.open_angle_conversion();
+            }
+            KeyCode::Char('z') | KeyCode::Char('Z') => {
+                app.toggle_presentation();
+            }
+            KeyCode::Char('l') | KeyCode::Char('L') => {
+                app.toggle_display();
+            }
+            // Desk-calculator rate keys, with the rates from the config
+            KeyCode::Char('x') => {
+                app.calculator.apply_rate(RateAdjustment::AddTax);
+            }
+            KeyCode::Char('X') => {
+                app.calculator.apply_rate(RateAdjustment::RemoveTax);
+            }
+            KeyCode::Char('g') => {
+                app.calculator.apply_rate(RateAdjustment::AddTip);
+            }
+            KeyCode::Char('G') => {
+                app.calculator.recall_grand_total();
+            }
+            // Constants in RPN; infix takes p and e as text, so pi and e are typed there
+            KeyCode::Char('p') => {
+                app.calculator.push_constant("pi");
+            }
+            KeyCode::Char('e') => {
+                app.calculator.push_constant("e");
+            }
+            // Build on an earlier result: y inserts the selected value, Y its expression
+            KeyCode::Char('y') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                app.calculator.copy_selected();
+            }
+            KeyCode::Char('y') => {
+                app.calculator.yank_selected(false);
+            }
+            KeyCode::Char('Y') => {
+                app.calculator.yank_selected(true);
+            }
+            // Lines typed earlier, failed ones too, apart from the history panel
+            KeyCode::Up if key.modifiers.contains(KeyModifiers::ALT) => {
+                app.calculator.recall_older_input();
+            }
+            KeyCode::Down if key.modifiers.contains(KeyModifiers::ALT) => {
+                app.calculator.recall_newer_input();
+            }
+            // Stack browsing, or whichever panel was clicked; a held key speeds up
+            KeyCode::Up | KeyCode::Down if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                app.focused_page(key.code == KeyCode::Down);
+            }
+            KeyCode::Up => {
+                let step = app.navigation_step(key) as isize;
+                app.focused_move(-step);
+            }
+            KeyCode::Down => {
+                let step = app.navigation_step(key) as isize;
+                app.focused_move(step);
+            }
+            // History browsing
+            KeyCode::PageUp => {
+                app.browse_history_by(-1);
+            }
+            KeyCode::PageDown => {
+                app.browse_history_by(1);
+            }
+            // All character input (numbers and operators)
+            KeyCode::Char(ch) => {
+                app.calculator.handle_char_input(ch);
             }
+            _ => {}
         }
     }
+    false
 }
@@ -0,0 +1,99 @@
+//! Quick reference card ('?'): the keys and functions of the mode the
+//! calculator is in, generated from the key binding and function tables so it
+//! says what the keys really do. In infix letters are typed, so their Alt
+//! variants are listed; in HEX a-f are digits, likewise.
+
+use tuic_core::functions::{CONSTANTS, FUNCTIONS, SERIES, SPECIAL_FORMS, VARIADIC, VECTOR};
+use tuic_core::{BaseMode, Calculator, CalculatorMode};
+
+use crate::keymap::{self, Focus};
+
+pub struct Section {
+    pub title: &'static str,
+    pub keys: Vec<(String, &'static str)>, // Keys, what they do
+    pub words: Vec<String>, // Functions and the like, listed run on
+}
+
+/// The card's title and sections for the calculator's current modes.
+pub fn card(calculator: &Calculator) -> (&'static str, Vec<Section>) {
+    let infix = calculator.mode == CalculatorMode::Infix;
+    let hex = calculator.base_mode == BaseMode::Hexadecimal;
+    let programmer = calculator.base_mode != BaseMode::Decimal;
+    let mut keys = Vec::new();
+    let mut base_keys = Vec::new();
+    // Cursor movement and the like work in a command line too, and are left to the full help
+    for binding in keymap::bindings_for(Focus::Input).filter(|binding| !binding.focus.contains(&Focus::Command)) {
+        let Some(keys_here) = usable_keys(binding.keys, infix, hex) else {
+            continue;
+        };
+        if !infix && binding.action.starts_with("Infix:") {
+            continue;
+        }
+        let row = (keys_here, short(binding.action));
+        // The programmer card puts the base and word size keys first
+        if programmer && ["HEX", "BIN", "word size"].iter().any(|word| row.1.contains(word)) {
+            base_keys.push(row);
+        } else {
+            keys.push(row);
+        }
+    }
+
+    let mut sections = Vec::new();
+    if programmer {
+        let digits = if hex { "0-9 and a-f are digits" } else { "0 and 1 are digits" };
+        sections.push(Section { title: "Bases", keys: base_keys, words: vec![digits.to_string()] });
+    }
+    sections.push(Section { title: "Keys", keys, words: Vec::new() });
+    let words = if infix {
+        let usages = FUNCTIONS.iter().map(|function| function.usage).chain([SPECIAL_FORMS, SERIES, VARIADIC, VECTOR].into_iter().flatten().map(|(_, usage)| *usage));
+        usages.map(str::to_string).chain(CONSTANTS.iter().map(|(name, _)| name.to_string())).collect()
+    } else {
+        FUNCTIONS.iter().map(|function| function.name).chain(VECTOR.iter().map(|(name, _)| *name)).map(|name| format!(":{}", name)).collect()
+    };
+    sections.push(Section { title: if infix { "Functions and constants" } else { "Functions of the stack" }, keys: Vec::new(), words });
+
+    let title = match (programmer, infix) {
+        (true, _) => "Programmer quick reference",
+        (false, true) => "Infix quick reference",
+        (false, false) => "RPN quick reference",
+    };
+    (title, sections)
+}
+
+/// The keys of a binding that work in these modes, joined; None when none do.
+fn usable_keys(keys: &[&str], infix: bool, hex: bool) -> Option<String> {
+    let typed = |key: &str| {
+        let mut chars = key.chars();
+        chars.next().is_some_and(|ch| chars.next().is_none() && (infix || (hex && ch.is_ascii_hexdigit())))
+    };
+    let usable: Vec<&str> = keys.iter().copied().filter(|key| !typed(key)).collect();
+    // A plain letter that works makes its Alt variant redundant
+    let plain_letter = usable.iter().any(|key| key.chars().count() == 1);
+    let usable: Vec<String> = usable
+        .into_iter()
+        .filter(|key| !(plain_letter && key.strip_prefix("Alt+").is_some_and(|rest| rest.chars().count() == 1)))
+        .map(|key| key.replace(' ', ""))
+        .collect();
+    (!usable.is_empty()).then(|| usable.join(" "))
+}
+
+/// The action up to its first aside, which is about the other modes.
+fn short(action: &'static str) -> &'static str {
+    action.split(" (").next().unwrap_or(action)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_for_each_mode() {
+        assert_eq!(usable_keys(&["p", "Alt+p"], false, false).as_deref(), Some("p"));
+        assert_eq!(usable_keys(&["+", "-", "*"], false, false).as_deref(), Some("+ - *"));
+        assert_eq!(usable_keys(&["p", "Alt+p"], true, false).as_deref(), Some("Alt+p"));
+        assert_eq!(usable_keys(&["e", "Alt+e"], false, true).as_deref(), Some("Alt+e"));
+        assert_eq!(usable_keys(&["+", "-"], true, false), None);
+        assert_eq!(usable_keys(&["d d"], false, false).as_deref(), Some("dd"));
+        assert_eq!(short("Push π (Alt+p inserts pi in infix)"), "Push π");
+    }
+}
@@ -0,0 +1,21 @@
+//! Background download of exchange rates, so the UI keeps running meanwhile.
+
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+use tuic_core::currency::RATES_URL;
+
+/// Starts the request on its own thread; the receiver yields the body or why it failed.
+pub fn spawn_fetch() -> Receiver<Result<String, String>> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = sender.send(fetch().map_err(|e| e.to_string()));
+    });
+    receiver
+}
+
+fn fetch() -> Result<String, ureq::Error> {
+    let agent: ureq::Agent = ureq::Agent::config_builder().timeout_global(Some(Duration::from_secs(10))).build().into();
+    agent.get(RATES_URL).call()?.body_mut().read_to_string()
+}
@@ -0,0 +1,56 @@
+//! Formatted stack and history rows kept between frames. The screen is redrawn
+//! every tick (for the caret), so rows are only formatted when they are new,
+//! their entry changed, or the display settings (base, precision, ...) did.
+
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use tuic_core::calculator::{Calculator, DisplaySettings, HistoryEntry, StackEntry, StackValue};
+
+/// Rows kept before starting over, so scrolling through a huge history can't grow it without bound.
+const MAX_ROWS: usize = 4096;
+
+#[derive(Default)]
+pub struct RowCache {
+    settings: Option<DisplaySettings>,
+    stack: HashMap<u64, (String, String)>, // Expression and result text
+    history: HashMap<u64, String>,
+}
+
+impl RowCache {
+    /// Called before drawing; forgets every row when the display settings changed.
+    pub fn begin_frame(&mut self, settings: DisplaySettings) {
+        if self.settings.as_ref() != Some(&settings) || self.stack.len() + self.history.len() > MAX_ROWS {
+            self.stack.clear();
+            self.history.clear();
+            self.settings = Some(settings);
+        }
+    }
+
+    /// Expression and result text of a stack entry, as displayed.
+    pub fn stack_row(&mut self, calculator: &Calculator, entry: &StackEntry) -> &(String, String) {
+        let key = entry_key(&entry.expression, Some(&entry.result), None);
+        self.stack.entry(key).or_insert_with(|| (calculator.display_expression(entry), calculator.format_stack_row(&entry.result)))
+    }
+
+    /// A history line as displayed.
+    pub fn history_row(&mut self, calculator: &Calculator, entry: &HistoryEntry) -> &str {
+        let key = entry_key(&entry.expression, entry.result.as_ref(), entry.comment.as_deref());
+        self.history.entry(key).or_insert_with(|| calculator.format_history_entry(entry))
+    }
+}
+
+/// Identifies an entry by what it shows: its expression, the exact bits of its result and its comment.
+fn entry_key(expression: &str, result: Option<&StackValue>, comment: Option<&str>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    expression.hash(&mut hasher);
+    comment.hash(&mut hasher);
+    match result {
+        Some(StackValue::Real(value)) => (0u8, value.to_bits()).hash(&mut hasher),
+        Some(StackValue::Complex(c)) => (1u8, c.real.to_bits(), c.imag.to_bits()).hash(&mut hasher),
+        Some(StackValue::Uncertain(m)) => (2u8, m.value.to_bits(), m.sigma.to_bits()).hash(&mut hasher),
+        Some(StackValue::Vector(items)) => (4u8, items.iter().map(|x| x.to_bits()).collect::<Vec<_>>()).hash(&mut hasher),
+        None => 3u8.hash(&mut hasher),
+    }
+    hasher.finish()
+}
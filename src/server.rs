@@ -0,0 +1,117 @@
+//! `serve <socket>`: a Unix socket through which editors and scripts use the
+//! running calculator. One request per line, one reply per line:
+//!
+//!   eval <expr>   evaluate without touching the stack (also a bare expression)
+//!   push <expr>   evaluate and push, as if typed and entered; the TUI shows it
+//!   top           the value on top of the stack
+//!
+//! A reply is `ok <result>` or `error <code> <message>`. Requests are answered
+//! by the UI thread between frames, so they see exactly what the screen shows.
+
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+/// A request line and where its reply goes.
+pub struct Request {
+    pub line: String,
+    pub reply: Sender<String>,
+}
+
+/// The listening socket; its file is removed when the server is dropped.
+pub struct Server {
+    path: PathBuf,
+    pub requests: Receiver<Request>,
+}
+
+impl Server {
+    /// Listens on `path`, replacing a socket file left by an instance that's gone.
+    /// Anything else already at `path` is left alone.
+    pub fn start(path: &Path) -> io::Result<Self> {
+        if let Ok(metadata) = fs::symlink_metadata(path) {
+            if !metadata.file_type().is_socket() {
+                return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} exists and isn't a socket", path.display())));
+            }
+            if UnixStream::connect(path).is_ok() {
+                return Err(io::Error::new(io::ErrorKind::AddrInUse, format!("{} is in use by another instance", path.display())));
+            }
+            fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        let (sender, requests) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let sender = sender.clone();
+                thread::spawn(move || serve_client(stream, sender));
+            }
+        });
+        Ok(Self { path: path.to_path_buf(), requests })
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Passes a client's lines to the UI thread and writes back its replies, until
+/// the client hangs up or the calculator quits.
+fn serve_client(stream: UnixStream, sender: Sender<Request>) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let (reply, answer) = mpsc::channel();
+        if sender.send(Request { line: line?, reply }).is_err() {
+            break;
+        }
+        let Ok(answer) = answer.recv() else {
+            break;
+        };
+        writeln!(writer, "{}", answer)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::App;
+    use tuic_core::Calculator;
+
+    #[test]
+    fn answers_over_the_socket() {
+        let path = std::env::temp_dir().join(format!("tuic-serve-{}.sock", std::process::id()));
+        let server = Server::start(&path).unwrap();
+        assert!(Server::start(&path).is_err(), "a second instance can't take over a live socket");
+
+        let client = UnixStream::connect(&path).unwrap();
+        let mut lines = BufReader::new(client.try_clone().unwrap()).lines();
+        let mut app = App::new(Calculator::new()).unwrap();
+        let mut ask = |request: &str| {
+            writeln!(&client, "{}", request).unwrap();
+            let Request { line, reply } = server.requests.recv().unwrap();
+            reply.send(app.answer(&line)).unwrap();
+            lines.next().unwrap().unwrap()
+        };
+        assert_eq!(ask("2 + 3 * 4"), "ok 14");
+        assert_eq!(ask("push sqrt(16)"), "ok 4");
+        assert_eq!(ask("top"), "ok 4");
+        assert_eq!(ask("eval 1 / 0"), "error E201 Division by zero (1 / 0)");
+        drop(server);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn leaves_other_files_alone() {
+        let path = std::env::temp_dir().join(format!("tuic-serve-{}.txt", std::process::id()));
+        fs::write(&path, "notes").unwrap();
+        let error = Server::start(&path).err().expect("a regular file isn't a stale socket");
+        assert_eq!(error.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "notes");
+        fs::remove_file(&path).unwrap();
+    }
+}
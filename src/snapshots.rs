@@ -0,0 +1,97 @@
+//! Golden-file snapshots of whole screens: each state is drawn on the test backend
+//! and compared, characters and colors, with tests/snapshots/<name>.txt. When a
+//! change to the UI is meant, `UPDATE_SNAPSHOTS=1 cargo test snapshots` rewrites them.
+
+use std::fs;
+use std::path::PathBuf;
+
+use ratatui::buffer::Buffer;
+
+use crate::testing::Driver;
+
+const WIDTH: u16 = 100;
+const HEIGHT: u16 = 32;
+
+/// Letters for the styles of a screen, in the order they first appear.
+const STYLE_MARKS: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+/// The screen's text, then the same grid with a letter per cell naming its style,
+/// then what each letter stands for, so a change of color diffs like one of text.
+fn serialize(buffer: &Buffer) -> String {
+    let area = buffer.area;
+    let mut styles: Vec<String> = Vec::new();
+    let mut text = String::new();
+    let mut marks = String::new();
+    for y in 0..area.height {
+        for x in 0..area.width {
+            let cell = &buffer[(x, y)];
+            text.push_str(cell.symbol());
+            let style = format!("fg={:?} bg={:?} modifier={:?}", cell.fg, cell.bg, cell.modifier);
+            let index = styles.iter().position(|known| *known == style).unwrap_or_else(|| {
+                styles.push(style);
+                styles.len() - 1
+            });
+            marks.push(STYLE_MARKS.chars().nth(index).expect("more styles on one screen than letters for them"));
+        }
+        text.push('\n');
+        marks.push('\n');
+    }
+    let legend: String = styles.iter().zip(STYLE_MARKS.chars()).map(|(style, mark)| format!("{} {}\n", mark, style)).collect();
+    format!("{}--- styles\n{}--- legend\n{}", text, marks, legend)
+}
+
+#[track_caller]
+fn assert_snapshot(name: &str, driver: &mut Driver) {
+    let actual = serialize(&driver.render_buffer(WIDTH, HEIGHT));
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots").join(format!("{}.txt", name));
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}; UPDATE_SNAPSHOTS=1 cargo test snapshots writes it", path.display(), e));
+    // The first line that differs, rather than two whole screens
+    if let Some((line, (expected, actual))) = expected.lines().zip(actual.lines()).enumerate().find(|(_, (expected, actual))| expected != actual) {
+        panic!("{} differs at line {}:\nexpected: {}\n  actual: {}\nUPDATE_SNAPSHOTS=1 cargo test snapshots accepts the change", path.display(), line + 1, expected, actual);
+    }
+    assert_eq!(expected.lines().count(), actual.lines().count(), "{} has a different number of lines", path.display());
+}
+
+#[test]
+fn empty() {
+    assert_snapshot("empty", &mut Driver::new());
+}
+
+#[test]
+fn full_stack() {
+    let mut driver = Driver::new();
+    for n in 1..=20 {
+        driver.type_text(&n.to_string()).keys("Enter");
+    }
+    driver.keys("m").type_text("sqrt(2) * 100").keys("Enter").type_text("s1 / 3").keys("Enter");
+    assert_snapshot("full_stack", &mut driver);
+}
+
+#[test]
+fn error() {
+    let mut driver = Driver::new();
+    driver.keys("1 Enter 0 Enter /");
+    assert!(driver.calculator().error.is_some());
+    assert_snapshot("error", &mut driver);
+}
+
+#[test]
+fn help_open() {
+    let mut driver = Driver::new();
+    driver.keys("h");
+    assert_snapshot("help_open", &mut driver);
+}
+
+#[test]
+fn theme_selector_open() {
+    let mut driver = Driver::new();
+    // In the order the directory lists them, which differs between file systems
+    driver.app.available_themes.sort();
+    driver.keys("t");
+    assert_snapshot("theme_selector_open", &mut driver);
+}
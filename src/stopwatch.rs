@@ -0,0 +1,134 @@
+//! The timer popup (F11): a stopwatch and a countdown timer for timing
+//! experiments. Enter pushes the elapsed seconds, so a rate is one division
+//! away. The clock keeps running while the popup is closed.
+
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimerMode {
+    Stopwatch,
+    Countdown,
+}
+
+pub struct Stopwatch {
+    pub mode: TimerMode,
+    running_since: Option<Instant>,
+    banked: Duration, // Run time before the current start
+    pub countdown: Duration, // Length of the countdown
+}
+
+/// Up/Down change the countdown by this much, PageUp/PageDown by a minute.
+pub const COUNTDOWN_STEP: Duration = Duration::from_secs(10);
+
+impl Default for Stopwatch {
+    fn default() -> Self {
+        Self { mode: TimerMode::Stopwatch, running_since: None, banked: Duration::ZERO, countdown: Duration::from_secs(60) }
+    }
+}
+
+impl Stopwatch {
+    pub fn is_running(&self) -> bool {
+        self.running_since.is_some()
+    }
+
+    /// Run time so far; a countdown stops counting at its length.
+    pub fn elapsed(&self, now: Instant) -> Duration {
+        let elapsed = self.banked + self.running_since.map_or(Duration::ZERO, |since| now.saturating_duration_since(since));
+        match self.mode {
+            TimerMode::Stopwatch => elapsed,
+            TimerMode::Countdown => elapsed.min(self.countdown),
+        }
+    }
+
+    /// What the popup shows: the time run, or for a countdown the time left.
+    pub fn shown(&self, now: Instant) -> Duration {
+        match self.mode {
+            TimerMode::Stopwatch => self.elapsed(now),
+            TimerMode::Countdown => self.countdown - self.elapsed(now),
+        }
+    }
+
+    /// Starts or pauses.
+    pub fn toggle(&mut self, now: Instant) {
+        match self.running_since.take() {
+            Some(since) => self.banked += now.saturating_duration_since(since),
+            None => self.running_since = Some(now),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.running_since = None;
+        self.banked = Duration::ZERO;
+    }
+
+    /// Switches between stopwatch and countdown, starting over.
+    pub fn switch_mode(&mut self) {
+        self.mode = match self.mode {
+            TimerMode::Stopwatch => TimerMode::Countdown,
+            TimerMode::Countdown => TimerMode::Stopwatch,
+        };
+        self.reset();
+    }
+
+    /// Lengthens or shortens the countdown while it isn't running; never below one step.
+    pub fn adjust_countdown(&mut self, longer: bool, step: Duration) {
+        if self.mode == TimerMode::Countdown && !self.is_running() {
+            self.countdown = if longer { self.countdown + step } else { self.countdown.saturating_sub(step).max(COUNTDOWN_STEP) };
+        }
+    }
+
+    /// Stops a countdown that has run out; returns true the first time it does.
+    pub fn check_finished(&mut self, now: Instant) -> bool {
+        let finished = self.mode == TimerMode::Countdown && self.is_running() && self.elapsed(now) >= self.countdown;
+        if finished {
+            self.running_since = None;
+            self.banked = self.countdown;
+        }
+        finished
+    }
+}
+
+/// `mm:ss.t`, or `h:mm:ss.t` from an hour on.
+pub fn format_duration(duration: Duration) -> String {
+    let tenths = duration.as_millis() / 100;
+    let (hours, minutes, seconds) = (tenths / 36_000, tenths / 600 % 60, tenths / 10 % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}.{}", hours, minutes, seconds, tenths % 10)
+    } else {
+        format!("{:02}:{:02}.{}", minutes, seconds, tenths % 10)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_pauses_and_counts_down() {
+        let start = Instant::now();
+        let at = |seconds: u64| start + Duration::from_secs(seconds);
+        let mut stopwatch = Stopwatch::default();
+        stopwatch.toggle(at(0));
+        assert_eq!(stopwatch.elapsed(at(5)), Duration::from_secs(5));
+        stopwatch.toggle(at(5));
+        stopwatch.toggle(at(20)); // The pause doesn't count
+        assert_eq!(stopwatch.elapsed(at(22)), Duration::from_secs(7));
+
+        stopwatch.switch_mode();
+        assert_eq!(stopwatch.elapsed(at(22)), Duration::ZERO);
+        stopwatch.adjust_countdown(false, Duration::from_secs(50));
+        assert_eq!(stopwatch.countdown, Duration::from_secs(10));
+        stopwatch.toggle(at(30));
+        assert_eq!(stopwatch.shown(at(34)), Duration::from_secs(6));
+        assert!(!stopwatch.check_finished(at(34)));
+        assert!(stopwatch.check_finished(at(45)));
+        assert!(!stopwatch.check_finished(at(46)));
+        assert_eq!((stopwatch.shown(at(50)), stopwatch.elapsed(at(50))), (Duration::ZERO, Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(format_duration(Duration::from_millis(65_430)), "01:05.4");
+        assert_eq!(format_duration(Duration::from_secs(3725)), "1:02:05.0");
+    }
+}
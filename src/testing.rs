@@ -0,0 +1,1877 @@
+//! Headless driver for tests: key sequences go through the same `handle_key`
+//! as the terminal, then stack and history are inspected directly.
+
+use std::time::Duration;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::{backend::TestBackend, buffer::Buffer, Terminal};
+use tuic_core::Calculator;
+
+use crate::app::App;
+use crate::handle_key;
+use crate::keyspec::parse_key;
+use crate::theme::Theme;
+use crate::ui;
+
+pub struct Driver {
+    pub app: App,
+    quit: bool,
+}
+
+impl Driver {
+    /// A driver around a fresh calculator: empty stack and history, RPN mode,
+    /// in the built-in default theme whatever theme.txt says.
+    pub fn new() -> Self {
+        let mut app = App::with_theme(Calculator::new(), Theme::builtin()).expect("the themes folder should be readable");
+        app.repeat_window = Duration::ZERO; // Keys come faster than anyone types; only Repeat events count as held
+        Self { app, quit: false }
+    }
+
+    /// Presses whitespace-separated keys in the automation syntax, e.g. `"2 Enter 3 Enter +"`.
+    pub fn keys(&mut self, script: &str) -> &mut Self {
+        for spec in script.split_whitespace() {
+            let key = parse_key(spec).unwrap_or_else(|| panic!("unknown key '{}'", spec));
+            self.press(key);
+        }
+        self
+    }
+
+    /// Presses each character of `text` as a plain key, spaces included.
+    pub fn type_text(&mut self, text: &str) -> &mut Self {
+        for ch in text.chars() {
+            self.press(KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE));
+        }
+        self
+    }
+
+    /// Presses a key and holds it down for `repeats` more, as terminals that mark repeats send it.
+    pub fn hold(&mut self, spec: &str, repeats: usize) -> &mut Self {
+        let key = parse_key(spec).unwrap_or_else(|| panic!("unknown key '{}'", spec));
+        self.press(key);
+        for _ in 0..repeats {
+            self.press(KeyEvent { kind: KeyEventKind::Repeat, ..key });
+        }
+        self
+    }
+
+    /// Pastes `text` as a terminal with bracketed paste would deliver it.
+    pub fn paste(&mut self, text: &str) -> &mut Self {
+        self.app.paste(text);
+        self
+    }
+
+    fn press(&mut self, key: KeyEvent) {
+        assert!(!self.quit, "key {:?} pressed after the app quit", key.code);
+        self.quit = handle_key(&mut self.app, key);
+    }
+
+    pub fn calculator(&self) -> &Calculator {
+        &self.app.calculator
+    }
+
+    pub fn has_quit(&self) -> bool {
+        self.quit
+    }
+
+    /// Draws one frame at the given terminal size and returns its text, row by row.
+    pub fn render(&mut self, width: u16, height: u16) -> String {
+        let buffer = self.render_buffer(width, height);
+        (0..buffer.area.height).map(|y| (0..buffer.area.width).map(|x| buffer[(x, y)].symbol()).collect::<String>() + "\n").collect()
+    }
+
+    /// Draws one frame at the given terminal size, colors and all.
+    pub fn render_buffer(&mut self, width: u16, height: u16) -> Buffer {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).expect("the test backend never fails");
+        terminal.draw(|f| ui::draw(f, &mut self.app)).expect("the test backend never fails");
+        terminal.backend().buffer().clone()
+    }
+
+    /// Stack results as displayed, bottom first.
+    pub fn stack(&self) -> Vec<String> {
+        let calculator = self.calculator();
+        calculator.stack.iter().map(|entry| calculator.format_stack_value(&entry.result)).collect()
+    }
+
+    /// History lines as displayed, oldest first.
+    pub fn history(&self) -> Vec<String> {
+        let calculator = self.calculator();
+        calculator.history.iter().map(|entry| calculator.format_history_entry(entry)).collect()
+    }
+
+    pub fn error_code(&self) -> Option<&'static str> {
+        self.calculator().error.as_ref().map(|error| error.code())
+    }
+
+    #[track_caller]
+    pub fn assert_stack(&self, expected: &[&str]) -> &Self {
+        assert_eq!(self.stack(), expected, "stack");
+        self
+    }
+
+    #[track_caller]
+    pub fn assert_history(&self, expected: &[&str]) -> &Self {
+        assert_eq!(self.history(), expected, "history");
+        self
+    }
+}
+
+mod tests {
+    use tuic_core::{AngleMode, Calculator, CalculatorMode, EntryOrigin, HistoryEntry, InputValidity, StackValue, WordSize, session_label, session_marker};
+    use tuic_core::locale::DecimalSeparator;
+    use tuic_core::config::{Autosave, Config, Layout};
+    use tuic_core::journal;
+
+    use super::Driver;
+    use crate::app::Panel;
+    use crate::keymap::Focus;
+    use crate::vi::ViMode;
+
+    #[test]
+    fn rpn_operations() {
+        let mut driver = Driver::new();
+        driver.keys("2 Enter 3 Enter +").assert_stack(&["5"]);
+        driver.keys("4 *").assert_stack(&["20"]);
+        // One key pushed and applied; the status bar says both, until the next key
+        assert_eq!(driver.calculator().notice.as_deref(), Some("Pushed 4, then *: ((2 + 3) * 4) = 20"));
+        assert!(driver.render(100, 30).contains("Pushed 4, then *"));
+        driver.keys("n").assert_stack(&["-20"]);
+        assert_eq!(driver.calculator().notice, None);
+        driver.keys("1 Enter Insert").assert_stack(&["1", "-20"]);
+        driver.keys("Delete").assert_stack(&["1"]);
+    }
+
+    #[test]
+    fn rpn_history() {
+        let mut driver = Driver::new();
+        driver.keys("6 Enter 7 Enter *").assert_history(&["6", "7", "(6 * 7) = 42"]);
+    }
+
+    #[test]
+    fn characters_follow_the_mode() {
+        let mut driver = Driver::new();
+        // RPN: an operator pushes the number being typed, then applies
+        driver.keys("8 Enter").type_text("2.5*").assert_stack(&["20"]);
+        assert_eq!(driver.calculator().input, "");
+        driver.type_text("w");
+        assert_eq!(driver.error_code(), Some("E107"));
+        driver.type_text(":sqrt x");
+        assert_eq!(driver.calculator().input, ":sqrt x");
+        driver.keys("Esc m").type_text("2.5*x");
+        assert_eq!(driver.calculator().input, "2.5*x");
+        driver.assert_stack(&["20"]);
+    }
+
+    #[test]
+    fn hex_digits() {
+        let mut driver = Driver::new();
+        driver.keys("F2").type_text("ff").keys("Enter").assert_stack(&["0xFF"]);
+        driver.type_text("cafe");
+        assert_eq!(driver.calculator().input, "cafe");
+        driver.keys("Alt+c");
+        assert_eq!(driver.calculator().input, "");
+        driver.type_text("1C").keys("Enter n").assert_stack(&["0xFF", "-0x1C"]);
+    }
+
+    #[test]
+    fn infix_evaluation() {
+        let mut driver = Driver::new();
+        driver.keys("m").type_text("2+3*4").keys("Enter").assert_stack(&["14"]);
+        driver.type_text("2^3^2").keys("Enter").assert_stack(&["14", "512"]);
+        driver.type_text("sqrt(16)+pi*0").keys("Enter").assert_stack(&["14", "512", "4"]);
+        driver.assert_history(&["2+3*4 = 14", "2^3^2 = 512", "sqrt(16)+pi*0 = 4"]);
+    }
+
+    #[test]
+    fn comparisons_and_conditionals() {
+        let mut driver = Driver::new();
+        driver.keys("m").type_text("1 + 2 < 4").keys("Enter").assert_stack(&["1"]);
+        driver.type_text("2 != 2").keys("Enter").assert_stack(&["1", "0"]);
+        driver.type_text("3 >= 3 == 1").keys("Enter").assert_stack(&["1", "0", "1"]);
+        // Only the chosen branch is evaluated, so 1/0 doesn't fail
+        driver.type_text("if(0 <= 0-1, 1/0, 7)").keys("Enter").assert_stack(&["1", "0", "1", "7"]);
+        assert_eq!(driver.calculator().stack[3].ast.pretty(), "if(0 <= 0 - 1, 1 / 0, 7)");
+        driver.type_text("2 = 2").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E101"));
+    }
+
+    #[test]
+    fn infix_errors_keep_the_input() {
+        let mut driver = Driver::new();
+        driver.keys("m").type_text("1/0").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E201"));
+        assert_eq!(driver.calculator().input, "1/0");
+        driver.assert_stack(&[]);
+
+        // Letters are text in infix mode, so clear the input with Backspace
+        driver.keys("Backspace Backspace Backspace").type_text("(1+2").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E103"));
+        driver.keys("Ctrl+f Enter").assert_stack(&["3"]);
+    }
+
+    #[test]
+    fn undo_reverts_whole_actions() {
+        let mut driver = Driver::new();
+        driver.keys("2 Enter 3 Enter +").assert_stack(&["5"]);
+        driver.keys("u").assert_stack(&["2", "3"]);
+        driver.keys("Ctrl+c y").assert_stack(&[]);
+        driver.keys("u").assert_stack(&["2", "3"]);
+    }
+
+    #[test]
+    fn commands() {
+        let mut driver = Driver::new();
+        driver.type_text(":set precision 2").keys("Enter");
+        driver.keys("1 Enter 3 /").assert_stack(&["0.33"]);
+        driver.type_text(":bogus").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E401"));
+    }
+
+    #[test]
+    fn dms_angles() {
+        let mut driver = Driver::new();
+        driver.keys("m").type_text("45°30'+0°30'").keys("Enter").assert_stack(&["46"]);
+        driver.type_text(":set dms on").keys("Enter F1").assert_stack(&["46°0'0\""]);
+        driver.type_text("10.2525").keys("Enter").assert_stack(&["46°0'0\"", "10°15'9\""]);
+        driver.keys("F1").assert_stack(&["46", "10.2525"]);
+    }
+
+    #[test]
+    fn coordinate_conversion() {
+        let mut driver = Driver::new();
+        driver.keys("3 Enter 4 Enter F8").assert_stack(&["5", "0.9272952180016122"]);
+        driver.keys("F9").assert_stack(&["3.0000000000000004", "3.9999999999999996"]);
+        driver.keys("u u").assert_stack(&["3", "4"]);
+        driver.keys("F1 F8").assert_stack(&["5", "53.13010235415598"]);
+        driver.keys("Delete Delete F8");
+        assert_eq!(driver.error_code(), Some("E301"));
+    }
+
+    #[test]
+    fn functions_on_the_stack() {
+        let mut driver = Driver::new();
+        driver.keys("1 2 Enter 1 8 Enter").type_text(":gcd").keys("Enter").assert_stack(&["6"]);
+        driver.assert_history(&["12", "18", "gcd(12, 18) = 6"]);
+        driver.keys("4 Enter").type_text(":lcm").keys("Enter").assert_stack(&["12"]);
+        driver.type_text(":ncr").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E301"));
+        driver.keys("m").type_text("ncr(52, 5) + npr(4, 2)").keys("Enter").assert_stack(&["12", "2598972"]);
+    }
+
+    #[test]
+    fn prime_factors() {
+        let mut driver = Driver::new();
+        driver.keys("3 6 0 Enter").type_text(":factor").keys("Enter").assert_stack(&["2", "2", "2", "3", "3", "5"]);
+        assert_eq!(driver.history().last().map(String::as_str), Some("factor(360) = 2^3 · 3^2 · 5"));
+        driver.keys("u").assert_stack(&["360"]);
+        driver.keys("m").type_text("factor(91)").keys("Enter").assert_stack(&["360", "7", "13"]);
+        driver.type_text("isprime(97) + nextprime(13) + modpow(4, 13, 497)").keys("Enter").assert_stack(&["360", "7", "13", "463"]);
+        driver.type_text("factor(1)").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E205"));
+    }
+
+    #[test]
+    fn equation_solver() {
+        let mut driver = Driver::new();
+        driver.keys("m").type_text("solve(x^2 - 2, 1)").keys("Enter").assert_stack(&["1.414213562373095"]);
+        assert!(driver.calculator().notice.as_deref().is_some_and(|notice| notice.starts_with("x = 1.414213562373095 by Newton")));
+        driver.type_text("solve(x^2 + 1, 0)").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E206"));
+        driver.assert_stack(&["1.414213562373095"]);
+    }
+
+    #[test]
+    fn polynomials() {
+        let mut driver = Driver::new();
+        driver.keys("m").type_text("poly(2, 0-3, 1, 4)").keys("Enter").assert_stack(&["21"]);
+        driver.type_text("roots(1, 0-6, 11, 0-6)").keys("Enter").assert_stack(&["21", "1", "2", "3"]);
+        driver.keys("Ctrl+c y").type_text("roots(1, 0, 0, 0-8)").keys("Enter").assert_stack(&["2"]);
+        assert_eq!(driver.calculator().notice.as_deref(), Some("x = 2, plus 2 complex roots (:set complex on to push them)"));
+        driver.type_text(":set complex on").keys("Enter").type_text("roots(1, 0, 4)").keys("Enter");
+        driver.assert_stack(&["2", "0 + 2i", "0 - 2i"]);
+        driver.type_text("roots(0, 0, 5)").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E205"));
+    }
+
+    #[test]
+    fn complex_results() {
+        let mut driver = Driver::new();
+        driver.keys("m").type_text("sqrt(0-4)").keys("Enter");
+        assert!(driver.calculator().stack[0].result.as_real().unwrap().is_nan());
+        driver.type_text("1 + 2i").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E215"));
+        driver.keys("Alt+c Ctrl+c y").type_text(":set complex on").keys("Enter");
+        driver.type_text("sqrt(0-4)").keys("Enter").type_text("ln(0-1)").keys("Enter").type_text("(0-8)^(1/3)").keys("Enter");
+        driver.assert_stack(&["0 + 2i", "0 + 3.141592653589793i", "1 + 1.732050807568877i"]);
+        driver.type_text("exp(ln(0-1))").keys("Enter").assert_stack(&["0 + 2i", "0 + 3.141592653589793i", "1 + 1.732050807568877i", "-1"]);
+        driver.keys("Ctrl+c y").type_text("(3+4i)*(1-2i)").keys("Enter").type_text("2i^2").keys("Enter").type_text("(1 + i) / (1 - i)").keys("Enter");
+        driver.assert_stack(&["11 - 2i", "-2", "0 + 1i"]);
+
+        // RPN: functions and operators on complex values
+        driver.keys("Ctrl+c y Alt+m").type_text("4").keys("Enter").keys("n").type_text(":sqrt").keys("Enter");
+        driver.keys("Enter *").assert_stack(&["-4"]);
+        driver.type_text("0.5^").assert_stack(&["0 + 2i"]);
+        driver.type_text("0/");
+        assert_eq!(driver.error_code(), Some("E201"));
+    }
+
+    #[test]
+    fn uncertainties() {
+        let mut driver = Driver::new();
+        driver.keys("m").type_text("5 +/- 0.3").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E210"));
+        driver.keys("Ctrl+c y").type_text(":set uncertainty on").keys("Enter");
+        driver.type_text("(5 ± 0.3) * (2 +/- 0.4)").keys("Enter").assert_stack(&["10.0 ± 2.1"]);
+        driver.type_text("sqrt(16 ± 0.8)").keys("Enter").assert_stack(&["10.0 ± 2.1", "4.00 ± 0.10"]);
+
+        // RPN: :pm attaches an uncertainty, and the operators propagate it
+        driver.keys("Ctrl+c y Alt+m").type_text("5").keys("Enter").type_text(":pm 0.3").keys("Enter");
+        driver.type_text("2").keys("Enter").type_text(":pm 0.4").keys("Enter").keys("+").assert_stack(&["7.00 ± 0.50"]);
+        driver.keys("n").assert_stack(&["-7.00 ± 0.50"]);
+        driver.keys("u").type_text("3-").assert_stack(&["4.00 ± 0.50"]);
+        driver.type_text(":sqrt").keys("Enter").assert_stack(&["2.00 ± 0.12"]);
+        driver.type_text("0/");
+        assert_eq!(driver.error_code(), Some("E201"));
+        driver.assert_stack(&["2.00 ± 0.12", "0"]);
+    }
+
+    #[test]
+    fn vectors() {
+        let mut driver = Driver::new();
+        driver.type_text("[1, 2, 3]").keys("Enter").type_text("[4, 5, -6]+").assert_stack(&["[5, 7, -3]"]);
+        driver.type_text("2*").assert_stack(&["[10, 14, -6]"]);
+        driver.type_text("[1, 2]-");
+        assert_eq!(driver.error_code(), Some("E212"));
+        driver.keys("Ctrl+c y").type_text("[3, 4]").keys("Enter").type_text(":norm").keys("Enter").assert_stack(&["5"]);
+        driver.type_text("[7, 8, 9]").keys("Enter").type_text("2").keys("Enter").type_text(":at").keys("Enter").assert_stack(&["5", "8"]);
+
+        driver.keys("Ctrl+c y m").type_text("dot([1, 2, 3], [4, 5, 6])").keys("Enter").assert_stack(&["32"]);
+        driver.type_text("[1, 2, 3][2] * 10").keys("Enter").type_text("sqrt([4, 9]) / 2").keys("Enter").assert_stack(&["32", "20", "[1, 1.5]"]);
+        driver.type_text("s1 + [1, 1]").keys("Enter").assert_stack(&["32", "20", "[1, 1.5]", "[2, 2.5]"]);
+        driver.type_text("[1, 2][3]").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E213"));
+
+        // A long vector is cut short on the stack; inspect lists every element
+        driver.keys("Ctrl+c y").type_text("[1, 2, 3, 4, 5, 6, 7, 8] * 2").keys("Enter").assert_stack(&["[2, 4, 6, 8, 10, 12, 14, 16]"]);
+        assert!(driver.render(100, 40).contains("[2, 4, 6, 8, 10, 12, … +2]"));
+        driver.keys("Alt+i");
+        let screen = driver.render(100, 40);
+        assert!(screen.contains("Length:      8") && screen.contains("[8]          16"));
+    }
+
+    #[test]
+    fn calculus() {
+        let mut driver = Driver::new();
+        driver.type_text(":set precision 6").keys("Enter m");
+        driver.type_text("integrate(sin(x), 0, pi)").keys("Enter").assert_stack(&["2.000000"]);
+        assert!(driver.calculator().notice.as_deref().is_some_and(|notice| notice.starts_with("integrate = 2.000000 ± ")));
+        driver.type_text("derivative(x^3, 2)").keys("Enter").assert_stack(&["2.000000", "12.000000"]);
+        driver.type_text("integrate(1/(x-1), 0, 2)").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E207"));
+        driver.keys("Alt+c").type_text("derivative(x)").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E205"));
+    }
+
+    #[test]
+    fn function_plot() {
+        let mut driver = Driver::new();
+        driver.keys("m").type_text("plot(1/x, 0-2, 2)").keys("Enter");
+        let plot = driver.calculator().function_plot.as_ref().expect("plot opens");
+        assert_eq!((plot.x_min, plot.x_max), (-2.0, 2.0));
+        // x = 0 is skipped rather than failing the whole plot
+        assert_eq!(driver.calculator().plot_points(5), [(-2.0, -0.5), (-1.0, -1.0), (1.0, 1.0), (2.0, 0.5)]);
+
+        driver.keys("Right Up");
+        let plot = driver.calculator().function_plot.as_ref().unwrap();
+        assert!((plot.x_min + 1.2).abs() < 1e-12 && (plot.x_max - 2.0).abs() < 1e-12);
+        driver.keys("Esc").assert_stack(&[]);
+        assert!(driver.calculator().function_plot.is_none());
+        assert!(driver.calculator().input.is_empty());
+    }
+
+    #[test]
+    fn expression_editor() {
+        let mut driver = Driver::new();
+        driver.type_text("2").keys("Ctrl+e").type_text("*").keys("Enter").type_text("(3 + 4)  # seven").keys("Enter");
+        assert_eq!(driver.calculator().editor.as_ref().unwrap().lines, ["2*", "(3 + 4)  # seven", ""]);
+        driver.keys("Ctrl+s").assert_stack(&["14"]).assert_history(&["2* (3 + 4) = 14"]);
+        assert!(driver.calculator().editor.is_none());
+
+        driver.keys("Ctrl+e").type_text("1/0").keys("Ctrl+s");
+        assert_eq!(driver.error_code(), Some("E201"));
+        assert!(driver.calculator().editor.is_some());
+        driver.keys("Backspace Esc").assert_stack(&["14"]);
+        assert!(driver.calculator().editor.is_none());
+        assert_eq!(driver.error_code(), None);
+    }
+
+    #[test]
+    fn selective_clearing() {
+        let mut driver = Driver::new();
+        driver.keys("m").type_text("1+1").keys("Enter").type_text("2+2").keys("Enter").type_text("1/").keys("Enter");
+        assert!(driver.error_code().is_some());
+        driver.keys("Ctrl+l");
+        assert!(driver.error_code().is_none());
+        driver.keys("Ctrl+k").assert_stack(&[]).assert_history(&["1+1 = 2", "2+2 = 4"]);
+        driver.keys("Alt+m u").assert_stack(&["2", "4"]);
+        driver.keys("Ctrl+w").assert_stack(&["2", "4"]).assert_history(&[]);
+
+        // Clearing everything asks first, and no leaves it all as it was
+        driver.type_text("3").keys("Ctrl+c");
+        assert_eq!(driver.calculator().confirmation_prompt().as_deref(), Some("Clear the input, 2 stack entries and 0 unpinned history entries?"));
+        assert!(driver.render(80, 30).contains("Are You Sure?"));
+        driver.keys("q n").assert_stack(&["2", "4"]);
+        assert_eq!(driver.calculator().input, "3");
+        driver.keys("Ctrl+c y").assert_stack(&[]);
+        assert!(driver.calculator().input.is_empty());
+
+        driver.type_text("5").keys("Enter").type_text(":clear stack").keys("Enter").assert_stack(&[]);
+        driver.type_text(":clear").keys("Enter");
+        assert!(driver.calculator().confirmation.is_some());
+        driver.keys("Esc");
+        assert!(driver.calculator().confirmation.is_none());
+    }
+
+    #[test]
+    fn pinned_history_and_rerun() {
+        let mut driver = Driver::new();
+        driver.keys("m").type_text("10+1").keys("Enter").type_text("2+2").keys("Enter");
+        driver.keys("PageUp PageUp Ctrl+p");
+        assert!(driver.calculator().history[0].pinned);
+
+        driver.keys("Alt+c Ctrl+c y").assert_stack(&[]).assert_history(&["10+1 = 11"]);
+        driver.keys("F2 Ctrl+r").assert_stack(&["0xB"]);
+        assert!(driver.calculator().input.is_empty());
+        assert_eq!(driver.history().len(), 2);
+    }
+
+    #[test]
+    fn history_export() {
+        let mut driver = Driver::new();
+        driver.keys("2 Enter 3 +").type_text(":export md").keys("Enter");
+        let copied = driver.app.calculator.clipboard.take().expect("export goes to the clipboard");
+        assert_eq!(copied, "| Expression | Result |\n| --- | --- |\n| `2` |  |\n| `3` |  |\n| `(2 + 3)` | 5 |\n");
+
+        let path = std::env::temp_dir().join(format!("tuic-export-{}.tex", std::process::id()));
+        driver.type_text(&format!(":export latex {}", path.display())).keys("Enter");
+        assert!(std::fs::read_to_string(&path).unwrap().contains("(2 + 3) &= 5"));
+        // Writing over the file asks first
+        driver.type_text(&format!(":export md {}", path.display())).keys("Enter n");
+        assert!(std::fs::read_to_string(&path).unwrap().contains("(2 + 3) &= 5"));
+        driver.type_text(&format!(":export md {}", path.display())).keys("Enter y");
+        assert!(std::fs::read_to_string(&path).unwrap().contains("| `(2 + 3)` | 5 |"));
+        std::fs::remove_file(&path).unwrap();
+        driver.type_text(":export pdf").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E402"));
+    }
+
+    #[test]
+    fn history_times_and_sessions() {
+        let mut driver = Driver::new();
+        // History restored from an earlier session, saved with times
+        let earlier = HistoryEntry { created_ms: 1_700_000_100_000, session_ms: 1_700_000_000_000, ..HistoryEntry::new("1 + 1", Some(StackValue::Real(2.0))) };
+        driver.app.calculator.history.push(earlier);
+        assert!(!driver.render(80, 30).contains("Session of"));
+        driver.type_text(":set times on").keys("Enter");
+        let screen = driver.render(80, 30);
+        assert!(screen.contains("22:15 1 + 1"));
+        assert!(screen.contains(&session_marker(driver.calculator().session_ms)));
+
+        driver.keys("4 Enter");
+        let entry = driver.calculator().history.last().unwrap().clone();
+        assert_eq!(entry.session_ms, driver.calculator().session_ms);
+        assert!(entry.created_ms >= entry.session_ms);
+        assert_eq!(driver.calculator().session_start_before(1), Some(entry.session_ms));
+        assert_eq!(driver.calculator().session_start_before(2), None);
+
+        driver.type_text(":export md").keys("Enter");
+        let copied = driver.app.calculator.clipboard.take().expect("export goes to the clipboard");
+        assert!(copied.contains(&format!("| `1 + 1` | 2 |\n| `{}` |  |\n| `4` |", session_label(entry.session_ms))), "{}", copied);
+    }
+
+    #[test]
+    fn stack_labels() {
+        let mut driver = Driver::new();
+        driver.keys("1 0 0 Enter").type_text(":label sub total").keys("Enter");
+        driver.keys("8 Enter").type_text(":label tax").keys("Enter");
+        let labels = |driver: &Driver| driver.calculator().stack.iter().map(|entry| entry.label.clone()).collect::<Vec<_>>();
+        assert_eq!(labels(&driver), [Some("sub total".to_string()), Some("tax".to_string())]);
+
+        driver.keys("Insert").assert_stack(&["8", "100"]);
+        assert_eq!(labels(&driver), [Some("tax".to_string()), Some("sub total".to_string())]);
+        driver.keys("+");
+        assert_eq!(labels(&driver), [None]);
+        driver.keys("u u");
+        assert_eq!(labels(&driver), [Some("sub total".to_string()), Some("tax".to_string())]);
+    }
+
+    #[test]
+    fn workspaces() {
+        let mut driver = Driver::new();
+        driver.keys("1 Enter 2 +");
+        driver.type_text(":ws new scratch").keys("Enter").assert_stack(&[]).assert_history(&[]);
+        driver.keys("7 Enter").assert_stack(&["7"]);
+        driver.type_text(":ws main").keys("Enter").assert_stack(&["3"]).assert_history(&["1", "2", "(1 + 2) = 3"]);
+        driver.type_text(":ws").keys("Enter");
+        assert_eq!(driver.calculator().notice.as_deref(), Some("Workspaces: main*, scratch"));
+
+        driver.keys("u").assert_stack(&["1", "2"]);
+        driver.type_text(":ws main").keys("Enter");
+        driver.type_text(":ws delete main").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E406"));
+        driver.type_text(":ws delete scratch").keys("Enter");
+        assert_eq!(driver.calculator().confirmation_prompt().as_deref(), Some("Delete workspace scratch with 1 stack entries and 1 history entries?"));
+        assert_eq!(driver.app.focus(), Focus::Confirm);
+        driver.keys("n").type_text(":ws delete scratch").keys("Enter y");
+        driver.type_text(":ws scratch").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E406"));
+    }
+
+    #[test]
+    fn config_defaults() {
+        let config = Config::parse("mode = \"infix\"\nangle = \"deg\"\nprecision = 2\nlayout = \"compact\"\nhistory-size = 2").unwrap();
+        let mut driver = Driver::new();
+        driver.app.calculator.apply_config(&config);
+        driver.app.apply_config(&config);
+        assert_eq!(driver.app.layout, Layout::Compact);
+        driver.type_text("1/3").keys("Enter").type_text("2/3").keys("Enter").type_text("1").keys("Enter");
+        driver.assert_stack(&["0.33", "0.67", "1.00"]).assert_history(&["2/3 = 0.67", "1 = 1.00"]);
+        assert_eq!(driver.calculator().angle_mode, AngleMode::Degrees);
+    }
+
+    #[test]
+    fn vi_bindings() {
+        let mut driver = Driver::new();
+        driver.app.apply_config(&Config::parse("keys = \"vi\"").unwrap());
+        assert_eq!(driver.app.vi_mode, Some(ViMode::Normal));
+        driver.keys("2 Enter 3 Enter").assert_stack(&["2", "3"]);
+        assert_eq!(driver.app.vi_mode, Some(ViMode::Insert));
+        driver.keys("Esc p + d j").assert_stack(&["2", "6"]);
+        driver.keys("d d").assert_stack(&["2"]);
+        driver.keys("k j Esc");
+        assert!(!driver.has_quit());
+
+        // Letters are shortcuts in normal mode, text in insert mode
+        driver.keys("m i").type_text("2*pi").keys("Esc");
+        assert_eq!(driver.calculator().input, "2*pi");
+        driver.keys("c u").assert_stack(&["2", "6"]);
+        driver.type_text(":ws").keys("Esc");
+        assert_eq!(driver.calculator().input, "");
+        driver.keys("q");
+        assert!(driver.has_quit());
+    }
+
+    #[test]
+    fn help_follows_focus() {
+        let mut driver = Driver::new();
+        driver.keys("h");
+        assert_eq!(driver.app.help_focus, Focus::Input);
+        driver.keys("Esc 2 Enter Up h");
+        assert_eq!(driver.app.help_focus, Focus::Stack);
+        driver.keys("h t ?");
+        assert!(driver.app.show_help && driver.app.show_theme_selector);
+        assert_eq!(driver.app.help_focus, Focus::ThemeSelector);
+        driver.keys("Esc");
+        assert!(!driver.app.show_help && driver.app.show_theme_selector);
+    }
+
+    #[test]
+    fn theme_selector_previews_and_reverts() {
+        let mut driver = Driver::new();
+        let original = driver.app.current_theme.name.clone();
+        driver.keys("t Down");
+        assert!(driver.app.show_theme_selector);
+        assert_ne!(driver.app.current_theme.name, original);
+        driver.keys("Up Up Esc");
+        assert!(!driver.app.show_theme_selector);
+        assert_eq!(driver.app.current_theme.name, original);
+        driver.keys("t Down t").assert_stack(&[]);
+        assert_eq!(driver.app.current_theme.name, original);
+    }
+
+    #[test]
+    fn quick_entry_copies_the_result_and_quits() {
+        let mut driver = Driver::new();
+        driver.app.start_quick();
+        driver.type_text("2^10 +").keys("Enter");
+        assert!(!driver.has_quit() && driver.calculator().error.is_some());
+        // A command runs and the line stays open for the expression
+        driver.keys("Ctrl+l Alt+c").type_text(":set precision 2").keys("Enter");
+        assert!(!driver.has_quit());
+        driver.type_text("2^10 + 1");
+        let screen = driver.render(60, 6);
+        assert!(screen.contains("Input") && screen.contains("Preview: = 1025.00"), "{}", screen);
+        assert!(!screen.contains("Stack") && !screen.contains("History"));
+        driver.keys("Enter");
+        assert!(driver.has_quit());
+        assert_eq!(driver.app.calculator.clipboard.as_deref(), Some("1025.00"));
+    }
+
+    #[test]
+    fn theme_colors_popup() {
+        let mut driver = Driver::new();
+        driver.keys("t i");
+        assert!(driver.app.show_theme_colors);
+        assert_eq!(driver.app.focus(), Focus::ThemeColors);
+        let screen = driver.render(100, 50);
+        assert!(screen.contains("Theme Colors"));
+        assert!(screen.contains("focused_border") && screen.contains("lightyellow (#ffff00 in xterm)"));
+        assert!(screen.contains("stack.background") && screen.contains("not set, uses background"));
+        driver.keys("Down Down Esc");
+        assert!(!driver.app.show_theme_colors && driver.app.show_theme_selector);
+        assert_eq!(driver.app.theme_colors_scroll, 2);
+    }
+
+    #[test]
+    fn theme_selector_checks_contrast() {
+        let mut driver = Driver::new();
+        assert!(["deuteranopia", "protanopia"].iter().all(|name| driver.app.available_themes.iter().any(|theme| theme == name)));
+        driver.keys("t");
+        let mut select = |name: &str| {
+            for _ in 0..driver.app.available_themes.len() {
+                if driver.app.current_theme.name == name {
+                    return driver.render(80, 30);
+                }
+                driver.keys("Down");
+            }
+            panic!("no theme {}", name);
+        };
+        assert!(select("protanopia").contains("All text colors meet 4.5:1"));
+        assert!(select("default").contains("⚠ info on background: "));
+    }
+
+    #[test]
+    fn mode_toggle_keeps_numbers() {
+        let mut driver = Driver::new();
+        driver.type_text("12.5").keys("m");
+        assert_eq!(driver.calculator().mode, CalculatorMode::Infix);
+        assert_eq!(driver.calculator().input, "12.5");
+        driver.type_text("*2").keys("Alt+m");
+        assert_eq!(driver.calculator().mode, CalculatorMode::RPN);
+        assert_eq!(driver.calculator().input, "");
+        assert!(driver.calculator().notice.is_some());
+        driver.type_text("45°30'").keys("m Alt+m Enter").assert_stack(&["45.5"]);
+        driver.type_text(":ws").keys("Alt+m");
+        assert_eq!(driver.calculator().input, ":ws");
+    }
+
+    #[test]
+    fn ticks_animate() {
+        let mut driver = Driver::new();
+        driver.app.on_tick();
+        driver.type_text("x");
+        driver.app.on_key(false);
+        assert_eq!(driver.app.error_flash, 2);
+        assert!(driver.app.caret_visible());
+        driver.app.on_tick();
+        driver.app.on_tick();
+        assert_eq!(driver.app.error_flash, 0);
+        assert!(!driver.app.caret_visible());
+        driver.app.on_tick();
+        driver.app.on_tick();
+        assert!(driver.app.caret_visible());
+        // Another key with the error still shown doesn't flash again
+        driver.app.on_key(true);
+        assert_eq!(driver.app.error_flash, 0);
+    }
+
+    #[test]
+    fn currency_conversion() {
+        let mut driver = Driver::new();
+        driver.keys("m").type_text("100 USD -> EUR").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E208"));
+        driver.keys("Alt+c").type_text(":rate USD 1.25").keys("Enter");
+        driver.type_text(":rate GBP 0.5").keys("Enter");
+        driver.type_text("100 usd -> EUR");
+        assert_eq!(driver.calculator().preview().as_deref(), Some("80"));
+        driver.keys("Enter").type_text("2*5 EUR->gbp").keys("Enter").assert_stack(&["80", "5"]);
+        driver.assert_history(&["100 usd -> EUR = 80", "2*5 EUR->gbp = 5"]);
+
+        driver.type_text(":rates").keys("Enter");
+        assert!(driver.calculator().show_rates);
+        driver.keys("f");
+        assert!(driver.calculator().rates_fetch_requested);
+        driver.keys("Esc");
+        assert!(!driver.calculator().show_rates);
+        driver.app.calculator.rates_fetch_requested = false;
+        driver.app.calculator.apply_fetched_rates(Err("offline".to_string()));
+        assert_eq!(driver.error_code(), Some("E409"));
+    }
+
+    #[test]
+    fn data_sizes() {
+        let mut driver = Driver::new();
+        driver.keys("m").type_text("4 * 512 MiB");
+        assert_eq!(driver.calculator().preview().as_deref(), Some("2 GiB"));
+        driver.keys("Enter").type_text("1.5 GiB in MB").keys("Enter").assert_stack(&["2", "1610.612736"]);
+        driver.type_text(":set sizes si").keys("Enter").type_text("4 * 512 MiB").keys("Enter");
+        driver.assert_history(&["4 * 512 MiB in GiB = 2", "1.5 GiB in MB = 1610.612736", "4 * 512 MiB in GB = 2.147483648"]);
+        driver.type_text("2 GiB in parsecs").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E209"));
+    }
+
+    #[test]
+    fn hashes() {
+        let mut driver = Driver::new();
+        driver.keys("F2").type_text(":hash md5 abc").keys("Enter");
+        driver.assert_history(&["md5(\"abc\") = 900150983cd24fb0d6963f7d28e17f72"]);
+        // The top of the stack hashes as big-endian bytes: 0x1234 is [0x12, 0x34]
+        driver.type_text("1234").keys("Enter").keys("F2 F2").type_text(":hash crc32").keys("Enter").assert_stack(&["4660"]);
+        assert_eq!(driver.calculator().notice.as_deref(), Some("crc32(4660) = 412718745"));
+        driver.type_text(":hash sha512").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E402"));
+    }
+
+    #[test]
+    fn subnets() {
+        let mut driver = Driver::new();
+        driver.type_text(":cidr 192.168.1.77/26 192.168.1.200").keys("Enter").assert_stack(&["62"]);
+        assert_eq!(driver.app.focus(), Focus::Subnet);
+        assert_eq!(driver.calculator().notice.as_deref(), Some("192.168.1.200 is not in 192.168.1.77/26"));
+        driver.assert_history(&["hosts(192.168.1.64/26) = 62"]);
+        driver.keys("Esc");
+        assert!(driver.calculator().subnet.is_none());
+        driver.type_text(":cidr 10.0.0.0/40").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E402"));
+        driver.keys("u").assert_stack(&[]);
+    }
+
+    #[test]
+    fn bulk_stack_operations() {
+        let mut driver = Driver::new();
+        driver.keys("1 0 Enter 2 0 Enter").type_text(":label b").keys("Enter 3 0 Enter");
+        driver.type_text(":map * 1.5").keys("Enter").assert_stack(&["15", "30", "45"]);
+        assert_eq!(driver.calculator().stack[1].label.as_deref(), Some("b"));
+        assert_eq!(&*driver.calculator().stack[0].expression, "(10 * 1.5)");
+        driver.type_text(":map sqrt").keys("Enter");
+        driver.keys("u").assert_stack(&["15", "30", "45"]);
+        driver.type_text(":map / 0 - 0").keys("Enter").assert_stack(&["15", "30", "45"]);
+        driver.type_text(":map %").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E402"));
+        driver.type_text(":fold +").keys("Enter").assert_stack(&["90"]);
+        assert_eq!(driver.history().last().map(String::as_str), Some("(((10 * 1.5) + (20 * 1.5)) + (30 * 1.5)) = 90"));
+        driver.keys("u").assert_stack(&["15", "30", "45"]);
+        driver.keys("6 0 Enter").type_text(":drop 2 3").keys("Enter Esc").assert_stack(&["15", "30", "45", "60"]);
+        driver.type_text(":drop 2 3").keys("Enter y").assert_stack(&["15", "60"]);
+        driver.type_text(":drop 3").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E301"));
+        driver.type_text(":drop 1").keys("Enter").assert_stack(&["15"]);
+        driver.type_text(":fold *").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E301"));
+    }
+
+    #[test]
+    fn copying_stack_entries() {
+        let mut driver = Driver::new();
+        driver.keys("Alt+m").type_text("1 / 4").keys("Enter").type_text("7").keys("Enter");
+        driver.keys("Down Alt+y").type_text(" * 2").keys("Enter").assert_stack(&["0.25", "7", "0.5"]);
+        driver.keys("Down Down Alt+Y").type_text(" + 1");
+        assert_eq!(driver.calculator().input, "(1 / 4) + 1");
+        driver.keys("Enter Up Up Ctrl+y").assert_stack(&["0.25", "7", "0.5", "1.25"]);
+        assert_eq!(driver.app.calculator.clipboard.take().as_deref(), Some("1.25"));
+        driver.keys("Ctrl+c y Alt+y");
+        assert_eq!(driver.error_code(), Some("E301"));
+    }
+
+    #[test]
+    fn recovering_an_interrupted_session() {
+        let dir = std::env::temp_dir().join(format!("tuic-recovery-{}", std::process::id()));
+        let path = dir.join("session.journal");
+        let mut crashed = Driver::new();
+        crashed.app.start_journal(journal::claim(&dir).unwrap());
+        crashed.keys("4 Enter 5 Enter +").type_text("1.5");
+        crashed.app.update_journal();
+        assert!(path.is_file());
+
+        // While it runs, another instance gets a journal of its own and nothing to restore
+        let mut other = Driver::new();
+        other.app.start_journal(journal::claim(&dir).unwrap());
+        assert!(other.app.recovery.is_none());
+        other.keys("1 Enter");
+        other.app.update_journal();
+        other.app.finish_journal().unwrap();
+        assert!(path.is_file());
+        drop(other);
+
+        drop(crashed);
+        let mut next = Driver::new();
+        next.app.start_journal(journal::claim(&dir).unwrap());
+        assert!(next.render(80, 30).contains("Restore Session?"));
+        next.keys("y").assert_stack(&["9"]);
+        assert_eq!(next.calculator().input, "1.5");
+        assert_eq!(next.calculator().history.len(), 3);
+        drop(next);
+
+        // Starting fresh keeps the new session, which a clean exit then clears away
+        let mut fresh = Driver::new();
+        fresh.app.start_journal(journal::claim(&dir).unwrap());
+        fresh.keys("n").assert_stack(&[]);
+        fresh.app.update_journal();
+        fresh.app.finish_journal().unwrap();
+        assert!(!path.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn autosaving_every_few_operations() {
+        let dir = std::env::temp_dir().join(format!("tuic-autosave-{}", std::process::id()));
+        let path = dir.join("session.journal");
+        let mut driver = Driver::new();
+        driver.app.autosave = Autosave::Operations(3);
+        driver.app.start_journal(journal::claim(&dir).unwrap());
+        driver.app.update_journal();
+        assert!(!path.exists());
+        assert_eq!(driver.app.save_status(), None);
+
+        for key in ["1 Enter", "2 Enter"] {
+            driver.keys(key);
+            driver.app.update_journal();
+        }
+        assert!(!path.exists());
+        assert!(driver.render(100, 30).contains("● 2 unsaved changes"));
+        driver.keys("+");
+        driver.app.update_journal();
+        assert!(path.is_file());
+        assert_eq!(driver.app.save_status().as_deref(), Some("saved 0s ago"));
+
+        driver.keys("3 Enter");
+        driver.app.update_journal();
+        assert_eq!(driver.app.save_status().as_deref(), Some("● 1 unsaved change · saved 0s ago"));
+        driver.app.finish_journal().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn output_templates() {
+        let mut driver = Driver::new();
+        let config = tuic_core::config::Config::parse(
+            "history-template = \"{expr} → {result} [{base}]\"\ncopy-template = \"{label}: {result}\"\nexport-template = \"{result}\\t{expr}\"",
+        )
+        .unwrap();
+        driver.app.calculator.apply_config(&config);
+        driver.keys("Alt+m").type_text("6 * 7").keys("Enter F2");
+        driver.assert_history(&["6 * 7 → 0x2A [HEX]"]);
+        driver.type_text(":label answer").keys("Enter Ctrl+y");
+        assert_eq!(driver.app.calculator.clipboard.take().as_deref(), Some("answer: 0x2A"));
+        driver.type_text(":export text").keys("Enter");
+        assert_eq!(driver.app.calculator.clipboard.take().as_deref(), Some("0x2A\t6 * 7\n"));
+    }
+
+    #[test]
+    fn entry_ids_and_origins() {
+        let mut driver = Driver::new();
+        driver.type_text("2").keys("Enter").type_text("3").keys("Enter").type_text("+");
+        assert_eq!(driver.app.answer("push 4"), "ok 4");
+        driver.type_text("!1").keys("Enter");
+        let stamps: Vec<_> = driver.calculator().stack().iter().map(|entry| (entry.id, entry.origin)).collect();
+        assert_eq!(stamps, [(3, EntryOrigin::Operation), (4, EntryOrigin::Ipc), (5, EntryOrigin::History)]);
+        assert!(driver.calculator().stack()[0].time_label().is_some());
+
+        driver.keys("Down Down i");
+        assert!(driver.render(100, 40).contains("Entry #3, operation,"));
+        driver.keys("Esc");
+        driver.app.calculator.apply_config(&Config::parse("copy-template = \"#{id} {origin}: {result}\"").unwrap());
+        driver.keys("Ctrl+y");
+        assert_eq!(driver.app.calculator.clipboard.take().as_deref(), Some("#3 operation: 5"));
+    }
+
+    #[test]
+    fn trigonometry_in_degrees() {
+        let mut driver = Driver::new();
+        driver.keys("F1 Alt+m").type_text("sin(90) + cos(180)").keys("Enter").assert_stack(&["0"]);
+        driver.type_text("asin(0.5)").keys("Enter").assert_stack(&["0", "30.000000000000004"]);
+        driver.type_text(":sin").keys("Enter").assert_stack(&["0", "0.5"]);
+        driver.keys("F1").type_text("sin(90)").keys("Enter").assert_stack(&["0", "0.5", "0.8939966636005579"]);
+    }
+
+    #[test]
+    fn rounding_to_places() {
+        let mut driver = Driver::new();
+        driver.type_text("3.14159").keys("Enter").type_text("2").keys("Enter").type_text(":rnd").keys("Enter").assert_stack(&["3.14"]);
+        driver.type_text("0.125").keys("Enter").type_text("2").keys("Enter").type_text(":rnd").keys("Enter").assert_stack(&["3.14", "0.13"]);
+        driver.type_text(":set rounding half-even").keys("Enter Alt+m").type_text("rnd(0.125, 2) + round(2.5)").keys("Enter");
+        driver.assert_stack(&["3.14", "0.13", "2.12"]);
+        driver.type_text(":set rounding up").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E402"));
+    }
+
+    #[test]
+    fn decimal_comma() {
+        let mut driver = Driver::new();
+        // With a point, 3,14 gets a hint to fix it
+        driver.keys("Alt+m").type_text("3,14 * 2").keys("Enter");
+        assert!(driver.calculator().suggestion.as_ref().is_some_and(|suggestion| suggestion.fix.as_deref() == Some("3.14 * 2")));
+        driver.keys("Ctrl+f Enter").assert_stack(&["6.28"]);
+
+        driver.type_text(":set decimal comma").keys("Enter").assert_stack(&["6,28"]);
+        driver.type_text("rnd(1.234,56; 1) + ,5").keys("Enter").assert_stack(&["6,28", "1235,1"]);
+        assert_eq!(driver.calculator().display_expression(driver.calculator().stack().last().unwrap()), "rnd(1234,56; 1) + 0,5");
+        driver.type_text("3.14").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E101"), "a point out of habit isn't read as grouping");
+        assert_eq!(driver.calculator().suggestion.as_ref().and_then(|suggestion| suggestion.fix.as_deref()), Some("3,14"));
+        driver.keys("Backspace Backspace Backspace Backspace Alt+m").type_text("2,5").keys("Enter").assert_stack(&["6,28", "1235,1", "2,5"]);
+        driver.keys("y");
+        assert_eq!(driver.calculator().input, "2,5");
+        driver.keys("Backspace Backspace Backspace").type_text(":set decimal dot").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E402"));
+    }
+
+    #[test]
+    fn screen_reader_log() {
+        let mut driver = Driver::new();
+        driver.app.apply_config(&Config::parse("screen-reader = \"on\"").unwrap());
+        driver.type_text("2").keys("Enter").type_text("3").keys("Enter").type_text("+").keys("F1 Delete m").type_text("2*").keys("Enter");
+        let lines = &driver.app.announcer.as_ref().unwrap().lines;
+        assert_eq!(lines, &["Pushed 2", "Pushed 3", "Result 5, from (2 + 3)", "Mode: RPN, Angle: DEG, Base: DEC, Complex: REC", "Stack empty", "Mode: INFIX, Angle: DEG, Base: DEC, Complex: REC", "Error E102: Expression ends unexpectedly"]);
+        let frame = driver.render(80, 30);
+        assert!(frame.contains("Log") && frame.contains("Stack empty") && !frame.contains("Quick Help"));
+        assert!(!driver.app.print_announcements);
+    }
+
+    #[test]
+    fn pasting_is_not_typing() {
+        let mut driver = Driver::new();
+        // In RPN mode 'q' quits and '+' adds, but pasted they are only text
+        driver.type_text("7").paste("q + 2\r\n\t* 3\n");
+        assert_eq!(driver.calculator().input, "7q + 2  * 3");
+        assert!(driver.calculator().stack.is_empty() && !driver.quit);
+
+        // The theme selector has no text field, so the input under it is left alone
+        driver.keys("Ctrl+c y t").paste("5").keys("Esc");
+        assert!(driver.calculator().input.is_empty());
+
+        driver.keys("Alt+m Ctrl+e").paste("(1 + 2)\n* 3  # nine").keys("Ctrl+s").assert_stack(&["9"]);
+        assert_eq!(driver.calculator().history.last().unwrap().expression, "(1 + 2) * 3");
+        driver.keys("F7").paste("6*7").keys("Enter");
+        assert_eq!(driver.calculator().input, "42");
+
+        let mut driver = Driver::new();
+        driver.app.apply_config(&Config::parse("keys = \"vi\"").unwrap());
+        driver.paste("12").keys("Enter").assert_stack(&["12"]);
+    }
+
+    #[test]
+    fn debug_overlay() {
+        let mut driver = Driver::new();
+        driver.keys("F12").type_text("2").keys("Enter Alt+m").type_text("2*").keys("Enter");
+        assert!(driver.app.show_debug);
+        assert_eq!(driver.app.debug.last_key.as_deref(), Some("Enter"));
+        assert_eq!(driver.app.debug.last_error, ["Expression ends unexpectedly"]);
+        let frame = driver.render(100, 40);
+        assert!(frame.contains("Debug (F12)") && frame.contains("Expression ends unexpectedly") && frame.contains("1 stack"));
+        // Keys went to the calculator underneath the whole time
+        driver.keys("F12").assert_stack(&["2"]);
+        assert!(!driver.app.show_debug && !driver.render(100, 40).contains("Debug (F12)"));
+    }
+
+    #[test]
+    fn logarithm_of_any_base() {
+        let mut driver = Driver::new();
+        driver.type_text("8").keys("Enter").type_text("2").keys("Enter").type_text(":logb").keys("Enter").assert_stack(&["3"]);
+        driver.keys("Alt+m").type_text("logb(0, 2)").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E211"));
+        driver.keys("Backspace Backspace Backspace Backspace Backspace Backspace Backspace Backspace Backspace Backspace");
+        driver.type_text("logb(100, 10)").keys("Enter").assert_stack(&["3", "2"]);
+    }
+
+    #[test]
+    fn sums_and_products() {
+        let mut driver = Driver::new();
+        driver.keys("Alt+m").type_text("sum(k, k, 1, 100)").keys("Enter").assert_stack(&["5050"]);
+        driver.type_text("prod(n, n, 1, 5) + sum(k, k, 3, 2)").keys("Enter").assert_stack(&["5050", "120"]);
+        driver.type_text("sqrt(6 * sum(1/n^2, n, 1, 1000))").keys("Enter");
+        assert_eq!(driver.calculator().top_real().map(|value| (value * 1000.0).round()), Some(3141.0));
+        driver.type_text("sum(k, k, 1, 10^7)").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E211"));
+    }
+
+    #[test]
+    fn fractions_from_decimals() {
+        let mut driver = Driver::new();
+        driver.type_text("0.333333").keys("Enter").type_text(":frac").keys("Enter").assert_stack(&["0.3333333333333333"]);
+        assert_eq!(driver.calculator().notice.as_deref(), Some("0.333333 ≈ 1/3 (off by 3.3e-7)"));
+        assert_eq!(driver.history().last().map(String::as_str), Some("1/3 = 0.3333333333333333"));
+        driver.type_text("3.1416").keys("Enter").type_text(":frac 0.01").keys("Enter");
+        assert_eq!(&*driver.calculator().stack()[1].expression, "22/7");
+        driver.type_text(":frac 0").keys("Enter");
+        assert_eq!(&*driver.calculator().stack()[1].expression, "22/7");
+        driver.type_text(":frac -1").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E205"));
+    }
+
+    #[test]
+    fn inline_comments() {
+        let mut driver = Driver::new();
+        driver.keys("Alt+m").type_text("2 * (3 + 4)  # area: 2×7, in m²").keys("Enter").assert_stack(&["14"]);
+        assert_eq!(driver.calculator().stack()[0].label.as_deref(), Some("area: 2×7, in m²"));
+        assert_eq!(driver.history().last().map(String::as_str), Some("2 * (3 + 4) = 14  # area: 2×7, in m²"));
+        // A failed evaluation keeps the comment for fixing, and the suggestion does too
+        driver.type_text("sqrt 4 # side").keys("Enter");
+        assert_eq!(driver.calculator().input, "sqrt 4 # side");
+        assert_eq!(driver.calculator().suggestion.as_ref().and_then(|s| s.fix.as_deref()), Some("sqrt(4) # side"));
+        driver.keys("Ctrl+f Enter").assert_stack(&["14", "2"]);
+        assert_eq!(driver.history().last().map(String::as_str), Some("sqrt(4) = 2  # side"));
+        driver.type_text("1 + 1 #").keys("Enter");
+        assert_eq!(driver.calculator().stack()[2].label, None);
+    }
+
+    #[test]
+    fn recalling_history_entries() {
+        let mut driver = Driver::new();
+        driver.keys("1 2 Enter 3 Enter +").assert_history(&["12", "3", "(12 + 3) = 15"]);
+        // A prefix of an earlier expression is just a number now
+        driver.keys("1 Enter").assert_stack(&["15", "1"]);
+        driver.type_text("!3").keys("Enter").type_text("!1+").assert_stack(&["15", "1", "27"]);
+        driver.type_text("!!").keys("Enter").assert_stack(&["15", "1", "27", "27"]);
+        driver.type_text("!99").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E304"));
+
+        driver.keys("Backspace Backspace Backspace Alt+m").type_text("!1 * 2 + (1 != 2)").keys("Enter");
+        assert_eq!(driver.history().last().map(String::as_str), Some("(12) * 2 + (1 != 2) = 25"));
+        driver.type_text(":set prefixrecall on").keys("Enter");
+        assert!(driver.calculator().prefix_recall);
+    }
+
+    #[test]
+    fn stack_references_in_infix() {
+        let mut driver = Driver::new();
+        driver.keys("Alt+m").type_text("2 + 3").keys("Enter").type_text("10").keys("Enter");
+        driver.type_text("s1 * s2").keys("Enter").assert_stack(&["5", "10", "50"]);
+        // Resolved when evaluated, so the same expression follows the stack
+        driver.type_text("s1 / s3").keys("Enter").assert_stack(&["5", "10", "50", "10"]);
+        driver.type_text("s9").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E301"));
+    }
+
+    #[test]
+    fn pushing_constants() {
+        let mut driver = Driver::new();
+        driver.keys("2 p *").assert_stack(&["6.283185307179586"]);
+        driver.keys("e u").assert_stack(&["6.283185307179586"]);
+        driver.keys("e").assert_history(&["2", "pi", "(2 * pi) = 6.283185307179586", "e", "e"]);
+        driver.keys("Alt+m").type_text("2 * ").keys("Alt+p");
+        assert_eq!(driver.calculator().input, "2 * pi");
+    }
+
+    #[test]
+    fn sorting_the_stack() {
+        let mut driver = Driver::new();
+        driver.keys("3 Enter 1 Enter 1 Enter +").type_text(":label pair").keys("Enter 5 Enter");
+        driver.type_text(":sort keep").keys("Enter").assert_stack(&["2", "3", "5"]);
+        assert_eq!(&*driver.calculator().stack[0].expression, "(1 + 1)");
+        assert_eq!(driver.calculator().stack[0].label.as_deref(), Some("pair"));
+        driver.type_text(":sort desc").keys("Enter").assert_stack(&["5", "3", "2"]);
+        assert_eq!(&*driver.calculator().stack[2].expression, "2");
+        assert_eq!(driver.calculator().stack[2].label, None);
+        driver.type_text(":reverse").keys("Enter").assert_stack(&["2", "3", "5"]);
+        driver.keys("u").assert_stack(&["5", "3", "2"]);
+        driver.type_text(":sort up").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E402"));
+    }
+
+    #[test]
+    fn rapid_entry() {
+        let mut driver = Driver::new();
+        driver.keys("9 Enter").type_text(":rapid").keys("Enter");
+        driver.type_text("12 7.5 3").keys("Enter Enter");
+        driver.assert_stack(&["9", "12", "7.5", "3"]);
+        assert_eq!(driver.calculator().rapid_tally(), (3, 22.5));
+        driver.keys("u");
+        assert_eq!(driver.calculator().rapid_tally(), (2, 19.5));
+        driver.type_text("4=").assert_stack(&["9", "23.5"]);
+        driver.assert_history(&["9", "12", "7.5", "3", "4", "(12 + 7.5 + 4) = 23.5"]);
+        driver.type_text("1 2~").assert_stack(&["9", "23.5", "1.5"]);
+        assert_eq!(&*driver.calculator().stack[2].expression, "(1 + 2) / 2");
+        driver.type_text("=");
+        assert_eq!(driver.error_code(), Some("E301"));
+        driver.type_text(":rapid").keys("Enter");
+        driver.type_text("1 ");
+        assert!(driver.calculator().rapid_start.is_none() && driver.calculator().input == "1");
+    }
+
+    #[test]
+    fn history_result_statistics() {
+        let mut driver = Driver::new();
+        driver.keys("1 0 Enter 2 0 Enter +").type_text("[1, 2]").keys("Enter 6 Enter");
+        driver.type_text(":stats results").keys("Enter");
+        let stats = driver.calculator().result_stats.clone().unwrap();
+        assert_eq!((stats.count(), stats.skipped, stats.sum), (4, 1, 66.0));
+        let screen = driver.render(100, 40);
+        assert!(screen.contains("History Results") && screen.contains("Mean    16.5") && screen.contains("Min     6"));
+        assert!(screen.contains("1 entries without a real value left out"));
+        driver.keys("Esc");
+        assert!(driver.calculator().result_stats.is_none());
+    }
+
+    #[test]
+    fn tax_and_tip_keys() {
+        let mut driver = Driver::new();
+        driver.keys("x");
+        assert_eq!(driver.error_code(), Some("E412"));
+        driver.type_text(":set tax 8.25").keys("Enter").type_text("100").keys("x");
+        driver.assert_stack(&["108.25"]);
+        assert_eq!(driver.calculator().history.last().and_then(|entry| entry.comment.as_deref()), Some("TAX+ 8.25%"));
+        driver.keys("X");
+        driver.assert_stack(&["100"]);
+        driver.type_text(":set tip 20").keys("Enter g");
+        driver.assert_stack(&["120"]);
+        assert!(driver.render(100, 40).contains("# TIP 20%"));
+        driver.keys("u");
+        driver.assert_stack(&["100"]);
+        driver.keys("m").type_text("50").keys("Alt+g");
+        driver.assert_stack(&["100", "60"]);
+    }
+
+    #[test]
+    fn grand_total_register() {
+        let mut driver = Driver::new();
+        driver.keys("2 Enter 3 +");
+        driver.keys("m").type_text("10 * 4").keys("Enter");
+        assert_eq!(driver.calculator().grand_total, 45.0);
+        assert!(driver.render(100, 40).contains("GT 45"));
+        driver.keys("Alt+G");
+        driver.assert_stack(&["5", "40", "45"]);
+        assert_eq!(driver.calculator().grand_total, 45.0); // Recalling it isn't a result
+        driver.type_text(":gt reset").keys("Enter");
+        assert_eq!(driver.calculator().grand_total, 0.0);
+        assert!(!driver.render(100, 40).contains("GT "));
+        // A number typed on its own isn't a result, even in infix
+        driver.type_text("7").keys("Enter");
+        assert_eq!(driver.calculator().grand_total, 0.0);
+        // Undo takes a result back out, and clear all starts over
+        driver.keys("Alt+c Ctrl+c y Alt+m 2 Enter 3 +");
+        assert_eq!(driver.calculator().grand_total, 5.0);
+        driver.keys("u");
+        driver.assert_stack(&["2", "3"]);
+        assert_eq!(driver.calculator().grand_total, 0.0);
+        driver.keys("+ Ctrl+c y");
+        assert_eq!(driver.calculator().grand_total, 0.0);
+        driver.keys("u");
+        assert_eq!(driver.calculator().grand_total, 5.0);
+    }
+
+    #[test]
+    fn sending_the_stack_to_gnuplot() {
+        let mut driver = Driver::new();
+        driver.app.gnuplot_command = "true {script} {data}".to_string();
+        driver.keys("Ctrl+g");
+        assert!(driver.calculator().notice.as_deref().is_some_and(|notice| notice.starts_with("No real values")));
+        driver.keys("3 Enter 1 . 5 Enter F10 g");
+        let notice = driver.calculator().notice.clone().unwrap();
+        let data = notice.strip_prefix("Sent 2 values to gnuplot (").and_then(|rest| rest.strip_suffix(')')).unwrap();
+        assert_eq!(std::fs::read_to_string(data).unwrap(), "n,value\n1,3\n2,1.5\n");
+        driver.app.gnuplot_command = "tuic-no-such-gnuplot {script}".to_string();
+        driver.keys("Esc Ctrl+g");
+        assert_eq!(driver.error_code(), Some("E407"));
+    }
+
+    #[test]
+    fn rpn_words() {
+        let mut driver = Driver::new();
+        driver.type_text(":set words on").keys("Enter");
+        driver.type_text("3 4 swap -").keys("Enter");
+        driver.assert_stack(&["1"]);
+        driver.type_text("2 dup * sqrt").keys("Enter");
+        driver.assert_stack(&["1", "2"]);
+        driver.type_text("6 mean").keys("Enter");
+        driver.assert_stack(&["3"]);
+        driver.keys("u");
+        assert_eq!(driver.calculator().input, "u"); // Letters are text now; Alt+u undoes
+        driver.keys("Backspace Alt+u");
+        driver.assert_stack(&["1", "2"]);
+        driver.type_text("5 frob drop").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E401"));
+        assert_eq!(driver.calculator().input, "frob drop");
+        driver.assert_stack(&["1", "2", "5"]);
+        driver.keys("Alt+c").type_text("9+");
+        driver.assert_stack(&["1", "2", "14"]);
+    }
+
+    #[test]
+    fn inspect_popup() {
+        let mut driver = Driver::new();
+        driver.keys("i");
+        assert!(!driver.app.show_inspect); // Nothing to inspect
+        driver.type_text("255").keys("Enter").type_text("0.5").keys("Enter Down i");
+        assert_eq!(driver.app.focus(), Focus::Inspect);
+        assert_eq!(driver.calculator().selected_entry().map(|entry| &*entry.expression), Some("255"));
+        driver.keys("7 Enter");
+        assert!(!driver.app.show_inspect && driver.calculator().input.is_empty());
+        driver.assert_stack(&["255", "0.5"]);
+        driver.keys("m").type_text("i");
+        assert!(!driver.app.show_inspect && driver.calculator().input == "i");
+        driver.keys("Alt+i");
+        assert!(driver.app.show_inspect);
+        driver.keys("q");
+        assert!(driver.has_quit());
+    }
+
+    #[test]
+    fn small_terminals() {
+        let mut driver = Driver::new();
+        driver.keys("2 Enter");
+        assert!(driver.render(100, 40).contains("Stack"));
+        let tiny = driver.render(30, 12);
+        assert!(tiny.contains("Terminal too small") && tiny.contains("30×12, needs 40×25"));
+        // Nothing panics at the edges, popups included
+        let sizes = [(1, 1), (40, 25), (39, 25), (40, 24), (200, 3)];
+        for (width, height) in sizes {
+            driver.render(width, height);
+        }
+        for popup in ["h", "F5", "i", "F10", "F11", "Ctrl+o"] {
+            driver.keys(popup);
+            for (width, height) in sizes {
+                driver.render(width, height);
+            }
+            driver.keys("Esc");
+        }
+        driver.keys("q");
+        assert!(driver.has_quit());
+    }
+
+    #[test]
+    fn clicking_focuses_panels() {
+        let mut driver = Driver::new();
+        driver.keys("1 Enter 2 Enter 3 Enter");
+        driver.render(100, 40);
+        let center = |driver: &Driver, panel: Panel| {
+            let (_, area) = driver.app.panel_areas.iter().find(|(p, _)| *p == panel).copied().unwrap();
+            (area.x + area.width / 2, area.y + area.height / 2)
+        };
+        let (column, row) = center(&driver, Panel::History);
+        driver.app.click(column, row);
+        assert_eq!(driver.app.panel_focus, Panel::History);
+        driver.keys("Up"); // Recalls the oldest entry, like PageUp
+        assert_eq!(driver.calculator().input, "1");
+        assert_eq!(driver.calculator().stack_position, 0);
+        let (column, row) = center(&driver, Panel::Input);
+        driver.app.click(column, row);
+        driver.keys("4 5 Up");
+        assert_eq!((driver.calculator().input.as_str(), driver.calculator().cursor), ("145", 0));
+        driver.app.click(0, 1); // The mode boxes aren't a panel
+        assert_eq!(driver.app.panel_focus, Panel::Input);
+        let (column, row) = center(&driver, Panel::Stack);
+        driver.app.click(column, row);
+        driver.keys("c Down");
+        assert_eq!(driver.calculator().stack_position, 1);
+    }
+
+    #[test]
+    fn long_lists_scroll() {
+        let mut driver = Driver::new();
+        for n in 1..=30 {
+            driver.type_text(&n.to_string()).keys("Enter");
+        }
+        // Top of the stack and the newest history in view, with scrollbars
+        let frame = driver.render(60, 30);
+        assert!(frame.contains("│1 30 = 30 ←") && !frame.contains("│9 22 = 22"));
+        assert!(frame.contains("│30 ") && frame.contains('█'));
+        // Browsing to the oldest entry scrolls it into view
+        for _ in 0..29 {
+            driver.keys("Down");
+        }
+        let frame = driver.render(60, 30);
+        assert!(frame.contains("│30 1 = 1 ←") && !frame.contains("│1 30 = 30"));
+        assert_eq!(driver.app.stack_list_state.offset(), 22);
+    }
+
+    #[test]
+    fn jumping_and_held_keys() {
+        let mut driver = Driver::new();
+        for n in 1..=60 {
+            driver.type_text(&n.to_string()).keys("Enter");
+        }
+        driver.keys("End");
+        assert_eq!(driver.calculator().stack_position, 59);
+        assert!(driver.render(60, 30).contains("│60 1 = 1 ←"));
+        driver.keys("Home");
+        assert_eq!(driver.calculator().stack_position, 0);
+        // A page is the rows the stack panel showed
+        driver.keys("Ctrl+Down");
+        let rows = driver.app.panel_areas.iter().find(|(panel, _)| *panel == Panel::Stack).unwrap().1.height as usize - 2;
+        assert_eq!(driver.calculator().stack_position, rows);
+        driver.keys("Ctrl+Up Ctrl+Up");
+        assert_eq!(driver.calculator().stack_position, 0);
+        // Ten rows one at a time, then five at a time
+        driver.hold("Down", 15);
+        assert_eq!(driver.calculator().stack_position, 40);
+        driver.hold("Down", 30).keys("Up");
+        assert_eq!(driver.calculator().stack_position, 58);
+
+        driver.app.panel_focus = Panel::History;
+        driver.type_text("7").keys("Home");
+        assert_eq!(driver.calculator().input, "1");
+        driver.keys("End");
+        assert_eq!((driver.calculator().history_position, driver.calculator().input.as_str()), (60, ""));
+    }
+
+    #[test]
+    fn cached_rows_follow_changes() {
+        let mut driver = Driver::new();
+        driver.keys("2 5 5 Enter 3 Enter");
+        assert!(driver.render(60, 30).contains("│2 255 = 255"));
+        driver.keys("F2"); // HEX
+        assert!(driver.render(60, 30).contains("│2 255 = 0xFF"));
+        driver.keys("F2 F2").type_text(":map * 2").keys("Enter");
+        let frame = driver.render(60, 30);
+        assert!(frame.contains("│2 255 * 2 = 510") && frame.contains("│1 3 * 2 = 6"));
+        driver.keys("r"); // Raw expressions
+        assert!(driver.render(60, 30).contains("│1 (3 * 2) = 6"));
+        driver.keys("r Ctrl+c y m").type_text("1.5 + 1").keys("Enter");
+        assert!(driver.render(60, 30).contains("│1 1.5 + 1 = 2.5"));
+        driver.type_text(":set decimal comma").keys("Enter");
+        let frame = driver.render(60, 30);
+        assert!(frame.contains("│1 1,5 + 1 = 2,5") && !frame.contains("2.5"), "{frame}");
+    }
+
+    #[test]
+    fn cached_history_rows_keep_their_comments() {
+        let mut driver = Driver::new();
+        driver.keys("m").type_text("2+3 # tax").keys("Enter");
+        driver.render(80, 30);
+        driver.type_text("2+3 # tip").keys("Enter");
+        let frame = driver.render(80, 30);
+        assert!(frame.contains("2+3 = 5  # tax") && frame.contains("2+3 = 5  # tip"), "{frame}");
+    }
+
+    #[test]
+    fn multibyte_rows_truncate() {
+        let mut driver = Driver::new();
+        driver.type_text(":set uncertainty on").keys("Enter m");
+        // ± is two bytes, so one of these lengths used to cut through it
+        for padding in ["", "0+", "00+"] {
+            driver.type_text(&format!("{}{}", padding, "(2 ± 0.1) + ".repeat(6))).type_text("1").keys("Enter");
+        }
+        let frame = driver.render(100, 40);
+        assert!(frame.contains("│1 0 + 2 ± 0.1 + 2 ± 0.1 + 2 ± 0.1 + 2 ± 0.1 + 2 ±... = 13.00 ± 0.24 ←"));
+        assert!(frame.contains("│3 2 ± 0.1 + 2 ± 0.1 + 2 ± 0.1 + 2 ± 0.1 + 2 ± 0.1... = 13.00 ± 0.24"));
+    }
+
+    #[test]
+    fn timing_with_the_stopwatch() {
+        let mut driver = Driver::new();
+        driver.keys("F11 Space");
+        assert!(driver.app.show_stopwatch && driver.app.stopwatch.is_running());
+        assert_eq!(driver.app.focus(), Focus::Stopwatch);
+        driver.keys("Esc");
+        assert!(!driver.app.show_stopwatch && driver.app.stopwatch.is_running(), "closing leaves it running");
+        driver.keys("F11 Space Enter");
+        assert_eq!(driver.calculator().stack().len(), 1);
+        assert!(driver.calculator().notice.as_deref().is_some_and(|notice| notice.starts_with("Stopwatch time pushed: ")));
+
+        driver.keys("Tab Down Down Down Down Down Down PageUp");
+        assert_eq!(driver.app.stopwatch.countdown, std::time::Duration::from_secs(70));
+        driver.keys("r Esc u").assert_stack(&[]);
+    }
+
+    #[test]
+    fn presentation_layout() {
+        let mut driver = Driver::new();
+        driver.keys("1 2 Enter");
+        let frame = driver.render(60, 30);
+        assert!(frame.contains("Mode") && frame.contains("Quick Help"));
+        driver.keys("z");
+        assert_eq!(driver.app.layout, Layout::Present);
+        let frame = driver.render(60, 30);
+        assert!(!frame.contains("Mode") && !frame.contains("Quick Help"));
+        assert!(frame.contains(" █  ███ ") && frame.contains("██    █ "), "12 in block digits");
+        // Too wide for block digits: shown as it is
+        driver.type_text("123456789012345").keys("Enter");
+        assert!(driver.render(60, 30).contains("123456789012345"));
+        assert!(driver.render(40, 21).contains("needs 40×22"));
+        driver.keys("z");
+        assert_eq!(driver.app.layout, Layout::Full);
+    }
+
+    #[test]
+    fn seven_segment_display() {
+        let mut driver = Driver::new();
+        assert!(!driver.render(60, 30).contains("Display"));
+        driver.keys("l");
+        assert!(driver.app.show_display);
+        let frame = driver.render(60, 30);
+        assert!(frame.contains("Display") && frame.contains("█▀█ │"), "0 until something is typed");
+        // What is being typed, then the top of the stack
+        driver.type_text("1.5");
+        assert!(driver.render(60, 30).contains("█▀▀ │") && driver.render(60, 30).contains("▀▀ ▀▀▀ │"));
+        driver.keys("Enter").type_text("7");
+        assert!(driver.render(60, 30).contains("▀▀█ │"));
+        assert!(driver.render(60, 29).contains("needs 40×30"));
+        driver.keys("Backspace l");
+        assert!(!driver.render(60, 30).contains("Display"));
+        driver.assert_stack(&["1.5"]);
+    }
+
+    #[test]
+    fn input_history_keeps_failed_lines() {
+        let mut driver = Driver::new();
+        driver.keys("m").type_text("2 +* 3").keys("Enter");
+        assert!(driver.error_code().is_some());
+        driver.keys("Alt+c").type_text("1 + 1").keys("Enter").assert_stack(&["2"]);
+        driver.type_text("7 -");
+        driver.keys("Alt+Up");
+        assert_eq!(driver.calculator().input, "1 + 1");
+        driver.keys("Alt+Up");
+        assert_eq!(driver.calculator().input, "2 +* 3");
+        assert_eq!(driver.calculator().history.len(), 1, "the failed line isn't in the history panel");
+        driver.keys("Alt+Down Alt+Down");
+        assert_eq!(driver.calculator().input, "7 -");
+        // Fix the typo and run it again
+        driver.keys("Alt+c Alt+Up Alt+Up Left Left Left Backspace Enter").assert_stack(&["2", "6"]);
+    }
+
+    #[test]
+    fn error_log_keeps_past_errors() {
+        let mut driver = Driver::new();
+        driver.keys("Ctrl+o");
+        assert_eq!(driver.app.focus(), Focus::ErrorLog);
+        assert!(driver.render(80, 30).contains("No errors this session"));
+        driver.keys("Esc m").type_text("6 / 0").keys("Enter Alt+c").type_text("2 +* 3").keys("Enter Alt+c");
+        driver.type_text("1 + 1").keys("Enter");
+        assert_eq!(driver.error_code(), None);
+        assert_eq!(driver.app.error_log.len(), 2);
+        driver.keys("Ctrl+o");
+        let frame = driver.render(100, 30);
+        assert!(frame.contains("Errors (2)") && frame.contains("E201  Division by zero") && frame.contains("input: 6 / 0"), "{}", frame);
+        assert!(frame.find("input: 2 +* 3") < frame.find("input: 6 / 0"), "newest first");
+        driver.keys("Down Enter");
+        assert!(!driver.app.show_error_log);
+        assert_eq!(driver.calculator().input, "6 / 0");
+    }
+
+    #[test]
+    fn warnings_show_and_are_logged() {
+        let mut driver = Driver::new();
+        driver.type_text("9007199254740993").keys("Enter");
+        assert_eq!(driver.calculator().warning.as_ref().map(|warning| warning.code()), Some("W101"));
+        assert!(driver.render(100, 30).contains("Warning W101: 9007199254740993 has more digits"));
+        driver.keys("Up");
+        assert!(driver.calculator().warning.is_none(), "gone with the next key");
+        driver.keys("Ctrl+k F2 F4").type_text("7F").keys("Enter").type_text("4").keys("Enter *");
+        assert_eq!(driver.calculator().word_size, WordSize::Bits8);
+        assert_eq!(driver.calculator().warning.as_ref().map(ToString::to_string).as_deref(), Some("508 doesn't fit in 8 bits and is shown wrapped to them"));
+        assert_eq!((driver.app.error_log.len(), driver.app.error_log.warnings()), (2, 2));
+        driver.keys("Ctrl+o");
+        assert!(driver.render(100, 30).contains("Errors (0) · warnings (2)"));
+    }
+
+    #[test]
+    fn pasted_amounts_are_cleaned_up() {
+        let mut driver = Driver::new();
+        driver.paste("$1,234.56").keys("Enter").assert_stack(&["1234.56"]);
+        driver.paste("(12,5 €)");
+        assert_eq!(driver.calculator().input, "-12.5");
+        assert_eq!(driver.calculator().notice.as_deref(), Some("Pasted (12,5 €) as -12.5"));
+        driver.keys("Alt+c");
+        driver.app.calculator.paste_decimal = Some(DecimalSeparator::Comma);
+        driver.paste("1.234").keys("Enter").assert_stack(&["1234.56", "1234"]);
+        // Expressions are left alone
+        driver.keys("m").paste("max(1,5) + 2");
+        assert_eq!(driver.calculator().input, "max(1,5) + 2");
+    }
+
+    #[test]
+    fn pasted_expressions_are_not_tables() {
+        let mut driver = Driver::new();
+        driver.keys("m");
+        for text in ["iterate(x/2 + 1/x, 1, 10)", "[1,2,3]", "if(1 < 2, 3, 4)", "sum(k, 1, 10)"] {
+            driver.paste(text);
+            assert!(driver.calculator().table_import.is_none(), "{text} opened the column picker");
+            assert_eq!(driver.calculator().input, text);
+            driver.keys("Alt+c");
+        }
+        // Tabs or several lines are still a table
+        driver.paste("1\t2\t3");
+        assert!(driver.calculator().table_import.is_some());
+        driver.app.calculator.cancel_table_import();
+        driver.paste("a,b\n1,2\n");
+        assert!(driver.calculator().table_import.is_some());
+    }
+
+    #[test]
+    fn base_prefixes_in_any_mode() {
+        let mut driver = Driver::new();
+        // x, b and o after a leading 0 are typed, not the TAX+ or other shortcuts, and so are hex digits after 0x
+        driver.type_text("0xff").keys("Enter").type_text("0b101").keys("Enter").type_text("0o17").keys("Enter");
+        driver.assert_stack(&["255", "5", "15"]);
+        driver.keys("Ctrl+k F2 F2").type_text("0xA").keys("Enter").type_text("0o7").keys("Enter").type_text("11").keys("Enter");
+        driver.keys("F2").assert_stack(&["10", "7", "3"]);
+        // In HEX mode 0b followed by hex digits is still hex
+        driver.keys("Ctrl+k F2").type_text("0bad").keys("Enter").type_text("0b11").keys("Enter");
+        driver.keys("F2 F2").assert_stack(&["2989", "3"]);
+        driver.keys("m").type_text("0o10 + 0XF + 0B1").keys("Enter");
+        assert_eq!(driver.calculator().stack.last().and_then(|entry| entry.result.as_real()), Some(24.0));
+    }
+
+    #[test]
+    fn peeking_at_other_bases() {
+        let mut driver = Driver::new();
+        driver.type_text("255").keys("Enter").type_text("1.5").keys("Enter b");
+        assert_eq!(driver.calculator().notice.as_deref(), Some("s1 isn't an integer, so it has no hex, octal or binary form"));
+        driver.keys("Down b");
+        assert_eq!(driver.calculator().notice.as_deref(), Some("s2 = 0xFF = 0o377 = 0b11111111"));
+        assert!(driver.render(100, 30).contains("s2 = 0xFF"));
+        // In HEX mode with an 8-bit word size
+        driver.keys("Up F2 F4 Ctrl+k 1 Enter n Alt+b");
+        assert_eq!(driver.calculator().notice.as_deref(), Some("s1 = -1 = 0o377 = 0b11111111"));
+        assert_eq!(driver.calculator().base_mode, tuic_core::BaseMode::Hexadecimal);
+    }
+
+    #[test]
+    fn si_prefixes_when_enabled() {
+        let mut driver = Driver::new();
+        // Off by default, so m still switches mode after a digit
+        driver.type_text("5m");
+        assert_eq!(driver.calculator().mode, CalculatorMode::Infix);
+        driver.keys("Alt+c Alt+m").type_text(":set si on").keys("Enter");
+        driver.type_text("4.7k").keys("Enter").type_text("5m").keys("Enter").type_text("2.2M").keys("Enter");
+        driver.assert_stack(&["4700", "0.005", "2200000"]);
+        assert_eq!(driver.calculator().mode, CalculatorMode::RPN);
+        driver.keys("m Ctrl+k").type_text("100n * 2u + 1k / 4").keys("Enter");
+        assert_eq!(driver.calculator().stack.last().and_then(|entry| entry.result.as_real()), Some(250.0000000000002));
+        // A prefix letter that starts a name is left alone
+        driver.type_text("2pi").keys("Enter");
+        assert!(driver.calculator().error.is_some());
+    }
+
+    #[test]
+    fn python_syntax_when_enabled() {
+        let mut driver = Driver::new();
+        driver.keys("m").type_text("2**10").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E101"));
+        driver.keys("Ctrl+l Alt+c").type_text(":set python on").keys("Enter");
+        for expression in ["2**10", "2 ** 3 ** 2", "7 // 2", "(0 - 7) // 2", "1 + 9 // 2 * 2", "1_000_000 + 0xff_ff"] {
+            driver.type_text(expression).keys("Enter");
+        }
+        driver.assert_stack(&["1024", "512", "3", "-4", "9", "1065535"]);
+        // An underscore only groups digits
+        driver.type_text("1__0").keys("Enter");
+        assert!(driver.calculator().error.is_some());
+        driver.keys("Ctrl+l Alt+c").type_text("1_").keys("Enter");
+        assert!(driver.calculator().error.is_some());
+    }
+
+    #[test]
+    fn polar_complex_input() {
+        let mut driver = Driver::new();
+        driver.keys("m").type_text(":set angle deg").keys("Enter").type_text("1∠30").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E216"));
+        driver.keys("Ctrl+l Alt+c").type_text(":set complex on").keys("Enter").type_text(":set precision 2").keys("Enter").keys("F3");
+        // < is an angle only while complex results show in polar form, and <= still compares
+        driver.type_text("2<90").keys("Enter").type_text("2 * 5∠-30").keys("Enter").type_text("!1 * 2").keys("Enter").type_text("1 <= 2").keys("Enter");
+        driver.assert_stack(&["2.00 ∠ 90.00°", "10.00 ∠ -30.00°", "4.00 ∠ 90.00°", "1.00"]);
+        driver.assert_history(&["2∠90 = 2.00 ∠ 90.00°", "2 * 5∠-30 = 10.00 ∠ -30.00°", "(2.00 ∠ 90.00°) * 2 = 4.00 ∠ 90.00°", "1 <= 2 = 1.00"]);
+        // An angle marked in degrees is converted in radians mode
+        driver.type_text(":set angle rad").keys("Enter").type_text("1∠30° - 1∠0.5236rad").keys("Enter");
+        assert_eq!(driver.calculator().stack.last().map(|entry| entry.result.as_complex().magnitude() < 1e-4), Some(true));
+        // 2∠90 was typed in degrees, so the script, which is in radians now, writes the value
+        driver.type_text(":export script").keys("Enter");
+        let script = driver.calculator().clipboard.clone().unwrap_or_default();
+        assert!(script.contains("\n2∠1.5707963267948966rad # from 2∠90\n"), "{}", script);
+        let mut replay = Calculator::new();
+        assert!(crate::batch::run(&mut replay, script.as_bytes(), &mut Vec::new(), crate::batch::Format::Plain).unwrap());
+        let phases = |calculator: &Calculator| calculator.stack().iter().map(|entry| (entry.result.as_complex().phase() * 1e9).round()).collect::<Vec<_>>();
+        assert_eq!(phases(&replay), phases(driver.calculator()));
+    }
+
+    #[test]
+    fn polar_input_follows_the_complex_mode() {
+        let mut driver = Driver::new();
+        driver.keys("m").type_text(":set complex on").keys("Enter").keys("F3").type_text("5<30");
+        assert!(driver.calculator().preview().is_some_and(|preview| preview.contains('∠')));
+        // Back in rectangular form the same text is a comparison again, not the parse from before
+        driver.keys("F3 Enter").assert_stack(&["1"]);
+    }
+
+    #[test]
+    fn decibel_conversions() {
+        let mut driver = Driver::new();
+        driver.type_text("100").keys("Enter").type_text(":db").keys("Enter").type_text("1").keys("Enter").type_text(":w2dbm").keys("Enter");
+        driver.assert_stack(&["20", "30"]);
+        driver.type_text("0").keys("Enter").type_text(":db").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E211"));
+        driver.keys("m").type_text("dbv(10) + dbm2w(0)").keys("Enter");
+        assert_eq!(driver.calculator().stack.last().and_then(|entry| entry.result.as_real()), Some(20.001));
+    }
+
+    #[test]
+    fn temperature_conversions_are_labeled() {
+        let mut driver = Driver::new();
+        driver.type_text("100").keys("Enter").type_text(":c2f").keys("Enter");
+        driver.assert_stack(&["212"]);
+        assert_eq!(driver.calculator().history.last().and_then(|entry| entry.comment.as_deref()), Some("°C → °F"));
+        driver.keys("m").type_text("k2c(300) + 1").keys("Enter").type_text("f2c(212)").keys("Enter");
+        driver.assert_stack(&["212", "27.85", "100"]);
+        let labels: Vec<_> = driver.calculator().history.iter().map(|entry| entry.comment.as_deref()).collect();
+        assert_eq!(labels[labels.len() - 2..], [None, Some("°F → °C")]);
+        assert!(driver.render(100, 30).contains("f2c(212) = 100  # °F → °C"));
+        driver.type_text("k2c(0 - 1)").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E211"));
+    }
+
+    #[test]
+    fn comparing_before_and_after() {
+        let mut driver = Driver::new();
+        driver.keys("v");
+        assert_eq!(driver.error_code(), Some("E301"));
+        driver.type_text("80").keys("Enter").type_text("120").keys("Enter").type_text("150").keys("Enter v");
+        assert_eq!(driver.app.focus(), Focus::Compare);
+        let screen = driver.render(100, 30);
+        assert!(screen.contains("Before (s2)") && screen.contains("25%"), "{}", screen);
+        driver.keys("Down Down Enter");
+        driver.assert_stack(&["80", "120", "150", "25"]);
+        let entry = driver.calculator().history.last().unwrap();
+        assert_eq!((entry.expression.as_str(), entry.comment.as_deref()), ("(150 - 120) / abs(120) * 100", Some("% change s2 → s1")));
+        // The selected entry is "before", and u takes the pushed value back
+        driver.keys("u Down Down v Down Enter");
+        assert!(driver.calculator().comparison.is_none());
+        assert_eq!(driver.calculator().stack.last().and_then(|entry| entry.result.as_real()), Some(1.875));
+        driver.keys("v Esc");
+        assert!(driver.calculator().comparison.is_none());
+    }
+
+    #[test]
+    fn pathological_input_is_refused() {
+        let mut driver = Driver::new();
+        driver.keys("m");
+        // Right at the default limits, which the evaluator handles
+        driver.paste(&format!("{}1{}", "(".repeat(64), ")".repeat(64))).keys("Enter");
+        driver.paste(&format!("{}1", "1 ^ ".repeat(249))).keys("Enter");
+        driver.assert_stack(&["1", "1"]);
+        driver.paste(&format!("{}1{}", "(".repeat(65), ")".repeat(65))).keys("Enter");
+        assert_eq!(driver.error_code(), Some("E110"));
+        driver.keys("Alt+c").paste(&format!("{}1", "1 + ".repeat(250))).keys("Enter");
+        assert_eq!(driver.error_code(), Some("E111"));
+
+        let mut config = Config::parse("max-input-length = 10\nmax-nesting = 2\nmax-tokens = 7").unwrap();
+        config.mode = None;
+        driver.app.calculator.apply_config(&config);
+        driver.keys("Alt+c").type_text("((1))").keys("Enter").type_text("(((1)))").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E110"));
+        assert_eq!(driver.calculator().error.as_ref().and_then(|error| error.span()), Some(2..3));
+        driver.keys("Alt+c").type_text("1+2+3+4+5").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E111"));
+        driver.keys("Alt+c").type_text("1 + 2 + 3.000").keys("Enter");
+        assert_eq!(driver.calculator().error.as_ref().map(ToString::to_string).as_deref(), Some("Input of 13 characters is over the limit of 10 (max-input-length)"));
+    }
+
+    #[test]
+    fn idle_ticks_redraw_only_what_moves() {
+        let mut driver = Driver::new();
+        driver.app.needs_redraw = false;
+        driver.app.on_tick();
+        assert!(!driver.app.needs_redraw, "the caret is still on");
+        driver.app.on_tick();
+        assert!(driver.app.needs_redraw, "the caret blinked off");
+        driver.app.needs_redraw = false;
+        driver.app.on_tick();
+        assert!(!driver.app.needs_redraw);
+        // A running stopwatch is redrawn on every tick
+        driver.keys("F11 Space");
+        driver.app.needs_redraw = false;
+        driver.app.on_tick();
+        assert!(driver.app.needs_redraw);
+    }
+
+    #[test]
+    fn angle_unit_conversion() {
+        let mut driver = Driver::new();
+        driver.keys("a");
+        assert_eq!(driver.error_code(), Some("E301"));
+        driver.type_text("90").keys("Enter").type_text("1").keys("Enter").keys("F1");
+        assert_eq!(driver.calculator().angle_mode, AngleMode::Degrees);
+        driver.keys("Down a");
+        assert_eq!(driver.app.focus(), Focus::Angles);
+        let screen = driver.render(100, 30);
+        assert!(screen.contains("s2 read as degrees") && screen.contains("100 grad") && screen.contains("0.25 turn"), "{}", screen);
+        driver.keys("Down Enter");
+        driver.assert_stack(&["100", "1"]);
+        let entry = driver.calculator().history.last().unwrap();
+        assert_eq!((entry.expression.as_str(), entry.comment.as_deref()), ("90 / 360 * 400", Some("s2 ° → grad")));
+        driver.keys("u").assert_stack(&["90", "1"]);
+        // Enter on the unit it already is in, or Esc, leaves it
+        driver.keys("a Enter a Esc").assert_stack(&["90", "1"]);
+        assert!(driver.calculator().angle_conversion.is_none());
+    }
+
+    #[test]
+    fn stack_and_history_limits() {
+        let config = Config::parse("stack-size = 3\nhistory-size = \"unlimited\"").unwrap();
+        let mut driver = Driver::new();
+        driver.app.calculator.apply_config(&config);
+        driver.keys("1 Enter 2 Enter 3 Enter 4 Enter").assert_stack(&["2", "3", "4"]);
+        assert!(driver.render(100, 30).contains("Stack: main (3 of max 3 items, 1 oldest dropped)"));
+        // Undo doesn't bring back the dropped entry, nor go past the limit
+        driver.keys("5 Enter u").assert_stack(&["2", "3", "4"]);
+
+        driver.type_text(":limits").keys("Enter");
+        assert_eq!(driver.app.focus(), Focus::Limits);
+        driver.keys("Right");
+        assert_eq!(driver.calculator().stack_limit, 100);
+        driver.keys("Down Left");
+        assert_eq!(driver.calculator().history_limit, 100_000);
+        driver.keys("Left Left Left Left Left Left");
+        assert_eq!(driver.calculator().history_limit, 100);
+        let screen = driver.render(100, 30);
+        assert!(screen.contains("◀ 100 ▶") && screen.contains("History (5 items)"), "{}", screen);
+        driver.keys("Esc");
+        assert!(driver.calculator().limits_dialog.is_none());
+        driver.keys("6 Enter 7 Enter").assert_stack(&["2", "3", "4", "6", "7"]);
+    }
+
+    #[test]
+    fn settings_are_applied_and_saved() {
+        let path = std::env::temp_dir().join(format!("tuic-settings-{}", std::process::id())).join("config.toml");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, "# mine\nprecision = 2\n").unwrap();
+        let mut driver = Driver::new();
+        driver.app.config_path = Some(path.clone());
+        driver.type_text(":settings").keys("Enter");
+        assert_eq!(driver.app.focus(), Focus::Settings);
+        assert!(driver.render(100, 30).contains("◀ auto ▶"));
+
+        driver.keys("Right Right");
+        assert_eq!(driver.calculator().precision, Some(1));
+        driver.keys("Down Down Down Right");
+        assert_eq!(driver.calculator().mode, CalculatorMode::Infix);
+        driver.keys("Down Down Down Right");
+        assert!(driver.app.vi_mode.is_some());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "# mine\nprecision = 1\nmode = \"infix\"\nkeys = \"vi\"\n");
+        let config = Config::parse(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(config.mode, Some(CalculatorMode::Infix));
+
+        driver.keys("Esc");
+        assert!(driver.calculator().settings_dialog.is_none());
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn quick_reference_per_mode() {
+        let mut driver = Driver::new();
+        driver.keys("?");
+        assert_eq!(driver.app.focus(), Focus::QuickRef);
+        let screen = driver.render(120, 50);
+        assert!(screen.contains("RPN quick reference") && screen.contains("Push π") && screen.contains(":sqrt"), "{}", screen);
+        driver.keys("? m ?");
+        let screen = driver.render(120, 50);
+        assert!(screen.contains("Infix quick reference") && screen.contains("Alt+p") && screen.contains("sqrt(x)"), "{}", screen);
+        driver.keys("Esc").type_text("2?");
+        assert_eq!(driver.calculator().input, "2", "'?' isn't typed into an expression");
+        driver.keys("Esc Alt+c Alt+m F2 ?");
+        let screen = driver.render(120, 50);
+        assert!(screen.contains("Programmer quick reference") && screen.contains("0-9 and a-f are digits") && screen.contains("Alt+e"), "{}", screen);
+        driver.keys("h");
+        assert!(driver.app.show_help && !driver.app.show_quick_ref);
+    }
+
+    #[test]
+    fn about_popup() {
+        let mut driver = Driver::new();
+        driver.type_text(":about").keys("Enter");
+        assert_eq!(driver.app.focus(), Focus::About);
+        let screen = driver.render(110, 32);
+        assert!(screen.contains(&format!("tui-calculator {}", env!("CARGO_PKG_VERSION"))));
+        assert!(screen.contains("Session journal") && screen.contains("history.json"));
+        // No config file in tests, and no log unless log-level is set
+        assert!(screen.contains("Config           off") && screen.contains("Log              off"));
+        driver.keys("Esc");
+        assert!(!driver.calculator().show_about);
+    }
+
+    #[test]
+    fn export_script_replays_the_stack() {
+        let mut driver = Driver::new();
+        driver.keys("F1 2 Enter 3 Enter + 4 * 7 Enter n m").type_text("sin(90) # right angle").keys("Enter");
+        driver.assert_stack(&["20", "-7", "1"]);
+        driver.type_text(":export script").keys("Enter");
+        let script = driver.calculator().clipboard.clone().unwrap_or_default();
+        let lines: Vec<&str> = script.lines().skip(1).collect();
+        // Operands the sum and product used up are comments; 7, negated in place, is pushed as -7
+        assert_eq!(lines, [":set angle deg", "# 2", "# 3", "# (2 + 3)", "# 4", "((2 + 3) * 4)", "# 7", "# sin(90) # right angle", "0 - 7 # from 7", "sin(90)"]);
+        let mut replay = Calculator::new();
+        let mut output = Vec::new();
+        assert!(crate::batch::run(&mut replay, script.as_bytes(), &mut output, crate::batch::Format::Plain).unwrap());
+        assert_eq!(String::from_utf8(output).unwrap(), "20\n-7\n1\n");
+        let values = |calculator: &Calculator| calculator.stack().iter().map(|entry| entry.result.clone()).collect::<Vec<_>>();
+        assert_eq!(values(&replay), values(driver.calculator()));
+    }
+
+    #[test]
+    fn find_value_in_stack_and_history() {
+        let mut driver = Driver::new();
+        driver.keys("4 2 . 3 7 1 2 Enter 7 Enter 3 Enter");
+        // Rounded as shown, the figure is found at full precision: the stack first, then history
+        driver.type_text(":find 42.37").keys("Enter");
+        assert_eq!(driver.calculator().stack_position, 2);
+        assert_eq!(driver.app.panel_focus, Panel::Stack);
+        assert_eq!(driver.calculator().notice.as_deref(), Some("42.37 is at stack level 3 (match 1 of 2, within ±0.005); :find again for the next"));
+        driver.type_text(":find").keys("Enter");
+        assert_eq!((driver.calculator().history_position, driver.calculator().input.as_str()), (0, "42.3712"));
+        assert_eq!(driver.app.panel_focus, Panel::History);
+        // Round again to the first
+        driver.keys("C").type_text(":find").keys("Enter");
+        assert_eq!(driver.app.panel_focus, Panel::Stack);
+        assert!(driver.calculator().notice.as_deref().is_some_and(|notice| notice.contains("match 1 of 2")));
+        driver.type_text(":find 42.37 0.0001").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E413"));
+        driver.keys("Ctrl+l").type_text(":find x").keys("Enter");
+        assert_eq!(driver.error_code(), Some("E402"));
+    }
+
+    #[test]
+    fn signed_number_entry() {
+        let mut driver = Driver::new();
+        // Nothing to subtract from yet, so '-' starts the number; after an e it's the exponent's
+        driver.type_text("-5").keys("Enter").type_text("2e-3").keys("Enter").assert_stack(&["-5", "0.002"]);
+        // With two entries it subtracts, as it always has
+        driver.type_text("-").assert_stack(&["-5.002"]);
+        driver.type_text("1.5-").assert_stack(&["-6.502"]);
+        // n flips the sign of what's typed, or of its exponent, and leaves the stack alone
+        driver.type_text("4").keys("n");
+        assert_eq!(driver.calculator().input, "-4");
+        driver.keys("n");
+        assert_eq!(driver.calculator().input, "4");
+        driver.keys("Backspace").type_text("1e3").keys("n");
+        assert_eq!(driver.calculator().input, "1e-3");
+        driver.keys("Enter").assert_stack(&["-6.502", "0.001"]);
+        driver.keys("n").assert_stack(&["-6.502", "-0.001"]);
+        // e on its own is still the constant
+        driver.keys("Ctrl+k").type_text("e");
+        assert_eq!(driver.calculator().stack.len(), 1);
+        driver.type_text(":set decimal comma").keys("Enter").type_text("-1,5e-2").keys("Enter");
+        assert_eq!(driver.calculator().stack.last().map(|entry| entry.result.clone()), Some(StackValue::Real(-0.015)));
+    }
+
+    #[test]
+    fn input_validity_while_typing() {
+        let mut driver = Driver::new();
+        assert_eq!(driver.calculator().input_validity(), None, "RPN input isn't checked");
+        driver.keys("m");
+        assert_eq!(driver.calculator().input_validity(), None);
+        let mut validity = |text: &str| {
+            driver.keys("Alt+c").type_text(text);
+            driver.calculator().input_validity()
+        };
+        assert_eq!(validity("2 *"), Some(InputValidity::Incomplete));
+        assert_eq!(validity("sqrt(2"), Some(InputValidity::Incomplete));
+        assert_eq!(validity("sqrt(2) # root"), Some(InputValidity::Valid));
+        assert_eq!(validity("2 * * 3"), Some(InputValidity::Invalid));
+        assert_eq!(validity("2)"), Some(InputValidity::Invalid));
+        assert_eq!(validity(":set"), None);
+    }
+
+    #[test]
+    fn popups_capture_keys() {
+        let mut driver = Driver::new();
+        driver.keys("h 2 Enter");
+        assert!(driver.app.show_help);
+        driver.assert_stack(&[]);
+        driver.keys("Esc 2 Enter").assert_stack(&["2"]);
+        driver.keys("F10 Tab 3 Enter");
+        assert!(driver.app.show_stack_chart && driver.app.stack_chart_sparkline);
+        driver.assert_stack(&["2"]);
+        driver.keys("Esc").assert_stack(&["2"]);
+        assert!(!driver.app.show_stack_chart);
+        driver.keys("q");
+        assert!(driver.has_quit());
+    }
+}
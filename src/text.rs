@@ -0,0 +1,45 @@
+//! Cutting text to a number of terminal cells. Results are full of multi-byte
+//! characters (∠, °, ±, σ) and may hold wide ones, so byte lengths won't do.
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+const ELLIPSIS: &str = "...";
+
+/// `text` if it fits in `max_width` cells, otherwise as many whole graphemes
+/// as fit followed by "...". Never splits a character or a grapheme cluster.
+pub fn truncate(text: &str, max_width: usize) -> String {
+    if text.width() <= max_width {
+        return text.to_string();
+    }
+    let budget = max_width.saturating_sub(ELLIPSIS.len());
+    let mut width = 0;
+    let mut cut = 0;
+    for (offset, grapheme) in text.grapheme_indices(true) {
+        width += grapheme.width();
+        if width > budget {
+            break;
+        }
+        cut = offset + grapheme.len();
+    }
+    format!("{}{}", &text[..cut], &ELLIPSIS[..ELLIPSIS.len().min(max_width)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cuts_by_display_width() {
+        assert_eq!(truncate("12.5", 10), "12.5");
+        assert_eq!(truncate("1234567890", 8), "12345...");
+        // Multi-byte characters are one cell each and never split
+        assert_eq!(truncate("5 ∠ 53.13°", 7), "5 ∠ ...");
+        assert_eq!(truncate("12.3 ± 0.5", 10), "12.3 ± 0.5");
+        // Wide characters take two cells
+        assert_eq!(truncate("数値数値", 7), "数値...");
+        // A letter with a combining accent stays together
+        assert_eq!(truncate("e\u{301}e\u{301}e\u{301}e\u{301}e\u{301}", 4), "e\u{301}...");
+        assert_eq!(truncate("abcdef", 2), "..");
+    }
+}
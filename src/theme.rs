@@ -1,6 +1,43 @@
-use ratatui::style::Color;
+use ratatui::layout::Alignment;
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, BorderType, Borders};
 use serde::{Deserialize, Deserializer};
 
+/// Line set used for every panel and dialog border.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BorderSet {
+    #[default]
+    Plain,
+    Rounded,
+    Double,
+    Thick,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TitleAlignment {
+    Left,
+    Center,
+    Right,
+}
+
+/// Colors one main panel can set for itself; each one left out is the theme's
+/// global color, so a theme only names what it changes.
+#[derive(Debug, Default, Deserialize, Clone, PartialEq)]
+pub struct PanelColors {
+    #[serde(default, deserialize_with = "deserialize_optional_color")]
+    pub background: Option<Color>,
+    /// Plain text: stack separators, history lines, the input, non-error status messages
+    #[serde(default, deserialize_with = "deserialize_optional_color")]
+    pub foreground: Option<Color>,
+    #[serde(default, deserialize_with = "deserialize_optional_color")]
+    pub border: Option<Color>,
+    #[serde(default, deserialize_with = "deserialize_optional_color")]
+    pub title: Option<Color>,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct Theme {
     pub name: String,
@@ -36,6 +73,392 @@ pub struct Theme {
     pub stack_line_number: Color,
     #[serde(deserialize_with = "deserialize_color")]
     pub history_text: Color,
+    // Optional look beyond colors; older theme files leave these out
+    #[serde(default)]
+    pub border_set: BorderSet,
+    /// Overrides the default of left-aligned panel titles and centered dialog titles
+    #[serde(default)]
+    pub title_alignment: Option<TitleAlignment>,
+    #[serde(default)]
+    pub bold_titles: bool,
+    /// Border of the panel that has the keyboard (clicked with the mouse); `warning` when left out
+    #[serde(default, deserialize_with = "deserialize_optional_color")]
+    pub focused_border: Option<Color>,
+    // Per-panel overrides, e.g. "stack": { "background": "#101010" }
+    #[serde(default)]
+    pub stack: PanelColors,
+    #[serde(default)]
+    pub history: PanelColors,
+    #[serde(default)]
+    pub input: PanelColors,
+    #[serde(default)]
+    pub status: PanelColors,
+}
+
+/// Compiled in, so the calculator starts even without a themes/ directory next to it.
+const BUILTIN_DEFAULT: &str = include_str!("../themes/default.json");
+
+/// Themes that are always there, the color-blind friendly ones with the default.
+pub const BUILTIN: &[(&str, &str)] = &[
+    ("default", BUILTIN_DEFAULT),
+    ("deuteranopia", include_str!("../themes/deuteranopia.json")),
+    ("protanopia", include_str!("../themes/protanopia.json")),
+];
+
+/// Text with less contrast against its background than this is hard to read (WCAG AA for normal text).
+pub const MIN_CONTRAST: f64 = 4.5;
+
+impl Theme {
+    pub fn builtin() -> Self {
+        serde_json::from_str(BUILTIN_DEFAULT).expect("themes/default.json is a valid theme")
+    }
+
+    pub fn builtin_named(name: &str) -> Option<Self> {
+        BUILTIN.iter().find(|(builtin, _)| *builtin == name).map(|(_, json)| serde_json::from_str(json).expect("built-in themes are valid"))
+    }
+
+    /// Text colors below `MIN_CONTRAST` on what they're drawn over, as
+    /// `"info on background: 2.4:1"`. Dimmed colors (placeholder, line numbers,
+    /// borders) are meant to recede and aren't checked; neither are colors
+    /// without a fixed RGB value (indexed, the terminal's own).
+    pub fn contrast_warnings(&self) -> Vec<String> {
+        let on_background = [
+            ("foreground", self.foreground),
+            ("input_text", self.input_text),
+            ("title", self.title),
+            ("error", self.error),
+            ("success", self.success),
+            ("warning", self.warning),
+            ("info", self.info),
+            ("stack_expression", self.stack_expression),
+            ("stack_result", self.stack_result),
+            ("history_text", self.history_text),
+        ];
+        let pairs = on_background.into_iter().map(|(name, color)| (name, color, "background", self.background));
+        // A panel's own text on its own background, where it sets either
+        let panels = [("stack", &self.stack, self.foreground), ("history", &self.history, self.history_text), ("input", &self.input, self.input_text), ("status", &self.status, self.foreground)]
+            .into_iter()
+            .filter(|(_, colors, _)| colors.foreground.is_some() || colors.background.is_some())
+            .map(|(name, colors, text)| (name, colors.foreground.unwrap_or(text), "its background", colors.background.unwrap_or(self.background)));
+        pairs
+            .chain(panels)
+            .chain([("highlight_fg", self.highlight_fg, "highlight_bg", self.highlight_bg)])
+            .filter_map(|(name, color, under, background)| {
+                let ratio = contrast_ratio(color, background)?;
+                (ratio < MIN_CONTRAST).then(|| format!("{} on {}: {:.1}:1", name, under, ratio))
+            })
+            .collect()
+    }
+
+    /// Every color field, named as in a theme file, for the theme colors popup. An
+    /// optional one left out is None, with the field whose color it takes instead.
+    pub fn color_fields(&self) -> Vec<(String, Option<Color>, &'static str)> {
+        let required = [
+            self.background,
+            self.foreground,
+            self.border,
+            self.title,
+            self.highlight_bg,
+            self.highlight_fg,
+            self.error,
+            self.success,
+            self.warning,
+            self.info,
+            self.input_text,
+            self.input_placeholder,
+            self.stack_expression,
+            self.stack_result,
+            self.stack_line_number,
+            self.history_text,
+        ];
+        let mut fields: Vec<(String, Option<Color>, &'static str)> = REQUIRED_COLORS.iter().zip(required).map(|(name, color)| (name.to_string(), Some(color), "")).collect();
+        fields.push(("focused_border".to_string(), self.focused_border, "warning"));
+        // A panel's text falls back to the color that panel's text has without it, as in contrast_warnings
+        for (panel, colors, text) in [("stack", &self.stack, "foreground"), ("history", &self.history, "history_text"), ("input", &self.input, "input_text"), ("status", &self.status, "foreground")] {
+            for (name, color) in PANEL_COLORS.iter().zip([colors.background, colors.foreground, colors.border, colors.title]) {
+                let fallback = if *name == "foreground" { text } else { name };
+                fields.push((format!("{}.{}", panel, name), color, fallback));
+            }
+        }
+        fields
+    }
+
+    /// Bordered block for the main panels (stack, history, input, ...).
+    pub fn panel<'a>(&self, title: impl Into<Line<'a>>) -> Block<'a> {
+        self.block(title, Alignment::Left)
+    }
+
+    /// Bordered block for popups, centered title unless the theme says otherwise.
+    pub fn dialog<'a>(&self, title: impl Into<Line<'a>>) -> Block<'a> {
+        self.block(title, Alignment::Center)
+    }
+
+    /// A main panel in the colors it overrides.
+    pub fn panel_in<'a>(&self, colors: &PanelColors, title: impl Into<Line<'a>>) -> Block<'a> {
+        let mut block = self.panel(title);
+        if let Some(border) = colors.border {
+            block = block.border_style(Style::default().fg(border));
+        }
+        if let Some(title) = colors.title {
+            block = block.title_style(self.title_style(title));
+        }
+        if let Some(background) = colors.background {
+            block = block.style(Style::default().bg(background));
+        }
+        block
+    }
+
+    /// A main panel with the focus border.
+    pub fn focused_panel<'a>(&self, colors: &PanelColors, title: impl Into<Line<'a>>) -> Block<'a> {
+        self.panel_in(colors, title).border_style(Style::default().fg(self.focused_border.unwrap_or(self.warning)))
+    }
+
+    fn title_style(&self, color: Color) -> Style {
+        let style = Style::default().fg(color);
+        if self.bold_titles { style.add_modifier(Modifier::BOLD) } else { style }
+    }
+
+    fn block<'a>(&self, title: impl Into<Line<'a>>, default_alignment: Alignment) -> Block<'a> {
+        let border_type = match self.border_set {
+            BorderSet::Plain => BorderType::Plain,
+            BorderSet::Rounded => BorderType::Rounded,
+            BorderSet::Double => BorderType::Double,
+            BorderSet::Thick => BorderType::Thick,
+        };
+        let alignment = match self.title_alignment {
+            Some(TitleAlignment::Left) => Alignment::Left,
+            Some(TitleAlignment::Center) => Alignment::Center,
+            Some(TitleAlignment::Right) => Alignment::Right,
+            None => default_alignment,
+        };
+        Block::default()
+            .borders(Borders::ALL)
+            .border_type(border_type)
+            .border_style(Style::default().fg(self.border))
+            .title(title)
+            .title_alignment(alignment)
+            .title_style(self.title_style(self.title))
+    }
+}
+
+/// The colors every theme file has to set; everything added later is optional,
+/// so themes written before it keep loading.
+pub const REQUIRED_COLORS: &[&str] = &[
+    "background",
+    "foreground",
+    "border",
+    "title",
+    "highlight_bg",
+    "highlight_fg",
+    "error",
+    "success",
+    "warning",
+    "info",
+    "input_text",
+    "input_placeholder",
+    "stack_expression",
+    "stack_result",
+    "stack_line_number",
+    "history_text",
+];
+
+const PANELS: &[&str] = &["stack", "history", "input", "status"];
+const PANEL_COLORS: &[&str] = &["background", "foreground", "border", "title"];
+
+/// Something `theme check` found, at a 1-based line and column as editors count them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThemeProblem {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+    pub warning: bool, // Loads anyway: unknown fields, low contrast
+}
+
+/// Checks a theme file's text against what `Theme` reads (the same rules as
+/// schema/theme.schema.json): JSON syntax, missing fields, invalid colors and
+/// values, with unknown fields and low contrast as warnings.
+pub fn check(text: &str) -> Vec<ThemeProblem> {
+    let at = |offset: usize, message: String, warning: bool| {
+        let before = &text[..offset];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+        ThemeProblem { line, column, message, warning }
+    };
+    let value: serde_json::Value = match serde_json::from_str(text) {
+        Ok(value) => value,
+        Err(e) => {
+            let message = e.to_string();
+            let message = message.split(" at line ").next().unwrap_or(&message).to_string();
+            return vec![ThemeProblem { line: e.line(), column: e.column(), message, warning: false }];
+        }
+    };
+    let Some(fields) = value.as_object() else {
+        return vec![at(text.len() - text.trim_start().len(), "a theme is a JSON object".to_string(), false)];
+    };
+    let (keys, end) = key_offsets(text);
+    // Where a field is written, or the closing brace for one that's missing
+    let offset = |path: &str| keys.iter().find(|(key, _)| key == path).map_or(end, |(_, offset)| *offset);
+    let mut problems = Vec::new();
+    let color = |path: &str, value: &serde_json::Value, problems: &mut Vec<ThemeProblem>| match value.as_str().map(parse_color) {
+        Some(Ok(_)) => {}
+        Some(Err(e)) => problems.push(at(offset(path), format!("`{}`: {}", path, e), false)),
+        None => problems.push(at(offset(path), format!("`{}` should be a color string", path), false)),
+    };
+    if fields.get("name").is_some_and(|name| !name.is_string()) {
+        problems.push(at(offset("name"), "`name` should be a string".to_string(), false));
+    }
+    for name in REQUIRED_COLORS {
+        if let Some(value) = fields.get(*name) {
+            color(name, value, &mut problems);
+        }
+    }
+    // All on one line, so a theme started from scratch doesn't list seventeen
+    let missing: Vec<String> = ["name"].iter().chain(REQUIRED_COLORS).filter(|name| !fields.contains_key(**name)).map(|name| format!("`{}`", name)).collect();
+    if !missing.is_empty() {
+        problems.push(at(end, format!("missing field{} {}", if missing.len() == 1 { "" } else { "s" }, missing.join(", ")), false));
+    }
+    for (name, value) in fields {
+        let valid = match name.as_str() {
+            "name" | "$schema" => true,
+            name if REQUIRED_COLORS.contains(&name) => true,
+            "focused_border" => {
+                color(name, value, &mut problems);
+                true
+            }
+            "border_set" => matches!(value.as_str(), Some("plain" | "rounded" | "double" | "thick")),
+            "title_alignment" => matches!(value.as_str(), Some("left" | "center" | "right")),
+            "bold_titles" => value.is_boolean(),
+            panel if PANELS.contains(&panel) => match value.as_object() {
+                Some(colors) => {
+                    for (key, value) in colors {
+                        let path = format!("{}.{}", panel, key);
+                        if PANEL_COLORS.contains(&key.as_str()) {
+                            color(&path, value, &mut problems);
+                        } else {
+                            problems.push(at(offset(&path), format!("unknown field `{}`, ignored", path), true));
+                        }
+                    }
+                    true
+                }
+                None => false,
+            },
+            _ => {
+                problems.push(at(offset(name), format!("unknown field `{}`, ignored", name), true));
+                true
+            }
+        };
+        if !valid {
+            let expected = match name.as_str() {
+                "border_set" => "\"plain\", \"rounded\", \"double\" or \"thick\"",
+                "title_alignment" => "\"left\", \"center\" or \"right\"",
+                "bold_titles" => "true or false",
+                _ => "an object of colors",
+            };
+            problems.push(at(offset(name), format!("`{}` should be {}", name, expected), false));
+        }
+    }
+    if problems.iter().all(|problem| problem.warning) {
+        match serde_json::from_str::<Theme>(text) {
+            // Each warning starts with the field it's about, e.g. "info on background: 2.4:1"
+            Ok(theme) => problems.extend(theme.contrast_warnings().into_iter().map(|warning| {
+                let field = warning.split(' ').next().unwrap_or("").to_string();
+                at(offset(&field), format!("low contrast, {}", warning), true)
+            })),
+            // Anything the checks above don't cover yet
+            Err(e) => problems.push(ThemeProblem { line: e.line(), column: e.column(), message: e.to_string(), warning: false }),
+        }
+    }
+    problems.sort_by_key(|problem| (problem.line, problem.column));
+    problems
+}
+
+/// Byte offsets of the keys in valid JSON, as dotted paths (`stack.border`),
+/// and that of the closing brace of the outermost object.
+fn key_offsets(text: &str) -> (Vec<(String, usize)>, usize) {
+    let bytes = text.as_bytes();
+    let mut keys: Vec<(String, usize)> = Vec::new();
+    let mut open: Vec<String> = Vec::new(); // Path of each object or array that's open
+    let mut key = String::new(); // The last key, naming the object it opens
+    let mut end = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => {
+                let start = i;
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+                if text.get(i + 1..).unwrap_or("").trim_start().starts_with(':') {
+                    let name = &text[start + 1..i.min(text.len())];
+                    key = match open.last() {
+                        Some(parent) if !parent.is_empty() => format!("{}.{}", parent, name),
+                        _ => name.to_string(),
+                    };
+                    keys.push((key.clone(), start));
+                }
+            }
+            b'{' | b'[' => open.push(if open.is_empty() { String::new() } else { key.clone() }),
+            b'}' | b']' => {
+                open.pop();
+                if open.is_empty() {
+                    end = i;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    (keys, end)
+}
+
+/// WCAG contrast ratio, from 1 (the same) to 21 (black on white).
+pub fn contrast_ratio(a: Color, b: Color) -> Option<f64> {
+    let (a, b) = (relative_luminance(a)?, relative_luminance(b)?);
+    Some((a.max(b) + 0.05) / (a.min(b) + 0.05))
+}
+
+fn relative_luminance(color: Color) -> Option<f64> {
+    let (r, g, b) = rgb(color)?;
+    let linear = |channel: u8| {
+        let channel = channel as f64 / 255.0;
+        if channel <= 0.03928 { channel / 12.92 } else { ((channel + 0.055) / 1.055).powf(2.4) }
+    };
+    Some(0.2126 * linear(r) + 0.7152 * linear(g) + 0.0722 * linear(b))
+}
+
+/// A color as hex, `#1e1e2e`; a named one with the hex xterm shows it as, since terminals can differ.
+pub fn color_text(color: Color) -> String {
+    match (color, rgb(color)) {
+        (Color::Rgb(..), Some((r, g, b))) => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        (_, Some((r, g, b))) => format!("{} (#{:02x}{:02x}{:02x} in xterm)", format!("{:?}", color).to_lowercase(), r, g, b),
+        (Color::Indexed(index), None) => format!("color {} of the terminal's 256", index),
+        _ => "the terminal's own".to_string(),
+    }
+}
+
+/// Named colors as xterm shows them by default; terminals can be set up otherwise.
+fn rgb(color: Color) -> Option<(u8, u8, u8)> {
+    Some(match color {
+        Color::Rgb(r, g, b) => (r, g, b),
+        Color::Black => (0, 0, 0),
+        Color::Red => (205, 0, 0),
+        Color::Green => (0, 205, 0),
+        Color::Yellow => (205, 205, 0),
+        Color::Blue => (0, 0, 238),
+        Color::Magenta => (205, 0, 205),
+        Color::Cyan => (0, 205, 205),
+        Color::Gray => (229, 229, 229),
+        Color::DarkGray => (127, 127, 127),
+        Color::LightRed => (255, 0, 0),
+        Color::LightGreen => (0, 255, 0),
+        Color::LightYellow => (255, 255, 0),
+        Color::LightBlue => (92, 92, 255),
+        Color::LightMagenta => (255, 0, 255),
+        Color::LightCyan => (0, 255, 255),
+        Color::White => (255, 255, 255),
+        Color::Reset | Color::Indexed(_) => return None,
+    })
 }
 
 fn deserialize_color<'de, D>(deserializer: D) -> Result<Color, D::Error>
@@ -46,6 +469,13 @@ where
     parse_color(&s).map_err(serde::de::Error::custom)
 }
 
+fn deserialize_optional_color<'de, D>(deserializer: D) -> Result<Option<Color>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_color(deserializer).map(Some)
+}
+
 fn parse_color(s: &str) -> Result<Color, String> {
     if s.starts_with('#') && s.len() == 7 {
         let r = u8::from_str_radix(&s[1..3], 16).map_err(|_| "Invalid R color component".to_string())?;
@@ -84,3 +514,95 @@ fn parse_color(s: &str) -> Result<Color, String> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_default_loads() {
+        let theme = Theme::builtin();
+        assert_eq!(theme.name, "default");
+    }
+
+    #[test]
+    fn focused_border_is_optional() {
+        let theme: Theme = serde_json::from_str(&BUILTIN_DEFAULT.replace("\"focused_border\": \"lightyellow\",", "")).unwrap();
+        assert_eq!(theme.focused_border, None);
+        assert_eq!(Theme::builtin().focused_border, Some(Color::LightYellow));
+    }
+
+    #[test]
+    fn panels_override_global_colors() {
+        let json = BUILTIN_DEFAULT.replace("\"name\": \"default\",", "\"name\": \"panels\", \"stack\": { \"background\": \"#101010\", \"border\": \"red\" }, \"status\": { \"foreground\": \"black\" },");
+        let theme: Theme = serde_json::from_str(&json).unwrap();
+        assert_eq!(theme.stack, PanelColors { background: Some(Color::Rgb(16, 16, 16)), border: Some(Color::Red), ..PanelColors::default() });
+        assert_eq!(theme.history, PanelColors::default());
+        assert!(theme.contrast_warnings().iter().any(|warning| warning.starts_with("status on its background: ")));
+    }
+
+    #[test]
+    fn themes_from_before_the_optional_fields_load() {
+        let theme: Theme = serde_json::from_str(include_str!("../tests/fixtures/theme_v1.json")).unwrap();
+        assert_eq!(theme.name, "classic");
+        assert!(matches!(theme.border_set, BorderSet::Plain));
+        assert!(theme.title_alignment.is_none() && !theme.bold_titles && theme.focused_border.is_none());
+        assert_eq!(theme.status, PanelColors::default());
+    }
+
+    #[test]
+    fn check_points_at_the_mistakes() {
+        for (name, json) in BUILTIN {
+            assert!(check(json).iter().all(|problem| problem.warning), "{}", name);
+        }
+        let json = include_str!("../tests/fixtures/theme_v1.json").replace("  \"info\": \"blue\",\n", "").replace("\"cyan\",\n  \"title\"", "\"cyaan\",\n  \"title\"").replace("\"classic\",", "\"classic\", \"stack\": { \"border\": \"#12345\", \"shadow\": \"red\" },");
+        let problems = check(&json);
+        let problems: Vec<(usize, usize, &str, bool)> = problems.iter().map(|problem| (problem.line, problem.column, problem.message.as_str(), problem.warning)).collect();
+        assert_eq!(problems, [
+            (2, 33, "`stack.border`: Unknown color name: #12345", false),
+            (2, 53, "unknown field `stack.shadow`, ignored", true),
+            (5, 3, "`border`: Unknown color name: cyaan", false),
+            (18, 1, "missing field `info`", false),
+        ]);
+        let problems = check("{\n  \"name\": \"x\",\n  \"background\": black\n}");
+        assert_eq!((problems[0].line, problems[0].column, problems[0].message.as_str()), (3, 17, "expected value"));
+        assert_eq!(check("[]")[0].message, "a theme is a JSON object");
+    }
+
+    #[test]
+    fn the_schema_requires_what_the_checker_does() {
+        let schema: serde_json::Value = serde_json::from_str(include_str!("../schema/theme.schema.json")).unwrap();
+        let required: Vec<&str> = schema["required"].as_array().unwrap().iter().filter_map(|name| name.as_str()).collect();
+        assert_eq!(required[0], "name");
+        assert_eq!(required[1..], *REQUIRED_COLORS);
+        let properties = schema["properties"].as_object().unwrap();
+        for name in ["border_set", "title_alignment", "bold_titles", "focused_border"].iter().chain(PANELS) {
+            assert!(properties.contains_key(*name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn lists_every_color_field() {
+        let theme = Theme::builtin();
+        let fields = theme.color_fields();
+        assert_eq!(fields.len(), REQUIRED_COLORS.len() + 1 + PANELS.len() * PANEL_COLORS.len());
+        assert_eq!(fields[0], ("background".to_string(), Some(theme.background), ""));
+        assert!(fields.contains(&("history.foreground".to_string(), None, "history_text")));
+        assert_eq!(color_text(Color::Rgb(30, 30, 46)), "#1e1e2e");
+        assert_eq!(color_text(Color::LightYellow), "lightyellow (#ffff00 in xterm)");
+        assert_eq!(color_text(Color::Reset), "the terminal's own");
+    }
+
+    #[test]
+    fn checks_contrast() {
+        assert_eq!(contrast_ratio(Color::Black, Color::White).map(|ratio| ratio.round()), Some(21.0));
+        assert_eq!(contrast_ratio(Color::Reset, Color::White), None);
+        assert!(Theme::builtin().contrast_warnings().iter().any(|warning| warning.starts_with("info on background: ")));
+        // The color-blind themes are built to pass
+        for (name, _) in &BUILTIN[1..] {
+            let theme = Theme::builtin_named(name).unwrap();
+            assert_eq!(&theme.name, name);
+            assert_eq!(theme.contrast_warnings(), Vec::<String>::new(), "{}", name);
+        }
+    }
+}
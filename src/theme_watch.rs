@@ -0,0 +1,60 @@
+//! Reloads the theme in use when its file changes on disk, so a theme can be
+//! tuned with the calculator open instead of restarting or reopening the selector.
+
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+pub struct ThemeWatcher {
+    _watcher: RecommendedWatcher, // Stops watching when dropped
+    events: Receiver<notify::Result<Event>>,
+}
+
+impl ThemeWatcher {
+    /// Watches the themes directory rather than one file: editors often save by
+    /// writing a new file and renaming it over the old one.
+    pub fn start(dir: &Path) -> notify::Result<Self> {
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        Ok(Self { _watcher: watcher, events })
+    }
+
+    /// Names of the theme files written since the last call, without `.json`.
+    pub fn changed(&self) -> BTreeSet<String> {
+        self.events
+            .try_iter()
+            .filter_map(Result::ok)
+            .filter(|event| matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)))
+            .flat_map(|event| event.paths)
+            .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+            .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn sees_theme_files_change() {
+        let dir = std::env::temp_dir().join(format!("tuic-theme-watch-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let watcher = ThemeWatcher::start(&dir).unwrap();
+        fs::write(dir.join("notes.txt"), "not a theme").unwrap();
+        fs::write(dir.join("mine.json"), "{}").unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut changed = BTreeSet::new();
+        while changed.is_empty() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+            changed.extend(watcher.changed());
+        }
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(changed, BTreeSet::from(["mine".to_string()]));
+    }
+}
@@ -1,366 +1,847 @@
-use crate::calculator::{Calculator, CalculatorMode, AngleMode, BaseMode, ComplexMode}; // Added CalculatorMode, AngleMode, BaseMode, ComplexMode
+use crate::app::{App, Panel};
+use crate::bigtext;
+use crate::error_log::ErrorLog;
+use crate::keymap;
+use crate::quickref;
+use crate::stopwatch::{self, Stopwatch, TimerMode};
+use crate::text;
+use crate::theme::{self, Theme};
+use crate::update;
+use crate::vi::ViMode;
+use tuic_core::calculator::{Calculator, CalculatorMode, AngleMode, BaseMode, ComplexMode, InputValidity, StackValue, session_marker}; // Added CalculatorMode, AngleMode, BaseMode, ComplexMode
+use tuic_core::compare::Metric;
+use tuic_core::completion::CandidateKind;
+use tuic_core::config;
+use tuic_core::float_inspect::{self, FloatParts};
+use tuic_core::highlight::{self, TokenKind};
+use tuic_core::inspect;
+use tuic_core::journal::Journal;
+use tuic_core::limits::{self, Limit};
+use tuic_core::settings::Setting;
+use tuic_core::network;
+use tuic_core::plot;
+use std::time::Instant;
 use ratatui::{
-    layout::{Alignment, Constraint, Direction, Layout, Rect},
+    layout::{Alignment, Constraint, Direction, Layout, Margin, Rect},
     style::{Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Wrap, block::Padding},
+    symbols::Marker,
+    widgets::{Axis, Bar, BarChart, BarGroup, Block, Chart, Clear, Dataset, GraphType, List, ListItem, ListState, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState, Sparkline, Wrap, block::Padding},
     Frame,
     prelude::Stylize,
 };
 
-const MAX_DISPLAY_ITEMS: usize = 100; // Limit display to last 100 items
 const MAX_DISPLAY_WIDTH: usize = 50; // Limit width of displayed strings
+// Smallest terminal the layout fits in (the row heights added up); below it a notice is shown instead
+const MIN_WIDTH: u16 = 40;
+const MIN_HEIGHT: u16 = 25;
+const MIN_HEIGHT_COMPACT: u16 = 18;
+const MIN_HEIGHT_PRESENT: u16 = 22;
+const MIN_HEIGHT_QUICK: u16 = 6;
 
-pub fn draw(f: &mut Frame, calculator: &mut Calculator) {
-    f.render_widget(Block::default().bg(calculator.current_theme.background), f.area());
+pub fn draw(f: &mut Frame, app: &mut App) {
+    let calculator = &app.calculator;
+    let theme = &app.current_theme;
+    // The panel that was clicked gets the focus border
+    let panel = |title: String, panel: Panel| {
+        let colors = match panel {
+            Panel::Stack => &theme.stack,
+            Panel::History => &theme.history,
+            Panel::Input => &theme.input,
+        };
+        if app.panel_focus == panel { theme.focused_panel(colors, title) } else { theme.panel_in(colors, title) }
+    };
+    let suggestion = calculator.error.as_ref().and(calculator.suggestion.clone());
+    let compact = app.layout == config::Layout::Compact;
+    let present = app.layout == config::Layout::Present;
+    let quick = app.layout == config::Layout::Quick;
+    f.render_widget(Block::default().bg(theme.background), f.area());
+    let display_height = if app.show_display { bigtext::SEGMENT_HEIGHT as u16 + 2 } else { 0 };
+    // Screen-reader mode's log takes the quick help's rows, in every layout
+    let screen_reader = app.announcer.is_some();
+    let log_height = if screen_reader && app.layout != config::Layout::Full { 6 } else { 0 };
+    let min_height = display_height + log_height + match app.layout {
+        config::Layout::Full => MIN_HEIGHT,
+        config::Layout::Compact => MIN_HEIGHT_COMPACT,
+        config::Layout::Present => MIN_HEIGHT_PRESENT,
+        config::Layout::Quick => MIN_HEIGHT_QUICK,
+    };
+    if f.area().width < MIN_WIDTH || f.area().height < min_height {
+        draw_too_small(f, theme, min_height);
+        app.panel_areas.clear();
+        return;
+    }
+    let screen_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(display_height), Constraint::Min(0)])
+        .split(f.area());
+    if app.show_display {
+        draw_seven_segment_display(f, calculator, theme, screen_chunks[0]);
+    }
     let main_chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
-            Constraint::Length(3),  // Top row for mode boxes
-            Constraint::Min(5),     // Stack display
-            Constraint::Length(5),  // History display
+            Constraint::Length(if quick { 0 } else if present { bigtext::HEIGHT as u16 + 2 } else { 3 }),  // Top row for mode boxes, or the top of the stack when presenting
+            if quick { Constraint::Length(0) } else { Constraint::Min(5) },     // Stack display
+            Constraint::Length(if quick { 0 } else if compact || present { 4 } else { 5 }),  // History display
             Constraint::Length(3),  // Input
-            Constraint::Length(3),  // Status/Error
-            Constraint::Length(6),  // Help
-        ])
-        .split(f.area());
-
-    let mode_chunks = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([
-            Constraint::Percentage(25), // Mode
-            Constraint::Percentage(25), // Angle
-            Constraint::Percentage(25), // Base
-            Constraint::Percentage(25), // Complex
+            Constraint::Length(if suggestion.is_some() { 4 } else { 3 }),  // Status/Error, plus a lint hint
+            Constraint::Length(if quick { 0 } else if screen_reader || !(compact || present) { 6 } else { 0 }),  // Help or the log, help hidden in the compact and presentation layouts
         ])
-        .split(main_chunks[0]); // Split the top row
-
-    // Mode Box
-    let mode_text = match calculator.mode {
-        CalculatorMode::RPN => Span::styled("RPN", Style::default().fg(calculator.current_theme.success).add_modifier(Modifier::BOLD)),
-        CalculatorMode::Infix => Span::styled("INFIX", Style::default().fg(calculator.current_theme.warning).add_modifier(Modifier::BOLD)),
-    };
-    let mode_paragraph = Paragraph::new(Line::from(mode_text)) // Removed Span::raw("Mode: ")
-        .block(Block::default().borders(Borders::ALL).title("Mode").border_style(Style::default().fg(calculator.current_theme.border)).title_style(Style::default().fg(calculator.current_theme.title)));
-    f.render_widget(mode_paragraph, mode_chunks[0]);
-
-    // Angle Box
-    let angle_text = match calculator.angle_mode {
-        AngleMode::Radians => Span::styled("RAD", Style::default().fg(calculator.current_theme.info)),
-        AngleMode::Degrees => Span::styled("DEG", Style::default().fg(calculator.current_theme.info)),
-    };
-    let angle_paragraph = Paragraph::new(Line::from(angle_text)) // Removed Span::raw("Angle: ")
-        .block(Block::default().borders(Borders::ALL).title("Angle").border_style(Style::default().fg(calculator.current_theme.border)).title_style(Style::default().fg(calculator.current_theme.title)));
-    f.render_widget(angle_paragraph, mode_chunks[1]);
-
-    // Base Box
-    let base_text = match calculator.base_mode {
-        BaseMode::Decimal => Span::styled("DEC", Style::default().fg(calculator.current_theme.success)),
-        BaseMode::Hexadecimal => Span::styled("HEX", Style::default().fg(calculator.current_theme.warning)),
-        BaseMode::Binary => Span::styled("BIN", Style::default().fg(calculator.current_theme.error)),
-    };
-    let base_paragraph = Paragraph::new(Line::from(base_text)) // Removed Span::raw("Base: ")
-        .block(Block::default().borders(Borders::ALL).title("Base").border_style(Style::default().fg(calculator.current_theme.border)).title_style(Style::default().fg(calculator.current_theme.title)));
-    f.render_widget(base_paragraph, mode_chunks[2]);
+        .split(screen_chunks[1]);
+    app.panel_areas = vec![(Panel::Stack, main_chunks[1]), (Panel::History, main_chunks[2]), (Panel::Input, main_chunks[3])];
 
-    // Complex Box
-    let complex_text = match calculator.complex_mode {
-        ComplexMode::Rectangular => Span::styled("REC", Style::default().fg(calculator.current_theme.warning)),
-        ComplexMode::Polar => Span::styled("POL", Style::default().fg(calculator.current_theme.error)),
-    };
-    let complex_paragraph = Paragraph::new(Line::from(complex_text)) // Removed Span::raw("Complex: ")
-        .block(Block::default().borders(Borders::ALL).title("Complex").border_style(Style::default().fg(calculator.current_theme.border)).title_style(Style::default().fg(calculator.current_theme.title)));
-    f.render_widget(complex_paragraph, mode_chunks[3]);
-
-    // Stack display
-    let stack_display_slice = if calculator.stack.len() > MAX_DISPLAY_ITEMS {
-        &calculator.stack[calculator.stack.len() - MAX_DISPLAY_ITEMS..]
+    if present {
+        draw_top_value(f, calculator, theme, main_chunks[0]);
     } else {
-        &calculator.stack[..]
-    };
-
-    let stack_items: Vec<ListItem> = stack_display_slice
-        .iter()
-        .enumerate()
-        .rev() // Still want top at bottom
-        .map(|(i, entry)| {
-            // The original_index needs to be relative to the full stack, but adjusted for the slice.
-            let full_stack_start_index = calculator.stack.len().saturating_sub(stack_display_slice.len());
-            let original_index = full_stack_start_index + (stack_display_slice.len() - 1 - i);
+        draw_mode_boxes(f, app, main_chunks[0]);
+    }
 
-            let truncated_expression = truncate_string(&entry.expression, MAX_DISPLAY_WIDTH);
-            let truncated_result = truncate_string(&calculator.format_stack_value(&entry.result), MAX_DISPLAY_WIDTH);
+    // Stack display: only the rows that fit are built, top of the stack first, from text kept between frames
+    let row_cache = &mut app.row_cache;
+    row_cache.begin_frame(calculator.display_settings());
+    let stack_rows = main_chunks[1].height.saturating_sub(2) as usize;
+    let stack_offset = scroll_offset(&app.stack_list_state, calculator.stack.len(), stack_rows, false);
+    let current_modes = calculator.entry_modes();
+    // Level 0 is the top of the stack, the same numbering as stack_position
+    let stack_items: Vec<ListItem> = (stack_offset..(stack_offset + stack_rows).min(calculator.stack.len()))
+        .filter_map(|level| calculator.stack.from_top(level).map(|entry| (level, entry)))
+        .map(|(level, entry)| {
+            let (expression, result) = row_cache.stack_row(calculator, entry);
+            let truncated_expression = text::truncate(expression, MAX_DISPLAY_WIDTH);
+            let truncated_result = text::truncate(result, MAX_DISPLAY_WIDTH);
 
-            let expression_span = Span::styled(truncated_expression, Style::default().fg(calculator.current_theme.stack_expression));
-            let result_span = Span::styled(truncated_result, Style::default().fg(calculator.current_theme.stack_result));
+            let expression_span = Span::styled(truncated_expression, Style::default().fg(theme.stack_expression));
+            let result_span = Span::styled(truncated_result, Style::default().fg(theme.stack_result));
 
-            let mut line_spans = vec![
-                Span::styled(format!("{} ", original_index + 1), Style::default().fg(calculator.current_theme.stack_line_number)),
+            let mut line_spans = vec![Span::styled(format!("{} ", level + 1), Style::default().fg(theme.stack_line_number))];
+            if let Some(label) = &entry.label {
+                line_spans.push(Span::styled(format!("{}: ", label), Style::default().fg(theme.info).add_modifier(Modifier::BOLD)));
+            }
+            line_spans.extend([
                 expression_span,
                 Span::raw(" = "),
                 result_span,
-            ];
+            ]);
+
+            // Entered under other modes: name them so e.g. an old hex input isn't read as decimal
+            let differences = entry.modes.differences(&current_modes);
+            if !differences.is_empty() {
+                line_spans.push(Span::styled(format!(" [{}]", differences.join(" ")), Style::default().fg(theme.input_placeholder)));
+            }
 
-            if original_index == calculator.stack_position {
-                line_spans.push(Span::raw(" ←"));
+            if level == calculator.stack_position {
+                line_spans.push(Span::raw(if screen_reader { " <" } else { " ←" }));
             }
-            
+
             ListItem::new(Line::from(line_spans))
         })
         .collect();
-    
-    let stack_title = format!("Stack ({} items)", calculator.stack.len());
+
+    let stack_title = format!("Stack: {} {}", calculator.workspace, calculator.limit_summary(Limit::Stack));
     let stack = List::new(stack_items)
-        .block(Block::default().borders(Borders::ALL).title(stack_title).border_style(Style::default().fg(calculator.current_theme.border)).title_style(Style::default().fg(calculator.current_theme.title)))
-        .highlight_style(Style::default().bg(calculator.current_theme.highlight_bg))
-        .style(Style::default().fg(calculator.current_theme.foreground));
-    f.render_stateful_widget(stack, main_chunks[1], &mut calculator.stack_list_state);
-
-    // History display
-    let history_display_slice = if calculator.history.len() > MAX_DISPLAY_ITEMS {
-        &calculator.history[calculator.history.len() - MAX_DISPLAY_ITEMS..]
-    } else {
-        &calculator.history[..]
-    };
+        .block(panel(stack_title, Panel::Stack))
+        .highlight_style(Style::default().bg(theme.highlight_bg))
+        .style(Style::default().fg(theme.stack.foreground.unwrap_or(theme.foreground)));
+    let mut stack_window = ListState::default().with_selected(app.stack_list_state.selected().map(|selected| selected.saturating_sub(stack_offset)));
+    f.render_stateful_widget(stack, main_chunks[1], &mut stack_window);
+    *app.stack_list_state.offset_mut() = stack_offset;
+    draw_scrollbar(f, theme, main_chunks[1], calculator.stack.len(), stack_rows, stack_offset);
 
-    let history_items: Vec<ListItem> = history_display_slice
+    // History display, following the newest entries unless one is being browsed
+    let history_rows = main_chunks[2].height.saturating_sub(2) as usize;
+    let history_offset = scroll_offset(&app.history_list_state, calculator.history.len(), history_rows, true);
+    let marker_style = Style::default().fg(theme.input_placeholder).add_modifier(Modifier::DIM);
+    let mut history_items: Vec<ListItem> = calculator.history[history_offset..(history_offset + history_rows).min(calculator.history.len())]
         .iter()
         .enumerate()
         .map(|(i, entry)| {
-            let full_history_start_index = calculator.history.len().saturating_sub(history_display_slice.len());
-            let original_index = full_history_start_index + i; // Correct index for history
-
-            let truncated_entry = truncate_string(entry, MAX_DISPLAY_WIDTH);
-            let mut item = ListItem::new(truncated_entry);
-            if original_index == calculator.history_position {
+            let truncated_entry = text::truncate(row_cache.history_row(calculator, entry), MAX_DISPLAY_WIDTH);
+            // Numbered from 1 for !N recall
+            let number = Span::styled(format!("{} ", history_offset + i + 1), Style::default().fg(theme.stack_line_number));
+            let pin = if entry.pinned { Span::styled(if screen_reader { "* " } else { "★ " }, Style::default().fg(theme.warning)) } else { Span::raw("") };
+            let time = match entry.time_label().filter(|_| calculator.history_times) {
+                Some(time) => Span::styled(format!("{} ", time), Style::default().fg(theme.input_placeholder)),
+                None => Span::raw(""),
+            };
+            let mut lines = vec![Line::from(vec![number, time, pin, Span::raw(truncated_entry)])];
+            // With times on, a marker line above the first entry of each later session
+            if calculator.history_times
+                && let Some(session) = calculator.session_start_before(history_offset + i)
+            {
+                lines.insert(0, Line::styled(session_marker(session), marker_style));
+            }
+            let mut item = ListItem::new(lines);
+            if history_offset + i == calculator.history_position {
                 item = item.style(Style::default().add_modifier(Modifier::REVERSED));
             }
             item
         })
         .collect();
+    // A restored session's history ends where this one begins
+    if calculator.history_times
+        && let Some(session) = calculator.session_start_before(calculator.history.len())
+    {
+        history_items.push(ListItem::new(Line::styled(session_marker(session), marker_style)));
+    }
 
-    let history_title = format!("History ({} items)", calculator.history.len());
+    let history_title = format!("History {}", calculator.limit_summary(Limit::History));
     let history = List::new(history_items)
-        .block(Block::default().borders(Borders::ALL).title(history_title).border_style(Style::default().fg(calculator.current_theme.border)).title_style(Style::default().fg(calculator.current_theme.title)))
-        .highlight_style(Style::default().bg(calculator.current_theme.highlight_bg).add_modifier(Modifier::BOLD))
+        .block(panel(history_title, Panel::History))
+        .highlight_style(Style::default().bg(theme.highlight_bg).add_modifier(Modifier::BOLD))
         .highlight_symbol(">> ")
-        .style(Style::default().fg(calculator.current_theme.history_text));
-    f.render_stateful_widget(history, main_chunks[2], &mut calculator.history_list_state);
+        .style(Style::default().fg(theme.history.foreground.unwrap_or(theme.history_text)));
+    let mut history_window = ListState::default().with_selected(app.history_list_state.selected().map(|selected| selected.saturating_sub(history_offset)));
+    f.render_stateful_widget(history, main_chunks[2], &mut history_window);
+    *app.history_list_state.offset_mut() = history_offset;
+    draw_scrollbar(f, theme, main_chunks[2], calculator.history.len(), history_rows, history_offset);
 
-    // Input
-    let input_text = if calculator.input.is_empty() {
-        "Enter expression..."
-    } else {
-        &calculator.input
+    // Input, with the running tally while in rapid entry
+    let input_title = match calculator.rapid_start {
+        Some(_) => {
+            let (count, sum) = calculator.rapid_tally();
+            format!("Input · rapid entry: n = {}, Σ = {}", count, calculator.format_real(sum))
+        }
+        None => "Input".to_string(),
     };
-    
-    let input_style = if calculator.input.is_empty() {
-        Style::default().fg(calculator.current_theme.input_placeholder)
-    } else {
-        Style::default().fg(calculator.current_theme.input_text)
+    // The border says whether the infix input parses, is unfinished or can't be fixed by typing on
+    let input_block = match calculator.input_validity() {
+        Some(validity) => panel(input_title, Panel::Input).border_style(Style::default().fg(match validity {
+            InputValidity::Valid => theme.success,
+            InputValidity::Incomplete => theme.warning,
+            InputValidity::Invalid => theme.error,
+        })),
+        None => panel(input_title, Panel::Input),
     };
-
-    let input = Paragraph::new(input_text)
-        .style(input_style)
-        .block(Block::default().borders(Borders::ALL).title("Input").border_style(Style::default().fg(calculator.current_theme.border)).title_style(Style::default().fg(calculator.current_theme.title)))
+    let input = Paragraph::new(input_line(calculator, theme, calculator.cursor, app.caret_visible()))
+        .block(input_block)
+        .style(if present { Style::default().add_modifier(Modifier::BOLD) } else { Style::default() })
         .wrap(Wrap { trim: true });
     f.render_widget(input, main_chunks[3]);
 
-    // Status: Show current value or error
+    // Status: Show current value or error. Errors keep the error color even where the panel sets its own text color
+    let status_color = |color| Style::default().fg(theme.status.foreground.unwrap_or(color));
     let (status_text, status_style) = if let Some(error) = &calculator.error {
-        (format!("Error: {}", error), Style::default().fg(calculator.current_theme.error))
+        (format!("Error {}: {}", error.code(), error), Style::default().fg(theme.error))
+    } else if let Some(warning) = &calculator.warning {
+        (format!("Warning {}: {}", warning.code(), warning), Style::default().fg(theme.warning).add_modifier(Modifier::ITALIC))
+    } else if let Some(notice) = &calculator.notice {
+        (notice.clone(), status_color(theme.info))
+    } else if let Some(preview) = calculator.preview() {
+        (format!("Preview: = {}  (Enter to evaluate)", preview), status_color(theme.info).add_modifier(Modifier::ITALIC))
     } else if let Some(current) = calculator.get_current_value() {
-        (format!("Current: {}", current), Style::default().fg(calculator.current_theme.success))
+        (format!("Current: {}", current), status_color(theme.success))
     } else {
-        ("Ready - Enter numbers to start".to_string(), Style::default().fg(calculator.current_theme.warning))
+        ("Ready - Enter numbers to start".to_string(), status_color(theme.warning))
     };
 
-    let status_widget = Paragraph::new(status_text)
+    // Long values (a 64-bit binary, a long notice) are cut to the lines the status panel has
+    let status_rows = main_chunks[4].height.saturating_sub(if suggestion.is_some() { 3 } else { 2 }) as usize;
+    let status_text = text::truncate(&status_text, main_chunks[4].width.saturating_sub(2) as usize * status_rows);
+    let mut status_lines = vec![Line::from(status_text)];
+    if let Some(suggestion) = &suggestion {
+        let mut hint = vec![Span::styled(format!("Hint: {}", suggestion.message), Style::default().fg(theme.info))];
+        if suggestion.fix.is_some() {
+            hint.push(Span::styled("  (Ctrl+F to apply)", Style::default().fg(theme.input_placeholder)));
+        }
+        status_lines.push(Line::from(hint));
+    }
+    // A new error flashes briefly so it isn't missed
+    let status_style = if app.error_flash > 0 { status_style.add_modifier(Modifier::REVERSED) } else { status_style };
+    let mut status_block = theme.panel_in(&theme.status, "Status");
+    if calculator.grand_total != 0.0 {
+        status_block = status_block.title(Line::from(format!(" GT {} ", calculator.format_real(calculator.grand_total))).right_aligned());
+    }
+    if let Some(save_status) = app.save_status() {
+        status_block = status_block.title(Line::from(format!(" {} ", save_status)).right_aligned());
+    }
+    let status_widget = Paragraph::new(status_lines)
         .style(status_style)
-        .block(Block::default().borders(Borders::ALL).title("Status").border_style(Style::default().fg(calculator.current_theme.border)).title_style(Style::default().fg(calculator.current_theme.title)))
+        .block(status_block)
         .wrap(Wrap { trim: true });
     f.render_widget(status_widget, main_chunks[4]);
 
     // Help
     let help_text = vec![
         Line::from(vec![
-            Span::styled("Enter", Style::default().fg(calculator.current_theme.warning)),
+            Span::styled("Enter", Style::default().fg(theme.warning)),
             Span::raw(": Calculate | "),
-            Span::styled("C", Style::default().fg(calculator.current_theme.warning)),
+            Span::styled("C", Style::default().fg(theme.warning)),
             Span::raw(": Clear | "),
-            Span::styled("h", Style::default().fg(calculator.current_theme.warning)),
+            Span::styled("h", Style::default().fg(theme.warning)),
             Span::raw(": Help Dialog"),
         ]),
         Line::from(vec![
-            Span::styled("Backspace", Style::default().fg(calculator.current_theme.warning)),
+            Span::styled("Backspace", Style::default().fg(theme.warning)),
             Span::raw(": Delete | "),
-            Span::styled("q/Esc", Style::default().fg(calculator.current_theme.warning)),
+            Span::styled("q/Esc", Style::default().fg(theme.warning)),
             Span::raw(": Quit | "),
-            Span::styled("Ctrl+C", Style::default().fg(calculator.current_theme.warning)),
+            Span::styled("Ctrl+C", Style::default().fg(theme.warning)),
             Span::raw(": Clear All"),
         ]),
         Line::from(vec![
-            Span::styled("m", Style::default().fg(calculator.current_theme.warning)),
+            Span::styled("m", Style::default().fg(theme.warning)),
             Span::raw(": Toggle RPN/Infix Mode | "),
             Span::raw("Operators: "),
-            Span::styled("+, -, *, /, ^", Style::default().fg(calculator.current_theme.info)),
+            Span::styled("+, -, *, /, ^", Style::default().fg(theme.info)),
             Span::raw(" | Parentheses: "),
-            Span::styled("( )", Style::default().fg(calculator.current_theme.info)),
+            Span::styled("( )", Style::default().fg(theme.info)),
         ]),
         Line::from(vec![
-            Span::styled("PageUp/PageDown", Style::default().fg(calculator.current_theme.warning)),
+            Span::styled("PageUp/PageDown", Style::default().fg(theme.warning)),
             Span::raw(": Browse History | "),
-            Span::styled("Up/Down", Style::default().fg(calculator.current_theme.warning)),
-            Span::raw(": Browse Stack"),
+            Span::styled("Up/Down", Style::default().fg(theme.warning)),
+            Span::raw(": Browse Stack | "),
+            Span::styled("Home/End", Style::default().fg(theme.warning)),
+            Span::raw(": Top/Bottom"),
         ]),
     ];
 
-    let help = Paragraph::new(help_text)
-        .block(Block::default().borders(Borders::ALL).title("Quick Help (Press 'h' for more)").border_style(Style::default().fg(calculator.current_theme.border)).title_style(Style::default().fg(calculator.current_theme.title)))
-        .wrap(Wrap { trim: true });
-    f.render_widget(help, main_chunks[5]);
+    if quick {
+        // Nothing under the status line: the window is meant to be no bigger than that
+    } else if let Some(announcer) = &app.announcer {
+        // The newest changes, oldest first so the last line is what just happened
+        let rows = main_chunks[5].height.saturating_sub(2) as usize;
+        let lines: Vec<Line> = announcer.lines[announcer.lines.len().saturating_sub(rows)..].iter().map(|line| Line::from(line.as_str())).collect();
+        let log = Paragraph::new(lines)
+            .style(Style::default().fg(theme.foreground))
+            .block(theme.panel("Log"));
+        f.render_widget(log, main_chunks[5]);
+    } else {
+        let help = Paragraph::new(help_text)
+            .block(theme.panel("Quick Help (Press 'h' for more)"))
+            .wrap(Wrap { trim: true });
+        f.render_widget(help, main_chunks[5]);
+    }
 
     // Render help dialog if active
-    if calculator.show_help {
-        draw_help_dialog(f, calculator);
-    } else if calculator.show_theme_selector {
-        draw_theme_selector_dialog(f, calculator);
+    if let Some(journal) = &app.recovery {
+        draw_recovery_dialog(f, journal, theme);
+    } else if let Some(prompt) = calculator.confirmation_prompt() {
+        draw_confirm_dialog(f, &prompt, theme);
+    } else if app.show_help {
+        draw_help_dialog(f, app);
+    } else if app.show_quick_ref {
+        draw_quick_ref_dialog(f, calculator, theme);
+    } else if app.show_float_inspector {
+        draw_float_inspector_dialog(f, calculator, theme);
+    } else if app.show_inspect {
+        draw_inspect_dialog(f, calculator, theme);
+    } else if calculator.table_import.is_some() {
+        draw_table_import_dialog(f, calculator, theme);
+    } else if app.show_iteration_plot {
+        draw_iteration_plot_dialog(f, calculator, theme);
+    } else if app.show_stack_chart {
+        draw_stack_chart_dialog(f, calculator, theme, app.stack_chart_sparkline);
+    } else if app.show_stopwatch {
+        draw_stopwatch_dialog(f, &app.stopwatch, theme);
+    } else if app.show_error_log {
+        draw_error_log_dialog(f, &app.error_log, theme);
+    } else if calculator.comparison.is_some() {
+        draw_compare_dialog(f, calculator, theme);
+    } else if calculator.angle_conversion.is_some() {
+        draw_angle_dialog(f, calculator, theme);
+    } else if calculator.settings_dialog.is_some() {
+        draw_settings_dialog(f, app, theme);
+    } else if calculator.limits_dialog.is_some() {
+        draw_limits_dialog(f, calculator, theme);
+    } else if calculator.subnet.is_some() {
+        draw_subnet_dialog(f, calculator, theme);
+    } else if calculator.show_about {
+        draw_about_dialog(f, app, theme);
+    } else if calculator.show_rates {
+        draw_rates_dialog(f, calculator, theme);
+    } else if calculator.usage_stats.is_some() {
+        draw_usage_stats_dialog(f, calculator, theme);
+    } else if calculator.result_stats.is_some() {
+        draw_result_stats_dialog(f, calculator, theme);
+    } else if calculator.backup_picker.is_some() {
+        draw_backup_picker_dialog(f, calculator, theme);
+    } else if calculator.function_plot.is_some() {
+        draw_function_plot_dialog(f, calculator, theme);
+    } else if calculator.editor.is_some() {
+        draw_editor_dialog(f, calculator, theme);
+    } else if calculator.mini_input.is_some() {
+        draw_mini_calculator_dialog(f, calculator, theme);
+    } else if calculator.completion.is_some() {
+        draw_completion_popup(f, calculator, theme, main_chunks[3]);
+    } else if app.show_theme_colors {
+        draw_theme_colors_dialog(f, app);
+    } else if app.show_theme_selector {
+        draw_theme_selector_dialog(f, app);
+    }
+
+    // Over everything, so a popup's state can be watched too
+    if app.show_debug {
+        draw_debug_overlay(f, app);
+    }
+}
+
+/// The top row of the full and compact layouts: mode, angle, base and complex display.
+fn draw_mode_boxes(f: &mut Frame, app: &App, area: Rect) {
+    let calculator = &app.calculator;
+    let theme = &app.current_theme;
+    let mode_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage(25), // Mode
+            Constraint::Percentage(25), // Angle
+            Constraint::Percentage(25), // Base
+            Constraint::Percentage(25), // Complex
+        ])
+        .split(area); // Split the top row
+
+    // Mode Box
+    let mode_text = match calculator.mode {
+        CalculatorMode::RPN => Span::styled("RPN", Style::default().fg(theme.success).add_modifier(Modifier::BOLD)),
+        CalculatorMode::Infix => Span::styled("INFIX", Style::default().fg(theme.warning).add_modifier(Modifier::BOLD)),
+    };
+    let vi_text = match app.vi_mode {
+        Some(ViMode::Normal) => Span::styled(" NORMAL", Style::default().fg(theme.info)),
+        Some(ViMode::Insert) => Span::styled(" INSERT", Style::default().fg(theme.info)),
+        None => Span::raw(""),
+    };
+    let mode_paragraph = Paragraph::new(Line::from(vec![mode_text, vi_text])) // Removed Span::raw("Mode: ")
+        .block(theme.panel("Mode"));
+    f.render_widget(mode_paragraph, mode_chunks[0]);
+
+    // Angle Box
+    let angle_text = match calculator.angle_mode {
+        AngleMode::Radians => Span::styled("RAD", Style::default().fg(theme.info)),
+        AngleMode::Degrees => Span::styled("DEG", Style::default().fg(theme.info)),
+    };
+    let angle_paragraph = Paragraph::new(Line::from(angle_text)) // Removed Span::raw("Angle: ")
+        .block(theme.panel("Angle"));
+    f.render_widget(angle_paragraph, mode_chunks[1]);
+
+    // Base Box
+    let base_text = match calculator.base_mode {
+        BaseMode::Decimal => Span::styled("DEC", Style::default().fg(theme.success)),
+        BaseMode::Hexadecimal => Span::styled("HEX", Style::default().fg(theme.warning)),
+        BaseMode::Binary => Span::styled("BIN", Style::default().fg(theme.error)),
+    };
+    let word_size_text = match calculator.word_size.bits() {
+        Some(bits) => Span::styled(format!(" {}-bit", bits), Style::default().fg(theme.info)),
+        None => Span::raw(""),
+    };
+    let base_paragraph = Paragraph::new(Line::from(vec![base_text, word_size_text])) // Removed Span::raw("Base: ")
+        .block(theme.panel("Base"));
+    f.render_widget(base_paragraph, mode_chunks[2]);
+
+    // Complex Box
+    let complex_text = match calculator.complex_mode {
+        ComplexMode::Rectangular => Span::styled("REC", Style::default().fg(theme.warning)),
+        ComplexMode::Polar => Span::styled("POL", Style::default().fg(theme.error)),
+    };
+    let complex_paragraph = Paragraph::new(Line::from(complex_text)) // Removed Span::raw("Complex: ")
+        .block(theme.panel("Complex"));
+    f.render_widget(complex_paragraph, mode_chunks[3]);
+}
+
+/// The top row of the presentation layout: the top of the stack in block
+/// digits, or in bold when they don't fit.
+fn draw_top_value(f: &mut Frame, calculator: &Calculator, theme: &Theme, area: Rect) {
+    let style = Style::default().fg(theme.stack_result).add_modifier(Modifier::BOLD);
+    let (title, lines) = match calculator.stack.last() {
+        Some(entry) => {
+            let value = calculator.format_stack_row(&entry.result);
+            let lines = match bigtext::render(&value, area.width.saturating_sub(2) as usize) {
+                Some(rows) => rows.into_iter().map(|row| Line::from(Span::styled(row, style))).collect(),
+                None => vec![Line::from(Span::styled(value, style))],
+            };
+            (entry.label.clone().unwrap_or_else(|| calculator.display_expression(entry)), lines)
+        }
+        None => (String::new(), vec![Line::from(Span::styled("Empty stack", Style::default().fg(theme.input_placeholder)))]),
+    };
+    let title = text::truncate(&title, area.width.saturating_sub(4) as usize);
+    f.render_widget(Paragraph::new(lines).alignment(Alignment::Center).block(theme.panel(title)), area);
+}
+
+/// The desk-calculator display: the number being typed, or else the top of the
+/// stack, right-aligned in seven-segment digits; plain text when it can't be shown in them.
+fn draw_seven_segment_display(f: &mut Frame, calculator: &Calculator, theme: &Theme, area: Rect) {
+    let value = calculator.get_current_value().unwrap_or_else(|| "0".to_string());
+    let style = Style::default().fg(theme.stack_result).add_modifier(Modifier::BOLD);
+    let lines: Vec<Line> = match bigtext::seven_segment(&value, area.width.saturating_sub(4) as usize) {
+        Some(rows) => rows.into_iter().map(|row| Line::from(Span::styled(row, style))).collect(),
+        None => vec![Line::from(""), Line::from(Span::styled(value, style))],
+    };
+    let display = Paragraph::new(lines)
+        .alignment(Alignment::Right)
+        .block(theme.panel("Display").padding(Padding::horizontal(1)));
+    f.render_widget(display, area);
+}
+
+/// Input rendered as colored spans with the parenthesis pair at the caret highlighted.
+/// `cursor` is a char index; the caret is drawn as a reversed cell.
+fn input_line(calculator: &Calculator, theme: &Theme, cursor: usize, blink_on: bool) -> Line<'static> {
+    let caret = if blink_on { Style::default().add_modifier(Modifier::REVERSED) } else { Style::default() };
+    if calculator.input.is_empty() {
+        return Line::from(vec![
+            Span::styled(" ", caret),
+            Span::styled("Enter expression...", Style::default().fg(theme.input_placeholder)),
+        ]);
+    }
+
+    let chars: Vec<char> = calculator.input.chars().collect();
+    let text_color = theme.input.foreground.unwrap_or(theme.input_text);
+    let mut styles = vec![Style::default().fg(text_color); chars.len()];
+    for run in highlight::highlight(&calculator.input) {
+        let color = match run.kind {
+            TokenKind::Number => theme.info,
+            TokenKind::Operator => theme.warning,
+            TokenKind::Function => theme.success,
+            TokenKind::Variable => theme.stack_expression,
+            TokenKind::Paren | TokenKind::Separator => theme.title,
+            TokenKind::Command => theme.success,
+            TokenKind::Unknown => theme.error,
+            TokenKind::Whitespace => text_color,
+            TokenKind::Comment => theme.input_placeholder,
+        };
+        for style in &mut styles[run.start..run.end] {
+            *style = Style::default().fg(color);
+        }
+    }
+    for index in highlight::unmatched_parens(&chars) {
+        styles[index] = Style::default().fg(theme.error).add_modifier(Modifier::BOLD);
+    }
+    // Where the last parse failed; an empty span (unexpected end) marks the cell after it
+    let error_cells = match calculator.error.as_ref().and_then(|error| error.span()) {
+        Some(span) => {
+            let to_char = |byte: usize| calculator.input[..byte.min(calculator.input.len())].chars().count();
+            let start = to_char(span.start);
+            start..to_char(span.end).max(start + 1)
+        }
+        None => 0..0,
+    };
+    let error_style = Style::default().fg(theme.error).add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+    for index in error_cells.clone().filter(|&i| i < chars.len()) {
+        styles[index] = error_style;
+    }
+    // Pair the parenthesis just before the caret, or under it
+    let paren_at_caret = [cursor.checked_sub(1), Some(cursor)]
+        .into_iter()
+        .flatten()
+        .find(|&i| matches!(chars.get(i), Some('(') | Some(')')));
+    if let Some(index) = paren_at_caret
+        && let Some(partner) = highlight::matching_paren(&chars, index)
+    {
+        for i in [index, partner] {
+            styles[i] = styles[i].bg(theme.highlight_bg).add_modifier(Modifier::BOLD);
+        }
+    }
+
+    let mut spans: Vec<Span<'static>> = chars
+        .iter()
+        .zip(styles)
+        .enumerate()
+        .map(|(i, (ch, style))| Span::styled(ch.to_string(), if i == cursor { style.patch(caret) } else { style }))
+        .collect();
+    if error_cells.contains(&chars.len()) {
+        spans.push(Span::styled(" ", Style::default().fg(theme.error).add_modifier(Modifier::REVERSED)));
+    } else if cursor >= chars.len() {
+        spans.push(Span::styled(" ", caret));
+    }
+    Line::from(spans)
+}
+
+fn draw_quick_ref_dialog(f: &mut Frame, calculator: &Calculator, theme: &Theme) {
+    let area = centered_rect(80, 80, f.area());
+
+    f.render_widget(Clear, area);
+
+    let (title, sections) = quickref::card(calculator);
+    let mut content = Vec::new();
+    for section in sections {
+        if !content.is_empty() {
+            content.push(Line::from(""));
+        }
+        content.push(Line::from(Span::styled(section.title, Style::default().fg(theme.warning).add_modifier(Modifier::BOLD))));
+        // Two columns of keys, each action cut to fit its column
+        let column = (area.width.saturating_sub(4) / 2) as usize;
+        let key_width = section.keys.iter().map(|(keys, _)| keys.chars().count()).max().unwrap_or(0).min(20);
+        let action_width = column.saturating_sub(key_width + 4);
+        let cell = |(keys, action): &(String, &str)| {
+            vec![
+                Span::styled(format!("  {:<key_width$} ", keys), Style::default().fg(theme.success)),
+                Span::raw(format!("{:<action_width$} ", text::truncate(action, action_width))),
+            ]
+        };
+        let half = section.keys.len().div_ceil(2);
+        for (i, left) in section.keys[..half].iter().enumerate() {
+            let mut spans = cell(left);
+            if let Some(right) = section.keys.get(half + i) {
+                spans.extend(cell(right));
+            }
+            content.push(Line::from(spans));
+        }
+        if !section.words.is_empty() {
+            content.push(Line::from(Span::styled(format!("  {}", section.words.join("  ")), Style::default().fg(theme.info))));
+        }
     }
+
+    let dialog = Paragraph::new(content)
+        .block(theme.dialog(format!(" {} · ? or Esc closes, h for the full help ", title))
+            .padding(Padding::horizontal(1)))
+        .style(Style::default().fg(theme.foreground))
+        .wrap(Wrap { trim: false });
+
+    f.render_widget(dialog, area);
 }
 
-fn draw_help_dialog(f: &mut Frame, calculator: &mut Calculator) {
+fn draw_help_dialog(f: &mut Frame, app: &App) {
+    let theme = &app.current_theme;
+    let focus = app.help_focus;
     // Create a centered popup area
     let area = centered_rect(80, 80, f.area());
     
     // Clear the background
     f.render_widget(Clear, area);
     
-    let help_content = vec![
+    let mut help_content = vec![
         Line::from(vec![
-            Span::styled("Advanced Calculator Help", Style::default().fg(calculator.current_theme.info).add_modifier(Modifier::BOLD))
+            Span::styled("Advanced Calculator Help", Style::default().fg(theme.info).add_modifier(Modifier::BOLD))
         ]),
         Line::from(""),
         Line::from(vec![
-            Span::styled("Calculator Modes:", Style::default().fg(calculator.current_theme.warning).add_modifier(Modifier::BOLD))
+            Span::styled(format!("Keys: {}", focus.title()), Style::default().fg(theme.warning).add_modifier(Modifier::BOLD))
         ]),
+    ];
+    // Generated from the same table the tests press, so it lists what the keys really do here
+    for binding in keymap::bindings_for(focus) {
+        let keys = binding.keys.iter().map(|keys| keys.replace(' ', "")).collect::<Vec<_>>().join("/");
+        help_content.push(Line::from(vec![
+            Span::raw("  • "),
+            Span::styled(format!("{:<22}", keys), Style::default().fg(theme.success)),
+            Span::raw(binding.action),
+        ]));
+    }
+    if let Some(note) = keymap::note(focus, app) {
+        help_content.push(Line::from(Span::styled(format!("  {}", note), Style::default().fg(theme.input_placeholder))));
+    }
+    let tips = vec![
+        Line::from(""),
         Line::from(vec![
-            Span::raw("  Mode: RPN/INFIX (toggle with 'm')")
+            Span::styled("Usage Tips:", Style::default().fg(theme.warning).add_modifier(Modifier::BOLD))
         ]),
         Line::from(vec![
-            Span::raw("  Angle: RAD/DEG (toggle with F1)")
+            Span::raw("  • RPN Mode: Enter numbers, then use operators. Example: '5', Enter, '3', Enter, '+'")
         ]),
         Line::from(vec![
-            Span::raw("  Base: DEC/HEX/BIN (cycle with F2)")
+            Span::raw("  • RPN negatives: '-5' or '1e-3' as written when there's nothing to subtract; 'n' flips the sign while typing")
         ]),
         Line::from(vec![
-            Span::raw("  Complex: REC/POL (toggle with F3)")
+            Span::raw("  • Infix Mode: Type full expression, then Enter. Example: '2 + 3 * 4', Enter")
         ]),
-        Line::from(""),
         Line::from(vec![
-            Span::styled("Common Operations:", Style::default().fg(calculator.current_theme.warning).add_modifier(Modifier::BOLD))
+            Span::raw("  • Switch to HEX mode and enter '0xFF' for hexadecimal")
         ]),
         Line::from(vec![
-            Span::raw("  • "),
-            Span::styled("Enter", Style::default().fg(calculator.current_theme.success)),
-            Span::raw("       RPN: Push number / Duplicate. Infix: Evaluate expression.")
+            Span::raw("  • Sharing your screen? 'z' shows the top of the stack in big digits, without the mode and help panels")
         ]),
         Line::from(vec![
-            Span::raw("  • "),
-            Span::styled("Delete", Style::default().fg(calculator.current_theme.success)),
-            Span::raw("      Drop (remove top of stack)")
+            Span::raw("  • Switch to BIN mode and enter '0b1010' for binary")
         ]),
         Line::from(vec![
-            Span::raw("  • "),
-            Span::styled("Insert", Style::default().fg(calculator.current_theme.success)),
-            Span::raw("      Swap top two stack items")
+            Span::raw("  • Angles can be typed as 45°30'15\"; ':set dms on' shows DEG results that way")
         ]),
         Line::from(vec![
-            Span::raw("  • "),
-            Span::styled("Backspace", Style::default().fg(calculator.current_theme.success)),
-            Span::raw("   Delete character from input")
+            Span::raw("  • Type ':stats usage' and Enter for operator/function usage statistics")
         ]),
         Line::from(vec![
-            Span::raw("  • "),
-            Span::styled("+, -, *, /, ^", Style::default().fg(calculator.current_theme.success)),
-            Span::raw("  Basic arithmetic operations")
+            Span::raw("  • ':restore-backup' brings back an earlier history or theme file")
         ]),
         Line::from(vec![
-            Span::raw("  • "),
-            Span::styled("n", Style::default().fg(calculator.current_theme.success)),
-            Span::raw("           Negation")
+            Span::raw("  • In the theme selector (t), 'i' lists the theme's colors with swatches and hex values")
         ]),
-        Line::from(""),
         Line::from(vec![
-            Span::styled("Miscellaneous:", Style::default().fg(calculator.current_theme.warning).add_modifier(Modifier::BOLD))
+            Span::raw("  • Ctrl+P pins the browsed history entry (kept through trimming and Ctrl+C); Ctrl+R re-runs it")
         ]),
         Line::from(vec![
-            Span::raw("  • "),
-            Span::styled("m", Style::default().fg(calculator.current_theme.success)),
-            Span::raw("           Toggle RPN/Infix Mode")
+            Span::raw("  • ':export md' or ':export latex' copies the history as a table; add a path to write a file")
         ]),
         Line::from(vec![
-            Span::raw("  • "),
-            Span::styled("t", Style::default().fg(calculator.current_theme.success)),
-            Span::raw("           Toggle Theme Selector"),
+            Span::raw("  • ':export script calc.tuic' writes a script that 'tui-calculator batch < calc.tuic' replays to rebuild the stack")
         ]),
         Line::from(vec![
-            Span::raw("  • "),
-            Span::styled("Up/Down", Style::default().fg(calculator.current_theme.success)),
-            Span::raw("     Navigate Themes (in selector)"),
+            Span::raw("  • 'i' shows the selected entry in decimal, hex, octal, binary, scientific, engineering, as a fraction and in polar form")
         ]),
         Line::from(vec![
-            Span::raw("  • "),
-            Span::styled("Enter", Style::default().fg(calculator.current_theme.success)),
-            Span::raw("       Select Theme (in selector)"),
+            Span::raw("  • ':map * 1.08' scales every stack entry, ':fold +' sums the stack, ':drop 2 4' removes levels 2 to 4")
         ]),
         Line::from(vec![
-            Span::raw("  • "),
-            Span::styled("Space", Style::default().fg(calculator.current_theme.success)),
-            Span::raw("       Scientific notation toggle")
+            Span::raw("  • ':frac' turns the top into the nearest simple fraction (0.333333 → 1/3); ':frac 0.01' for rougher ones")
         ]),
         Line::from(vec![
-            Span::raw("  • "),
-            Span::styled("F1/F2/F3", Style::default().fg(calculator.current_theme.success)),
-            Span::raw("    Toggle angle/base/complex modes")
+            Span::raw("  • Ctrl+K clears the stack, Ctrl+W the history, Ctrl+L the error; Ctrl+C clears everything after asking")
         ]),
         Line::from(vec![
-            Span::raw("  • "),
-            Span::styled("Up/Down", Style::default().fg(calculator.current_theme.success)),
-            Span::raw("     Stack browsing mode")
+            Span::raw("  • ':sort', ':sort desc keep' (entries keep their expressions and labels) and ':reverse' reorder the stack")
         ]),
         Line::from(vec![
-            Span::raw("  • "),
-            Span::styled("PageUp/PageDown", Style::default().fg(calculator.current_theme.success)),
-            Span::raw("  History browsing mode")
+            Span::raw("  • ':rapid' keys lists like an adding machine: Space or Enter pushes each number, '=' totals them, '~' averages")
         ]),
-        Line::from(""),
         Line::from(vec![
-            Span::styled("Usage Tips:", Style::default().fg(calculator.current_theme.warning).add_modifier(Modifier::BOLD))
+            Span::raw("  • ':label subtotal' names the selected stack entry (Up/Down to select); ':label' alone removes it")
         ]),
         Line::from(vec![
-            Span::raw("  • RPN Mode: Enter numbers, then use operators. Example: '5', Enter, '3', Enter, '+'")
+            Span::raw("  • '?' shows a one-screen card of the keys and functions for the mode you're in")
         ]),
         Line::from(vec![
-            Span::raw("  • Infix Mode: Type full expression, then Enter. Example: '2 + 3 * 4', Enter")
+            Span::raw("  • ':settings' changes precision, limits, mode, autosave, decimal and keys, saving them to config.toml")
         ]),
         Line::from(vec![
-            Span::raw("  • Switch to HEX mode and enter '0xFF' for hexadecimal")
+            Span::raw("  • ':limits' sets how many stack and history entries are kept, up to unlimited")
         ]),
         Line::from(vec![
-            Span::raw("  • Switch to BIN mode and enter '0b1010' for binary")
+            Span::raw("  • ':about' shows the version, the config and data files, and checks for a newer release")
+        ]),
+        Line::from(vec![
+            Span::raw("  • ':find 42.37' selects the stack or history entry with that result; ':find' again for the next")
+        ]),
+        Line::from(vec![
+            Span::raw("  • ':ws new NAME', ':ws NAME', ':ws delete NAME' manage workspaces, each with its own stack and history")
+        ]),
+        Line::from(vec![
+            Span::raw("  • Infix: '100 USD -> EUR' converts currencies; ':rates' lists the rates, ':rate USD 1.08' sets one by hand")
+        ]),
+        Line::from(vec![
+            Span::raw("  • Infix: '4 * 512 MiB' and '1.5 GiB in MB' do data-size math; ':set sizes si' shows GB instead of GiB")
+        ]),
+        Line::from(vec![
+            Span::raw("  • ':set uncertainty on', then '(5 ± 0.3) * (2 +/- 0.4)' or ':pm 0.3' on the stack, propagates errors through the math")
+        ]),
+        Line::from(vec![
+            Span::raw("  • Vectors: '[1, 2, 3] * 2', 'dot(u, v)', 'norm(v)', 'v[2]'; ':dot', ':norm' and ':at' in RPN")
+        ]),
+        Line::from(vec![
+            Span::raw("  • ':set times on' shows when each history entry was made, with a line where each session begins")
+        ]),
+        Line::from(vec![
+            Span::raw("  • ':set complex on' makes sqrt(0-4) = 2i; ln, log and ^ give principal values (angle in (−π, π], so (0-8)^(1/3) = 1 + 1.732i)")
+        ]),
+        Line::from(vec![
+            Span::raw("  • With complex on and F3 polar display, '5<30' or '5∠30' is magnitude 5 at angle 30 in the current unit")
+        ]),
+        Line::from(vec![
+            Span::raw("  • 'roots(1, 0-3, 2)' pushes the roots of x² − 3x + 2; ':set complex on' pushes complex roots too")
+        ]),
+        Line::from(vec![
+            Span::raw("  • Comparisons (==, !=, <, <=, >, >=) give 1 or 0; if(cond, a, b) picks a branch for threshold checks")
+        ]),
+        Line::from(vec![
+            Span::raw("  • ':hash sha256 some text' shows a digest in the current base; without text it hashes the top of the stack")
+        ]),
+        Line::from(vec![
+            Span::raw("  • ':cidr 10.0.0.0/22' shows the subnet and pushes its host count; add an address to check membership")
+        ]),
+        Line::from(vec![
+            Span::raw("  • ':set precision 4' (or 'auto') re-formats every stack and history value")
+        ]),
+        Line::from(vec![
+            Span::raw("  • ':set autoclose on' pairs '(' with ')' as you type; ':set closeonenter on' closes them on Enter")
+        ]),
+        Line::from(vec![
+            Span::raw("  • Infix: 'iterate(x/2 + 1/x, 1, 10)' pushes x1..x10 of x(k+1) = f(x(k)); F6 plots the sequence")
+        ]),
+        Line::from(vec![
+            Span::raw("  • Ctrl+E opens a multi-line editor for long formulas ('#' comments); Ctrl+S pushes the result")
+        ]),
+        Line::from(vec![
+            Span::raw("  • keys = \"vi\" in config.toml: j/k browse, dd drops, p duplicates, i or a digit to type, Esc back")
+        ]),
+        Line::from(vec![
+            Span::raw("  • F10 charts the real values on the stack as bars or a sparkline (Tab switches)")
+        ]),
+        Line::from(vec![
+            Span::raw("  • Ctrl+G writes the stack to a CSV and plots it in gnuplot; g does the same in the F6 and F10 plots")
+        ]),
+        Line::from(vec![
+            Span::raw("  • F11 opens a stopwatch and countdown timer; Enter pushes the elapsed seconds")
+        ]),
+        Line::from(vec![
+            Span::raw("  • Holding Up/Down speeds up; Home/End jump to the top or bottom, Ctrl+Up/Down by a screenful")
+        ]),
+        Line::from(vec![
+            Span::raw("  • Alt+Up/Down bring back lines you typed this session, including ones that failed, to fix and Enter again")
+        ]),
+        Line::from(vec![
+            Span::raw("  • b shows the selected entry in hex, octal and binary in the status line, without switching the base")
+        ]),
+        Line::from(vec![
+            Span::raw("  • Ctrl+O lists the session's recent errors with their time and input; Enter puts one's input back to fix")
+        ]),
+        Line::from(vec![
+            Span::raw("  • F12 shows a debug overlay with the frame time, last key and last error, over any screen")
+        ]),
+        Line::from(vec![
+            Span::raw("  • Infix: 'solve(cos(x) - x, 0)' pushes a root of the expression in x found from the guess 0")
+        ]),
+        Line::from(vec![
+            Span::raw("  • Infix: 'integrate(sin(x), 0, pi)' and 'derivative(x^3, 2)' push the value; the status shows ± error")
+        ]),
+        Line::from(vec![
+            Span::raw("  • Infix: 'plot(sin(x)/x, -20, 20)' graphs y = f(x); ←/→ pan, ↑/↓ or +/- zoom, Esc closes")
+        ]),
+        Line::from(vec![
+            Span::raw("  • F7 opens a side calculator; Enter inserts its result at the cursor without touching the stack")
+        ]),
+        Line::from(vec![
+            Span::raw("  • After an error, Ctrl+F applies the suggested fix shown under it (typos, missing ')', 0x prefix)")
+        ]),
+        Line::from(vec![
+            Span::raw("  • Infix: Tab completes function and constant names (sin, sqrt, pi, ...); Enter picks from the list")
+        ]),
+        Line::from(vec![
+            Span::raw("  • ':set words on' types RPN words: '3 4 swap -', '2 dup *', '1 2 3 mean'; Enter runs the line")
+        ]),
+        Line::from(vec![
+            Span::raw("  • ':set si on' reads SI prefixes: '4.7k' is 4700, '100n' is 1e-7, '2.2M' is 2200000")
+        ]),
+        Line::from(vec![
+            Span::raw("  • ':set python on' reads Python: '2**10', '7 // 2' (floor division) and '1_000_000'")
+        ]),
+        Line::from(vec![
+            Span::raw("  • ncr, npr, gcd, lcm work on integers; in RPN ':gcd' applies a function to the stack")
+        ]),
+        Line::from(vec![
+            Span::raw("  • round, floor, ceil, trunc; rnd(x, 2) to 2 decimals; ':set rounding half-even' for banker's rounding")
+        ]),
+        Line::from(vec![
+            Span::raw("  • x adds tax, X takes it back out, g adds a tip, at tax-rate/tip-rate from config.toml or ':set tax 8.25'")
+        ]),
+        Line::from(vec![
+            Span::raw("  • Every result adds to the grand total in the status bar; G pushes it, ':gt reset' zeroes it")
+        ]),
+        Line::from(vec![
+            Span::raw("  • isprime, nextprime, modpow; factor(n) or ':factor' pushes the prime factors")
+        ]),
+        Line::from(vec![
+            Span::raw("  • db/undb for power, dbv/undbv for amplitude ratios; dbm2w and w2dbm for RF levels")
+        ]),
+        Line::from(vec![
+            Span::raw("  • c2f, f2c, c2k, k2c convert temperatures; in RPN ':c2f' converts the top of the stack")
+        ]),
+        Line::from(vec![
+            Span::raw("  • v compares the selected entry (or s2) with the top: difference, ratio, % change; Enter pushes one")
+        ]),
+        Line::from(vec![
+            Span::raw("  • a shows the selected entry in radians, degrees, gradians and turns; Enter replaces it with one")
         ]),
-        Line::from(""),
         Line::from(vec![
-            Span::styled("Press 'h' or Esc to close this dialog", Style::default().fg(calculator.current_theme.input_placeholder).add_modifier(Modifier::ITALIC))
+            Span::raw("  • Infix mode types letters into the expression; hold Alt for letter shortcuts (Alt+m, Alt+h, ...)")
         ]),
     ];
+    if focus.is_main_screen() {
+        help_content.extend(tips);
+    }
+    help_content.push(Line::from(""));
+    help_content.push(Line::from(vec![
+        Span::styled("Press 'h' or Esc to close this dialog", Style::default().fg(theme.input_placeholder).add_modifier(Modifier::ITALIC))
+    ]));
     
     let help_dialog = Paragraph::new(help_content)
-        .block(Block::default()
-            .borders(Borders::ALL)
-            .title(" Help ")
-            .title_alignment(Alignment::Center)
-            .border_style(Style::default().fg(calculator.current_theme.border))
+        .block(theme.dialog(" Help ")
             .padding(Padding::uniform(2)))
         .wrap(Wrap { trim: false })
         .alignment(Alignment::Left);
@@ -368,28 +849,1019 @@ fn draw_help_dialog(f: &mut Frame, calculator: &mut Calculator) {
     f.render_widget(help_dialog, area);
 }
 
-fn draw_theme_selector_dialog(f: &mut Frame, calculator: &mut Calculator) {
+fn draw_theme_selector_dialog(f: &mut Frame, app: &mut App) {
     let area = centered_rect(60, 50, f.area());
 
     f.render_widget(Clear, area);
 
-    let theme_items: Vec<ListItem> = calculator.available_themes.iter().map(|theme_name| {
+    let theme_items: Vec<ListItem> = app.available_themes.iter().map(|theme_name| {
         ListItem::new(Span::raw(theme_name))
     }).collect();
 
+    // The theme being previewed, checked for text that's hard to read
+    let theme = &app.current_theme;
+    let warnings = theme.contrast_warnings();
+    let contrast_lines: Vec<Line> = if warnings.is_empty() {
+        vec![Line::from(Span::styled(format!("All text colors meet {}:1", theme::MIN_CONTRAST), Style::default().fg(theme.success)))]
+    } else {
+        warnings.into_iter().map(|warning| Line::from(Span::styled(format!("⚠ {}", warning), Style::default().fg(theme.warning)))).collect()
+    };
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(3), Constraint::Length(contrast_lines.len().min(4) as u16 + 2)])
+        .split(area);
+
     let theme_list = List::new(theme_items)
-        .block(Block::default()
-            .borders(Borders::ALL)
-            .title(" Select Theme ")
-            .title_alignment(Alignment::Center)
-            .border_style(Style::default().fg(calculator.current_theme.border)))
-        .highlight_style(Style::default().bg(calculator.current_theme.highlight_bg).fg(calculator.current_theme.highlight_fg))
+        .block(theme.dialog(" Select Theme "))
+        .highlight_style(Style::default().bg(theme.highlight_bg).fg(theme.highlight_fg))
         .highlight_symbol("-> ");
+    let contrast = Paragraph::new(contrast_lines)
+        .block(theme.dialog(" Contrast "))
+        .wrap(Wrap { trim: true });
+    f.render_widget(contrast, chunks[1]);
+
+    f.render_stateful_widget(theme_list, chunks[0], &mut app.theme_list_state);
+}
+
+/// The previewed theme's colors, each with a swatch, for picking the field to change.
+fn draw_theme_colors_dialog(f: &mut Frame, app: &App) {
+    let theme = &app.current_theme;
+    let area = centered_rect(70, 80, f.area());
+
+    f.render_widget(Clear, area);
+
+    let faint = Style::default().fg(theme.input_placeholder);
+    let mut lines = vec![Line::from(Span::styled(format!("Theme '{}'", theme.name), Style::default().fg(theme.info))), Line::from("")];
+    for (name, color, fallback) in theme.color_fields() {
+        let mut spans = vec![Span::styled(format!("{:<20}", name), Style::default().fg(theme.warning))];
+        match color {
+            Some(color) => spans.extend([Span::styled("      ", Style::default().bg(color)), Span::raw(format!("  {}", theme::color_text(color)))]),
+            None => spans.extend([Span::raw("      "), Span::styled(format!("  not set, uses {}", fallback), faint)]),
+        }
+        lines.push(Line::from(spans));
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled("Up/Down scroll, Esc goes back to the selector", faint)));
+
+    let dialog = Paragraph::new(lines)
+        .block(theme.dialog(" Theme Colors ")
+            .padding(Padding::horizontal(1)))
+        .style(Style::default().fg(theme.foreground))
+        .scroll((app.theme_colors_scroll, 0));
+
+    f.render_widget(dialog, area);
+}
+
+/// Candidate list anchored just above the word being completed in the input box.
+fn draw_completion_popup(f: &mut Frame, calculator: &Calculator, theme: &Theme, input_area: Rect) {
+    let Some(completion) = &calculator.completion else {
+        return;
+    };
+    let name_width = completion.candidates.iter().map(|candidate| candidate.name.len()).max().unwrap_or(0);
+
+    let items: Vec<ListItem> = completion.candidates.iter().map(|candidate| {
+        let color = match candidate.kind {
+            CandidateKind::Function => theme.success,
+            CandidateKind::Constant => theme.info,
+        };
+        ListItem::new(Line::from(vec![
+            Span::styled(format!("{:<width$}", candidate.name, width = name_width), Style::default().fg(color)),
+            Span::styled(format!("  {}", candidate.detail), Style::default().fg(theme.input_placeholder)),
+        ]))
+    }).collect();
+
+    let detail_width = completion.candidates.iter().map(|candidate| candidate.detail.len()).max().unwrap_or(0);
+    let width = ((name_width + detail_width + 4) as u16).min(f.area().width);
+    let height = (completion.candidates.len().min(8) as u16 + 2).min(input_area.y);
+    let x = (input_area.x + 1 + completion.start as u16).min(f.area().width.saturating_sub(width));
+    let area = Rect::new(x, input_area.y - height, width, height);
+
+    f.render_widget(Clear, area);
+    let list = List::new(items)
+        .block(theme.dialog(""))
+        .highlight_style(Style::default().bg(theme.highlight_bg).fg(theme.highlight_fg));
+    let mut state = ListState::default().with_selected(Some(completion.selected));
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_float_inspector_dialog(f: &mut Frame, calculator: &Calculator, theme: &Theme) {
+    let area = centered_rect(80, 60, f.area());
+
+    f.render_widget(Clear, area);
+
+    let label = |text: &'static str| Span::styled(text, Style::default().fg(theme.warning));
+
+    let content = match calculator.top_real() {
+        Some(value) => {
+            let parts = FloatParts::decompose(value);
+            let exponent = match parts.unbiased_exponent() {
+                Some(e) => format!("{} (biased {})", e, parts.exponent_bits),
+                None => format!("special (biased {})", parts.exponent_bits),
+            };
+            let mut lines = vec![
+                Line::from(vec![label("Value:      "), Span::raw(format!("{:e}", parts.value))]),
+                Line::from(vec![label("Class:      "), Span::raw(parts.category_name())]),
+                Line::from(vec![label("Hex:        "), Span::styled(format!("0x{:016X}", parts.bits), Style::default().fg(theme.info))]),
+                Line::from(vec![label("Bits:       "), Span::styled(parts.grouped_binary(), Style::default().fg(theme.info))]),
+                Line::from(""),
+                Line::from(vec![label("Sign:       "), Span::raw(if parts.sign == 1 { "1 (negative)" } else { "0 (positive)" })]),
+                Line::from(vec![label("Exponent:   "), Span::raw(exponent)]),
+                Line::from(vec![label("Mantissa:   "), Span::raw(format!("0x{:013X}", parts.mantissa))]),
+                Line::from(""),
+                Line::from(vec![label("Previous:   "), Span::raw(format!("{:e}", parts.previous))]),
+                Line::from(vec![label("Next:       "), Span::raw(format!("{:e}", parts.next))]),
+                Line::from(vec![label("ULP:        "), Span::raw(format!("{:e}", parts.ulp))]),
+            ];
+            if let Some(second) = calculator.second_real() {
+                let distance = float_inspect::ulp_distance(second, value)
+                    .map_or_else(|| "n/a (NaN)".to_string(), |d| format!("{} ULPs", d));
+                lines.push(Line::from(vec![label("To Y:       "), Span::raw(distance)]));
+            }
+            lines
+        }
+        None => vec![Line::from(Span::styled(
+            "Top of stack must be a real number",
+            Style::default().fg(theme.error),
+        ))],
+    };
+
+    let dialog = Paragraph::new(content)
+        .block(theme.dialog(" IEEE-754 Inspector ")
+            .padding(Padding::horizontal(1)))
+        .style(Style::default().fg(theme.foreground))
+        .wrap(Wrap { trim: false });
+
+    f.render_widget(dialog, area);
+}
+
+fn draw_inspect_dialog(f: &mut Frame, calculator: &Calculator, theme: &Theme) {
+    let Some(entry) = calculator.selected_entry() else {
+        return;
+    };
+    let area = centered_rect(80, 60, f.area());
+
+    f.render_widget(Clear, area);
+
+    let mut lines = vec![Line::from(Span::styled(entry.expression.to_string(), Style::default().fg(theme.info)))];
+    // Where the value came from; entries saved before ids were kept have none
+    if entry.id > 0 {
+        let time = entry.time_label().map(|time| format!(", {} UTC", time)).unwrap_or_default();
+        lines.push(Line::from(Span::styled(format!("Entry #{}, {}{}", entry.id, entry.origin.label(), time), Style::default().fg(theme.border))));
+    }
+    lines.push(Line::from(""));
+    lines.extend(inspect::rows(&entry.result, calculator.word_size, calculator.angle_mode).into_iter().map(|(name, text)| {
+        Line::from(vec![Span::styled(format!("{:<13}", format!("{}:", name)), Style::default().fg(theme.warning)), Span::raw(text)])
+    }));
+    // Every element of a vector, which its stack row may cut short
+    if let StackValue::Vector(items) = &entry.result {
+        lines.push(Line::from(""));
+        lines.extend(items.iter().enumerate().map(|(i, x)| {
+            Line::from(vec![Span::styled(format!("{:<13}", format!("[{}]", i + 1)), Style::default().fg(theme.warning)), Span::raw(calculator.format_real(*x))])
+        }));
+    }
+
+    let dialog = Paragraph::new(lines)
+        .block(theme.dialog(" Inspect ")
+            .padding(Padding::horizontal(1)))
+        .style(Style::default().fg(theme.foreground))
+        .wrap(Wrap { trim: false });
+
+    f.render_widget(dialog, area);
+}
+
+/// F12: frame time, last key, the state keys are dispatched on, and the last error's chain.
+fn draw_debug_overlay(f: &mut Frame, app: &App) {
+    let calculator = &app.calculator;
+    let theme = &app.current_theme;
+    let screen = f.area();
+    let width = 44.min(screen.width);
+    let row = |name: &str, value: String| Line::from(vec![Span::styled(format!("{:<8}", name), Style::default().fg(theme.warning)), Span::raw(value)]);
+    let on = |flag: bool| if flag { "on" } else { "off" };
+
+    let mut lines = vec![
+        row("Frame", format!("{:.2} ms", app.debug.frame_time.as_secs_f64() * 1000.0)),
+        row("Key", app.debug.last_key.clone().unwrap_or_else(|| "-".to_string())),
+        row("Focus", app.focus().title().to_string()),
+        row("Mode", format!("{:?}, {:?} layout", calculator.mode, app.layout)),
+        row("Vi", app.vi_mode.map(|mode| format!("{:?}", mode)).unwrap_or_else(|| "off".to_string())),
+        row("Rapid", on(calculator.rapid_start.is_some()).to_string()),
+        row("Reader", on(app.announcer.is_some()).to_string()),
+        row("Journal", on(app.journal.is_some()).to_string()),
+    ];
+    #[cfg(unix)]
+    lines.push(row("Server", on(app.server.is_some()).to_string()));
+    lines.push(row("Sizes", format!("{} stack, {} history", calculator.stack.len(), calculator.history.len())));
+    lines.push(row("Input", format!("{:?} at {}", calculator.input, calculator.cursor)));
+    lines.push(Line::from(""));
+    if app.debug.last_error.is_empty() {
+        lines.push(Line::from(Span::styled("No errors", Style::default().fg(theme.border))));
+    }
+    for (depth, message) in app.debug.last_error.iter().enumerate() {
+        let prefix = if depth == 0 { String::new() } else { format!("{}caused by: ", "  ".repeat(depth - 1)) };
+        lines.push(Line::from(Span::styled(format!("{}{}", prefix, message), Style::default().fg(theme.error))));
+    }
+
+    let height = (lines.len() as u16 + 2).min(screen.height);
+    let area = Rect::new(screen.x + screen.width - width, screen.y, width, height);
+    f.render_widget(Clear, area);
+    let overlay = Paragraph::new(lines)
+        .block(theme.dialog(" Debug (F12) "))
+        .style(Style::default().fg(theme.foreground))
+        .wrap(Wrap { trim: false });
+    f.render_widget(overlay, area);
+}
+
+fn draw_recovery_dialog(f: &mut Frame, journal: &Journal, theme: &Theme) {
+    let area = centered_rect(60, 30, f.area());
+
+    f.render_widget(Clear, area);
+
+    let mut lines = vec![
+        Line::from("The last session didn't exit cleanly."),
+        Line::from(""),
+        Line::from(format!("Stack entries: {} in {} workspace(s)", journal.entry_count(), journal.workspaces.len())),
+    ];
+    if !journal.input.is_empty() {
+        lines.push(Line::from(vec![Span::raw("Input: "), Span::styled(journal.input.clone(), Style::default().fg(theme.info))]));
+    }
+    lines.extend([
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("y", Style::default().fg(theme.warning)),
+            Span::raw(" restore it   "),
+            Span::styled("n", Style::default().fg(theme.warning)),
+            Span::raw(" start fresh   "),
+            Span::styled("q", Style::default().fg(theme.warning)),
+            Span::raw(" quit and decide later"),
+        ]),
+    ]);
+
+    let dialog = Paragraph::new(lines)
+        .block(theme.dialog(" Restore Session? ")
+            .padding(Padding::horizontal(1)))
+        .style(Style::default().fg(theme.foreground))
+        .wrap(Wrap { trim: false });
+
+    f.render_widget(dialog, area);
+}
+
+/// A destructive action waiting for y or n; nothing else takes keys meanwhile.
+fn draw_confirm_dialog(f: &mut Frame, prompt: &str, theme: &Theme) {
+    let area = centered_rect(50, 25, f.area());
+
+    f.render_widget(Clear, area);
+
+    let lines = vec![
+        Line::from(prompt.to_string()),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("y", Style::default().fg(theme.warning)),
+            Span::raw(" yes   "),
+            Span::styled("n", Style::default().fg(theme.warning)),
+            Span::raw(" or "),
+            Span::styled("Esc", Style::default().fg(theme.warning)),
+            Span::raw(" no"),
+        ]),
+    ];
+
+    let dialog = Paragraph::new(lines)
+        .block(theme.dialog(" Are You Sure? ")
+            .padding(Padding::horizontal(1)))
+        .style(Style::default().fg(theme.foreground))
+        .wrap(Wrap { trim: false });
+
+    f.render_widget(dialog, area);
+}
+
+fn draw_table_import_dialog(f: &mut Frame, calculator: &Calculator, theme: &Theme) {
+    let Some(import) = &calculator.table_import else {
+        return;
+    };
+    let area = centered_rect(70, 60, f.area());
+
+    f.render_widget(Clear, area);
+
+    let mut content = vec![
+        Line::from(format!("Pasted table: {} rows, {} columns", import.rows.len(), import.columns())),
+        Line::from(vec![
+            Span::raw("Import as: "),
+            Span::styled(
+                if import.summary { "summary (sum, mean, min, max)" } else { "raw values" },
+                Style::default().fg(theme.info).add_modifier(Modifier::BOLD),
+            ),
+        ]),
+        Line::from(""),
+    ];
+    for column in 0..import.columns() {
+        let values = import.values(column);
+        let marker = if import.selected[column] { "[x]" } else { "[ ]" };
+        let detail = match import.summarize(column) {
+            Some(summary) => format!("{} numbers, sum {}", values.len(), calculator.format_real(summary.sum)),
+            None => "no numbers".to_string(),
+        };
+        let mut style = Style::default().fg(theme.foreground);
+        if column == import.cursor {
+            style = style.bg(theme.highlight_bg).fg(theme.highlight_fg);
+        }
+        content.push(Line::from(Span::styled(
+            format!("{} {:<16} {}", marker, text::truncate(&import.column_name(column), 16), detail),
+            style,
+        )));
+    }
+    content.push(Line::from(""));
+    content.push(Line::from(Span::styled(
+        "Up/Down: column | Space: select | s: raw/summary | Enter: import | Esc: cancel",
+        Style::default().fg(theme.input_placeholder).add_modifier(Modifier::ITALIC),
+    )));
+
+    let dialog = Paragraph::new(content)
+        .block(theme.dialog(" Import Table ")
+            .padding(Padding::horizontal(1)))
+        .wrap(Wrap { trim: false });
+
+    f.render_widget(dialog, area);
+}
+
+fn draw_iteration_plot_dialog(f: &mut Frame, calculator: &Calculator, theme: &Theme) {
+    let Some(sequence) = &calculator.last_iteration else {
+        return;
+    };
+    let area = centered_rect(80, 70, f.area());
+
+    f.render_widget(Clear, area);
+
+    let points: Vec<(f64, f64)> = sequence
+        .iter()
+        .enumerate()
+        .filter(|(_, y)| y.is_finite())
+        .map(|(k, y)| (k as f64, *y))
+        .collect();
+    let (y_min, y_max) = points.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), (_, y)| (lo.min(*y), hi.max(*y)));
+    // Pad flat or empty series so the axis still has some height
+    let (y_min, y_max) = if y_min.is_finite() && y_max > y_min { (y_min, y_max) } else if y_min.is_finite() { (y_min - 1.0, y_min + 1.0) } else { (-1.0, 1.0) };
+    let x_max = (sequence.len().saturating_sub(1)).max(1) as f64;
+
+    let dataset = Dataset::default()
+        .marker(Marker::Braille)
+        .graph_type(GraphType::Line)
+        .style(Style::default().fg(theme.success))
+        .data(&points);
+
+    let axis_style = Style::default().fg(theme.foreground);
+    let chart = Chart::new(vec![dataset])
+        .block(theme.dialog(format!(" Iteration ({} steps, g plots in gnuplot) ", sequence.len() - 1)))
+        .x_axis(Axis::default()
+            .title("k")
+            .style(axis_style)
+            .bounds([0.0, x_max])
+            .labels(["0".to_string(), format!("{}", x_max)]))
+        .y_axis(Axis::default()
+            .title("x")
+            .style(axis_style)
+            .bounds([y_min, y_max])
+            .labels([calculator.format_real(y_min), calculator.format_real(y_max)]));
+
+    f.render_widget(chart, area);
+}
+
+fn draw_function_plot_dialog(f: &mut Frame, calculator: &Calculator, theme: &Theme) {
+    let Some(plot) = &calculator.function_plot else {
+        return;
+    };
+    let area = centered_rect(90, 80, f.area());
+
+    f.render_widget(Clear, area);
+
+    // Braille packs two dots per cell horizontally; oversample so steep parts still read as lines
+    let points = calculator.plot_points(area.width as usize * 8);
+    let (y_min, y_max) = plot::y_bounds(&points);
+
+    // Scatter rather than a line so poles and gaps aren't bridged
+    let dataset = Dataset::default()
+        .marker(Marker::Braille)
+        .graph_type(GraphType::Scatter)
+        .style(Style::default().fg(theme.success))
+        .data(&points);
+
+    let axis_style = Style::default().fg(theme.foreground);
+    let chart = Chart::new(vec![dataset])
+        .block(theme.dialog(format!(" y = {}  (←→ pan, ↑↓ zoom, Esc close) ", plot.label)))
+        .x_axis(Axis::default()
+            .title("x")
+            .style(axis_style)
+            .bounds([plot.x_min, plot.x_max])
+            .labels([axis_label(plot.x_min), axis_label((plot.x_min + plot.x_max) / 2.0), axis_label(plot.x_max)]))
+        .y_axis(Axis::default()
+            .title("y")
+            .style(axis_style)
+            .bounds([y_min, y_max])
+            .labels([axis_label(y_min), axis_label((y_min + y_max) / 2.0), axis_label(y_max)]));
+
+    f.render_widget(chart, area);
+}
+
+fn draw_stack_chart_dialog(f: &mut Frame, calculator: &Calculator, theme: &Theme, sparkline: bool) {
+    let area = centered_rect(80, 70, f.area());
+
+    f.render_widget(Clear, area);
+
+    // Complex and non-finite entries can't be drawn as a height, so they're left out
+    let values: Vec<f64> = calculator.stack.iter().filter_map(|entry| entry.result.as_real()).filter(|value| value.is_finite()).collect();
+    let style = if sparkline { "sparkline" } else { "bars" };
+    let block = theme.dialog(format!(" Stack Chart: {} values, {} (Tab switches, g gnuplot, Esc closes) ", values.len(), style));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+    if values.is_empty() {
+        let empty = Paragraph::new("No real values on the stack").style(Style::default().fg(theme.input_placeholder));
+        f.render_widget(empty, inner);
+        return;
+    }
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .split(inner);
+
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let heights = chart_heights(&values);
+
+    if sparkline {
+        // The sparkline shows the newest values that fit, one per column
+        let start = heights.len().saturating_sub(chunks[0].width as usize);
+        let chart = Sparkline::default().data(&heights[start..]).max(1000).style(Style::default().fg(theme.success));
+        f.render_widget(chart, chunks[0]);
+    } else {
+        // Oldest value on the left, numbered by stack level like the stack list
+        let bars: Vec<Bar> = values.iter().zip(&heights).enumerate().map(|(index, (value, height))| {
+            Bar::default()
+                .value(*height)
+                .label(Line::from(format!("{}", values.len() - index)))
+                .text_value(axis_label(*value))
+        }).collect();
+        let bar_width = (chunks[0].width / values.len().max(1) as u16).saturating_sub(1).clamp(1, 9);
+        let chart = BarChart::default()
+            .data(BarGroup::default().bars(&bars))
+            .bar_width(bar_width)
+            .bar_gap(1)
+            .max(1000)
+            .bar_style(Style::default().fg(theme.success))
+            .value_style(Style::default().fg(theme.background).bg(theme.success))
+            .label_style(Style::default().fg(theme.foreground));
+        f.render_widget(chart, chunks[0]);
+    }
+
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let summary = Line::from(vec![
+        Span::styled("min ", Style::default().fg(theme.input_placeholder)),
+        Span::raw(calculator.format_real(min)),
+        Span::styled("  max ", Style::default().fg(theme.input_placeholder)),
+        Span::raw(calculator.format_real(max)),
+        Span::styled("  mean ", Style::default().fg(theme.input_placeholder)),
+        Span::raw(calculator.format_real(mean)),
+    ]);
+    f.render_widget(Paragraph::new(summary).style(Style::default().fg(theme.foreground)), chunks[1]);
+}
+
+// Short tick labels: three decimals, or scientific notation for very large or small values
+fn axis_label(value: f64) -> String {
+    if value != 0.0 && !(1e-3..1e5).contains(&value.abs()) {
+        return format!("{:.2e}", value);
+    }
+    let fixed = format!("{:.3}", value);
+    let trimmed = fixed.trim_end_matches('0').trim_end_matches('.');
+    if trimmed == "-0" { "0".to_string() } else { trimmed.to_string() }
+}
+
+fn draw_editor_dialog(f: &mut Frame, calculator: &Calculator, theme: &Theme) {
+    let Some(editor) = &calculator.editor else {
+        return;
+    };
+    let area = centered_rect(80, 70, f.area());
+
+    f.render_widget(Clear, area);
+
+    let caret = Style::default().add_modifier(Modifier::REVERSED);
+    let comment = Style::default().fg(theme.input_placeholder);
+    let gutter_width = editor.lines.len().to_string().len();
+    let mut content: Vec<Line> = editor.lines.iter().enumerate().map(|(row, line)| {
+        let mut spans = vec![Span::styled(format!("{:>width$} ", row + 1, width = gutter_width), comment)];
+        let comment_start = line.find('#').map_or(usize::MAX, |offset| line[..offset].chars().count());
+        for (col, ch) in line.chars().enumerate() {
+            let mut style = if col >= comment_start { comment } else { Style::default().fg(theme.input_text) };
+            if row == editor.row && col == editor.col {
+                style = style.patch(caret);
+            }
+            spans.push(Span::styled(ch.to_string(), style));
+        }
+        if row == editor.row && editor.col >= line.chars().count() {
+            spans.push(Span::styled(" ", caret));
+        }
+        Line::from(spans)
+    }).collect();
+
+    // Live result of the whole formula, or why it doesn't evaluate (yet)
+    let source = editor.source();
+    content.push(Line::from(""));
+    content.push(match (&calculator.error, source.is_empty()) {
+        (Some(error), _) => Line::from(Span::styled(format!("{}: {}", error.code(), error), Style::default().fg(theme.error))),
+        (None, true) => Line::from(Span::styled("Lines are joined into one expression; # starts a comment", comment)),
+        (None, false) => match calculator.evaluate(&source) {
+            Ok(value) => Line::from(Span::styled(format!("= {}", calculator.format_real(value)), Style::default().fg(theme.success))),
+            Err(error) => Line::from(Span::styled(error.to_string(), comment)),
+        },
+    });
+
+    let dialog = Paragraph::new(content)
+        .block(theme.dialog(" Expression Editor (Ctrl+S evaluates and pushes, Esc cancels) "));
+    f.render_widget(dialog, area);
+}
+
+fn draw_mini_calculator_dialog(f: &mut Frame, calculator: &Calculator, theme: &Theme) {
+    let Some(input) = &calculator.mini_input else {
+        return;
+    };
+    let screen = f.area();
+    let width = (screen.width * 3 / 5).max(30).min(screen.width);
+    let area = Rect::new(screen.x + (screen.width - width) / 2, screen.y + screen.height.saturating_sub(4) / 2, width, 4.min(screen.height));
+
+    f.render_widget(Clear, area);
+
+    let result_line = match calculator.mini_result() {
+        Some(Ok(value)) => Line::from(Span::styled(format!("= {}", calculator.format_real(value)), Style::default().fg(theme.success))),
+        Some(Err(error)) => Line::from(Span::styled(error.to_string(), Style::default().fg(theme.error))),
+        None => Line::from(Span::styled("Enter inserts the result, Esc cancels", Style::default().fg(theme.input_placeholder))),
+    };
+    let content = vec![
+        Line::from(vec![
+            Span::styled(input.clone(), Style::default().fg(theme.input_text)),
+            Span::styled(" ", Style::default().add_modifier(Modifier::REVERSED)),
+        ]),
+        result_line,
+    ];
+
+    let dialog = Paragraph::new(content)
+        .block(theme.dialog(" Quick Calculation "));
+    f.render_widget(dialog, area);
+}
+
+fn draw_compare_dialog(f: &mut Frame, calculator: &Calculator, theme: &Theme) {
+    let Some(comparison) = &calculator.comparison else {
+        return;
+    };
+    let area = centered_rect(50, 40, f.area());
+
+    f.render_widget(Clear, area);
+
+    let row = |name: String, value: String, selected: bool| {
+        let line = Line::from(vec![
+            Span::styled(format!("{:<12}", name), Style::default().fg(theme.warning)),
+            Span::styled(value, Style::default().fg(theme.success)),
+        ]);
+        if selected { line.style(Style::default().bg(theme.highlight_bg)) } else { line }
+    };
+    let mut content = vec![
+        row(format!("Before (s{})", comparison.before_level), calculator.format_real(comparison.before), false),
+        row(format!("After (s{})", comparison.after_level), calculator.format_real(comparison.after), false),
+        Line::from(""),
+    ];
+    for (i, (metric, value)) in comparison.metrics().enumerate() {
+        let shown = if metric == Metric::PercentChange && value.is_finite() { format!("{}%", calculator.format_real(value)) } else { calculator.format_real(value) };
+        content.push(row(metric.label().to_string(), shown, i == comparison.selected));
+    }
+    content.push(Line::from(""));
+    content.push(Line::from(Span::styled("Enter pushes the selected one", Style::default().fg(theme.input_placeholder))));
+
+    let dialog = Paragraph::new(content)
+        .block(theme.dialog(" Compare ")
+            .padding(Padding::horizontal(1)))
+        .style(Style::default().fg(theme.foreground))
+        .wrap(Wrap { trim: false });
+
+    f.render_widget(dialog, area);
+}
+
+fn draw_angle_dialog(f: &mut Frame, calculator: &Calculator, theme: &Theme) {
+    let Some(conversion) = &calculator.angle_conversion else {
+        return;
+    };
+    let area = centered_rect(50, 40, f.area());
+
+    f.render_widget(Clear, area);
+
+    let mut content = vec![
+        Line::from(Span::styled(format!("s{} read as {}", conversion.level, conversion.from.label().to_lowercase()), Style::default().fg(theme.info))),
+        Line::from(""),
+    ];
+    for (i, (unit, value)) in conversion.conversions().enumerate() {
+        let line = Line::from(vec![
+            Span::styled(format!("{:<10}", unit.label()), Style::default().fg(theme.warning)),
+            Span::styled(format!("{} {}", calculator.format_real(value), unit.symbol()), Style::default().fg(theme.success)),
+        ]);
+        content.push(if i == conversion.selected { line.style(Style::default().bg(theme.highlight_bg)) } else { line });
+    }
+    content.push(Line::from(""));
+    content.push(Line::from(Span::styled("Enter replaces the entry with the selected unit", Style::default().fg(theme.input_placeholder))));
+
+    let dialog = Paragraph::new(content)
+        .block(theme.dialog(" Angle units ")
+            .padding(Padding::horizontal(1)))
+        .style(Style::default().fg(theme.foreground))
+        .wrap(Wrap { trim: false });
+
+    f.render_widget(dialog, area);
+}
+
+fn draw_settings_dialog(f: &mut Frame, app: &App, theme: &Theme) {
+    let Some(dialog) = &app.calculator.settings_dialog else {
+        return;
+    };
+    let area = centered_rect(60, 50, f.area());
+
+    f.render_widget(Clear, area);
+
+    let saved_to = app.config_path.as_ref().map_or_else(|| "Changes last for this session".to_string(), |path| format!("Changes are saved to {}", path.display()));
+    let mut content = vec![Line::from(Span::styled(saved_to, Style::default().fg(theme.info))), Line::from("")];
+    for (i, setting) in Setting::ALL.into_iter().enumerate() {
+        let line = Line::from(vec![
+            Span::styled(format!("{:<19}", setting.label()), Style::default().fg(theme.warning)),
+            Span::styled(format!("◀ {} ▶", app.setting_value(setting)), Style::default().fg(theme.success)),
+            Span::styled(format!("  {}", setting.key()), Style::default().fg(theme.input_placeholder)),
+        ]);
+        content.push(if i == dialog.selected { line.style(Style::default().bg(theme.highlight_bg)) } else { line });
+    }
+    content.push(Line::from(""));
+    content.push(Line::from(Span::styled("Left/Right changes the setting at once; Esc closes", Style::default().fg(theme.input_placeholder))));
+
+    let dialog = Paragraph::new(content)
+        .block(theme.dialog(" Settings ")
+            .padding(Padding::horizontal(1)))
+        .style(Style::default().fg(theme.foreground))
+        .wrap(Wrap { trim: false });
+
+    f.render_widget(dialog, area);
+}
+
+fn draw_limits_dialog(f: &mut Frame, calculator: &Calculator, theme: &Theme) {
+    let Some(dialog) = &calculator.limits_dialog else {
+        return;
+    };
+    let area = centered_rect(50, 40, f.area());
+
+    f.render_widget(Clear, area);
+
+    let mut content = vec![
+        Line::from(Span::styled("Entries kept before the oldest are dropped", Style::default().fg(theme.info))),
+        Line::from(""),
+    ];
+    for (i, limit) in Limit::ALL.into_iter().enumerate() {
+        let line = Line::from(vec![
+            Span::styled(format!("{:<17}", limit.label()), Style::default().fg(theme.warning)),
+            Span::styled(format!("◀ {} ▶", limits::label(calculator.limit(limit))), Style::default().fg(theme.success)),
+            Span::styled(format!("  {}", calculator.limit_summary(limit)), Style::default().fg(theme.input_placeholder)),
+        ]);
+        content.push(if i == dialog.selected { line.style(Style::default().bg(theme.highlight_bg)) } else { line });
+    }
+    content.push(Line::from(""));
+    content.push(Line::from(Span::styled("Left/Right changes the limit; pinned history entries are always kept", Style::default().fg(theme.input_placeholder))));
+
+    let dialog = Paragraph::new(content)
+        .block(theme.dialog(" Limits ")
+            .padding(Padding::horizontal(1)))
+        .style(Style::default().fg(theme.foreground))
+        .wrap(Wrap { trim: false });
+
+    f.render_widget(dialog, area);
+}
+
+fn draw_subnet_dialog(f: &mut Frame, calculator: &Calculator, theme: &Theme) {
+    let Some(view) = &calculator.subnet else {
+        return;
+    };
+    let cidr = view.cidr;
+    let area = centered_rect(50, 50, f.area());
+
+    f.render_widget(Clear, area);
+
+    let row = |name: &str, value: String| {
+        Line::from(vec![
+            Span::styled(format!("{:<12}", name), Style::default().fg(theme.warning)),
+            Span::styled(value, Style::default().fg(theme.success)),
+        ])
+    };
+    let (first, last) = cidr.host_range();
+    let mut content = vec![
+        row("Network", format!("{}/{}", network::format_ip(cidr.network()), cidr.prefix)),
+        row("Netmask", network::format_ip(cidr.netmask())),
+        row("Wildcard", network::format_ip(!cidr.netmask())),
+        row("Broadcast", network::format_ip(cidr.broadcast())),
+        row("Hosts", format!("{} to {}", network::format_ip(first), network::format_ip(last))),
+        row("Usable", cidr.usable_hosts().to_string()),
+        row("Addresses", cidr.size().to_string()),
+    ];
+    if let Some(probe) = view.probe {
+        let (verdict, color) = if cidr.contains(probe) { ("inside", theme.success) } else { ("outside", theme.error) };
+        content.push(Line::from(""));
+        content.push(Line::from(vec![
+            Span::raw(format!("{} is ", network::format_ip(probe))),
+            Span::styled(verdict, Style::default().fg(color).add_modifier(Modifier::BOLD)),
+            Span::raw(" the block"),
+        ]));
+    }
+
+    let dialog = Paragraph::new(content)
+        .block(theme.dialog(" Subnet ")
+            .padding(Padding::horizontal(1)))
+        .style(Style::default().fg(theme.foreground))
+        .wrap(Wrap { trim: false });
+
+    f.render_widget(dialog, area);
+}
+
+fn draw_stopwatch_dialog(f: &mut Frame, stopwatch: &Stopwatch, theme: &Theme) {
+    let area = centered_rect(40, 30, f.area());
+
+    f.render_widget(Clear, area);
+
+    let now = Instant::now();
+    let (title, state) = match stopwatch.mode {
+        TimerMode::Stopwatch => (" Stopwatch ", if stopwatch.is_running() { "running" } else { "paused" }.to_string()),
+        TimerMode::Countdown if stopwatch.shown(now).is_zero() => (" Countdown ", "time's up".to_string()),
+        TimerMode::Countdown => (" Countdown ", format!("{} of {}", if stopwatch.is_running() { "running" } else { "paused" }, stopwatch::format_duration(stopwatch.countdown))),
+    };
+    let color = if stopwatch.mode == TimerMode::Countdown && stopwatch.shown(now).is_zero() { theme.error } else { theme.success };
+    let content = vec![
+        Line::from(""),
+        Line::from(Span::styled(stopwatch::format_duration(stopwatch.shown(now)), Style::default().fg(color).add_modifier(Modifier::BOLD))).centered(),
+        Line::from(Span::styled(state, Style::default().fg(theme.input_placeholder))).centered(),
+        Line::from(""),
+        Line::from("Space start/pause · r reset · Tab mode").centered(),
+        Line::from("Enter pushes the seconds · Esc closes").centered(),
+    ];
+
+    let dialog = Paragraph::new(content)
+        .block(theme.dialog(title))
+        .style(Style::default().fg(theme.foreground));
+    f.render_widget(dialog, area);
+}
+
+/// The session's errors, newest first, each with the input that caused it underneath.
+fn draw_error_log_dialog(f: &mut Frame, log: &ErrorLog, theme: &Theme) {
+    let area = centered_rect(80, 70, f.area());
+
+    f.render_widget(Clear, area);
+
+    let title = match log.warnings() {
+        0 => format!(" Errors ({}) · times in UTC · Enter edits the input again ", log.len()),
+        warnings => format!(" Errors ({}) · warnings ({}) · times in UTC · Enter edits the input again ", log.len() - warnings, warnings),
+    };
+    if log.is_empty() {
+        let empty = Paragraph::new(Line::from(Span::styled("No errors this session", Style::default().fg(theme.success))).centered())
+            .block(theme.dialog(title))
+            .style(Style::default().fg(theme.foreground));
+        f.render_widget(empty, area);
+        return;
+    }
+    let items: Vec<ListItem> = log.iter().map(|error| {
+        let input = if error.input.is_empty() { "(no input)".to_string() } else { format!("input: {}", error.input) };
+        ListItem::new(vec![
+            Line::from(vec![
+                Span::styled(format!("{}  ", error.time_label()), Style::default().fg(theme.input_placeholder)),
+                Span::styled(format!("{}  ", error.code), Style::default().fg(theme.warning)),
+                Span::styled(error.message.clone(), Style::default().fg(if error.is_warning() { theme.warning } else { theme.error })),
+            ]),
+            Line::from(Span::styled(format!("{:10}{}", "", input), Style::default().fg(theme.foreground))),
+        ])
+    }).collect();
+    let list = List::new(items)
+        .block(theme.dialog(title))
+        .highlight_style(Style::default().bg(theme.highlight_bg));
+    let mut state = ListState::default().with_selected(Some(log.selected));
+    f.render_stateful_widget(list, area, &mut state);
+}
 
-    f.render_stateful_widget(theme_list, area, &mut calculator.theme_list_state);
+fn draw_about_dialog(f: &mut Frame, app: &App, theme: &Theme) {
+    let area = centered_rect(70, 60, f.area());
+
+    f.render_widget(Clear, area);
+
+    let faint = Style::default().fg(theme.input_placeholder);
+    let mut content = vec![
+        Line::from(Span::styled(format!("tui-calculator {}", update::VERSION), Style::default().fg(theme.info).add_modifier(Modifier::BOLD))),
+        Line::from(Span::styled(update::REPOSITORY, faint)),
+        Line::from(""),
+    ];
+    for (name, path) in app.about_files() {
+        let location = match path {
+            Some(path) if path.exists() => Span::raw(path.display().to_string()),
+            Some(path) => Span::styled(format!("{} (not there yet)", path.display()), faint),
+            None => Span::styled("off", faint),
+        };
+        content.push(Line::from(vec![Span::styled(format!("{:<17}", name), Style::default().fg(theme.warning)), location]));
+    }
+    content.push(Line::from(""));
+    let checking = if app.update_check.is_some() { "Checking GitHub for a newer release..." } else { "u checks GitHub for a newer release; update-check = \"on\" does at every start" };
+    content.push(Line::from(Span::styled(checking, faint)));
+    content.push(Line::from(Span::styled("Esc closes", faint)));
+
+    let dialog = Paragraph::new(content)
+        .block(theme.dialog(" About ")
+            .padding(Padding::horizontal(1)))
+        .style(Style::default().fg(theme.foreground))
+        .wrap(Wrap { trim: false });
+
+    f.render_widget(dialog, area);
+}
+
+fn draw_rates_dialog(f: &mut Frame, calculator: &Calculator, theme: &Theme) {
+    let rates = &calculator.rates;
+    let area = centered_rect(70, 70, f.area());
+
+    f.render_widget(Clear, area);
+
+    let source = match &rates.date {
+        Some(date) => format!("Reference rates of {}", date),
+        None => "No rates fetched yet".to_string(),
+    };
+    let mut content = vec![
+        Line::from(Span::styled(source, Style::default().fg(theme.warning).add_modifier(Modifier::BOLD))),
+        Line::from(format!("1 {} =", rates.base)),
+    ];
+    if rates.rates.is_empty() {
+        content.push(Line::from(Span::styled("  (none; press f to fetch, or ':rate USD 1.08' to enter one)", Style::default().fg(theme.input_placeholder))));
+    }
+    // Three columns; manual rates are marked with *
+    let cells: Vec<Span> = rates
+        .rates
+        .iter()
+        .map(|(code, rate)| {
+            let marker = if rates.manual.contains(code) { "*" } else { " " };
+            Span::styled(format!("  {} {:<12}{}", code, format!("{:.4}", rate), marker), Style::default().fg(theme.success))
+        })
+        .collect();
+    for row in cells.chunks(3) {
+        content.push(Line::from(row.to_vec()));
+    }
+    content.push(Line::from(""));
+    content.push(Line::from(Span::styled("* entered with :rate    f fetch    Esc close", Style::default().fg(theme.input_placeholder))));
+
+    let dialog = Paragraph::new(content)
+        .block(theme.dialog(" Exchange Rates ")
+            .padding(Padding::horizontal(1)))
+        .style(Style::default().fg(theme.foreground))
+        .wrap(Wrap { trim: false });
+
+    f.render_widget(dialog, area);
+}
+
+fn draw_usage_stats_dialog(f: &mut Frame, calculator: &Calculator, theme: &Theme) {
+    let Some(stats) = &calculator.usage_stats else {
+        return;
+    };
+    let area = centered_rect(60, 60, f.area());
+
+    f.render_widget(Clear, area);
+
+    let heading = |text: &'static str| Line::from(Span::styled(text, Style::default().fg(theme.warning).add_modifier(Modifier::BOLD)));
+    let counts = |entries: &[(String, usize)]| -> Vec<Line> {
+        if entries.is_empty() {
+            vec![Line::from(Span::styled("  (none yet)", Style::default().fg(theme.input_placeholder)))]
+        } else {
+            entries.iter().take(8).map(|(name, count)| {
+                Line::from(vec![
+                    Span::styled(format!("  {:<10}", name), Style::default().fg(theme.success)),
+                    Span::raw(format!("{}", count)),
+                ])
+            }).collect()
+        }
+    };
+
+    let mut content = vec![
+        Line::from(format!("Expressions in history: {}", stats.expressions)),
+        Line::from(format!("Average expression length: {:.1} characters", stats.average_length)),
+        Line::from(""),
+        heading("Operators"),
+    ];
+    content.extend(counts(&stats.operators));
+    content.push(Line::from(""));
+    content.push(heading("Functions"));
+    content.extend(counts(&stats.functions));
+
+    let dialog = Paragraph::new(content)
+        .block(theme.dialog(" Usage Statistics ")
+            .padding(Padding::horizontal(1)))
+        .style(Style::default().fg(theme.foreground))
+        .wrap(Wrap { trim: false });
+
+    f.render_widget(dialog, area);
+}
+
+/// Heights out of 1000 for a bar chart or sparkline. They're measured from
+/// min(0, smallest) so negative series still show their shape.
+fn chart_heights(values: &[f64]) -> Vec<u64> {
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let base = min.min(0.0);
+    values.iter().map(|value| if max > base { ((value - base) / (max - base) * 1000.0).round() as u64 } else { 0 }).collect()
+}
+
+fn draw_result_stats_dialog(f: &mut Frame, calculator: &Calculator, theme: &Theme) {
+    let Some(stats) = &calculator.result_stats else {
+        return;
+    };
+    let area = centered_rect(60, 60, f.area());
+
+    f.render_widget(Clear, area);
+
+    let block = theme.dialog(" History Results (Esc closes) ").padding(Padding::horizontal(1));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let row = |name: &str, value: String| Line::from(vec![Span::styled(format!("{:<8}", name), Style::default().fg(theme.warning)), Span::raw(value)]);
+    let mut content = vec![row("Count", format!("{} values", stats.count()))];
+    if stats.skipped > 0 {
+        content.push(Line::from(Span::styled(format!("{} entries without a real value left out", stats.skipped), Style::default().fg(theme.input_placeholder))));
+    }
+    if stats.count() > 0 {
+        content.push(row("Sum", calculator.format_real(stats.sum)));
+        content.push(row("Mean", calculator.format_real(stats.mean)));
+        content.push(row("Min", calculator.format_real(stats.min)));
+        content.push(row("Max", calculator.format_real(stats.max)));
+        content.push(Line::from(""));
+        content.push(Line::from(Span::styled("Trend, oldest to newest", Style::default().fg(theme.warning))));
+    }
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(content.len() as u16), Constraint::Min(0)])
+        .split(inner);
+    f.render_widget(Paragraph::new(content).style(Style::default().fg(theme.foreground)), chunks[0]);
+    if stats.count() > 0 {
+        // The newest values that fit, one per column
+        let heights = chart_heights(&stats.values);
+        let start = heights.len().saturating_sub(chunks[1].width as usize);
+        let trend = Sparkline::default().data(&heights[start..]).max(1000).style(Style::default().fg(theme.success));
+        f.render_widget(trend, chunks[1]);
+    }
+}
+
+fn draw_backup_picker_dialog(f: &mut Frame, calculator: &Calculator, theme: &Theme) {
+    let Some(picker) = &calculator.backup_picker else {
+        return;
+    };
+    let area = centered_rect(60, 50, f.area());
+
+    f.render_widget(Clear, area);
+
+    let items: Vec<ListItem> = picker.backups.iter().map(|backup| {
+        ListItem::new(Line::from(vec![
+            Span::styled(format!("{:<14}", backup.file_name), Style::default().fg(theme.success)),
+            Span::raw(format!("{} UTC", backup.time_label())),
+            Span::styled(format!("  {} bytes", backup.size), Style::default().fg(theme.input_placeholder)),
+        ]))
+    }).collect();
+
+    let list = List::new(items)
+        .block(theme.dialog(" Restore Backup (Enter to restore, Esc to cancel) "))
+        .highlight_style(Style::default().bg(theme.highlight_bg).fg(theme.highlight_fg))
+        .highlight_symbol("-> ");
+    let mut state = ListState::default().with_selected(Some(picker.selected));
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+/// Shown instead of the layout when the terminal is too small for it; keys still work.
+fn draw_too_small(f: &mut Frame, theme: &Theme, min_height: u16) {
+    let area = f.area();
+    let content = vec![
+        Line::from(Span::styled("Terminal too small", Style::default().fg(theme.warning).add_modifier(Modifier::BOLD))),
+        Line::from(format!("{}×{}, needs {}×{}", area.width, area.height, MIN_WIDTH, min_height)),
+        Line::from(Span::styled("Enlarge the window or press q to quit", Style::default().fg(theme.foreground))),
+    ];
+    // Vertically centered when there is room
+    let top = area.height.saturating_sub(content.len() as u16) / 2;
+    let message = Paragraph::new(content).alignment(Alignment::Center).wrap(Wrap { trim: true });
+    f.render_widget(message, Rect { y: area.y + top, height: area.height - top, ..area });
 }
 
 // Helper function to create a centered rectangle
+/// Shown in place of the calculator when it can't start; no theme is loaded yet.
+pub fn draw_startup_error(f: &mut Frame, message: &str) {
+    let area = centered_rect(70, 40, f.area());
+    let content = vec![
+        Line::from(Span::styled("The calculator couldn't start", Style::default().add_modifier(Modifier::BOLD))),
+        Line::from(""),
+        Line::from(message.to_string()),
+        Line::from(""),
+        Line::from("Check the files in the tui-calculator config directory."),
+        Line::from(""),
+        Line::from(Span::styled("Press any key to quit", Style::default().add_modifier(Modifier::ITALIC))),
+    ];
+    let dialog = Paragraph::new(content)
+        .block(Block::bordered().title(" Startup error ").padding(Padding::uniform(1)))
+        .wrap(Wrap { trim: false });
+    f.render_widget(dialog, area);
+}
+
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     let popup_layout = Layout::default()
         .direction(Direction::Vertical)
@@ -410,11 +1882,38 @@ fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
         .split(popup_layout[1])[1]
 }
 
-fn truncate_string(s: &str, max_len: usize) -> String {
-    if s.len() > max_len && max_len >= 3 { // Ensure max_len is at least 3 for "..."
-        format!("{}...", &s[..max_len - 3])
-    } else {
-        s.to_string()
+/// First row to show so the selected row stays in view, moving the previous
+/// offset as little as possible. With nothing selected, `follow_end` keeps the last rows in view.
+fn scroll_offset(state: &ListState, len: usize, rows: usize, follow_end: bool) -> usize {
+    let max_offset = len.saturating_sub(rows);
+    match state.selected() {
+        Some(selected) => {
+            let selected = selected.min(len.saturating_sub(1));
+            let offset = state.offset().min(max_offset);
+            if selected < offset {
+                selected
+            } else if selected >= offset + rows {
+                (selected + 1).saturating_sub(rows)
+            } else {
+                offset
+            }
+        }
+        None if follow_end => max_offset,
+        None => state.offset().min(max_offset),
     }
 }
 
+/// Scrollbar on the right border of a list panel, only when the list doesn't fit.
+fn draw_scrollbar(f: &mut Frame, theme: &Theme, area: Rect, len: usize, rows: usize, offset: usize) {
+    if len <= rows {
+        return;
+    }
+    let mut state = ScrollbarState::new(len - rows + 1).viewport_content_length(rows).position(offset);
+    let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight)
+        .begin_symbol(None)
+        .end_symbol(None)
+        .style(Style::default().fg(theme.border));
+    f.render_stateful_widget(scrollbar, area.inner(Margin { vertical: 1, horizontal: 0 }), &mut state);
+}
+
+
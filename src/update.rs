@@ -0,0 +1,64 @@
+//! The update check (`update-check = "on"`, or **u** in the about popup): asks GitHub
+//! for the latest release in the background, like the rates fetch, and says in the
+//! status bar whether it is newer than this build.
+
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const REPOSITORY: &str = env!("CARGO_PKG_REPOSITORY");
+
+/// Starts the request on its own thread; the receiver yields the latest release's tag or why there is none.
+pub fn spawn_check() -> Receiver<Result<String, String>> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = sender.send(fetch());
+    });
+    receiver
+}
+
+fn fetch() -> Result<String, String> {
+    let url = format!("https://api.github.com/repos/{}/releases/latest", REPOSITORY.trim_start_matches("https://github.com/"));
+    let agent: ureq::Agent = ureq::Agent::config_builder().timeout_global(Some(Duration::from_secs(10))).build().into();
+    let body = agent.get(&url).header("Accept", "application/vnd.github+json").call().and_then(|mut response| response.body_mut().read_to_string()).map_err(|e| e.to_string())?;
+    latest_tag(&body).ok_or_else(|| "no release tag in GitHub's answer".to_string())
+}
+
+/// The `tag_name` of a release as the GitHub API returns it.
+fn latest_tag(body: &str) -> Option<String> {
+    let release: serde_json::Value = serde_json::from_str(body).ok()?;
+    release["tag_name"].as_str().map(str::to_string)
+}
+
+/// Whether `tag` (v1.2.0 or 1.2.0) is a later version than `current`; numbers are compared part by part.
+fn is_newer(tag: &str, current: &str) -> bool {
+    let parts = |version: &str| -> Vec<u64> { version.trim_start_matches('v').split(['.', '-']).map_while(|part| part.parse().ok()).collect() };
+    parts(tag) > parts(current)
+}
+
+/// The status bar line for a finished check.
+pub fn summary(outcome: Result<String, String>) -> String {
+    match outcome {
+        Ok(tag) if is_newer(&tag, VERSION) => format!("tuic {} is out (this is {}): {}/releases", tag, VERSION, REPOSITORY),
+        Ok(_) => format!("tuic {} is the latest release", VERSION),
+        Err(e) => format!("Update check failed: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_release_tags() {
+        assert_eq!(latest_tag(r#"{"tag_name": "v0.2.0", "name": "0.2"}"#).as_deref(), Some("v0.2.0"));
+        assert_eq!(latest_tag(r#"{"message": "Not Found"}"#), None);
+        assert!(is_newer("v0.10.0", "0.9.3"));
+        assert!(is_newer("1.0.0", "0.1.0"));
+        assert!(!is_newer("v0.1.0", "0.1.0"));
+        assert!(!is_newer("v0.1.0-rc1", "0.1.1"));
+        assert_eq!(summary(Ok("v0.0.1".to_string())), format!("tuic {} is the latest release", VERSION));
+        assert!(summary(Ok("v99.0.0".to_string())).starts_with("tuic v99.0.0 is out"));
+    }
+}
@@ -0,0 +1,64 @@
+//! Optional vi-style bindings (`keys = "vi"` in config.toml). Normal mode works
+//! on the stack with single letters; insert mode types like the default bindings.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use tuic_core::{BaseMode, CalculatorMode};
+
+use crate::app::App;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ViMode {
+    Normal,
+    Insert,
+}
+
+/// Handles a key in normal mode. `None` passes it on to the default bindings,
+/// where letters are shortcuts rather than text.
+pub fn handle_normal_key(app: &mut App, key: KeyEvent) -> Option<bool> {
+    if key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) {
+        app.vi_pending_delete = false;
+        return None;
+    }
+    // Second key of `dd`; anything else cancels it
+    if std::mem::take(&mut app.vi_pending_delete) && key.code == KeyCode::Char('d') {
+        app.calculator.drop();
+        return Some(false);
+    }
+    match key.code {
+        KeyCode::Char('j') => {
+            let step = app.navigation_step(key) as isize;
+            app.browse_stack_by(step);
+        }
+        KeyCode::Char('k') => {
+            let step = app.navigation_step(key) as isize;
+            app.browse_stack_by(-step);
+        }
+        KeyCode::Char('d') => app.vi_pending_delete = true,
+        KeyCode::Char('p') => app.calculator.duplicate(),
+        KeyCode::Char('i') | KeyCode::Char('a') => app.vi_mode = Some(ViMode::Insert),
+        // Stack operators apply straight away in RPN
+        KeyCode::Char(ch @ ('+' | '-' | '*' | '/' | '^')) if app.calculator.mode == CalculatorMode::RPN => {
+            app.calculator.handle_char_input(ch);
+        }
+        // Starting a number, expression or command switches to insert mode
+        KeyCode::Char(ch @ ('0'..='9' | '.' | '(' | ':' | '+' | '-' | '*' | '/' | '^')) => {
+            app.vi_mode = Some(ViMode::Insert);
+            app.calculator.handle_char_input(ch);
+        }
+        KeyCode::Char(ch) if app.calculator.base_mode == BaseMode::Hexadecimal && ch.is_ascii_hexdigit() => {
+            app.vi_mode = Some(ViMode::Insert);
+            app.calculator.handle_char_input(ch);
+        }
+        KeyCode::Esc => {} // Never quits from normal mode; q does
+        _ => return None,
+    }
+    Some(false)
+}
+
+/// Esc in insert mode: back to normal mode, abandoning a half-typed command line.
+pub fn leave_insert(app: &mut App) {
+    if app.calculator.is_command_input() {
+        app.calculator.clear_input();
+    }
+    app.vi_mode = Some(ViMode::Normal);
+}
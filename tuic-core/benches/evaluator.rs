@@ -0,0 +1,54 @@
+//! Parsing and evaluating infix expressions: cold (tokenizer, shunting yard
+//! and tree walk), warm (the parse cache hit on every frame), and the preview
+//! as an expression is typed one character at a time.
+//!
+//! Run with `cargo bench -p tuic-core`.
+
+use criterion::{BatchSize, Criterion, black_box, criterion_group, criterion_main};
+use tuic_core::{Calculator, CalculatorMode};
+
+const EXPRESSIONS: &[(&str, &str)] = &[
+    ("arithmetic", "(1 + 2) * 3 - 4 / 5 ^ 2"),
+    ("functions", "sqrt(sin(0.5)^2 + cos(0.5)^2) * logb(1024, 2) + modpow(3, 200, 13)"),
+    ("long", "1 + 2 * 3 - 4 / 5 + 6 * 7 - 8 / 9 + 10 * 11 - 12 / 13 + 14 * 15 - 16 / 17 + 18 * 19 - 20"),
+];
+
+fn cold(c: &mut Criterion) {
+    let mut group = c.benchmark_group("evaluate_cold");
+    for (name, expression) in EXPRESSIONS {
+        // A fresh calculator each time, so nothing comes from the cache
+        group.bench_function(*name, |b| b.iter_batched(Calculator::new, |calculator| calculator.evaluate(black_box(expression)), BatchSize::SmallInput));
+    }
+    group.finish();
+}
+
+fn warm(c: &mut Criterion) {
+    let mut group = c.benchmark_group("evaluate_warm");
+    for (name, expression) in EXPRESSIONS {
+        let calculator = Calculator::new();
+        group.bench_function(*name, |b| b.iter(|| calculator.evaluate(black_box(expression))));
+    }
+    group.finish();
+}
+
+fn typing(c: &mut Criterion) {
+    let (_, expression) = EXPRESSIONS[1];
+    c.bench_function("preview_while_typing", |b| {
+        b.iter_batched(
+            Calculator::new,
+            |mut calculator| {
+                calculator.mode = CalculatorMode::Infix;
+                for (end, _) in expression.char_indices().skip(1) {
+                    calculator.input = expression[..end].to_string();
+                    // The status panel and the display each ask once per frame
+                    black_box(calculator.preview());
+                    black_box(calculator.preview());
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+criterion_group!(benches, cold, warm, typing);
+criterion_main!(benches);
@@ -0,0 +1,120 @@
+//! Angle unit converter ('a'): a stack value read as an angle in the current
+//! unit and shown in radians, degrees, gradians and turns at once, any of
+//! which can replace it.
+
+use std::f64::consts::TAU;
+
+use crate::calculator::AngleMode;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AngleUnit {
+    Radians,
+    Degrees,
+    Gradians,
+    Turns,
+}
+
+impl AngleUnit {
+    pub const ALL: [AngleUnit; 4] = [AngleUnit::Radians, AngleUnit::Degrees, AngleUnit::Gradians, AngleUnit::Turns];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            AngleUnit::Radians => "Radians",
+            AngleUnit::Degrees => "Degrees",
+            AngleUnit::Gradians => "Gradians",
+            AngleUnit::Turns => "Turns",
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            AngleUnit::Radians => "rad",
+            AngleUnit::Degrees => "°",
+            AngleUnit::Gradians => "grad",
+            AngleUnit::Turns => "turn",
+        }
+    }
+
+    fn full_turn(self) -> f64 {
+        match self {
+            AngleUnit::Radians => TAU,
+            AngleUnit::Degrees => 360.0,
+            AngleUnit::Gradians => 400.0,
+            AngleUnit::Turns => 1.0,
+        }
+    }
+
+    /// A full turn as infix, for the history line.
+    fn full_turn_expression(self) -> &'static str {
+        match self {
+            AngleUnit::Radians => "(2 * pi)",
+            AngleUnit::Degrees => "360",
+            AngleUnit::Gradians => "400",
+            AngleUnit::Turns => "1",
+        }
+    }
+
+    /// `value` in this unit, in `to`.
+    pub fn convert(self, value: f64, to: AngleUnit) -> f64 {
+        if self == to { value } else { value / self.full_turn() * to.full_turn() }
+    }
+
+    /// The conversion as an infix expression.
+    pub fn expression(self, value: &str, to: AngleUnit) -> String {
+        format!("{} / {} * {}", value, self.full_turn_expression(), to.full_turn_expression())
+    }
+}
+
+impl From<AngleMode> for AngleUnit {
+    fn from(mode: AngleMode) -> Self {
+        match mode {
+            AngleMode::Radians => AngleUnit::Radians,
+            AngleMode::Degrees => AngleUnit::Degrees,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AngleConversion {
+    pub value: f64,
+    pub from: AngleUnit, // The angle mode when it was opened
+    pub level: usize,    // Stack level, 1 being the top
+    pub selected: usize, // Index into AngleUnit::ALL, starting at `from`
+}
+
+impl AngleConversion {
+    pub fn new(value: f64, from: AngleUnit, level: usize) -> Self {
+        let selected = AngleUnit::ALL.iter().position(|unit| *unit == from).unwrap_or(0);
+        Self { value, from, level, selected }
+    }
+
+    pub fn conversions(&self) -> impl Iterator<Item = (AngleUnit, f64)> + '_ {
+        AngleUnit::ALL.into_iter().map(|unit| (unit, self.from.convert(self.value, unit)))
+    }
+
+    pub fn move_selection(&mut self, down: bool) {
+        self.selected = if down { (self.selected + 1).min(AngleUnit::ALL.len() - 1) } else { self.selected.saturating_sub(1) };
+    }
+
+    pub fn selected_unit(&self) -> AngleUnit {
+        AngleUnit::ALL[self.selected]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_angle_in_every_unit() {
+        let mut conversion = AngleConversion::new(90.0, AngleMode::Degrees.into(), 1);
+        assert_eq!(conversion.selected_unit(), AngleUnit::Degrees);
+        let values: Vec<f64> = conversion.conversions().map(|(_, value)| value).collect();
+        assert_eq!(values, [std::f64::consts::FRAC_PI_2, 90.0, 100.0, 0.25]);
+        conversion.move_selection(false);
+        conversion.move_selection(false);
+        assert_eq!(conversion.selected_unit(), AngleUnit::Radians);
+        assert_eq!(AngleUnit::Turns.convert(0.5, AngleUnit::Radians), std::f64::consts::PI);
+        assert_eq!(AngleUnit::Degrees.expression("90", AngleUnit::Radians), "90 / 360 * (2 * pi)");
+    }
+}
@@ -0,0 +1,172 @@
+use std::cmp::Reverse;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Result, anyhow};
+
+/// Backups kept per persisted file; older ones are deleted on the next write.
+pub const MAX_BACKUPS: usize = 5;
+
+const BACKUP_DIR: &str = "backups";
+
+/// A previous version of a persisted file, stored as `backups/<file>.<millis>.bak`.
+#[derive(Debug, Clone)]
+pub struct Backup {
+    pub path: PathBuf,
+    pub file_name: String, // Name of the file it restores, e.g. "history.json"
+    pub timestamp_ms: u64,
+    pub size: u64,
+}
+
+impl Backup {
+    /// Creation time as `YYYY-MM-DD HH:MM:SS` in UTC.
+    pub fn time_label(&self) -> String {
+        utc_label(self.timestamp_ms)
+    }
+}
+
+/// Milliseconds since 1970 as `YYYY-MM-DD HH:MM:SS` in UTC.
+pub fn utc_label(timestamp_ms: u64) -> String {
+    let seconds = timestamp_ms / 1000;
+    let (days, time) = (seconds / 86_400, seconds % 86_400);
+    let (year, month, day) = civil_from_days(days as i64);
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", year, month, day, time / 3600, time % 3600 / 60, time % 60)
+}
+
+/// Writes `contents` to `path`, first moving the current file into the backup
+/// rotation. The new contents go to a temporary file that is renamed into
+/// place, so a crash mid-write leaves the old file intact.
+pub fn write_with_backup(path: &Path, contents: &str) -> Result<()> {
+    let dir = path.parent().ok_or_else(|| anyhow!("{} has no parent directory", path.display()))?;
+    let file_name = file_name(path)?;
+    fs::create_dir_all(dir)?;
+
+    if path.is_file() && fs::read_to_string(path).ok().as_deref() != Some(contents) {
+        let backup_dir = dir.join(BACKUP_DIR);
+        fs::create_dir_all(&backup_dir)?;
+        fs::copy(path, backup_dir.join(format!("{}.{}.bak", file_name, now_ms())))?;
+        prune(dir, &file_name)?;
+    }
+
+    let temporary = dir.join(format!(".{}.tmp", file_name));
+    fs::write(&temporary, contents)?;
+    fs::rename(&temporary, path)?;
+    Ok(())
+}
+
+/// Every backup in `dir`'s backup folder, newest first.
+pub fn list(dir: &Path) -> Result<Vec<Backup>> {
+    let backup_dir = dir.join(BACKUP_DIR);
+    if !backup_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(&backup_dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let Some((file_name, timestamp)) = name.strip_suffix(".bak").and_then(|stem| stem.rsplit_once('.')) else {
+            continue;
+        };
+        let Ok(timestamp_ms) = timestamp.parse() else {
+            continue;
+        };
+        backups.push(Backup { path: entry.path(), file_name: file_name.to_string(), timestamp_ms, size: entry.metadata()?.len() });
+    }
+    backups.sort_by_key(|backup| Reverse(backup.timestamp_ms));
+    Ok(backups)
+}
+
+/// Puts the backup's contents back in place. The file being replaced is
+/// itself backed up, so a restore can be undone with another restore.
+pub fn restore(dir: &Path, backup: &Backup) -> Result<String> {
+    let contents = fs::read_to_string(&backup.path)?;
+    write_with_backup(&dir.join(&backup.file_name), &contents)?;
+    Ok(contents)
+}
+
+fn prune(dir: &Path, file_name: &str) -> Result<()> {
+    for backup in list(dir)?.iter().filter(|backup| backup.file_name == file_name).skip(MAX_BACKUPS) {
+        fs::remove_file(&backup.path)?;
+    }
+    Ok(())
+}
+
+fn file_name(path: &Path) -> Result<String> {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("{} has no file name", path.display()))
+}
+
+pub(crate) fn now_ms() -> u128 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_millis())
+}
+
+// Days since 1970-01-01 to a Gregorian date (Howard Hinnant's civil_from_days)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// State of the `:restore-backup` picker.
+#[derive(Debug, Clone)]
+pub struct BackupPicker {
+    pub backups: Vec<Backup>,
+    pub selected: usize,
+}
+
+impl BackupPicker {
+    pub fn move_selection(&mut self, forward: bool) {
+        let count = self.backups.len();
+        if count > 0 {
+            self.selected = if forward { (self.selected + 1) % count } else { (self.selected + count - 1) % count };
+        }
+    }
+
+    pub fn selected(&self) -> Option<&Backup> {
+        self.backups.get(self.selected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotation_keeps_the_newest_backups() {
+        let dir = std::env::temp_dir().join(format!("tuic-backup-test-{}", std::process::id()));
+        let path = dir.join("history.json");
+        for version in 0..MAX_BACKUPS + 3 {
+            write_with_backup(&path, &version.to_string()).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(2));
+        }
+        // Writing unchanged contents doesn't add a backup
+        write_with_backup(&path, &(MAX_BACKUPS + 2).to_string()).unwrap();
+
+        let backups = list(&dir).unwrap();
+        assert_eq!(backups.len(), MAX_BACKUPS);
+        assert_eq!(fs::read_to_string(&backups[0].path).unwrap(), (MAX_BACKUPS + 1).to_string());
+
+        let restored = restore(&dir, &backups[0]).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), restored);
+        assert_eq!(fs::read_to_string(&list(&dir).unwrap()[0].path).unwrap(), (MAX_BACKUPS + 2).to_string());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn time_labels() {
+        let backup = |timestamp_ms| Backup { path: PathBuf::new(), file_name: String::new(), timestamp_ms, size: 0 };
+        assert_eq!(backup(0).time_label(), "1970-01-01 00:00:00");
+        assert_eq!(backup(951_782_400_000).time_label(), "2000-02-29 00:00:00");
+        assert_eq!(backup(1_792_248_645_000).time_label(), "2026-10-17 14:50:45");
+    }
+}
@@ -0,0 +1,4448 @@
+use std::f64::consts::PI;
+use std::ops::Range;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use anyhow::{Result, anyhow};
+use crate::backup::{self, Backup, BackupPicker};
+use crate::completion::{CandidateKind, Completion};
+use crate::config::Config;
+use crate::confirm::Confirmation;
+use crate::context::{EvalContext, Rounding};
+use crate::locale::{self, DecimalSeparator};
+use crate::currency::{self, Rates};
+use crate::datasize::{self, SizeUnits};
+use crate::digest::{self, HashKind};
+use crate::dms;
+use crate::editor::ExpressionEditor;
+use crate::export::{self, ExportFormat, ScriptLine};
+pub use crate::error::CalculatorError;
+use crate::expr::{self, Expr};
+use crate::functions::{self, Function};
+use crate::highlight;
+use crate::input_history::InputHistory;
+use crate::inspect;
+use crate::find::{self, Found, Search};
+use crate::limits::{self, Limit, LimitsDialog};
+use crate::lint::{self, Suggestion};
+use crate::network::{self, Cidr, SubnetView};
+use crate::angle::AngleConversion;
+use crate::compare::Comparison;
+use crate::number::Number;
+use crate::numeric;
+use crate::parse_cache::ParseCache;
+use crate::plot::FunctionPlot;
+use crate::polynomial;
+use crate::primes;
+use crate::settings::SettingsDialog;
+use crate::shared_history::SharedHistory;
+use crate::stack::{MAX_STACK_SIZE, Stack, UNLIMITED};
+use crate::template::{Fields, Template};
+use crate::table::TableImport;
+use crate::uncertainty::{self, Measurement};
+use crate::vector;
+use crate::warning::Warning;
+use crate::versioned::{self, NewerVersion};
+use crate::undo::UndoLog;
+use crate::result_stats::ResultStats;
+use crate::usage::UsageStats;
+use crate::workspace::{MAIN_WORKSPACE, Workspace};
+
+const DEFAULT_HISTORY_SIZE: usize = 1000;
+const MAX_ITERATIONS: usize = MAX_STACK_SIZE;
+const COMPLEX_NOISE: f64 = 1e-15; // Relative size of a complex part that is only rounding error
+const FRACTION_TOLERANCE: f64 = 1e-6; // :frac without a tolerance, enough to see 0.333333 as 1/3
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum AngleMode {
+    Radians,
+    Degrees,
+}
+
+impl AngleMode {
+    pub fn label(self) -> &'static str {
+        match self {
+            AngleMode::Radians => "RAD",
+            AngleMode::Degrees => "DEG",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BaseMode {
+    Decimal,
+    Hexadecimal,
+    Binary,
+}
+
+impl BaseMode {
+    pub fn label(self) -> &'static str {
+        match self {
+            BaseMode::Decimal => "DEC",
+            BaseMode::Hexadecimal => "HEX",
+            BaseMode::Binary => "BIN",
+        }
+    }
+}
+
+/// Integer width used for two's-complement display in HEX/BIN modes.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum WordSize {
+    Unbounded,
+    Bits8,
+    Bits16,
+    Bits32,
+    Bits64,
+}
+
+impl WordSize {
+    pub fn bits(self) -> Option<u32> {
+        match self {
+            WordSize::Unbounded => None,
+            WordSize::Bits8 => Some(8),
+            WordSize::Bits16 => Some(16),
+            WordSize::Bits32 => Some(32),
+            WordSize::Bits64 => Some(64),
+        }
+    }
+
+    fn mask(bits: u32) -> u64 {
+        if bits >= 64 { u64::MAX } else { (1u64 << bits) - 1 }
+    }
+
+    /// Bit pattern of `value` truncated to this word size.
+    pub fn to_pattern(self, value: i64) -> u64 {
+        match self.bits() {
+            Some(bits) => value as u64 & Self::mask(bits),
+            None => value as u64,
+        }
+    }
+
+    /// Interprets the low bits of `pattern` as a signed number of this word size.
+    pub fn sign_extend(self, pattern: u64) -> i64 {
+        match self.bits() {
+            Some(bits) => {
+                let shift = 64 - bits;
+                (((pattern & Self::mask(bits)) << shift) as i64) >> shift
+            }
+            None => pattern as i64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ComplexMode {
+    Rectangular,
+    Polar,
+}
+
+impl ComplexMode {
+    pub fn label(self) -> &'static str {
+        match self {
+            ComplexMode::Rectangular => "REC",
+            ComplexMode::Polar => "POL",
+        }
+    }
+}
+
+/// The desk-calculator rate keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateAdjustment {
+    AddTax, // TAX+: the price with tax
+    RemoveTax, // TAX-: the price before tax, from one that includes it
+    AddTip, // TIP: the bill with the tip
+}
+
+impl RateAdjustment {
+    pub fn label(self) -> &'static str {
+        match self {
+            RateAdjustment::AddTax => "TAX+",
+            RateAdjustment::RemoveTax => "TAX-",
+            RateAdjustment::AddTip => "TIP",
+        }
+    }
+}
+
+/// Everything that changes how stack and history entries are displayed, so
+/// text formatted under equal settings can be reused.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisplaySettings {
+    pub modes: EntryModes,
+    pub word_size: WordSize,
+    pub precision: Option<usize>,
+    pub abbreviation: bool,
+    pub dms: bool,
+    pub raw_expressions: bool,
+    pub decimal_separator: DecimalSeparator,
+    pub history_template: Option<Template>,
+}
+
+/// Angle, base and complex modes in effect when a stack entry was created.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct EntryModes {
+    pub angle: AngleMode,
+    pub base: BaseMode,
+    pub complex: ComplexMode,
+}
+
+impl EntryModes {
+    /// Labels of this entry's modes that differ from `current`, e.g. `["HEX", "DEG"]`.
+    pub fn differences(&self, current: &EntryModes) -> Vec<&'static str> {
+        let mut labels = Vec::new();
+        if self.base != current.base {
+            labels.push(self.base.label());
+        }
+        if self.angle != current.angle {
+            labels.push(self.angle.label());
+        }
+        if self.complex != current.complex {
+            labels.push(self.complex.label());
+        }
+        labels
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum CalculatorMode {
+    RPN,
+    Infix,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ComplexNumber {
+    pub real: f64,
+    pub imag: f64,
+}
+
+impl ComplexNumber {
+    pub fn new(real: f64, imag: f64) -> Self {
+        Self { real, imag }
+    }
+    
+    pub fn magnitude(&self) -> f64 {
+        (self.real * self.real + self.imag * self.imag).sqrt()
+    }
+    
+    pub fn phase(&self) -> f64 {
+        self.imag.atan2(self.real)
+    }
+    
+    pub fn from_polar(magnitude: f64, phase: f64) -> Self {
+        Self {
+            real: magnitude * phase.cos(),
+            imag: magnitude * phase.sin(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum StackValue {
+    Real(f64),
+    Complex(ComplexNumber),
+    Uncertain(Measurement), // A value ± its uncertainty
+    Vector(Vec<f64>), // [1, 2, 3]
+}
+
+impl StackValue {
+    pub fn as_real(&self) -> Option<f64> {
+        match self {
+            StackValue::Real(r) => Some(*r),
+            StackValue::Complex(c) if c.imag == 0.0 => Some(c.real),
+            StackValue::Uncertain(m) => Some(m.value),
+            _ => None,
+        }
+    }
+
+    /// The value with its uncertainty; exact for plain reals, None for complex values.
+    pub fn as_measurement(&self) -> Option<Measurement> {
+        match self {
+            StackValue::Uncertain(m) => Some(*m),
+            _ => self.as_real().map(Measurement::exact),
+        }
+    }
+
+    /// A measurement, as a plain real when it is exact.
+    pub fn from_measurement(m: Measurement) -> Self {
+        if m.sigma == 0.0 { StackValue::Real(m.value) } else { StackValue::Uncertain(m) }
+    }
+    
+    pub fn as_complex(&self) -> ComplexNumber {
+        match self {
+            StackValue::Real(r) => ComplexNumber::new(*r, 0.0),
+            StackValue::Complex(c) => *c,
+            StackValue::Uncertain(m) => ComplexNumber::new(m.value, 0.0),
+            StackValue::Vector(_) => ComplexNumber::new(f64::NAN, 0.0),
+        }
+    }
+
+    /// A complex result, as a plain real when it has no imaginary part. Parts
+    /// below rounding noise are dropped, so exp(i·pi) is -1 rather than -1 + 1.2e-16i.
+    pub fn from_complex(c: ComplexNumber) -> Self {
+        let noise = c.magnitude() * COMPLEX_NOISE;
+        let (real, imag) = (if c.real.abs() < noise { 0.0 } else { c.real }, if c.imag.abs() < noise { 0.0 } else { c.imag });
+        if imag == 0.0 { StackValue::Real(real) } else { StackValue::Complex(ComplexNumber::new(real, imag)) }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Token {
+    Number(f64),
+    Operator(char),
+    /// Function name; the arity is filled in once the call's arguments are counted
+    Function { name: String, arity: usize },
+    Identifier(String),
+    Comma,
+    LeftParen,
+    RightParen,
+}
+
+/// Where a stack entry came from, for the inspect popup and copy templates.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryOrigin {
+    #[default]
+    Typed, // Keyed in, or an expression evaluated from the input line
+    Operation, // The result of an operator or function applied to the stack
+    History, // Recalled from the history with !N or prefix recall
+    Script, // Pushed by batch mode or an automation script
+    Ipc, // Pushed by a --serve client
+}
+
+impl EntryOrigin {
+    pub fn label(&self) -> &'static str {
+        match self {
+            EntryOrigin::Typed => "typed",
+            EntryOrigin::Operation => "operation",
+            EntryOrigin::History => "history",
+            EntryOrigin::Script => "script",
+            EntryOrigin::Ipc => "IPC",
+        }
+    }
+}
+
+/// Cloned for every undo step, so the text and tree are shared rather than copied.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct StackEntry {
+    pub expression: Arc<str>,
+    pub ast: Arc<Expr>,
+    pub result: StackValue,
+    pub modes: EntryModes, // Modes the entry was created under
+    pub label: Option<String>, // User note such as "subtotal", kept when the entry is moved or copied
+    #[serde(default)]
+    pub id: u64, // Numbered from 1 in the order entries are made; 0 for entries saved before ids
+    #[serde(default)]
+    pub created_ms: u64, // Milliseconds since 1970; 0 when unknown
+    #[serde(default)]
+    pub origin: EntryOrigin,
+}
+
+impl StackEntry {
+    /// When the entry was made, as `YYYY-MM-DD HH:MM:SS` in UTC; None for entries saved before times were kept.
+    pub fn time_label(&self) -> Option<String> {
+        (self.created_ms > 0).then(|| backup::utc_label(self.created_ms))
+    }
+}
+
+fn is_word_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_' || ch == '.'
+}
+
+/// Directory holding the theme choice and persisted history.
+pub fn config_dir() -> Result<PathBuf> {
+    let config_dir = dirs::config_dir()
+        .ok_or_else(|| anyhow!("Could not find config directory"))?;
+    Ok(config_dir.join("tui-calculator"))
+}
+
+fn load_history() -> Result<Vec<HistoryEntry>> {
+    parse_history(&fs::read_to_string(config_dir()?.join("history.json"))?)
+}
+
+pub(crate) fn parse_history(content: &str) -> Result<Vec<HistoryEntry>> {
+    // Trimmed to the calculator's limit by whoever reads it, which may be unlimited
+    versioned::HISTORY.parse(content)
+}
+
+// Drops the oldest unpinned entries beyond `limit`, returning how many went
+pub(crate) fn trim_history(history: &mut Vec<HistoryEntry>, limit: usize) -> usize {
+    let len = history.len();
+    let mut excess = len.saturating_sub(limit);
+    history.retain(|entry| {
+        let drop = excess > 0 && !entry.pinned;
+        excess -= usize::from(drop);
+        !drop
+    });
+    len - history.len()
+}
+
+/// A history line; the result is kept as a value so it is re-formatted
+/// whenever the base or precision changes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub expression: String,
+    pub result: Option<StackValue>,
+    #[serde(default)]
+    pub pinned: bool, // Survives trimming and clear-all
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>, // From a trailing `# comment` in the input
+    #[serde(default)]
+    pub created_ms: u64, // Milliseconds since 1970; 0 for entries saved before times were kept
+    #[serde(default)]
+    pub session_ms: u64, // When the session it was made in started, likewise
+}
+
+impl HistoryEntry {
+    pub fn new(expression: impl Into<String>, result: Option<StackValue>) -> Self {
+        Self { expression: expression.into(), result, pinned: false, comment: None, created_ms: 0, session_ms: 0 }
+    }
+
+    /// `HH:MM` in UTC, for the history panel; None for entries saved before times were kept.
+    pub fn time_label(&self) -> Option<String> {
+        (self.created_ms > 0).then(|| backup::utc_label(self.created_ms)[11..16].to_string())
+    }
+}
+
+/// What starts a session's history, in exports: "Session of 2024-05-01 09:30 UTC".
+pub fn session_label(session_ms: u64) -> String {
+    match session_ms {
+        0 => "Earlier session".to_string(),
+        ms => format!("Session of {} UTC", &backup::utc_label(ms)[..16]),
+    }
+}
+
+/// The line that separates one session's history from the next in the history panel.
+pub fn session_marker(session_ms: u64) -> String {
+    format!("── {} ──", session_label(session_ms))
+}
+
+/// The items of an RPN word line, split at spaces outside brackets so a vector stays whole.
+fn split_items(line: &str) -> Vec<String> {
+    let mut items = vec![String::new()];
+    let mut depth = 0usize;
+    for ch in line.chars() {
+        match ch {
+            '[' => depth += 1,
+            ']' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        if ch.is_whitespace() && depth == 0 {
+            items.push(String::new());
+        } else if let Some(item) = items.last_mut() {
+            item.push(ch);
+        }
+    }
+    items.retain(|item| !item.is_empty());
+    items
+}
+
+/// Splits a trailing `# comment` off infix input: the expression before it and
+/// the comment's text, if it has any.
+pub fn split_comment(input: &str) -> (&str, Option<&str>) {
+    match input.split_once('#') {
+        Some((expression, comment)) => (expression.trim_end(), Some(comment.trim()).filter(|comment| !comment.is_empty())),
+        None => (input, None),
+    }
+}
+
+/// The digits after a `0x`, `0b` or `0o` prefix (either case) and their radix.
+fn radix_prefix(text: &str) -> Option<(&str, u32)> {
+    let radix = match text.get(..2)? {
+        "0x" | "0X" => 16,
+        "0b" | "0B" => 2,
+        "0o" | "0O" => 8,
+        _ => return None,
+    };
+    Some((&text[2..], radix))
+}
+
+/// A literal with Python's digit grouping, 1_000_000, taken out; None when an
+/// underscore isn't between two digits. Text without one comes back as it is.
+fn python_digits(text: &str) -> Option<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let grouped = |i: usize| i > 0 && chars[i - 1].is_ascii_alphanumeric() && chars.get(i + 1).is_some_and(char::is_ascii_alphanumeric);
+    (0..chars.len()).filter(|&i| chars[i] == '_').all(grouped).then(|| text.replace('_', ""))
+}
+
+/// The power of ten an SI prefix after a number stands for, as in 4.7k or
+/// 100n; `u` is there for µ, which few keyboards have.
+fn si_exponent(prefix: char) -> Option<i32> {
+    Some(match prefix {
+        'f' => -15,
+        'p' => -12,
+        'n' => -9,
+        'u' | 'µ' => -6,
+        'm' => -3,
+        'k' => 3,
+        'M' => 6,
+        'G' => 9,
+        'T' => 12,
+        _ => return None,
+    })
+}
+
+/// Scaled through the decimal text, so 2.2M is 2200000 rather than 2.2 × 10⁶ rounded.
+fn scale_by_si_prefix(value: f64, exponent: i32) -> f64 {
+    format!("{}e{}", value, exponent).parse().unwrap_or(value * 10f64.powi(exponent))
+}
+
+/// Bounds on infix input, so that something pasted or piped in by mistake
+/// fails at once with its own error rather than taking long to evaluate or
+/// running out of stack in the recursive evaluator.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InputLimits {
+    pub max_length: usize, // Characters
+    pub max_depth: usize,  // Parentheses and brackets open at once
+    pub max_tokens: usize, // Also what bounds the depth of a chain like 2^2^2^..., half of it
+}
+
+impl Default for InputLimits {
+    fn default() -> Self {
+        Self { max_length: 10_000, max_depth: 64, max_tokens: 500 }
+    }
+}
+
+/// Whether the infix input would parse, shown as the Input border's color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputValidity {
+    Valid,
+    Incomplete, // Can still become valid by typing on: "2 *", "sqrt(2"
+    Invalid,
+}
+
+pub struct Calculator {
+    pub input: String,
+    pub cursor: usize, // Char index into input where typing inserts
+    pub stack: Stack,
+    pub error: Option<CalculatorError>,
+    pub notice: Option<String>, // Informational status message, cleared on the next key
+    pub warning: Option<Warning>, // Something done, but not exactly as typed; cleared on the next key
+    pub session_ms: u64, // When this session started, stamped on its history entries
+    pub history_times: bool, // Times and session markers in the history panel (:set times on)
+    pub history: Vec<HistoryEntry>,
+    pub history_position: usize,
+    pub angle_mode: AngleMode,
+    pub base_mode: BaseMode,
+    pub word_size: WordSize,
+    pub complex_mode: ComplexMode,
+    pub stack_position: usize,
+    pub abbreviation_mode: bool,
+    pub precision: Option<usize>, // Decimal places shown; None prints the shortest exact form
+    pub auto_close_parens: bool, // Typing '(' also inserts its ')'
+    pub close_on_enter: bool, // Missing ')' are appended before evaluating
+    pub dms_display: bool, // Degrees-mode results shown as 45°30'15"
+    pub complex_results: bool, // Complex answers are pushed rather than left out (:set complex on)
+    pub uncertainty_mode: bool, // Values may carry an uncertainty, 5 ± 0.1 (:set uncertainty on)
+    pub rapid_start: Option<usize>, // Rapid entry: the current run is the stack from this index up
+    pub prefix_recall: bool, // RPN Enter recalls the first history entry starting with the input (:set prefixrecall on)
+    pub rpn_words: bool, // RPN input takes words like dup and swap, run on Enter as in dc (:set words on)
+    pub si_prefixes: bool, // 4.7k, 100n and 2.2M in DEC mode are numbers (:set si on)
+    pub python_syntax: bool, // Infix takes 2**10, 7 // 2 and 1_000_000 as Python writes them (:set python on)
+    pub mode: CalculatorMode, // New field
+    pub show_raw_expressions: bool,
+    pub last_iteration: Option<Vec<f64>>,
+    pub function_plot: Option<FunctionPlot>,
+    pub usage_stats: Option<UsageStats>,
+    pub result_stats: Option<ResultStats>, // :stats results popup
+    pub backup_picker: Option<BackupPicker>,
+    pub table_import: Option<TableImport>,
+    pub completion: Option<Completion>,
+    pub mini_input: Option<String>, // Side calculation popup; never touches the stack
+    pub editor: Option<ExpressionEditor>, // Multi-line expression popup
+    pub clipboard: Option<String>, // Text for the front end to copy to the system clipboard
+    pub history_limit: usize, // Unpinned entries beyond this are dropped, oldest first; UNLIMITED keeps them all
+    pub history_dropped: usize, // Entries dropped so far this session, for the history panel's title
+    pub stack_limit: usize, // Applied to every stack, the parked workspaces' too (stack-size)
+    pub limits_dialog: Option<LimitsDialog>, // a popup: the stack and history limits (:limits)
+    pub found: Option<Found>, // What :find just selected, for the front end to bring its panel into view
+    last_find: Option<Search>,
+    pub settings_dialog: Option<SettingsDialog>, // a popup: config.toml settings, changed by the front end (:settings)
+    pub suggestion: Option<Suggestion>, // Lint hint for the last failed evaluation
+    pub confirmation: Option<Confirmation>, // Destructive action waiting for y or n
+    pub undo: UndoLog,
+    pub input_history: InputHistory, // Lines submitted this session, failed ones too, for Alt+Up/Down
+    pub workspace: String, // Name of the active workspace
+    pub parked_workspaces: Vec<Workspace>, // The inactive ones, most recently left last
+    pub rates: Rates, // Exchange rates for `100 USD -> EUR`
+    pub rates_file: Option<PathBuf>, // Where rate changes are saved; None keeps them in memory
+    pub show_rates: bool, // Rates popup
+    pub show_about: bool, // About popup (:about): version, config and data files
+    pub rates_fetch_requested: bool, // For the front end, which does the network request
+    pub size_units: SizeUnits, // Unit family for data-size results without `in <unit>`
+    pub rounding: Rounding, // How round() and rnd() break ties
+    pub decimal_separator: DecimalSeparator, // 3.14 or 3,14, typed and shown
+    pub paste_decimal: Option<DecimalSeparator>, // Decimal separator of pasted amounts like $1,234.56; None guesses
+    pub tax_rate: Option<f64>, // Percent for TAX+ and TAX-; None until configured
+    pub tip_rate: Option<f64>, // Percent for TIP
+    pub defines: Vec<(String, f64)>, // Variables from --define, [define] or TUIC_DEFINE_<name>
+    pub grand_total: f64, // GT: every real result added up, as on an adding machine, until `:gt reset`
+    pub subnet: Option<SubnetView>, // :cidr popup
+    pub comparison: Option<Comparison>, // v popup: the selected entry against the top
+    pub angle_conversion: Option<AngleConversion>, // a popup: the selected entry in every angle unit
+    pub limits: InputLimits,
+    pub shared_history: Option<SharedHistory>, // History file kept in step with other instances (share-history = "on")
+    history_appended: usize, // Lines added to the main workspace's history since the last shared sync
+    history_from_newer_release: bool, // history.json is a format this release can't read, so it's left alone
+    pub history_template: Option<Template>, // Output templates from the config; None keeps the built-in formats
+    pub copy_template: Option<Template>,
+    pub export_template: Option<Template>,
+    parse_cache: ParseCache, // Infix input already parsed, for the preview on every frame
+    pub input_origin: EntryOrigin, // Origin given to entries made from the input line
+    pub(crate) last_entry_id: u64, // Id of the newest entry made, in any workspace
+}
+
+impl Default for Calculator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Calculator {
+    /// A calculator with an empty stack and history.
+    pub fn new() -> Self {
+        Self {
+            input: String::new(),
+            cursor: 0,
+            stack: Stack::default(),
+            error: None,
+            notice: None,
+            warning: None,
+            session_ms: u64::try_from(backup::now_ms()).unwrap_or(u64::MAX),
+            history_times: false,
+            history: Vec::new(),
+            history_position: 0,
+            angle_mode: AngleMode::Radians,
+            base_mode: BaseMode::Decimal,
+            word_size: WordSize::Unbounded,
+            complex_mode: ComplexMode::Rectangular,
+            stack_position: 0,
+            abbreviation_mode: false,
+            precision: None,
+            auto_close_parens: false,
+            close_on_enter: false,
+            dms_display: false,
+            complex_results: false,
+            uncertainty_mode: false,
+            rapid_start: None,
+            prefix_recall: false,
+            rpn_words: false,
+            si_prefixes: false,
+            python_syntax: false,
+            mode: CalculatorMode::RPN, // Initialize to RPN
+            show_raw_expressions: false,
+            last_iteration: None,
+            function_plot: None,
+            usage_stats: None,
+            result_stats: None,
+            backup_picker: None,
+            table_import: None,
+            completion: None,
+            mini_input: None,
+            editor: None,
+            clipboard: None,
+            history_limit: DEFAULT_HISTORY_SIZE,
+            history_dropped: 0,
+            stack_limit: MAX_STACK_SIZE,
+            limits_dialog: None,
+            found: None,
+            last_find: None,
+            settings_dialog: None,
+            suggestion: None,
+            confirmation: None,
+            undo: UndoLog::default(),
+            input_history: InputHistory::default(),
+            workspace: MAIN_WORKSPACE.to_string(),
+            parked_workspaces: Vec::new(),
+            rates: Rates::default(),
+            rates_file: None,
+            show_rates: false,
+            show_about: false,
+            rates_fetch_requested: false,
+            size_units: SizeUnits::Iec,
+            rounding: Rounding::HalfAway,
+            decimal_separator: DecimalSeparator::Point,
+            paste_decimal: None,
+            tax_rate: None,
+            tip_rate: None,
+            grand_total: 0.0,
+            defines: Vec::new(),
+            subnet: None,
+            comparison: None,
+            angle_conversion: None,
+            limits: InputLimits::default(),
+            shared_history: None,
+            history_appended: 0,
+            history_from_newer_release: false,
+            history_template: None,
+            copy_template: None,
+            export_template: None,
+            parse_cache: ParseCache::default(),
+            input_origin: EntryOrigin::Typed,
+            last_entry_id: 0,
+        }
+    }
+
+    /// Like `new`, but with the history saved by the previous session.
+    pub fn with_saved_history() -> Self {
+        let mut calculator = Self::new();
+        // A missing or unreadable history file just means a fresh start, but
+        // one from a newer release is kept as it is rather than saved over
+        match load_history() {
+            Ok(history) => calculator.history = history,
+            Err(e) if e.is::<NewerVersion>() => {
+                calculator.notice = Some(format!("History not loaded: {}", e));
+                calculator.history_from_newer_release = true;
+            }
+            Err(_) => {}
+        }
+        calculator.history_position = calculator.history.len();
+        if let Ok(dir) = config_dir() {
+            let path = dir.join("rates.json");
+            calculator.rates = fs::read_to_string(&path).ok().and_then(|content| serde_json::from_str(&content).ok()).unwrap_or_default();
+            calculator.rates_file = Some(path);
+        }
+        calculator
+    }
+
+    /// Replaces the built-in defaults with the ones from `config`.
+    pub fn apply_config(&mut self, config: &Config) {
+        if let Some(mode) = config.mode {
+            self.mode = mode;
+        }
+        if let Some(angle) = config.angle {
+            self.angle_mode = angle;
+        }
+        if let Some(base) = config.base {
+            self.base_mode = base;
+        }
+        if let Some(precision) = config.precision {
+            self.precision = precision;
+        }
+        if let Some(rounding) = config.rounding {
+            self.rounding = rounding;
+        }
+        if let Some(separator) = config.decimal {
+            self.decimal_separator = separator;
+        }
+        if let Some(separator) = config.paste_decimal {
+            self.paste_decimal = separator;
+        }
+        if let Some(enabled) = config.si_prefixes {
+            self.si_prefixes = enabled;
+        }
+        if let Some(enabled) = config.python_syntax {
+            self.python_syntax = enabled;
+        }
+        if config.tax_rate.is_some() {
+            self.tax_rate = config.tax_rate;
+        }
+        if config.tip_rate.is_some() {
+            self.tip_rate = config.tip_rate;
+        }
+        self.defines.clone_from(&config.defines);
+        for (limit, configured) in [
+            (&mut self.limits.max_length, config.max_input_length),
+            (&mut self.limits.max_depth, config.max_nesting),
+            (&mut self.limits.max_tokens, config.max_tokens),
+        ] {
+            if let Some(configured) = configured {
+                *limit = configured;
+            }
+        }
+        if let Some(size) = config.stack_size {
+            self.set_limit(Limit::Stack, size);
+        }
+        self.set_limit(Limit::History, config.history_size.unwrap_or(self.history_limit));
+        for (template, configured) in [
+            (&mut self.history_template, &config.history_template),
+            (&mut self.copy_template, &config.copy_template),
+            (&mut self.export_template, &config.export_template),
+        ] {
+            if configured.is_some() {
+                template.clone_from(configured);
+            }
+        }
+        if config.share_history == Some(true)
+            && let Ok(dir) = config_dir()
+        {
+            self.shared_history = Some(SharedHistory::new(dir.join("history.json"), self.history.clone()));
+            self.history_appended = 0;
+            self.sync_shared_history();
+        }
+    }
+
+    /// Pushes a plain number onto the stack, as if it had been typed and entered.
+    pub fn push(&mut self, value: f64) {
+        self.push_value(value.to_string(), value.to_string(), value, self.input_origin);
+    }
+
+    /// Pushes a named constant (`pi`, `e`) in one keystroke, after the number
+    /// being typed, and records it in the history like a typed number. In
+    /// infix the name is inserted into the expression instead.
+    pub fn push_constant(&mut self, name: &str) {
+        let Some(value) = functions::constant(name) else {
+            return;
+        };
+        if self.mode == CalculatorMode::Infix {
+            self.insert_str(name);
+            self.error = None;
+            return;
+        }
+        self.undoable(name, |calc| {
+            if !calc.input.is_empty()
+                && let Err(e) = calc.parse_current_input_to_stack_entry()
+            {
+                calc.error = Some(e);
+                return;
+            }
+            calc.push_value(name.to_string(), name.to_string(), value, calc.input_origin);
+            calc.push_history(name.to_string(), None);
+            calc.error = None;
+        });
+    }
+
+    /// Evaluates an infix expression with the calculator's functions and
+    /// constants, without touching the stack or history.
+    pub fn evaluate(&self, expression: &str) -> Result<f64, CalculatorError> {
+        self.parse_expression(expression).and_then(|ast| self.evaluate_expr(&ast, &[]))
+    }
+
+    /// Like `evaluate`, over one of the `Number` types, such as the exact
+    /// `Integer` and `Rational`: `evaluate_in::<Rational>("1/3 + 1/6")` is 1/2.
+    pub fn evaluate_in<N: Number>(&self, expression: &str) -> Result<N, CalculatorError> {
+        self.parse_expression(expression).and_then(|ast| self.evaluate_number(&ast))
+    }
+
+    /// Evaluates `expression` as infix and pushes it as Enter would, with a history
+    /// line, leaving the input being typed alone. For requests from outside the UI,
+    /// which say where the entry comes from.
+    pub fn push_expression(&mut self, expression: &str, origin: EntryOrigin) -> Result<StackValue, CalculatorError> {
+        let input = std::mem::take(&mut self.input);
+        let input_origin = std::mem::replace(&mut self.input_origin, origin);
+        let (cursor, mode, error, suggestion) = (self.cursor, self.mode, self.error.take(), self.suggestion.take());
+        // Scripts and the front end write numbers with a point, whatever the user types
+        let separator = std::mem::take(&mut self.decimal_separator);
+        self.set_input(expression.trim());
+        self.mode = CalculatorMode::Infix;
+        self.enter();
+        let outcome = match self.error.take() {
+            Some(e) => Err(e),
+            None => self.peek().cloned().ok_or(CalculatorError::InvalidExpression),
+        };
+        (self.input, self.cursor, self.mode, self.error, self.suggestion, self.decimal_separator) = (input, cursor, mode, error, suggestion, separator);
+        self.input_origin = input_origin;
+        outcome
+    }
+
+    /// Stack entries, bottom first.
+    pub fn stack(&self) -> &Stack {
+        &self.stack
+    }
+
+    /// Top of the stack, if any.
+    pub fn peek(&self) -> Option<&StackValue> {
+        self.stack.last().map(|entry| &entry.result)
+    }
+
+    /// Removes and returns the top of the stack.
+    pub fn pop(&mut self) -> Option<StackValue> {
+        self.stack.pop().map(|entry| entry.result)
+    }
+
+    /// Whether `ch` goes on with a number being typed in RPN: the letter after a
+    /// leading 0, a hex digit after 0x, or with SI prefixes on, a prefix after
+    /// the digits. Those keys are typed rather than taken as shortcuts then.
+    pub fn continues_number(&self, ch: char) -> bool {
+        if self.si_prefixes
+            && self.base_mode == BaseMode::Decimal
+            && si_exponent(ch).is_some()
+            && self.input.ends_with(|c: char| c.is_ascii_digit())
+            && !self.input.starts_with(['!', '['])
+            && radix_prefix(&self.input).is_none()
+        {
+            return true;
+        }
+        // 1e-3: an exponent after the digits, and its sign straight after the e
+        if self.typing_decimal() {
+            match ch {
+                'e' | 'E' => return self.input.contains(|c: char| c.is_ascii_digit()) && !self.input.contains(['e', 'E']),
+                '-' => return self.input.ends_with(['e', 'E']),
+                _ => {}
+            }
+        }
+        match radix_prefix(&self.input) {
+            _ if self.input == "0" => matches!(ch, 'x' | 'X' | 'b' | 'B' | 'o' | 'O'),
+            Some((_, 16)) => ch.is_ascii_hexdigit(),
+            _ => false,
+        }
+    }
+
+    /// A plain decimal number is being typed in RPN, sign and exponent included.
+    fn typing_decimal(&self) -> bool {
+        self.mode == CalculatorMode::RPN
+            && self.base_mode == BaseMode::Decimal
+            && !self.input.is_empty()
+            && radix_prefix(&self.input).is_none()
+            && self.input.chars().all(|c| c.is_ascii_digit() || matches!(c, '.' | ',' | '-' | 'e' | 'E'))
+    }
+
+    /// The one entry point for typed characters, whatever front end sends them.
+    /// RPN accumulates a number and applies operators at once (pushing the number
+    /// first); infix builds an expression; a command line takes anything.
+    pub fn handle_char_input(&mut self, input_char: char) {
+        // A leading ':' starts a command line, which accepts any character
+        if self.is_command_input() || (input_char == ':' && self.input.is_empty()) {
+            self.insert_char(input_char);
+            self.error = None;
+            return;
+        }
+
+        match self.mode {
+            CalculatorMode::RPN => {
+                let in_vector = self.input.starts_with('[') && !self.input.ends_with(']');
+                match input_char {
+                    // With words on, letters spell words and a line of several items waits for Enter
+                    _ if self.rpn_words && (input_char.is_alphabetic() || input_char == '_') => {
+                        self.insert_char(input_char);
+                        self.error = None;
+                    }
+                    ' ' if self.rpn_words && self.input.is_empty() => {} // Nothing to separate yet
+                    ' ' | '+' | '-' | '*' | '/' | '^' if self.rpn_words && !self.input.is_empty() && (input_char == ' ' || self.is_word_line()) => {
+                        self.insert_char(input_char);
+                        self.error = None;
+                    }
+                    // A vector is typed between brackets, [1, 2, -3]; operators wait for the ']'
+                    '[' if self.input.is_empty() => {
+                        self.insert_char(input_char);
+                        self.error = None;
+                    }
+                    ',' | ';' | ' ' | '-' | ']' if in_vector => {
+                        self.insert_char(input_char);
+                        self.error = None;
+                    }
+                    // With nothing to subtract from, a leading '-' starts a negative number
+                    '-' if self.input.is_empty() && self.base_mode == BaseMode::Decimal && self.stack.len() < 2 => {
+                        self.insert_char(input_char);
+                        self.error = None;
+                    }
+                    '0'..='9' | '.' | '°' | '\'' | '"' => {
+                        // Accumulate digits (and DMS marks) for the current number
+                        self.insert_char(input_char);
+                        self.error = None;
+                    }
+                    'a'..='f' | 'A'..='F' if self.base_mode == BaseMode::Hexadecimal => {
+                        self.insert_char(input_char);
+                        self.error = None;
+                    }
+                    _ if self.continues_number(input_char) => {
+                        self.insert_char(input_char);
+                        self.error = None;
+                    }
+                    ',' if self.decimal_separator == DecimalSeparator::Comma => {
+                        self.insert_char(input_char);
+                        self.error = None;
+                    }
+                    '=' | '~' if self.rapid_start.is_some() => self.finish_rapid_run(input_char == '~'),
+                    // !N or !! recalls a history entry
+                    '!' if self.input.is_empty() || self.input == "!" => {
+                        self.insert_char(input_char);
+                        self.error = None;
+                    }
+                    '+' | '-' | '*' | '/' | '^' => {
+                        // If there's a number being typed, push it to the stack first
+                        let typed = self.input.clone();
+                        if !typed.is_empty()
+                            && let Err(e) = self.parse_current_input_to_stack_entry()
+                        {
+                            self.error = Some(e);
+                            return;
+                        }
+                        // Now apply the operator, which reports its own errors
+                        self.error = None;
+                        self.apply_rpn_operator(input_char);
+                        // Both steps in the status bar until the next key, so one key doing two things is no surprise
+                        if !typed.is_empty()
+                            && self.error.is_none()
+                            && let Some(top) = self.stack.last()
+                        {
+                            self.notice = Some(format!("Pushed {}, then {}: {} = {}", typed, input_char, top.expression, self.format_stack_value(&top.result)));
+                        }
+                    }
+                    _ => {
+                        // Ignore other characters for now, or handle as invalid input
+                        self.error = Some(CalculatorError::InvalidCharacter { ch: input_char, mode: "RPN" });
+                    }
+                }
+            }
+            CalculatorMode::Infix => {
+                // In infix mode, just append all valid characters to the input string; after a '#' everything is comment text
+                let in_comment = self.input.chars().take(self.cursor).any(|ch| ch == '#');
+                let is_valid_infix_char = in_comment || input_char.is_ascii_alphanumeric() || "_.,;+-*/^()[]<>=!±∠# ".contains(input_char) || dms::is_dms_char(input_char);
+                let next_char = self.input.chars().nth(self.cursor);
+                if in_comment {
+                    self.insert_char(input_char);
+                    self.error = None;
+                } else if input_char == ')' && self.auto_close_parens && next_char == Some(')') {
+                    // Type over the ')' that was inserted automatically
+                    self.cursor += 1;
+                    self.error = None;
+                } else if input_char == '(' && self.auto_close_parens {
+                    self.insert_str("()");
+                    self.cursor -= 1;
+                    self.error = None;
+                } else if is_valid_infix_char {
+                    self.insert_char(input_char);
+                    self.error = None;
+                } else {
+                    self.error = Some(CalculatorError::InvalidCharacter { ch: input_char, mode: "infix" });
+                }
+            }
+        }
+    }
+
+    pub fn is_command_input(&self) -> bool {
+        self.input.starts_with(':')
+    }
+
+    /// RPN input with words or several items, e.g. `3 4 swap -`, which Enter runs in order.
+    pub fn is_word_line(&self) -> bool {
+        let hex = self.base_mode == BaseMode::Hexadecimal;
+        let mut depth = 0usize; // Spaces inside a vector's brackets don't separate items
+        self.rpn_words
+            && self.mode == CalculatorMode::RPN
+            && !self.is_command_input()
+            && self.input.chars().any(|ch| {
+                match ch {
+                    '[' => depth += 1,
+                    ']' => depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+                (ch == ' ' && depth == 0) || (ch.is_alphabetic() && !(hex && ch.is_ascii_hexdigit()))
+            })
+    }
+
+    fn execute_command(&mut self, command: &str) {
+        let words: Vec<&str> = command.split_whitespace().collect();
+        match words.as_slice() {
+            ["restore-backup"] => self.open_backup_picker(),
+            ["export", format] | ["export", format, _] => match ExportFormat::parse(format) {
+                Some(format) => self.export_history(format, words.get(2).copied()),
+                None => {
+                    self.error = Some(CalculatorError::InvalidSetting { setting: "export", value: format.to_string(), expected: "md, latex, text or script" })
+                }
+            },
+            ["rates"] => self.show_rates = true,
+            ["about"] => self.show_about = true,
+            ["rates", "fetch"] => self.request_rates_fetch(),
+            ["rate", code, rate] => match (currency::currency_code(code), rate.parse::<f64>()) {
+                (Some(code), Ok(rate)) if rate > 0.0 && rate.is_finite() => {
+                    self.rates.set_manual(&code, rate);
+                    self.notice = Some(format!("1 {} = {} {}", self.rates.base, rate, code));
+                    self.save_rates();
+                }
+                _ => {
+                    self.error = Some(CalculatorError::InvalidSetting { setting: "rate", value: format!("{} {}", code, rate), expected: "a currency code and a positive rate" })
+                }
+            },
+            ["compare"] => self.open_comparison(),
+            ["cidr", block] => self.open_subnet(block, None),
+            ["cidr", block, address] => self.open_subnet(block, Some(address)),
+            ["hash", kind, ..] => {
+                // Text is everything after the algorithm name, spacing included
+                let rest = command.trim_start()["hash".len()..].trim_start()[kind.len()..].strip_prefix(' ');
+                self.hash(kind, rest.filter(|text| !text.is_empty()));
+            }
+            ["pm", sigma] => self.set_uncertainty(sigma),
+            ["map", operation] => self.map_stack(operation, None),
+            ["map", operation, operand @ ..] => self.map_stack(operation, Some(&operand.join(" "))),
+            ["fold", operation] => self.fold_stack(operation),
+            ["frac"] => self.to_fraction(None),
+            ["frac", tolerance] => self.to_fraction(Some(tolerance)),
+            ["drop", level] => self.drop_levels(level, level),
+            ["drop", from, to] => self.drop_levels(from, to),
+            ["sort", options @ ..] => self.sort_stack(options),
+            ["reverse"] => self.undoable("reverse", |calc| calc.stack.reverse()),
+            ["rapid"] => self.toggle_rapid_entry(),
+            ["clear", "stack"] => self.clear_stack(),
+            ["clear", "history"] => self.clear_history(),
+            ["clear", "error"] => self.clear_error(),
+            ["clear"] | ["clear", "all"] => self.request_clear_all(),
+            ["pin"] => self.toggle_pin(),
+            ["rerun"] => self.rerun_history(),
+            ["label"] => self.set_label(None),
+            ["label", words @ ..] => self.set_label(Some(words.join(" "))),
+            ["limits"] => self.open_limits(),
+            ["find", args @ ..] => self.find_value(args),
+            ["settings"] => self.settings_dialog = Some(SettingsDialog::default()),
+            ["ws" | "workspace"] => {
+                let mut names = vec![format!("{}*", self.workspace)];
+                names.extend(self.parked_workspaces.iter().map(|workspace| workspace.name.clone()));
+                self.notice = Some(format!("Workspaces: {}", names.join(", ")));
+            }
+            ["ws" | "workspace", "new", name] => self.create_workspace(name),
+            ["ws" | "workspace", "delete", name] => self.delete_workspace(name),
+            ["ws" | "workspace", "switch", name] | ["ws" | "workspace", name] => self.switch_workspace(name),
+            ["gt"] => self.recall_grand_total(),
+            ["gt", "reset"] => {
+                self.grand_total = 0.0;
+                self.notice = Some("Grand total reset".to_string());
+            }
+            ["stats", "usage"] => {
+                self.usage_stats = Some(UsageStats::from_history(&self.history));
+            }
+            ["stats", "results"] => {
+                self.result_stats = Some(self.history_result_stats());
+            }
+            ["set", "precision", "auto"] => {
+                self.precision = None;
+            }
+            ["set", "precision", digits] => match digits.parse::<usize>() {
+                Ok(digits) if digits <= 20 => self.precision = Some(digits),
+                _ => {
+                    self.error = Some(CalculatorError::InvalidSetting {
+                        setting: "precision",
+                        value: digits.to_string(),
+                        expected: "0-20 or 'auto'",
+                    })
+                }
+            },
+            ["tax+"] => self.apply_rate(RateAdjustment::AddTax),
+            ["tax-"] => self.apply_rate(RateAdjustment::RemoveTax),
+            ["tip"] => self.apply_rate(RateAdjustment::AddTip),
+            ["set", name @ ("tax" | "tip"), percent] => match percent.trim_end_matches('%').parse::<f64>() {
+                Ok(rate) if (0.0..=100.0).contains(&rate) => {
+                    if *name == "tax" { self.tax_rate = Some(rate) } else { self.tip_rate = Some(rate) }
+                    self.notice = Some(format!("{} rate {}%", if *name == "tax" { "Tax" } else { "Tip" }, rate));
+                }
+                _ => {
+                    self.error = Some(CalculatorError::InvalidSetting {
+                        setting: if *name == "tax" { "tax" } else { "tip" },
+                        value: percent.to_string(),
+                        expected: "a percent from 0 to 100",
+                    })
+                }
+            },
+            ["set", "sizes", units @ ("si" | "iec")] => {
+                self.size_units = if *units == "si" { SizeUnits::Si } else { SizeUnits::Iec };
+                self.notice = Some(format!("Data sizes in {} units", units.to_uppercase()));
+            }
+            ["set", "angle", unit @ ("rad" | "deg")] => {
+                self.angle_mode = if *unit == "deg" { AngleMode::Degrees } else { AngleMode::Radians };
+                self.notice = Some(format!("Angles in {}", self.angle_mode.label()));
+            }
+            ["set", "angle", unit] => {
+                self.error = Some(CalculatorError::InvalidSetting { setting: "angle", value: unit.to_string(), expected: "rad or deg" })
+            }
+            ["set", "rounding", rounding @ ("half-away" | "half-even")] => {
+                self.rounding = if *rounding == "half-even" { Rounding::HalfEven } else { Rounding::HalfAway };
+                self.notice = Some(format!("Rounding ties {}", if *rounding == "half-even" { "to even" } else { "away from zero" }));
+            }
+            ["set", "rounding", rounding] => {
+                self.error = Some(CalculatorError::InvalidSetting { setting: "rounding", value: rounding.to_string(), expected: "half-away or half-even" })
+            }
+            ["set", "decimal", separator @ ("point" | "comma")] => {
+                self.decimal_separator = if *separator == "comma" { DecimalSeparator::Comma } else { DecimalSeparator::Point };
+                self.notice = Some(match self.decimal_separator {
+                    DecimalSeparator::Point => "Decimal point: 3.14, arguments split with ,".to_string(),
+                    DecimalSeparator::Comma => "Decimal comma: 3,14 or 1.234,5, arguments split with ;".to_string(),
+                });
+            }
+            ["set", "decimal", separator] => {
+                self.error = Some(CalculatorError::InvalidSetting { setting: "decimal", value: separator.to_string(), expected: "point or comma" })
+            }
+            ["set", "sizes", units] => {
+                self.error = Some(CalculatorError::InvalidSetting { setting: "sizes", value: units.to_string(), expected: "si or iec" })
+            }
+            ["set", option @ ("autoclose" | "closeonenter" | "dms" | "complex" | "uncertainty" | "prefixrecall" | "words" | "si" | "python" | "times"), value @ ("on" | "off")] => {
+                let enabled = *value == "on";
+                // si and python change how the same text reads
+                self.parse_cache.clear();
+                match *option {
+                    "words" => self.rpn_words = enabled,
+                    "si" => self.si_prefixes = enabled,
+                    "python" => self.python_syntax = enabled,
+                    "prefixrecall" => self.prefix_recall = enabled,
+                    "autoclose" => self.auto_close_parens = enabled,
+                    "closeonenter" => self.close_on_enter = enabled,
+                    "complex" => self.complex_results = enabled,
+                    "uncertainty" => self.uncertainty_mode = enabled,
+                    "times" => self.history_times = enabled,
+                    _ => self.dms_display = enabled,
+                }
+                self.notice = Some(format!("{} {}", option, value));
+            }
+            // :<function> applies a built-in function to the top of the stack
+            ["factor"] => self.factor_top(),
+            [name @ ("dot" | "norm" | "at")] => self.apply_vector_function(name),
+            [name] if functions::lookup(name).is_some() => self.apply_function(name),
+            _ => {
+                self.error = Some(CalculatorError::UnknownCommand(command.to_string()));
+            }
+        }
+    }
+
+    /// :pm σ gives the top of the stack an uncertainty of σ, replacing any it had.
+    pub fn set_uncertainty(&mut self, sigma: &str) {
+        if !self.uncertainty_mode {
+            self.error = Some(CalculatorError::UncertaintyOff);
+            return;
+        }
+        let Some(sigma) = sigma.parse::<f64>().ok().filter(|sigma| *sigma >= 0.0 && sigma.is_finite()) else {
+            self.error = Some(CalculatorError::InvalidSetting { setting: "pm", value: sigma.to_string(), expected: "a non-negative uncertainty" });
+            return;
+        };
+        let Some(top) = self.stack.pop() else {
+            self.error = Some(CalculatorError::StackUnderflow { operation: "pm".to_string(), needed: 1, available: 0 });
+            return;
+        };
+        let Some(value) = top.result.as_real() else {
+            self.error = Some(CalculatorError::ComplexUnsupported { operation: "pm".to_string() });
+            self.stack.push(top);
+            return;
+        };
+        let b = self.new_entry(sigma.to_string(), Expr::number(sigma, sigma.to_string()), StackValue::Real(sigma), EntryOrigin::Operation);
+        self.push_operation('±', top, b, StackValue::from_measurement(Measurement::new(value, sigma)));
+    }
+
+    /// Hashes `text`, or the integer bytes of the top of the stack, and shows
+    /// the digest in the current base.
+    pub fn hash(&mut self, kind: &str, text: Option<&str>) {
+        let Some(kind) = HashKind::parse(kind) else {
+            self.error = Some(CalculatorError::InvalidSetting { setting: "hash", value: kind.to_string(), expected: "crc32, md5, sha1 or sha256" });
+            return;
+        };
+        let (subject, data) = match text {
+            Some(text) => (format!("\"{}\"", text), text.as_bytes().to_vec()),
+            None => {
+                let Some(top) = self.stack.last() else {
+                    self.error = Some(CalculatorError::StackUnderflow { operation: kind.name().to_string(), needed: 1, available: 0 });
+                    return;
+                };
+                let Some(value) = top.result.as_real().filter(|value| value.fract() == 0.0 && value.abs() < i64::MAX as f64) else {
+                    self.error = Some(CalculatorError::InvalidArguments("an integer on the stack, or text after the algorithm"));
+                    return;
+                };
+                let pattern = self.word_size.to_pattern(value as i64);
+                (self.format_real(value), digest::integer_bytes(pattern, self.word_size.bits()))
+            }
+        };
+        let radix = match self.base_mode {
+            BaseMode::Decimal => 10,
+            BaseMode::Hexadecimal => 16,
+            BaseMode::Binary => 2,
+        };
+        let line = format!("{}({}) = {}", kind.name(), subject, digest::format_digest(&kind.digest(&data), radix));
+        self.push_history(line.clone(), None);
+        self.history_position = self.history.len();
+        self.notice = Some(line);
+    }
+
+    /// Shows the subnet details of `block` and pushes its usable host count;
+    /// with `address`, also whether that address is inside the block.
+    pub fn open_subnet(&mut self, block: &str, address: Option<&str>) {
+        let Some(cidr) = Cidr::parse(block) else {
+            self.error = Some(CalculatorError::InvalidSetting { setting: "cidr", value: block.to_string(), expected: "an IPv4 block like 192.168.1.0/24" });
+            return;
+        };
+        let probe = match address.map(|address| Cidr::parse(address).filter(|probe| probe.prefix == 32)) {
+            Some(None) => {
+                self.error = Some(CalculatorError::InvalidSetting { setting: "cidr", value: address.unwrap_or_default().to_string(), expected: "an IPv4 address" });
+                return;
+            }
+            Some(Some(probe)) => Some(probe.addr),
+            None => None,
+        };
+        let hosts = cidr.usable_hosts() as f64;
+        let expression = format!("hosts({}/{})", network::format_ip(cidr.network()), cidr.prefix);
+        self.push_value(expression.clone(), expression.clone(), hosts, self.input_origin);
+        self.push_history(expression, Some(StackValue::Real(hosts)));
+        self.history_position = self.history.len();
+        if let Some(probe) = probe {
+            let verdict = if cidr.contains(probe) { "is in" } else { "is not in" };
+            self.notice = Some(format!("{} {} {}", network::format_ip(probe), verdict, block));
+        }
+        self.subnet = Some(SubnetView { cidr, probe });
+    }
+
+    pub fn close_subnet(&mut self) {
+        self.subnet = None;
+    }
+
+    /// Compares the top of the stack with the entry selected below it, or
+    /// with the one under it when browsing hasn't started: the selection is
+    /// "before" and the top "after".
+    pub fn open_comparison(&mut self) {
+        let available = self.stack.len();
+        if available < 2 {
+            self.error = Some(CalculatorError::StackUnderflow { operation: "compare".to_string(), needed: 2, available });
+            return;
+        }
+        let before_level = self.stack_position.max(1) + 1;
+        let values = (self.stack.from_top(before_level - 1).and_then(|entry| entry.result.as_real()), self.stack.from_top(0).and_then(|entry| entry.result.as_real()));
+        let (Some(before), Some(after)) = values else {
+            self.error = Some(CalculatorError::ComplexUnsupported { operation: "compare".to_string() });
+            return;
+        };
+        self.comparison = Some(Comparison::new(before, before_level, after, 1));
+        self.error = None;
+    }
+
+    /// Pushes the comparison's selected metric, with its calculation in the history, and closes it.
+    pub fn push_comparison(&mut self) {
+        let Some(comparison) = self.comparison.take() else {
+            return;
+        };
+        let metric = comparison.selected_metric();
+        let value = metric.value(comparison.before, comparison.after);
+        if !value.is_finite() {
+            self.error = Some(CalculatorError::DivisionByZero { dividend: comparison.after });
+            self.comparison = Some(comparison);
+            return;
+        }
+        let expression = metric.expression(&comparison.before.to_string(), &comparison.after.to_string());
+        self.undoable("compare", |calc| {
+            calc.push_value(expression.clone(), value.to_string(), value, EntryOrigin::Operation);
+            calc.push_history(expression, Some(StackValue::Real(value)));
+            calc.history_position = calc.history.len();
+            if let Some(entry) = calc.history.last_mut() {
+                entry.comment = Some(format!("{} s{} → s{}", metric.label(), comparison.before_level, comparison.after_level));
+            }
+            calc.error = None;
+        });
+    }
+
+    pub fn close_comparison(&mut self) {
+        self.comparison = None;
+    }
+
+    /// Reads the selected entry as an angle in the current unit, for the converter.
+    pub fn open_angle_conversion(&mut self) {
+        let Some(entry) = self.selected_entry() else {
+            self.error = Some(CalculatorError::StackUnderflow { operation: "angle".to_string(), needed: 1, available: 0 });
+            return;
+        };
+        let Some(value) = entry.result.as_real() else {
+            self.error = Some(CalculatorError::ComplexUnsupported { operation: "angle".to_string() });
+            return;
+        };
+        self.angle_conversion = Some(AngleConversion::new(value, self.angle_mode.into(), self.stack_position + 1));
+        self.error = None;
+    }
+
+    /// Replaces the entry with its value in the converter's selected unit, and closes it.
+    pub fn replace_with_angle_conversion(&mut self) {
+        let Some(conversion) = self.angle_conversion.take() else {
+            return;
+        };
+        let unit = conversion.selected_unit();
+        if unit == conversion.from {
+            return;
+        }
+        let value = conversion.from.convert(conversion.value, unit);
+        let expression = conversion.from.expression(&conversion.value.to_string(), unit);
+        self.undoable("angle", |calc| {
+            let index = calc.stack.len() - conversion.level;
+            let entry = calc.new_entry(expression.clone(), Expr::number(value, value.to_string()), StackValue::Real(value), EntryOrigin::Operation);
+            calc.stack.remove(index);
+            calc.stack.insert(index, entry);
+            calc.push_history(expression, Some(StackValue::Real(value)));
+            calc.history_position = calc.history.len();
+            if let Some(entry) = calc.history.last_mut() {
+                entry.comment = Some(format!("s{} {} → {}", conversion.level, conversion.from.symbol(), unit.symbol()));
+            }
+            calc.error = None;
+        });
+    }
+
+    pub fn close_angle_conversion(&mut self) {
+        self.angle_conversion = None;
+    }
+
+    pub fn open_limits(&mut self) {
+        self.limits_dialog = Some(LimitsDialog::default());
+    }
+
+    pub fn close_limits(&mut self) {
+        self.limits_dialog = None;
+    }
+
+    pub fn limit(&self, limit: Limit) -> usize {
+        match limit {
+            Limit::Stack => self.stack_limit,
+            Limit::History => self.history_limit,
+        }
+    }
+
+    /// Left/Right in the limits popup: the selected limit one preset down or up.
+    pub fn step_selected_limit(&mut self, up: bool) {
+        if let Some(limit) = self.limits_dialog.as_ref().map(LimitsDialog::selected_limit) {
+            self.set_limit(limit, limits::step(self.limit(limit), up));
+        }
+    }
+
+    /// Changes a limit, dropping the oldest entries beyond a lower one.
+    pub fn set_limit(&mut self, limit: Limit, size: usize) {
+        match limit {
+            Limit::Stack => {
+                self.stack_limit = size;
+                self.apply_stack_limit();
+                for workspace in &mut self.parked_workspaces {
+                    workspace.stack.set_limit(size);
+                }
+                self.stack_position = self.stack_position.min(self.stack.len().saturating_sub(1));
+            }
+            Limit::History => {
+                self.history_limit = size;
+                self.history_dropped += trim_history(&mut self.history, size);
+                self.history_position = self.history.len();
+            }
+        }
+    }
+
+    /// Puts the stack limit on a stack that was swapped in (undo, a workspace, a journal).
+    pub(crate) fn apply_stack_limit(&mut self) {
+        if self.stack.limit() != self.stack_limit {
+            self.stack.set_limit(self.stack_limit);
+        }
+    }
+
+    /// "(12 items)", with the limit once it's reached and what was dropped to keep to it.
+    pub fn limit_summary(&self, limit: Limit) -> String {
+        let (len, max, dropped) = match limit {
+            Limit::Stack => (self.stack.len(), self.stack_limit, self.stack.dropped()),
+            Limit::History => (self.history.len(), self.history_limit, self.history_dropped),
+        };
+        let mut summary = format!("{} items", len);
+        if max != UNLIMITED && len >= max {
+            summary = format!("{} of max {} items", len, max);
+        }
+        if dropped > 0 {
+            summary.push_str(&format!(", {} oldest dropped", dropped));
+        }
+        format!("({})", summary)
+    }
+
+    /// Asks the front end to fetch current exchange rates.
+    pub fn request_rates_fetch(&mut self) {
+        self.rates_fetch_requested = true;
+        self.notice = Some("Fetching exchange rates...".to_string());
+    }
+
+    /// Takes the outcome of a fetch: the API's JSON, or why the request failed.
+    pub fn apply_fetched_rates(&mut self, response: Result<String, String>) {
+        match response.map_err(|e| anyhow!(e)).and_then(|json| self.rates.apply_fetched(&json)) {
+            Ok(count) => {
+                self.notice = Some(format!("Fetched {} exchange rates for {}", count, self.rates.date.as_deref().unwrap_or("today")));
+                self.save_rates();
+            }
+            Err(e) => self.error = Some(CalculatorError::Rates(format!("{} (cached and manual rates still apply)", e))),
+        }
+    }
+
+    fn save_rates(&mut self) {
+        let Some(path) = &self.rates_file else {
+            return;
+        };
+        let result = serde_json::to_string_pretty(&self.rates).map_err(anyhow::Error::from).and_then(|json| Ok(fs::write(path, json)?));
+        if let Err(e) = result {
+            self.error = Some(CalculatorError::Rates(format!("{}: {}", path.display(), e)));
+        }
+    }
+
+    /// `100 USD -> EUR` converted, or None when `input` isn't a conversion.
+    fn currency_conversion(&self, input: &str) -> Option<Result<f64, CalculatorError>> {
+        let (amount, from, to) = currency::parse_conversion(input)?;
+        let amount = self.parse_expression(amount).and_then(|ast| self.evaluate_expr(&ast, &[]));
+        Some(amount.and_then(|amount| self.rates.convert(amount, &from, &to).map_err(CalculatorError::UnknownCurrency)))
+    }
+
+    /// `4 * 512 MiB` or `1.5 GiB in MB`: the value in the unit asked for (or a readable
+    /// one of the current family) and that unit. None when `input` has no sizes.
+    fn size_conversion(&self, input: &str) -> Option<Result<(f64, String), CalculatorError>> {
+        let size = datasize::parse(input)?;
+        let bytes = self.parse_expression(&size.bytes_expression).and_then(|ast| self.evaluate_expr(&ast, &[]));
+        // Positions in parse errors refer to the rewritten text, so drop them
+        let bytes = bytes.map_err(|e| if e.span().is_some() { CalculatorError::InvalidExpression } else { e });
+        Some(bytes.and_then(|bytes| {
+            let (unit, factor) = match size.target {
+                Some(unit) => {
+                    let factor = datasize::unit_factor(&unit).ok_or(CalculatorError::UnknownUnit(unit.clone()))?;
+                    (unit, factor)
+                }
+                None => {
+                    let (unit, factor) = datasize::auto_unit(bytes, self.size_units);
+                    (unit.to_string(), factor)
+                }
+            };
+            Ok((bytes / factor, unit))
+        }))
+    }
+
+    /// Writes the history as Markdown, LaTeX or text to `path`, or hands it to the
+    /// front end for the clipboard when no path is given.
+    /// `:export`: to the clipboard, or to `path`, asking before an existing file is replaced.
+    pub fn export_history(&mut self, format: ExportFormat, path: Option<&str>) {
+        match path {
+            Some(path) if Path::new(path).exists() => self.confirmation = Some(Confirmation::OverwriteExport { format, path: path.to_string() }),
+            _ => self.write_export(format, path),
+        }
+    }
+
+    fn write_export(&mut self, format: ExportFormat, path: Option<&str>) {
+        let mut rows: Vec<(String, Option<String>)> = Vec::new();
+        for (index, entry) in self.history.iter().enumerate() {
+            // Where one session's entries end and the next one's begin
+            if let Some(session) = self.session_start_before(index) {
+                rows.push((session_label(session), None));
+            }
+            let result = entry.result.as_ref().map(|result| self.format_stack_value(result));
+            rows.push(match (&self.export_template, result) {
+                // Text lines come out of the template whole
+                (Some(template), Some(result)) if format == ExportFormat::Text => (self.render_template(template, &entry.expression, &result, ""), None),
+                (_, result) => (entry.expression.clone(), result),
+            });
+        }
+        let text = if format == ExportFormat::Script { export::script(&self.script_lines()) } else { export::render(&rows, format) };
+        match path {
+            Some(path) => match fs::write(path, text) {
+                Ok(()) => self.notice = Some(format!("Exported {} history lines to {}", self.history.len(), path)),
+                Err(e) => self.error = Some(CalculatorError::Export(format!("{}: {}", path, e))),
+            },
+            None => {
+                self.clipboard = Some(text);
+                self.notice = Some(format!("Copied {} history lines to the clipboard", self.history.len()));
+            }
+        }
+    }
+
+    // The history as a script that rebuilds the stack: the settings results depend on,
+    // then each entry in order. An entry runs when its value is the next one up the
+    // stack; the rest, operands a later line used up, are comments. Stack entries no
+    // line accounts for, trimmed from the history or changed in place, run at the end.
+    fn script_lines(&self) -> Vec<ScriptLine> {
+        let mut lines = Vec::new();
+        if self.angle_mode == AngleMode::Degrees {
+            lines.push(ScriptLine::Run(":set angle deg".to_string()));
+        }
+        if self.rounding == Rounding::HalfEven {
+            lines.push(ScriptLine::Run(":set rounding half-even".to_string()));
+        }
+        for (option, enabled) in [("complex", self.complex_results), ("uncertainty", self.uncertainty_mode), ("si", self.si_prefixes), ("python", self.python_syntax)] {
+            if enabled {
+                lines.push(ScriptLine::Run(format!(":set {} on", option)));
+            }
+        }
+        if let Some(digits) = self.precision {
+            lines.push(ScriptLine::Run(format!(":set precision {}", digits)));
+        }
+        // The expression if batch reads it back as the value, else the value itself with
+        // the expression after it. Batch reads decimals with a point and no HEX or BIN
+        // digits, and infix has no unary minus, so -7 is written 0 - 7; an angle typed in
+        // another angle mode is why a complex value would read back differently.
+        let as_typed = self.decimal_separator == DecimalSeparator::Point && self.base_mode == BaseMode::Decimal;
+        let replayable = |expression: &str, value: &StackValue| match value {
+            StackValue::Real(x) if x.is_finite() && !(as_typed && self.evaluate(expression).ok() == Some(*x)) => {
+                let number = if *x < 0.0 { format!("0 - {}", -x) } else { x.to_string() };
+                if number == expression { number } else { format!("{} # from {}", number, expression) }
+            }
+            // r∠θ in radians, marked so it reads the same in either angle mode
+            StackValue::Complex(z) if !(as_typed && self.parse_expression(expression).and_then(|ast| self.evaluate_value(&ast)).ok().as_ref() == Some(value)) => {
+                format!("{}∠{}rad # from {}", z.magnitude(), z.phase(), expression)
+            }
+            _ => expression.to_string(),
+        };
+        let mut next = 0; // The stack entry, counted from the bottom, the next line has to push
+        for (index, entry) in self.history.iter().enumerate() {
+            if let Some(session) = self.session_start_before(index) {
+                lines.push(ScriptLine::Note(session_label(session)));
+            }
+            let value = self.history_value(index);
+            let with_comment = |text: String| match &entry.comment {
+                Some(comment) => format!("{} # {}", text, comment),
+                None => text,
+            };
+            match self.stack.iter().nth(next) {
+                Some(top) if *top.expression == *entry.expression && value.as_ref() == Some(&top.result) => {
+                    next += 1;
+                    lines.push(ScriptLine::Run(with_comment(replayable(&entry.expression, &top.result))));
+                }
+                _ => lines.push(ScriptLine::Note(with_comment(entry.expression.clone()))),
+            }
+        }
+        for entry in self.stack.iter().skip(next) {
+            lines.push(ScriptLine::Run(replayable(&entry.expression, &entry.result)));
+        }
+        lines
+    }
+
+    // The history entry being browsed, or the newest one
+    fn selected_history_index(&self) -> Option<usize> {
+        if self.history_position < self.history.len() { Some(self.history_position) } else { self.history.len().checked_sub(1) }
+    }
+
+    /// Pins or unpins the selected history entry.
+    pub fn toggle_pin(&mut self) {
+        let Some(index) = self.selected_history_index() else {
+            return;
+        };
+        let entry = &mut self.history[index];
+        entry.pinned = !entry.pinned;
+        self.notice = Some(format!("{} {}", if entry.pinned { "Pinned" } else { "Unpinned" }, entry.expression));
+    }
+
+    /// Evaluates the selected history expression again under the current modes
+    /// and pushes the fresh result; the input line is left as it was.
+    pub fn rerun_history(&mut self) {
+        let Some(index) = self.selected_history_index() else {
+            return;
+        };
+        let source = self.history[index].expression.clone();
+        self.evaluate_source("rerun", source);
+    }
+
+    // Runs `source` through the infix path as one undo step, keeping the current input.
+    // Returns whether it succeeded.
+    fn evaluate_source(&mut self, label: &str, source: String) -> bool {
+        let previous = std::mem::take(&mut self.input);
+        self.set_input(source);
+        self.undoable(label, Self::submit_infix);
+        self.set_input(previous);
+        self.error.is_none()
+    }
+
+    /// The selected stack entry: the top unless browsing.
+    pub fn selected_entry(&self) -> Option<&StackEntry> {
+        self.stack.from_top(self.stack_position)
+    }
+
+    /// Inserts the selected stack entry at the cursor: its value, or with `expression`
+    /// its raw expression in parentheses, to build a new calculation on it.
+    pub fn yank_selected(&mut self, expression: bool) {
+        let Some(entry) = self.selected_entry() else {
+            self.error = Some(CalculatorError::StackUnderflow { operation: "copy".to_string(), needed: 1, available: 0 });
+            return;
+        };
+        let text = if expression {
+            let raw = entry.expression.trim();
+            if raw.contains(' ') && !(raw.starts_with('(') && raw.ends_with(')')) { format!("({})", raw) } else { raw.to_string() }
+        } else {
+            // Full precision in decimal, like the side calculator's result
+            match (&entry.result, self.base_mode) {
+                (StackValue::Real(value), BaseMode::Decimal) => self.decimal_separator.localize(&value.to_string()),
+                (result, _) => self.format_stack_value(result),
+            }
+        };
+        self.insert_str(&text);
+        self.error = None;
+    }
+
+    /// Hands the selected entry's displayed value to the front end for the clipboard.
+    pub fn copy_selected(&mut self) {
+        let Some(entry) = self.selected_entry() else {
+            self.error = Some(CalculatorError::StackUnderflow { operation: "copy".to_string(), needed: 1, available: 0 });
+            return;
+        };
+        let result = self.format_stack_value(&entry.result);
+        let text = match &self.copy_template {
+            Some(template) => {
+                let (id, time) = (entry.id.to_string(), entry.time_label().unwrap_or_default());
+                let (expr, label) = (self.display_expression(entry), entry.label.as_deref().unwrap_or(""));
+                template.render(&Fields { expr: &expr, result: &result, base: self.base_mode.label(), angle: self.angle_mode.label(), label, id: &id, origin: entry.origin.label(), time: &time })
+            }
+            None => result,
+        };
+        self.notice = Some(format!("Copied {} to the clipboard", text));
+        self.clipboard = Some(text);
+        self.error = None;
+    }
+
+    /// `b`: the selected entry in the bases other than the current one, as a
+    /// notice that goes with the next key; the base mode stays as it is.
+    pub fn peek_bases(&mut self) {
+        let Some(entry) = self.selected_entry() else {
+            self.error = Some(CalculatorError::StackUnderflow { operation: "peek".to_string(), needed: 1, available: 0 });
+            return;
+        };
+        let level = format!("s{}", self.stack_position + 1);
+        let Some(integer) = entry.result.as_real().filter(|value| value.fract() == 0.0 && value.abs() < i64::MAX as f64).map(|value| value as i64) else {
+            self.notice = Some(format!("{} isn't an integer, so it has no hex, octal or binary form", level));
+            return;
+        };
+        // With a fixed word size, negatives are shown as their bit pattern, as in HEX and BIN mode
+        let pattern = self.word_size.to_pattern(integer);
+        let mut forms = Vec::new();
+        if self.base_mode != BaseMode::Decimal {
+            forms.push(integer.to_string());
+        }
+        if self.base_mode != BaseMode::Hexadecimal {
+            forms.push(format!("0x{:X}", pattern));
+        }
+        forms.push(format!("0o{:o}", pattern));
+        if self.base_mode != BaseMode::Binary {
+            forms.push(format!("0b{:b}", pattern));
+        }
+        self.notice = Some(format!("{} = {}", level, forms.join(" = ")));
+        self.error = None;
+    }
+
+    /// Labels the selected stack entry (the top unless browsing); `None` removes the label.
+    pub fn set_label(&mut self, label: Option<String>) {
+        let Some(index) = self.stack.len().checked_sub(self.stack_position + 1) else {
+            self.error = Some(CalculatorError::StackUnderflow { operation: "label".to_string(), needed: 1, available: 0 });
+            return;
+        };
+        self.undoable("label", |calc| calc.stack[index].label = label);
+    }
+
+    /// Creates an empty workspace and switches to it.
+    pub fn create_workspace(&mut self, name: &str) {
+        if name == self.workspace || self.parked_workspaces.iter().any(|workspace| workspace.name == name) {
+            self.error = Some(CalculatorError::Workspace(format!("'{}' already exists", name)));
+            return;
+        }
+        self.park_workspace(Workspace::new(name));
+        self.notice = Some(format!("Created workspace {}", name));
+    }
+
+    /// Parks the active workspace and brings `name` forward.
+    pub fn switch_workspace(&mut self, name: &str) {
+        if name == self.workspace {
+            return;
+        }
+        let Some(index) = self.parked_workspaces.iter().position(|workspace| workspace.name == name) else {
+            self.error = Some(CalculatorError::Workspace(format!("no workspace named '{}'", name)));
+            return;
+        };
+        let workspace = self.parked_workspaces.remove(index);
+        self.park_workspace(workspace);
+        self.notice = Some(format!("Switched to workspace {}", name));
+    }
+
+    pub fn delete_workspace(&mut self, name: &str) {
+        let problem = if name == MAIN_WORKSPACE {
+            Some("'main' holds the saved history and can't be deleted".to_string())
+        } else if name == self.workspace {
+            Some("switch away before deleting the active workspace".to_string())
+        } else {
+            None
+        };
+        if let Some(problem) = problem {
+            self.error = Some(CalculatorError::Workspace(problem));
+            return;
+        }
+        if self.parked_workspaces.iter().any(|workspace| workspace.name == name) {
+            self.confirmation = Some(Confirmation::DeleteWorkspace(name.to_string()));
+        } else {
+            self.error = Some(CalculatorError::Workspace(format!("no workspace named '{}'", name)));
+        }
+    }
+
+    fn remove_workspace(&mut self, name: &str) {
+        self.parked_workspaces.retain(|workspace| workspace.name != name);
+        self.notice = Some(format!("Deleted workspace {}", name));
+    }
+
+    // Swaps `workspace` in as the active one and parks the current stack, history and undo log
+    fn park_workspace(&mut self, workspace: Workspace) {
+        let Workspace { name, stack, history, undo } = workspace;
+        let parked = Workspace {
+            name: std::mem::replace(&mut self.workspace, name),
+            stack: std::mem::replace(&mut self.stack, stack),
+            history: std::mem::replace(&mut self.history, history),
+            undo: std::mem::replace(&mut self.undo, undo),
+        };
+        self.parked_workspaces.push(parked);
+        self.apply_stack_limit();
+        self.stack_position = 0;
+        self.history_position = self.history.len();
+        if self.rapid_start.is_some() {
+            self.rapid_start = Some(self.stack.len());
+        }
+    }
+
+    pub fn close_usage_stats(&mut self) {
+        self.usage_stats = None;
+    }
+
+    /// Totals over the history: each entry's result, or for a typed number the number itself.
+    pub fn history_result_stats(&self) -> ResultStats {
+        ResultStats::new(self.history.iter().map(|entry| match &entry.result {
+            Some(result) => result.as_real(),
+            None => self.evaluate(&entry.expression).ok(),
+        }))
+    }
+
+    pub fn close_result_stats(&mut self) {
+        self.result_stats = None;
+    }
+
+    /// With a shared history: writes this instance's new entries to the file and
+    /// picks up other instances' ones. Cheap when neither side changed; true
+    /// when something did that the screen should show.
+    pub fn sync_shared_history(&mut self) -> bool {
+        let appended = std::mem::take(&mut self.history_appended);
+        let Some(shared) = &mut self.shared_history else {
+            return false;
+        };
+        let history = match self.parked_workspaces.iter_mut().find(|workspace| workspace.name == MAIN_WORKSPACE) {
+            Some(main) => &mut main.history,
+            None => &mut self.history,
+        };
+        let was_at_end = self.history_position >= history.len();
+        match shared.sync(history, appended, self.history_limit) {
+            Ok(true) if self.workspace == MAIN_WORKSPACE => {
+                // Keep browsing where it was, or keep following the newest entry
+                self.history_position = if was_at_end { self.history.len() } else { self.history_position.min(self.history.len()) };
+                true
+            }
+            Ok(_) => false,
+            Err(e) => {
+                // Back to a private history rather than failing on every sync
+                self.shared_history = None;
+                self.error = Some(CalculatorError::SharedHistory(format!("turned off: {}", e)));
+                true
+            }
+        }
+    }
+
+    /// The session starting at history entry `index` (the history's length
+    /// standing for the current session, before it has entries), when entries
+    /// of an earlier session come before it: where a session marker goes.
+    pub fn session_start_before(&self, index: usize) -> Option<u64> {
+        let previous = self.history.get(index.checked_sub(1)?)?;
+        let session = self.history.get(index).map_or(self.session_ms, |entry| entry.session_ms);
+        (session != previous.session_ms).then_some(session)
+    }
+
+    /// Saves the main workspace's history, whichever workspace is active. A shared
+    /// history is already on disk, kept there by `sync_shared_history`, and one
+    /// written by a newer release isn't replaced.
+    pub fn save_history(&self) -> Result<()> {
+        if self.shared_history.is_some() || self.history_from_newer_release {
+            return Ok(());
+        }
+        let history = match self.parked_workspaces.iter().find(|workspace| workspace.name == MAIN_WORKSPACE) {
+            Some(main) => &main.history,
+            None => &self.history,
+        };
+        backup::write_with_backup(&config_dir()?.join("history.json"), &versioned::HISTORY.to_string(history)?)
+    }
+
+    pub fn open_backup_picker(&mut self) {
+        match config_dir().and_then(|dir| backup::list(&dir)) {
+            Ok(backups) if backups.is_empty() => self.notice = Some("No backups yet".to_string()),
+            Ok(backups) => self.backup_picker = Some(BackupPicker { backups, selected: 0 }),
+            Err(e) => self.error = Some(CalculatorError::Backup(e.to_string())),
+        }
+    }
+
+    pub fn close_backup_picker(&mut self) {
+        self.backup_picker = None;
+    }
+
+    /// Restores the backup selected in the picker; a restored history replaces the current one.
+    /// Enter in the picker: asks before the file is replaced.
+    pub fn restore_selected_backup(&mut self) {
+        if let Some(backup) = self.backup_picker.take().and_then(|picker| picker.selected().cloned()) {
+            self.confirmation = Some(Confirmation::RestoreBackup(backup));
+        }
+    }
+
+    fn restore_backup(&mut self, backup: &Backup) {
+        let restored = config_dir().and_then(|dir| backup::restore(&dir, backup)).and_then(|contents| {
+            if backup.file_name == "history.json" {
+                self.history = parse_history(&contents)?;
+                self.set_limit(Limit::History, self.history_limit);
+            }
+            Ok(())
+        });
+        match restored {
+            Ok(()) => self.notice = Some(format!("Restored {} from {}", backup.file_name, backup.time_label())),
+            Err(e) => self.error = Some(CalculatorError::Backup(e.to_string())),
+        }
+    }
+
+    pub fn backspace(&mut self) {
+        if self.cursor > 0 {
+            let start = self.byte_index(self.cursor - 1);
+            // Deleting an auto-inserted "(" takes its empty ")" along
+            if self.auto_close_parens && self.input[start..].starts_with("()") {
+                self.input.remove(start);
+            }
+            self.input.remove(start);
+            self.cursor -= 1;
+        }
+        self.error = None;
+    }
+
+    /// `input` with its unclosed '(' closed, if that is all that is unbalanced.
+    fn balanced_input(input: &str) -> Option<String> {
+        let chars: Vec<char> = input.chars().collect();
+        let unmatched = highlight::unmatched_parens(&chars);
+        if unmatched.is_empty() || unmatched.iter().any(|&i| chars[i] == ')') {
+            return None;
+        }
+        Some(format!("{}{}", input, ")".repeat(unmatched.len())))
+    }
+
+    /// Deletes the character under the cursor (Delete key while editing).
+    pub fn delete_forward(&mut self) {
+        if self.cursor < self.input.chars().count() {
+            let start = self.byte_index(self.cursor);
+            self.input.remove(start);
+        }
+        self.error = None;
+    }
+
+    // Byte offset of a char index into the input
+    fn byte_index(&self, char_index: usize) -> usize {
+        self.input.char_indices().nth(char_index).map_or(self.input.len(), |(i, _)| i)
+    }
+
+    fn insert_char(&mut self, ch: char) {
+        let at = self.byte_index(self.cursor);
+        self.input.insert(at, ch);
+        self.cursor += 1;
+    }
+
+    fn insert_str(&mut self, text: &str) {
+        let at = self.byte_index(self.cursor);
+        self.input.insert_str(at, text);
+        self.cursor += text.chars().count();
+    }
+
+    /// Replaces the input line and puts the cursor at its end.
+    fn set_input(&mut self, text: impl Into<String>) {
+        self.input = text.into();
+        self.cursor = self.input.chars().count();
+    }
+
+    /// Tab: a single match is inserted right away, several open the popup.
+    pub fn complete(&mut self) {
+        let usage = UsageStats::from_history(&self.history);
+        self.completion = Completion::new(&self.input, self.cursor, &usage);
+        if self.completion.as_ref().is_some_and(|completion| completion.candidates.len() == 1) {
+            self.accept_completion();
+        }
+    }
+
+    /// Re-filters the open popup after the word under the cursor changed.
+    pub fn update_completion(&mut self) {
+        let usage = UsageStats::from_history(&self.history);
+        let selected = self.completion.as_ref().map(|completion| completion.selected().name);
+        self.completion = Completion::new(&self.input, self.cursor, &usage);
+        if let Some(completion) = &mut self.completion
+            && let Some(index) = completion.candidates.iter().position(|candidate| Some(candidate.name) == selected)
+        {
+            completion.selected = index;
+        }
+    }
+
+    pub fn cancel_completion(&mut self) {
+        self.completion = None;
+    }
+
+    /// Replaces the partial word with the chosen name; functions get their '('.
+    pub fn accept_completion(&mut self) {
+        let Some(completion) = self.completion.take() else {
+            return;
+        };
+        let candidate = completion.selected();
+        let (start, end) = (self.byte_index(completion.start), self.byte_index(self.cursor));
+        self.input.replace_range(start..end, candidate.name);
+        self.cursor = completion.start + candidate.name.chars().count();
+        if candidate.kind == CandidateKind::Function && !self.input[self.byte_index(self.cursor)..].starts_with('(') {
+            self.handle_char_input('(');
+        }
+        self.error = None;
+    }
+
+    pub fn move_cursor_left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn move_cursor_right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.input.chars().count());
+    }
+
+    pub fn move_cursor_home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn move_cursor_end(&mut self) {
+        self.cursor = self.input.chars().count();
+    }
+
+    /// Jumps to the start of the previous word (numbers and names count as words).
+    pub fn move_cursor_word_left(&mut self) {
+        let chars: Vec<char> = self.input.chars().collect();
+        let mut i = self.cursor;
+        while i > 0 && !is_word_char(chars[i - 1]) {
+            i -= 1;
+        }
+        while i > 0 && is_word_char(chars[i - 1]) {
+            i -= 1;
+        }
+        self.cursor = i;
+    }
+
+    /// Jumps past the end of the next word.
+    pub fn move_cursor_word_right(&mut self) {
+        let chars: Vec<char> = self.input.chars().collect();
+        let mut i = self.cursor;
+        while i < chars.len() && !is_word_char(chars[i]) {
+            i += 1;
+        }
+        while i < chars.len() && is_word_char(chars[i]) {
+            i += 1;
+        }
+        self.cursor = i;
+    }
+
+    pub fn clear_input(&mut self) {
+        self.set_input("");
+        self.error = None;
+    }
+
+    /// Ctrl+C and `:clear all`: asks before clearing everything.
+    pub fn request_clear_all(&mut self) {
+        self.confirmation = Some(Confirmation::ClearAll);
+    }
+
+    /// The question for the open confirmation, with what it would remove.
+    pub fn confirmation_prompt(&self) -> Option<String> {
+        let unpinned = self.history.iter().filter(|entry| !entry.pinned).count();
+        self.confirmation.as_ref().map(|confirmation| match confirmation {
+            Confirmation::ClearAll => format!("Clear the input, {} stack entries and {} unpinned history entries?", self.stack.len(), unpinned),
+            Confirmation::DropLevels { low, high } => format!("Drop stack levels {} to {} ({} entries)?", low, high, high - low + 1),
+            Confirmation::DeleteWorkspace(name) => {
+                let workspace = self.parked_workspaces.iter().find(|workspace| workspace.name == *name);
+                let (stack, history) = workspace.map_or((0, 0), |workspace| (workspace.stack.len(), workspace.history.len()));
+                format!("Delete workspace {} with {} stack entries and {} history entries?", name, stack, history)
+            }
+            Confirmation::RestoreBackup(backup) => format!("Replace {} with the version from {}? The current one is backed up first.", backup.file_name, backup.time_label()),
+            Confirmation::OverwriteExport { path, .. } => format!("{} exists. Overwrite it?", path),
+        })
+    }
+
+    /// Runs the waiting action on yes; either answer closes the prompt.
+    pub fn answer_confirmation(&mut self, yes: bool) {
+        let Some(confirmation) = self.confirmation.take() else {
+            return;
+        };
+        if yes {
+            match confirmation {
+                Confirmation::ClearAll => self.clear_all(),
+                Confirmation::DropLevels { low, high } => self.drop_range(low, high),
+                Confirmation::DeleteWorkspace(name) => self.remove_workspace(&name),
+                Confirmation::RestoreBackup(backup) => self.restore_backup(&backup),
+                Confirmation::OverwriteExport { format, path } => self.write_export(format, Some(&path)),
+            }
+        }
+    }
+
+    /// Empties the stack, leaving input and history alone; undo brings it back.
+    pub fn clear_stack(&mut self) {
+        self.undoable("clear stack", |calc| calc.stack.clear());
+        self.stack_position = 0;
+    }
+
+    /// Drops every unpinned history entry.
+    pub fn clear_history(&mut self) {
+        self.history.retain(|entry| entry.pinned);
+        self.history_position = self.history.len();
+    }
+
+    /// Dismisses the error and its suggested fix.
+    pub fn clear_error(&mut self) {
+        self.error = None;
+        self.suggestion = None;
+    }
+
+    pub fn clear_all(&mut self) {
+        self.set_input("");
+        self.undo.begin("clear", &self.stack, self.grand_total);
+        self.stack.clear();
+        self.undo.commit(&self.stack);
+        self.error = None;
+        self.grand_total = 0.0;
+        self.history.retain(|entry| entry.pinned);
+        self.stack_position = 0;
+        self.history_position = self.history.len();
+    }
+
+    /// Top of stack as a real number, if it has no imaginary part.
+    pub fn top_real(&self) -> Option<f64> {
+        self.stack.last().and_then(|entry| entry.result.as_real())
+    }
+
+    /// Second stack entry (the one below the top) as a real number.
+    pub fn second_real(&self) -> Option<f64> {
+        self.stack.from_top(1).and_then(|entry| entry.result.as_real())
+    }
+
+    // Mode switching functions
+    pub fn toggle_angle_mode(&mut self) {
+        self.angle_mode = match self.angle_mode {
+            AngleMode::Radians => AngleMode::Degrees,
+            AngleMode::Degrees => AngleMode::Radians,
+        };
+    }
+
+    pub fn cycle_base_mode(&mut self) {
+        self.base_mode = match self.base_mode {
+            BaseMode::Decimal => BaseMode::Hexadecimal,
+            BaseMode::Hexadecimal => BaseMode::Binary,
+            BaseMode::Binary => BaseMode::Decimal,
+        };
+    }
+
+    pub fn cycle_word_size(&mut self) {
+        self.word_size = match self.word_size {
+            WordSize::Unbounded => WordSize::Bits8,
+            WordSize::Bits8 => WordSize::Bits16,
+            WordSize::Bits16 => WordSize::Bits32,
+            WordSize::Bits32 => WordSize::Bits64,
+            WordSize::Bits64 => WordSize::Unbounded,
+        };
+    }
+
+    /// Whether infix reads 5<30 as a magnitude and an angle: complex results, shown in polar form.
+    pub fn polar_input(&self) -> bool {
+        self.complex_results && self.complex_mode == ComplexMode::Polar
+    }
+
+    pub fn toggle_complex_mode(&mut self) {
+        self.complex_mode = match self.complex_mode {
+            ComplexMode::Rectangular => ComplexMode::Polar,
+            ComplexMode::Polar => ComplexMode::Rectangular,
+        };
+        // With complex results on, '<' reads as ∠ only in polar mode
+        self.parse_cache.clear();
+    }
+
+    pub fn toggle_raw_expressions(&mut self) {
+        self.show_raw_expressions = !self.show_raw_expressions;
+    }
+
+    /// Current modes, as recorded on new stack entries.
+    pub fn entry_modes(&self) -> EntryModes {
+        EntryModes { angle: self.angle_mode, base: self.base_mode, complex: self.complex_mode }
+    }
+
+    /// A new stack entry under the current modes, numbered and stamped with the time.
+    fn new_entry(&mut self, expression: impl Into<Arc<str>>, ast: Expr, result: StackValue, origin: EntryOrigin) -> StackEntry {
+        let expression = expression.into();
+        self.apply_stack_limit(); // Before the entry is pushed
+        if let Some(warning) = Warning::for_entry(&expression, &result, self.base_mode, self.word_size) {
+            self.warning = Some(warning);
+        }
+        self.last_entry_id += 1;
+        let created_ms = u64::try_from(backup::now_ms()).unwrap_or(u64::MAX);
+        StackEntry { expression, ast: ast.into(), result, modes: self.entry_modes(), label: None, id: self.last_entry_id, created_ms, origin }
+    }
+
+    pub fn display_settings(&self) -> DisplaySettings {
+        DisplaySettings {
+            modes: self.entry_modes(),
+            word_size: self.word_size,
+            precision: self.precision,
+            abbreviation: self.abbreviation_mode,
+            dms: self.dms_display,
+            raw_expressions: self.show_raw_expressions,
+            decimal_separator: self.decimal_separator,
+            history_template: self.history_template.clone(),
+        }
+    }
+
+    /// Stack expression as displayed: minimal parentheses unless the raw form is toggled on.
+    pub fn display_expression(&self, entry: &StackEntry) -> String {
+        if self.show_raw_expressions {
+            entry.expression.to_string()
+        } else {
+            self.decimal_separator.localize(&entry.ast.pretty())
+        }
+    }
+
+    pub fn toggle_abbreviation(&mut self) {
+        self.abbreviation_mode = !self.abbreviation_mode;
+    }
+
+    /// Switches RPN/Infix. What's typed so far is kept when the other mode reads it the
+    /// same way (a number, or a command line); an infix expression can't be an RPN number.
+    pub fn toggle_mode(&mut self) {
+        let keep_input = self.is_command_input()
+            || match self.mode {
+                CalculatorMode::RPN => true, // An RPN number is a valid infix expression
+                CalculatorMode::Infix => self.input.chars().all(|ch| ch.is_ascii_digit() || ch == '.' || dms::is_dms_char(ch)),
+            };
+        self.mode = match self.mode {
+            CalculatorMode::RPN => CalculatorMode::Infix,
+            CalculatorMode::Infix => CalculatorMode::RPN,
+        };
+        self.error = None; // Clear any error when mode changes
+        if !keep_input {
+            self.notice = Some(format!("Cleared '{}': RPN input is a single number", self.input));
+            self.set_input("");
+        }
+    }
+
+    /// Runs `action` as one undo step. Actions that call other undoable
+    /// actions (enter duplicating, iterate pushing a sequence) still undo at once.
+    fn undoable(&mut self, label: &str, action: impl FnOnce(&mut Self)) {
+        self.apply_stack_limit();
+        self.undo.begin(label, &self.stack, self.grand_total);
+        action(self);
+        self.undo.commit(&self.stack);
+    }
+
+    pub fn undo(&mut self) {
+        match self.undo.undo() {
+            Some(step) => {
+                self.stack = step.stack;
+                self.grand_total = step.grand_total;
+                self.apply_stack_limit();
+                self.stack_position = self.stack_position.min(self.stack.len().saturating_sub(1));
+                self.error = None;
+                self.notice = Some(format!("Undid {}", step.label));
+            }
+            None => self.error = Some(CalculatorError::NothingToUndo),
+        }
+    }
+
+    // Stack operations
+    #[allow(clippy::should_implement_trait)] // RPN "drop", not Drop::drop
+    pub fn drop(&mut self) {
+        self.undoable("drop", |calc| {
+            if !calc.stack.is_empty() {
+                calc.stack.pop();
+                calc.stack_position = calc.stack_position.min(calc.stack.len().saturating_sub(1));
+            }
+        });
+    }
+
+    pub fn swap(&mut self) {
+        self.undoable("swap", |calc| {
+            if calc.stack.len() >= 2 {
+                let len = calc.stack.len();
+                calc.stack.swap(len - 1, len - 2);
+            }
+        });
+    }
+
+    pub fn duplicate(&mut self) {
+        self.undoable("duplicate", |calc| {
+            if let Some(top) = calc.stack.last() {
+                calc.stack.push(top.clone());
+            }
+        });
+    }
+
+    /// :map applies `operation operand` (`* 1.08`), or a one-argument function
+    /// (`sqrt`), to every stack entry in one undo step. Labels stay attached,
+    /// and if any entry fails the stack is left as it was.
+    pub fn map_stack(&mut self, operation: &str, operand: Option<&str>) {
+        let op_char = match (operation, operand) {
+            ("+" | "-" | "*" | "/" | "^", Some(_)) => operation.chars().next(),
+            (name, None) if functions::lookup(name).is_some_and(|function| function.arity == 1) => None,
+            _ => {
+                let value = [Some(operation), operand].into_iter().flatten().collect::<Vec<_>>().join(" ");
+                self.error = Some(CalculatorError::InvalidSetting { setting: "map", value, expected: "an operator and a value (* 1.08) or a one-argument function" });
+                return;
+            }
+        };
+        let operand = match operand {
+            Some(text) => match self.parse_expression(text).and_then(|ast| Ok((self.evaluate_value(&ast)?, ast))) {
+                Ok((result, ast)) => Some(self.new_entry(text, ast, result, EntryOrigin::Operation)),
+                Err(error) => {
+                    self.error = Some(error);
+                    return;
+                }
+            },
+            None => None,
+        };
+        if self.stack.is_empty() {
+            self.error = Some(CalculatorError::StackUnderflow { operation: "map".to_string(), needed: 1, available: 0 });
+            return;
+        }
+        self.undoable("map", |calc| {
+            let (entries, history) = (std::mem::take(&mut calc.stack), calc.history.clone());
+            let mut mapped = Stack::default();
+            for entry in &entries {
+                // Each entry is worked out alone, on a stack of just itself and the operand
+                calc.error = None;
+                calc.stack = Stack::from_iter([entry.clone()]);
+                match (op_char, &operand) {
+                    (Some(op_char), Some(operand)) => {
+                        calc.stack.push(operand.clone());
+                        calc.apply_rpn_operator(op_char);
+                    }
+                    _ => calc.apply_function(operation),
+                }
+                match calc.stack.pop() {
+                    Some(mut result) if calc.error.is_none() && calc.stack.is_empty() => {
+                        result.label = entry.label.clone();
+                        mapped.push(result);
+                    }
+                    _ => {
+                        calc.stack = entries;
+                        calc.history = history;
+                        return;
+                    }
+                }
+            }
+            calc.stack = mapped;
+            calc.notice = Some(format!("Mapped over {} entries", calc.stack.len()));
+        });
+    }
+
+    /// :fold + or :fold * combines the whole stack into one entry, oldest first,
+    /// recorded as a single history line.
+    pub fn fold_stack(&mut self, operation: &str) {
+        let op_char = match operation {
+            "+" => '+',
+            "*" => '*',
+            _ => {
+                self.error = Some(CalculatorError::InvalidSetting { setting: "fold", value: operation.to_string(), expected: "+ or *" });
+                return;
+            }
+        };
+        if self.stack.len() < 2 {
+            self.error = Some(CalculatorError::StackUnderflow { operation: "fold".to_string(), needed: 2, available: self.stack.len() });
+            return;
+        }
+        self.undoable("fold", |calc| {
+            let entries = std::mem::take(&mut calc.stack);
+            let history = std::mem::take(&mut calc.history);
+            calc.error = None;
+            for entry in entries.iter().cloned() {
+                calc.stack.push(entry);
+                if calc.stack.len() > 1 {
+                    calc.apply_rpn_operator(op_char);
+                }
+                if calc.error.is_some() {
+                    break;
+                }
+            }
+            let steps = std::mem::replace(&mut calc.history, history);
+            match (&calc.error, steps.last()) {
+                (None, Some(step)) => calc.push_history(step.expression.clone(), step.result.clone()),
+                _ => calc.stack = entries,
+            }
+            calc.stack_position = 0;
+        });
+    }
+
+    /// :frac [tolerance] replaces the top of the stack with the simplest fraction
+    /// within `tolerance` of it, as a `1/3` entry, and shows both forms.
+    pub fn to_fraction(&mut self, tolerance: Option<&str>) {
+        let tolerance = match tolerance.map(str::parse::<f64>) {
+            None => FRACTION_TOLERANCE,
+            Some(Ok(tolerance)) if tolerance >= 0.0 => tolerance,
+            _ => {
+                self.error = Some(CalculatorError::InvalidArguments(":frac [tolerance], e.g. :frac 1e-3"));
+                return;
+            }
+        };
+        let Some(entry) = self.stack.last() else {
+            self.error = Some(CalculatorError::StackUnderflow { operation: "frac".to_string(), needed: 1, available: 0 });
+            return;
+        };
+        let Some(value) = entry.result.as_real() else {
+            self.error = Some(CalculatorError::ComplexUnsupported { operation: "frac".to_string() });
+            return;
+        };
+        let Some((numerator, denominator)) = inspect::simplest_fraction(value, tolerance) else {
+            self.error = Some(CalculatorError::Domain { function: "frac", expected: "a value that close to a fraction with a denominator up to 1,000,000" });
+            return;
+        };
+        let fraction = format!("{}/{}", numerator, denominator);
+        let exact = numerator as f64 / denominator as f64;
+        self.undoable("frac", |calc| {
+            let label = calc.stack.pop().and_then(|entry| entry.label);
+            let ast = Expr::binary('/', Expr::number(numerator as f64, numerator.to_string()), Expr::number(denominator as f64, denominator.to_string()));
+            let entry = calc.new_entry(fraction.as_str(), ast, StackValue::Real(exact), EntryOrigin::Operation);
+            calc.stack.push(StackEntry { label, ..entry });
+            calc.push_history(fraction.clone(), Some(StackValue::Real(exact)));
+            calc.error = None;
+        });
+        self.notice = Some(match exact - value {
+            0.0 => format!("{} = {}", self.format_real(value), fraction),
+            error => format!("{} ≈ {} (off by {:.1e})", self.format_real(value), fraction, error),
+        });
+    }
+
+    /// :sort [asc|desc] [keep] orders the stack by value, the largest on top
+    /// when ascending. The sorted values become plain numbers unless `keep`
+    /// moves whole entries, expressions and labels included.
+    pub fn sort_stack(&mut self, options: &[&str]) {
+        let (descending, keep) = match options {
+            [] | ["asc"] => (false, false),
+            ["desc"] => (true, false),
+            ["keep"] | ["asc", "keep"] => (false, true),
+            ["desc", "keep"] => (true, true),
+            _ => {
+                self.error = Some(CalculatorError::InvalidSetting { setting: "sort", value: options.join(" "), expected: "asc or desc, optionally followed by keep" });
+                return;
+            }
+        };
+        if self.stack.iter().any(|entry| entry.result.as_real().is_none()) {
+            self.error = Some(CalculatorError::ComplexUnsupported { operation: "sort".to_string() });
+            return;
+        }
+        self.undoable("sort", |calc| {
+            // Stable, so equal values keep their order; NaN sorts past every number
+            calc.stack.sort_by(|a, b| {
+                let order = a.result.as_real().unwrap_or(f64::NAN).total_cmp(&b.result.as_real().unwrap_or(f64::NAN));
+                if descending { order.reverse() } else { order }
+            });
+            if !keep {
+                let entries = std::mem::take(&mut calc.stack);
+                calc.stack = entries
+                    .into_iter()
+                    .map(|entry| {
+                        let expression = calc.format_stack_value(&entry.result);
+                        let ast = Expr::number(entry.result.as_real().unwrap_or(f64::NAN), expression.clone());
+                        // The same values, so they keep their ids, times and origins
+                        StackEntry { expression: expression.into(), ast: ast.into(), modes: calc.entry_modes(), label: None, ..entry }
+                    })
+                    .collect();
+            }
+            calc.notice = Some(format!("Sorted {} entries {}", calc.stack.len(), if descending { "descending" } else { "ascending" }));
+        });
+    }
+
+    /// :rapid starts or stops adding-machine entry: Space or Enter pushes each
+    /// number, and = or ~ replaces the numbers keyed since the start with their
+    /// total or mean.
+    pub fn toggle_rapid_entry(&mut self) {
+        self.rapid_start = match self.rapid_start {
+            Some(_) => None,
+            None => Some(self.stack.len()),
+        };
+        self.notice = Some(match self.rapid_start {
+            Some(_) => "Rapid entry: Space or Enter pushes, = totals, ~ averages; :rapid ends".to_string(),
+            None => "Rapid entry off".to_string(),
+        });
+    }
+
+    /// Entries keyed in the current rapid entry run; entries dropped or undone leave it too.
+    pub fn rapid_run(&self) -> impl ExactSizeIterator<Item = &StackEntry> {
+        let start = self.rapid_start.map_or(self.stack.len(), |start| start.min(self.stack.len()));
+        self.stack.range(start..)
+    }
+
+    /// Count and sum of the current run, for the live display.
+    pub fn rapid_tally(&self) -> (usize, f64) {
+        let run = self.rapid_run();
+        (run.len(), run.filter_map(|entry| entry.result.as_real()).sum())
+    }
+
+    /// Replaces the run with its total (or mean) and starts a new one after it.
+    fn finish_rapid_run(&mut self, mean: bool) {
+        if !self.input.is_empty()
+            && let Err(e) = self.parse_current_input_to_stack_entry()
+        {
+            self.error = Some(e);
+            return;
+        }
+        let (count, sum) = self.rapid_tally();
+        self.replace_top_with_total(count, sum, mean);
+        if self.error.is_none() {
+            self.rapid_start = Some(self.stack.len());
+        }
+    }
+
+    /// Replaces the top `count` entries with `sum`, their total, or with their mean.
+    fn replace_top_with_total(&mut self, count: usize, sum: f64, mean: bool) {
+        if count == 0 {
+            self.error = Some(CalculatorError::StackUnderflow { operation: if mean { "mean" } else { "total" }.to_string(), needed: 1, available: 0 });
+            return;
+        }
+        self.undoable(if mean { "mean" } else { "total" }, |calc| {
+            let run = calc.stack.split_off(calc.stack.len() - count);
+            let mut expression = run.iter().map(|entry| &*entry.expression).collect::<Vec<_>>().join(" + ");
+            let mut ast = run.into_iter().map(|entry| Arc::unwrap_or_clone(entry.ast)).reduce(|total, next| Expr::binary('+', total, next)).expect("run is not empty");
+            let mut value = sum;
+            if count > 1 {
+                expression = format!("({})", expression);
+            }
+            if mean {
+                expression = format!("{} / {}", expression, count);
+                ast = Expr::binary('/', ast, Expr::number(count as f64, count.to_string()));
+                value /= count as f64;
+            }
+            let entry = calc.new_entry(expression.as_str(), ast, StackValue::Real(value), EntryOrigin::Operation);
+            calc.stack.push(entry);
+            calc.push_history(expression, Some(StackValue::Real(value)));
+            calc.notice = Some(format!("{} of {} {}", if mean { "Mean" } else { "Total" }, count, if count == 1 { "entry" } else { "entries" }));
+            calc.error = None;
+        });
+    }
+
+    /// Runs an RPN line item by item, as dc does: numbers are pushed, operators,
+    /// constants and functions applied, and drop, dup, swap, clear, neg, sum and
+    /// mean do what their keys do (sum and mean over the whole stack).
+    /// It stops at the first item that fails, leaving it and the rest as input.
+    fn run_words(&mut self, line: &str) {
+        let items = split_items(line);
+        for (i, item) in items.iter().enumerate() {
+            self.error = None;
+            self.run_word(item);
+            if self.error.is_some() {
+                self.set_input(items[i..].join(" "));
+                return;
+            }
+        }
+    }
+
+    fn run_word(&mut self, item: &str) {
+        let hex_digits = self.base_mode == BaseMode::Hexadecimal && item.chars().all(|ch| ch.is_ascii_hexdigit());
+        match item {
+            "drop" => self.drop(),
+            "dup" => self.duplicate(),
+            "swap" => self.swap(),
+            "clear" => self.clear_stack(),
+            "neg" => self.negate(),
+            "sum" | "mean" => match self.stack.iter().map(|entry| entry.result.as_real()).sum::<Option<f64>>() {
+                Some(sum) => self.replace_top_with_total(self.stack.len(), sum, item == "mean"),
+                None => self.error = Some(CalculatorError::ComplexUnsupported { operation: item.to_string() }),
+            },
+            "+" | "-" | "*" | "/" | "^" => self.apply_rpn_operator(item.chars().next().unwrap_or('+')),
+            _ if hex_digits => self.push_word_number(item),
+            name if functions::constant(name).is_some() => self.push_constant(name),
+            name if functions::lookup(name).is_some() => self.apply_function(name),
+            name if name.chars().any(char::is_alphabetic) => self.error = Some(CalculatorError::UnknownCommand(name.to_string())),
+            number => self.push_word_number(number),
+        }
+    }
+
+    fn push_word_number(&mut self, number: &str) {
+        self.set_input(number);
+        if let Err(e) = self.parse_current_input_to_stack_entry() {
+            self.set_input("");
+            self.error = Some(e);
+        }
+    }
+
+    /// :drop N M removes stack levels N through M, where 1 is the top.
+    pub fn drop_levels(&mut self, from: &str, to: &str) {
+        let level = |text: &str| text.parse::<usize>().ok().filter(|level| *level >= 1);
+        let (Some(from), Some(to)) = (level(from), level(to)) else {
+            let value = if from == to { from.to_string() } else { format!("{} {}", from, to) };
+            self.error = Some(CalculatorError::InvalidSetting { setting: "drop", value, expected: "stack levels, 1 being the top" });
+            return;
+        };
+        let (low, high) = (from.min(to), from.max(to));
+        let available = self.stack.len();
+        if high > available {
+            self.error = Some(CalculatorError::StackUnderflow { operation: "drop".to_string(), needed: high, available });
+            return;
+        }
+        if low == high {
+            self.drop_range(low, high);
+        } else {
+            self.confirmation = Some(Confirmation::DropLevels { low, high });
+        }
+    }
+
+    fn drop_range(&mut self, low: usize, high: usize) {
+        let available = self.stack.len();
+        self.undoable("drop", |calc| {
+            calc.stack.drain(available - high..=available - low);
+            calc.stack_position = calc.stack_position.min(calc.stack.len().saturating_sub(1));
+            calc.notice = Some(format!("Dropped {} {}", high - low + 1, if high == low { "entry" } else { "entries" }));
+        });
+    }
+
+    /// The +/- key: flips the sign of the number being typed (of its exponent once
+    /// there is one, so 1e3 becomes 1e-3), otherwise negates the top of the stack.
+    pub fn negate(&mut self) {
+        if self.typing_decimal() {
+            let sign_at = self.input.find(['e', 'E']).map_or(0, |e| e + 1);
+            if self.input[sign_at..].starts_with('-') {
+                self.input.remove(sign_at);
+                if self.cursor > sign_at {
+                    self.cursor -= 1;
+                }
+            } else {
+                self.input.insert(sign_at, '-');
+                if self.cursor >= sign_at {
+                    self.cursor += 1;
+                }
+            }
+            self.error = None;
+            return;
+        }
+        self.undoable("negate", Self::negate_top);
+    }
+
+    fn negate_top(&mut self) {
+        if let Some(top) = self.stack.last_mut() {
+            match top {
+                StackEntry { result: StackValue::Real(r), .. } => *r = -*r,
+                StackEntry { result: StackValue::Complex(c), .. } => {
+                    c.real = -c.real;
+                    c.imag = -c.imag;
+                }
+                StackEntry { result: StackValue::Uncertain(m), .. } => m.value = -m.value,
+                StackEntry { result: StackValue::Vector(items), .. } => items.iter_mut().for_each(|x| *x = -*x),
+            }
+        }
+    }
+
+    /// GT: pushes the grand total of the results so far. It goes into history
+    /// as a typed number would, so it isn't added to itself.
+    pub fn recall_grand_total(&mut self) {
+        self.undoable("GT", |calc| {
+            if !calc.input.is_empty()
+                && !calc.is_command_input()
+                && let Err(e) = calc.parse_current_input_to_stack_entry()
+            {
+                calc.error = Some(e);
+                return;
+            }
+            let text = calc.grand_total.to_string();
+            calc.push_value(text.clone(), text.clone(), calc.grand_total, EntryOrigin::Operation);
+            calc.push_history(text, None);
+            calc.error = None;
+        });
+    }
+
+    /// TAX+, TAX- and TIP: the top of the stack times or divided by 1 + rate,
+    /// with the history line labelled by the key and the rate it used.
+    pub fn apply_rate(&mut self, adjustment: RateAdjustment) {
+        let (name, rate) = match adjustment {
+            RateAdjustment::AddTip => ("tip", self.tip_rate),
+            _ => ("tax", self.tax_rate),
+        };
+        let Some(rate) = rate else {
+            self.error = Some(CalculatorError::NoRate(name));
+            return;
+        };
+        self.undoable(adjustment.label(), |calc| {
+            if !calc.input.is_empty() && !calc.is_command_input() {
+                calc.submit_input();
+                if calc.error.is_some() {
+                    return;
+                }
+            }
+            if calc.stack.is_empty() {
+                calc.error = Some(CalculatorError::StackUnderflow { operation: adjustment.label().to_string(), needed: 1, available: 0 });
+                return;
+            }
+            let factor = 1.0 + rate / 100.0;
+            let factor_text = factor.to_string();
+            calc.push_value(factor_text.clone(), factor_text, factor, EntryOrigin::Operation);
+            calc.error = None;
+            match adjustment {
+                RateAdjustment::RemoveTax => calc.binary_operation('/'),
+                _ => calc.binary_operation('*'),
+            }
+            if calc.error.is_some() {
+                calc.stack.pop(); // The factor; the value it was applied to stays
+                return;
+            }
+            if let Some(entry) = calc.history.last_mut() {
+                entry.comment = Some(format!("{} {}%", adjustment.label(), rate));
+            }
+        });
+    }
+
+    /// →POL: replaces x and y (or a complex top) with the radius and the
+    /// angle, in the current angle unit.
+    pub fn convert_to_polar(&mut self) {
+        self.undoable("→POL", |calc| calc.convert_coordinates(true));
+    }
+
+    /// →REC: replaces a radius and angle with x and y; a complex top is split
+    /// into its real and imaginary parts.
+    pub fn convert_to_rectangular(&mut self) {
+        self.undoable("→REC", |calc| calc.convert_coordinates(false));
+    }
+
+    fn convert_coordinates(&mut self, to_polar: bool) {
+        let operation = if to_polar { "→POL" } else { "→REC" };
+        let (names, source, values) = if let Some(StackValue::Complex(c)) = self.stack.last().map(|entry| &entry.result) {
+            let values = if to_polar { (c.magnitude(), self.angle_from_radians(c.phase())) } else { (c.real, c.imag) };
+            let source = self.stack.pop().map(|entry| entry.expression.to_string()).unwrap_or_default();
+            (if to_polar { ("r", "θ") } else { ("re", "im") }, source, values)
+        } else {
+            if self.stack.len() < 2 {
+                self.error = Some(CalculatorError::StackUnderflow { operation: operation.to_string(), needed: 2, available: self.stack.len() });
+                return;
+            }
+            let (Some(a), Some(b)) = (self.second_real(), self.top_real()) else {
+                self.error = Some(CalculatorError::ComplexUnsupported { operation: operation.to_string() });
+                return;
+            };
+            let values = if to_polar {
+                (a.hypot(b), self.angle_from_radians(b.atan2(a)))
+            } else {
+                let theta = self.angle_to_radians(b);
+                (a * theta.cos(), a * theta.sin())
+            };
+            let top = self.stack.pop().map(|entry| entry.expression).unwrap_or_default();
+            let below = self.stack.pop().map(|entry| entry.expression).unwrap_or_default();
+            (if to_polar { ("r", "θ") } else { ("x", "y") }, format!("{}, {}", below, top), values)
+        };
+
+        for (name, value) in [(names.0, values.0), (names.1, values.1)] {
+            let expression = format!("{}({})", name, source);
+            self.push_value(expression.clone(), expression.clone(), value, EntryOrigin::Operation);
+            self.push_history(expression, Some(StackValue::Real(value)));
+        }
+        self.error = None;
+    }
+
+    /// Calls a built-in function on the top `arity` stack entries, e.g. `gcd` on the top two.
+    pub fn apply_function(&mut self, name: &str) {
+        let Some(function) = functions::lookup(name) else {
+            self.error = Some(CalculatorError::UnknownFunction(name.to_string()));
+            return;
+        };
+        self.undoable(function.name, |calc| {
+            let available = calc.stack.len();
+            if available < function.arity {
+                calc.error = Some(CalculatorError::StackUnderflow { operation: function.name.to_string(), needed: function.arity, available });
+                return;
+            }
+            let args: Vec<StackEntry> = calc.stack.split_off(available - function.arity).into_iter().collect();
+            // A one-argument function applies to every element of a vector
+            let vector_value = match args.as_slice() {
+                [StackEntry { result: StackValue::Vector(items), .. }] => Some(calc.map_function(function, items)),
+                _ if args.iter().any(|entry| matches!(entry.result, StackValue::Vector(_))) => Some(Err(CalculatorError::VectorUnsupported { operation: function.name.to_string() })),
+                _ => None,
+            };
+            let values: Vec<&StackValue> = args.iter().map(|entry| &entry.result).collect();
+            let value = match vector_value.map(|items| items.map(StackValue::Vector)).unwrap_or_else(|| calc.call_on_stack(function, &values)) {
+                Ok(value) => value,
+                Err(e) => {
+                    calc.stack.extend(args);
+                    calc.error = Some(e);
+                    return;
+                }
+            };
+            let expressions: Vec<&str> = args.iter().map(|entry| &*entry.expression).collect();
+            let expression = format!("{}({})", function.name, expressions.join(", "));
+            let ast = Expr::Call { name: function.name.to_string(), args: args.into_iter().map(|entry| Arc::unwrap_or_clone(entry.ast)).collect() };
+            let entry = calc.new_entry(expression.as_str(), ast, value.clone(), EntryOrigin::Operation);
+            calc.stack.push(entry);
+            calc.push_history(expression, Some(value));
+            calc.label_conversion(function.name);
+            calc.error = None;
+        });
+    }
+
+    /// `function` of stack values, in the Number type they call for: measurements
+    /// when any is uncertain, complex numbers with `:set complex on`, else reals.
+    fn call_on_stack(&self, function: &Function, args: &[&StackValue]) -> Result<StackValue, CalculatorError> {
+        if args.iter().any(|value| matches!(value, StackValue::Uncertain(_))) {
+            self.call_number::<Measurement>(function, args)
+        } else if self.complex_results {
+            self.call_number::<ComplexNumber>(function, args)
+        } else {
+            self.call_number::<f64>(function, args)
+        }
+    }
+
+    fn call_number<N: Number>(&self, function: &Function, args: &[&StackValue]) -> Result<StackValue, CalculatorError> {
+        let args = args.iter().map(|value| N::from_value(value).ok_or_else(|| CalculatorError::ComplexUnsupported { operation: function.name.to_string() })).collect::<Result<Vec<N>, _>>()?;
+        N::call(function, &args, self.eval_context()).map(N::into_value)
+    }
+
+    /// `function` on each element, checking each against its domain.
+    fn map_function(&self, function: &Function, items: &[f64]) -> Result<Vec<f64>, CalculatorError> {
+        items.iter().map(|x| function.check_domain(&[*x]).map(|_| function.call(&[*x], self.eval_context()))).collect()
+    }
+
+    /// :dot, :norm and :at on the stack: the dot product of the top two
+    /// vectors, the length of the top one, and the element of the vector
+    /// below at the index on top, counting from 1.
+    pub fn apply_vector_function(&mut self, name: &str) {
+        let (name, arity, usage) = match name {
+            "dot" => ("dot", 2, "dot(u, v)"),
+            "norm" => ("norm", 1, "norm(v)"),
+            _ => ("at", 2, "a vector and an index"),
+        };
+        self.undoable(name, |calc| {
+            let available = calc.stack.len();
+            if available < arity {
+                calc.error = Some(CalculatorError::StackUnderflow { operation: name.to_string(), needed: arity, available });
+                return;
+            }
+            let args: Vec<StackEntry> = calc.stack.split_off(available - arity).into_iter().collect();
+            let value = match (name, &args[0].result, args.get(1).map(|entry| &entry.result)) {
+                ("dot", StackValue::Vector(u), Some(StackValue::Vector(v))) => vector::dot(u, v),
+                ("norm", StackValue::Vector(v), None) => Ok(vector::norm(v)),
+                ("at", StackValue::Vector(v), Some(index)) => index.as_real().ok_or(CalculatorError::InvalidArguments(usage)).and_then(|index| vector::index(v, index)),
+                _ => Err(CalculatorError::InvalidArguments(usage)),
+            };
+            let value = match value {
+                Ok(value) => StackValue::Real(value),
+                Err(e) => {
+                    calc.stack.extend(args);
+                    calc.error = Some(e);
+                    return;
+                }
+            };
+            let (expression, ast) = match args.as_slice() {
+                [v, index] if name == "at" => (format!("{}[{}]", v.expression, index.expression), Expr::binary('[', (*v.ast).clone(), (*index.ast).clone())),
+                _ => {
+                    let expressions: Vec<&str> = args.iter().map(|entry| &*entry.expression).collect();
+                    (format!("{}({})", name, expressions.join(", ")), Expr::Call { name: name.to_string(), args: args.iter().map(|entry| (*entry.ast).clone()).collect() })
+                }
+            };
+            let entry = calc.new_entry(expression.as_str(), ast, value.clone(), EntryOrigin::Operation);
+            calc.stack.push(entry);
+            calc.push_history(expression, Some(value));
+            calc.error = None;
+        });
+    }
+
+    /// The modes expressions are evaluated under.
+    pub fn eval_context(&self) -> EvalContext {
+        EvalContext { angle: self.angle_mode, rounding: self.rounding }
+    }
+
+    /// Converts an angle in radians to the current angle unit.
+    pub fn angle_from_radians(&self, radians: f64) -> f64 {
+        self.eval_context().from_radians(radians)
+    }
+
+    /// Converts an angle in the current unit to radians.
+    pub fn angle_to_radians(&self, angle: f64) -> f64 {
+        self.eval_context().to_radians(angle)
+    }
+
+    pub fn browse_stack_up(&mut self) {
+        self.browse_stack_to(self.stack_position.saturating_sub(1));
+    }
+
+    pub fn browse_stack_down(&mut self) {
+        self.browse_stack_to(self.stack_position + 1);
+    }
+
+    /// Selects the entry `position` places down from the top, or the bottom one past it.
+    pub fn browse_stack_to(&mut self, position: usize) {
+        self.stack_position = position.min(self.stack.len().saturating_sub(1));
+    }
+
+    pub fn browse_history_up(&mut self) {
+        self.browse_history_to(self.history_position.saturating_sub(1));
+    }
+
+    pub fn browse_history_down(&mut self) {
+        self.browse_history_to(self.history_position + 1);
+    }
+
+    /// Recalls history entry `position` into the input. Past the newest entry the
+    /// input is cleared, back where a new expression is typed.
+    pub fn browse_history_to(&mut self, position: usize) {
+        if self.history.is_empty() {
+            return;
+        }
+        self.history_position = position.min(self.history.len());
+        let expression = self.history.get(self.history_position).map(|entry| entry.expression.clone()).unwrap_or_default();
+        self.set_input(expression);
+        self.error = None;
+    }
+
+    /// `:find <number> [tolerance]`: selects the first entry whose result is the number,
+    /// the stack from the top and then history from the newest; `:find` alone steps to
+    /// the next match. The tolerance is half a unit in the last digit typed unless given.
+    fn find_value(&mut self, args: &[&str]) {
+        let number = |text: &str| self.decimal_separator.canonical(text).parse::<f64>().ok().filter(|x| x.is_finite());
+        let search = match args {
+            [] => self.last_find.take().map(|search| Search { next: search.next + 1, ..search }),
+            [typed] => number(typed).map(|target| Search { typed: typed.to_string(), target, tolerance: find::tolerance(&self.decimal_separator.canonical(typed)), next: 0 }),
+            [typed, tolerance] => number(typed).zip(number(tolerance)).map(|(target, tolerance)| Search { typed: typed.to_string(), target, tolerance: tolerance.abs(), next: 0 }),
+            _ => None,
+        };
+        let Some(search) = search else {
+            self.error = Some(CalculatorError::InvalidSetting { setting: "find", value: args.join(" "), expected: "a number and an optional tolerance" });
+            return;
+        };
+        let in_stack = (0..self.stack.len()).filter(|&level| self.stack.from_top(level).is_some_and(|entry| find::matches(&entry.result, search.target, search.tolerance))).map(Found::Stack);
+        let in_history = (0..self.history.len()).rev().filter(|&index| self.history_value(index).is_some_and(|value| find::matches(&value, search.target, search.tolerance))).map(Found::History);
+        let matches: Vec<Found> = in_stack.chain(in_history).collect();
+        if matches.is_empty() {
+            self.error = Some(CalculatorError::NoMatch { value: search.typed, tolerance: search.tolerance });
+            return;
+        }
+        let next = search.next % matches.len();
+        let place = match matches[next] {
+            Found::Stack(level) => {
+                self.browse_stack_to(level);
+                format!("stack level {}", level + 1)
+            }
+            Found::History(index) => {
+                self.browse_history_to(index);
+                format!("history entry !{}", index + 1)
+            }
+        };
+        self.notice = Some(format!("{} is at {} (match {} of {}, within ±{}); :find again for the next", search.typed, place, next + 1, matches.len(), search.tolerance));
+        self.found = Some(matches[next]);
+        self.last_find = Some(Search { next, ..search });
+    }
+
+    /// Puts a line back into the input to be fixed and entered again, e.g. one from the error log.
+    pub fn restore_input(&mut self, line: &str) {
+        self.set_input(line);
+        self.error = None;
+    }
+
+    /// Alt+Up: the line submitted before the one shown, as typed, even if it failed.
+    pub fn recall_older_input(&mut self) {
+        if let Some(line) = self.input_history.older(&self.input) {
+            self.set_input(line);
+            self.error = None;
+        }
+    }
+
+    /// Alt+Down: the next line submitted, and after the newest whatever was being typed.
+    pub fn recall_newer_input(&mut self) {
+        if let Some(line) = self.input_history.newer() {
+            self.set_input(line);
+            self.error = None;
+        }
+    }
+
+    pub fn enter(&mut self) {
+        self.input_history.record(&self.input);
+        self.undoable("enter", Self::submit_input);
+        self.suggestion = match &self.error {
+            Some(_) if !self.is_command_input() => lint::lint(&self.input, self.base_mode, self.decimal_separator),
+            _ => None,
+        };
+    }
+
+    /// Replaces the input with the lint suggestion's correction.
+    pub fn apply_suggestion(&mut self) {
+        if self.error.is_none() {
+            return;
+        }
+        if let Some(fix) = self.suggestion.take().and_then(|suggestion| suggestion.fix) {
+            self.set_input(fix);
+            self.error = None;
+        }
+    }
+
+    fn submit_input(&mut self) {
+        if self.is_command_input() {
+            let command = self.input[1..].trim().to_string();
+            self.set_input("");
+            self.error = None;
+            self.execute_command(&command);
+            return;
+        }
+
+        match self.mode {
+            CalculatorMode::RPN if self.is_word_line() => {
+                let line = self.input.clone();
+                self.set_input("");
+                self.run_words(&line);
+                return;
+            }
+            CalculatorMode::RPN if self.rapid_start.is_some() => {
+                // Every Enter is a new number: no history recall, and an empty input does nothing
+                if !self.input.is_empty()
+                    && let Err(e) = self.parse_current_input_to_stack_entry()
+                {
+                    self.error = Some(e);
+                    return;
+                }
+            }
+            CalculatorMode::RPN => {
+                if !self.input.is_empty() {
+                    // Opt-in: recall the first history entry whose expression starts with the input
+                    if self.prefix_recall
+                        && !self.input.starts_with('!')
+                        && let Some(history_entry) = self.history.iter().find(|entry| entry.expression.starts_with(&self.input))
+                    {
+                        // Recall the stored result
+                        if let Some(result) = &history_entry.result
+                            && let Some(num) = result.as_real()
+                        {
+                            let result_str = self.format_real(num);
+                            let new_entry = self.new_entry(result_str.clone(), Expr::number(num, result_str), StackValue::Real(num), EntryOrigin::History);
+                            self.stack.push(new_entry);
+                            self.set_input("");
+                            self.error = None;
+                            return;
+                        }
+                    }
+
+                    // If not a history recall, push the current number to the stack
+                    if let Err(e) = self.parse_current_input_to_stack_entry() {
+                        self.error = Some(e);
+                        return;
+                    }
+                } else {
+                    // If input is empty, duplicate the top stack item (RPN behavior)
+                    self.duplicate();
+                }
+            }
+            CalculatorMode::Infix => {
+                self.submit_infix();
+                return;
+            }
+        }
+        self.error = None; // Clear error after successful operation
+    }
+
+    /// The value of history entry `!N` (numbered from 1, as in the history panel)
+    /// or `!!` (the newest). A number entered in RPN has no stored result, so its
+    /// expression is read again.
+    fn recall(&self, reference: &str) -> Result<StackValue, CalculatorError> {
+        let index = match reference.strip_prefix('!') {
+            Some("!") => self.history.len().checked_sub(1),
+            Some(number) => number.parse::<usize>().ok().and_then(|number| number.checked_sub(1)),
+            None => None,
+        };
+        index.and_then(|index| self.history_value(index)).ok_or_else(|| CalculatorError::NoSuchHistoryEntry(reference.to_string()))
+    }
+
+    // A history entry's result; an entry without one, such as a pushed number, gives the value of its expression
+    fn history_value(&self, index: usize) -> Option<StackValue> {
+        match self.history.get(index)? {
+            HistoryEntry { result: Some(result), .. } => Some(result.clone()),
+            entry => self.evaluate(&entry.expression).map(StackValue::Real).ok(),
+        }
+    }
+
+    /// Replaces each `!N` and `!!` in an infix expression with the recalled value;
+    /// `!=` is left alone.
+    fn expand_recalls(&self, input: &str) -> Result<String, CalculatorError> {
+        let mut expanded = String::new();
+        let mut rest = input;
+        while let Some(at) = rest.find('!') {
+            expanded.push_str(&rest[..at]);
+            let after = &rest[at + 1..];
+            let length = if after.starts_with('!') { 1 } else { after.find(|c: char| !c.is_ascii_digit()).unwrap_or(after.len()) };
+            if length == 0 {
+                expanded.push('!');
+                rest = after;
+                continue;
+            }
+            let value = self.recall(&rest[at..at + 1 + length])?;
+            // Full precision for plain numbers; other values keep their notation
+            let text = match value {
+                StackValue::Real(value) => value.to_string(),
+                other => self.format_stack_value(&other),
+            };
+            expanded.push_str(&format!("({})", text));
+            rest = &after[length..];
+        }
+        expanded.push_str(rest);
+        Ok(expanded)
+    }
+
+    /// Evaluates the input as an infix expression and pushes the result. A
+    /// trailing `# comment` labels the result and is kept with its history line;
+    /// if evaluation fails the input is left as typed, comment included.
+    fn submit_infix(&mut self) {
+        let typed = (self.input.clone(), self.cursor);
+        let (expression, comment) = split_comment(&typed.0);
+        if expression.len() == typed.0.len() {
+            return self.submit_infix_expression();
+        }
+        let comment = comment.map(str::to_string);
+        let (stack_len, history_len) = (self.stack.len(), self.history.len());
+        self.set_input(expression.to_string());
+        self.submit_infix_expression();
+        if self.error.is_some() {
+            self.set_input(typed.0);
+            self.cursor = typed.1;
+            return;
+        }
+        if comment.is_some() {
+            // Trimming can keep the lengths the same, but then something was still added
+            if (self.stack.len() != stack_len || stack_len == self.stack_limit)
+                && let Some(top) = self.stack.last_mut()
+            {
+                top.label.clone_from(&comment);
+            }
+            if (self.history.len() != history_len || history_len == self.history_limit)
+                && let Some(entry) = self.history.last_mut()
+            {
+                entry.comment = comment;
+            }
+        }
+    }
+
+    fn submit_infix_expression(&mut self) {
+        if self.input.is_empty() {
+            // In infix mode, if input is empty, duplicate top stack item
+            self.duplicate();
+            self.error = None;
+            return;
+        }
+
+        // !N and !! become the recalled values, which the history line then shows
+        match self.expand_recalls(&self.input) {
+            Ok(expanded) => self.set_input(expanded),
+            Err(e) => {
+                self.error = Some(e);
+                return;
+            }
+        }
+
+        if self.close_on_enter
+            && let Some(balanced) = Self::balanced_input(&self.input)
+        {
+            self.set_input(balanced);
+        }
+
+        // 5<30 is kept as 5∠30, which reads the same after polar display is turned off
+        if self.polar_input() {
+            let input: String = self.input.char_indices().map(|(i, c)| if c == '<' && !self.input[i + 1..].starts_with('=') { '∠' } else { c }).collect();
+            self.set_input(input);
+        }
+
+        if let Some(outcome) = self.currency_conversion(&self.input) {
+            match outcome {
+                Ok(value) => {
+                    let input = self.input.clone();
+                    self.push_value(input.clone(), input.clone(), value, self.input_origin);
+                    self.push_history(input, Some(StackValue::Real(value)));
+                    self.history_position = self.history.len();
+                    self.set_input("");
+                    self.error = None;
+                }
+                Err(e) => self.error = Some(e),
+            }
+            return;
+        }
+
+        if let Some(outcome) = self.size_conversion(&self.input) {
+            match outcome {
+                Ok((value, unit)) => {
+                    // Name the unit the result is in when the input didn't
+                    let expression = if self.input.contains(" in ") { self.input.clone() } else { format!("{} in {}", self.input, unit) };
+                    self.push_value(expression.clone(), expression.clone(), value, self.input_origin);
+                    self.push_history(expression, Some(StackValue::Real(value)));
+                    self.history_position = self.history.len();
+                    self.set_input("");
+                    self.error = None;
+                }
+                Err(e) => self.error = Some(e),
+            }
+            return;
+        }
+
+        let ast = match self.parse_expression(&self.input) {
+            Ok(ast) => ast,
+            Err(e) => {
+                self.error = Some(e);
+                return;
+            }
+        };
+
+        // Special forms bind x in their first argument or push several values, so they aren't plain functions
+        if let Expr::Call { name, args } = &ast
+            && functions::SPECIAL_FORMS.iter().any(|(special, _)| special == name)
+        {
+            let outcome = match name.as_str() {
+                "iterate" => self.iterate(args),
+                "factor" => self.factor_call(args),
+                "solve" => self.solve(args),
+                "plot" => self.open_plot(args),
+                "roots" => self.polynomial_roots(args),
+                _ => self.calculus(name, args),
+            };
+            match outcome {
+                Ok(()) => {
+                    self.set_input("");
+                    self.error = None;
+                }
+                Err(e) => self.error = Some(e),
+            }
+            return;
+        }
+
+        // Try to evaluate the input as an expression
+        match self.evaluate_value(&ast).map(|result| (result, ast)) {
+            Ok((result, ast)) => {
+                let conversion = match &ast {
+                    Expr::Call { name, .. } => Some(name.clone()),
+                    _ => None,
+                };
+                // A number typed on its own is an entry, not a result, so GT leaves it out
+                let operation = matches!(ast, Expr::Binary { .. } | Expr::Call { .. });
+                let new_entry = self.new_entry(self.input.clone(), ast, result.clone(), self.input_origin);
+                self.stack.push(new_entry); // A full stack drops its oldest entry
+
+                if operation {
+                    self.push_history(self.input.clone(), Some(result));
+                } else {
+                    self.push_history_line(self.input.clone(), Some(result));
+                }
+                if let Some(name) = conversion {
+                    self.label_conversion(&name);
+                }
+                self.history_position = self.history.len(); // Reset history position to the end
+                self.set_input("");
+                self.error = None;
+            }
+            Err(e) => self.error = Some(e),
+        }
+    }
+
+    /// Applies `args[0]` (an expression in x) to its own result `n` times starting at x0.
+    fn iterate(&mut self, args: &[Expr]) -> Result<(), CalculatorError> {
+        const USAGE: &str = "iterate(expr, x0, n)";
+        let [step, start, count] = args else {
+            return Err(CalculatorError::InvalidArguments(USAGE));
+        };
+        let x0 = self.evaluate_expr(start, &[])?;
+        let n = self.evaluate_expr(count, &[])?;
+        if n < 0.0 || n.fract() != 0.0 || n as usize > MAX_ITERATIONS {
+            return Err(CalculatorError::InvalidArguments(USAGE));
+        }
+
+        let mut sequence = vec![x0];
+        let mut x = x0;
+        for _ in 0..n as usize {
+            x = self.evaluate_expr(step, &[("x", x)])?;
+            sequence.push(x);
+        }
+
+        for (k, value) in sequence.iter().enumerate().skip(1) {
+            self.push_value(format!("x{} = {}", k, step.pretty()), format!("x{}", k), *value, self.input_origin);
+        }
+
+        self.push_history(self.input.clone(), Some(StackValue::Real(x)));
+        self.history_position = self.history.len();
+        self.last_iteration = Some(sequence);
+        Ok(())
+    }
+
+    /// Pushes a root of `args[0]` (an expression in x) found from the guess x0.
+    fn solve(&mut self, args: &[Expr]) -> Result<(), CalculatorError> {
+        let [expr, guess] = args else {
+            return Err(CalculatorError::InvalidArguments("solve(expr, x0)"));
+        };
+        let x0 = self.evaluate_expr(guess, &[])?;
+        // Surface unknown names and the like up front; later failures only mean f is undefined there
+        self.evaluate_expr(expr, &[("x", x0)])?;
+        let root = numeric::solve(|x| self.evaluate_expr(expr, &[("x", x)]).unwrap_or(f64::NAN), x0).ok_or(CalculatorError::NoRoot { x0 })?;
+
+        self.push_value(self.input.clone(), self.input.clone(), root.x, self.input_origin);
+        self.push_history(self.input.clone(), Some(StackValue::Real(root.x)));
+        self.history_position = self.history.len();
+        self.notice = Some(format!(
+            "x = {} by {} after {} iterations, |f(x)| = {:.1e}",
+            self.format_real(root.x),
+            root.method.label(),
+            root.iterations,
+            root.residual
+        ));
+        Ok(())
+    }
+
+    /// roots(a, b, c) or roots(a, b, c, d): pushes every real root, and the
+    /// complex ones too with `:set complex on`.
+    fn polynomial_roots(&mut self, args: &[Expr]) -> Result<(), CalculatorError> {
+        const USAGE: &str = "roots(a, b, c) or roots(a, b, c, d), highest power first";
+        if !(2..=4).contains(&args.len()) {
+            return Err(CalculatorError::InvalidArguments(USAGE));
+        }
+        let coefficients = args.iter().map(|arg| self.evaluate_expr(arg, &[])).collect::<Result<Vec<f64>, _>>()?;
+        let roots = polynomial::roots(&coefficients).ok_or(CalculatorError::InvalidArguments(USAGE))?;
+
+        let mut shown = Vec::new();
+        let mut skipped = 0;
+        for (real, imag) in roots {
+            let value = if imag == 0.0 { StackValue::Real(real) } else { StackValue::Complex(ComplexNumber::new(real, imag)) };
+            if imag != 0.0 && !self.complex_results {
+                skipped += 1;
+                continue;
+            }
+            let text = self.format_stack_value(&value);
+            self.push_result(text.clone(), text.clone(), value, self.input_origin);
+            shown.push(text);
+        }
+        let listed = if shown.is_empty() { "no real roots".to_string() } else { shown.join(", ") };
+        self.push_history(format!("{} = {}", self.input, listed), None);
+        self.history_position = self.history.len();
+        self.notice = Some(match skipped {
+            0 => format!("x = {}", listed),
+            _ => format!("x = {}, plus {} complex roots (:set complex on to push them)", listed, skipped),
+        });
+        Ok(())
+    }
+
+    /// integrate(expr, a, b) and derivative(expr, x0) over an expression in x,
+    /// pushing the value and showing the estimated error.
+    fn calculus(&mut self, name: &str, args: &[Expr]) -> Result<(), CalculatorError> {
+        let (operation, estimate) = match (name, args) {
+            ("integrate", [expr, a, b]) => {
+                let (a, b) = (self.evaluate_expr(a, &[])?, self.evaluate_expr(b, &[])?);
+                self.evaluate_expr(expr, &[("x", a)])?;
+                ("integrate", numeric::integrate(|x| self.evaluate_expr(expr, &[("x", x)]).unwrap_or(f64::NAN), a, b))
+            }
+            ("derivative", [expr, x0]) => {
+                let x0 = self.evaluate_expr(x0, &[])?;
+                self.evaluate_expr(expr, &[("x", x0)])?;
+                ("derivative", numeric::derivative(|x| self.evaluate_expr(expr, &[("x", x)]).unwrap_or(f64::NAN), x0))
+            }
+            ("integrate", _) => return Err(CalculatorError::InvalidArguments("integrate(expr, a, b)")),
+            _ => return Err(CalculatorError::InvalidArguments("derivative(expr, x0)")),
+        };
+        let estimate = estimate.ok_or(CalculatorError::NotFinite { operation })?;
+
+        self.push_value(self.input.clone(), self.input.clone(), estimate.value, self.input_origin);
+        self.push_history(self.input.clone(), Some(StackValue::Real(estimate.value)));
+        self.history_position = self.history.len();
+        self.notice = Some(format!("{} = {} ± {:.1e}", operation, self.format_real(estimate.value), estimate.error));
+        Ok(())
+    }
+
+    /// sum(expr, n, start, end) and prod(...): `expr` with `n` bound to each whole
+    /// number from start to end. An empty range is 0 for a sum and 1 for a product.
+    fn series(&self, name: &str, args: &[Expr], vars: &[(&str, f64)]) -> Result<f64, CalculatorError> {
+        let usage = functions::SERIES.iter().find(|(series, _)| *series == name).map_or("", |(_, usage)| usage);
+        let [expr, Expr::Variable(var), start, end] = args else {
+            return Err(CalculatorError::InvalidArguments(usage));
+        };
+        let (start, end) = (self.evaluate_expr(start, vars)?, self.evaluate_expr(end, vars)?);
+        if start.fract() != 0.0 || end.fract() != 0.0 {
+            return Err(CalculatorError::InvalidArguments(usage));
+        }
+        if end - start >= functions::MAX_SERIES_TERMS {
+            return Err(CalculatorError::Domain { function: if name == "sum" { "sum" } else { "prod" }, expected: "at most 1,000,000 terms" });
+        }
+        let mut bound = vars.to_vec();
+        bound.push((var.as_str(), start));
+        let mut total = if name == "sum" { 0.0 } else { 1.0 };
+        let mut n = start;
+        while n <= end {
+            bound.last_mut().expect("just pushed").1 = n;
+            let term = self.evaluate_expr(expr, &bound)?;
+            total = if name == "sum" { total + term } else { total * term };
+            n += 1.0;
+        }
+        Ok(total)
+    }
+
+    /// Opens the plot popup for `args[0]` (an expression in x) over [a, b].
+    fn open_plot(&mut self, args: &[Expr]) -> Result<(), CalculatorError> {
+        const USAGE: &str = "plot(expr, a, b) with a < b";
+        let [expr, a, b] = args else {
+            return Err(CalculatorError::InvalidArguments(USAGE));
+        };
+        let (x_min, x_max) = (self.evaluate_expr(a, &[])?, self.evaluate_expr(b, &[])?);
+        if !(x_min < x_max && x_min.is_finite() && x_max.is_finite()) {
+            return Err(CalculatorError::InvalidArguments(USAGE));
+        }
+        // Catch unknown names now rather than drawing an empty chart
+        if let Err(e) = self.evaluate_expr(expr, &[("x", x_min)])
+            && !matches!(e, CalculatorError::DivisionByZero { .. })
+        {
+            return Err(e);
+        }
+        self.function_plot = Some(FunctionPlot { expr: expr.clone(), label: expr.pretty(), x_min, x_max });
+        Ok(())
+    }
+
+    pub fn close_plot(&mut self) {
+        self.function_plot = None;
+    }
+
+    /// `count` points of the open plot; x values where f is undefined are left out.
+    pub fn plot_points(&self, count: usize) -> Vec<(f64, f64)> {
+        let Some(plot) = &self.function_plot else {
+            return Vec::new();
+        };
+        plot.sample_xs(count)
+            .filter_map(|x| self.evaluate_expr(&plot.expr, &[("x", x)]).ok().filter(|y| y.is_finite()).map(|y| (x, y)))
+            .collect()
+    }
+
+    fn factor_call(&mut self, args: &[Expr]) -> Result<(), CalculatorError> {
+        const USAGE: &str = "factor(n) with a whole number n ≥ 2";
+        let [arg] = args else {
+            return Err(CalculatorError::InvalidArguments(USAGE));
+        };
+        let n = functions::whole_u64(self.evaluate_expr(arg, &[])?).filter(|&n| n >= 2).ok_or(CalculatorError::InvalidArguments(USAGE))?;
+        self.push_factors(self.input.clone(), n, self.input_origin);
+        self.history_position = self.history.len();
+        Ok(())
+    }
+
+    /// :factor replaces the top of the stack with its prime factors.
+    pub fn factor_top(&mut self) {
+        self.undoable("factor", |calc| {
+            let Some(top) = calc.stack.last() else {
+                calc.error = Some(CalculatorError::StackUnderflow { operation: "factor".to_string(), needed: 1, available: 0 });
+                return;
+            };
+            let Some(n) = top.result.as_real().and_then(functions::whole_u64).filter(|&n| n >= 2) else {
+                calc.error = Some(CalculatorError::InvalidArguments("factor(n) with a whole number n ≥ 2"));
+                return;
+            };
+            let expression = format!("factor({})", top.expression);
+            calc.stack.pop();
+            calc.push_factors(expression, n, EntryOrigin::Operation);
+        });
+    }
+
+    /// Pushes the prime factors of `n` one per entry and shows the factorization.
+    fn push_factors(&mut self, expression: String, n: u64, origin: EntryOrigin) {
+        let factors = primes::factor(n);
+        for factor in &factors {
+            self.push_value(factor.to_string(), factor.to_string(), *factor as f64, origin);
+        }
+        let factorization = primes::format_factors(&factors);
+        self.push_history(format!("{} = {}", expression, factorization), None);
+        self.notice = Some(format!("{} = {}", n, factorization));
+    }
+
+    /// Pushes a real value whose raw expression and display label differ.
+    fn push_value(&mut self, expression: String, label: String, value: f64, origin: EntryOrigin) {
+        self.push_result(expression, label, StackValue::Real(value), origin);
+    }
+
+    fn push_result(&mut self, expression: String, label: String, value: StackValue, origin: EntryOrigin) {
+        let entry = self.new_entry(expression, Expr::number(value.as_complex().real, label), value, origin);
+        self.stack.push(entry);
+    }
+
+    fn push_history(&mut self, expression: String, result: Option<StackValue>) {
+        if let Some(value) = result.as_ref().and_then(StackValue::as_real).filter(|value| value.is_finite()) {
+            self.grand_total += value;
+        }
+        self.push_history_line(expression, result);
+    }
+
+    /// Adds a history line without counting its result towards the grand total.
+    fn push_history_line(&mut self, expression: String, result: Option<StackValue>) {
+        let created_ms = u64::try_from(backup::now_ms()).unwrap_or(u64::MAX);
+        self.history.push(HistoryEntry { created_ms, session_ms: self.session_ms, ..HistoryEntry::new(expression, result) });
+        if self.workspace == MAIN_WORKSPACE {
+            self.history_appended += 1;
+        }
+        self.history_dropped += trim_history(&mut self.history, self.history_limit);
+    }
+
+    /// Notes what a conversion converted (°C → °F) on the history line just added.
+    fn label_conversion(&mut self, function: &str) {
+        if let Some(label) = functions::conversion_label(function)
+            && let Some(entry) = self.history.last_mut()
+        {
+            entry.comment = Some(label.to_string());
+        }
+    }
+
+    /// History line as displayed, formatted with the current base and precision.
+    pub fn format_history_entry(&self, entry: &HistoryEntry) -> String {
+        match (&entry.result, &self.history_template) {
+            (Some(result), Some(template)) => self.render_template(template, &entry.expression, &self.format_stack_value(result), entry.comment.as_deref().unwrap_or("")),
+            (Some(result), None) => match &entry.comment {
+                Some(comment) => format!("{} = {}  # {}", entry.expression, self.format_stack_value(result), comment),
+                None => format!("{} = {}", entry.expression, self.format_stack_value(result)),
+            },
+            (None, _) => entry.expression.clone(),
+        }
+    }
+
+    fn render_template(&self, template: &Template, expr: &str, result: &str, label: &str) -> String {
+        template.render(&Fields { expr, result, base: self.base_mode.label(), angle: self.angle_mode.label(), label, id: "", origin: "", time: "" })
+    }
+
+    /// Pasted text: tables open the column picker, anything else lands in the input line.
+    /// Bracketed paste: the text arrives whole, so none of it is taken for a key.
+    /// The expression editor keeps the line breaks; a one-line input gets spaces for them.
+    pub fn handle_paste(&mut self, text: &str) {
+        if let Some(editor) = &mut self.editor {
+            editor.insert_str(text);
+            return;
+        }
+        let mut line: String = text.trim_end_matches(['\r', '\n']).chars().map(|ch| if ch == '\n' || ch == '\t' { ' ' } else { ch }).filter(|ch| !ch.is_control()).collect();
+        // An amount copied from a web page or a bill, "$1,234.56", as the number it is rather than a table row
+        let amount = if self.base_mode == BaseMode::Decimal { locale::clean_pasted_number(&line, self.paste_decimal, self.decimal_separator) } else { None };
+        if let Some(number) = &amount
+            && number != line.trim()
+        {
+            self.notice = Some(format!("Pasted {} as {}", line.trim(), number));
+            line.clone_from(number);
+        }
+        if let Some(mini_input) = &mut self.mini_input {
+            mini_input.push_str(&line);
+            return;
+        }
+        // A single line that reads as an expression is typed, whatever its tabs or commas
+        let expression = !text.trim().contains('\n') && self.parse_uncached(line.trim()).is_ok();
+        if amount.is_none()
+            && !expression
+            && let Some(import) = TableImport::detect(text)
+        {
+            self.table_import = Some(import);
+            return;
+        }
+        self.completion = None;
+        self.insert_str(&line);
+        self.error = None;
+    }
+
+    pub fn cancel_table_import(&mut self) {
+        self.table_import = None;
+    }
+
+    /// Pushes the selected columns, either cell by cell or as sum/mean/min/max.
+    pub fn import_table(&mut self) {
+        let Some(import) = self.table_import.take() else {
+            return;
+        };
+        let columns: Vec<usize> = (0..import.columns()).filter(|&column| import.selected[column]).collect();
+        if columns.is_empty() {
+            self.error = Some(CalculatorError::NoColumnsSelected);
+            self.table_import = Some(import);
+            return;
+        }
+
+        // However many values a paste pushes, one undo removes them all
+        self.undo.begin("import", &self.stack, self.grand_total);
+        let mut pushed = 0;
+        for &column in &columns {
+            let name = import.column_name(column);
+            if import.summary {
+                if let Some(summary) = import.summarize(column) {
+                    for (stat, value) in [("sum", summary.sum), ("mean", summary.mean), ("min", summary.min), ("max", summary.max)] {
+                        let label = format!("{}({})", stat, name);
+                        self.push_value(label.clone(), label, value, self.input_origin);
+                        pushed += 1;
+                    }
+                }
+            } else {
+                for (row, value) in import.values(column).into_iter().enumerate() {
+                    let label = format!("{}[{}]", name, row + 1);
+                    self.push_value(label.clone(), label, value, self.input_origin);
+                    pushed += 1;
+                }
+            }
+        }
+
+        self.undo.commit(&self.stack);
+
+        let kind = if import.summary { "summary values" } else { "values" };
+        self.push_history(format!("import {} column(s): {} {}", columns.len(), pushed, kind), None);
+        self.history_position = self.history.len();
+        self.error = None;
+    }
+
+    pub fn open_mini_calculator(&mut self) {
+        self.mini_input = Some(String::new());
+    }
+
+    pub fn close_mini_calculator(&mut self) {
+        self.mini_input = None;
+    }
+
+    /// Live result of the side calculation, evaluated as infix.
+    pub fn mini_result(&self) -> Option<Result<f64, CalculatorError>> {
+        let input = self.mini_input.as_deref()?.trim();
+        if input.is_empty() {
+            return None;
+        }
+        Some(self.evaluate(input))
+    }
+
+    /// Inserts the side result into the main input at the cursor and closes the popup.
+    pub fn insert_mini_result(&mut self) {
+        if let Some(Ok(value)) = self.mini_result() {
+            // Full precision in decimal; other bases use their usual notation
+            let text = match self.base_mode {
+                BaseMode::Decimal => self.decimal_separator.localize(&value.to_string()),
+                _ => self.format_real(value),
+            };
+            self.insert_str(&text);
+            self.mini_input = None;
+            self.error = None;
+        }
+    }
+
+    /// Opens the multi-line editor, starting from the current input.
+    pub fn open_editor(&mut self) {
+        self.editor = Some(ExpressionEditor::new(&self.input));
+        self.error = None;
+    }
+
+    pub fn close_editor(&mut self) {
+        self.editor = None;
+    }
+
+    /// Evaluates the editor's expression as infix, whatever the mode, and pushes
+    /// the result. On an error the editor stays open so the formula can be fixed.
+    pub fn submit_editor(&mut self) {
+        let Some(editor) = &self.editor else {
+            return;
+        };
+        let source = editor.source();
+        if source.is_empty() {
+            self.editor = None;
+            return;
+        }
+        // The editor started from the input line, so that is used up on success
+        if self.evaluate_source("editor", source) {
+            self.editor = None;
+            self.set_input("");
+        }
+    }
+
+    fn parse_current_input_to_stack_entry(&mut self) -> Result<(), CalculatorError> {
+        if self.input.is_empty() {
+            return Err(CalculatorError::InvalidExpression); // Or a more specific error
+        }
+
+        if self.input.starts_with('!') {
+            let value = self.recall(self.input.trim())?;
+            let text = self.format_stack_value(&value);
+            self.push_result(text.clone(), text, value, EntryOrigin::History);
+            self.set_input("");
+            return Ok(());
+        }
+
+        let stack_value = self.parse_input()?; // Re-use existing parse_input
+        let ast = match &stack_value {
+            StackValue::Vector(items) => Expr::Vector(items.iter().map(|x| Expr::number(*x, x.to_string())).collect()),
+            value => Expr::number(value.as_real().unwrap_or(f64::NAN), self.decimal_separator.canonical(self.input.trim())),
+        };
+        let new_entry = self.new_entry(self.input.clone(), ast, stack_value, self.input_origin);
+        self.stack.push(new_entry.clone()); // Clone new_entry before moving it
+
+        // Log the pushed number to history
+        self.push_history(new_entry.expression.to_string(), None); // new_entry is still available here
+        
+        self.set_input("");
+        Ok(())
+    }
+
+    fn apply_rpn_operator(&mut self, op_char: char) {
+        match op_char {
+            '+' => self.add(),
+            '-' => self.subtract(),
+            '*' => self.multiply(),
+            '/' => self.divide(),
+            '^' => self.power(),
+            _ => self.error = Some(CalculatorError::UnknownOperator(op_char)),
+        }
+    }
+
+    /// Splits infix input into tokens, each with the byte range it came from.
+    fn tokenize(&self, input: &str) -> Result<Vec<(Token, Range<usize>)>, CalculatorError> {
+        let comma = self.decimal_separator == DecimalSeparator::Comma;
+        let python = self.python_syntax;
+        let polar = self.polar_input();
+        let length = input.chars().count();
+        if length > self.limits.max_length {
+            return Err(CalculatorError::InputTooLong { length, limit: self.limits.max_length });
+        }
+        let mut tokens = Vec::new();
+        let mut chars = input.char_indices().peekable();
+        // Consumes characters while `accept` holds and returns the end offset
+        let take_while = |chars: &mut std::iter::Peekable<std::str::CharIndices>, accept: &dyn Fn(char) -> bool| {
+            while let Some(&(_, ch)) = chars.peek() {
+                if !accept(ch) {
+                    break;
+                }
+                chars.next();
+            }
+            chars.peek().map_or(input.len(), |&(i, _)| i)
+        };
+
+        let mut negative_angle = false; // A '-' just after ∠, for the number that follows
+        while let Some(&(start, ch)) = chars.peek() {
+            let after_angle = matches!(tokens.last(), Some((Token::Operator('∠'), _)));
+            match ch {
+                ' ' => {
+                    chars.next();
+                }
+                // 0x/0b/0o literals, so other bases can be written without leaving the mode
+                '0' if let Some((_, radix)) = radix_prefix(&input[start..]) => {
+                    chars.nth(1);
+                    let end = take_while(&mut chars, &|c| c.is_digit(radix) || (python && c == '_'));
+                    let digits = python_digits(&input[start + 2..end]).unwrap_or_default();
+                    let num = i64::from_str_radix(&digits, radix).map_err(|_| CalculatorError::UnexpectedToken {
+                        offset: start,
+                        token: input[start..end].to_string(),
+                    })?;
+                    tokens.push((Token::Number(num as f64), start..end));
+                }
+                // With a decimal comma a number can start with one, and '.' only groups thousands
+                '0'..='9' | '.' | ',' if ch != ',' || comma => {
+                    // 45°30'15": degrees, minutes, seconds
+                    let rest = if comma { input[start..].replace(',', ".") } else { input[start..].to_string() }; // Same length, so offsets still fit
+                    if let Some((degrees, length)) = dms::parse_prefix(&rest) {
+                        while chars.next_if(|&(i, _)| i < start + length).is_some() {}
+                        // An angle marked in degrees, as polar display writes it, is converted to radians when that's the mode
+                        let degrees = if after_angle && self.angle_mode == AngleMode::Radians { degrees.to_radians() } else { degrees };
+                        let degrees = if std::mem::take(&mut negative_angle) { -degrees } else { degrees };
+                        tokens.push((Token::Number(degrees), start..start + length));
+                        continue;
+                    }
+                    let end = take_while(&mut chars, &|c| c.is_ascii_digit() || c == '.' || (comma && c == ',') || (python && c == '_'));
+                    let text = &input[start..end];
+                    let digits = python_digits(text);
+                    let num = match digits.as_deref() {
+                        Some(text) if comma => locale::parse_comma_number(text),
+                        Some(text) => text.parse::<f64>().ok(),
+                        None => None,
+                    };
+                    let mut num = num.ok_or_else(|| CalculatorError::UnexpectedToken { offset: start, token: text.to_string() })?;
+                    let mut end = end;
+                    // 4.7k: a prefix letter straight after the digits, with no name going on from it
+                    if self.si_prefixes
+                        && self.base_mode == BaseMode::Decimal
+                        && let Some(prefix) = input[end..].chars().next()
+                        && let Some(exponent) = si_exponent(prefix)
+                        && !input[end + prefix.len_utf8()..].starts_with(|c: char| c.is_alphanumeric() || c == '_' || c == '(')
+                    {
+                        chars.next();
+                        num = scale_by_si_prefix(num, exponent);
+                        end += prefix.len_utf8();
+                    }
+                    // Likewise an angle marked in radians
+                    if after_angle && input[end..].starts_with("rad") && !input[end + 3..].starts_with(|c: char| c.is_alphanumeric() || c == '_' || c == '(') {
+                        chars.nth(2);
+                        end += 3;
+                        if self.angle_mode == AngleMode::Degrees {
+                            num = num.to_degrees();
+                        }
+                    }
+                    if std::mem::take(&mut negative_angle) {
+                        num = -num;
+                    }
+                    tokens.push((Token::Number(num), start..end));
+                    // 4i: an imaginary literal, read as 4 * i so "1 - 2i" and "2i^2" group as written
+                    if input[end..].starts_with('i') && !input[end + 1..].starts_with(|c: char| c.is_alphanumeric() || c == '_' || c == '(') {
+                        chars.next();
+                        tokens.push((Token::Operator('*'), end..end));
+                        tokens.push((Token::Identifier("i".to_string()), end..end + 1));
+                    }
+                }
+                // ± can also be typed as +/-
+                '+' if input[start..].starts_with("+/-") => {
+                    chars.nth(2);
+                    tokens.push((Token::Operator('±'), start..start + 3));
+                }
+                // 5∠30, or 5<30 while complex results show in polar form: a magnitude and an angle
+                '∠' | '<' if ch == '∠' || (polar && !input[start + 1..].starts_with('=')) => {
+                    chars.next();
+                    tokens.push((Token::Operator('∠'), start..start + ch.len_utf8()));
+                }
+                // The angle can be negative, as polar display writes 5 ∠ -30°
+                '-' if after_angle && input[start + 1..].starts_with(|c: char| c.is_ascii_digit() || c == '.' || (comma && c == ',')) => {
+                    chars.next();
+                    negative_angle = true;
+                }
+                // Python's ** for powers and // for floor division
+                '*' | '/' if python && input[start + 1..].starts_with(ch) => {
+                    chars.nth(1);
+                    tokens.push((Token::Operator(if ch == '*' { '^' } else { expr::FLOOR_DIVIDE }), start..start + 2));
+                }
+                '+' | '-' | '*' | '/' | '^' | '±' => {
+                    chars.next();
+                    tokens.push((Token::Operator(ch), start..start + ch.len_utf8()));
+                }
+                '<' | '>' | '=' | '!' => {
+                    chars.next();
+                    let op = match (ch, chars.next_if(|&(_, next)| next == '=').is_some()) {
+                        ('<', false) | ('>', false) => ch,
+                        ('<', true) => '≤',
+                        ('>', true) => '≥',
+                        ('=', true) => '=',
+                        ('!', true) => '≠',
+                        _ => return Err(CalculatorError::UnexpectedToken { offset: start, token: ch.to_string() }),
+                    };
+                    let end = chars.peek().map_or(input.len(), |&(i, _)| i);
+                    tokens.push((Token::Operator(op), start..end));
+                }
+                // [1, 2, 3] is read as a call to "[", so its elements are counted like
+                // arguments; straight after a value, v[2] indexes
+                '[' => {
+                    chars.next();
+                    if matches!(tokens.last(), Some((Token::Number(_) | Token::Identifier(_) | Token::RightParen, _))) {
+                        tokens.push((Token::Operator('['), start..start));
+                    } else {
+                        tokens.push((Token::Function { name: "[".to_string(), arity: 0 }, start..start));
+                    }
+                    tokens.push((Token::LeftParen, start..start + 1));
+                }
+                ']' => {
+                    chars.next();
+                    tokens.push((Token::RightParen, start..start + 1));
+                }
+                '(' | ')' | ',' | ';' if ch != ';' || comma => {
+                    chars.next();
+                    let token = match ch {
+                        '(' => Token::LeftParen,
+                        ')' => Token::RightParen,
+                        _ => Token::Comma,
+                    };
+                    tokens.push((token, start..start + 1));
+                }
+                'a'..='z' | 'A'..='Z' | '_' => {
+                    let end = take_while(&mut chars, &|c| c.is_ascii_alphanumeric() || c == '_');
+                    let name = input[start..end].to_string();
+                    // A name directly followed by '(' is a call, anything else a variable
+                    if input[end..].trim_start().starts_with('(') {
+                        tokens.push((Token::Function { name, arity: 0 }, start..end));
+                    } else {
+                        tokens.push((Token::Identifier(name), start..end));
+                    }
+                }
+                _ => {
+                    return Err(CalculatorError::UnexpectedToken { offset: start, token: ch.to_string() });
+                }
+            }
+        }
+
+        let limits = self.limits;
+        if let Some((_, span)) = tokens.get(limits.max_tokens) {
+            return Err(CalculatorError::TooManyTokens { offset: span.start, limit: limits.max_tokens });
+        }
+        let mut depth = 0usize;
+        for (token, span) in &tokens {
+            match token {
+                Token::LeftParen if depth == limits.max_depth => return Err(CalculatorError::NestingTooDeep { offset: span.start, limit: limits.max_depth }),
+                Token::LeftParen => depth += 1,
+                Token::RightParen => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+        Ok(tokens)
+    }
+
+    /// Rejects token sequences that cannot form an expression, pointing at the
+    /// first offending token. The shunting-yard pass assumes this has run.
+    fn check_syntax(&self, tokens: &[(Token, Range<usize>)], input: &str) -> Result<(), CalculatorError> {
+        let unexpected = |span: &Range<usize>| CalculatorError::UnexpectedToken { offset: span.start, token: input[span.clone()].to_string() };
+        let mut expect_operand = true;
+        // Open parentheses, and whether each one belongs to a function call
+        let mut open: Vec<(usize, bool)> = Vec::new();
+        let mut previous: Option<&Token> = None;
+
+        for (token, span) in tokens {
+            match token {
+                Token::Number(_) | Token::Identifier(_) | Token::Function { .. } | Token::LeftParen if !expect_operand => {
+                    return Err(unexpected(span));
+                }
+                Token::Number(_) | Token::Identifier(_) => expect_operand = false,
+                Token::Function { .. } => {}
+                Token::LeftParen => open.push((span.start, matches!(previous, Some(Token::Function { .. })))),
+                Token::RightParen => {
+                    let Some((_, is_call)) = open.pop() else {
+                        return Err(CalculatorError::UnmatchedParenthesis { offset: span.start });
+                    };
+                    // "f()" is an empty call, "()" or "2+)" are not
+                    if expect_operand && !(is_call && matches!(previous, Some(Token::LeftParen))) {
+                        return Err(unexpected(span));
+                    }
+                    expect_operand = false;
+                }
+                Token::Comma if expect_operand || !open.last().is_some_and(|(_, is_call)| *is_call) => {
+                    return Err(unexpected(span));
+                }
+                Token::Operator(_) if expect_operand => return Err(unexpected(span)),
+                Token::Comma | Token::Operator(_) => expect_operand = true,
+            }
+            previous = Some(token);
+        }
+
+        if let Some((offset, _)) = open.last() {
+            return Err(CalculatorError::UnmatchedParenthesis { offset: *offset });
+        }
+        if expect_operand && !tokens.is_empty() {
+            return Err(CalculatorError::UnexpectedEnd { offset: input.trim_end().len() });
+        }
+        Ok(())
+    }
+
+    fn parse_expression(&self, input: &str) -> Result<Expr, CalculatorError> {
+        self.parse_cache.get_or_parse(input, self.decimal_separator, || self.parse_uncached(input))
+    }
+
+    fn parse_uncached(&self, input: &str) -> Result<Expr, CalculatorError> {
+        let tokens = self.tokenize(input)?;
+        self.check_syntax(&tokens, input)?;
+        let postfix = self.infix_to_postfix(tokens.into_iter().map(|(token, _)| token).collect())?;
+        Expr::from_postfix(postfix)
+    }
+
+    fn is_right_associative(&self, op: char) -> bool {
+        op == '^'
+    }
+
+    fn infix_to_postfix(&self, tokens: Vec<Token>) -> Result<Vec<Token>, CalculatorError> {
+        let mut output = Vec::new();
+        let mut operators = Vec::new();
+        let mut arg_counts: Vec<usize> = Vec::new(); // One entry per open function call
+        let mut after_left_paren = false;
+
+        for token in tokens {
+            let is_left_paren = matches!(token, Token::LeftParen);
+            match token {
+                Token::Number(_) | Token::Identifier(_) => output.push(token),
+                Token::Function { .. } => operators.push(token),
+                Token::Comma => {
+                    loop {
+                        match operators.last() {
+                            Some(Token::LeftParen) => break,
+                            Some(_) => output.push(operators.pop().unwrap()),
+                            None => return Err(CalculatorError::MismatchedParentheses),
+                        }
+                    }
+                    match arg_counts.last_mut() {
+                        Some(count) => *count += 1,
+                        None => return Err(CalculatorError::InvalidExpression),
+                    }
+                }
+                Token::Operator(op) => {
+                    while let Some(Token::Operator(top_op)) = operators.last() {
+                        let top_precedence = expr::precedence(*top_op);
+                        let curr_precedence = expr::precedence(op);
+                        
+                        if top_precedence > curr_precedence ||
+                           (top_precedence == curr_precedence && !self.is_right_associative(op)) {
+                            output.push(operators.pop().unwrap());
+                        } else {
+                            break;
+                        }
+                    }
+                    operators.push(token);
+                }
+                Token::LeftParen => {
+                    if matches!(operators.last(), Some(Token::Function { .. })) {
+                        arg_counts.push(1);
+                    }
+                    operators.push(token);
+                }
+                Token::RightParen => {
+                    loop {
+                        match operators.pop() {
+                            Some(Token::LeftParen) => break,
+                            Some(op) => output.push(op),
+                            None => return Err(CalculatorError::MismatchedParentheses),
+                        }
+                    }
+                    if let Some(Token::Function { name, .. }) = operators.last() {
+                        let name = name.clone();
+                        operators.pop();
+                        let count = arg_counts.pop().unwrap_or(0);
+                        // "f()" has no arguments even though its count starts at one
+                        let arity = if after_left_paren { 0 } else { count };
+                        output.push(Token::Function { name, arity });
+                    }
+                }
+            }
+            after_left_paren = is_left_paren;
+        }
+
+        while let Some(op) = operators.pop() {
+            match op {
+                Token::LeftParen | Token::RightParen | Token::Function { .. } => {
+                    return Err(CalculatorError::MismatchedParentheses);
+                }
+                _ => output.push(op),
+            }
+        }
+
+        Ok(output)
+    }
+
+    /// The infix result: a vector when the expression has one, otherwise
+    /// real, or complex with `:set complex on`, or a measurement with `:set uncertainty on`.
+    fn evaluate_value(&self, ast: &Expr) -> Result<StackValue, CalculatorError> {
+        if self.mentions_vector(ast) {
+            self.evaluate_vector(ast)
+        } else if self.uncertainty_mode {
+            self.evaluate_number::<Measurement>(ast).map(Number::into_value)
+        } else if self.complex_results {
+            self.evaluate_number::<ComplexNumber>(ast).map(Number::into_value)
+        } else {
+            self.evaluate_expr(ast, &[]).map(StackValue::Real)
+        }
+    }
+
+    /// Whether `expr` has a vector in it: a literal, an index, dot() or norm(), or a stack reference to one.
+    fn mentions_vector(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::Vector(_) | Expr::Binary { op: '[', .. } => true,
+            Expr::Binary { lhs, rhs, .. } => self.mentions_vector(lhs) || self.mentions_vector(rhs),
+            Expr::Call { name, args } => functions::VECTOR.iter().any(|(function, _)| function == name) || args.iter().any(|arg| self.mentions_vector(arg)),
+            Expr::Variable(name) => matches!(self.stack_reference(name), Some(Ok(StackValue::Vector(_)))),
+            Expr::Number { .. } => false,
+        }
+    }
+
+    /// Like `evaluate_expr` with vectors: operators work element by element,
+    /// v[i] indexes from 1, dot() and norm() give reals, and one-argument
+    /// functions apply to every element.
+    fn evaluate_vector(&self, expr: &Expr) -> Result<StackValue, CalculatorError> {
+        if !self.mentions_vector(expr) {
+            return self.evaluate_expr(expr, &[]).map(StackValue::Real);
+        }
+        let vector = |arg: &Expr, usage: &'static str| match self.evaluate_vector(arg)? {
+            StackValue::Vector(items) => Ok(items),
+            _ => Err(CalculatorError::InvalidArguments(usage)),
+        };
+        match expr {
+            Expr::Vector(elements) => elements
+                .iter()
+                .map(|element| self.evaluate_vector(element)?.as_real().ok_or(CalculatorError::InvalidArguments("numbers as vector elements")))
+                .collect::<Result<_, _>>()
+                .map(StackValue::Vector),
+            Expr::Binary { op: '[', lhs, rhs } => vector::index(&vector(lhs, "a vector before [index]")?, self.evaluate_expr(rhs, &[])?).map(StackValue::Real),
+            Expr::Binary { op, lhs, rhs } => match (self.evaluate_vector(lhs)?, self.evaluate_vector(rhs)?) {
+                (StackValue::Real(a), StackValue::Real(b)) => self.evaluate_expr(&Expr::binary(*op, Expr::number(a, ""), Expr::number(b, "")), &[]).map(StackValue::Real),
+                (a, b) => vector::binary(*op, &a, &b),
+            },
+            Expr::Call { name, args } if name == "dot" => match args.as_slice() {
+                [u, v] => vector::dot(&vector(u, "dot(u, v)")?, &vector(v, "dot(u, v)")?).map(StackValue::Real),
+                _ => Err(CalculatorError::InvalidArguments("dot(u, v)")),
+            },
+            Expr::Call { name, args } if name == "norm" => match args.as_slice() {
+                [v] => Ok(StackValue::Real(vector::norm(&vector(v, "norm(v)")?))),
+                _ => Err(CalculatorError::InvalidArguments("norm(v)")),
+            },
+            Expr::Call { name, args } => match (functions::lookup(name), args.as_slice()) {
+                (Some(function), [arg]) if function.arity == 1 && name != "if" => match self.evaluate_vector(arg)? {
+                    StackValue::Vector(items) => self.map_function(function, &items).map(StackValue::Vector),
+                    value => {
+                        let x = value.as_real().ok_or_else(|| CalculatorError::ComplexUnsupported { operation: name.clone() })?;
+                        self.map_function(function, &[x]).map(|values| StackValue::Real(values[0]))
+                    }
+                },
+                _ => Err(CalculatorError::VectorUnsupported { operation: name.clone() }),
+            },
+            Expr::Variable(name) => self.named_value(name).unwrap_or_else(|| Err(CalculatorError::UnknownVariable(name.clone()))),
+            Expr::Number { value, .. } => Ok(StackValue::Real(*value)),
+        }
+    }
+
+    /// Like `evaluate_expr` over another `Number` type: complex numbers, so
+    /// sqrt(-1), ln(-2) and (-8)^(1/3) have principal values instead of NaN
+    /// and `i` is the imaginary unit, or measurements, whose uncertainties
+    /// operators and functions propagate. Comparisons, if() and poly() only
+    /// see reals.
+    fn evaluate_number<N: Number>(&self, expr: &Expr) -> Result<N, CalculatorError> {
+        match expr {
+            Expr::Binary { op, lhs, rhs } => {
+                let (a, b) = (self.evaluate_number::<N>(lhs)?, self.evaluate_number::<N>(rhs)?);
+                if *op == '/' && b.is_zero() {
+                    return Err(CalculatorError::DivisionByZero { dividend: a.real() });
+                }
+                if *op == '∠'
+                    && let Some(z) = N::from_polar(a.real(), self.angle_to_radians(b.real()))
+                {
+                    return Ok(z);
+                }
+                match N::binary(*op, a, b) {
+                    Some(result) => Ok(result),
+                    None => self.evaluate_expr(expr, &[]).map(N::from_real),
+                }
+            }
+            Expr::Call { name, args } if name != "if" && let Some(function) = functions::lookup(name) => {
+                let args = args.iter().map(|arg| self.evaluate_number::<N>(arg)).collect::<Result<Vec<_>, _>>()?;
+                if args.len() != function.arity {
+                    return Err(CalculatorError::InvalidArguments(function.usage));
+                }
+                N::call(function, &args, self.eval_context())
+            }
+            Expr::Variable(name) if let Some(value) = self.named_value(name) => {
+                N::from_value(&value?).ok_or_else(|| CalculatorError::ComplexUnsupported { operation: name.clone() })
+            }
+            Expr::Variable(name) if name == "i" && let Some(i) = N::imaginary_unit() => Ok(i),
+            _ => self.evaluate_expr(expr, &[]).map(N::from_real),
+        }
+    }
+
+    /// A variable bound with --define, or else a stack reference.
+    fn named_value(&self, name: &str) -> Option<Result<StackValue, CalculatorError>> {
+        match self.defines.iter().find(|(defined, _)| defined == name) {
+            Some((_, value)) => Some(Ok(StackValue::Real(*value))),
+            None => self.stack_reference(name),
+        }
+    }
+
+    /// `s1`, `s2`, ...: the stack entries from the top, as they are when the
+    /// expression is evaluated. None for names that aren't stack references.
+    fn stack_reference(&self, name: &str) -> Option<Result<StackValue, CalculatorError>> {
+        let depth: usize = name.strip_prefix('s').filter(|digits| !digits.starts_with('0')).and_then(|digits| digits.parse().ok())?;
+        Some(match self.stack.from_top(depth - 1) {
+            Some(entry) => Ok(entry.result.clone()),
+            None => Err(CalculatorError::StackUnderflow { operation: name.to_string(), needed: depth, available: self.stack.len() }),
+        })
+    }
+
+    /// Evaluates `expr` with `vars` bound as variables (e.g. `x` inside iterate).
+    fn evaluate_expr(&self, expr: &Expr, vars: &[(&str, f64)]) -> Result<f64, CalculatorError> {
+        match expr {
+            Expr::Number { value, .. } => Ok(*value),
+            Expr::Variable(name) => match vars.iter().find(|(var, _)| var == name).map(|(_, value)| *value).or_else(|| functions::constant(name)) {
+                Some(value) => Ok(value),
+                None => match self.named_value(name) {
+                    Some(value) => match value? {
+                        StackValue::Vector(_) => Err(CalculatorError::InvalidArguments("numbers, not vectors")),
+                        value => value.as_real().ok_or_else(|| CalculatorError::ComplexUnsupported { operation: name.clone() }),
+                    },
+                    None if name == "i" => Err(CalculatorError::ImaginaryUnitOff),
+                    None => Err(CalculatorError::UnknownVariable(name.clone())),
+                },
+            },
+            Expr::Call { name, args } if name == "poly" => {
+                let values = args.iter().map(|arg| self.evaluate_expr(arg, vars)).collect::<Result<Vec<f64>, _>>()?;
+                match values.split_last() {
+                    Some((x, coefficients)) if !coefficients.is_empty() => Ok(polynomial::evaluate(coefficients, *x)),
+                    _ => Err(CalculatorError::InvalidArguments("poly(a_n, ..., a_0, x)")),
+                }
+            }
+            Expr::Call { name, args } if name == "sum" || name == "prod" => self.series(name, args, vars),
+            // Vectors are evaluated by evaluate_vector; here, inside sum() or plot(), they have no meaning
+            Expr::Vector(_) => Err(CalculatorError::InvalidArguments("numbers, not vectors")),
+            // Only the chosen branch is evaluated, so if(x == 0, 0, 1 / x) works at 0
+            Expr::Call { name, args } if name == "if" && args.len() == 3 => {
+                let branch = if self.evaluate_expr(&args[0], vars)? != 0.0 { &args[1] } else { &args[2] };
+                self.evaluate_expr(branch, vars)
+            }
+            Expr::Call { name, args } => {
+                let function = functions::lookup(name).ok_or_else(|| CalculatorError::UnknownFunction(name.clone()))?;
+                if args.len() != function.arity {
+                    return Err(CalculatorError::InvalidArguments(function.usage));
+                }
+                // Arguments go in a fixed array: a call is evaluated on every preview, so it shouldn't allocate
+                let mut buffer = [0.0; functions::MAX_ARITY];
+                let values = &mut buffer[..args.len()];
+                for (value, arg) in values.iter_mut().zip(args) {
+                    *value = self.evaluate_expr(arg, vars)?;
+                }
+                function.check_domain(values)?;
+                Ok(function.call(values, self.eval_context()))
+            }
+            Expr::Binary { op, lhs, rhs } => {
+                let a = self.evaluate_expr(lhs, vars)?;
+                let b = self.evaluate_expr(rhs, vars)?;
+                match op {
+                    '/' if b == 0.0 => Err(CalculatorError::DivisionByZero { dividend: a }),
+                    '+' | '-' | '*' | '/' | '^' => Number::binary(*op, a, b).ok_or(CalculatorError::UnknownOperator(*op)),
+                    // Plain arithmetic only sees the value; evaluate_number keeps the ±
+                    '±' if self.uncertainty_mode => Ok(a),
+                    '±' => Err(CalculatorError::UncertaintyOff),
+                    '∠' => Err(CalculatorError::PolarOff),
+                    // Comparisons give 1 for true and 0 for false
+                    '=' => Ok(f64::from(u8::from(a == b))),
+                    '≠' => Ok(f64::from(u8::from(a != b))),
+                    '<' => Ok(f64::from(u8::from(a < b))),
+                    '≤' => Ok(f64::from(u8::from(a <= b))),
+                    '>' => Ok(f64::from(u8::from(a > b))),
+                    '≥' => Ok(f64::from(u8::from(a >= b))),
+                    _ => Err(CalculatorError::UnknownOperator(*op)),
+                }
+            }
+        }
+    }
+
+    fn parse_input(&self) -> Result<StackValue, CalculatorError> {
+        let input = self.input.trim();
+        if input.starts_with('[') {
+            let number = |text: &str| match self.decimal_separator {
+                DecimalSeparator::Point => text.parse::<f64>().ok(),
+                DecimalSeparator::Comma => text.strip_prefix('-').map_or_else(|| locale::parse_comma_number(text), |digits| locale::parse_comma_number(digits).map(|x| -x)),
+            };
+            return vector::parse(input, self.decimal_separator.list_separator(), number)
+                .map(StackValue::Vector)
+                .ok_or_else(|| CalculatorError::InvalidNumber { input: input.to_string() });
+        }
+        
+        // A prefix names the base in any mode; the mode is only the default for
+        // unprefixed input. In HEX mode 0b... that isn't binary is read as hex digits.
+        if let Some((digits, radix)) = radix_prefix(input) {
+            let value = self.parse_integer(digits, radix);
+            if value.is_ok() || !(self.base_mode == BaseMode::Hexadecimal && radix == 2) {
+                return value;
+            }
+        }
+        match self.base_mode {
+            BaseMode::Decimal => {
+                let number = |text: &str| match self.decimal_separator {
+                    DecimalSeparator::Point => text.parse::<f64>().ok(),
+                    DecimalSeparator::Comma => {
+                        // -1,5e-3: the sign and exponent around the grouped digits
+                        let (mantissa, exponent) = text.split_once(['e', 'E']).unwrap_or((text, "0"));
+                        let magnitude = locale::parse_comma_number(mantissa.strip_prefix('-').unwrap_or(mantissa))?;
+                        let sign = if mantissa.starts_with('-') { "-" } else { "" };
+                        format!("{}{}e{}", sign, magnitude, exponent.parse::<i32>().ok()?).parse().ok()
+                    }
+                };
+                let num = number(input).or_else(|| {
+                    let prefix = input.chars().last().filter(|_| self.si_prefixes)?;
+                    Some(scale_by_si_prefix(number(&input[..input.len() - prefix.len_utf8()])?, si_exponent(prefix)?))
+                });
+                num.or_else(|| dms::parse(&self.decimal_separator.canonical(input)))
+                    .map(StackValue::Real)
+                    .ok_or_else(|| CalculatorError::InvalidNumber { input: input.to_string() })
+            }
+            BaseMode::Hexadecimal => self.parse_integer(input, 16),
+            BaseMode::Binary => self.parse_integer(input, 2),
+        }
+    }
+
+    fn parse_integer(&self, digits: &str, radix: u32) -> Result<StackValue, CalculatorError> {
+        if self.word_size == WordSize::Unbounded {
+            return i64::from_str_radix(digits, radix)
+                .map(|num| StackValue::Real(num as f64))
+                .map_err(|_| CalculatorError::InvalidDigits { digits: digits.to_string(), radix });
+        }
+        // With a fixed word size the digits are a bit pattern, so 0xFF in 8-bit is -1
+        u64::from_str_radix(digits, radix)
+            .map(|pattern| StackValue::Real(self.word_size.sign_extend(pattern) as f64))
+            .map_err(|_| CalculatorError::InvalidDigits { digits: digits.to_string(), radix })
+    }
+
+    // Integer in the current base, honoring the two's-complement word size
+    fn format_integer(&self, value: i64, prefix: &str, radix: u32) -> String {
+        let digits = |pattern: u64| match radix {
+            2 => format!("{:b}", pattern),
+            _ => format!("{:X}", pattern),
+        };
+        match self.word_size.bits() {
+            Some(bits) => {
+                let width = if radix == 2 { bits as usize } else { bits as usize / 4 };
+                format!("{}{:0>width$}", prefix, digits(self.word_size.to_pattern(value)), width = width)
+            }
+            None if value < 0 => format!("-{}{}", prefix, digits(value.unsigned_abs())),
+            None => format!("{}{}", prefix, digits(value as u64)),
+        }
+    }
+
+    pub fn format_stack_value(&self, value: &StackValue) -> String {
+        match value {
+            StackValue::Real(r) => self.format_real(*r),
+            StackValue::Complex(c) => self.format_complex(c),
+            StackValue::Uncertain(m) if self.base_mode == BaseMode::Decimal && self.precision.is_none() => self.decimal_separator.localize(&uncertainty::format(*m)),
+            StackValue::Uncertain(m) => format!("{} ± {}", self.format_real(m.value), self.format_real(m.sigma)),
+            StackValue::Vector(items) => vector::format(items, self.decimal_separator.list_separator(), |x| self.format_real(x)),
+        }
+    }
+
+    /// As shown in a stack row: a long vector is cut short, and inspect lists all of it.
+    pub fn format_stack_row(&self, value: &StackValue) -> String {
+        match value {
+            StackValue::Vector(items) => vector::format_compact(items, self.decimal_separator.list_separator(), |x| self.format_real(x)),
+            value => self.format_stack_value(value),
+        }
+    }
+
+    pub fn format_real(&self, value: f64) -> String {
+        let text = match self.base_mode {
+            BaseMode::Decimal => {
+                if self.dms_display && self.angle_mode == AngleMode::Degrees && value.is_finite() {
+                    dms::format(value, self.precision)
+                } else if self.abbreviation_mode && value.abs() >= 1e6 {
+                    format!("{:.*e}", self.precision.unwrap_or(3), value)
+                } else if let Some(digits) = self.precision {
+                    format!("{:.*}", digits, value)
+                } else {
+                    format!("{}", value)
+                }
+            }
+            BaseMode::Hexadecimal => {
+                if value.fract() == 0.0 && value.abs() <= i64::MAX as f64 {
+                    self.format_integer(value as i64, "0x", 16)
+                } else {
+                    format!("{} (hex: {})", value, self.format_integer(value as i64, "0x", 16))
+                }
+            }
+            BaseMode::Binary => {
+                if value.fract() == 0.0 && value.abs() <= i64::MAX as f64 {
+                    self.format_integer(value as i64, "0b", 2)
+                } else {
+                    format!("{} (bin: {})", value, self.format_integer(value as i64, "0b", 2))
+                }
+            }
+        };
+        self.decimal_separator.localize(&text)
+    }
+
+    fn format_complex(&self, c: &ComplexNumber) -> String {
+        match self.complex_mode {
+            ComplexMode::Rectangular => {
+                if c.imag >= 0.0 {
+                    format!("{} + {}i", self.format_real(c.real), self.format_real(c.imag))
+                } else {
+                    format!("{} - {}i", self.format_real(c.real), self.format_real(-c.imag))
+                }
+            }
+            ComplexMode::Polar => {
+                let mag = c.magnitude();
+                let phase = c.phase();
+                let phase_display = if self.angle_mode == AngleMode::Degrees {
+                    phase * 180.0 / PI
+                } else {
+                    phase
+                };
+                let unit = if self.angle_mode == AngleMode::Degrees { "°" } else { "rad" };
+                format!("{} ∠ {}{}", self.format_real(mag), self.format_real(phase_display), unit)
+            }
+        }
+    }
+
+    // Arithmetic operations on stack
+    pub fn add(&mut self) {
+        self.undoable("add", |calc| calc.binary_operation('+'));
+    }
+
+    pub fn subtract(&mut self) {
+        self.undoable("subtract", |calc| calc.binary_operation('-'));
+    }
+
+    pub fn multiply(&mut self) {
+        self.undoable("multiply", |calc| calc.binary_operation('*'));
+    }
+
+    pub fn divide(&mut self) {
+        self.undoable("divide", |calc| calc.binary_operation('/'));
+    }
+
+    pub fn power(&mut self) {
+        self.undoable("power", |calc| calc.binary_operation('^'));
+    }
+
+    /// Pushes the result of `a op b` and records it in the history.
+    fn push_operation(&mut self, op_char: char, a: StackEntry, b: StackEntry, result_value: StackValue) {
+        let new_expression = format!("({} {} {})", a.expression, op_char, b.expression);
+        let new_ast = Expr::binary(op_char, Arc::unwrap_or_clone(a.ast), Arc::unwrap_or_clone(b.ast));
+
+        let entry = self.new_entry(new_expression.as_str(), new_ast, result_value.clone(), EntryOrigin::Operation);
+        self.stack.push(entry);
+
+        // Log the operation to history
+        self.push_history(new_expression, Some(result_value));
+    }
+
+    fn binary_operation(&mut self, op_char: char) {
+        // Pop b first
+        let b_opt = self.stack.pop();
+        // Pop a second
+        let a_opt = self.stack.pop();
+
+        match (a_opt, b_opt) {
+            (Some(a), Some(b)) => { // Both operands available
+                let result = match (&a.result, &b.result) {
+                    (StackValue::Vector(_), _) | (_, StackValue::Vector(_)) => vector::binary(op_char, &a.result, &b.result),
+                    (x, y) => self.binary_on_stack(op_char, x, y),
+                };
+                match result {
+                    Ok(result_value) => self.push_operation(op_char, a, b, result_value),
+                    Err(e) => {
+                        self.error = Some(e);
+                        self.stack.push(a);
+                        self.stack.push(b);
+                    }
+                }
+            }
+            (a_opt, b_opt) => {
+                // Stack underflow: push back any item that was popped
+                if let Some(a) = a_opt { self.stack.push(a); }
+                if let Some(b) = b_opt { self.stack.push(b); } // b was popped first, so push it back last
+                self.error = Some(CalculatorError::StackUnderflow { operation: op_char.to_string(), needed: 2, available: self.stack.len() });
+            }
+        }
+    }
+
+    /// `a op b` in the Number type the operands call for: measurements when either
+    /// is uncertain, complex numbers with `:set complex on` unless both are real
+    /// (a negative number to a fractional power isn't), else reals.
+    fn binary_on_stack(&self, op_char: char, a: &StackValue, b: &StackValue) -> Result<StackValue, CalculatorError> {
+        let complex_power = op_char == '^' && a.as_real().is_some_and(|x| x < 0.0) && b.as_real().is_some_and(|y| y.fract() != 0.0);
+        match (a, b) {
+            (StackValue::Uncertain(_), _) | (_, StackValue::Uncertain(_)) => Self::binary_number::<Measurement>(op_char, a, b),
+            (StackValue::Real(_), StackValue::Real(_)) if !(self.complex_results && complex_power) => Self::binary_number::<f64>(op_char, a, b),
+            _ if self.complex_results => Self::binary_number::<ComplexNumber>(op_char, a, b),
+            _ => Self::binary_number::<f64>(op_char, a, b),
+        }
+    }
+
+    fn binary_number<N: Number>(op_char: char, a: &StackValue, b: &StackValue) -> Result<StackValue, CalculatorError> {
+        let (Some(x), Some(y)) = (N::from_value(a), N::from_value(b)) else {
+            return Err(CalculatorError::ComplexUnsupported { operation: op_char.to_string() });
+        };
+        if op_char == '/' && y.is_zero() {
+            return Err(CalculatorError::DivisionByZero { dividend: x.real() });
+        }
+        N::binary(op_char, x, y).map(N::into_value).ok_or(CalculatorError::UnknownOperator(op_char))
+    }
+
+    /// Provisional result of the infix input, if it is already a complete expression.
+    /// Nothing is pushed or recorded; special forms like iterate(...) are skipped since they have side effects.
+    pub fn preview(&self) -> Option<String> {
+        if self.mode != CalculatorMode::Infix || self.input.trim().is_empty() || self.is_command_input() {
+            return None;
+        }
+        let (code, _) = split_comment(&self.input);
+        let input = match Self::balanced_input(code) {
+            Some(balanced) if self.close_on_enter => balanced,
+            _ => code.to_string(),
+        };
+        if let Some(outcome) = self.currency_conversion(&input) {
+            return outcome.ok().map(|value| self.format_real(value));
+        }
+        if let Some(outcome) = self.size_conversion(&input) {
+            return outcome.ok().map(|(value, unit)| format!("{} {}", self.format_real(value), unit));
+        }
+        let ast = self.parse_expression(&input).ok()?;
+        if matches!(&ast, Expr::Call { name, .. } if functions::SPECIAL_FORMS.iter().any(|(special, _)| special == name)) {
+            return None;
+        }
+        self.evaluate_value(&ast).ok().map(|value| self.format_stack_value(&value))
+    }
+
+    /// Checks the infix input's syntax without evaluating it; the parse is
+    /// cached, so this is cheap enough for every frame. None when there's no
+    /// expression to check.
+    pub fn input_validity(&self) -> Option<InputValidity> {
+        if self.mode != CalculatorMode::Infix || self.is_command_input() {
+            return None;
+        }
+        let (code, _) = split_comment(&self.input);
+        if code.trim().is_empty() {
+            return None;
+        }
+        if self.currency_conversion(code).is_some() || self.size_conversion(code).is_some() {
+            return Some(InputValidity::Valid);
+        }
+        Some(match self.parse_expression(code) {
+            Ok(_) => InputValidity::Valid,
+            Err(CalculatorError::UnexpectedEnd { .. }) => InputValidity::Incomplete,
+            Err(CalculatorError::UnmatchedParenthesis { offset }) if code[offset..].starts_with(['(', '[']) => InputValidity::Incomplete,
+            Err(_) => InputValidity::Invalid,
+        })
+    }
+
+    pub fn get_current_value(&self) -> Option<String> {
+        if !self.input.is_empty() {
+            Some(self.input.clone())
+        } else {
+            self.stack.last().map(|top| self.format_stack_value(&top.result))
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn get_mode_string(&self) -> String {
+        format!(
+            "Mode: {} | Angle: {} | Base: {} | Complex: {}",
+            match self.mode {
+                CalculatorMode::RPN => "RPN",
+                CalculatorMode::Infix => "INFIX",
+            },
+            self.angle_mode.label(),
+            self.base_mode.label(),
+            self.complex_mode.label(),
+        )
+    }
+
+    
+}
\ No newline at end of file
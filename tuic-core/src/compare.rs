@@ -0,0 +1,88 @@
+//! Before/after comparison of two stack entries (v, or `:compare`): the
+//! difference, ratio and percent change side by side, any of which can then
+//! be pushed.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    Difference,
+    Ratio,
+    PercentChange,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 3] = [Metric::Difference, Metric::Ratio, Metric::PercentChange];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Metric::Difference => "Difference",
+            Metric::Ratio => "Ratio",
+            Metric::PercentChange => "% change",
+        }
+    }
+
+    /// Of `after` against `before`; the percent change is relative to the size
+    /// of `before`, so a loss shrinking from -200 to -100 is +50%.
+    pub fn value(self, before: f64, after: f64) -> f64 {
+        match self {
+            Metric::Difference => after - before,
+            Metric::Ratio => after / before,
+            Metric::PercentChange => (after - before) / before.abs() * 100.0,
+        }
+    }
+
+    /// The calculation as an infix expression, for the history line.
+    pub fn expression(self, before: &str, after: &str) -> String {
+        match self {
+            Metric::Difference => format!("{} - {}", after, before),
+            Metric::Ratio => format!("{} / {}", after, before),
+            Metric::PercentChange => format!("({} - {}) / abs({}) * 100", after, before, before),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub before: f64,
+    pub after: f64,
+    pub before_level: usize, // Stack levels, 1 being the top
+    pub after_level: usize,
+    pub selected: usize, // Index into Metric::ALL
+}
+
+impl Comparison {
+    pub fn new(before: f64, before_level: usize, after: f64, after_level: usize) -> Self {
+        Self { before, after, before_level, after_level, selected: 0 }
+    }
+
+    pub fn metrics(&self) -> impl Iterator<Item = (Metric, f64)> + '_ {
+        Metric::ALL.into_iter().map(|metric| (metric, metric.value(self.before, self.after)))
+    }
+
+    pub fn move_selection(&mut self, down: bool) {
+        self.selected = if down { (self.selected + 1).min(Metric::ALL.len() - 1) } else { self.selected.saturating_sub(1) };
+    }
+
+    pub fn selected_metric(&self) -> Metric {
+        Metric::ALL[self.selected]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn before_and_after() {
+        let mut comparison = Comparison::new(120.0, 2, 150.0, 1);
+        assert_eq!(comparison.metrics().map(|(_, value)| value).collect::<Vec<_>>(), [30.0, 1.25, 25.0]);
+        assert_eq!(Metric::PercentChange.value(-200.0, -100.0), 50.0);
+        assert!(Metric::Ratio.value(0.0, 5.0).is_infinite());
+        comparison.move_selection(false);
+        assert_eq!(comparison.selected_metric(), Metric::Difference);
+        comparison.move_selection(true);
+        comparison.move_selection(true);
+        comparison.move_selection(true);
+        assert_eq!(comparison.selected_metric(), Metric::PercentChange);
+        assert_eq!(Metric::PercentChange.expression("120", "150"), "(150 - 120) / abs(120) * 100");
+    }
+}
@@ -0,0 +1,68 @@
+use crate::functions::{CONSTANTS, FUNCTIONS, SERIES, SPECIAL_FORMS, VARIADIC, VECTOR};
+use crate::usage::UsageStats;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CandidateKind {
+    Function,
+    Constant,
+}
+
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub name: &'static str,
+    pub kind: CandidateKind,
+    pub detail: String,
+}
+
+/// Names matching the word before the cursor, shown in the completion popup.
+#[derive(Debug, Clone)]
+pub struct Completion {
+    pub start: usize, // Char index where the completed word begins
+    pub candidates: Vec<Candidate>,
+    pub selected: usize,
+}
+
+impl Completion {
+    /// Candidates for the identifier ending at `cursor`, most used first.
+    pub fn new(input: &str, cursor: usize, usage: &UsageStats) -> Option<Self> {
+        let chars: Vec<char> = input.chars().collect();
+        let mut start = cursor;
+        while start > 0 && (chars[start - 1].is_ascii_alphanumeric() || chars[start - 1] == '_') {
+            start -= 1;
+        }
+        // Only identifiers complete, never the digits of a number
+        if start == cursor || chars[start].is_ascii_digit() {
+            return None;
+        }
+        let prefix: String = chars[start..cursor].iter().collect();
+
+        let functions = FUNCTIONS
+            .iter()
+            .map(|function| (function.name, function.usage))
+            .chain(SPECIAL_FORMS.iter().copied())
+            .chain(VARIADIC.iter().copied())
+            .chain(SERIES.iter().copied())
+            .chain(VECTOR.iter().copied())
+            .map(|(name, usage)| Candidate { name, kind: CandidateKind::Function, detail: usage.to_string() });
+        let constants = CONSTANTS
+            .iter()
+            .map(|(name, value)| Candidate { name, kind: CandidateKind::Constant, detail: format!("{:.6}", value) });
+        let mut candidates: Vec<Candidate> = functions.chain(constants).filter(|candidate| candidate.name.starts_with(&prefix)).collect();
+        if candidates.is_empty() {
+            return None;
+        }
+
+        let uses = |name: &str| usage.functions.iter().find(|(function, _)| function == name).map_or(0, |(_, count)| *count);
+        candidates.sort_by(|a, b| uses(b.name).cmp(&uses(a.name)).then_with(|| a.name.cmp(b.name)));
+        Some(Self { start, candidates, selected: 0 })
+    }
+
+    pub fn move_selection(&mut self, forward: bool) {
+        let count = self.candidates.len();
+        self.selected = if forward { (self.selected + 1) % count } else { (self.selected + count - 1) % count };
+    }
+
+    pub fn selected(&self) -> &Candidate {
+        &self.candidates[self.selected]
+    }
+}
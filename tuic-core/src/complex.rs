@@ -0,0 +1,138 @@
+//! Complex arithmetic for `:set complex on`. Multi-valued functions give their
+//! principal value: ln and powers use the argument in (−π, π], and sqrt has a
+//! non-negative real part, so sqrt(-4) is 2i and (-8)^(1/3) is 1 + 1.732i.
+
+use std::f64::consts::LN_10;
+use std::ops::{Add, Div, Mul, Sub};
+
+use crate::calculator::ComplexNumber;
+
+/// Integer powers up to this size multiply exactly instead of going through ln.
+const MAX_EXACT_POWER: f64 = 64.0;
+
+/// Functions with a complex version; the rest take real arguments only.
+pub const FUNCTIONS: &[&str] = &["sqrt", "exp", "ln", "log", "abs"];
+
+impl Add for ComplexNumber {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(self.real + other.real, self.imag + other.imag)
+    }
+}
+
+impl Sub for ComplexNumber {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.real - other.real, self.imag - other.imag)
+    }
+}
+
+impl Mul for ComplexNumber {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Self::new(self.real * other.real - self.imag * other.imag, self.real * other.imag + self.imag * other.real)
+    }
+}
+
+impl Div for ComplexNumber {
+    type Output = Self;
+
+    fn div(self, other: Self) -> Self {
+        let denominator = other.real * other.real + other.imag * other.imag;
+        Self::new(
+            (self.real * other.real + self.imag * other.imag) / denominator,
+            (self.imag * other.real - self.real * other.imag) / denominator,
+        )
+    }
+}
+
+impl ComplexNumber {
+    pub fn exp(self) -> Self {
+        Self::from_polar(self.real.exp(), self.imag)
+    }
+
+    /// Principal logarithm: ln|z| + i·arg(z).
+    pub fn ln(self) -> Self {
+        Self::new(self.magnitude().ln(), self.phase())
+    }
+
+    /// Principal square root, the one with a non-negative real part.
+    pub fn sqrt(self) -> Self {
+        let magnitude = self.magnitude();
+        let real = ((magnitude + self.real) / 2.0).sqrt();
+        let imag = ((magnitude - self.real) / 2.0).sqrt();
+        Self::new(real, if self.imag.is_sign_negative() { -imag } else { imag })
+    }
+
+    /// Principal power exp(w·ln z); whole exponents multiply so (1+i)^2 is exactly 2i.
+    pub fn pow(self, exponent: Self) -> Self {
+        if exponent.imag == 0.0 && exponent.real.fract() == 0.0 && exponent.real.abs() <= MAX_EXACT_POWER {
+            let mut result = Self::new(1.0, 0.0);
+            for _ in 0..exponent.real.abs() as u32 {
+                result = result * self;
+            }
+            return if exponent.real < 0.0 { Self::new(1.0, 0.0) / result } else { result };
+        }
+        if self.magnitude() == 0.0 {
+            return if exponent.real > 0.0 { Self::new(0.0, 0.0) } else { Self::new(f64::NAN, f64::NAN) };
+        }
+        (exponent * self.ln()).exp()
+    }
+}
+
+/// `a op b` for the infix and RPN operators; None for any other operator.
+pub fn binary(op: char, a: ComplexNumber, b: ComplexNumber) -> Option<ComplexNumber> {
+    match op {
+        '+' => Some(a + b),
+        '-' => Some(a - b),
+        '*' => Some(a * b),
+        '/' => Some(a / b),
+        '^' => Some(a.pow(b)),
+        _ => None,
+    }
+}
+
+/// One of `FUNCTIONS` applied to `z`.
+pub fn function(name: &str, z: ComplexNumber) -> Option<ComplexNumber> {
+    match name {
+        "sqrt" => Some(z.sqrt()),
+        "exp" => Some(z.exp()),
+        "ln" => Some(z.ln()),
+        "log" => Some(z.ln() / ComplexNumber::new(LN_10, 0.0)),
+        "abs" => Some(ComplexNumber::new(z.magnitude(), 0.0)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    fn close(a: ComplexNumber, real: f64, imag: f64) -> bool {
+        (a.real - real).abs() < 1e-12 && (a.imag - imag).abs() < 1e-12
+    }
+
+    #[test]
+    fn principal_branches() {
+        assert!(close(ComplexNumber::new(-4.0, 0.0).sqrt(), 0.0, 2.0));
+        assert!(close(ComplexNumber::new(-1.0, -0.0).sqrt(), 0.0, -1.0)); // Just below the branch cut
+        assert!(close(ComplexNumber::new(-1.0, 0.0).ln(), 0.0, PI));
+        assert!(close(ComplexNumber::new(-8.0, 0.0).pow(ComplexNumber::new(1.0 / 3.0, 0.0)), 1.0, 3f64.sqrt()));
+        assert!(close(function("log", ComplexNumber::new(-100.0, 0.0)).unwrap(), 2.0, PI / LN_10));
+    }
+
+    #[test]
+    fn arithmetic() {
+        let (a, b) = (ComplexNumber::new(1.0, 1.0), ComplexNumber::new(3.0, -4.0));
+        assert_eq!(a.pow(ComplexNumber::new(2.0, 0.0)), ComplexNumber::new(0.0, 2.0));
+        assert!(close(a * b, 7.0, -1.0));
+        assert!(close(a * b / b, 1.0, 1.0));
+        assert!(close(ComplexNumber::new(0.0, PI).exp(), -1.0, 0.0));
+        assert!(close(a.pow(ComplexNumber::new(-1.0, 0.0)), 0.5, -0.5));
+        assert_eq!(binary('<', a, b), None);
+    }
+}
@@ -0,0 +1,438 @@
+//! Startup defaults from `config.toml` in the config directory, with
+//! command-line overrides using the same keys (`--angle deg`).
+
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{Result, anyhow, bail};
+
+use crate::calculator::{AngleMode, BaseMode, CalculatorMode, config_dir};
+use crate::context::Rounding;
+use crate::functions;
+use crate::limits;
+use crate::locale::DecimalSeparator;
+use crate::template::Template;
+
+/// How much of the screen the panels take.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layout {
+    Full,
+    Compact, // No quick-help panel and a shorter history
+    Present, // For screen sharing: no mode or help panels, the top of the stack in block digits
+    Quick, // --quick, never from config.toml: only the input and status lines
+}
+
+/// Which set of key bindings the main screen uses.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Keymap {
+    Default,
+    Vi, // Modal: normal mode for the stack, insert mode for typing
+}
+
+/// Plain-text descriptions of each change, for following along with a screen reader.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScreenReader {
+    Off,
+    On, // Shown in a log panel
+    Print, // And printed to stdout on exit
+}
+
+/// Least severe events written to the log file; Off writes none.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug, // Every key, too
+    Trace,
+}
+
+/// When the session journal is written for crash recovery.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Autosave {
+    #[default]
+    Change, // After every change
+    Seconds(u64), // At most this often
+    Operations(u32), // Once this many changes have built up
+    Off, // Never; a crash loses the session
+}
+
+/// Stack entries printed to stdout on quitting, for `result=$(tui-calculator)`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum PrintOnExit {
+    #[default]
+    Off,
+    Top(usize), // The top N entries; "top" is 1
+    Stack, // All of them
+}
+
+/// How they are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum PrintFormat {
+    #[default]
+    Plain, // One formatted value per line, the top last
+    Json, // One object per line
+    Csv, // level,expression,result with a header row
+}
+
+/// Settings that aren't given keep the built-in defaults.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Config {
+    pub mode: Option<CalculatorMode>,
+    pub angle: Option<AngleMode>,
+    pub base: Option<BaseMode>,
+    pub precision: Option<Option<usize>>, // Some(None) is "auto"
+    pub theme: Option<String>,
+    pub layout: Option<Layout>,
+    pub history_size: Option<usize>, // limits::UNLIMITED for "unlimited"
+    pub stack_size: Option<usize>, // Likewise
+    pub max_input_length: Option<usize>, // Limits on infix input, see InputLimits
+    pub max_nesting: Option<usize>,
+    pub max_tokens: Option<usize>,
+    pub keys: Option<Keymap>,
+    pub share_history: Option<bool>, // History file shared by running instances
+    pub rounding: Option<Rounding>,
+    pub decimal: Option<DecimalSeparator>, // Left out, the front end follows the locale
+    pub paste_decimal: Option<Option<DecimalSeparator>>, // Of pasted amounts; Some(None) is "auto"
+    pub si_prefixes: Option<bool>, // 4.7k and 100n read as numbers
+    pub python_syntax: Option<bool>, // ** , // and 1_000 in infix
+    pub screen_reader: Option<ScreenReader>,
+    pub log_level: Option<LogLevel>,
+    pub update_check: Option<bool>, // Ask GitHub for a newer release at start
+    pub autosave: Option<Autosave>,
+    pub tax_rate: Option<f64>, // Percent, for the TAX+ and TAX- keys
+    pub tip_rate: Option<f64>, // Percent, for the TIP key
+    pub gnuplot_command: Option<String>, // Run by Ctrl+G, with {script} and {data} filled in
+    pub print_on_exit: Option<PrintOnExit>,
+    pub print_format: Option<PrintFormat>,
+    pub defines: Vec<(String, f64)>, // Variables for expressions, e.g. rate = 0.21; a later one wins
+    pub history_template: Option<Template>, // Output formats, e.g. "{expr} → {result} [{base}]"
+    pub copy_template: Option<Template>,
+    pub export_template: Option<Template>,
+}
+
+/// Environment variables named with this prefix are bound as variables: TUIC_DEFINE_rate=0.21.
+pub const DEFINE_PREFIX: &str = "TUIC_DEFINE_";
+
+pub const KEYS: &[&str] = &["mode", "angle", "base", "precision", "theme", "layout", "history-size", "stack-size", "max-input-length", "max-nesting", "max-tokens", "keys", "share-history", "rounding", "decimal", "paste-decimal", "si-prefixes", "python-syntax", "screen-reader", "log-level", "update-check", "autosave", "tax-rate", "tip-rate", "gnuplot-command", "print-on-exit", "print-format", "define", "history-template", "copy-template", "export-template"];
+
+impl Config {
+    /// Where `config.toml` is.
+    pub fn path() -> Result<PathBuf> {
+        Ok(config_dir()?.join("config.toml"))
+    }
+
+    /// Reads `config.toml`; a missing file is an empty config.
+    pub fn load() -> Result<Self> {
+        let path = Self::path()?;
+        match fs::read_to_string(&path) {
+            Ok(content) => Self::parse(&content).map_err(|e| anyhow!("{}: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn parse(content: &str) -> Result<Self> {
+        let table: toml::Table = content.parse()?;
+        let mut config = Self::default();
+        for (key, value) in &table {
+            // [define] is a table of names and numbers
+            if let ("define", toml::Value::Table(defines)) = (key.as_str(), value) {
+                for (name, value) in defines {
+                    match value {
+                        toml::Value::Integer(_) | toml::Value::Float(_) => config.set(key, &format!("{}={}", name, value))?,
+                        other => bail!("define.{} must be a number, not {}", name, other.type_str()),
+                    }
+                }
+                continue;
+            }
+            let value = match value {
+                toml::Value::String(text) => text.clone(),
+                toml::Value::Integer(number) => number.to_string(),
+                toml::Value::Float(number) => number.to_string(),
+                other => bail!("{} must be a string or number, not {}", key, other.type_str()),
+            };
+            config.set(key, &value)?;
+        }
+        Ok(config)
+    }
+
+    /// Binds the TUIC_DEFINE_<name> variables in `vars`, the process environment in practice.
+    pub fn apply_env(&mut self, vars: impl IntoIterator<Item = (String, String)>) -> Result<()> {
+        for (var, value) in vars {
+            if let Some(name) = var.strip_prefix(DEFINE_PREFIX) {
+                self.set("define", &format!("{}={}", name, value)).map_err(|e| anyhow!("{}: {}", var, e))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies `--key value` pairs from the command line over the file's settings.
+    pub fn apply_args(&mut self, args: &[String]) -> Result<()> {
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let Some(key) = arg.strip_prefix("--") else {
+                bail!("unexpected argument '{}'", arg);
+            };
+            let value = args.next().ok_or_else(|| anyhow!("--{} needs a value", key))?;
+            self.set(key, value)?;
+        }
+        Ok(())
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        let invalid = |expected: &str| anyhow!("invalid {} '{}', expected {}", key, value, expected);
+        match key {
+            "mode" => {
+                self.mode = Some(match value {
+                    "rpn" => CalculatorMode::RPN,
+                    "infix" => CalculatorMode::Infix,
+                    _ => return Err(invalid("rpn or infix")),
+                })
+            }
+            "angle" => {
+                self.angle = Some(match value {
+                    "rad" => AngleMode::Radians,
+                    "deg" => AngleMode::Degrees,
+                    _ => return Err(invalid("rad or deg")),
+                })
+            }
+            "base" => {
+                self.base = Some(match value {
+                    "dec" => BaseMode::Decimal,
+                    "hex" => BaseMode::Hexadecimal,
+                    "bin" => BaseMode::Binary,
+                    _ => return Err(invalid("dec, hex or bin")),
+                })
+            }
+            "precision" => {
+                self.precision = Some(match value {
+                    "auto" => None,
+                    digits => Some(digits.parse().ok().filter(|digits| *digits <= 20).ok_or_else(|| invalid("0-20 or auto"))?),
+                })
+            }
+            "theme" => self.theme = Some(value.to_string()),
+            "layout" => {
+                self.layout = Some(match value {
+                    "full" => Layout::Full,
+                    "compact" => Layout::Compact,
+                    "present" => Layout::Present,
+                    _ => return Err(invalid("full, compact or present")),
+                })
+            }
+            "keys" => {
+                self.keys = Some(match value {
+                    "default" => Keymap::Default,
+                    "vi" => Keymap::Vi,
+                    _ => return Err(invalid("default or vi")),
+                })
+            }
+            "share-history" => {
+                self.share_history = Some(match value {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err(invalid("on or off")),
+                })
+            }
+            "rounding" => {
+                self.rounding = Some(match value {
+                    "half-away" => Rounding::HalfAway,
+                    "half-even" => Rounding::HalfEven,
+                    _ => return Err(invalid("half-away or half-even")),
+                })
+            }
+            "decimal" => {
+                self.decimal = Some(match value {
+                    "point" => DecimalSeparator::Point,
+                    "comma" => DecimalSeparator::Comma,
+                    _ => return Err(invalid("point or comma")),
+                })
+            }
+            "paste-decimal" => {
+                self.paste_decimal = Some(match value {
+                    "auto" => None,
+                    "point" => Some(DecimalSeparator::Point),
+                    "comma" => Some(DecimalSeparator::Comma),
+                    _ => return Err(invalid("auto, point or comma")),
+                })
+            }
+            "si-prefixes" => {
+                self.si_prefixes = Some(match value {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err(invalid("on or off")),
+                })
+            }
+            "python-syntax" => {
+                self.python_syntax = Some(match value {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err(invalid("on or off")),
+                })
+            }
+            "update-check" => {
+                self.update_check = Some(match value {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err(invalid("on or off")),
+                })
+            }
+            "screen-reader" => {
+                self.screen_reader = Some(match value {
+                    "off" => ScreenReader::Off,
+                    "on" => ScreenReader::On,
+                    "print" => ScreenReader::Print,
+                    _ => return Err(invalid("off, on or print")),
+                })
+            }
+            "log-level" => {
+                self.log_level = Some(match value {
+                    "off" => LogLevel::Off,
+                    "error" => LogLevel::Error,
+                    "warn" => LogLevel::Warn,
+                    "info" => LogLevel::Info,
+                    "debug" => LogLevel::Debug,
+                    "trace" => LogLevel::Trace,
+                    _ => return Err(invalid("off, error, warn, info, debug or trace")),
+                })
+            }
+            "autosave" => {
+                let count = |digits: &str| digits.parse().ok().filter(|count| *count > 0);
+                self.autosave = Some(match value {
+                    "change" => Autosave::Change,
+                    "off" => Autosave::Off,
+                    _ => match (value.strip_suffix('s').and_then(count), value.strip_suffix("ops").and_then(count)) {
+                        (_, Some(operations)) => Autosave::Operations(operations as u32),
+                        (Some(seconds), _) => Autosave::Seconds(seconds),
+                        _ => return Err(invalid("change, off, <N>s or <K>ops")),
+                    },
+                })
+            }
+            "tax-rate" | "tip-rate" => {
+                let rate = value.trim_end_matches('%').parse().ok().filter(|rate: &f64| (0.0..=100.0).contains(rate)).ok_or_else(|| invalid("a percent from 0 to 100"))?;
+                match key {
+                    "tax-rate" => self.tax_rate = Some(rate),
+                    _ => self.tip_rate = Some(rate),
+                }
+            }
+            "print-on-exit" => {
+                self.print_on_exit = Some(match value {
+                    "off" => PrintOnExit::Off,
+                    "top" => PrintOnExit::Top(1),
+                    "stack" => PrintOnExit::Stack,
+                    levels => PrintOnExit::Top(levels.parse().ok().filter(|levels| *levels > 0).ok_or_else(|| invalid("off, top, stack or a number of entries"))?),
+                })
+            }
+            "print-format" => {
+                self.print_format = Some(match value {
+                    "plain" => PrintFormat::Plain,
+                    "json" => PrintFormat::Json,
+                    "csv" => PrintFormat::Csv,
+                    _ => return Err(invalid("plain, json or csv")),
+                })
+            }
+            "define" => {
+                let (name, number) = value.split_once('=').ok_or_else(|| invalid("name=number, e.g. rate=0.21"))?;
+                let (name, number) = (name.trim(), number.trim());
+                let identifier = name.starts_with(|ch: char| ch.is_ascii_alphabetic() || ch == '_') && name.chars().all(|ch| ch.is_ascii_alphanumeric() || ch == '_');
+                let stack_reference = name.strip_prefix('s').is_some_and(|digits| !digits.is_empty() && digits.chars().all(|ch| ch.is_ascii_digit()));
+                if !identifier || stack_reference || functions::lookup(name).is_some() || functions::constant(name).is_some() {
+                    return Err(invalid("a name of letters, digits and _ that isn't a function, constant or s1, s2, ..."));
+                }
+                let number: f64 = number.parse().ok().filter(|number: &f64| number.is_finite()).ok_or_else(|| invalid("name=number, e.g. rate=0.21"))?;
+                self.defines.retain(|(defined, _)| defined != name);
+                self.defines.push((name.to_string(), number));
+            }
+            "gnuplot-command" if value.trim().is_empty() => return Err(invalid("a command, e.g. \"gnuplot -persist {script}\"")),
+            "gnuplot-command" => self.gnuplot_command = Some(value.to_string()),
+            "history-template" | "copy-template" | "export-template" => {
+                let template = Template::parse(value).map_err(|e| anyhow!("invalid {} '{}': {}", key, value, e))?;
+                match key {
+                    "history-template" => self.history_template = Some(template),
+                    "copy-template" => self.copy_template = Some(template),
+                    _ => self.export_template = Some(template),
+                }
+            }
+            "history-size" => self.history_size = Some(limits::parse(value).ok_or_else(|| invalid("a positive number or \"unlimited\""))?),
+            "stack-size" => self.stack_size = Some(limits::parse(value).ok_or_else(|| invalid("a positive number or \"unlimited\""))?),
+            "max-input-length" | "max-nesting" | "max-tokens" => {
+                let limit = Some(value.parse().ok().filter(|limit| *limit > 0).ok_or_else(|| invalid("a positive number"))?);
+                match key {
+                    "max-input-length" => self.max_input_length = limit,
+                    "max-nesting" => self.max_nesting = limit,
+                    _ => self.max_tokens = limit,
+                }
+            }
+            _ => bail!("unknown setting '{}', expected one of {}", key, KEYS.join(", ")),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_file_format() {
+        let config = Config::parse("mode = \"infix\"\nangle = \"deg\"\nprecision = 4\nhistory-size = 200\nstack-size = \"unlimited\"\nkeys = \"vi\"\nshare-history = \"on\"\nrounding = \"half-even\"\ndecimal = \"comma\"\npaste-decimal = \"auto\"\nsi-prefixes = \"on\"\nscreen-reader = \"print\"\nlog-level = \"debug\"\nautosave = \"30s\"\ntax-rate = 8.25\ntip-rate = 15\n").unwrap();
+        assert_eq!(config.mode, Some(CalculatorMode::Infix));
+        assert_eq!(config.angle, Some(AngleMode::Degrees));
+        assert_eq!(config.precision, Some(Some(4)));
+        assert_eq!(config.history_size, Some(200));
+        assert_eq!(config.stack_size, Some(crate::stack::UNLIMITED));
+        assert_eq!(config.keys, Some(Keymap::Vi));
+        assert_eq!(config.share_history, Some(true));
+        assert_eq!(config.rounding, Some(Rounding::HalfEven));
+        assert_eq!(config.decimal, Some(DecimalSeparator::Comma));
+        assert_eq!(config.paste_decimal, Some(None));
+        assert_eq!(config.si_prefixes, Some(true));
+        assert_eq!(config.screen_reader, Some(ScreenReader::Print));
+        assert_eq!(config.log_level, Some(LogLevel::Debug));
+        assert_eq!(config.autosave, Some(Autosave::Seconds(30)));
+        assert_eq!(Config::parse("autosave = \"20ops\"").unwrap().autosave, Some(Autosave::Operations(20)));
+        assert!(Config::parse("autosave = \"0s\"").is_err());
+        assert_eq!((config.tax_rate, config.tip_rate), (Some(8.25), Some(15.0)));
+        assert_eq!(Config::parse("tip-rate = \"18%\"").unwrap().tip_rate, Some(18.0));
+        assert!(Config::parse("tax-rate = -5").is_err());
+        assert_eq!(Config::parse("gnuplot-command = \"gnuplot -p {script}\"").unwrap().gnuplot_command.as_deref(), Some("gnuplot -p {script}"));
+        assert!(Config::parse("gnuplot-command = \" \"").is_err());
+        assert_eq!(Config::parse("print-on-exit = 3").unwrap().print_on_exit, Some(PrintOnExit::Top(3)));
+        assert_eq!(Config::parse("print-on-exit = \"top\"\nprint-format = \"json\"").unwrap().print_format, Some(PrintFormat::Json));
+        assert!(Config::parse("print-on-exit = 0").is_err());
+        assert_eq!(Config::parse("max-nesting = 16").unwrap().max_nesting, Some(16));
+        assert!(Config::parse("max-tokens = 0").is_err());
+        assert_eq!(config.base, None);
+
+        assert!(Config::parse("precision = 99").is_err());
+        assert!(Config::parse("colour = \"red\"").is_err());
+        assert!(Config::parse("layout = true").is_err());
+        assert!(Config::parse("history-template = \"{expr} is {value}\"").is_err());
+    }
+
+    #[test]
+    fn defines_from_the_file_environment_and_flags() {
+        let mut config = Config::parse("[define]\nrate = 0.21\nyears = 10\n").unwrap();
+        assert_eq!(config.defines, [("rate".to_string(), 0.21), ("years".to_string(), 10.0)]);
+        config.apply_env([("TUIC_DEFINE_rate".to_string(), "0.25".to_string()), ("HOME".to_string(), "/root".to_string())]).unwrap();
+        config.apply_args(&["--define", "n_2 = 3"].map(String::from)).unwrap();
+        assert_eq!(config.defines, [("years".to_string(), 10.0), ("rate".to_string(), 0.25), ("n_2".to_string(), 3.0)]);
+        for bad in ["rate", "rate=x", "2x=1", "pi=3", "sqrt=1", "s2=1", "r-1=2"] {
+            assert!(config.set("define", bad).is_err(), "{}", bad);
+        }
+        assert!(config.apply_env([("TUIC_DEFINE_rate".to_string(), "lots".to_string())]).is_err());
+        assert!(Config::parse("[define]\nrate = \"high\"").is_err());
+    }
+
+    #[test]
+    fn arguments_override_the_file() {
+        let mut config = Config::parse("angle = \"deg\"\nlayout = \"compact\"").unwrap();
+        let args = ["--angle", "rad", "--precision", "auto"].map(String::from);
+        config.apply_args(&args).unwrap();
+        assert_eq!(config.angle, Some(AngleMode::Radians));
+        assert_eq!(config.precision, Some(None));
+        assert_eq!(config.layout, Some(Layout::Compact));
+        assert!(config.apply_args(&["--base".to_string()]).is_err());
+    }
+}
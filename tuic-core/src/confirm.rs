@@ -0,0 +1,16 @@
+//! Destructive actions wait for a yes or no, so a single key can't throw away
+//! a long session. The command that asks has already checked its arguments;
+//! the front end shows the prompt and passes the answer back.
+
+use crate::backup::Backup;
+use crate::export::ExportFormat;
+
+/// What runs when the prompt is answered with yes.
+#[derive(Debug, Clone)]
+pub enum Confirmation {
+    ClearAll, // Input, stack and unpinned history
+    DropLevels { low: usize, high: usize }, // Stack levels, 1 being the top; only ranges ask
+    DeleteWorkspace(String),
+    RestoreBackup(Backup), // Replaces the file it was taken from
+    OverwriteExport { format: ExportFormat, path: String }, // The file exists
+}
@@ -0,0 +1,62 @@
+//! Modes that change what an expression means, as opposed to how its result
+//! is shown: in DEG mode sin(90) is 1. The evaluator hands them to functions
+//! together, so a new such mode is one more field here.
+
+use crate::calculator::AngleMode;
+
+/// How `round` and `rnd` break ties.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Rounding {
+    #[default]
+    HalfAway, // 2.5 → 3, -2.5 → -3
+    HalfEven, // Banker's rounding: 2.5 → 2, 3.5 → 4, so sums of rounded amounts don't drift
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EvalContext {
+    pub angle: AngleMode, // Unit of trigonometric arguments and of inverse trigonometric results
+    pub rounding: Rounding,
+}
+
+impl EvalContext {
+    pub fn to_radians(self, angle: f64) -> f64 {
+        match self.angle {
+            AngleMode::Radians => angle,
+            AngleMode::Degrees => angle.to_radians(),
+        }
+    }
+
+    pub fn from_radians(self, radians: f64) -> f64 {
+        match self.angle {
+            AngleMode::Radians => radians,
+            AngleMode::Degrees => radians.to_degrees(),
+        }
+    }
+
+    /// Quarter turns in `angle` when it is a whole number of them, in DEG mode,
+    /// so sin(180) can be exactly 0 rather than π's rounding error.
+    pub fn quarter_turns(self, angle: f64) -> Option<i64> {
+        (self.angle == AngleMode::Degrees && (angle / 90.0).fract() == 0.0 && angle.abs() < 1e15).then(|| (angle / 90.0) as i64)
+    }
+}
+
+impl Rounding {
+    /// Rounds `x` to `places` decimals (tens, hundreds, ... when negative). A
+    /// value within rounding error of a tie counts as one, so 2.675, stored as
+    /// 2.67499999..., still rounds to 2.68.
+    pub fn round(self, x: f64, places: i32) -> f64 {
+        if !x.is_finite() || !(-15..=15).contains(&places) {
+            return if x.is_finite() { f64::NAN } else { x };
+        }
+        let scale = 10f64.powi(places.abs());
+        let scaled = if places >= 0 { x * scale } else { x / scale };
+        let tie = (scaled.fract().abs() - 0.5).abs() <= scaled.abs() * f64::EPSILON * 4.0;
+        let rounded = match (tie, self) {
+            (false, _) => scaled.round(),
+            (true, Rounding::HalfAway) => scaled.trunc() + scaled.signum(),
+            (true, Rounding::HalfEven) if scaled.trunc() % 2.0 == 0.0 => scaled.trunc(),
+            (true, Rounding::HalfEven) => scaled.trunc() + scaled.signum(),
+        };
+        if places >= 0 { rounded / scale } else { rounded * scale }
+    }
+}
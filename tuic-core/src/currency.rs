@@ -0,0 +1,132 @@
+//! Currency conversion for `100 USD -> EUR`. Rates come from a public API
+//! (fetched by the front end), are cached in `rates.json`, and can be set by
+//! hand with `:rate` when offline.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+
+/// Where the front end fetches rates from: daily reference rates, no API key.
+pub const RATES_URL: &str = "https://api.frankfurter.app/latest";
+
+/// Exchange rates quoted against one base currency.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rates {
+    pub base: String,
+    pub date: Option<String>, // Day of the fetched rates; None until the first fetch
+    pub rates: BTreeMap<String, f64>, // Units of each currency per one `base`
+    #[serde(default)]
+    pub manual: BTreeSet<String>, // Codes whose rate was entered with :rate
+}
+
+impl Default for Rates {
+    fn default() -> Self {
+        Self { base: "EUR".to_string(), date: None, rates: BTreeMap::new(), manual: BTreeSet::new() }
+    }
+}
+
+#[derive(Deserialize)]
+struct ApiResponse {
+    base: String,
+    date: String,
+    rates: BTreeMap<String, f64>,
+}
+
+impl Rates {
+    /// Units of `code` per one base currency.
+    pub fn rate(&self, code: &str) -> Option<f64> {
+        if code == self.base { Some(1.0) } else { self.rates.get(code).copied() }
+    }
+
+    /// Converts `amount` of `from` into `to`; `Err` names the currency without a rate.
+    pub fn convert(&self, amount: f64, from: &str, to: &str) -> Result<f64, String> {
+        let from_rate = self.rate(from).ok_or_else(|| from.to_string())?;
+        let to_rate = self.rate(to).ok_or_else(|| to.to_string())?;
+        Ok(amount / from_rate * to_rate)
+    }
+
+    /// Sets a rate by hand: one base currency is `rate` units of `code`.
+    pub fn set_manual(&mut self, code: &str, rate: f64) {
+        self.rates.insert(code.to_string(), rate);
+        self.manual.insert(code.to_string());
+    }
+
+    /// Takes fetched rates (the API's JSON). Fresh rates replace manual ones;
+    /// manual rates for currencies the API doesn't list are kept.
+    pub fn apply_fetched(&mut self, json: &str) -> Result<usize> {
+        let response: ApiResponse = serde_json::from_str(json)?;
+        if response.rates.is_empty() {
+            bail!("the response has no rates");
+        }
+        let kept: BTreeMap<String, f64> = self
+            .manual
+            .iter()
+            .filter(|code| !response.rates.contains_key(*code) && **code != response.base)
+            .filter_map(|code| self.rates.get(code).map(|rate| (code.clone(), *rate)))
+            .collect();
+        let count = response.rates.len();
+        self.manual.retain(|code| kept.contains_key(code));
+        self.base = response.base;
+        self.date = Some(response.date);
+        self.rates = response.rates;
+        self.rates.extend(kept);
+        Ok(count)
+    }
+}
+
+/// An ISO 4217-style code: three letters, returned in upper case.
+pub fn currency_code(word: &str) -> Option<String> {
+    (word.len() == 3 && word.chars().all(|ch| ch.is_ascii_alphabetic())).then(|| word.to_ascii_uppercase())
+}
+
+/// Splits `100 USD -> EUR` into the amount expression and the two codes.
+pub fn parse_conversion(input: &str) -> Option<(&str, String, String)> {
+    let (left, right) = input.split_once("->")?;
+    let to = currency_code(right.trim())?;
+    let left = left.trim_end();
+    let (amount, from) = left.rsplit_once(char::is_whitespace)?;
+    let amount = amount.trim();
+    if amount.is_empty() {
+        return None;
+    }
+    Some((amount, currency_code(from)?, to))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_conversions() {
+        assert_eq!(parse_conversion("100 USD -> EUR"), Some(("100", "USD".to_string(), "EUR".to_string())));
+        assert_eq!(parse_conversion(" 2*(3+4)  gbp->chf"), Some(("2*(3+4)", "GBP".to_string(), "CHF".to_string())));
+        assert_eq!(parse_conversion("USD -> EUR"), None);
+        assert_eq!(parse_conversion("100 DOLLARS -> EUR"), None);
+        assert_eq!(parse_conversion("100 USD"), None);
+    }
+
+    #[test]
+    fn converts_through_the_base() {
+        let mut rates = Rates::default();
+        rates.set_manual("USD", 1.25);
+        rates.set_manual("GBP", 0.5);
+        assert_eq!(rates.convert(10.0, "EUR", "USD"), Ok(12.5));
+        assert_eq!(rates.convert(12.5, "USD", "GBP"), Ok(5.0));
+        assert_eq!(rates.convert(1.0, "USD", "JPY"), Err("JPY".to_string()));
+    }
+
+    #[test]
+    fn fetched_rates_replace_manual_ones() {
+        let mut rates = Rates::default();
+        rates.set_manual("USD", 2.0);
+        rates.set_manual("XAU", 0.0004);
+        let count = rates.apply_fetched(r#"{"amount":1.0,"base":"EUR","date":"2026-10-16","rates":{"USD":1.08,"JPY":161.2}}"#).unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(rates.rate("USD"), Some(1.08));
+        assert_eq!(rates.rate("XAU"), Some(0.0004));
+        assert_eq!(rates.manual, BTreeSet::from(["XAU".to_string()]));
+        assert_eq!(rates.date.as_deref(), Some("2026-10-16"));
+        assert!(rates.apply_fetched(r#"{"message":"not found"}"#).is_err());
+    }
+}
@@ -0,0 +1,99 @@
+//! Data sizes for capacity math: `4 * 512 MiB`, `1.5 GiB in MB`. A unit after a
+//! number scales it to bytes; `in <unit>` picks the unit of the result.
+
+/// Which unit family an unconverted result is shown in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SizeUnits {
+    Si,  // Powers of 1000: kB, MB, GB
+    Iec, // Powers of 1024: KiB, MiB, GiB
+}
+
+const SI: &[(&str, f64)] = &[("B", 1.0), ("kB", 1e3), ("MB", 1e6), ("GB", 1e9), ("TB", 1e12), ("PB", 1e15)];
+const IEC: &[(&str, f64)] = &[
+    ("B", 1.0),
+    ("KiB", 1024.0),
+    ("MiB", 1048576.0),
+    ("GiB", 1073741824.0),
+    ("TiB", 1099511627776.0),
+    ("PiB", 1125899906842624.0),
+];
+
+/// Bytes per `name`. "KB" is accepted as the common spelling of kB.
+pub fn unit_factor(name: &str) -> Option<f64> {
+    let name = if name == "KB" { "kB" } else { name };
+    SI.iter().chain(IEC).find(|(unit, _)| *unit == name).map(|(_, factor)| *factor)
+}
+
+/// The largest unit of the family that `bytes` is at least one of.
+pub fn auto_unit(bytes: f64, units: SizeUnits) -> (&'static str, f64) {
+    let table = match units {
+        SizeUnits::Si => SI,
+        SizeUnits::Iec => IEC,
+    };
+    table.iter().rev().find(|(_, factor)| bytes.abs() >= *factor).copied().unwrap_or(table[0])
+}
+
+/// A size expression rewritten into plain arithmetic in bytes.
+#[derive(Debug, PartialEq)]
+pub struct SizeExpr {
+    pub bytes_expression: String,
+    pub target: Option<String>, // From `in <unit>`, not necessarily a known unit
+}
+
+/// Rewrites `input` when it uses size units or converts to one with `in <unit>`; None otherwise.
+pub fn parse(input: &str) -> Option<SizeExpr> {
+    let (expression, target) = match input.rsplit_once(" in ") {
+        Some((expression, unit)) => (expression, Some(unit.trim().to_string())),
+        None => (input, None),
+    };
+
+    let mut rewritten = String::new();
+    let mut found_unit = false;
+    let mut rest = expression;
+    while let Some(start) = rest.find(|ch: char| ch.is_ascii_alphabetic()) {
+        let word_len = rest[start..].find(|ch: char| !ch.is_ascii_alphanumeric() && ch != '_').unwrap_or(rest.len() - start);
+        let word = &rest[start..start + word_len];
+        let before = rest[..start].trim_end();
+        // Only a unit right after a number or a closing parenthesis; elsewhere it's a name
+        let after_operand = before.ends_with(|ch: char| ch.is_ascii_digit() || ch == ')' || ch == '.');
+        match unit_factor(word).filter(|_| after_operand) {
+            Some(factor) => {
+                rewritten.push_str(before);
+                rewritten.push_str(&format!("*{}", factor));
+                found_unit = true;
+            }
+            None => rewritten.push_str(&rest[..start + word_len]),
+        }
+        rest = &rest[start + word_len..];
+    }
+    rewritten.push_str(rest);
+
+    let known_target = target.as_deref().and_then(unit_factor).is_some();
+    (found_unit || known_target).then(|| SizeExpr { bytes_expression: rewritten.trim().to_string(), target })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrites_units_after_numbers() {
+        let size = parse("4 * 512 MiB").unwrap();
+        assert_eq!(size.bytes_expression, "4 * 512*1048576");
+        assert_eq!(size.target, None);
+        assert_eq!(parse("1.5 GiB in MB").unwrap(), SizeExpr { bytes_expression: "1.5*1073741824".to_string(), target: Some("MB".to_string()) });
+        assert_eq!(parse("(1+1)KB").unwrap().bytes_expression, "(1+1)*1000");
+        assert_eq!(parse("sqrt(16)"), None);
+        assert_eq!(parse("2 * B"), None); // Not after a number
+        assert_eq!(parse("3 in parsecs"), None);
+        assert_eq!(parse("3 GB in parsecs").unwrap().target.as_deref(), Some("parsecs"));
+    }
+
+    #[test]
+    fn picks_a_readable_unit() {
+        assert_eq!(auto_unit(2147483648.0, SizeUnits::Iec), ("GiB", 1073741824.0));
+        assert_eq!(auto_unit(2147483648.0, SizeUnits::Si), ("GB", 1e9));
+        assert_eq!(auto_unit(12.0, SizeUnits::Si), ("B", 1.0));
+        assert_eq!(auto_unit(0.5, SizeUnits::Iec), ("B", 1.0));
+    }
+}
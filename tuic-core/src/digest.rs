@@ -0,0 +1,109 @@
+//! Checksums and hashes for `:hash sha256 some text`, or of the top of the
+//! stack's integer bytes, for quick verification without leaving the calculator.
+
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HashKind {
+    Crc32,
+    Md5,
+    Sha1,
+    Sha256,
+}
+
+impl HashKind {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "crc32" => Some(HashKind::Crc32),
+            "md5" => Some(HashKind::Md5),
+            "sha1" => Some(HashKind::Sha1),
+            "sha256" => Some(HashKind::Sha256),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HashKind::Crc32 => "crc32",
+            HashKind::Md5 => "md5",
+            HashKind::Sha1 => "sha1",
+            HashKind::Sha256 => "sha256",
+        }
+    }
+
+    /// The digest of `data`, most significant byte first.
+    pub fn digest(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            HashKind::Crc32 => crc32fast::hash(data).to_be_bytes().to_vec(),
+            HashKind::Md5 => Md5::digest(data).to_vec(),
+            HashKind::Sha1 => Sha1::digest(data).to_vec(),
+            HashKind::Sha256 => Sha256::digest(data).to_vec(),
+        }
+    }
+}
+
+/// Big-endian bytes of an integer: the whole word when a word size is set,
+/// otherwise just enough bytes for its value (at least one).
+pub fn integer_bytes(pattern: u64, word_bits: Option<u32>) -> Vec<u8> {
+    let bytes = pattern.to_be_bytes();
+    let len = match word_bits {
+        Some(bits) => bits as usize / 8,
+        None => (8 - pattern.leading_zeros() as usize / 8).max(1),
+    };
+    bytes[8 - len..].to_vec()
+}
+
+/// Digest bytes as one number in `radix` (2, 10 or 16). Hex and binary keep
+/// leading zeros, so hex reads like `sha256sum` output.
+pub fn format_digest(bytes: &[u8], radix: u32) -> String {
+    match radix {
+        16 => bytes.iter().map(|byte| format!("{:02x}", byte)).collect(),
+        2 => bytes.iter().map(|byte| format!("{:08b}", byte)).collect::<Vec<_>>().join(" "),
+        _ => {
+            // Long division of the big-endian number by 10, one decimal digit at a time
+            let mut number = bytes.to_vec();
+            let mut digits = Vec::new();
+            while number.iter().any(|byte| *byte != 0) {
+                let mut remainder = 0u32;
+                for byte in number.iter_mut() {
+                    let value = (remainder << 8) | *byte as u32;
+                    *byte = (value / 10) as u8;
+                    remainder = value % 10;
+                }
+                digits.push(char::from(b'0' + remainder as u8));
+            }
+            if digits.is_empty() {
+                digits.push('0');
+            }
+            digits.iter().rev().collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_digests() {
+        let hex = |kind: HashKind, text: &str| format_digest(&kind.digest(text.as_bytes()), 16);
+        assert_eq!(hex(HashKind::Crc32, "123456789"), "cbf43926");
+        assert_eq!(hex(HashKind::Md5, "abc"), "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(hex(HashKind::Sha1, "abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(hex(HashKind::Sha256, "abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(HashKind::parse("SHA256"), Some(HashKind::Sha256));
+        assert_eq!(HashKind::parse("sha512"), None);
+    }
+
+    #[test]
+    fn formats_in_each_base() {
+        assert_eq!(format_digest(&[0xcb, 0xf4, 0x39, 0x26], 10), "3421780262");
+        assert_eq!(format_digest(&[0, 0], 10), "0");
+        assert_eq!(format_digest(&[0x0f, 0x01], 2), "00001111 00000001");
+        assert_eq!(integer_bytes(0x1234, None), vec![0x12, 0x34]);
+        assert_eq!(integer_bytes(0, None), vec![0]);
+        assert_eq!(integer_bytes(0xff, Some(16)), vec![0, 0xff]);
+    }
+}
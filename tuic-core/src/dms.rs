@@ -0,0 +1,89 @@
+//! Degrees/minutes/seconds angles: `45°30'15"` in, decimal degrees out, and back.
+
+/// Characters that may follow the digits of a DMS literal.
+pub fn is_dms_char(ch: char) -> bool {
+    matches!(ch, '°' | '\'' | '"')
+}
+
+/// Reads an unsigned DMS literal (`45°30'15"`, `45°30'`, `45°`, `12°0'7.5"`) at
+/// the start of `text`. Returns decimal degrees and the literal's byte length.
+pub fn parse_prefix(text: &str) -> Option<(f64, usize)> {
+    let mut rest = text;
+    let mut degrees = 0.0;
+    // Each unit is optional after degrees, but they must come in order
+    for (index, (mark, scale)) in [('°', 1.0), ('\'', 60.0), ('"', 3600.0)].into_iter().enumerate() {
+        let digits = rest.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(rest.len());
+        if digits == 0 || !rest[digits..].starts_with(mark) {
+            if index == 0 {
+                return None;
+            }
+            break;
+        }
+        let amount: f64 = rest[..digits].parse().ok()?;
+        if index > 0 && amount >= 60.0 {
+            return None;
+        }
+        degrees += amount / scale;
+        rest = &rest[digits + mark.len_utf8()..];
+    }
+    Some((degrees, text.len() - rest.len()))
+}
+
+/// Parses a whole DMS angle, optionally negative: `-45°30'15"`.
+pub fn parse(text: &str) -> Option<f64> {
+    let text = text.trim();
+    let (sign, unsigned) = match text.strip_prefix('-') {
+        Some(rest) => (-1.0, rest),
+        None => (1.0, text),
+    };
+    match parse_prefix(unsigned)? {
+        (degrees, length) if length == unsigned.len() => Some(sign * degrees),
+        _ => None,
+    }
+}
+
+/// Formats decimal degrees as `45°30'15"`. Seconds get `second_digits`
+/// decimals when given, otherwise up to two with trailing zeros dropped.
+pub fn format(degrees: f64, second_digits: Option<usize>) -> String {
+    let digits = second_digits.unwrap_or(2).min(9);
+    let scale = 10u64.pow(digits as u32);
+    // Round once in the smallest unit so 59.999" carries into the minutes
+    let units = (degrees.abs() * 3600.0 * scale as f64).round() as u64;
+    let (minutes_total, second_units) = (units / (60 * scale), units % (60 * scale));
+    let (whole_degrees, minutes) = (minutes_total / 60, minutes_total % 60);
+
+    let mut seconds = format!("{}", second_units / scale);
+    if digits > 0 {
+        let fraction = format!("{:0width$}", second_units % scale, width = digits);
+        let fraction = if second_digits.is_some() { fraction.as_str() } else { fraction.trim_end_matches('0') };
+        if !fraction.is_empty() {
+            seconds = format!("{}.{}", seconds, fraction);
+        }
+    }
+    let sign = if degrees < 0.0 && units > 0 { "-" } else { "" };
+    format!("{}{}°{}'{}\"", sign, whole_degrees, minutes, seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_dms() {
+        assert_eq!(parse("45°30'15\""), Some(45.50416666666667));
+        assert_eq!(parse("45°30'"), Some(45.5));
+        assert_eq!(parse("-10°"), Some(-10.0));
+        assert_eq!(parse("12°0'7.2\""), Some(12.002));
+        assert_eq!(parse("45°75'"), None);
+        assert_eq!(parse("45'"), None);
+        assert_eq!(parse_prefix("30°15'+1"), Some((30.25, 7)));
+    }
+
+    #[test]
+    fn formats_dms() {
+        assert_eq!(format(45.50416666666667, None), "45°30'15\"");
+        assert_eq!(format(12.002, None), "12°0'7.2\"");
+        assert_eq!(format(-0.5, Some(1)), "-0°30'0.0\"");
+        assert_eq!(format(29.99999999, None), "30°0'0\"");
+    }
+}
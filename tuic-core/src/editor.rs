@@ -0,0 +1,163 @@
+//! Multi-line expression editor: a long formula laid out over several lines,
+//! with `#` comments. Lines are joined with spaces before evaluation.
+
+/// Text and caret of the editor popup. `col` is a char index into `lines[row]`.
+#[derive(Debug, Clone, Default)]
+pub struct ExpressionEditor {
+    pub lines: Vec<String>,
+    pub row: usize,
+    pub col: usize,
+}
+
+impl ExpressionEditor {
+    /// An editor holding `text`, caret at the end.
+    pub fn new(text: &str) -> Self {
+        let lines: Vec<String> = text.split('\n').map(str::to_string).collect();
+        let row = lines.len() - 1;
+        let col = lines[row].chars().count();
+        Self { lines, row, col }
+    }
+
+    /// The expression to evaluate: comments dropped, lines joined.
+    pub fn source(&self) -> String {
+        self.lines
+            .iter()
+            .map(|line| line.split_once('#').map_or(line.as_str(), |(code, _)| code).trim())
+            .filter(|code| !code.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn insert_char(&mut self, ch: char) {
+        let at = self.byte_offset();
+        self.lines[self.row].insert(at, ch);
+        self.col += 1;
+    }
+
+    /// Pasted text at the caret, line breaks kept; tabs become spaces.
+    pub fn insert_str(&mut self, text: &str) {
+        for ch in text.chars() {
+            match ch {
+                '\n' => self.insert_newline(),
+                '\t' => self.insert_char(' '),
+                ch if ch.is_control() => {}
+                ch => self.insert_char(ch),
+            }
+        }
+    }
+
+    /// Splits the line at the caret.
+    pub fn insert_newline(&mut self) {
+        let at = self.byte_offset();
+        let rest = self.lines[self.row].split_off(at);
+        self.row += 1;
+        self.lines.insert(self.row, rest);
+        self.col = 0;
+    }
+
+    /// Deletes before the caret, joining with the previous line at a line start.
+    pub fn backspace(&mut self) {
+        if self.col > 0 {
+            self.col -= 1;
+            let at = self.byte_offset();
+            self.lines[self.row].remove(at);
+        } else if self.row > 0 {
+            let line = self.lines.remove(self.row);
+            self.row -= 1;
+            self.col = self.line_len();
+            self.lines[self.row].push_str(&line);
+        }
+    }
+
+    pub fn move_left(&mut self) {
+        if self.col > 0 {
+            self.col -= 1;
+        } else if self.row > 0 {
+            self.row -= 1;
+            self.col = self.line_len();
+        }
+    }
+
+    pub fn move_right(&mut self) {
+        if self.col < self.line_len() {
+            self.col += 1;
+        } else if self.row + 1 < self.lines.len() {
+            self.row += 1;
+            self.col = 0;
+        }
+    }
+
+    pub fn move_up(&mut self) {
+        if self.row > 0 {
+            self.row -= 1;
+            self.col = self.col.min(self.line_len());
+        }
+    }
+
+    pub fn move_down(&mut self) {
+        if self.row + 1 < self.lines.len() {
+            self.row += 1;
+            self.col = self.col.min(self.line_len());
+        }
+    }
+
+    pub fn move_home(&mut self) {
+        self.col = 0;
+    }
+
+    pub fn move_end(&mut self) {
+        self.col = self.line_len();
+    }
+
+    fn line_len(&self) -> usize {
+        self.lines[self.row].chars().count()
+    }
+
+    fn byte_offset(&self) -> usize {
+        let line = &self.lines[self.row];
+        line.char_indices().nth(self.col).map_or(line.len(), |(offset, _)| offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn editing_across_lines() {
+        let mut editor = ExpressionEditor::new("1 + 2");
+        editor.insert_newline();
+        for ch in "* 3 # triple it".chars() {
+            editor.insert_char(ch);
+        }
+        assert_eq!(editor.lines, ["1 + 2", "* 3 # triple it"]);
+        assert_eq!(editor.source(), "1 + 2 * 3");
+
+        editor.move_home();
+        editor.backspace();
+        assert_eq!(editor.lines, ["1 + 2* 3 # triple it"]);
+        assert_eq!((editor.row, editor.col), (0, 5));
+        editor.move_left();
+        editor.insert_char('√');
+        editor.move_right();
+        editor.insert_char('°');
+        assert_eq!(editor.lines[0], "1 + √2°* 3 # triple it");
+    }
+
+    #[test]
+    fn pasting_keeps_line_breaks() {
+        let mut editor = ExpressionEditor::new("sqrt()");
+        editor.move_left();
+        editor.insert_str("2 +\r\n\t3");
+        assert_eq!(editor.lines, ["sqrt(2 +", " 3)"]);
+        assert_eq!((editor.row, editor.col), (1, 2));
+        assert_eq!(editor.source(), "sqrt(2 + 3)");
+    }
+
+    #[test]
+    fn comment_only_lines_vanish() {
+        let editor = ExpressionEditor::new("# area of a circle\npi *\n  2^2  # r = 2\n");
+        assert_eq!(editor.source(), "pi * 2^2");
+        assert_eq!((editor.row, editor.col), (3, 0));
+    }
+}
@@ -0,0 +1,171 @@
+use std::ops::Range;
+
+use thiserror::Error;
+
+/// Everything that can go wrong in the calculator. Variants carry enough
+/// context to explain the failure, and `code` gives each a stable identifier.
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum CalculatorError {
+    // Parsing
+    #[error("Invalid expression")]
+    InvalidExpression,
+    #[error("Unexpected '{token}' at position {}", offset + 1)]
+    UnexpectedToken { offset: usize, token: String },
+    #[error("Expression ends unexpectedly")]
+    UnexpectedEnd { offset: usize },
+    #[error("Unmatched parenthesis at position {}", offset + 1)]
+    UnmatchedParenthesis { offset: usize },
+    #[error("Mismatched parentheses")]
+    MismatchedParentheses,
+    #[error("'{input}' is not a number")]
+    InvalidNumber { input: String },
+    #[error("'{digits}' is not a valid base-{radix} number")]
+    InvalidDigits { digits: String, radix: u32 },
+    #[error("Invalid character '{ch}' for {mode} mode")]
+    InvalidCharacter { ch: char, mode: &'static str },
+    #[allow(dead_code)]
+    #[error("Invalid complex number")]
+    InvalidComplex,
+    #[error("Input of {length} characters is over the limit of {limit} (max-input-length)")]
+    InputTooLong { length: usize, limit: usize },
+    #[error("Parentheses nest more than {limit} deep at position {} (max-nesting)", offset + 1)]
+    NestingTooDeep { offset: usize, limit: usize },
+    #[error("More than {limit} numbers, names and operators, from position {} (max-tokens)", offset + 1)]
+    TooManyTokens { offset: usize, limit: usize },
+
+    // Evaluation
+    #[error("Division by zero ({dividend} / 0)")]
+    DivisionByZero { dividend: f64 },
+    #[error("Unknown operator '{0}'")]
+    UnknownOperator(char),
+    #[error("Unknown function '{0}'")]
+    UnknownFunction(String),
+    #[error("Unknown variable '{0}'")]
+    UnknownVariable(String),
+    #[error("Invalid arguments, expected {0}")]
+    InvalidArguments(&'static str),
+    #[error("No root found near x = {x0}")]
+    NoRoot { x0: f64 },
+    #[error("'{operation}' has no finite value here")]
+    NotFinite { operation: &'static str },
+    #[error("No exchange rate for {0}; ':rates fetch' or ':rate {0} <per base>' sets one")]
+    UnknownCurrency(String),
+    #[error("Unknown unit '{0}', expected B, kB, MB, GB, TB, PB or KiB ... PiB")]
+    UnknownUnit(String),
+    #[error("Uncertainties need uncertainty mode; ':set uncertainty on' turns it on")]
+    UncertaintyOff,
+    #[error("'{function}' needs {expected}")]
+    Domain { function: &'static str, expected: &'static str },
+    #[error("Vectors of different lengths ({left} and {right})")]
+    VectorLength { left: usize, right: usize },
+    #[error("Index {index} is outside a vector of {len}; elements count from 1")]
+    IndexOutOfRange { index: f64, len: usize },
+    #[error("Vectors are not supported by '{operation}'")]
+    VectorUnsupported { operation: String },
+    #[error("'i' is the imaginary unit only with :set complex on")]
+    ImaginaryUnitOff,
+    #[error("'∠' makes a complex number, so it needs ':set complex on'")]
+    PolarOff,
+
+    // Stack operations
+    #[error("Stack underflow: '{operation}' needs {needed} values, the stack has {available}")]
+    StackUnderflow { operation: String, needed: usize, available: usize },
+    #[error("Complex operands are not supported by '{operation}' yet")]
+    ComplexUnsupported { operation: String },
+    #[error("Nothing to undo")]
+    NothingToUndo,
+    #[error("No history entry with a value for {0}")]
+    NoSuchHistoryEntry(String),
+
+    // Commands and settings
+    #[error("Unknown command: {0}")]
+    UnknownCommand(String),
+    #[error("Invalid value '{value}' for {setting}, expected {expected}")]
+    InvalidSetting { setting: &'static str, value: String, expected: &'static str },
+    #[error("No columns selected for import")]
+    NoColumnsSelected,
+    #[error("Failed to set theme: {0}")]
+    Theme(String),
+    #[error("Backup failed: {0}")]
+    Backup(String),
+    #[error("Workspace: {0}")]
+    Workspace(String),
+    #[error("Export failed: {0}")]
+    Export(String),
+    #[error("Config: {0}")]
+    Config(String),
+    #[error("Exchange rates: {0}")]
+    Rates(String),
+    #[error("Session journal: {0}")]
+    Journal(String),
+    #[error("Shared history: {0}")]
+    SharedHistory(String),
+    #[error("No {0} rate set; '{0}-rate' in config.toml or ':set {0} <percent>' sets one")]
+    NoRate(&'static str),
+    #[error("No stack or history entry is {value} (within ±{tolerance})")]
+    NoMatch { value: String, tolerance: f64 },
+}
+
+impl CalculatorError {
+    /// Stable identifier: 1xx parsing, 2xx evaluation, 3xx stack, 4xx commands.
+    pub fn code(&self) -> &'static str {
+        match self {
+            CalculatorError::InvalidExpression => "E100",
+            CalculatorError::UnexpectedToken { .. } => "E101",
+            CalculatorError::UnexpectedEnd { .. } => "E102",
+            CalculatorError::UnmatchedParenthesis { .. } => "E103",
+            CalculatorError::MismatchedParentheses => "E104",
+            CalculatorError::InvalidNumber { .. } => "E105",
+            CalculatorError::InvalidDigits { .. } => "E106",
+            CalculatorError::InvalidCharacter { .. } => "E107",
+            CalculatorError::InvalidComplex => "E108",
+            CalculatorError::InputTooLong { .. } => "E109",
+            CalculatorError::NestingTooDeep { .. } => "E110",
+            CalculatorError::TooManyTokens { .. } => "E111",
+            CalculatorError::DivisionByZero { .. } => "E201",
+            CalculatorError::UnknownOperator(_) => "E202",
+            CalculatorError::UnknownFunction(_) => "E203",
+            CalculatorError::UnknownVariable(_) => "E204",
+            CalculatorError::InvalidArguments(_) => "E205",
+            CalculatorError::NoRoot { .. } => "E206",
+            CalculatorError::NotFinite { .. } => "E207",
+            CalculatorError::UnknownCurrency(_) => "E208",
+            CalculatorError::UnknownUnit(_) => "E209",
+            CalculatorError::UncertaintyOff => "E210",
+            CalculatorError::Domain { .. } => "E211",
+            CalculatorError::VectorLength { .. } => "E212",
+            CalculatorError::IndexOutOfRange { .. } => "E213",
+            CalculatorError::VectorUnsupported { .. } => "E214",
+            CalculatorError::ImaginaryUnitOff => "E215",
+            CalculatorError::PolarOff => "E216",
+            CalculatorError::StackUnderflow { .. } => "E301",
+            CalculatorError::ComplexUnsupported { .. } => "E302",
+            CalculatorError::NothingToUndo => "E303",
+            CalculatorError::NoSuchHistoryEntry(_) => "E304",
+            CalculatorError::UnknownCommand(_) => "E401",
+            CalculatorError::InvalidSetting { .. } => "E402",
+            CalculatorError::NoColumnsSelected => "E403",
+            CalculatorError::Theme(_) => "E404",
+            CalculatorError::Backup(_) => "E405",
+            CalculatorError::Workspace(_) => "E406",
+            CalculatorError::Export(_) => "E407",
+            CalculatorError::Config(_) => "E408",
+            CalculatorError::Rates(_) => "E409",
+            CalculatorError::Journal(_) => "E410",
+            CalculatorError::SharedHistory(_) => "E411",
+            CalculatorError::NoRate(_) => "E412",
+            CalculatorError::NoMatch { .. } => "E413",
+        }
+    }
+
+    /// Byte range of the input the error points at, for parse errors.
+    pub fn span(&self) -> Option<Range<usize>> {
+        match self {
+            CalculatorError::UnexpectedToken { offset, token } => Some(*offset..offset + token.len()),
+            CalculatorError::UnexpectedEnd { offset } => Some(*offset..*offset),
+            CalculatorError::UnmatchedParenthesis { offset } | CalculatorError::NestingTooDeep { offset, .. } => Some(*offset..offset + 1),
+            CalculatorError::TooManyTokens { offset, .. } => Some(*offset..*offset),
+            _ => None,
+        }
+    }
+}
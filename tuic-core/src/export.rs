@@ -0,0 +1,170 @@
+//! History export for pasting into reports: a Markdown table, a LaTeX align* block,
+//! or plain `expr = result` lines; and as a script that `batch` replays.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Markdown,
+    Latex,
+    Text, // One line per entry, in the export template's format when there is one
+    Script, // Lines `batch` runs to rebuild the stack, the rest of the history as comments
+}
+
+impl ExportFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "md" | "markdown" => Some(ExportFormat::Markdown),
+            "tex" | "latex" => Some(ExportFormat::Latex),
+            "text" | "txt" => Some(ExportFormat::Text),
+            "script" | "tuic" => Some(ExportFormat::Script),
+            _ => None,
+        }
+    }
+}
+
+/// Renders `(expression, result)` rows; lines without a result keep the result empty.
+pub fn render(rows: &[(String, Option<String>)], format: ExportFormat) -> String {
+    match format {
+        ExportFormat::Markdown => {
+            let mut out = String::from("| Expression | Result |\n| --- | --- |\n");
+            for (expression, result) in rows {
+                let cell = |text: &str| text.replace('|', "\\|");
+                out.push_str(&format!("| `{}` | {} |\n", cell(expression), cell(result.as_deref().unwrap_or(""))));
+            }
+            out
+        }
+        ExportFormat::Latex => {
+            let lines: Vec<String> = rows
+                .iter()
+                .map(|(expression, result)| match result {
+                    Some(result) => format!("  {} &= {}", latex_math(expression), latex_math(result)),
+                    None => format!("  &\\text{{{}}}", latex_text(expression)),
+                })
+                .collect();
+            format!("\\begin{{align*}}\n{}\n\\end{{align*}}\n", lines.join(" \\\\\n"))
+        }
+        ExportFormat::Text => rows
+            .iter()
+            .map(|(expression, result)| match result {
+                Some(result) => format!("{} = {}\n", expression, result),
+                None => format!("{}\n", expression),
+            })
+            .collect(),
+        // Every row replayed; the calculator picks which to run through `script`
+        ExportFormat::Script => script(&rows.iter().map(|(expression, _)| ScriptLine::Run(expression.clone())).collect::<Vec<_>>()),
+    }
+}
+
+/// A line of a script export.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptLine {
+    Run(String),  // A setting, or an entry whose value is on the stack at the end
+    Note(String), // Kept as a comment: a session start, or a value a later line used up
+}
+
+/// Lines in `batch`'s syntax, infix expressions and `:` commands, so that
+/// `tui-calculator batch < calc.tuic` pushes the stack again, printing it bottom first.
+pub fn script(lines: &[ScriptLine]) -> String {
+    let mut out = String::from("# tuic script: replay with `tui-calculator batch < <this file>`\n");
+    for line in lines {
+        match line {
+            ScriptLine::Run(text) => out.push_str(&format!("{}\n", text)),
+            ScriptLine::Note(text) => out.push_str(&format!("# {}\n", text)),
+        }
+    }
+    out
+}
+
+// Calculator syntax as LaTeX math: \cdot for *, \sin and \pi for names, braced exponents
+fn latex_math(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '*' => out.push_str("\\cdot"),
+            '°' => out.push_str("^\\circ"),
+            '∠' => out.push_str("\\angle "),
+            '<' | '>' | '=' | '!' if chars.next_if_eq(&'=').is_some() => out.push_str(match ch {
+                '<' => "\\le",
+                '>' => "\\ge",
+                '!' => "\\ne",
+                _ => "=",
+            }),
+            '^' => {
+                // Brace a whole number or name so 2^10 doesn't render as 2¹0
+                let mut exponent = String::new();
+                while let Some(&next) = chars.peek().filter(|next| next.is_alphanumeric() || **next == '.') {
+                    exponent.push(next);
+                    chars.next();
+                }
+                out.push_str(&format!("^{{{}}}", exponent));
+            }
+            c if c.is_ascii_alphabetic() => {
+                let mut name = String::from(c);
+                while let Some(&next) = chars.peek().filter(|next| next.is_ascii_alphanumeric()) {
+                    name.push(next);
+                    chars.next();
+                }
+                match name.as_str() {
+                    "sin" | "cos" | "tan" | "ln" | "log" | "exp" | "pi" | "gcd" => out.push_str(&format!("\\{}", name)),
+                    "asin" | "acos" | "atan" => out.push_str(&format!("\\arc{}", &name[1..])),
+                    "e" | "x" => out.push_str(&name),
+                    _ => out.push_str(&format!("\\operatorname{{{}}}", name)),
+                }
+            }
+            '#' | '$' | '%' | '&' | '_' | '{' | '}' => {
+                out.push('\\');
+                out.push(ch);
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn latex_text(text: &str) -> String {
+    text.chars()
+        .map(|ch| match ch {
+            '#' | '$' | '%' | '&' | '_' | '{' | '}' => format!("\\{}", ch),
+            '^' => "\\^{}".to_string(),
+            '\\' => "\\textbackslash{}".to_string(),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows() -> Vec<(String, Option<String>)> {
+        vec![
+            ("2*sqrt(16)".to_string(), Some("8".to_string())),
+            ("2^10 | 1".to_string(), Some("1024".to_string())),
+            ("factor(360) = 2^3 · 3^2 · 5".to_string(), None),
+        ]
+    }
+
+    #[test]
+    fn markdown_table() {
+        assert_eq!(
+            render(&rows(), ExportFormat::Markdown),
+            "| Expression | Result |\n| --- | --- |\n| `2*sqrt(16)` | 8 |\n| `2^10 \\| 1` | 1024 |\n| `factor(360) = 2^3 · 3^2 · 5` |  |\n"
+        );
+    }
+
+    #[test]
+    fn latex_align() {
+        assert_eq!(
+            render(&rows(), ExportFormat::Latex),
+            "\\begin{align*}\n  2\\cdot\\operatorname{sqrt}(16) &= 8 \\\\\n  2^{10} | 1 &= 1024 \\\\\n  &\\text{factor(360) = 2\\^{}3 · 3\\^{}2 · 5}\n\\end{align*}\n"
+        );
+        assert_eq!(latex_math("sin(pi/2) + asin(x)"), "\\sin(\\pi/2) + \\arcsin(x)");
+        assert_eq!(latex_math("if(x >= 2, 1, x != 0)"), "\\operatorname{if}(x \\ge 2, 1, x \\ne 0)");
+    }
+
+    #[test]
+    fn script_lines() {
+        let lines = [ScriptLine::Run(":set angle deg".to_string()), ScriptLine::Note("2".to_string()), ScriptLine::Run("(2 + 3)".to_string())];
+        assert_eq!(script(&lines), "# tuic script: replay with `tui-calculator batch < <this file>`\n:set angle deg\n# 2\n(2 + 3)\n");
+    }
+}
@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+
+use crate::calculator::Token;
+use crate::error::CalculatorError;
+
+/// Expression tree kept alongside each stack entry so it can be re-rendered.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Expr {
+    /// A number, printed exactly as it was entered
+    Number { value: f64, text: String },
+    Binary { op: char, lhs: Box<Expr>, rhs: Box<Expr> },
+    Variable(String),
+    Call { name: String, args: Vec<Expr> },
+    /// A vector literal, [1, 2, 3]
+    Vector(Vec<Expr>),
+}
+
+impl Expr {
+    pub fn number(value: f64, text: impl Into<String>) -> Self {
+        Expr::Number { value, text: text.into() }
+    }
+
+    pub fn binary(op: char, lhs: Expr, rhs: Expr) -> Self {
+        Expr::Binary { op, lhs: Box::new(lhs), rhs: Box::new(rhs) }
+    }
+
+    /// Builds a tree from shunting-yard output.
+    pub fn from_postfix(tokens: Vec<Token>) -> Result<Self, CalculatorError> {
+        let mut operands: Vec<Expr> = Vec::new();
+        for token in tokens {
+            match token {
+                Token::Number(value) => operands.push(Expr::number(value, value.to_string())),
+                Token::Operator(op) => {
+                    let rhs = operands.pop().ok_or(CalculatorError::InvalidExpression)?;
+                    let lhs = operands.pop().ok_or(CalculatorError::InvalidExpression)?;
+                    // Python's a // b is floor(a / b), so every number type already handles it
+                    if op == FLOOR_DIVIDE {
+                        operands.push(Expr::Call { name: "floor".to_string(), args: vec![Expr::binary('/', lhs, rhs)] });
+                    } else {
+                        operands.push(Expr::binary(op, lhs, rhs));
+                    }
+                }
+                Token::Identifier(name) => operands.push(Expr::Variable(name)),
+                Token::Function { name, arity } => {
+                    if operands.len() < arity {
+                        return Err(CalculatorError::InvalidExpression);
+                    }
+                    let args = operands.split_off(operands.len() - arity);
+                    // The tokenizer reads '[' as a call to "[", so elements are counted like arguments
+                    operands.push(if name == "[" { Expr::Vector(args) } else { Expr::Call { name, args } });
+                }
+                _ => return Err(CalculatorError::InvalidExpression),
+            }
+        }
+
+        match (operands.pop(), operands.is_empty()) {
+            (Some(expr), true) => Ok(expr),
+            _ => Err(CalculatorError::InvalidExpression),
+        }
+    }
+
+    /// Form with only the parentheses precedence and associativity require.
+    pub fn pretty(&self) -> String {
+        match self {
+            Expr::Number { text, .. } => text.clone(),
+            Expr::Variable(name) => name.clone(),
+            Expr::Call { name, args } => {
+                let args: Vec<String> = args.iter().map(Expr::pretty).collect();
+                format!("{}({})", name, args.join(", "))
+            }
+            Expr::Vector(items) => {
+                let items: Vec<String> = items.iter().map(Expr::pretty).collect();
+                format!("[{}]", items.join(", "))
+            }
+            Expr::Binary { op: '[', lhs, rhs } => format!("{}[{}]", parenthesize(lhs.pretty(), lhs.precedence() < precedence('[')), rhs.pretty()),
+            Expr::Binary { op, lhs, rhs } => {
+                let prec = precedence(*op);
+                let right_assoc = *op == '^';
+                // A child at the same level only binds without parentheses on its associative side
+                let lhs_needs_parens = lhs.precedence() < prec || (right_assoc && lhs.precedence() == prec);
+                let rhs_needs_parens = rhs.precedence() < prec || (!right_assoc && rhs.precedence() == prec);
+                format!(
+                    "{} {} {}",
+                    parenthesize(lhs.pretty(), lhs_needs_parens),
+                    operator_text(*op),
+                    parenthesize(rhs.pretty(), rhs_needs_parens)
+                )
+            }
+        }
+    }
+
+    fn precedence(&self) -> i32 {
+        match self {
+            Expr::Number { text, .. } if text.starts_with('-') => precedence('-'),
+            Expr::Number { .. } | Expr::Variable(_) | Expr::Call { .. } | Expr::Vector(_) => i32::MAX,
+            Expr::Binary { op, .. } => precedence(*op),
+        }
+    }
+}
+
+/// The token for // with `:set python on`; it never reaches a tree.
+pub const FLOOR_DIVIDE: char = '⫽';
+
+pub fn precedence(op: char) -> i32 {
+    match op {
+        '+' | '-' => 1,
+        '*' | '/' | FLOOR_DIVIDE => 2,
+        '^' => 3,
+        '±' | '∠' => 4, // 5 ± 0.1 * 2 doubles the whole measurement, 2 * 5∠30 the whole complex number
+        '[' => 5, // Indexing, v[2]: the element is taken before anything else
+        _ => 0, // Comparisons bind loosest: 1 + 2 < 4 compares 3 with 4
+    }
+}
+
+/// How an operator is typed. Two-character comparisons are stored as one
+/// char: '=' for ==, '≠' for !=, '≤' for <=, '≥' for >=.
+pub fn operator_text(op: char) -> String {
+    match op {
+        '=' => "==".to_string(),
+        '≠' => "!=".to_string(),
+        '≤' => "<=".to_string(),
+        '≥' => ">=".to_string(),
+        _ => op.to_string(),
+    }
+}
+
+fn parenthesize(text: String, needed: bool) -> String {
+    if needed { format!("({})", text) } else { text }
+}
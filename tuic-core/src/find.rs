@@ -0,0 +1,57 @@
+//! Finding a figure (`:find 42.37`): the stack and history entries whose result
+//! is that number within a tolerance, to trace where a figure in a long session came from.
+
+use crate::calculator::StackValue;
+
+/// A match: a stack level (0 is the top) or a history index (0 is the oldest).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Found {
+    Stack(usize),
+    History(usize),
+}
+
+/// The last `:find`, which `:find` on its own steps through.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Search {
+    pub typed: String,
+    pub target: f64,
+    pub tolerance: f64,
+    pub next: usize, // Index of the match to select, counted over all of them
+}
+
+/// Half a unit in the last digit typed, so a figure copied off a rounded display
+/// finds the full-precision result: 0.005 for 42.37, 0.5 for 42, 0.00005 for 1.5e-3.
+pub fn tolerance(typed: &str) -> f64 {
+    let (mantissa, exponent) = typed.split_once(['e', 'E']).unwrap_or((typed, "0"));
+    let decimals = mantissa.split_once('.').map_or(0, |(_, fraction)| fraction.len()) as i32;
+    // Divided rather than multiplied by a negative power, so 0.005 is exactly what prints
+    let shift = decimals - exponent.parse::<i32>().unwrap_or(0);
+    if shift >= 0 { 0.5 / 10f64.powi(shift) } else { 0.5 * 10f64.powi(-shift) }
+}
+
+/// Whether `value`, or any item of a vector, is within `tolerance` of `target`.
+pub fn matches(value: &StackValue, target: f64, tolerance: f64) -> bool {
+    // A little slack for binary rounding, so 0.1 + 0.2 is found by 0.3 with a tolerance of 0
+    let close = |x: f64| (x - target).abs() <= tolerance + target.abs() * 1e-12;
+    match value {
+        StackValue::Vector(items) => items.iter().any(|x| close(*x)),
+        value => value.as_real().is_some_and(close),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tolerance_follows_the_digits_typed() {
+        assert_eq!(tolerance("42"), 0.5);
+        assert_eq!(tolerance("42.37"), 0.005);
+        assert_eq!(tolerance("1.5e-3"), 0.00005);
+        assert_eq!(tolerance("2e3"), 500.0);
+        assert!(matches(&StackValue::Real(42.3712), 42.37, tolerance("42.37")));
+        assert!(!matches(&StackValue::Real(42.376), 42.37, tolerance("42.37")));
+        assert!(matches(&StackValue::Real(0.1 + 0.2), 0.3, 0.0));
+        assert!(matches(&StackValue::Vector(vec![1.0, -7.25]), -7.25, 0.0));
+    }
+}
@@ -0,0 +1,82 @@
+use std::num::FpCategory;
+
+const EXPONENT_BIAS: i32 = 1023;
+const MANTISSA_BITS: u32 = 52;
+const MANTISSA_MASK: u64 = (1 << MANTISSA_BITS) - 1;
+const EXPONENT_MASK: u64 = 0x7FF;
+
+/// Bit-level breakdown of an f64, as shown by the float inspector popup.
+#[derive(Debug, Clone)]
+pub struct FloatParts {
+    pub value: f64,
+    pub bits: u64,
+    pub sign: u8,
+    pub exponent_bits: u16,
+    pub mantissa: u64,
+    pub category: FpCategory,
+    pub previous: f64,
+    pub next: f64,
+    pub ulp: f64,
+}
+
+impl FloatParts {
+    pub fn decompose(value: f64) -> Self {
+        let bits = value.to_bits();
+        let previous = value.next_down();
+        let next = value.next_up();
+        Self {
+            value,
+            bits,
+            sign: (bits >> 63) as u8,
+            exponent_bits: ((bits >> MANTISSA_BITS) & EXPONENT_MASK) as u16,
+            mantissa: bits & MANTISSA_MASK,
+            category: value.classify(),
+            previous,
+            next,
+            // Spacing between this value and the next one away from zero
+            ulp: if value.is_finite() { value.abs().next_up() - value.abs() } else { f64::NAN },
+        }
+    }
+
+    /// Exponent with the bias removed (subnormals use the minimum exponent).
+    pub fn unbiased_exponent(&self) -> Option<i32> {
+        match self.category {
+            FpCategory::Normal => Some(self.exponent_bits as i32 - EXPONENT_BIAS),
+            FpCategory::Subnormal => Some(1 - EXPONENT_BIAS),
+            _ => None,
+        }
+    }
+
+    pub fn category_name(&self) -> &'static str {
+        match self.category {
+            FpCategory::Nan => "NaN",
+            FpCategory::Infinite => "Infinite",
+            FpCategory::Zero => "Zero",
+            FpCategory::Subnormal => "Subnormal",
+            FpCategory::Normal => "Normal",
+        }
+    }
+
+    /// Bit pattern split into "sign exponent mantissa" groups.
+    pub fn grouped_binary(&self) -> String {
+        format!("{} {:011b} {:052b}", self.sign, self.exponent_bits, self.mantissa)
+    }
+}
+
+/// Number of representable doubles between `a` and `b`, or None if either is NaN.
+pub fn ulp_distance(a: f64, b: f64) -> Option<u64> {
+    if a.is_nan() || b.is_nan() {
+        return None;
+    }
+    Some((ordered_bits(a) - ordered_bits(b)).unsigned_abs() as u64)
+}
+
+// Maps the sign-magnitude bit pattern onto a monotonically ordered integer line
+fn ordered_bits(value: f64) -> i128 {
+    let bits = value.to_bits() as i64;
+    if bits < 0 {
+        i64::MIN as i128 - bits as i128
+    } else {
+        bits as i128
+    }
+}
@@ -0,0 +1,374 @@
+use std::f64::consts::{E, PI};
+
+use crate::context::{EvalContext, Rounding};
+use crate::error::CalculatorError;
+use crate::primes;
+
+/// A built-in function callable from infix expressions.
+pub struct Function {
+    pub name: &'static str,
+    pub usage: &'static str,
+    pub arity: usize,
+    pub apply: fn(&[f64]) -> f64,
+}
+
+impl Function {
+    /// Applies the function with angles in `context`'s unit.
+    pub fn call(&self, args: &[f64], context: EvalContext) -> f64 {
+        match self.name {
+            "sin" | "cos" | "tan" => match context.quarter_turns(args[0]) {
+                // Exact at multiples of 90°: sin(180) is 0 and tan(90) has no value
+                Some(turns) => {
+                    let (sin, cos) = [(0.0, 1.0), (1.0, 0.0), (0.0, -1.0), (-1.0, 0.0)][turns.rem_euclid(4) as usize];
+                    match self.name {
+                        "sin" => sin,
+                        "cos" => cos,
+                        _ => sin / cos,
+                    }
+                }
+                None => (self.apply)(&[context.to_radians(args[0])]),
+            },
+            "asin" | "acos" | "atan" => context.from_radians((self.apply)(args)),
+            "round" => context.rounding.round(args[0], 0),
+            "rnd" => round_places(args[0], args[1], context.rounding),
+            _ => (self.apply)(args),
+        }
+    }
+
+    /// Arguments with no real value, reported as an error rather than NaN.
+    pub fn check_domain(&self, args: &[f64]) -> Result<(), CalculatorError> {
+        match self.name {
+            "logb" if !(args[0] > 0.0 && args[1] > 0.0 && args[1] != 1.0) => {
+                Err(CalculatorError::Domain { function: self.name, expected: "x > 0 and a base b > 0 other than 1" })
+            }
+            "db" | "dbv" | "w2dbm" if args[0] <= 0.0 => Err(CalculatorError::Domain { function: self.name, expected: "x > 0" }),
+            "c2f" | "c2k" if args[0] < -273.15 => Err(CalculatorError::Domain { function: self.name, expected: "x ≥ -273.15 °C, absolute zero" }),
+            "f2c" if args[0] < -459.67 => Err(CalculatorError::Domain { function: self.name, expected: "x ≥ -459.67 °F, absolute zero" }),
+            "k2c" if args[0] < 0.0 => Err(CalculatorError::Domain { function: self.name, expected: "x ≥ 0 K, absolute zero" }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The most arguments any function in FUNCTIONS takes.
+pub const MAX_ARITY: usize = 3;
+
+pub const FUNCTIONS: &[Function] = &[
+    Function { name: "sin", usage: "sin(x)", arity: 1, apply: |args| args[0].sin() },
+    Function { name: "cos", usage: "cos(x)", arity: 1, apply: |args| args[0].cos() },
+    Function { name: "tan", usage: "tan(x)", arity: 1, apply: |args| args[0].tan() },
+    Function { name: "asin", usage: "asin(x)", arity: 1, apply: |args| args[0].asin() },
+    Function { name: "acos", usage: "acos(x)", arity: 1, apply: |args| args[0].acos() },
+    Function { name: "atan", usage: "atan(x)", arity: 1, apply: |args| args[0].atan() },
+    Function { name: "sqrt", usage: "sqrt(x)", arity: 1, apply: |args| args[0].sqrt() },
+    Function { name: "exp", usage: "exp(x)", arity: 1, apply: |args| args[0].exp() },
+    Function { name: "ln", usage: "ln(x)", arity: 1, apply: |args| args[0].ln() },
+    Function { name: "log", usage: "log(x)", arity: 1, apply: |args| args[0].log10() },
+    Function { name: "logb", usage: "logb(x, b)", arity: 2, apply: |args| log_base(args[0], args[1]) },
+    Function { name: "abs", usage: "abs(x)", arity: 1, apply: |args| args[0].abs() },
+    Function { name: "round", usage: "round(x)", arity: 1, apply: |args| Rounding::default().round(args[0], 0) },
+    Function { name: "rnd", usage: "rnd(x, places)", arity: 2, apply: |args| round_places(args[0], args[1], Rounding::default()) },
+    Function { name: "floor", usage: "floor(x)", arity: 1, apply: |args| args[0].floor() },
+    Function { name: "ceil", usage: "ceil(x)", arity: 1, apply: |args| args[0].ceil() },
+    Function { name: "trunc", usage: "trunc(x)", arity: 1, apply: |args| args[0].trunc() },
+    Function { name: "ncr", usage: "ncr(n, k)", arity: 2, apply: |args| combinations(args[0], args[1]) },
+    Function { name: "npr", usage: "npr(n, k)", arity: 2, apply: |args| permutations(args[0], args[1]) },
+    Function { name: "gcd", usage: "gcd(a, b)", arity: 2, apply: |args| gcd(args[0], args[1]) },
+    Function { name: "lcm", usage: "lcm(a, b)", arity: 2, apply: |args| lcm(args[0], args[1]) },
+    Function { name: "isprime", usage: "isprime(n)", arity: 1, apply: |args| is_prime(args[0]) },
+    Function { name: "nextprime", usage: "nextprime(n)", arity: 1, apply: |args| next_prime(args[0]) },
+    Function { name: "modpow", usage: "modpow(b, e, m)", arity: 3, apply: |args| mod_pow(args[0], args[1], args[2]) },
+    // Decibels: 10·log10 for power ratios, 20·log10 for amplitude (voltage, pressure) ratios
+    Function { name: "db", usage: "db(power ratio)", arity: 1, apply: |args| 10.0 * args[0].log10() },
+    Function { name: "undb", usage: "undb(dB)", arity: 1, apply: |args| power_of_ten(args[0] / 10.0) },
+    Function { name: "dbv", usage: "dbv(amplitude ratio)", arity: 1, apply: |args| 20.0 * args[0].log10() },
+    Function { name: "undbv", usage: "undbv(dB)", arity: 1, apply: |args| power_of_ten(args[0] / 20.0) },
+    Function { name: "dbm2w", usage: "dbm2w(dBm)", arity: 1, apply: |args| power_of_ten((args[0] - 30.0) / 10.0) },
+    Function { name: "w2dbm", usage: "w2dbm(watts)", arity: 1, apply: |args| 10.0 * args[0].log10() + 30.0 },
+    // Temperatures: offsets as well as factors, so not something a unit factor can do
+    Function { name: "c2f", usage: "c2f(°C)", arity: 1, apply: |args| with_decimals(args[0] * 9.0 / 5.0 + 32.0, args[0], 1) },
+    Function { name: "f2c", usage: "f2c(°F)", arity: 1, apply: |args| (args[0] - 32.0) * 5.0 / 9.0 },
+    Function { name: "c2k", usage: "c2k(°C)", arity: 1, apply: |args| with_decimals(args[0] + 273.15, args[0], 2) },
+    Function { name: "k2c", usage: "k2c(K)", arity: 1, apply: |args| with_decimals(args[0] - 273.15, args[0], 2) },
+    Function { name: "if", usage: "if(cond, a, b)", arity: 3, apply: |args| if args[0] != 0.0 { args[1] } else { args[2] } },
+];
+
+/// Calls the calculator handles itself because they push more than one value.
+pub const SPECIAL_FORMS: &[(&str, &str)] = &[
+    ("iterate", "iterate(expr, x0, n)"), ("factor", "factor(n)"), ("solve", "solve(expr, x0)"),
+    ("integrate", "integrate(expr, a, b)"),
+    ("derivative", "derivative(expr, x0)"),
+    ("plot", "plot(expr, a, b)"),
+    ("roots", "roots(a, b, c) or roots(a, b, c, d)"),
+];
+
+/// Functions taking any number of arguments, evaluated by the calculator.
+pub const VARIADIC: &[(&str, &str)] = &[("poly", "poly(a_n, ..., a_0, x)")];
+
+/// Sums and products over a bound variable, evaluated by the calculator; unlike
+/// the special forms they give one value, so they nest: `2 * sum(k, k, 1, 10)`.
+pub const SERIES: &[(&str, &str)] = &[("sum", "sum(expr, n, start, end)"), ("prod", "prod(expr, n, start, end)")];
+
+/// Functions of vectors, evaluated by the calculator; one-argument functions
+/// like sqrt also take a vector and apply to each element.
+pub const VECTOR: &[(&str, &str)] = &[("dot", "dot(u, v)"), ("norm", "norm(v)")];
+
+/// More terms than this is more likely a typo than a series worth the wait.
+pub const MAX_SERIES_TERMS: f64 = 1_000_000.0;
+
+pub const CONSTANTS: &[(&str, f64)] = &[("pi", PI), ("e", E)];
+
+pub fn lookup(name: &str) -> Option<&'static Function> {
+    FUNCTIONS.iter().find(|function| function.name == name)
+}
+
+/// What a conversion function converts, for the history line of its result.
+pub fn conversion_label(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "c2f" => "°C → °F",
+        "f2c" => "°F → °C",
+        "c2k" => "°C → K",
+        "k2c" => "K → °C",
+        "dbm2w" => "dBm → W",
+        "w2dbm" => "W → dBm",
+        _ => return None,
+    })
+}
+
+pub fn constant(name: &str) -> Option<f64> {
+    CONSTANTS.iter().find(|(constant, _)| *constant == name).map(|(_, value)| *value)
+}
+
+/// Exact for powers of 2 and 10, where ln(x) / ln(b) can be off in the last digit.
+fn log_base(x: f64, base: f64) -> f64 {
+    match base {
+        2.0 => x.log2(),
+        10.0 => x.log10(),
+        _ => x.ln() / base.ln(),
+    }
+}
+
+/// `result` cut to the decimals it can really have, those of `x` and `more`:
+/// 300 - 273.15 is 26.85 rather than 26.850000000000023.
+fn with_decimals(result: f64, x: f64, more: usize) -> f64 {
+    let text = x.to_string();
+    let places = match text.split_once('.') {
+        _ if text.contains('e') => return result,
+        Some((_, fraction)) => fraction.len() + more,
+        None => more,
+    };
+    if places > 12 || !result.is_finite() {
+        return result;
+    }
+    format!("{:.*}", places, result).parse().unwrap_or(result)
+}
+
+/// 10^x, exact for whole x, where powf can be off in the last digit (10^-3).
+fn power_of_ten(x: f64) -> f64 {
+    if x.fract() == 0.0 && x.abs() < 400.0 {
+        return format!("1e{}", x).parse().unwrap_or(f64::NAN);
+    }
+    10f64.powf(x)
+}
+
+/// `x` to a whole number of decimal places, like a fixed-point display does.
+fn round_places(x: f64, places: f64, rounding: Rounding) -> f64 {
+    if places.fract() != 0.0 {
+        return f64::NAN;
+    }
+    rounding.round(x, places as i32)
+}
+
+// The integer functions work on u128 so results stay exact up to 2^53 (the
+// largest whole number an f64 holds exactly); non-integer arguments give NaN.
+
+fn whole(x: f64) -> Option<u128> {
+    (x >= 0.0 && x.fract() == 0.0 && x < u64::MAX as f64).then_some(x as u128)
+}
+
+fn combinations(n: f64, k: f64) -> f64 {
+    let (Some(n), Some(k)) = (whole(n), whole(k)) else {
+        return f64::NAN;
+    };
+    if k > n {
+        return 0.0;
+    }
+    let k = k.min(n - k);
+    let mut result: u128 = 1;
+    for i in 1..=k {
+        // result is C(n - k + i - 1, i - 1), so the division is always exact
+        match result.checked_mul(n - k + i) {
+            Some(product) => result = product / i,
+            None => return (i..=k).fold(result as f64, |acc, j| acc * (n - k + j) as f64 / j as f64),
+        }
+    }
+    result as f64
+}
+
+fn permutations(n: f64, k: f64) -> f64 {
+    let (Some(n), Some(k)) = (whole(n), whole(k)) else {
+        return f64::NAN;
+    };
+    if k > n {
+        return 0.0;
+    }
+    let mut result: u128 = 1;
+    for factor in n - k + 1..=n {
+        match result.checked_mul(factor) {
+            Some(product) => result = product,
+            None => return (factor..=n).fold(result as f64, |acc, f| acc * f as f64),
+        }
+    }
+    result as f64
+}
+
+/// `x` as a u64 when it is a whole number in range.
+pub fn whole_u64(x: f64) -> Option<u64> {
+    whole(x).and_then(|n| u64::try_from(n).ok())
+}
+
+fn is_prime(n: f64) -> f64 {
+    match whole_u64(n) {
+        Some(n) => f64::from(u8::from(primes::is_prime(n))),
+        None if n.fract() == 0.0 => 0.0, // Negative numbers aren't prime
+        None => f64::NAN,
+    }
+}
+
+fn next_prime(n: f64) -> f64 {
+    if n < 2.0 {
+        return 2.0;
+    }
+    match whole_u64(n.floor()).and_then(primes::next_prime) {
+        Some(prime) => prime as f64,
+        None => f64::NAN,
+    }
+}
+
+fn mod_pow(base: f64, exponent: f64, modulus: f64) -> f64 {
+    match (whole_u64(base), whole_u64(exponent), whole_u64(modulus)) {
+        (Some(base), Some(exponent), Some(modulus)) if modulus > 0 => primes::mod_pow(base, exponent, modulus) as f64,
+        _ => f64::NAN,
+    }
+}
+
+fn integer_gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+fn gcd(a: f64, b: f64) -> f64 {
+    match (whole(a.abs()), whole(b.abs())) {
+        (Some(a), Some(b)) => integer_gcd(a, b) as f64,
+        _ => f64::NAN,
+    }
+}
+
+fn lcm(a: f64, b: f64) -> f64 {
+    match (whole(a.abs()), whole(b.abs())) {
+        (Some(0), _) | (_, Some(0)) => 0.0,
+        (Some(a), Some(b)) => (a / integer_gcd(a, b) * b) as f64,
+        _ => f64::NAN,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculator::AngleMode;
+
+    #[test]
+    fn max_arity_covers_every_function() {
+        assert_eq!(FUNCTIONS.iter().map(|function| function.arity).max(), Some(MAX_ARITY));
+    }
+
+    #[test]
+    fn combinatorics_are_exact() {
+        assert_eq!(combinations(5.0, 2.0), 10.0);
+        assert_eq!(combinations(52.0, 5.0), 2_598_960.0);
+        assert_eq!(combinations(60.0, 30.0), 118_264_581_564_861_424.0);
+        assert_eq!(combinations(3.0, 4.0), 0.0);
+        assert!(combinations(5.5, 2.0).is_nan());
+        assert_eq!(permutations(10.0, 3.0), 720.0);
+        assert_eq!(permutations(20.0, 20.0), 2_432_902_008_176_640_000.0);
+        assert!(permutations(1000.0, 1000.0).is_infinite());
+    }
+
+    #[test]
+    fn divisors_and_multiples() {
+        assert_eq!(gcd(12.0, 18.0), 6.0);
+        assert_eq!(gcd(-4.0, 6.0), 2.0);
+        assert_eq!(gcd(0.0, 7.0), 7.0);
+        assert_eq!(lcm(4.0, 6.0), 12.0);
+        assert_eq!(lcm(0.0, 6.0), 0.0);
+        assert!(lcm(1.5, 2.0).is_nan());
+    }
+
+    #[test]
+    fn trigonometry_follows_the_angle_mode() {
+        let (degrees, radians) = (EvalContext { angle: AngleMode::Degrees, rounding: Rounding::HalfAway }, EvalContext { angle: AngleMode::Radians, rounding: Rounding::HalfAway });
+        let call = |name, x, context| lookup(name).unwrap().call(&[x], context);
+        assert_eq!(call("sin", 90.0, degrees), 1.0);
+        assert_eq!(call("sin", 180.0, degrees), 0.0);
+        assert_eq!(call("cos", -90.0, degrees), 0.0);
+        assert!((call("sin", 30.0, degrees) - 0.5).abs() < 1e-15);
+        assert!(call("tan", 90.0, degrees).is_infinite());
+        assert_eq!(call("asin", 1.0, degrees), 90.0);
+        assert_eq!(call("atan", 1.0, radians), PI / 4.0);
+        assert!((call("sin", 90.0, radians) - 90f64.sin()).abs() < 1e-15);
+        assert_eq!(call("sqrt", 16.0, degrees), 4.0);
+    }
+
+    #[test]
+    fn rounding() {
+        let even = EvalContext { angle: AngleMode::Radians, rounding: Rounding::HalfEven };
+        let away = EvalContext { rounding: Rounding::HalfAway, ..even };
+        let call = |name, args: &[f64], context| lookup(name).unwrap().call(args, context);
+        assert_eq!(call("rnd", &[1.23456, 2.0], away), 1.23);
+        assert_eq!(call("rnd", &[2.675, 2.0], away), 2.68);
+        assert_eq!(call("rnd", &[1234.5, -2.0], away), 1200.0);
+        assert!(call("rnd", &[1.5, 0.5], away).is_nan());
+        assert_eq!((call("round", &[2.5], away), call("round", &[-2.5], away)), (3.0, -3.0));
+        assert_eq!((call("round", &[2.5], even), call("round", &[3.5], even), call("round", &[-2.5], even)), (2.0, 4.0, -2.0));
+        assert_eq!(call("rnd", &[0.125, 2.0], even), 0.12);
+        assert_eq!((call("floor", &[-1.5], away), call("ceil", &[-1.5], away), call("trunc", &[-1.5], away)), (-2.0, -1.0, -1.0));
+    }
+
+    #[test]
+    fn logarithm_of_any_base() {
+        let logb = lookup("logb").unwrap();
+        assert_eq!(log_base(1024.0, 2.0), 10.0);
+        assert_eq!(log_base(1000.0, 10.0), 3.0);
+        assert!((log_base(81.0, 3.0) - 4.0).abs() < 1e-15);
+        assert!(logb.check_domain(&[8.0, 2.0]).is_ok());
+        for args in [[0.0, 2.0], [-8.0, 2.0], [8.0, 1.0], [8.0, -2.0], [f64::NAN, 2.0]] {
+            assert_eq!(logb.check_domain(&args).unwrap_err().code(), "E211");
+        }
+    }
+
+    #[test]
+    fn temperatures_convert_both_ways() {
+        let call = |name, x| (lookup(name).unwrap().apply)(&[x]);
+        assert_eq!((call("c2f", 100.0), call("c2f", -40.0), call("c2f", 37.0)), (212.0, -40.0, 98.6));
+        assert_eq!((call("f2c", 212.0), call("f2c", 32.0)), (100.0, 0.0));
+        assert_eq!((call("c2k", 0.0), call("k2c", 0.0)), (273.15, -273.15));
+        assert_eq!((call("k2c", 300.0), call("c2k", 36.6), call("c2f", 36.6)), (26.85, 309.75, 97.88));
+        assert!(lookup("k2c").unwrap().check_domain(&[-1.0]).is_err());
+        assert!(lookup("f2c").unwrap().check_domain(&[-459.67]).is_ok());
+        assert_eq!(conversion_label("f2c"), Some("°F → °C"));
+    }
+
+    #[test]
+    fn decibels_of_power_and_amplitude() {
+        let call = |name, x| (lookup(name).unwrap().apply)(&[x]);
+        assert_eq!((call("db", 100.0), call("dbv", 100.0)), (20.0, 40.0));
+        assert_eq!((call("undb", 20.0), call("undbv", 20.0)), (100.0, 10.0));
+        assert!((call("db", 2.0) - 3.0103).abs() < 1e-4);
+        assert_eq!((call("dbm2w", 30.0), call("dbm2w", 0.0), call("dbm2w", -30.0)), (1.0, 0.001, 1e-6));
+        assert_eq!((call("w2dbm", 1.0), call("w2dbm", 0.001)), (30.0, 0.0));
+        assert!((call("undb", call("db", 7.5)) - 7.5).abs() < 1e-12);
+        assert_eq!(lookup("w2dbm").unwrap().check_domain(&[0.0]).unwrap_err().code(), "E211");
+    }
+}
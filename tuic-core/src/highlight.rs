@@ -0,0 +1,107 @@
+use crate::dms;
+
+/// Lexical class of a stretch of input, used only for coloring.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenKind {
+    Number,
+    Operator,
+    Function,
+    Variable,
+    Paren,
+    Separator,
+    Command,
+    Whitespace,
+    Comment, // A trailing `# comment`
+    Unknown,
+}
+
+/// A classified run of characters; `start..end` are char (not byte) indices.
+#[derive(Debug, Clone, Copy)]
+pub struct Highlight {
+    pub kind: TokenKind,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Splits input into colored runs. Unlike the evaluator's tokenizer this never
+/// fails, so half-typed expressions still highlight.
+pub fn highlight(input: &str) -> Vec<Highlight> {
+    let chars: Vec<char> = input.chars().collect();
+    if chars.first() == Some(&':') {
+        return vec![Highlight { kind: TokenKind::Command, start: 0, end: chars.len() }];
+    }
+
+    let mut runs = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let start = i;
+        let ch = chars[i];
+        let kind = if ch == '#' {
+            i = chars.len();
+            TokenKind::Comment
+        } else if ch.is_ascii_digit() || ch == '.' {
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.' || chars[i] == '_' || dms::is_dms_char(chars[i])) {
+                i += 1;
+            }
+            TokenKind::Number
+        } else if ch.is_ascii_alphabetic() || ch == '_' {
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let next = chars[i..].iter().find(|c| **c != ' ');
+            if next == Some(&'(') { TokenKind::Function } else { TokenKind::Variable }
+        } else {
+            i += 1;
+            match ch {
+                '+' | '-' | '*' | '/' | '^' | '<' | '>' | '=' | '!' | '±' | '∠' => TokenKind::Operator,
+                '(' | ')' | '[' | ']' => TokenKind::Paren,
+                ',' => TokenKind::Separator,
+                c if c.is_whitespace() => TokenKind::Whitespace,
+                _ => TokenKind::Unknown,
+            }
+        };
+        runs.push(Highlight { kind, start, end: i });
+    }
+    runs
+}
+
+/// Index of the parenthesis paired with the one at `index`, if any.
+pub fn matching_paren(chars: &[char], index: usize) -> Option<usize> {
+    let (open, close, forward) = match chars.get(index)? {
+        '(' => ('(', ')', true),
+        ')' => (')', '(', false),
+        _ => return None,
+    };
+    let mut depth = 0;
+    let indices: Box<dyn Iterator<Item = usize>> = if forward {
+        Box::new(index..chars.len())
+    } else {
+        Box::new((0..=index).rev())
+    };
+    for i in indices {
+        if chars[i] == open {
+            depth += 1;
+        } else if chars[i] == close {
+            depth -= 1;
+            if depth == 0 {
+                return Some(i);
+            }
+        }
+    }
+    None
+}
+
+/// Parentheses that have no partner anywhere in the input.
+pub fn unmatched_parens(chars: &[char]) -> Vec<usize> {
+    let mut open = Vec::new();
+    let mut unmatched = Vec::new();
+    for (i, ch) in chars.iter().enumerate() {
+        match ch {
+            '(' => open.push(i),
+            ')' if open.pop().is_none() => unmatched.push(i),
+            _ => {}
+        }
+    }
+    unmatched.extend(open);
+    unmatched
+}
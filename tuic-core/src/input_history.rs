@@ -0,0 +1,84 @@
+//! Lines submitted from the input, one per Enter and whether they worked or
+//! not, for Alt+Up/Down to bring back as readline does. The history panel only
+//! keeps results, so a line that failed can only be fixed from here. It lasts
+//! for the session and isn't saved.
+
+const MAX_LINES: usize = 200;
+
+#[derive(Debug, Default)]
+pub struct InputHistory {
+    lines: Vec<String>,
+    position: Option<usize>, // Line being shown; None while typing a new one
+    draft: String,           // What was typed before browsing, given back past the newest line
+}
+
+impl InputHistory {
+    /// Adds a submitted line and stops browsing; blank lines and a repeat of the newest aren't stored.
+    pub fn record(&mut self, line: &str) {
+        self.position = None;
+        if line.trim().is_empty() || self.lines.last().is_some_and(|last| last == line) {
+            return;
+        }
+        if self.lines.len() >= MAX_LINES {
+            self.lines.remove(0);
+        }
+        self.lines.push(line.to_string());
+    }
+
+    /// The line before the one shown; when browsing starts, `current` is kept to come back to.
+    pub fn older(&mut self, current: &str) -> Option<String> {
+        let position = match self.position {
+            None if self.lines.is_empty() => return None,
+            None => {
+                self.draft = current.to_string();
+                self.lines.len() - 1
+            }
+            Some(position) => position.saturating_sub(1),
+        };
+        self.position = Some(position);
+        Some(self.lines[position].clone())
+    }
+
+    /// The line after the one shown, and past the newest the line that was being typed.
+    pub fn newer(&mut self) -> Option<String> {
+        let position = self.position?;
+        if position + 1 < self.lines.len() {
+            self.position = Some(position + 1);
+            Some(self.lines[position + 1].clone())
+        } else {
+            self.position = None;
+            Some(std::mem::take(&mut self.draft))
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn browses_back_and_returns_to_the_draft() {
+        let mut history = InputHistory::default();
+        assert_eq!(history.older("2 +"), None);
+        for line in ["1 + 1", "sqrt(-", "sqrt(-", " ", "2 * 3"] {
+            history.record(line);
+        }
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.older("4 /").as_deref(), Some("2 * 3"));
+        assert_eq!(history.older("").as_deref(), Some("sqrt(-"));
+        assert_eq!(history.older("").as_deref(), Some("1 + 1"));
+        assert_eq!(history.older("").as_deref(), Some("1 + 1"));
+        assert_eq!(history.newer().as_deref(), Some("sqrt(-"));
+        assert_eq!(history.newer().as_deref(), Some("2 * 3"));
+        assert_eq!(history.newer().as_deref(), Some("4 /"));
+        assert_eq!(history.newer(), None);
+    }
+}
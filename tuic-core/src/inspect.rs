@@ -0,0 +1,170 @@
+//! The inspect popup ('i'): one stack value in every base and notation at once.
+
+use crate::calculator::{AngleMode, ComplexNumber, StackValue, WordSize};
+use crate::vector;
+
+/// Largest denominator tried for the fraction approximation.
+const MAX_DENOMINATOR: i64 = 1_000_000;
+/// Engineering mantissas are rounded to this many decimals to hide float noise.
+const MANTISSA_DECIMALS: i32 = 9;
+
+/// Label and text for each row of the popup.
+pub fn rows(value: &StackValue, word_size: WordSize, angle: AngleMode) -> Vec<(&'static str, String)> {
+    let real = match value {
+        StackValue::Complex(c) if c.imag != 0.0 => return complex_rows(*c, angle),
+        StackValue::Vector(items) => return vector_rows(items),
+        _ => value.as_real().unwrap_or(f64::NAN),
+    };
+    let mut rows = vec![("Decimal", real.to_string())];
+    match whole(real) {
+        Some(integer) => {
+            let width = word_size.bits().map_or(String::new(), |bits| format!(" ({}-bit)", bits));
+            rows.push(("Hexadecimal", format!("0x{:X}{}", word_size.to_pattern(integer), width)));
+            rows.push(("Octal", format!("0o{:o}{}", word_size.to_pattern(integer), width)));
+            rows.push(("Binary", format!("0b{}{}", grouped_binary(word_size.to_pattern(integer)), width)));
+        }
+        None => rows.push(("Hex/oct/bin", "not an integer".to_string())),
+    }
+    rows.push(("Scientific", format!("{:e}", real)));
+    rows.push(("Engineering", engineering(real)));
+    rows.push(("Fraction", fraction_text(real)));
+    if let StackValue::Uncertain(m) = value {
+        let relative = if m.value != 0.0 { format!(" ({:.2}%)", 100.0 * m.sigma / m.value.abs()) } else { String::new() };
+        rows.push(("Uncertainty", format!("± {}{}", m.sigma, relative)));
+    }
+    rows.extend(complex_rows(ComplexNumber::new(real, 0.0), angle).into_iter().skip(1));
+    rows
+}
+
+/// A vector's summary; the popup lists the elements after it.
+fn vector_rows(items: &[f64]) -> Vec<(&'static str, String)> {
+    let mut rows = vec![("Length", items.len().to_string()), ("Norm", vector::norm(items).to_string()), ("Sum", items.iter().sum::<f64>().to_string())];
+    if !items.is_empty() {
+        rows.push(("Min", items.iter().copied().fold(f64::INFINITY, f64::min).to_string()));
+        rows.push(("Max", items.iter().copied().fold(f64::NEG_INFINITY, f64::max).to_string()));
+    }
+    rows
+}
+
+fn complex_rows(c: ComplexNumber, angle: AngleMode) -> Vec<(&'static str, String)> {
+    let sign = if c.imag.is_sign_negative() { '-' } else { '+' };
+    let (phase, unit) = match angle {
+        AngleMode::Radians => (c.phase(), " rad"),
+        AngleMode::Degrees => (c.phase().to_degrees(), "°"),
+    };
+    vec![
+        ("Real", c.real.to_string()),
+        ("Imaginary", c.imag.to_string()),
+        ("Rectangular", format!("{} {} {}i", c.real, sign, c.imag.abs())),
+        ("Polar", format!("{} ∠ {}{}", c.magnitude(), phase, unit)),
+    ]
+}
+
+fn whole(value: f64) -> Option<i64> {
+    (value.fract() == 0.0 && value.abs() < i64::MAX as f64).then_some(value as i64)
+}
+
+/// Nibbles separated by spaces: 1010 0101.
+fn grouped_binary(pattern: u64) -> String {
+    let digits = format!("{:b}", pattern);
+    let padded = format!("{:0>width$}", digits, width = digits.len().div_ceil(4) * 4);
+    padded.as_bytes().chunks(4).map(|chunk| String::from_utf8_lossy(chunk).into_owned()).collect::<Vec<_>>().join(" ")
+}
+
+/// Scientific notation with the exponent a multiple of three: 12.5e3.
+pub fn engineering(value: f64) -> String {
+    if value == 0.0 || !value.is_finite() {
+        return format!("{}e0", value);
+    }
+    let exponent = (value.abs().log10() / 3.0).floor() as i32 * 3;
+    let scale = 10f64.powi(MANTISSA_DECIMALS);
+    let mantissa = (value / 10f64.powi(exponent) * scale).round() / scale;
+    format!("{}e{}", mantissa, exponent)
+}
+
+/// The closest fraction with a denominator up to `MAX_DENOMINATOR`, from
+/// the continued fraction expansion: 3.14159… gives 355/113 before 103993/33102.
+pub fn fraction(value: f64) -> Option<(i64, i64)> {
+    convergents(value).pop()
+}
+
+/// The simplest fraction within `tolerance` of `value`, for recognizing
+/// results: 0.333333 is 1/3 within 1e-6.
+pub fn simplest_fraction(value: f64, tolerance: f64) -> Option<(i64, i64)> {
+    convergents(value).into_iter().find(|(numerator, denominator)| (*numerator as f64 / *denominator as f64 - value).abs() <= tolerance)
+}
+
+/// Successive best approximations of `value`, each with a larger denominator,
+/// up to `MAX_DENOMINATOR` or the first that is exact.
+fn convergents(value: f64) -> Vec<(i64, i64)> {
+    let mut found = Vec::new();
+    if !value.is_finite() || value.abs() >= MAX_DENOMINATOR as f64 * MAX_DENOMINATOR as f64 {
+        return found;
+    }
+    let (mut h0, mut h1, mut k0, mut k1) = (0i64, 1i64, 1i64, 0i64);
+    let mut x = value;
+    loop {
+        let a = x.floor();
+        let (h2, k2) = (a as i64 * h1 + h0, a as i64 * k1 + k0);
+        if k2 > MAX_DENOMINATOR {
+            break;
+        }
+        (h0, h1, k0, k1) = (h1, h2, k1, k2);
+        found.push((h1, k1));
+        let rest = x - a;
+        if rest.abs() < 1e-12 || (h1 as f64 / k1 as f64 - value).abs() <= f64::EPSILON * value.abs() {
+            break;
+        }
+        x = 1.0 / rest;
+    }
+    found
+}
+
+fn fraction_text(value: f64) -> String {
+    match fraction(value) {
+        Some((numerator, denominator)) => {
+            let error = numerator as f64 / denominator as f64 - value;
+            if error == 0.0 {
+                format!("{}/{}", numerator, denominator)
+            } else {
+                format!("≈ {}/{} (off by {:.1e})", numerator, denominator, error)
+            }
+        }
+        None => "none".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    #[test]
+    fn notations() {
+        assert_eq!(engineering(12500.0), "12.5e3");
+        assert_eq!(engineering(-0.00047), "-470e-6");
+        assert_eq!(engineering(1.0), "1e0");
+        assert_eq!(fraction(0.75), Some((3, 4)));
+        assert_eq!(fraction(-2.5), Some((-5, 2)));
+        assert_eq!(fraction(PI), Some((1146408, 364913)));
+        assert_eq!(fraction_text(0.375), "3/8");
+        assert_eq!(simplest_fraction(0.333333, 1e-6), Some((1, 3)));
+        assert_eq!(simplest_fraction(PI, 2e-3), Some((22, 7)));
+        assert_eq!(simplest_fraction(PI, 1e-6), Some((355, 113)));
+        assert_eq!(simplest_fraction(PI, 0.0), None);
+        assert!(fraction_text(PI).starts_with("≈ 1146408/364913 (off by"));
+        assert_eq!(grouped_binary(0xA5), "1010 0101");
+        assert_eq!(grouped_binary(5), "0101");
+    }
+
+    #[test]
+    fn rows_for_each_kind() {
+        let rows = rows(&StackValue::Real(-1.0), WordSize::Bits8, AngleMode::Degrees);
+        assert!(rows.contains(&("Hexadecimal", "0xFF (8-bit)".to_string())));
+        assert!(rows.contains(&("Octal", "0o377 (8-bit)".to_string())));
+        assert!(rows.contains(&("Polar", "1 ∠ 180°".to_string())));
+        let complex = super::rows(&StackValue::Complex(ComplexNumber::new(0.0, -2.0)), WordSize::Unbounded, AngleMode::Radians);
+        assert!(complex.contains(&("Rectangular", "0 - 2i".to_string())));
+        assert_eq!(super::rows(&StackValue::Real(0.5), WordSize::Unbounded, AngleMode::Radians)[1], ("Hex/oct/bin", "not an integer".to_string()));
+    }
+}
@@ -0,0 +1,216 @@
+//! Crash recovery. While the calculator runs, its session (every workspace's
+//! stack and history, the modes and the half-typed input) is kept in
+//! `session.journal` in the config directory, rewritten whenever it changes.
+//! A clean exit deletes the file, so finding one at startup means the previous
+//! session was cut off, by a crash or a dropped SSH connection.
+//!
+//! Each running instance holds a lock on its journal's `.lock` file. A second
+//! instance skips a locked journal, which is in use rather than left by a crash,
+//! and takes `session-2.journal`, and so on.
+
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::calculator::{AngleMode, BaseMode, Calculator, CalculatorMode, ComplexMode, HistoryEntry, WordSize};
+use crate::stack::Stack;
+use crate::undo::UndoLog;
+use crate::versioned;
+use crate::workspace::Workspace;
+
+/// Journals that can be in use at once, one per running instance.
+const MAX_SLOTS: usize = 16;
+
+/// The journal this instance writes, locked against other instances until dropped.
+pub struct Slot {
+    pub path: PathBuf,
+    _lock: File,
+}
+
+/// Takes the first journal in `dir` that no running instance holds:
+/// `session.journal`, then `session-2.journal`, ...
+pub fn claim(dir: &Path) -> Result<Slot> {
+    fs::create_dir_all(dir)?;
+    for slot in 1..=MAX_SLOTS {
+        let name = if slot == 1 { "session".to_string() } else { format!("session-{}", slot) };
+        let lock = OpenOptions::new().create(true).truncate(false).write(true).open(dir.join(format!("{}.lock", name)))?;
+        match lock.try_lock() {
+            Ok(()) => return Ok(Slot { path: dir.join(format!("{}.journal", name)), _lock: lock }),
+            Err(TryLockError::WouldBlock) => continue,
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+    }
+    Err(anyhow!("all {} session journals are in use", MAX_SLOTS))
+}
+
+/// A workspace's entries; undo steps aren't kept.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct JournalWorkspace {
+    pub name: String,
+    pub stack: Stack,
+    pub history: Vec<HistoryEntry>,
+}
+
+/// Everything needed to pick the session up where it stopped.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Journal {
+    pub mode: CalculatorMode,
+    pub angle: AngleMode,
+    pub base: BaseMode,
+    pub complex: ComplexMode,
+    pub word_size: WordSize,
+    pub precision: Option<usize>,
+    pub input: String,
+    pub cursor: usize,
+    pub workspaces: Vec<JournalWorkspace>, // The active one first
+}
+
+impl Journal {
+    pub fn capture(calculator: &Calculator) -> Self {
+        let active = JournalWorkspace { name: calculator.workspace.clone(), stack: calculator.stack.clone(), history: calculator.history.clone() };
+        let parked = calculator.parked_workspaces.iter().map(|workspace| JournalWorkspace {
+            name: workspace.name.clone(),
+            stack: workspace.stack.clone(),
+            history: workspace.history.clone(),
+        });
+        Self {
+            mode: calculator.mode,
+            angle: calculator.angle_mode,
+            base: calculator.base_mode,
+            complex: calculator.complex_mode,
+            word_size: calculator.word_size,
+            precision: calculator.precision,
+            input: calculator.input.clone(),
+            cursor: calculator.cursor,
+            workspaces: std::iter::once(active).chain(parked).collect(),
+        }
+    }
+
+    /// Puts the journaled session in place of the calculator's current one.
+    pub fn restore(self, calculator: &mut Calculator) {
+        let mut workspaces = self.workspaces.into_iter();
+        let Some(active) = workspaces.next() else {
+            return;
+        };
+        calculator.mode = self.mode;
+        calculator.angle_mode = self.angle;
+        calculator.base_mode = self.base;
+        calculator.complex_mode = self.complex;
+        calculator.word_size = self.word_size;
+        calculator.precision = self.precision;
+        calculator.cursor = self.cursor.min(self.input.chars().count());
+        calculator.input = self.input;
+        calculator.workspace = active.name;
+        calculator.stack = active.stack;
+        calculator.history = active.history;
+        calculator.apply_stack_limit();
+        calculator.undo = UndoLog::default();
+        calculator.parked_workspaces = workspaces
+            .map(|mut workspace| {
+                workspace.stack.set_limit(calculator.stack_limit);
+                Workspace { name: workspace.name, stack: workspace.stack, history: workspace.history, undo: UndoLog::default() }
+            })
+            .collect();
+        // New entries are numbered after every restored one
+        calculator.last_entry_id = std::iter::once(&calculator.stack)
+            .chain(calculator.parked_workspaces.iter().map(|workspace| &workspace.stack))
+            .flat_map(|stack| stack.iter().map(|entry| entry.id))
+            .max()
+            .unwrap_or(0);
+        calculator.stack_position = 0;
+        calculator.rapid_start = None;
+        calculator.history_position = calculator.history.len();
+        calculator.error = None;
+    }
+
+    /// Stack entries across all workspaces, for the restore prompt.
+    pub fn entry_count(&self) -> usize {
+        self.workspaces.iter().map(|workspace| workspace.stack.len()).sum()
+    }
+}
+
+/// The journal left by an interrupted session, if there is one.
+pub fn load(path: &Path) -> Result<Option<Journal>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => versioned::JOURNAL.parse(&contents).map(Some).map_err(|e| anyhow!("{}: {}", path.display(), e)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Replaces the journal through a temporary file, so a crash mid-write leaves the previous one.
+pub fn write(path: &Path, contents: &str) -> Result<()> {
+    let dir = path.parent().ok_or_else(|| anyhow!("{} has no parent directory", path.display()))?;
+    fs::create_dir_all(dir)?;
+    let temporary = path.with_extension("journal.tmp");
+    fs::write(&temporary, contents)?;
+    fs::rename(&temporary, path)?;
+    Ok(())
+}
+
+pub fn remove(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let dir = std::env::temp_dir().join(format!("tuic-journal-{}", std::process::id()));
+        let path = dir.join("session.journal");
+        assert!(load(&path).unwrap().is_none());
+
+        let mut calculator = Calculator::new();
+        calculator.mode = CalculatorMode::Infix;
+        for ch in "2*(3+4)".chars() {
+            calculator.handle_char_input(ch);
+        }
+        calculator.enter();
+        calculator.create_workspace("side");
+        calculator.push(5.0);
+        for ch in "1 + sq".chars() {
+            calculator.handle_char_input(ch);
+        }
+        write(&path, &versioned::JOURNAL.to_string(&Journal::capture(&calculator)).unwrap()).unwrap();
+
+        let mut restored = Calculator::new();
+        let journal = load(&path).unwrap().expect("just written");
+        assert_eq!(journal.entry_count(), 2);
+        journal.restore(&mut restored);
+        assert_eq!((restored.mode, restored.input.as_str(), restored.cursor), (CalculatorMode::Infix, "1 + sq", 6));
+        assert_eq!(restored.workspace, "side");
+        assert_eq!(restored.stack[0].result.as_real(), Some(5.0));
+        assert_eq!(&*restored.parked_workspaces[0].stack[0].expression, "2*(3+4)");
+        assert_eq!(restored.display_expression(&restored.parked_workspaces[0].stack[0]), "2 * (3 + 4)");
+        assert_eq!(restored.parked_workspaces[0].history.len(), 1);
+        restored.push(1.0); // Numbered after the parked entry, not from 1 again
+        assert_eq!((restored.parked_workspaces[0].stack[0].id, restored.stack[1].id), (1, 3));
+
+        remove(&path).unwrap();
+        remove(&path).unwrap();
+        assert!(load(&path).unwrap().is_none());
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn one_journal_per_running_instance() {
+        let dir = std::env::temp_dir().join(format!("tuic-journal-slots-{}", std::process::id()));
+        let first = claim(&dir).unwrap();
+        let second = claim(&dir).unwrap();
+        assert_eq!(first.path, dir.join("session.journal"));
+        assert_eq!(second.path, dir.join("session-2.journal"));
+        // Once the first instance is gone, its journal is free for the next one to recover
+        drop(first);
+        assert_eq!(claim(&dir).unwrap().path, dir.join("session.journal"));
+        drop(second);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,55 @@
+//! Calculator engine for tui-calculator: the RPN/infix stack, the expression
+//! parser and evaluator, and the data behind the app's popups. Nothing here
+//! depends on a terminal, so the engine can be embedded and tested headlessly.
+
+pub mod angle;
+pub mod backup;
+pub mod calculator;
+pub mod compare;
+pub mod completion;
+pub mod complex;
+pub mod config;
+pub mod confirm;
+pub mod context;
+pub mod currency;
+pub mod datasize;
+pub mod digest;
+pub mod dms;
+pub mod editor;
+pub mod error;
+pub mod export;
+pub mod expr;
+pub mod find;
+pub mod float_inspect;
+pub mod functions;
+pub mod highlight;
+pub mod input_history;
+pub mod inspect;
+pub mod journal;
+pub mod limits;
+pub mod lint;
+pub mod locale;
+pub mod network;
+pub mod number;
+pub mod numeric;
+pub mod parse_cache;
+pub mod plot;
+pub mod polynomial;
+pub mod primes;
+pub mod result_stats;
+pub mod settings;
+pub mod shared_history;
+pub mod stack;
+pub mod table;
+pub mod template;
+pub mod uncertainty;
+pub mod undo;
+pub mod usage;
+pub mod vector;
+pub mod versioned;
+pub mod warning;
+pub mod workspace;
+
+pub use calculator::{AngleMode, BaseMode, Calculator, CalculatorMode, ComplexMode, ComplexNumber, EntryModes, EntryOrigin, HistoryEntry, InputValidity, StackEntry, StackValue, WordSize, config_dir, session_label, session_marker};
+pub use error::CalculatorError;
+pub use warning::Warning;
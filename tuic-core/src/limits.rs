@@ -0,0 +1,80 @@
+//! Stack and history limits (`:limits`): how many entries each keeps before
+//! dropping the oldest, stepped through a few sizes or set to unlimited.
+
+use crate::stack::UNLIMITED;
+
+/// The sizes Left/Right step through in the popup.
+pub const PRESETS: [usize; 7] = [100, 500, 1000, 5000, 10_000, 100_000, UNLIMITED];
+
+/// A limit as shown and as written in the config file.
+pub fn label(limit: usize) -> String {
+    if limit == UNLIMITED { "unlimited".to_string() } else { limit.to_string() }
+}
+
+/// A positive number or "unlimited".
+pub fn parse(text: &str) -> Option<usize> {
+    match text {
+        "unlimited" => Some(UNLIMITED),
+        _ => text.parse().ok().filter(|limit| *limit > 0),
+    }
+}
+
+/// The next preset above or below `limit`, which needn't be one of them.
+pub fn step(limit: usize, up: bool) -> usize {
+    if up {
+        PRESETS.into_iter().find(|preset| *preset > limit).unwrap_or(UNLIMITED)
+    } else {
+        PRESETS.into_iter().rev().find(|preset| *preset < limit).unwrap_or(PRESETS[0])
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Stack,
+    History,
+}
+
+impl Limit {
+    pub const ALL: [Limit; 2] = [Limit::Stack, Limit::History];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Limit::Stack => "Stack entries",
+            Limit::History => "History entries",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LimitsDialog {
+    pub selected: usize, // Index into Limit::ALL
+}
+
+impl LimitsDialog {
+    pub fn move_selection(&mut self, down: bool) {
+        self.selected = if down { (self.selected + 1).min(Limit::ALL.len() - 1) } else { self.selected.saturating_sub(1) };
+    }
+
+    pub fn selected_limit(&self) -> Limit {
+        Limit::ALL[self.selected]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_through_the_presets() {
+        assert_eq!(step(1000, true), 5000);
+        assert_eq!(step(1000, false), 500);
+        assert_eq!(step(750, true), 1000);
+        assert_eq!(step(100_000, true), UNLIMITED);
+        assert_eq!(step(UNLIMITED, true), UNLIMITED);
+        assert_eq!(step(UNLIMITED, false), 100_000);
+        assert_eq!(step(100, false), 100);
+        assert_eq!(parse("unlimited"), Some(UNLIMITED));
+        assert_eq!(parse("0"), None);
+        assert_eq!(label(parse("250").unwrap()), "250");
+    }
+}
@@ -0,0 +1,199 @@
+use crate::calculator::{BaseMode, split_comment};
+use crate::functions::{self, CONSTANTS, FUNCTIONS, SERIES, SPECIAL_FORMS, VARIADIC, VECTOR};
+use crate::highlight;
+use crate::locale::{self, DecimalSeparator};
+
+/// A hint for input that failed to evaluate, with the corrected input if there is one.
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+    pub message: String,
+    pub fix: Option<String>,
+}
+
+/// Looks for the most likely cause of an evaluation error. Checks run from
+/// structural (parentheses) to lexical (names, number prefixes). A trailing
+/// `# comment` is left out of the checks and kept in the fix.
+pub fn lint(input: &str, base_mode: BaseMode, separator: DecimalSeparator) -> Option<Suggestion> {
+    let (code, _) = split_comment(input);
+    let mut suggestion = lint_code(code, base_mode, separator)?;
+    if let Some(fix) = &mut suggestion.fix {
+        fix.push_str(&input[code.len()..]);
+    }
+    Some(suggestion)
+}
+
+fn lint_code(input: &str, base_mode: BaseMode, separator: DecimalSeparator) -> Option<Suggestion> {
+    let chars: Vec<char> = input.chars().collect();
+    if let Some(suggestion) = unbalanced_parens(&chars) {
+        return Some(suggestion);
+    }
+    let separator_hint = match separator {
+        DecimalSeparator::Point => decimal_comma(&chars),
+        DecimalSeparator::Comma => decimal_point(&chars),
+    };
+    if separator_hint.is_some() {
+        return separator_hint;
+    }
+
+    let mut i = 0;
+    while i < chars.len() {
+        if !(chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+            i += 1;
+        }
+        let word: String = chars[start..i].iter().collect();
+        let is_call = chars[i..].iter().find(|c| **c != ' ') == Some(&'(');
+        if let Some(suggestion) = check_word(input, &chars, start, i, &word, is_call, base_mode) {
+            return Some(suggestion);
+        }
+    }
+    None
+}
+
+/// `3,14` outside a call or vector: a decimal comma where the comma only separates arguments.
+fn decimal_comma(chars: &[char]) -> Option<Suggestion> {
+    let mut depth = 0;
+    for (i, ch) in chars.iter().enumerate() {
+        match ch {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            ',' if depth == 0 && i > 0 && chars[i - 1].is_ascii_digit() && chars.get(i + 1).is_some_and(char::is_ascii_digit) => {
+                let mut fixed = chars.to_vec();
+                fixed[i] = '.';
+                return Some(Suggestion {
+                    message: "decimals are written with `.` (or `:set decimal comma`)".to_string(),
+                    fix: Some(fixed.into_iter().collect()),
+                });
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// `3.14` typed with a decimal comma set: a point that doesn't group thousands.
+fn decimal_point(chars: &[char]) -> Option<Suggestion> {
+    let mut i = 0;
+    while i < chars.len() {
+        let start = i;
+        while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.' || chars[i] == ',') {
+            i += 1;
+        }
+        let number: String = chars[start..i].iter().collect();
+        if number.contains('.') && !number.contains(',') && locale::parse_comma_number(&number).is_none() {
+            let fixed = chars[..start].iter().collect::<String>() + &number.replace('.', ",") + &chars[i..].iter().collect::<String>();
+            return Some(Suggestion { message: "decimals are written with `,` (or `:set decimal point`)".to_string(), fix: Some(fixed) });
+        }
+        i = i.max(start + 1);
+    }
+    None
+}
+
+fn unbalanced_parens(chars: &[char]) -> Option<Suggestion> {
+    let unmatched = highlight::unmatched_parens(chars);
+    let first = *unmatched.iter().min()?;
+    if chars[first] == ')' {
+        let mut fixed = chars.to_vec();
+        fixed.remove(first);
+        return Some(Suggestion {
+            message: format!("unmatched `)` at position {}", first + 1),
+            fix: Some(fixed.into_iter().collect()),
+        });
+    }
+    // Every remaining unmatched paren is an unclosed '(' once no stray ')' is left
+    let missing = unmatched.len();
+    let fixed: String = chars.iter().collect::<String>() + &")".repeat(missing);
+    Some(Suggestion {
+        message: format!("unbalanced `(` opened at position {}", first + 1),
+        fix: Some(fixed),
+    })
+}
+
+fn check_word(input: &str, chars: &[char], start: usize, end: usize, word: &str, is_call: bool, base_mode: BaseMode) -> Option<Suggestion> {
+    let replace = |replacement: &str| -> String {
+        chars[..start].iter().collect::<String>() + replacement + &chars[end..].iter().collect::<String>()
+    };
+
+    if word.starts_with(|c: char| c.is_ascii_digit()) {
+        // 1A or 0FF in DEC mode: probably meant as hexadecimal
+        let hex_like = word.chars().all(|c| c.is_ascii_hexdigit()) && word.chars().any(|c| c.is_ascii_alphabetic());
+        if hex_like && base_mode == BaseMode::Decimal {
+            return Some(hex_prefix(word, replace));
+        }
+        return None;
+    }
+
+    if is_call {
+        if functions::lookup(word).is_some() || SPECIAL_FORMS.iter().chain(VARIADIC).chain(SERIES).chain(VECTOR).any(|(name, _)| *name == word) {
+            return None;
+        }
+        let names = FUNCTIONS.iter().map(|function| function.name).chain(SPECIAL_FORMS.iter().chain(VARIADIC).chain(SERIES).chain(VECTOR).map(|(name, _)| *name));
+        return closest(word, names).map(|name| Suggestion {
+            message: format!("unknown function `{}`, did you mean `{}(`?", word, name),
+            fix: Some(replace(name)),
+        });
+    }
+
+    // s1, s2, ...: stack references
+    let stack_reference = word.len() > 1 && word.starts_with('s') && word[1..].chars().all(|c| c.is_ascii_digit());
+    if functions::constant(word).is_some() || word == "x" || word == "i" || stack_reference {
+        return None;
+    }
+    if word.chars().all(|c| c.is_ascii_hexdigit()) && base_mode == BaseMode::Decimal {
+        return Some(hex_prefix(word, replace));
+    }
+    // A function name used without parentheses: sqrt 2
+    if functions::lookup(word).is_some() {
+        let rest = input[input.char_indices().nth(end).map_or(input.len(), |(i, _)| i)..].trim();
+        return Some(Suggestion {
+            message: format!("`{}` is a function, did you mean `{}({})`?", word, word, rest),
+            fix: Some(chars[..start].iter().collect::<String>() + word + "(" + rest + ")"),
+        });
+    }
+    closest(word, CONSTANTS.iter().map(|(name, _)| *name)).map(|name| Suggestion {
+        message: format!("unknown variable `{}`, did you mean `{}`?", word, name),
+        fix: Some(replace(name)),
+    })
+}
+
+fn hex_prefix(word: &str, replace: impl Fn(&str) -> String) -> Suggestion {
+    let prefixed = format!("0x{}", word);
+    Suggestion {
+        message: format!("`{}` looks hexadecimal, use the `0x` prefix in DEC mode: `{}`", word, prefixed),
+        fix: Some(replace(&prefixed)),
+    }
+}
+
+// Nearest name within an edit distance that still reads as a typo
+fn closest<'a>(word: &str, names: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let limit = if word.len() <= 3 { 1 } else { 2 };
+    names
+        .map(|name| (edit_distance(word, name), name))
+        .filter(|(distance, _)| *distance <= limit)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, name)| name)
+}
+
+// Levenshtein distance where swapping two adjacent letters (lgo/log) counts as one edit
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    d[0] = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
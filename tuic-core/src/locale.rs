@@ -0,0 +1,217 @@
+//! Decimal comma, as most of Europe writes numbers: `3,14` with `.` grouping
+//! thousands (`1.234,5`), and `;` between function arguments (`max(1,5; 2)`)
+//! since the comma is taken. Values are kept and computed the same either way;
+//! only what's typed and shown changes.
+
+use std::env;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum DecimalSeparator {
+    #[default]
+    Point,
+    Comma,
+}
+
+/// Languages that write a decimal comma, by their locale prefix.
+const COMMA_LANGUAGES: &[&str] = &["bg", "ca", "cs", "da", "de", "el", "es", "et", "eu", "fi", "fr", "gl", "hr", "hu", "id", "is", "it", "lt", "lv", "nb", "nl", "nn", "no", "pl", "pt", "ro", "ru", "sk", "sl", "sr", "sv", "tr", "uk", "vi"];
+
+impl DecimalSeparator {
+    /// From a locale name such as `de_DE.UTF-8`; English, `C` and `POSIX` use a point.
+    pub fn from_locale(locale: &str) -> Self {
+        let language = locale.split(['_', '.', '@', '-']).next().unwrap_or("");
+        if COMMA_LANGUAGES.contains(&language) { DecimalSeparator::Comma } else { DecimalSeparator::Point }
+    }
+
+    /// From the first of LC_ALL, LC_NUMERIC and LANG that is set, the order the C library reads them in.
+    pub fn from_environment() -> Self {
+        ["LC_ALL", "LC_NUMERIC", "LANG"]
+            .iter()
+            .find_map(|name| env::var(name).ok().filter(|value| !value.is_empty()))
+            .map_or(DecimalSeparator::Point, |locale| Self::from_locale(&locale))
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DecimalSeparator::Point => "point",
+            DecimalSeparator::Comma => "comma",
+        }
+    }
+
+    /// What separates arguments and vector elements: `;` when `,` is the decimal separator.
+    pub fn list_separator(self) -> char {
+        match self {
+            DecimalSeparator::Point => ',',
+            DecimalSeparator::Comma => ';',
+        }
+    }
+
+    /// A number as typed with this separator, in the usual notation: `1.234,5` is `1234.5`.
+    pub fn canonical(self, text: &str) -> String {
+        match self {
+            DecimalSeparator::Point => text.to_string(),
+            DecimalSeparator::Comma => text.replace('.', "").replace(',', "."),
+        }
+    }
+
+    /// Text in the usual notation (`3.14`, `max(1, 2)`) as it's written with this separator.
+    pub fn localize(self, text: &str) -> String {
+        match self {
+            DecimalSeparator::Point => text.to_string(),
+            DecimalSeparator::Comma => text.chars().map(|ch| match ch {
+                '.' => ',',
+                ',' => ';',
+                ch => ch,
+            }).collect(),
+        }
+    }
+}
+
+/// A number written with a decimal comma: digits, optionally grouped in threes
+/// by `.`, then `,` and the fraction. None when it isn't one, so `3.14` typed
+/// out of habit is an error rather than 314.
+pub fn parse_comma_number(text: &str) -> Option<f64> {
+    let (whole, fraction) = text.split_once(',').unwrap_or((text, ""));
+    let groups: Vec<&str> = whole.split('.').collect();
+    let grouped = groups.len() == 1 || (!groups[0].is_empty() && groups[0].len() <= 3 && groups[1..].iter().all(|group| group.len() == 3));
+    let digits = |part: &str| part.chars().all(|ch| ch.is_ascii_digit());
+    if !grouped || !groups.iter().all(|group| digits(group)) || !digits(fraction) || (whole.is_empty() && fraction.is_empty()) {
+        return None;
+    }
+    format!("{}.{}", groups.concat(), fraction).parse().ok()
+}
+
+/// Currency signs amounts are pasted with; three-letter codes (`EUR`) are taken off too.
+const CURRENCY_SIGNS: &[char] = &['$', '€', '£', '¥', '₹', '₽', '₩', '₪', '₺', '₫', '₴', '₦', '₿', '¢'];
+
+/// Group separators besides `.` and `,`: spaces, including the no-break ones, and apostrophes (`1'234.50`).
+const GROUP_MARKS: &[char] = &[' ', '\u{a0}', '\u{202f}', '\'', '’'];
+
+/// A pasted amount such as `$1,234.56`, `1.234,56 €`, `CHF 1'234.50` or the
+/// accountant's `(1,234)` as a plain number written with `typed`: `1234.56`.
+/// The decimal separator is `decimal`, or guessed when None: the last of `.`
+/// and `,` when both appear, none when one repeats, and `typed`'s reading
+/// for a lone one before exactly three digits (`1,234`). None when the text
+/// isn't a single amount.
+pub fn clean_pasted_number(text: &str, decimal: Option<DecimalSeparator>, typed: DecimalSeparator) -> Option<String> {
+    let mut text = text.trim();
+    let mut negative = false;
+    if let Some(inner) = text.strip_prefix('(').and_then(|text| text.strip_suffix(')')) {
+        (text, negative) = (inner.trim(), true);
+    }
+    // Signs, codes and a minus, in whatever order they come
+    let is_code = |word: &str| word.len() == 3 && word.chars().all(|ch| ch.is_ascii_uppercase());
+    loop {
+        let before = text;
+        text = text.trim_matches(|ch: char| CURRENCY_SIGNS.contains(&ch) || ch.is_whitespace());
+        if let Some(rest) = text.strip_prefix(['-', '−']).or_else(|| text.strip_suffix(['-', '−'])) {
+            (text, negative) = (rest, true);
+        }
+        text = text.strip_prefix('+').unwrap_or(text);
+        if let Some((_, rest)) = text.split_once(char::is_whitespace).filter(|(code, _)| is_code(code)) {
+            text = rest;
+        }
+        if let Some((rest, _)) = text.rsplit_once(char::is_whitespace).filter(|(_, code)| is_code(code)) {
+            text = rest;
+        }
+        if text == before {
+            break;
+        }
+    }
+    if !text.starts_with(|ch: char| ch.is_ascii_digit()) || !text.ends_with(|ch: char| ch.is_ascii_digit()) {
+        return None;
+    }
+    // Runs of digits and the single separator after each, e.g. "1", ',', "234", '.', "56"
+    let mut runs: Vec<(String, Option<char>)> = vec![(String::new(), None)];
+    for ch in text.chars() {
+        match ch {
+            '0'..='9' if runs.last().is_some_and(|(_, separator)| separator.is_none()) => runs.last_mut()?.0.push(ch),
+            '0'..='9' => runs.push((ch.to_string(), None)),
+            // One separator between runs of digits, not two in a row
+            ch if (ch == '.' || ch == ',' || GROUP_MARKS.contains(&ch)) && runs.last()?.1.is_some() => return None,
+            '.' | ',' => runs.last_mut()?.1 = Some(ch),
+            ch if GROUP_MARKS.contains(&ch) => runs.last_mut()?.1 = Some(' '),
+            _ => return None,
+        }
+    }
+    let separators: Vec<char> = runs.iter().filter_map(|(_, separator)| *separator).collect();
+    let count = |separator: char| separators.iter().filter(|&&other| other == separator).count();
+    let as_char = |separator: DecimalSeparator| if separator == DecimalSeparator::Point { '.' } else { ',' };
+    let decimal = match decimal {
+        Some(separator) => Some(as_char(separator)),
+        None => match (count('.'), count(',')) {
+            (0, 0) => None,
+            (_, 0) | (0, _) => {
+                let separator = if count('.') > 0 { '.' } else { ',' };
+                let lone = count(separator) == 1;
+                let three_after = runs.last().is_some_and(|(digits, _)| digits.len() == 3) && runs[runs.len() - 2].1 == Some(separator);
+                (lone && (!three_after || separator == as_char(typed))).then_some(separator)
+            }
+            _ => separators.iter().rev().find(|separator| **separator != ' ').copied(),
+        },
+    };
+    // The decimal separator once, before the last run; every group after the first has three digits
+    let (whole, fraction) = match runs.iter().position(|(_, separator)| Some(*separator) == decimal.map(Some)) {
+        Some(at) if at == runs.len() - 2 => (&runs[..=at], Some(&runs[at + 1].0)),
+        Some(_) => return None,
+        None => (&runs[..], None),
+    };
+    let grouped = whole.len() > 1;
+    if grouped && (whole[0].0.len() > 3 || whole[1..].iter().any(|(digits, _)| digits.len() != 3)) {
+        return None;
+    }
+    let digits: String = whole.iter().map(|(digits, _)| digits.as_str()).collect();
+    let number = match fraction {
+        Some(fraction) => format!("{}{}.{}", if negative { "-" } else { "" }, digits, fraction),
+        None => format!("{}{}", if negative { "-" } else { "" }, digits),
+    };
+    Some(typed.localize(&number))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_locales() {
+        assert_eq!(DecimalSeparator::from_locale("de_DE.UTF-8"), DecimalSeparator::Comma);
+        assert_eq!(DecimalSeparator::from_locale("fr"), DecimalSeparator::Comma);
+        assert_eq!(DecimalSeparator::from_locale("en_GB.UTF-8"), DecimalSeparator::Point);
+        assert_eq!(DecimalSeparator::from_locale("C"), DecimalSeparator::Point);
+    }
+
+    #[test]
+    fn parses_comma_numbers() {
+        assert_eq!(parse_comma_number("2,5"), Some(2.5));
+        assert_eq!(parse_comma_number("1.234.567,5"), Some(1234567.5));
+        assert_eq!(parse_comma_number(",5"), Some(0.5));
+        assert_eq!(parse_comma_number("12"), Some(12.0));
+        assert_eq!(parse_comma_number("2.5"), None);
+        assert_eq!(parse_comma_number("1,2,3"), None);
+        assert_eq!(parse_comma_number(","), None);
+        assert_eq!(DecimalSeparator::Comma.localize("max(1.5, 2)"), "max(1,5; 2)");
+    }
+
+    #[test]
+    fn cleans_pasted_amounts() {
+        let clean = |text| clean_pasted_number(text, None, DecimalSeparator::Point);
+        assert_eq!(clean("$1,234.56").as_deref(), Some("1234.56"));
+        assert_eq!(clean("1.234,56 €").as_deref(), Some("1234.56"));
+        assert_eq!(clean("CHF 1'234.50").as_deref(), Some("1234.50"));
+        assert_eq!(clean("1\u{202f}234\u{202f}567,5").as_deref(), Some("1234567.5"));
+        assert_eq!(clean("(1,234)").as_deref(), Some("-1234"));
+        assert_eq!(clean("-€ 12,5").as_deref(), Some("-12.5"));
+        assert_eq!(clean("12.50 USD").as_deref(), Some("12.50"));
+        assert_eq!(clean("1,234,567").as_deref(), Some("1234567"));
+        assert_eq!(clean("42").as_deref(), Some("42"));
+        // A lone separator before three digits is read as typed here
+        assert_eq!(clean("1.234").as_deref(), Some("1.234"));
+        assert_eq!(clean_pasted_number("1.234", None, DecimalSeparator::Comma).as_deref(), Some("1234"));
+        assert_eq!(clean_pasted_number("1,5 €", None, DecimalSeparator::Comma).as_deref(), Some("1,5"));
+        // Unless the separator is set
+        assert_eq!(clean_pasted_number("1.234", Some(DecimalSeparator::Comma), DecimalSeparator::Point).as_deref(), Some("1234"));
+        assert_eq!(clean_pasted_number("1,234.5", Some(DecimalSeparator::Comma), DecimalSeparator::Point), None);
+        for text in ["2 + 3", "max(1,5)", "1,23,4", "12 34", "1..5", "$", "0xFF", "1e5", "EUR"] {
+            assert_eq!(clean(text), None, "{}", text);
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! IPv4 subnet math for `:cidr 192.168.1.0/24`.
+
+use std::net::Ipv4Addr;
+
+/// An IPv4 address with a prefix length; the address needn't be the network's first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cidr {
+    pub addr: u32,
+    pub prefix: u8,
+}
+
+impl Cidr {
+    /// `10.0.0.7/8`, or a bare address as a /32.
+    pub fn parse(text: &str) -> Option<Self> {
+        let (addr, prefix) = match text.split_once('/') {
+            Some((addr, prefix)) => (addr, prefix.parse().ok().filter(|prefix| *prefix <= 32)?),
+            None => (text, 32),
+        };
+        Some(Self { addr: addr.parse::<Ipv4Addr>().ok()?.into(), prefix })
+    }
+
+    pub fn netmask(&self) -> u32 {
+        u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0)
+    }
+
+    pub fn network(&self) -> u32 {
+        self.addr & self.netmask()
+    }
+
+    pub fn broadcast(&self) -> u32 {
+        self.network() | !self.netmask()
+    }
+
+    /// Addresses in the block, network and broadcast included.
+    pub fn size(&self) -> u64 {
+        1u64 << (32 - self.prefix)
+    }
+
+    /// Assignable hosts: all but the network and broadcast addresses, except
+    /// for point-to-point /31s and single-address /32s.
+    pub fn usable_hosts(&self) -> u64 {
+        if self.prefix >= 31 { self.size() } else { self.size() - 2 }
+    }
+
+    /// First and last assignable addresses.
+    pub fn host_range(&self) -> (u32, u32) {
+        if self.prefix >= 31 { (self.network(), self.broadcast()) } else { (self.network() + 1, self.broadcast() - 1) }
+    }
+
+    pub fn contains(&self, addr: u32) -> bool {
+        addr & self.netmask() == self.network()
+    }
+}
+
+/// The subnet popup: a block, and optionally an address checked against it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubnetView {
+    pub cidr: Cidr,
+    pub probe: Option<u32>,
+}
+
+pub fn format_ip(addr: u32) -> String {
+    Ipv4Addr::from(addr).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(text: &str) -> u32 {
+        text.parse::<Ipv4Addr>().unwrap().into()
+    }
+
+    #[test]
+    fn subnet_properties() {
+        let cidr = Cidr::parse("192.168.1.77/24").unwrap();
+        assert_eq!(format_ip(cidr.network()), "192.168.1.0");
+        assert_eq!(format_ip(cidr.broadcast()), "192.168.1.255");
+        assert_eq!(format_ip(cidr.netmask()), "255.255.255.0");
+        assert_eq!(cidr.usable_hosts(), 254);
+        assert_eq!(cidr.host_range(), (ip("192.168.1.1"), ip("192.168.1.254")));
+        assert!(cidr.contains(ip("192.168.1.200")));
+        assert!(!cidr.contains(ip("192.168.2.1")));
+    }
+
+    #[test]
+    fn edge_prefixes() {
+        let all = Cidr::parse("0.0.0.0/0").unwrap();
+        assert_eq!((all.netmask(), all.size()), (0, 1 << 32));
+        assert_eq!(Cidr::parse("10.0.0.0/31").unwrap().usable_hosts(), 2);
+        assert_eq!(Cidr::parse("10.0.0.5").unwrap().usable_hosts(), 1);
+        assert_eq!(Cidr::parse("10.0.0.0/33"), None);
+        assert_eq!(Cidr::parse("10.0.0/8"), None);
+    }
+}
@@ -0,0 +1,337 @@
+//! The value types expressions and stack operations are evaluated over: reals,
+//! complex numbers (`:set complex on`) and measurements (`:set uncertainty on`).
+//! Infix operators and functions are walked once, in `Calculator::evaluate_number`,
+//! and the RPN keys pick a type from the stack values they take; each type only
+//! says how its arithmetic works and how a function applies to it. What a type
+//! has no arithmetic for (comparisons, `if`, `poly`) is evaluated over reals.
+//!
+//! `Integer` and `Rational` are exact: `Calculator::evaluate_in` evaluates over
+//! them, so `0.1 + 0.2` is exactly 3/10. Where they can't stay exact (an
+//! overflow, a fractional power, a function) the real result is converted back.
+
+use crate::calculator::{ComplexNumber, StackValue};
+use crate::complex;
+use crate::context::EvalContext;
+use crate::error::CalculatorError;
+use crate::functions::Function;
+use crate::inspect;
+use crate::uncertainty::{self, Measurement};
+
+pub trait Number: Copy {
+    fn from_real(x: f64) -> Self;
+
+    /// A stack value or --define as this type; None when it has more than the type holds.
+    fn from_value(value: &StackValue) -> Option<Self>;
+
+    fn into_value(self) -> StackValue;
+
+    /// The real part, for the dividend of a division by zero.
+    fn real(self) -> f64;
+
+    fn is_zero(self) -> bool;
+
+    /// `a op b`; None for operators the type has no arithmetic for.
+    fn binary(op: char, a: Self, b: Self) -> Option<Self>;
+
+    /// `function` of `args`, already counted against its arity.
+    fn call(function: &Function, args: &[Self], context: EvalContext) -> Result<Self, CalculatorError>;
+
+    /// The number with this magnitude and phase in radians, in the types that have one.
+    fn from_polar(_magnitude: f64, _phase: f64) -> Option<Self> {
+        None
+    }
+
+    /// What `i` stands for, in the types that have one.
+    fn imaginary_unit() -> Option<Self> {
+        None
+    }
+}
+
+impl Number for f64 {
+    fn from_real(x: f64) -> Self {
+        x
+    }
+
+    fn from_value(value: &StackValue) -> Option<Self> {
+        value.as_real()
+    }
+
+    fn into_value(self) -> StackValue {
+        StackValue::Real(self)
+    }
+
+    fn real(self) -> f64 {
+        self
+    }
+
+    fn is_zero(self) -> bool {
+        self == 0.0
+    }
+
+    fn binary(op: char, a: Self, b: Self) -> Option<Self> {
+        match op {
+            '+' => Some(a + b),
+            '-' => Some(a - b),
+            '*' => Some(a * b),
+            '/' => Some(a / b),
+            '^' => Some(a.powf(b)),
+            _ => None,
+        }
+    }
+
+    fn call(function: &Function, args: &[Self], context: EvalContext) -> Result<Self, CalculatorError> {
+        function.check_domain(args)?;
+        Ok(function.call(args, context))
+    }
+}
+
+impl Number for ComplexNumber {
+    fn from_real(x: f64) -> Self {
+        ComplexNumber::new(x, 0.0)
+    }
+
+    fn from_value(value: &StackValue) -> Option<Self> {
+        Some(value.as_complex())
+    }
+
+    fn into_value(self) -> StackValue {
+        StackValue::from_complex(self)
+    }
+
+    fn real(self) -> f64 {
+        self.real
+    }
+
+    fn is_zero(self) -> bool {
+        self.magnitude() == 0.0
+    }
+
+    fn binary(op: char, a: Self, b: Self) -> Option<Self> {
+        complex::binary(op, a, b)
+    }
+
+    /// The functions with a complex version use it; the rest accept complex arguments that happen to be real.
+    fn call(function: &Function, args: &[Self], context: EvalContext) -> Result<Self, CalculatorError> {
+        if let [z] = args
+            && let Some(result) = complex::function(function.name, *z)
+        {
+            return Ok(result);
+        }
+        let values = args
+            .iter()
+            .map(|z| StackValue::from_complex(*z).as_real().ok_or_else(|| CalculatorError::ComplexUnsupported { operation: function.name.to_string() }))
+            .collect::<Result<Vec<f64>, _>>()?;
+        f64::call(function, &values, context).map(Self::from_real)
+    }
+
+    fn from_polar(magnitude: f64, phase: f64) -> Option<Self> {
+        Some(ComplexNumber::from_polar(magnitude, phase))
+    }
+
+    fn imaginary_unit() -> Option<Self> {
+        Some(ComplexNumber::new(0.0, 1.0))
+    }
+}
+
+impl Number for Measurement {
+    fn from_real(x: f64) -> Self {
+        Measurement::exact(x)
+    }
+
+    fn from_value(value: &StackValue) -> Option<Self> {
+        value.as_measurement()
+    }
+
+    fn into_value(self) -> StackValue {
+        StackValue::from_measurement(self)
+    }
+
+    fn real(self) -> f64 {
+        self.value
+    }
+
+    fn is_zero(self) -> bool {
+        self.value == 0.0
+    }
+
+    /// `a ± s` adds s to a's uncertainty; the other operators propagate them.
+    fn binary(op: char, a: Self, b: Self) -> Option<Self> {
+        match op {
+            '±' => Some(Measurement::new(a.value, a.sigma.hypot(b.value))),
+            _ => uncertainty::binary(op, a, b),
+        }
+    }
+
+    fn call(function: &Function, args: &[Self], context: EvalContext) -> Result<Self, CalculatorError> {
+        function.check_domain(&args.iter().map(|m| m.value).collect::<Vec<_>>())?;
+        Ok(uncertainty::apply(|values| function.call(values, context), args))
+    }
+}
+
+/// A whole number. Division truncates toward zero, as integer division does in
+/// most languages, and a real from a function or an overflow is truncated too.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Integer(pub i64);
+
+impl Number for Integer {
+    fn from_real(x: f64) -> Self {
+        Integer(x.trunc() as i64)
+    }
+
+    fn from_value(value: &StackValue) -> Option<Self> {
+        value.as_real().filter(|x| x.fract() == 0.0 && x.abs() < i64::MAX as f64).map(|x| Integer(x as i64))
+    }
+
+    fn into_value(self) -> StackValue {
+        StackValue::Real(self.0 as f64)
+    }
+
+    fn real(self) -> f64 {
+        self.0 as f64
+    }
+
+    fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    /// None on overflow or a negative power, which are then worked out over reals.
+    fn binary(op: char, a: Self, b: Self) -> Option<Self> {
+        let (a, b) = (a.0, b.0);
+        match op {
+            '+' => a.checked_add(b),
+            '-' => a.checked_sub(b),
+            '*' => a.checked_mul(b),
+            '/' => a.checked_div(b),
+            '^' => a.checked_pow(u32::try_from(b).ok()?),
+            _ => None,
+        }
+        .map(Integer)
+    }
+
+    fn call(function: &Function, args: &[Self], context: EvalContext) -> Result<Self, CalculatorError> {
+        f64::call(function, &args.iter().map(|n| n.real()).collect::<Vec<_>>(), context).map(Self::from_real)
+    }
+}
+
+/// A fraction in lowest terms with a positive denominator. A real that comes in
+/// is the closest fraction with a denominator up to 10⁶; NaN and the infinities
+/// are kept as 0/0 and ±1/0.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rational {
+    pub numerator: i64,
+    pub denominator: i64,
+}
+
+impl Rational {
+    /// `numerator / denominator` reduced; None when it doesn't fit in i64.
+    pub fn new(numerator: i128, denominator: i128) -> Option<Self> {
+        if denominator == 0 {
+            return Some(Self { numerator: numerator.signum() as i64, denominator: 0 });
+        }
+        let divisor = gcd(numerator.unsigned_abs(), denominator.unsigned_abs()) as i128 * denominator.signum();
+        Some(Self { numerator: i64::try_from(numerator / divisor).ok()?, denominator: i64::try_from(denominator / divisor).ok()? })
+    }
+
+    fn is_finite(self) -> bool {
+        self.denominator != 0
+    }
+}
+
+impl Number for Rational {
+    fn from_real(x: f64) -> Self {
+        if !x.is_finite() {
+            return Self { numerator: if x.is_nan() { 0 } else { x.signum() as i64 }, denominator: 0 };
+        }
+        match inspect::fraction(x) {
+            Some((numerator, denominator)) => Self { numerator, denominator },
+            None => Self { numerator: x.round() as i64, denominator: 1 }, // Too large for a denominator to matter
+        }
+    }
+
+    fn from_value(value: &StackValue) -> Option<Self> {
+        value.as_real().map(Self::from_real)
+    }
+
+    fn into_value(self) -> StackValue {
+        StackValue::Real(self.real())
+    }
+
+    fn real(self) -> f64 {
+        self.numerator as f64 / self.denominator as f64
+    }
+
+    fn is_zero(self) -> bool {
+        self.numerator == 0 && self.is_finite()
+    }
+
+    /// None on overflow, a fractional power or a non-finite operand, which are then worked out over reals.
+    fn binary(op: char, a: Self, b: Self) -> Option<Self> {
+        if !a.is_finite() || !b.is_finite() {
+            return None;
+        }
+        let (p, q, r, s) = (a.numerator as i128, a.denominator as i128, b.numerator as i128, b.denominator as i128);
+        match op {
+            '+' => Self::new((p * s).checked_add(r * q)?, q * s),
+            '-' => Self::new((p * s).checked_sub(r * q)?, q * s),
+            '*' => Self::new(p * r, q * s),
+            '/' => Self::new(p * s, q * r),
+            '^' if s == 1 => {
+                let exponent = u32::try_from(r.unsigned_abs()).ok()?;
+                let (numerator, denominator) = (p.checked_pow(exponent)?, q.checked_pow(exponent)?);
+                if r < 0 { Self::new(denominator, numerator) } else { Self::new(numerator, denominator) }
+            }
+            _ => None,
+        }
+    }
+
+    fn call(function: &Function, args: &[Self], context: EvalContext) -> Result<Self, CalculatorError> {
+        f64::call(function, &args.iter().map(|x| x.real()).collect::<Vec<_>>(), context).map(Self::from_real)
+    }
+}
+
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculator::AngleMode;
+    use crate::context::Rounding;
+    use crate::functions;
+
+    #[test]
+    fn each_type_has_its_own_arithmetic() {
+        let context = EvalContext { angle: AngleMode::Radians, rounding: Rounding::HalfAway };
+        let sqrt = functions::lookup("sqrt").unwrap();
+        assert!(f64::call(sqrt, &[-4.0], context).unwrap().is_nan());
+        assert_eq!(ComplexNumber::call(sqrt, &[ComplexNumber::from_real(-4.0)], context).unwrap(), ComplexNumber::new(0.0, 2.0));
+        assert_eq!(Measurement::binary('±', Measurement::exact(5.0), Measurement::exact(0.3)), Some(Measurement::new(5.0, 0.3)));
+        assert_eq!(f64::binary('<', 1.0, 2.0), None);
+
+        let floor = functions::lookup("floor").unwrap();
+        assert!(matches!(ComplexNumber::call(floor, &[ComplexNumber::new(1.5, 1.0)], context), Err(CalculatorError::ComplexUnsupported { .. })));
+        assert_eq!(ComplexNumber::call(floor, &[ComplexNumber::from_real(1.5)], context).unwrap(), ComplexNumber::from_real(1.0));
+        assert_eq!(f64::imaginary_unit(), None);
+    }
+
+    #[test]
+    fn exact_types_stay_exact() {
+        let calculator = crate::calculator::Calculator::new();
+        let third = Rational::new(1, 3).unwrap();
+        assert_eq!(Rational::binary('+', third, Rational::new(1, 6).unwrap()), Rational::new(1, 2));
+        assert_eq!(Rational::binary('^', Rational::new(2, 3).unwrap(), Rational::from_real(-2.0)), Rational::new(9, 4));
+        assert_eq!(Rational::binary('^', third, third), None);
+        assert_eq!(calculator.evaluate_in::<Rational>("0.1 + 0.2").unwrap(), Rational::new(3, 10).unwrap());
+        assert_eq!(calculator.evaluate_in::<Rational>("1/3 * 3").unwrap().into_value(), StackValue::Real(1.0));
+        assert!(matches!(calculator.evaluate_in::<Rational>("1 / (1 - 1)"), Err(CalculatorError::DivisionByZero { .. })));
+
+        assert_eq!(Integer::binary('*', Integer(i64::MAX), Integer(2)), None);
+        assert_eq!(calculator.evaluate_in::<Integer>("7 / 2 * 2").unwrap(), Integer(6));
+        assert_eq!(calculator.evaluate_in::<Integer>("2^62 + 2^62").unwrap(), Integer(i64::MAX)); // Over reals, then saturated
+        assert_eq!(Integer::from_value(&StackValue::Real(2.5)), None);
+    }
+}
@@ -0,0 +1,239 @@
+//! Numerical methods over a real function of one variable.
+
+const MAX_NEWTON_STEPS: usize = 50;
+const MAX_BRACKET_STEPS: usize = 60;
+const MAX_BISECTION_STEPS: usize = 200;
+const TOLERANCE: f64 = 1e-12;
+const MAX_SIMPSON_DEPTH: u32 = 40;
+const RIDDERS_STEPS: usize = 10;
+
+/// How a root was found.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Method {
+    Newton,
+    Bisection,
+}
+
+impl Method {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Method::Newton => "Newton",
+            Method::Bisection => "bisection",
+        }
+    }
+}
+
+/// A root of `f` with the diagnostics shown after solving.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Root {
+    pub x: f64,
+    pub residual: f64, // |f(x)|
+    pub iterations: usize,
+    pub method: Method,
+}
+
+/// Finds a root of `f` near `x0`. Newton's method runs first with a central
+/// difference derivative; if it stalls or diverges, the search widens around
+/// `x0` until `f` changes sign and bisects that bracket instead.
+/// Points where `f` is undefined should come back as NaN.
+pub fn solve(mut f: impl FnMut(f64) -> f64, x0: f64) -> Option<Root> {
+    let newton = newton(&mut f, x0);
+    if newton.is_some() {
+        return newton;
+    }
+    let (low, high, steps) = bracket(&mut f, x0)?;
+    let mut root = bisect(&mut f, low, high)?;
+    root.iterations += steps;
+    Some(root)
+}
+
+fn newton(f: &mut impl FnMut(f64) -> f64, x0: f64) -> Option<Root> {
+    let mut x = x0;
+    for step in 1..=MAX_NEWTON_STEPS {
+        let y = f(x);
+        if y == 0.0 {
+            return Some(Root { x, residual: 0.0, iterations: step - 1, method: Method::Newton });
+        }
+        let h = 1e-6 * x.abs().max(1.0);
+        let slope = (f(x + h) - f(x - h)) / (2.0 * h);
+        let next = x - y / slope;
+        if !next.is_finite() || !y.is_finite() {
+            return None;
+        }
+        let converged = (next - x).abs() <= TOLERANCE * next.abs().max(1.0);
+        x = next;
+        if converged {
+            // A vanishing step alone isn't enough: f(x) = 1/x "converges" to infinity
+            let residual = f(x).abs();
+            return (residual.is_finite() && residual <= 1e-6 * y.abs().max(1.0))
+                .then_some(Root { x, residual, iterations: step, method: Method::Newton });
+        }
+    }
+    None
+}
+
+// Widens a symmetric interval around x0 until f changes sign across one of its halves
+fn bracket(f: &mut impl FnMut(f64) -> f64, x0: f64) -> Option<(f64, f64, usize)> {
+    let center = f(x0);
+    if !center.is_finite() {
+        return None;
+    }
+    let mut width = 0.01 * x0.abs().max(1.0);
+    for step in 1..=MAX_BRACKET_STEPS {
+        for edge in [x0 - width, x0 + width] {
+            let value = f(edge);
+            if value.is_finite() && value.signum() != center.signum() {
+                return Some((x0.min(edge), x0.max(edge), step));
+            }
+        }
+        width *= 2.0;
+    }
+    None
+}
+
+fn bisect(f: &mut impl FnMut(f64) -> f64, mut low: f64, mut high: f64) -> Option<Root> {
+    let (low_value, high_value) = (f(low), f(high));
+    let low_sign = low_value.signum();
+    for step in 1..=MAX_BISECTION_STEPS {
+        let mid = low + (high - low) / 2.0;
+        let value = f(mid);
+        if value == 0.0 || high - low <= TOLERANCE * mid.abs().max(1.0) {
+            // A sign change across a pole (tan at π/2) isn't a root: f grew instead of shrinking
+            let residual = value.abs();
+            return (residual <= low_value.abs().min(high_value.abs())).then_some(Root { x: mid, residual, iterations: step, method: Method::Bisection });
+        }
+        if value.is_nan() {
+            return None;
+        }
+        if value.signum() == low_sign {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    None
+}
+
+/// A numerical result with its estimated absolute error.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Estimate {
+    pub value: f64,
+    pub error: f64,
+}
+
+/// ∫ f from `a` to `b` by adaptive Simpson quadrature. Each panel is split
+/// until its two halves agree, and the error is the sum of their differences.
+pub fn integrate(mut f: impl FnMut(f64) -> f64, a: f64, b: f64) -> Option<Estimate> {
+    if a == b {
+        return Some(Estimate { value: 0.0, error: 0.0 });
+    }
+    let (fa, fm, fb) = (f(a), f((a + b) / 2.0), f(b));
+    let whole = simpson(a, b, fa, fm, fb);
+    let estimate = adaptive_simpson(&mut f, Panel { a, b, fa, fm, fb, whole }, 1e-10 * whole.abs().max(1.0), MAX_SIMPSON_DEPTH);
+    (estimate.value.is_finite() && estimate.error.is_finite()).then_some(estimate)
+}
+
+struct Panel {
+    a: f64,
+    b: f64,
+    fa: f64,
+    fm: f64,
+    fb: f64,
+    whole: f64, // Simpson's rule over the whole panel
+}
+
+fn simpson(a: f64, b: f64, fa: f64, fm: f64, fb: f64) -> f64 {
+    (b - a) / 6.0 * (fa + 4.0 * fm + fb)
+}
+
+fn adaptive_simpson(f: &mut impl FnMut(f64) -> f64, panel: Panel, tolerance: f64, depth: u32) -> Estimate {
+    let Panel { a, b, fa, fm, fb, whole } = panel;
+    let m = (a + b) / 2.0;
+    let (lm, rm) = ((a + m) / 2.0, (m + b) / 2.0);
+    let (flm, frm) = (f(lm), f(rm));
+    let left = simpson(a, m, fa, flm, fm);
+    let right = simpson(m, b, fm, frm, fb);
+    let difference = left + right - whole;
+    if depth == 0 || difference.abs() <= 15.0 * tolerance || difference.is_nan() {
+        // Richardson extrapolation; the correction is also the error estimate
+        return Estimate { value: left + right + difference / 15.0, error: difference.abs() / 15.0 };
+    }
+    let left = adaptive_simpson(f, Panel { a, b: m, fa, fm: flm, fb: fm, whole: left }, tolerance / 2.0, depth - 1);
+    let right = adaptive_simpson(f, Panel { a: m, b, fa: fm, fm: frm, fb, whole: right }, tolerance / 2.0, depth - 1);
+    Estimate { value: left.value + right.value, error: left.error + right.error }
+}
+
+/// f'(x) by Ridders' method: central differences with shrinking steps,
+/// extrapolated to a zero step. The error comes from the extrapolation table.
+pub fn derivative(mut f: impl FnMut(f64) -> f64, x: f64) -> Option<Estimate> {
+    const SHRINK: f64 = 1.4;
+    let mut h = 0.1 * x.abs().max(1.0);
+    let mut table = [[0.0; RIDDERS_STEPS]; RIDDERS_STEPS];
+    table[0][0] = (f(x + h) - f(x - h)) / (2.0 * h);
+    let mut best = Estimate { value: table[0][0], error: f64::INFINITY };
+    for i in 1..RIDDERS_STEPS {
+        h /= SHRINK;
+        table[0][i] = (f(x + h) - f(x - h)) / (2.0 * h);
+        let mut factor = SHRINK * SHRINK;
+        for j in 1..=i {
+            table[j][i] = (table[j - 1][i] * factor - table[j - 1][i - 1]) / (factor - 1.0);
+            factor *= SHRINK * SHRINK;
+            let error = (table[j][i] - table[j - 1][i]).abs().max((table[j][i] - table[j - 1][i - 1]).abs());
+            if error <= best.error {
+                best = Estimate { value: table[j][i], error };
+            }
+        }
+        // Higher orders got worse by a significant factor, so stop early
+        if (table[i][i] - table[i - 1][i - 1]).abs() >= 2.0 * best.error {
+            break;
+        }
+    }
+    (best.value.is_finite() && best.error.is_finite()).then_some(best)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn newton_finds_nearby_roots() {
+        let root = solve(|x| x * x - 2.0, 1.0).unwrap();
+        assert!((root.x - 2f64.sqrt()).abs() < 1e-12);
+        assert_eq!(root.method, Method::Newton);
+        assert!(root.iterations < 10);
+
+        let root = solve(|x| x.cos() - x, 0.0).unwrap();
+        assert!((root.x - 0.7390851332151607).abs() < 1e-12);
+    }
+
+    #[test]
+    fn falls_back_to_bisection() {
+        // Newton overshoots forever on the cube root; the sign change is still there
+        let root = solve(f64::cbrt, 1.0).unwrap();
+        assert_eq!(root.method, Method::Bisection);
+        assert!(root.x.abs() < 1e-9);
+    }
+
+    #[test]
+    fn integrals() {
+        let area = integrate(f64::sin, 0.0, std::f64::consts::PI).unwrap();
+        assert!((area.value - 2.0).abs() < 1e-10);
+        assert!(area.error < 1e-9);
+        assert_eq!(integrate(|x| x * x, 3.0, 0.0).unwrap().value, -9.0);
+        assert_eq!(integrate(|x| 1.0 / x, -1.0, 1.0), None);
+    }
+
+    #[test]
+    fn derivatives() {
+        let slope = derivative(f64::exp, 1.0).unwrap();
+        assert!((slope.value - std::f64::consts::E).abs() < 1e-10);
+        assert!(slope.error < 1e-8);
+        assert!((derivative(|x| x.powi(3), -2.0).unwrap().value - 12.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn reports_missing_roots() {
+        assert_eq!(solve(|x| x * x + 1.0, 0.5), None);
+        assert_eq!(solve(|x| 1.0 / x, 1.0), None);
+    }
+}
@@ -0,0 +1,78 @@
+//! Parsed infix expressions by input text, so the preview and the render
+//! don't re-tokenize and re-run the shunting yard for input that hasn't
+//! changed since the last keystroke. Errors are cached too: an incomplete
+//! expression is parsed as often as a complete one.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::error::CalculatorError;
+use crate::expr::Expr;
+use crate::locale::DecimalSeparator;
+
+/// Entries kept before the cache starts over; typing rarely revisits older input.
+const CAPACITY: usize = 64;
+
+type Parsed = Result<Expr, CalculatorError>;
+
+#[derive(Default)]
+pub struct ParseCache {
+    // The separator is part of the key: "1,5" is a number in one and two arguments in the other
+    entries: RefCell<HashMap<(String, DecimalSeparator), Parsed>>,
+}
+
+impl ParseCache {
+    /// The cached parse of `input`, or `parse`'s result, kept for next time.
+    pub fn get_or_parse(&self, input: &str, separator: DecimalSeparator, parse: impl FnOnce() -> Parsed) -> Parsed {
+        let key = (input.to_string(), separator);
+        if let Some(parsed) = self.entries.borrow().get(&key) {
+            return parsed.clone();
+        }
+        let parsed = parse();
+        let mut entries = self.entries.borrow_mut();
+        if entries.len() == CAPACITY {
+            entries.clear();
+        }
+        entries.insert(key, parsed.clone());
+        parsed
+    }
+
+    /// Forgets every parse, for when a setting reads the same text differently.
+    pub fn clear(&self) {
+        self.entries.borrow_mut().clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_each_input_once() {
+        let cache = ParseCache::default();
+        let mut parses = 0;
+        for _ in 0..3 {
+            let parsed = cache.get_or_parse("1 + 2", DecimalSeparator::Point, || {
+                parses += 1;
+                Ok(Expr::number(3.0, "3"))
+            });
+            assert!(parsed.is_ok());
+        }
+        assert_eq!(parses, 1);
+        cache.get_or_parse("1 + 2", DecimalSeparator::Comma, || Err(CalculatorError::InvalidExpression)).unwrap_err();
+        assert_eq!(cache.len(), 2);
+
+        for i in 0..CAPACITY {
+            let _ = cache.get_or_parse(&i.to_string(), DecimalSeparator::Point, || Ok(Expr::number(i as f64, i.to_string())));
+        }
+        assert!(cache.len() <= CAPACITY);
+    }
+}
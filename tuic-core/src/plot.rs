@@ -0,0 +1,81 @@
+//! State of the `plot(expr, a, b)` popup: which expression and which x range.
+
+use crate::expr::Expr;
+
+/// A y = f(x) graph over a movable window of x.
+#[derive(Debug, Clone)]
+pub struct FunctionPlot {
+    pub expr: Expr,
+    pub label: String, // The expression as typed, for the title
+    pub x_min: f64,
+    pub x_max: f64,
+}
+
+impl FunctionPlot {
+    /// Shifts the window by `fraction` of its width (negative pans left).
+    pub fn pan(&mut self, fraction: f64) {
+        let shift = (self.x_max - self.x_min) * fraction;
+        self.x_min += shift;
+        self.x_max += shift;
+    }
+
+    /// Scales the window around its center; factors below 1 zoom in.
+    pub fn zoom(&mut self, factor: f64) {
+        let center = (self.x_min + self.x_max) / 2.0;
+        let half_width = (self.x_max - self.x_min) / 2.0 * factor;
+        // Stop before the range collapses below what f64 can tell apart
+        if half_width > center.abs().max(1.0) * 1e-12 && half_width.is_finite() {
+            self.x_min = center - half_width;
+            self.x_max = center + half_width;
+        }
+    }
+
+    /// `count` evenly spaced x values across the window, both ends included.
+    pub fn sample_xs(&self, count: usize) -> impl Iterator<Item = f64> + '_ {
+        let steps = count.max(2) - 1;
+        (0..=steps).map(move |i| self.x_min + (self.x_max - self.x_min) * i as f64 / steps as f64)
+    }
+}
+
+/// Y axis bounds for sampled points. Outliers beyond the 2nd and 98th
+/// percentiles are cut off so a pole (tan, 1/x) doesn't flatten the rest.
+pub fn y_bounds(points: &[(f64, f64)]) -> (f64, f64) {
+    let mut ys: Vec<f64> = points.iter().map(|(_, y)| *y).filter(|y| y.is_finite()).collect();
+    if ys.is_empty() {
+        return (-1.0, 1.0);
+    }
+    ys.sort_by(f64::total_cmp);
+    let trim = if ys.len() >= 50 { ys.len() / 50 } else { 0 };
+    let (low, high) = (ys[trim], ys[ys.len() - 1 - trim]);
+    if high > low {
+        let margin = (high - low) * 0.05;
+        (low - margin, high + margin)
+    } else {
+        (low - 1.0, low + 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pan_and_zoom() {
+        let mut plot = FunctionPlot { expr: Expr::number(0.0, "0"), label: String::new(), x_min: -10.0, x_max: 10.0 };
+        plot.pan(0.25);
+        assert_eq!((plot.x_min, plot.x_max), (-5.0, 15.0));
+        plot.zoom(0.5);
+        assert_eq!((plot.x_min, plot.x_max), (0.0, 10.0));
+        assert_eq!(plot.sample_xs(3).collect::<Vec<_>>(), [0.0, 5.0, 10.0]);
+    }
+
+    #[test]
+    fn bounds_ignore_outliers() {
+        let mut points: Vec<(f64, f64)> = (0..100).map(|i| (i as f64, (i % 10) as f64)).collect();
+        points[50].1 = 1e12;
+        points[51].1 = f64::INFINITY;
+        let (low, high) = y_bounds(&points);
+        assert!(low < 0.0 && high > 9.0 && high < 10.0);
+        assert_eq!(y_bounds(&[(0.0, 3.0)]), (2.0, 4.0));
+    }
+}
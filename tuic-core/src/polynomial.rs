@@ -0,0 +1,100 @@
+//! Polynomials given by their coefficients, highest power first:
+//! `poly(1, 0, -2, x)` is x² − 2, and `roots(1, 0, -2)` solves it.
+
+const NEWTON_STEPS: usize = 4;
+
+/// Value at `x` by Horner's rule.
+pub fn evaluate(coefficients: &[f64], x: f64) -> f64 {
+    coefficients.iter().fold(0.0, |acc, coefficient| acc * x + coefficient)
+}
+
+/// All roots of a polynomial of degree 1 to 3 as (real, imaginary) pairs:
+/// real roots ascending, then complex conjugates. Leading zero coefficients
+/// lower the degree; None when nothing of degree 1 to 3 is left.
+pub fn roots(coefficients: &[f64]) -> Option<Vec<(f64, f64)>> {
+    let start = coefficients.iter().position(|coefficient| *coefficient != 0.0)?;
+    let mut roots = match coefficients[start..] {
+        [a, b] => vec![(-b / a, 0.0)],
+        [a, b, c] => quadratic(a, b, c),
+        [a, b, c, d] => cubic(a, b, c, d),
+        _ => return None,
+    };
+    roots.sort_by(|x, y| (x.1 != 0.0).cmp(&(y.1 != 0.0)).then(x.0.total_cmp(&y.0)).then(y.1.total_cmp(&x.1)));
+    Some(roots)
+}
+
+fn quadratic(a: f64, b: f64, c: f64) -> Vec<(f64, f64)> {
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        // Adding 0.0 turns −0 into 0, so x² + 4 gives ±2i rather than -0 ± 2i
+        let (real, imag) = (-b / (2.0 * a) + 0.0, (-discriminant).sqrt() / (2.0 * a).abs());
+        return vec![(real, imag), (real, -imag)];
+    }
+    // q has the sign of b so nothing cancels; the second root comes from the product c/a
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    if q == 0.0 { vec![(0.0, 0.0), (0.0, 0.0)] } else { vec![(q / a, 0.0), (c / q, 0.0)] }
+}
+
+fn cubic(a: f64, b: f64, c: f64, d: f64) -> Vec<(f64, f64)> {
+    // Depressed form t³ + pt + q with x = t − b/3a
+    let (b, c, d) = (b / a, c / a, d / a);
+    let shift = b / 3.0;
+    let p = c - b * b / 3.0;
+    let q = 2.0 * b * b * b / 27.0 - b * c / 3.0 + d;
+    let discriminant = (q / 2.0).powi(2) + (p / 3.0).powi(3);
+    let t = if discriminant >= 0.0 {
+        let root = discriminant.sqrt();
+        (-q / 2.0 + root).cbrt() + (-q / 2.0 - root).cbrt()
+    } else {
+        // Three real roots: the trigonometric form avoids complex cube roots
+        2.0 * (-p / 3.0).sqrt() * ((3.0 * q / (2.0 * p) * (-3.0 / p).sqrt()).acos() / 3.0).cos()
+    };
+    let mut x = t - shift;
+    let coefficients = [1.0, b, c, d];
+    for _ in 0..NEWTON_STEPS {
+        let slope = 3.0 * x * x + 2.0 * b * x + c;
+        if slope == 0.0 {
+            break;
+        }
+        x -= evaluate(&coefficients, x) / slope;
+    }
+    // Divide out (x − root) and solve the quadratic that's left
+    let mut roots = quadratic(1.0, b + x, c + x * (b + x));
+    roots.push((x, 0.0));
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(actual: &[(f64, f64)], expected: &[(f64, f64)]) -> bool {
+        actual.len() == expected.len() && actual.iter().zip(expected).all(|(a, e)| (a.0 - e.0).abs() < 1e-9 && (a.1 - e.1).abs() < 1e-9)
+    }
+
+    #[test]
+    fn evaluates_by_horner() {
+        assert_eq!(evaluate(&[2.0, -3.0, 1.0], 4.0), 21.0);
+        assert_eq!(evaluate(&[5.0], 9.0), 5.0);
+    }
+
+    #[test]
+    fn solves_low_degrees() {
+        assert!(close(&roots(&[2.0, -4.0]).unwrap(), &[(2.0, 0.0)]));
+        assert!(close(&roots(&[1.0, -3.0, 2.0]).unwrap(), &[(1.0, 0.0), (2.0, 0.0)]));
+        assert!(close(&roots(&[1.0, 0.0, 1.0]).unwrap(), &[(0.0, 1.0), (0.0, -1.0)]));
+        assert!(close(&roots(&[0.0, 1.0, -1.0]).unwrap(), &[(1.0, 0.0)])); // Leading zero: linear
+        assert_eq!(roots(&[0.0, 0.0, 5.0]), None);
+        assert_eq!(roots(&[1.0, 0.0, 0.0, 0.0, 1.0]), None);
+    }
+
+    #[test]
+    fn solves_cubics() {
+        // (x − 1)(x − 2)(x − 3), three real roots
+        assert!(close(&roots(&[1.0, -6.0, 11.0, -6.0]).unwrap(), &[(1.0, 0.0), (2.0, 0.0), (3.0, 0.0)]));
+        // x³ − 1: one real root and a conjugate pair
+        let half_root3 = 3f64.sqrt() / 2.0;
+        assert!(close(&roots(&[1.0, 0.0, 0.0, -1.0]).unwrap(), &[(1.0, 0.0), (-0.5, half_root3), (-0.5, -half_root3)]));
+        assert!(close(&roots(&[2.0, 0.0, 0.0, 0.0]).unwrap(), &[(0.0, 0.0), (0.0, 0.0), (0.0, 0.0)]));
+    }
+}
@@ -0,0 +1,157 @@
+//! Integer number theory on u64: primality, prime factorization, modular powers.
+
+pub fn mod_mul(a: u64, b: u64, modulus: u64) -> u64 {
+    (a as u128 * b as u128 % modulus as u128) as u64
+}
+
+pub fn mod_pow(mut base: u64, mut exponent: u64, modulus: u64) -> u64 {
+    if modulus == 1 {
+        return 0;
+    }
+    let mut result = 1;
+    base %= modulus;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = mod_mul(result, base, modulus);
+        }
+        base = mod_mul(base, base, modulus);
+        exponent >>= 1;
+    }
+    result
+}
+
+/// Deterministic Miller-Rabin; these witnesses cover every u64.
+pub fn is_prime(n: u64) -> bool {
+    const WITNESSES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+    if n < 2 {
+        return false;
+    }
+    for p in WITNESSES {
+        if n.is_multiple_of(p) {
+            return n == p;
+        }
+    }
+    let (mut d, mut s) = (n - 1, 0);
+    while d % 2 == 0 {
+        d /= 2;
+        s += 1;
+    }
+    WITNESSES.iter().all(|&a| {
+        let mut x = mod_pow(a, d, n);
+        if x == 1 || x == n - 1 {
+            return true;
+        }
+        for _ in 1..s {
+            x = mod_mul(x, x, n);
+            if x == n - 1 {
+                return true;
+            }
+        }
+        false
+    })
+}
+
+/// Smallest prime greater than `n`, if it fits in a u64.
+pub fn next_prime(n: u64) -> Option<u64> {
+    let mut candidate = n.checked_add(1)?;
+    while !is_prime(candidate) {
+        candidate = candidate.checked_add(1)?;
+    }
+    Some(candidate)
+}
+
+/// Prime factors in ascending order, repeated by multiplicity: 360 → [2, 2, 2, 3, 3, 5].
+pub fn factor(n: u64) -> Vec<u64> {
+    let mut factors = Vec::new();
+    let mut rest = n;
+    for p in [2, 3, 5] {
+        while rest.is_multiple_of(p) && rest > 1 {
+            factors.push(p);
+            rest /= p;
+        }
+    }
+    split(rest, &mut factors);
+    factors.sort_unstable();
+    factors
+}
+
+fn split(n: u64, factors: &mut Vec<u64>) {
+    if n == 1 {
+        return;
+    }
+    if is_prime(n) {
+        factors.push(n);
+        return;
+    }
+    let divisor = pollard_rho(n);
+    split(divisor, factors);
+    split(n / divisor, factors);
+}
+
+// Some non-trivial divisor of the composite `n` (Pollard's rho with Floyd cycle detection)
+fn pollard_rho(n: u64) -> u64 {
+    for c in 1u128.. {
+        let step = |x: u64| ((mod_mul(x, x, n) as u128 + c) % n as u128) as u64;
+        let (mut x, mut y, mut d) = (2, 2, 1);
+        while d == 1 {
+            x = step(x);
+            y = step(step(y));
+            d = gcd(x.abs_diff(y), n);
+        }
+        if d != n {
+            return d;
+        }
+    }
+    unreachable!("a composite number always has a divisor")
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// `2^3 · 3^2 · 5` for the output of `factor`.
+pub fn format_factors(factors: &[u64]) -> String {
+    let mut parts = Vec::new();
+    let mut i = 0;
+    while i < factors.len() {
+        let count = factors[i..].iter().take_while(|&&p| p == factors[i]).count();
+        parts.push(if count > 1 { format!("{}^{}", factors[i], count) } else { factors[i].to_string() });
+        i += count;
+    }
+    parts.join(" · ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn primality() {
+        let small: Vec<u64> = (0..30).filter(|&n| is_prime(n)).collect();
+        assert_eq!(small, [2, 3, 5, 7, 11, 13, 17, 19, 23, 29]);
+        assert!(is_prime(9_007_199_254_740_881)); // Largest prime below 2^53
+        assert!(!is_prime(3_215_031_751)); // Strong pseudoprime to bases 2, 3, 5, 7
+        assert_eq!(next_prime(13), Some(17));
+        assert_eq!(next_prime(u64::MAX), None);
+    }
+
+    #[test]
+    fn factorization() {
+        assert_eq!(factor(360), [2, 2, 2, 3, 3, 5]);
+        assert_eq!(factor(97), [97]);
+        assert_eq!(factor(1), Vec::<u64>::new());
+        assert_eq!(factor(600_851_475_143), [71, 839, 1471, 6857]);
+        assert_eq!(factor(9_007_199_254_740_991), [6361, 69_431, 20_394_401]);
+        assert_eq!(format_factors(&factor(360)), "2^3 · 3^2 · 5");
+    }
+
+    #[test]
+    fn modular_power() {
+        assert_eq!(mod_pow(4, 13, 497), 445);
+        assert_eq!(mod_pow(2, 0, 1), 0);
+        assert_eq!(mod_pow(u64::MAX, 2, u64::MAX - 1), 1);
+    }
+}
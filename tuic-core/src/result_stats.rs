@@ -0,0 +1,56 @@
+//! `:stats results`: totals over the numbers in history, results and typed
+//! values alike, so a long session can be checked without entering anything again.
+
+/// Summary of the real values in history, oldest first.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResultStats {
+    pub values: Vec<f64>, // For the trend sparkline
+    pub skipped: usize, // Entries without a real value: complex, vectors, NaN
+    pub sum: f64,
+    pub mean: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl ResultStats {
+    /// Over `values`, in history order; None and non-finite values are counted as skipped.
+    pub fn new(values: impl IntoIterator<Item = Option<f64>>) -> Self {
+        let mut stats = Self { min: f64::INFINITY, max: f64::NEG_INFINITY, ..Self::default() };
+        for value in values {
+            match value.filter(|value| value.is_finite()) {
+                Some(value) => {
+                    stats.sum += value;
+                    stats.min = stats.min.min(value);
+                    stats.max = stats.max.max(value);
+                    stats.values.push(value);
+                }
+                None => stats.skipped += 1,
+            }
+        }
+        if stats.values.is_empty() {
+            (stats.min, stats.max) = (0.0, 0.0);
+        } else {
+            stats.mean = stats.sum / stats.values.len() as f64;
+        }
+        stats
+    }
+
+    pub fn count(&self) -> usize {
+        self.values.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn totals_skip_what_isnt_real() {
+        let stats = ResultStats::new([Some(4.0), None, Some(-2.0), Some(f64::NAN), Some(10.0)]);
+        assert_eq!(stats.count(), 3);
+        assert_eq!(stats.skipped, 2);
+        assert_eq!((stats.sum, stats.mean, stats.min, stats.max), (12.0, 4.0, -2.0, 10.0));
+        assert_eq!(stats.values, [4.0, -2.0, 10.0]);
+        assert_eq!(ResultStats::new([]), ResultStats::default());
+    }
+}
@@ -0,0 +1,151 @@
+//! Settings popup (`:settings`): a few config.toml settings, each stepped
+//! through its usual values, applied at once and written back to the file so
+//! the next start has them too. Other lines and comments in the file are kept.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::backup;
+use crate::config::Autosave;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Setting {
+    Precision,
+    StackSize,
+    HistorySize,
+    Mode,
+    Autosave,
+    Decimal,
+    Keys,
+}
+
+impl Setting {
+    pub const ALL: [Setting; 7] = [Setting::Precision, Setting::StackSize, Setting::HistorySize, Setting::Mode, Setting::Autosave, Setting::Decimal, Setting::Keys];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Setting::Precision => "Precision",
+            Setting::StackSize => "Stack entries",
+            Setting::HistorySize => "History entries",
+            Setting::Mode => "Mode at start",
+            Setting::Autosave => "Autosave",
+            Setting::Decimal => "Decimal separator",
+            Setting::Keys => "Key bindings",
+        }
+    }
+
+    /// Its config.toml key.
+    pub fn key(self) -> &'static str {
+        match self {
+            Setting::Precision => "precision",
+            Setting::StackSize => "stack-size",
+            Setting::HistorySize => "history-size",
+            Setting::Mode => "mode",
+            Setting::Autosave => "autosave",
+            Setting::Decimal => "decimal",
+            Setting::Keys => "keys",
+        }
+    }
+
+    /// The values Left/Right step through, as written in config.toml.
+    pub fn choices(self) -> &'static [&'static str] {
+        match self {
+            Setting::Precision => &["auto", "0", "1", "2", "3", "4", "6", "8", "10", "12", "15", "20"],
+            Setting::StackSize | Setting::HistorySize => &["100", "500", "1000", "5000", "10000", "100000", "unlimited"],
+            Setting::Mode => &["rpn", "infix"],
+            Setting::Autosave => &["change", "10s", "30s", "60s", "300s", "off"],
+            Setting::Decimal => &["point", "comma"],
+            Setting::Keys => &["default", "vi"],
+        }
+    }
+
+    /// The choice after or before `current`; one that isn't a choice steps from the first.
+    pub fn step(self, current: &str, up: bool) -> &'static str {
+        let choices = self.choices();
+        let index = match choices.iter().position(|choice| *choice == current) {
+            Some(index) if up => (index + 1).min(choices.len() - 1),
+            Some(index) => index.saturating_sub(1),
+            None => 0,
+        };
+        choices[index]
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SettingsDialog {
+    pub selected: usize, // Index into Setting::ALL
+}
+
+impl SettingsDialog {
+    pub fn move_selection(&mut self, down: bool) {
+        self.selected = if down { (self.selected + 1).min(Setting::ALL.len() - 1) } else { self.selected.saturating_sub(1) };
+    }
+
+    pub fn selected_setting(&self) -> Setting {
+        Setting::ALL[self.selected]
+    }
+}
+
+/// An autosave setting as written in config.toml.
+pub fn autosave_value(autosave: Autosave) -> String {
+    match autosave {
+        Autosave::Change => "change".to_string(),
+        Autosave::Seconds(seconds) => format!("{}s", seconds),
+        Autosave::Operations(operations) => format!("{}ops", operations),
+        Autosave::Off => "off".to_string(),
+    }
+}
+
+/// `content` with `key` set to `value`: its line replaced where the file has
+/// one before any [table], or added after the last top-level setting.
+pub fn with_setting(content: &str, key: &str, value: &str) -> String {
+    let line = format!("{} = {}", key, if value.parse::<u64>().is_ok() { value.to_string() } else { format!("\"{}\"", value) });
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    let tables = lines.iter().position(|line| line.trim_start().starts_with('[')).unwrap_or(lines.len());
+    let existing = lines[..tables].iter().position(|existing| existing.split('=').next().is_some_and(|name| name.trim() == key));
+    match existing {
+        Some(index) => lines[index] = line,
+        None => {
+            // After the last setting, so the line doesn't land below blank lines or a table's comment
+            let after = lines[..tables].iter().rposition(|line| line.contains('=')).map_or(0, |index| index + 1);
+            lines.insert(after, line);
+        }
+    }
+    let mut text = lines.join("\n");
+    text.push('\n');
+    text
+}
+
+/// Writes `key = value` into the config file at `path`, keeping a backup of the old one.
+pub fn save(path: &Path, key: &str, value: &str) -> Result<()> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    backup::write_with_backup(path, &with_setting(&content, key, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn rewrites_one_line_and_keeps_the_rest() {
+        let content = "# mine\nprecision = 4 # two more than usual\nkeys = \"vi\"\n\n[define]\ng = 9.81\n";
+        assert_eq!(with_setting(content, "precision", "auto"), "# mine\nprecision = \"auto\"\nkeys = \"vi\"\n\n[define]\ng = 9.81\n");
+        assert_eq!(with_setting(content, "stack-size", "5000"), "# mine\nprecision = 4 # two more than usual\nkeys = \"vi\"\nstack-size = 5000\n\n[define]\ng = 9.81\n");
+        assert_eq!(with_setting("", "mode", "infix"), "mode = \"infix\"\n");
+        // What's written reads back as the same setting
+        let config = Config::parse(&with_setting(content, "autosave", "30s")).unwrap();
+        assert_eq!(config.autosave.map(autosave_value).as_deref(), Some("30s"));
+
+        assert_eq!(Setting::Precision.step("4", true), "6");
+        assert_eq!(Setting::Precision.step("5", true), "auto");
+        assert_eq!(Setting::Keys.step("vi", true), "vi");
+        assert_eq!(Setting::Mode.step("infix", false), "rpn");
+    }
+}
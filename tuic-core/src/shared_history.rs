@@ -0,0 +1,153 @@
+//! History shared by every running instance (`share-history = "on"`), like a
+//! shell's `histappend`: new entries are appended to `history.json` as they are
+//! made, and entries other instances append show up on the next sync. Writers
+//! take an advisory lock on `history.lock` next to it, so two instances never
+//! interleave a read and a write.
+
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::Result;
+
+use crate::calculator::{HistoryEntry, parse_history, trim_history};
+use crate::versioned;
+
+pub struct SharedHistory {
+    path: PathBuf,
+    synced: Vec<HistoryEntry>, // History as of the last sync, which the file held then too
+    stamp: Option<Stamp>, // The file as of the last sync; a different one means another instance wrote
+}
+
+/// Modification time, length and inode: the time alone can be too coarse to tell two quick writes apart.
+type Stamp = (SystemTime, u64, u64);
+
+impl SharedHistory {
+    /// `loaded` is the history as read from `path` at startup, so it isn't appended a second time.
+    pub fn new(path: PathBuf, loaded: Vec<HistoryEntry>) -> Self {
+        Self { path, synced: loaded, stamp: None }
+    }
+
+    /// Brings `history` and the file together and returns whether `history` changed.
+    /// The last `appended` entries, added here since the last sync, are appended to
+    /// what's on disk before trimming to `limit`; any other local change (a pin, a
+    /// clear) is written over it as it stands.
+    pub fn sync(&mut self, history: &mut Vec<HistoryEntry>, appended: usize, limit: usize) -> Result<bool> {
+        if appended == 0 && *history == self.synced && stamp(&self.path) == self.stamp {
+            return Ok(false);
+        }
+        // A full history trims an old entry for each new one, so the appends are
+        // told apart from edits by redoing them on the last synced history
+        let added = &history[history.len() - appended.min(history.len())..];
+        let mut expected: Vec<HistoryEntry> = self.synced.iter().chain(added).cloned().collect();
+        let mut only_appended = expected == *history;
+        trim_history(&mut expected, limit);
+        only_appended |= expected == *history;
+
+        let lock = File::options().create(true).truncate(false).write(true).open(self.path.with_extension("lock"))?;
+        lock.lock()?; // Released when `lock` is dropped
+        let on_disk = match fs::read_to_string(&self.path) {
+            Ok(contents) => parse_history(&contents)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let mut merged = if only_appended { on_disk.iter().chain(added).cloned().collect() } else { history.clone() };
+        trim_history(&mut merged, limit);
+        if merged != on_disk {
+            let temporary = self.path.with_extension("json.tmp");
+            fs::write(&temporary, versioned::HISTORY.to_string(&merged)?)?;
+            fs::rename(&temporary, &self.path)?;
+        }
+        self.stamp = stamp(&self.path);
+
+        let changed = merged != *history;
+        *history = merged.clone();
+        self.synced = merged;
+        Ok(changed)
+    }
+}
+
+fn stamp(path: &Path) -> Option<Stamp> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len(), inode(&metadata)))
+}
+
+#[cfg(unix)]
+fn inode(metadata: &fs::Metadata) -> u64 {
+    std::os::unix::fs::MetadataExt::ino(metadata)
+}
+
+#[cfg(not(unix))]
+fn inode(_metadata: &fs::Metadata) -> u64 {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculator::StackValue;
+
+    #[test]
+    fn instances_see_each_others_entries() {
+        let dir = std::env::temp_dir().join(format!("tuic-shared-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("history.json");
+        let entry = |text: &str| HistoryEntry::new(text, Some(StackValue::Real(1.0)));
+
+        let (mut first, mut second) = (SharedHistory::new(path.clone(), Vec::new()), SharedHistory::new(path.clone(), Vec::new()));
+        let (mut mine, mut theirs) = (Vec::new(), Vec::new());
+        mine.push(entry("a"));
+        assert!(!first.sync(&mut mine, 1, 100).unwrap());
+        theirs.push(entry("b"));
+        assert!(second.sync(&mut theirs, 1, 100).unwrap());
+        assert_eq!(theirs, [entry("a"), entry("b")]);
+
+        // Nothing new on either side: no change
+        assert!(!second.sync(&mut theirs, 0, 100).unwrap());
+        mine.push(entry("c"));
+        assert!(first.sync(&mut mine, 1, 100).unwrap());
+        assert_eq!(mine, [entry("a"), entry("b"), entry("c")]);
+        assert!(second.sync(&mut theirs, 0, 100).unwrap());
+        assert_eq!(theirs, mine);
+
+        // A clear isn't an append, so it replaces the file for everyone
+        mine.clear();
+        first.sync(&mut mine, 0, 100).unwrap();
+        assert!(second.sync(&mut theirs, 0, 2).unwrap());
+        assert!(theirs.is_empty());
+
+        // A third instance starting with the file's history doesn't append it again
+        let mut loaded = vec![entry("d")];
+        fs::write(&path, serde_json::to_string(&loaded).unwrap()).unwrap();
+        let mut third = SharedHistory::new(path.clone(), loaded.clone());
+        assert!(!third.sync(&mut loaded, 0, 100).unwrap());
+        assert_eq!(loaded, [entry("d")]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn full_histories_keep_each_others_entries() {
+        let dir = std::env::temp_dir().join(format!("tuic-shared-full-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("history.json");
+        let entry = |text: &str| HistoryEntry::new(text, Some(StackValue::Real(1.0)));
+        let full = vec![entry("1"), entry("2"), entry("3")];
+        fs::write(&path, serde_json::to_string(&full).unwrap()).unwrap();
+
+        let (mut a, mut b) = (SharedHistory::new(path.clone(), full.clone()), SharedHistory::new(path.clone(), full.clone()));
+        let (mut mine, mut theirs) = (full.clone(), full);
+        // Each push trims the oldest entry, as the calculator's push does at its limit
+        let push = |history: &mut Vec<HistoryEntry>, text: &str| {
+            history.push(entry(text));
+            trim_history(history, 3);
+        };
+        push(&mut theirs, "x");
+        b.sync(&mut theirs, 1, 3).unwrap();
+        push(&mut mine, "y");
+        assert!(a.sync(&mut mine, 1, 3).unwrap());
+        assert_eq!(mine, [entry("3"), entry("x"), entry("y")]);
+        assert!(b.sync(&mut theirs, 0, 3).unwrap());
+        assert_eq!(theirs, mine);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
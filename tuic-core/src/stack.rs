@@ -0,0 +1,232 @@
+//! The value stack, bottom first, on a ring buffer: a full stack drops its
+//! oldest entry in O(1) instead of shifting the other thousand down, and an
+//! unlimited one just keeps growing.
+
+use std::collections::VecDeque;
+use std::collections::vec_deque::{Drain, Iter, IterMut};
+use std::ops::{Index, IndexMut, RangeBounds};
+
+use serde::{Deserialize, Serialize};
+
+use crate::calculator::StackEntry;
+
+/// Entries kept by default; pushing onto a full stack drops the bottom one.
+pub const MAX_STACK_SIZE: usize = 1000;
+
+/// A stack or history limit meaning no limit at all.
+pub const UNLIMITED: usize = usize::MAX;
+
+/// Saved as a plain list, so journals and workspaces written before it load unchanged.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Stack {
+    entries: VecDeque<StackEntry>,
+    #[serde(skip, default = "default_limit")]
+    limit: usize, // Set by the calculator (stack-size); UNLIMITED keeps everything
+    #[serde(skip)]
+    dropped: usize, // Bottom entries pushed off so far, for the stack panel's title
+}
+
+fn default_limit() -> usize {
+    MAX_STACK_SIZE
+}
+
+impl Default for Stack {
+    fn default() -> Self {
+        Stack { entries: VecDeque::new(), limit: MAX_STACK_SIZE, dropped: 0 }
+    }
+}
+
+impl Stack {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.entries.len() >= self.limit
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Bottom entries dropped to stay within the limit.
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Changes the limit, dropping bottom entries beyond a lower one.
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit.max(1);
+        let excess = self.entries.len().saturating_sub(self.limit);
+        self.entries.drain(..excess);
+        self.dropped += excess;
+    }
+
+    /// Pushes onto the top, dropping the bottom entry if the stack is full.
+    pub fn push(&mut self, entry: StackEntry) {
+        if self.is_full() {
+            self.entries.pop_front();
+            self.dropped += 1;
+        }
+        self.entries.push_back(entry);
+    }
+
+    pub fn pop(&mut self) -> Option<StackEntry> {
+        self.entries.pop_back()
+    }
+
+    /// The top entry.
+    pub fn last(&self) -> Option<&StackEntry> {
+        self.entries.back()
+    }
+
+    pub fn last_mut(&mut self) -> Option<&mut StackEntry> {
+        self.entries.back_mut()
+    }
+
+    /// The entry `depth` places down from the top: 0 is the top, 1 the one under it.
+    pub fn from_top(&self, depth: usize) -> Option<&StackEntry> {
+        self.entries.len().checked_sub(depth + 1).and_then(|index| self.entries.get(index))
+    }
+
+    pub fn get(&self, index: usize) -> Option<&StackEntry> {
+        self.entries.get(index)
+    }
+
+    pub fn iter(&self) -> Iter<'_, StackEntry> {
+        self.entries.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, StackEntry> {
+        self.entries.iter_mut()
+    }
+
+    /// Entries in `range`, bottom first.
+    pub fn range(&self, range: impl RangeBounds<usize>) -> Iter<'_, StackEntry> {
+        self.entries.range(range)
+    }
+
+    pub fn insert(&mut self, index: usize, entry: StackEntry) {
+        self.entries.insert(index, entry);
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<StackEntry> {
+        self.entries.remove(index)
+    }
+
+    /// Sorts bottom to top, keeping equal entries in order.
+    pub fn sort_by(&mut self, compare: impl FnMut(&StackEntry, &StackEntry) -> std::cmp::Ordering) {
+        self.entries.make_contiguous().sort_by(compare);
+    }
+
+    /// Turns the stack upside down.
+    pub fn reverse(&mut self) {
+        self.entries.make_contiguous().reverse();
+    }
+
+    pub fn swap(&mut self, a: usize, b: usize) {
+        self.entries.swap(a, b);
+    }
+
+    pub fn drain(&mut self, range: impl RangeBounds<usize>) -> Drain<'_, StackEntry> {
+        self.entries.drain(range)
+    }
+
+    /// Takes the entries from `index` up off the stack.
+    pub fn split_off(&mut self, index: usize) -> Stack {
+        Stack { entries: self.entries.split_off(index), limit: self.limit, dropped: 0 }
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        self.entries.truncate(len);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+impl Index<usize> for Stack {
+    type Output = StackEntry;
+
+    fn index(&self, index: usize) -> &StackEntry {
+        &self.entries[index]
+    }
+}
+
+impl IndexMut<usize> for Stack {
+    fn index_mut(&mut self, index: usize) -> &mut StackEntry {
+        &mut self.entries[index]
+    }
+}
+
+impl<'a> IntoIterator for &'a Stack {
+    type Item = &'a StackEntry;
+    type IntoIter = Iter<'a, StackEntry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter()
+    }
+}
+
+impl IntoIterator for Stack {
+    type Item = StackEntry;
+    type IntoIter = std::collections::vec_deque::IntoIter<StackEntry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+/// Pushes in order, so the last entry ends on top and a long list keeps its newest ones.
+impl Extend<StackEntry> for Stack {
+    fn extend<I: IntoIterator<Item = StackEntry>>(&mut self, entries: I) {
+        entries.into_iter().for_each(|entry| self.push(entry));
+    }
+}
+
+impl FromIterator<StackEntry> for Stack {
+    fn from_iter<I: IntoIterator<Item = StackEntry>>(entries: I) -> Self {
+        let mut stack = Stack::default();
+        stack.extend(entries);
+        stack
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculator::{Calculator, EntryOrigin, StackValue};
+    use crate::expr::Expr;
+
+    fn entry(value: usize) -> StackEntry {
+        let text = value.to_string();
+        StackEntry { expression: text.as_str().into(), ast: Expr::number(value as f64, text).into(), result: StackValue::Real(value as f64), modes: Calculator::new().entry_modes(), label: None, id: value as u64, created_ms: 0, origin: EntryOrigin::Typed }
+    }
+
+    #[test]
+    fn drops_the_bottom_when_full() {
+        let mut stack: Stack = (0..MAX_STACK_SIZE).map(entry).collect();
+        assert!(stack.is_full());
+        stack.push(entry(MAX_STACK_SIZE));
+        assert_eq!(stack.len(), MAX_STACK_SIZE);
+        assert_eq!(&*stack[0].expression, "1");
+        assert_eq!(stack.from_top(0).map(|entry| &*entry.expression), Some("1000"));
+        assert_eq!(stack.from_top(1).map(|entry| &*entry.expression), Some("999"));
+        assert!(stack.from_top(MAX_STACK_SIZE).is_none());
+        assert_eq!(stack.dropped(), 1);
+
+        stack.set_limit(UNLIMITED);
+        stack.push(entry(MAX_STACK_SIZE + 1));
+        assert_eq!(stack.len(), MAX_STACK_SIZE + 1);
+        stack.set_limit(10);
+        assert_eq!(stack.len(), 10);
+        assert_eq!(stack.dropped(), MAX_STACK_SIZE - 8);
+        assert_eq!(&*stack[0].expression, "992");
+    }
+}
@@ -0,0 +1,117 @@
+/// Pasted text that looks like delimited columns, awaiting a column choice.
+#[derive(Debug, Clone)]
+pub struct TableImport {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+    pub selected: Vec<bool>,
+    pub cursor: usize,
+    pub summary: bool,
+}
+
+/// Column statistics pushed when importing in summary mode.
+pub struct ColumnSummary {
+    pub sum: f64,
+    pub mean: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl TableImport {
+    /// Recognizes tab- or comma-separated text with at least two columns or rows
+    /// and at least one numeric cell; anything else is treated as ordinary input.
+    /// A single line only counts when it has tabs, since `max(1, 2)` or `[1,2,3]`
+    /// has commas too.
+    pub fn detect(text: &str) -> Option<Self> {
+        let lines: Vec<&str> = text.lines().map(str::trim_end).filter(|line| !line.trim().is_empty()).collect();
+        if lines.len() < 2 && !text.contains('\t') {
+            return None;
+        }
+        let delimiter = if text.contains('\t') {
+            '\t'
+        } else if lines.iter().any(|line| line.contains(',')) {
+            ','
+        } else {
+            // A plain column of numbers, one per line
+            '\n'
+        };
+
+        let mut rows: Vec<Vec<String>> = lines
+            .iter()
+            .map(|line| line.split(delimiter).map(|cell| cell.trim().to_string()).collect())
+            .collect();
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        if columns == 0 || (columns < 2 && rows.len() < 2) {
+            return None;
+        }
+        if !rows.iter().flatten().any(|cell| parse_cell(cell).is_some()) {
+            return None;
+        }
+
+        // A first row without any numbers is taken as the header
+        let headers = if rows[0].iter().all(|cell| parse_cell(cell).is_none()) && rows.len() > 1 {
+            rows.remove(0)
+        } else {
+            Vec::new()
+        };
+
+        let mut import = Self {
+            headers,
+            rows,
+            selected: vec![false; columns],
+            cursor: 0,
+            summary: false,
+        };
+        // Preselect every column that contains numbers
+        for column in 0..columns {
+            import.selected[column] = !import.values(column).is_empty();
+        }
+        Some(import)
+    }
+
+    pub fn columns(&self) -> usize {
+        self.selected.len()
+    }
+
+    pub fn column_name(&self, column: usize) -> String {
+        match self.headers.get(column) {
+            Some(header) if !header.is_empty() => header.clone(),
+            _ => format!("column {}", column + 1),
+        }
+    }
+
+    /// Numeric cells of a column; text and blank cells are skipped.
+    pub fn values(&self, column: usize) -> Vec<f64> {
+        self.rows.iter().filter_map(|row| row.get(column).and_then(|cell| parse_cell(cell))).collect()
+    }
+
+    pub fn summarize(&self, column: usize) -> Option<ColumnSummary> {
+        let values = self.values(column);
+        if values.is_empty() {
+            return None;
+        }
+        let sum: f64 = values.iter().sum();
+        Some(ColumnSummary {
+            sum,
+            mean: sum / values.len() as f64,
+            min: values.iter().copied().fold(f64::INFINITY, f64::min),
+            max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        })
+    }
+
+    pub fn move_cursor(&mut self, forward: bool) {
+        let columns = self.columns();
+        self.cursor = if forward { (self.cursor + 1) % columns } else { (self.cursor + columns - 1) % columns };
+    }
+
+    pub fn toggle_column(&mut self) {
+        self.selected[self.cursor] = !self.selected[self.cursor];
+    }
+
+    pub fn toggle_summary(&mut self) {
+        self.summary = !self.summary;
+    }
+}
+
+fn parse_cell(cell: &str) -> Option<f64> {
+    cell.trim().parse::<f64>().ok().filter(|value| value.is_finite())
+}
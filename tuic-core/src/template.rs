@@ -0,0 +1,100 @@
+//! Output templates from `config.toml`, such as `"{expr} → {result} [{base}]"`,
+//! for history lines, clipboard copies and `:export text`. `{{` and `}}` are
+//! literal braces.
+
+/// Names a template can use, in braces.
+pub const PLACEHOLDERS: &[&str] = &["expr", "result", "base", "angle", "label", "id", "origin", "time"];
+
+/// Values for one rendering: an entry's expression and formatted result, and the modes.
+/// The id, origin and time are a stack entry's and empty for history lines.
+pub struct Fields<'a> {
+    pub expr: &'a str,
+    pub result: &'a str,
+    pub base: &'static str,
+    pub angle: &'static str,
+    pub label: &'a str,
+    pub id: &'a str,
+    pub origin: &'a str,
+    pub time: &'a str,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Piece {
+    Text(String),
+    Field(&'static str),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    pieces: Vec<Piece>,
+}
+
+impl Template {
+    /// Checks the braces and placeholder names up front, so rendering can't fail.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut pieces = Vec::new();
+        let mut literal = String::new();
+        let mut chars = text.chars().peekable();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '{' if chars.next_if_eq(&'{').is_some() => literal.push('{'),
+                '}' if chars.next_if_eq(&'}').is_some() => literal.push('}'),
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(ch) => name.push(ch),
+                            None => return Err(format!("unclosed '{{{}'", name)),
+                        }
+                    }
+                    let field = PLACEHOLDERS.iter().find(|placeholder| **placeholder == name).ok_or_else(|| format!("unknown placeholder {{{}}}", name))?;
+                    if !literal.is_empty() {
+                        pieces.push(Piece::Text(std::mem::take(&mut literal)));
+                    }
+                    pieces.push(Piece::Field(field));
+                }
+                '}' => return Err("unmatched '}' (write '}}' for a brace)".to_string()),
+                _ => literal.push(ch),
+            }
+        }
+        if !literal.is_empty() {
+            pieces.push(Piece::Text(literal));
+        }
+        Ok(Self { pieces })
+    }
+
+    pub fn render(&self, fields: &Fields) -> String {
+        self.pieces
+            .iter()
+            .map(|piece| match piece {
+                Piece::Text(text) => text,
+                Piece::Field("expr") => fields.expr,
+                Piece::Field("result") => fields.result,
+                Piece::Field("base") => fields.base,
+                Piece::Field("angle") => fields.angle,
+                Piece::Field("id") => fields.id,
+                Piece::Field("origin") => fields.origin,
+                Piece::Field("time") => fields.time,
+                Piece::Field(_) => fields.label,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn substitutes_placeholders() {
+        let fields = Fields { expr: "0xFF + 1", result: "0x100", base: "HEX", angle: "RAD", label: "total", id: "7", origin: "typed", time: "" };
+        let template = Template::parse("{expr} → {result} [{base}]").unwrap();
+        assert_eq!(template.render(&fields), "0xFF + 1 → 0x100 [HEX]");
+        assert_eq!(Template::parse("{label}: {{{result}}} {angle}").unwrap().render(&fields), "total: {0x100} RAD");
+        assert_eq!(Template::parse("#{id} ({origin})").unwrap().render(&fields), "#7 (typed)");
+        assert_eq!(Template::parse("{value}").unwrap_err(), "unknown placeholder {value}");
+        assert!(Template::parse("{result").is_err());
+        assert!(Template::parse("result}").is_err());
+    }
+}
@@ -0,0 +1,126 @@
+//! Values with an uncertainty, `5.0 ± 0.1`, for lab-style calculations.
+//! Errors propagate to first order assuming independent inputs: each input
+//! contributes |∂f/∂x|·σx, and the contributions add in quadrature.
+
+use serde::{Deserialize, Serialize};
+
+use crate::numeric;
+
+/// Significant digits the uncertainty is shown with; the value is rounded to match.
+const SIGMA_DIGITS: i32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Measurement {
+    pub value: f64,
+    pub sigma: f64, // Standard uncertainty, never negative
+}
+
+impl Measurement {
+    pub fn new(value: f64, sigma: f64) -> Self {
+        Self { value, sigma: sigma.abs() }
+    }
+
+    pub fn exact(value: f64) -> Self {
+        Self { value, sigma: 0.0 }
+    }
+}
+
+/// Combines the contributions ∂f/∂x·σx in quadrature, skipping exact inputs
+/// so a NaN partial (like ln of a negative base) doesn't matter when σ is 0.
+fn propagate(value: f64, contributions: &[(f64, f64)]) -> Measurement {
+    let sigma = contributions.iter().filter(|(_, sigma)| *sigma != 0.0).map(|(partial, sigma)| (partial * sigma).powi(2)).sum::<f64>().sqrt();
+    Measurement::new(value, sigma)
+}
+
+/// `a op b` with exact partial derivatives; None for operators without one.
+pub fn binary(op: char, a: Measurement, b: Measurement) -> Option<Measurement> {
+    let (x, y) = (a.value, b.value);
+    let (value, dx, dy) = match op {
+        '+' => (x + y, 1.0, 1.0),
+        '-' => (x - y, 1.0, -1.0),
+        '*' => (x * y, y, x),
+        '/' => (x / y, 1.0 / y, -x / (y * y)),
+        '^' => (x.powf(y), y * x.powf(y - 1.0), x.powf(y) * x.ln()),
+        _ => return None,
+    };
+    Some(propagate(value, &[(dx, a.sigma), (dy, b.sigma)]))
+}
+
+/// A real function of measurements, with its partial derivatives estimated numerically.
+pub fn apply(function: impl Fn(&[f64]) -> f64, args: &[Measurement]) -> Measurement {
+    let values: Vec<f64> = args.iter().map(|arg| arg.value).collect();
+    let contributions: Vec<(f64, f64)> = args
+        .iter()
+        .enumerate()
+        .filter(|(_, arg)| arg.sigma != 0.0)
+        .map(|(i, arg)| {
+            let mut point = values.clone();
+            let partial = numeric::derivative(
+                |x| {
+                    point[i] = x;
+                    function(&point)
+                },
+                arg.value,
+            );
+            (partial.map_or(f64::NAN, |estimate| estimate.value), arg.sigma)
+        })
+        .collect();
+    propagate(function(&values), &contributions)
+}
+
+/// `value ± sigma` with the uncertainty to two significant digits and the
+/// value rounded to the same decimal place: 12.3456 ± 0.0234 is 12.346 ± 0.023.
+pub fn format(measurement: Measurement) -> String {
+    let Measurement { value, sigma } = measurement;
+    if sigma == 0.0 || !sigma.is_finite() || !value.is_finite() {
+        return format!("{} ± {}", value, sigma);
+    }
+    // The exponent after rounding, so 0.09996 counts as 0.10
+    let rounded = format!("{:.*e}", SIGMA_DIGITS as usize - 1, sigma);
+    let exponent: i32 = rounded.split_once('e').and_then(|(_, exponent)| exponent.parse().ok()).unwrap_or(0);
+    let decimals = SIGMA_DIGITS - 1 - exponent;
+    if decimals >= 0 {
+        format!("{:.*} ± {:.*}", decimals as usize, value, decimals as usize, sigma)
+    } else {
+        // Uncertain in the tens or more: round both to that place
+        let place = 10f64.powi(-decimals);
+        format!("{:.0} ± {:.0}", (value / place).round() * place, (sigma / place).round() * place)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(actual: Measurement, value: f64, sigma: f64) -> bool {
+        (actual.value - value).abs() < 1e-9 && (actual.sigma - sigma).abs() < 1e-9
+    }
+
+    #[test]
+    fn propagates_through_operators() {
+        let (a, b) = (Measurement::new(5.0, 0.3), Measurement::new(2.0, 0.4));
+        assert!(close(binary('+', a, b).unwrap(), 7.0, 0.5));
+        assert!(close(binary('-', a, b).unwrap(), 3.0, 0.5));
+        // Relative errors add in quadrature: 6% and 20%
+        assert!(close(binary('*', a, b).unwrap(), 10.0, 10.0 * 0.06f64.hypot(0.2)));
+        assert!(close(binary('/', a, b).unwrap(), 2.5, 2.5 * 0.06f64.hypot(0.2)));
+        assert!(close(binary('^', Measurement::new(-2.0, 0.1), Measurement::exact(2.0)).unwrap(), 4.0, 0.4));
+        assert_eq!(binary('<', a, b), None);
+    }
+
+    #[test]
+    fn propagates_through_functions() {
+        let sqrt: fn(&[f64]) -> f64 = |args| args[0].sqrt();
+        assert!(close(apply(sqrt, &[Measurement::new(16.0, 0.8)]), 4.0, 0.1));
+        let exact = apply(sqrt, &[Measurement::exact(9.0)]);
+        assert_eq!((exact.value, exact.sigma), (3.0, 0.0));
+    }
+
+    #[test]
+    fn lab_style_rounding() {
+        assert_eq!(format(Measurement::new(12.3456, 0.0234)), "12.346 ± 0.023");
+        assert_eq!(format(Measurement::new(5.0, 0.1)), "5.00 ± 0.10");
+        assert_eq!(format(Measurement::new(1234.5, 123.0)), "1230 ± 120");
+        assert_eq!(format(Measurement::new(4.0, 0.09996)), "4.00 ± 0.10");
+    }
+}
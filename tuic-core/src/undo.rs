@@ -0,0 +1,54 @@
+use crate::stack::Stack;
+
+const MAX_UNDO_STEPS: usize = 100;
+
+/// Stack, and the grand total its results added to, as they were before one logical action.
+#[derive(Clone)]
+pub struct UndoStep {
+    pub label: String,
+    pub stack: Stack,
+    pub grand_total: f64,
+}
+
+/// Undo log built on transactions: everything between the outermost `begin`
+/// and its `commit` becomes one step, so composite actions undo in one go.
+#[derive(Default)]
+pub struct UndoLog {
+    steps: Vec<UndoStep>,
+    open: Option<UndoStep>,
+    depth: usize,
+}
+
+impl UndoLog {
+    /// Opens a transaction; nested calls join the one already open.
+    pub fn begin(&mut self, label: &str, stack: &Stack, grand_total: f64) {
+        if self.depth == 0 {
+            self.open = Some(UndoStep { label: label.to_string(), stack: stack.clone(), grand_total });
+        }
+        self.depth += 1;
+    }
+
+    /// Closes a transaction; the outermost one is recorded only if the stack changed.
+    pub fn commit(&mut self, stack: &Stack) {
+        self.depth = self.depth.saturating_sub(1);
+        if self.depth > 0 {
+            return;
+        }
+        if let Some(step) = self.open.take()
+            && !same_stack(&step.stack, stack)
+        {
+            if self.steps.len() >= MAX_UNDO_STEPS {
+                self.steps.remove(0);
+            }
+            self.steps.push(step);
+        }
+    }
+
+    pub fn undo(&mut self) -> Option<UndoStep> {
+        self.steps.pop()
+    }
+}
+
+fn same_stack(a: &Stack, b: &Stack) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(x, y)| x.expression == y.expression && x.result == y.result && x.label == y.label)
+}
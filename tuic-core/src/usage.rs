@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+
+use crate::calculator::HistoryEntry;
+
+const OPERATORS: &str = "+-*/^";
+
+/// Operator/function frequencies gathered from the expressions in history.
+#[derive(Debug, Clone, Default)]
+pub struct UsageStats {
+    pub expressions: usize,
+    pub average_length: f64,
+    pub operators: Vec<(String, usize)>,
+    pub functions: Vec<(String, usize)>,
+}
+
+impl UsageStats {
+    pub fn from_history(history: &[HistoryEntry]) -> Self {
+        let mut operators: HashMap<String, usize> = HashMap::new();
+        let mut functions: HashMap<String, usize> = HashMap::new();
+        let mut total_length = 0;
+
+        for entry in history {
+            let expression = &entry.expression;
+            total_length += expression.chars().count();
+
+            let mut identifier = String::new();
+            for ch in expression.chars() {
+                if ch.is_alphabetic() || (ch.is_ascii_digit() && !identifier.is_empty()) {
+                    identifier.push(ch);
+                    continue;
+                }
+                if ch == '(' && !identifier.is_empty() {
+                    *functions.entry(identifier.clone()).or_insert(0) += 1;
+                }
+                identifier.clear();
+                if OPERATORS.contains(ch) {
+                    *operators.entry(ch.to_string()).or_insert(0) += 1;
+                }
+            }
+        }
+
+        Self {
+            expressions: history.len(),
+            average_length: if history.is_empty() { 0.0 } else { total_length as f64 / history.len() as f64 },
+            operators: sorted_by_count(operators),
+            functions: sorted_by_count(functions),
+        }
+    }
+}
+
+// Most frequent first, ties broken alphabetically so the popup is stable
+fn sorted_by_count(counts: HashMap<String, usize>) -> Vec<(String, usize)> {
+    let mut sorted: Vec<(String, usize)> = counts.into_iter().collect();
+    sorted.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    sorted
+}
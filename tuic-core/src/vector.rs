@@ -0,0 +1,129 @@
+//! Vectors, `[1, 2, 3]`: lists of reals with element-wise arithmetic. A
+//! scalar on either side of an operator applies to every element, and
+//! elements are indexed from 1, as on HP calculators.
+
+use crate::calculator::StackValue;
+use crate::error::CalculatorError;
+
+/// Elements a stack row shows before the rest is summarized; inspect lists them all.
+pub const ROW_ELEMENTS: usize = 6;
+
+/// `a op b` element by element, for when either side is a vector.
+pub fn binary(op: char, a: &StackValue, b: &StackValue) -> Result<StackValue, CalculatorError> {
+    elementwise(op, &elements(a, op)?, &elements(b, op)?).map(StackValue::Vector)
+}
+
+/// A vector's elements, or a real as the one element every element is combined with.
+fn elements(value: &StackValue, op: char) -> Result<Vec<f64>, CalculatorError> {
+    match value {
+        StackValue::Vector(items) => Ok(items.clone()),
+        other => other.as_real().map(|x| vec![x]).ok_or_else(|| CalculatorError::ComplexUnsupported { operation: op.to_string() }),
+    }
+}
+
+/// `xs op ys` pairwise; a single element on either side is paired with every element of the other.
+pub fn elementwise(op: char, xs: &[f64], ys: &[f64]) -> Result<Vec<f64>, CalculatorError> {
+    let len = match (xs.len(), ys.len()) {
+        (1, len) | (len, 1) => len,
+        (left, right) if left == right => left,
+        (left, right) => return Err(CalculatorError::VectorLength { left, right }),
+    };
+    let at = |items: &[f64], i: usize| if items.len() == 1 { items[0] } else { items[i] };
+    (0..len)
+        .map(|i| {
+            let (x, y) = (at(xs, i), at(ys, i));
+            match op {
+                '+' => Ok(x + y),
+                '-' => Ok(x - y),
+                '*' => Ok(x * y),
+                '/' if y == 0.0 => Err(CalculatorError::DivisionByZero { dividend: x }),
+                '/' => Ok(x / y),
+                '^' => Ok(x.powf(y)),
+                _ => Err(CalculatorError::VectorUnsupported { operation: op.to_string() }),
+            }
+        })
+        .collect()
+}
+
+pub fn dot(xs: &[f64], ys: &[f64]) -> Result<f64, CalculatorError> {
+    if xs.len() != ys.len() {
+        return Err(CalculatorError::VectorLength { left: xs.len(), right: ys.len() });
+    }
+    Ok(xs.iter().zip(ys).map(|(x, y)| x * y).sum())
+}
+
+/// Euclidean length.
+pub fn norm(xs: &[f64]) -> f64 {
+    xs.iter().map(|x| x * x).sum::<f64>().sqrt()
+}
+
+/// The element at a 1-based `index`.
+pub fn index(xs: &[f64], index: f64) -> Result<f64, CalculatorError> {
+    let position = index as usize;
+    if index.fract() != 0.0 || index < 1.0 || position > xs.len() {
+        return Err(CalculatorError::IndexOutOfRange { index, len: xs.len() });
+    }
+    Ok(xs[position - 1])
+}
+
+/// `[1, 2, 3]`, each element formatted by `element`; with a decimal comma the
+/// elements are separated by ';' instead, as they are typed.
+pub fn format(xs: &[f64], separator: char, element: impl Fn(f64) -> String) -> String {
+    let items: Vec<String> = xs.iter().map(|x| element(*x)).collect();
+    format!("[{}]", items.join(&format!("{} ", separator)))
+}
+
+/// Like `format`, cut to the first ROW_ELEMENTS elements with a count of the rest.
+pub fn format_compact(xs: &[f64], separator: char, element: impl Fn(f64) -> String) -> String {
+    if xs.len() <= ROW_ELEMENTS {
+        return format(xs, separator, element);
+    }
+    let items: Vec<String> = xs[..ROW_ELEMENTS].iter().map(|x| element(*x)).collect();
+    format!("[{}{} … +{}]", items.join(&format!("{} ", separator)), separator, xs.len() - ROW_ELEMENTS)
+}
+
+/// Elements typed between brackets, `[1, 2.5, -3]`, each read by `number`.
+pub fn parse(text: &str, separator: char, number: impl Fn(&str) -> Option<f64>) -> Option<Vec<f64>> {
+    let inner = text.trim().strip_prefix('[')?.strip_suffix(']')?;
+    if inner.trim().is_empty() {
+        return Some(Vec::new());
+    }
+    inner.split(separator).map(|item| number(item.trim())).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn element_wise_with_broadcasting() {
+        assert_eq!(elementwise('+', &[1.0, 2.0, 3.0], &[10.0, 20.0, 30.0]), Ok(vec![11.0, 22.0, 33.0]));
+        assert_eq!(elementwise('*', &[2.0], &[1.0, 2.0, 3.0]), Ok(vec![2.0, 4.0, 6.0]));
+        assert_eq!(elementwise('/', &[1.0, 2.0], &[4.0]), Ok(vec![0.25, 0.5]));
+        assert_eq!(elementwise('-', &[1.0, 2.0], &[1.0, 2.0, 3.0]), Err(CalculatorError::VectorLength { left: 2, right: 3 }));
+        assert_eq!(elementwise('/', &[1.0, 2.0], &[1.0, 0.0]), Err(CalculatorError::DivisionByZero { dividend: 2.0 }));
+    }
+
+    #[test]
+    fn dot_norm_and_index() {
+        assert_eq!(dot(&[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0]), Ok(32.0));
+        assert!(dot(&[1.0], &[1.0, 2.0]).is_err());
+        assert_eq!(norm(&[3.0, 4.0]), 5.0);
+        assert_eq!(index(&[7.0, 8.0, 9.0], 1.0), Ok(7.0));
+        assert_eq!(index(&[7.0, 8.0, 9.0], 3.0), Ok(9.0));
+        for bad in [0.0, 4.0, 1.5, -1.0] {
+            assert!(index(&[7.0, 8.0, 9.0], bad).is_err());
+        }
+    }
+
+    #[test]
+    fn formats_and_parses() {
+        let items: Vec<f64> = (1..=8).map(f64::from).collect();
+        assert_eq!(format(&items[..3], ',', |x| x.to_string()), "[1, 2, 3]");
+        assert_eq!(format_compact(&items, ',', |x| x.to_string()), "[1, 2, 3, 4, 5, 6, … +2]");
+        assert_eq!(parse("[1, 2.5,-3]", ',', |text| text.parse().ok()), Some(vec![1.0, 2.5, -3.0]));
+        assert_eq!(parse("[]", ',', |text| text.parse().ok()), Some(vec![]));
+        assert_eq!(parse("[1, x]", ',', |text| text.parse().ok()), None);
+        assert_eq!(parse("[1, 2", ',', |text| text.parse().ok()), None);
+    }
+}
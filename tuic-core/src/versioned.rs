@@ -0,0 +1,130 @@
+//! On-disk formats of the files kept between runs (`history.json`,
+//! `session.journal`). Each is written as `{"version": N, "data": ...}`, and
+//! reading goes through the format's migrations, one per version step, so a
+//! file from an older release is upgraded rather than rejected or misread
+//! when `StackEntry` or `StackValue` change shape. A file from a newer release
+//! is refused instead of being guessed at.
+
+use std::fmt;
+
+use anyhow::{Result, bail};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::calculator::{HistoryEntry, StackValue};
+
+/// Upgrades a file's data from one version to the next.
+type Migration = fn(Value) -> Result<Value>;
+
+pub struct Format {
+    pub name: &'static str,
+    migrations: &'static [Migration], // The n-th takes version n + 1 to n + 2
+    unversioned: fn(&Value) -> u64,   // Version of a file from before versioning, told from its shape
+}
+
+/// `history.json`. Version 1 held pre-formatted "expr = result" lines, version 2 `HistoryEntry`s.
+pub const HISTORY: Format = Format { name: "history", migrations: &[history_from_lines], unversioned: unversioned_history };
+
+/// `session.journal`. Version 1 is the `Journal` as first written.
+pub const JOURNAL: Format = Format { name: "journal", migrations: &[], unversioned: |_| 1 };
+
+#[derive(Serialize)]
+struct Envelope<'a, T> {
+    version: u64,
+    data: &'a T,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StoredEnvelope {
+    version: u64,
+    data: Value,
+}
+
+/// A file written by a later release, in a version this one can't read.
+#[derive(Debug)]
+pub struct NewerVersion {
+    pub name: &'static str,
+    pub version: u64,
+    pub supported: u64,
+}
+
+impl fmt::Display for NewerVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} file is format version {}, newer than this release reads ({}); upgrade to open it", self.name, self.version, self.supported)
+    }
+}
+
+impl std::error::Error for NewerVersion {}
+
+impl Format {
+    /// The version written now.
+    pub fn version(&self) -> u64 {
+        self.migrations.len() as u64 + 1
+    }
+
+    pub fn to_string<T: Serialize>(&self, data: &T) -> Result<String> {
+        Ok(serde_json::to_string(&Envelope { version: self.version(), data })?)
+    }
+
+    /// Reads a file of any version up to the current one, migrating it first.
+    pub fn parse<T: DeserializeOwned>(&self, text: &str) -> Result<T> {
+        let value: Value = serde_json::from_str(text)?;
+        let (version, mut data) = match serde_json::from_value::<StoredEnvelope>(value.clone()) {
+            Ok(envelope) => (envelope.version, envelope.data),
+            Err(_) => ((self.unversioned)(&value), value),
+        };
+        if version > self.version() {
+            return Err(NewerVersion { name: self.name, version, supported: self.version() }.into());
+        }
+        if version == 0 {
+            bail!("{} file has no format version 0", self.name);
+        }
+        for migrate in &self.migrations[version as usize - 1..] {
+            data = migrate(data)?;
+        }
+        Ok(serde_json::from_value(data)?)
+    }
+}
+
+fn unversioned_history(value: &Value) -> u64 {
+    match value.as_array().and_then(|entries| entries.first()) {
+        Some(Value::String(_)) => 1,
+        _ => 2,
+    }
+}
+
+fn history_from_lines(data: Value) -> Result<Value> {
+    let entries: Vec<HistoryEntry> = serde_json::from_value::<Vec<String>>(data)?
+        .into_iter()
+        .map(|line| match line.split_once(" = ") {
+            Some((expression, result)) => HistoryEntry::new(expression, result.parse::<f64>().ok().map(StackValue::Real)),
+            None => HistoryEntry::new(line, None),
+        })
+        .collect();
+    Ok(serde_json::to_value(entries)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn older_files_are_migrated_and_newer_ones_refused() {
+        let entries = vec![HistoryEntry::new("1 + 2", Some(StackValue::Real(3.0)))];
+        let written = HISTORY.to_string(&entries).unwrap();
+        assert!(written.starts_with("{\"version\":2,"));
+        assert_eq!(HISTORY.parse::<Vec<HistoryEntry>>(&written).unwrap(), entries);
+
+        // Unversioned files of either shape, and a versioned one of the first
+        let bare = serde_json::to_string(&entries).unwrap();
+        assert_eq!(HISTORY.parse::<Vec<HistoryEntry>>(&bare).unwrap(), entries);
+        assert_eq!(HISTORY.parse::<Vec<HistoryEntry>>(r#"["1 + 2 = 3"]"#).unwrap(), entries);
+        assert_eq!(HISTORY.parse::<Vec<HistoryEntry>>(r#"{"version":1,"data":["1 + 2 = 3"]}"#).unwrap(), entries);
+
+        let error = HISTORY.parse::<Vec<HistoryEntry>>(r#"{"version":3,"data":[]}"#).unwrap_err();
+        assert_eq!(error.downcast_ref::<NewerVersion>().map(|newer| (newer.version, newer.supported)), Some((3, 2)));
+        assert!(HISTORY.parse::<Vec<HistoryEntry>>(r#"{"version":0,"data":[]}"#).is_err());
+    }
+}
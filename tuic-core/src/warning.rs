@@ -0,0 +1,93 @@
+//! Warnings: something the calculator did as asked, but not quite exactly, so
+//! neither an error that stops it nor something to let pass silently. Each
+//! one is shown in the status bar until the next key and kept in the error log.
+
+use thiserror::Error;
+
+use crate::calculator::{BaseMode, StackValue, WordSize};
+
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum Warning {
+    #[error("{literal} has more digits than a float holds exactly and became {rounded}")]
+    PrecisionLoss { literal: String, rounded: f64 },
+    #[error("{value} doesn't fit in {bits} bits and is shown wrapped to them")]
+    Overflow { value: f64, bits: u32 },
+    #[error("Theme '{name}' couldn't be loaded, so the built-in default is used: {reason}")]
+    ThemeFallback { name: String, reason: String },
+}
+
+impl Warning {
+    /// Stable identifier, numbered apart from the errors' E codes.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Warning::PrecisionLoss { .. } => "W101",
+            Warning::Overflow { .. } => "W102",
+            Warning::ThemeFallback { .. } => "W103",
+        }
+    }
+
+    /// What's worth saying about a new stack entry: an integer typed in it that
+    /// a float rounds, or a result too wide for the HEX/BIN word size.
+    pub fn for_entry(expression: &str, result: &StackValue, base: BaseMode, word_size: WordSize) -> Option<Warning> {
+        if base == BaseMode::Decimal
+            && let Some((literal, rounded)) = inexact_integer(expression)
+        {
+            return Some(Warning::PrecisionLoss { literal, rounded });
+        }
+        let value = result.as_real().filter(|value| value.is_finite() && value.fract() == 0.0)?;
+        let bits = word_size.bits().filter(|_| base != BaseMode::Decimal)?;
+        let (min, max) = (-(2f64.powi(bits as i32 - 1)), 2f64.powi(bits as i32) - 1.0);
+        (value < min || value > max).then_some(Warning::Overflow { value, bits })
+    }
+}
+
+/// The first decimal integer in `text` that an f64 can't hold exactly, with what it became.
+fn inexact_integer(text: &str) -> Option<(String, f64)> {
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if !bytes[i].is_ascii_digit() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < bytes.len() && bytes[i].is_ascii_digit() {
+            i += 1;
+        }
+        // Only whole literals: not part of a name, a decimal fraction or an exponent
+        let attached = |byte: Option<&u8>| byte.is_some_and(|b| b.is_ascii_alphanumeric() || *b == b'.' || *b == b'_');
+        if attached(start.checked_sub(1).and_then(|before| bytes.get(before))) || attached(bytes.get(i)) {
+            continue;
+        }
+        let literal = &text[start..i];
+        let digits = literal.trim_start_matches('0');
+        if digits.len() > 15
+            && let Ok(rounded) = digits.parse::<f64>()
+            && format!("{:.0}", rounded) != digits
+        {
+            return Some((literal.to_string(), rounded));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn warns_about_rounding_and_wrapping() {
+        let entry = |expression: &str, value: f64, base, word_size| Warning::for_entry(expression, &StackValue::Real(value), base, word_size);
+        assert_eq!(
+            entry("9007199254740993 + 1", 0.0, BaseMode::Decimal, WordSize::Unbounded),
+            Some(Warning::PrecisionLoss { literal: "9007199254740993".to_string(), rounded: 9007199254740992.0 })
+        );
+        assert_eq!(entry("9007199254740992", 0.0, BaseMode::Decimal, WordSize::Unbounded), None);
+        assert_eq!(entry("1.9007199254740993", 0.0, BaseMode::Decimal, WordSize::Unbounded), None);
+        assert_eq!(entry("", 300.0, BaseMode::Hexadecimal, WordSize::Bits8), Some(Warning::Overflow { value: 300.0, bits: 8 }));
+        assert_eq!(entry("", 255.0, BaseMode::Hexadecimal, WordSize::Bits8), None);
+        assert_eq!(entry("", -128.0, BaseMode::Binary, WordSize::Bits8), None);
+        assert_eq!(entry("", 300.0, BaseMode::Decimal, WordSize::Bits8), None);
+        assert_eq!(Warning::Overflow { value: 300.0, bits: 8 }.to_string(), "300 doesn't fit in 8 bits and is shown wrapped to them");
+    }
+}
@@ -0,0 +1,21 @@
+use crate::calculator::HistoryEntry;
+use crate::stack::Stack;
+use crate::undo::UndoLog;
+
+/// Workspace the calculator starts in. Its history is the one saved between sessions.
+pub const MAIN_WORKSPACE: &str = "main";
+
+/// A stack, history and undo log set aside while another workspace is active.
+#[derive(Default)]
+pub struct Workspace {
+    pub name: String,
+    pub stack: Stack,
+    pub history: Vec<HistoryEntry>,
+    pub undo: UndoLog,
+}
+
+impl Workspace {
+    pub fn new(name: &str) -> Self {
+        Self { name: name.to_string(), ..Self::default() }
+    }
+}
@@ -0,0 +1,49 @@
+//! The engine through its public API, as an embedding program would use it:
+//! no terminal, no key handling, just values in and values out.
+
+use tuic_core::{Calculator, CalculatorError, EntryOrigin, StackValue};
+
+#[test]
+fn pushes_and_reads_the_stack() {
+    let mut calculator = Calculator::new();
+    assert!(calculator.peek().is_none());
+    calculator.push(2.0);
+    calculator.push(3.0);
+    assert_eq!(calculator.stack().len(), 2);
+    assert_eq!(calculator.peek(), Some(&StackValue::Real(3.0)));
+
+    calculator.add();
+    assert_eq!(calculator.stack().len(), 1);
+    assert_eq!(calculator.peek(), Some(&StackValue::Real(5.0)));
+    assert_eq!(calculator.pop(), Some(StackValue::Real(5.0)));
+    assert!(calculator.stack().is_empty());
+
+    calculator.add();
+    assert!(matches!(calculator.error, Some(CalculatorError::StackUnderflow { needed: 2, available: 0, .. })));
+}
+
+#[test]
+fn evaluates_without_touching_the_stack() {
+    let mut calculator = Calculator::new();
+    calculator.push(1.0);
+    assert_eq!(calculator.evaluate("sqrt(16) + 2 * 3").unwrap(), 10.0);
+    assert!((calculator.evaluate("pi / 2").unwrap() - std::f64::consts::FRAC_PI_2).abs() < 1e-12);
+    assert!(matches!(calculator.evaluate("1 / 0"), Err(CalculatorError::DivisionByZero { .. })));
+    assert!(calculator.evaluate("2 +").is_err());
+    assert_eq!(calculator.stack().len(), 1);
+    assert!(calculator.history.is_empty());
+}
+
+#[test]
+fn pushes_expressions_with_history() {
+    let mut calculator = Calculator::new();
+    assert_eq!(calculator.push_expression("2^10", EntryOrigin::Script).unwrap(), StackValue::Real(1024.0));
+    calculator.push(24.0);
+    calculator.subtract();
+    assert_eq!(calculator.peek(), Some(&StackValue::Real(1000.0)));
+    let lines: Vec<&str> = calculator.history.iter().map(|entry| entry.expression.as_str()).collect();
+    assert_eq!(lines, ["2^10", "(2^10 - 24)"]);
+
+    assert!(calculator.push_expression("nosuch(1)", EntryOrigin::Script).is_err());
+    assert_eq!(calculator.stack().len(), 1);
+}
@@ -0,0 +1,119 @@
+//! Property tests for the tokenizer -> postfix -> evaluate pipeline: random
+//! expression trees are printed with and without redundant parentheses and
+//! must evaluate to the value computed directly from the tree.
+
+use proptest::prelude::*;
+use tuic_core::{Calculator, CalculatorError};
+
+#[derive(Debug, Clone)]
+enum Node {
+    Number(u32),
+    Binary(char, Box<Node>, Box<Node>),
+}
+
+fn node() -> impl Strategy<Value = Node> {
+    let leaf = (0u32..50).prop_map(Node::Number);
+    leaf.prop_recursive(4, 24, 2, |inner| {
+        (prop::sample::select(vec!['+', '-', '*', '/', '^']), inner.clone(), inner)
+            .prop_map(|(op, left, right)| Node::Binary(op, Box::new(left), Box::new(right)))
+    })
+}
+
+// None when the tree divides by zero
+fn value(node: &Node) -> Option<f64> {
+    match node {
+        Node::Number(n) => Some(*n as f64),
+        Node::Binary(op, left, right) => {
+            let (a, b) = (value(left)?, value(right)?);
+            match op {
+                '+' => Some(a + b),
+                '-' => Some(a - b),
+                '*' => Some(a * b),
+                '/' if b == 0.0 => None,
+                '/' => Some(a / b),
+                '^' => Some(a.powf(b)),
+                _ => unreachable!(),
+            }
+        }
+    }
+}
+
+fn precedence(op: char) -> u8 {
+    match op {
+        '+' | '-' => 1,
+        '*' | '/' => 2,
+        _ => 3,
+    }
+}
+
+fn parenthesized(node: &Node, separator: &str) -> String {
+    match node {
+        Node::Number(n) => n.to_string(),
+        Node::Binary(op, left, right) => {
+            format!("({}{sep}{}{sep}{})", parenthesized(left, separator), op, parenthesized(right, separator), sep = separator)
+        }
+    }
+}
+
+// Only the parentheses precedence and associativity require; '^' is right-associative
+fn minimal(node: &Node, separator: &str) -> String {
+    let Node::Binary(op, left, right) = node else {
+        return parenthesized(node, separator);
+    };
+    let side = |child: &Node, is_left: bool| {
+        let text = minimal(child, separator);
+        let needs_parens = match child {
+            Node::Binary(child_op, ..) => {
+                let (parent, child) = (precedence(*op), precedence(*child_op));
+                child < parent || (child == parent && is_left == (*op == '^'))
+            }
+            Node::Number(_) => false,
+        };
+        if needs_parens { format!("({})", text) } else { text }
+    };
+    format!("{}{sep}{}{sep}{}", side(left, true), op, side(right, false), sep = separator)
+}
+
+fn check(input: &str, expected: Option<f64>) -> Result<(), TestCaseError> {
+    let calculator = Calculator::new();
+    match (calculator.evaluate(input), expected) {
+        (Ok(actual), Some(expected)) if expected.is_finite() => {
+            let tolerance = 1e-9 * expected.abs().max(1.0);
+            prop_assert!((actual - expected).abs() <= tolerance, "{} = {}, expected {}", input, actual, expected);
+        }
+        (Err(CalculatorError::DivisionByZero { .. }), None) => {}
+        // Overflow and NaN results aren't comparable
+        (Ok(_), Some(_)) => {}
+        (actual, expected) => prop_assert!(false, "{} gave {:?}, expected {:?}", input, actual, expected),
+    }
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn fully_parenthesized(tree in node(), spaced in any::<bool>()) {
+        let separator = if spaced { " " } else { "" };
+        check(&parenthesized(&tree, separator), value(&tree))?;
+    }
+
+    #[test]
+    fn minimal_parentheses(tree in node(), spaced in any::<bool>()) {
+        let separator = if spaced { " " } else { "" };
+        check(&minimal(&tree, separator), value(&tree))?;
+    }
+
+    #[test]
+    fn functions_of_subexpressions(tree in node()) {
+        let inner = minimal(&tree, "");
+        check(&format!("abs({})", inner), value(&tree).map(f64::abs))?;
+    }
+}
+
+#[test]
+fn associativity() {
+    let calculator = Calculator::new();
+    assert_eq!(calculator.evaluate("10-4-3"), Ok(3.0));
+    assert_eq!(calculator.evaluate("64/4/2"), Ok(8.0));
+    assert_eq!(calculator.evaluate("2^3^2"), Ok(512.0));
+    assert_eq!(calculator.evaluate("2*3^2"), Ok(18.0));
+}